@@ -0,0 +1,46 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use clap::Args;
+
+use pbf_craft::analysis::AdminHierarchy;
+
+#[derive(Args)]
+pub struct AdminBoundariesCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path of the GeoJSON file; it's printed to stdout if omitted
+    #[clap(short, long, value_parser)]
+    output: Option<String>,
+}
+
+impl AdminBoundariesCommand {
+    pub fn run(self) {
+        let hierarchy = AdminHierarchy::from_path(&self.file)
+            .unwrap_or_else(|err| panic!("Failed to build the hierarchy: {}", err));
+        let geojson = hierarchy.to_geojson();
+        match &self.output {
+            Some(output) => {
+                let mut writer =
+                    BufWriter::new(File::create(output).expect("create output file failed"));
+                serde_json::to_writer(&mut writer, &geojson).expect("write geojson failed");
+                writer.flush().expect("write geojson failed");
+                eprintln!(
+                    "{} boundaries written to {}",
+                    hierarchy.boundaries.len(),
+                    output
+                );
+            }
+            None => println!("{}", geojson),
+        }
+        if !hierarchy.incomplete_relation_ids.is_empty() {
+            eprintln!(
+                "{} incomplete boundaries skipped: {:?}",
+                hierarchy.incomplete_relation_ids.len(),
+                hierarchy.incomplete_relation_ids
+            );
+        }
+    }
+}
@@ -1,42 +1,55 @@
-use clap::Args;
-use geo::{self, ConvexHull, Geometry, Polygon};
+use clap::{Args, ValueEnum};
+use geo::Geometry;
 use geojson::Value;
 
-use pbf_craft::readers::PbfReader;
+use pbf_craft::analysis::{coverage, CoverageShape};
+
+/// The shapes of `CoverageShape`, as named on the command line.
+#[derive(Clone, Copy, ValueEnum)]
+enum Shape {
+    /// the bounding box of all nodes
+    Bbox,
+    /// the convex hull of all nodes
+    Convex,
+    /// a concave hull following the outline of the nodes
+    Concave,
+}
 
 #[derive(Args)]
 pub struct BoundaryCommand {
     /// file path
     #[clap(short, long, value_parser)]
     file: String,
+
+    /// shape of the boundary
+    #[clap(long, value_enum, default_value_t = Shape::Concave)]
+    shape: Shape,
+
+    /// how closely the concave hull follows the nodes; the lower, the more concave
+    #[clap(long, value_parser, default_value_t = 2.0)]
+    concavity: f64,
+
+    /// size in degrees of the grid cells the nodes are snapped to for the concave hull
+    #[clap(long, value_parser, default_value_t = 0.01)]
+    resolution: f64,
 }
 
 impl BoundaryCommand {
     pub fn run(self) {
-        let mut reader =
-            PbfReader::from_path(&self.file).expect(&format!("No such file: {}", self.file));
-
-        let mut polygons: Vec<Polygon> = Vec::new();
-        while let Some(blob_data) = reader.read_next_blob() {
-            if blob_data.nodes.len() > 0 {
-                let points: Vec<geo::Point> = blob_data
-                    .nodes
-                    .into_iter()
-                    .map(|node| {
-                        let x: f64 = node.longitude as f64 / 1000000000f64;
-                        let y: f64 = node.latitude as f64 / 1000000000f64;
-                        geo::Point::new(x, y)
-                    })
-                    .collect();
-                let multi_points = geo::MultiPoint::new(points);
-                polygons.push(multi_points.convex_hull());
-            }
-        }
-        let multi_polygons = geo::MultiPolygon::new(polygons);
-        let boundary = multi_polygons.convex_hull();
+        let shape = match self.shape {
+            Shape::Bbox => CoverageShape::BoundingBox,
+            Shape::Convex => CoverageShape::ConvexHull,
+            Shape::Concave => CoverageShape::ConcaveHull {
+                concavity: self.concavity,
+                resolution: self.resolution,
+            },
+        };
+
+        let boundary = coverage(&self.file, shape)
+            .unwrap_or_else(|err| panic!("Failed to compute the boundary: {}", err));
         let geometry: Geometry<f64> = boundary.into();
         let geojson = Value::from(&geometry);
         dark_yellow_ln!("---------");
-        println!("{}", geojson.to_string());
+        println!("{}", geojson);
     }
 }
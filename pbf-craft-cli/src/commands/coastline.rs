@@ -0,0 +1,95 @@
+use std::fs;
+
+use clap::Args;
+use geo::{Coord, Geometry, Rect};
+use geojson::Value;
+
+use pbf_craft::analysis::Coastline;
+
+#[derive(Args)]
+pub struct CoastlineCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path of the land polygons as GeoJSON
+    #[clap(short, long, value_parser)]
+    output: Option<String>,
+
+    /// write the water polygons within this bounding box instead of the land polygons:
+    /// min_lon,min_lat,max_lon,max_lat
+    #[clap(long, value_parser, requires = "output")]
+    water_bbox: Option<String>,
+}
+
+impl CoastlineCommand {
+    pub fn run(self) {
+        let water_bbox = match self.water_bbox.as_deref().map(parse_bbox) {
+            Some(Ok(bbox)) => Some(bbox),
+            Some(Err(err)) => {
+                eprintln!("{}", err);
+                return;
+            }
+            None => None,
+        };
+
+        blue!("Assembling the coastline of ");
+        dark_yellow!("{}", self.file);
+        println!(" ...");
+        let coastline = Coastline::from_path(&self.file)
+            .unwrap_or_else(|err| panic!("Failed to assemble the coastline: {}", err));
+        println!(
+            "{} rings, {} open chains, {} incomplete ways",
+            coastline.rings.len(),
+            coastline.open_chains.len(),
+            coastline.incomplete_way_ids.len()
+        );
+        for gap in coastline.gaps() {
+            yellow!("Gap ");
+            print!("at node {} ({}, {})", gap.node_id, gap.coord.x, gap.coord.y);
+            match (gap.nearest_start_node_id, gap.distance) {
+                (Some(node_id), Some(distance)) => {
+                    println!(", nearest start at node {} {:.0} m away", node_id, distance)
+                }
+                _ => println!(),
+            }
+        }
+
+        if let Some(output) = &self.output {
+            let polygons = match water_bbox {
+                Some(bbox) => coastline.water_polygons(bbox),
+                None => coastline.land_polygons(),
+            };
+            let count = polygons.0.len();
+            let geometry = Geometry::MultiPolygon(polygons);
+            let geojson = geojson::Geometry::new(Value::from(&geometry));
+            fs::write(output, geojson.to_string()).expect("write geojson failed");
+            println!("{} polygons written to {}", count, output);
+        }
+    }
+}
+
+pub(super) fn parse_bbox(bbox: &str) -> anyhow::Result<Rect<f64>> {
+    let coordinates = bbox
+        .split(',')
+        .map(|number| {
+            number
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| anyhow!("Invalid coordinate: {}", number))
+        })
+        .collect::<anyhow::Result<Vec<f64>>>()?;
+    let [min_lon, min_lat, max_lon, max_lat] = coordinates[..] else {
+        bail!("A bounding box needs four coordinates: min_lon,min_lat,max_lon,max_lat");
+    };
+    Ok(Rect::new(
+        Coord {
+            x: min_lon,
+            y: min_lat,
+        },
+        Coord {
+            x: max_lon,
+            y: max_lat,
+        },
+    ))
+}
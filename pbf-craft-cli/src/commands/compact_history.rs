@@ -0,0 +1,35 @@
+use clap::Args;
+use pbf_craft::readers::IterableReader;
+use pbf_craft::writers::{HistoryCompactingSink, PbfWriter};
+
+use super::copy;
+
+#[derive(Args)]
+pub struct CompactHistoryCommand {
+    /// history file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path
+    #[clap(short, long, value_parser)]
+    output: String,
+}
+
+impl CompactHistoryCommand {
+    pub fn run(self) {
+        blue!("Compacting the history of ");
+        dark_yellow!("{}", self.file);
+        blue!(" to ");
+        dark_yellow!("{}", self.output);
+        println!(" ...");
+        let reader = IterableReader::from_path(&self.file).expect("read pbf failed");
+        let mut writer = PbfWriter::from_path(&self.output, true).expect("create pbf failed");
+        writer.set_historical_information(true);
+        let mut sink = HistoryCompactingSink::new(writer);
+        copy(reader, &mut sink).expect("compact history failed");
+        println!(
+            "{} versions differing only in metadata dropped",
+            sink.dropped()
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs::File;
+
+use clap::Args;
+use serde::Serialize;
+
+use pbf_craft::conflate::{Conflator, Feature, TagMatcher};
+use pbf_craft::models::{Element, ElementType};
+
+#[derive(Serialize)]
+struct ConflateRow {
+    status: &'static str,
+    a_type: Option<ElementType>,
+    a_id: Option<i64>,
+    b_type: Option<ElementType>,
+    b_id: Option<i64>,
+    distance: Option<f64>,
+    score: Option<f64>,
+}
+
+#[derive(Args)]
+pub struct ConflateCommand {
+    /// first pbf path
+    #[clap(short, long, value_parser)]
+    a: String,
+
+    /// second pbf path
+    #[clap(short, long, value_parser)]
+    b: String,
+
+    /// output csv path
+    #[clap(short, long, value_parser, default_value = "./conflate.csv")]
+    output: String,
+
+    /// the nodes and ways with any of these tag keys are matched
+    #[clap(
+        long,
+        value_parser,
+        value_delimiter = ',',
+        default_value = "amenity,shop,tourism"
+    )]
+    keys: Vec<String>,
+
+    /// only match features with the same value of this tag key. It can be repeated.
+    #[clap(long, value_parser)]
+    match_tag: Vec<String>,
+
+    /// maximum distance of a match in meters
+    #[clap(long, value_parser, default_value_t = 50.0)]
+    max_distance: f64,
+
+    /// minimum score of a match, from 0 to 1
+    #[clap(long, value_parser, default_value_t = 0.5)]
+    min_score: f64,
+}
+
+impl ConflateCommand {
+    pub fn run(self) {
+        let mut conflator = Conflator::with_default_scorers(self.max_distance);
+        conflator.set_min_score(self.min_score);
+        for key in &self.match_tag {
+            conflator.add_matcher(TagMatcher::new(key));
+        }
+        let keys = &self.keys;
+        let filter = |element: &Element| {
+            let tags = match element {
+                Element::Node(node) => &node.tags,
+                Element::Way(way) => &way.tags,
+                Element::Relation(_) => return false,
+            };
+            tags.iter().any(|tag| keys.contains(&tag.key))
+        };
+
+        blue!("Conflating ");
+        dark_yellow!("{}", self.a);
+        blue!(" with ");
+        dark_yellow!("{}", self.b);
+        println!(" ...");
+        let conflation = conflator
+            .conflate_paths(&self.a, &self.b, filter)
+            .unwrap_or_else(|err| panic!("Failed to conflate: {}", err));
+
+        let mut writer = csv::WriterBuilder::new()
+            .from_writer(File::create(&self.output).expect("create output file failed"));
+        let meta = |feature: &Feature| {
+            let (element_type, element_id) = feature.element.get_meta();
+            (Some(element_type), Some(element_id))
+        };
+        for feature_match in &conflation.matches {
+            let (a_type, a_id) = meta(&feature_match.a);
+            let (b_type, b_id) = meta(&feature_match.b);
+            writer
+                .serialize(ConflateRow {
+                    status: "matched",
+                    a_type,
+                    a_id,
+                    b_type,
+                    b_id,
+                    distance: Some(feature_match.distance),
+                    score: Some(feature_match.score),
+                })
+                .expect("write csv failed");
+        }
+        for feature in &conflation.unmatched_a {
+            let (a_type, a_id) = meta(feature);
+            writer
+                .serialize(ConflateRow {
+                    status: "unmatched_a",
+                    a_type,
+                    a_id,
+                    b_type: None,
+                    b_id: None,
+                    distance: None,
+                    score: None,
+                })
+                .expect("write csv failed");
+        }
+        for feature in &conflation.unmatched_b {
+            let (b_type, b_id) = meta(feature);
+            writer
+                .serialize(ConflateRow {
+                    status: "unmatched_b",
+                    a_type: None,
+                    a_id: None,
+                    b_type,
+                    b_id,
+                    distance: None,
+                    score: None,
+                })
+                .expect("write csv failed");
+        }
+        writer.flush().expect("write csv failed");
+        println!(
+            "{} matched, {} unmatched in {} and {} unmatched in {}",
+            conflation.matches.len(),
+            conflation.unmatched_a.len(),
+            self.a,
+            conflation.unmatched_b.len(),
+            self.b
+        );
+    }
+}
@@ -1,11 +1,12 @@
 use std::fs::File;
+use std::io::Write;
 
 use clap::Args;
 use csv;
 use serde::{Deserialize, Serialize};
 
 use pbf_craft::models::{Element, ElementType};
-use pbf_craft::readers::IterableReader;
+use pbf_craft::readers::{ElementSource, IterableReader, SortedSource};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DiffType {
@@ -41,208 +42,186 @@ impl DiffCommand {
         let mut diff_csv =
             csv::WriterBuilder::new().from_writer(File::create(&self.output).unwrap());
 
-        let mut source = IterableReader::from_path(&self.source)
-            .expect(&format!("No such file: {}", self.source))
-            .into_iter();
-        let mut target = IterableReader::from_path(&self.target)
-            .expect(&format!("No such file: {}", self.target))
-            .into_iter();
+        let source = IterableReader::from_path(&self.source)
+            .expect(&format!("No such file: {}", self.source));
+        let target = IterableReader::from_path(&self.target)
+            .expect(&format!("No such file: {}", self.target));
 
-        let mut source_element_cnt = source.next();
-        let mut target_element_cnt = target.next();
+        diff(
+            SortedSource::new(source),
+            SortedSource::new(target),
+            &mut diff_csv,
+        )
+        .expect("diff failed");
+        diff_csv.flush().unwrap();
+        println!("Diff file created: ./{}", &self.output);
+    }
+}
 
-        loop {
-            match (&source_element_cnt, &target_element_cnt) {
-                (Some(source_element), Some(target_element)) => {
-                    match (source_element, target_element) {
-                        (Element::Node(source_element), Element::Node(target_element)) => {
-                            if source_element.id == target_element.id {
-                                if source_element != target_element {
-                                    diff_csv
-                                        .serialize(ElementDiff {
-                                            element_type: ElementType::Node,
-                                            element_id: source_element.id,
-                                            diff_type: DiffType::Modify,
-                                        })
-                                        .unwrap();
-                                }
-                                source_element_cnt = source.next();
-                                target_element_cnt = target.next();
-                            } else if source_element.id < target_element.id {
-                                diff_csv
-                                    .serialize(ElementDiff {
-                                        element_type: ElementType::Node,
-                                        element_id: source_element.id,
-                                        diff_type: DiffType::Delete,
-                                    })
-                                    .unwrap();
-                                source_element_cnt = source.next();
-                            } else {
-                                diff_csv
-                                    .serialize(ElementDiff {
-                                        element_type: ElementType::Node,
-                                        element_id: target_element.id,
-                                        diff_type: DiffType::Add,
-                                    })
-                                    .unwrap();
-                                target_element_cnt = target.next();
-                            }
-                        }
-                        (Element::Node(source_element), Element::Way(_)) => {
-                            diff_csv
-                                .serialize(ElementDiff {
+/// Compares two sources sorted in the canonical order and writes the differences as CSV.
+fn diff<S: ElementSource, T: ElementSource, W: Write>(
+    mut source: S,
+    mut target: T,
+    diff_csv: &mut csv::Writer<W>,
+) -> anyhow::Result<()> {
+    let mut source_element_cnt = source.next_element()?;
+    let mut target_element_cnt = target.next_element()?;
+
+    loop {
+        match (&source_element_cnt, &target_element_cnt) {
+            (Some(source_element), Some(target_element)) => {
+                match (source_element, target_element) {
+                    (Element::Node(source_element), Element::Node(target_element)) => {
+                        if source_element.id == target_element.id {
+                            if source_element != target_element {
+                                diff_csv.serialize(ElementDiff {
                                     element_type: ElementType::Node,
                                     element_id: source_element.id,
-                                    diff_type: DiffType::Delete,
-                                })
-                                .unwrap();
-                            source_element_cnt = source.next();
-                        }
-                        (Element::Way(_), Element::Node(target_element)) => {
-                            diff_csv
-                                .serialize(ElementDiff {
-                                    element_type: ElementType::Node,
-                                    element_id: target_element.id,
-                                    diff_type: DiffType::Add,
-                                })
-                                .unwrap();
-                            target_element_cnt = target.next();
-                        }
-                        (Element::Way(source_element), Element::Way(target_element)) => {
-                            if source_element.id == target_element.id {
-                                if source_element != target_element {
-                                    diff_csv
-                                        .serialize(ElementDiff {
-                                            element_type: ElementType::Way,
-                                            element_id: source_element.id,
-                                            diff_type: DiffType::Modify,
-                                        })
-                                        .unwrap();
-                                }
-                                source_element_cnt = source.next();
-                                target_element_cnt = target.next();
-                            } else if source_element.id < target_element.id {
-                                diff_csv
-                                    .serialize(ElementDiff {
-                                        element_type: ElementType::Way,
-                                        element_id: source_element.id,
-                                        diff_type: DiffType::Delete,
-                                    })
-                                    .unwrap();
-                                source_element_cnt = source.next();
-                            } else {
-                                diff_csv
-                                    .serialize(ElementDiff {
-                                        element_type: ElementType::Way,
-                                        element_id: target_element.id,
-                                        diff_type: DiffType::Add,
-                                    })
-                                    .unwrap();
-                                target_element_cnt = target.next();
+                                    diff_type: DiffType::Modify,
+                                })?;
                             }
+                            source_element_cnt = source.next_element()?;
+                            target_element_cnt = target.next_element()?;
+                        } else if source_element.id < target_element.id {
+                            diff_csv.serialize(ElementDiff {
+                                element_type: ElementType::Node,
+                                element_id: source_element.id,
+                                diff_type: DiffType::Delete,
+                            })?;
+                            source_element_cnt = source.next_element()?;
+                        } else {
+                            diff_csv.serialize(ElementDiff {
+                                element_type: ElementType::Node,
+                                element_id: target_element.id,
+                                diff_type: DiffType::Add,
+                            })?;
+                            target_element_cnt = target.next_element()?;
                         }
-                        (Element::Way(source_way), Element::Relation(_)) => {
-                            diff_csv
-                                .serialize(ElementDiff {
-                                    element_type: ElementType::Way,
-                                    element_id: source_way.id,
-                                    diff_type: DiffType::Delete,
-                                })
-                                .unwrap();
-                            source_element_cnt = source.next();
-                        }
-                        (Element::Relation(_), Element::Way(target_way)) => {
-                            diff_csv
-                                .serialize(ElementDiff {
+                    }
+                    (Element::Node(source_element), Element::Way(_)) => {
+                        diff_csv.serialize(ElementDiff {
+                            element_type: ElementType::Node,
+                            element_id: source_element.id,
+                            diff_type: DiffType::Delete,
+                        })?;
+                        source_element_cnt = source.next_element()?;
+                    }
+                    (Element::Way(_), Element::Node(target_element)) => {
+                        diff_csv.serialize(ElementDiff {
+                            element_type: ElementType::Node,
+                            element_id: target_element.id,
+                            diff_type: DiffType::Add,
+                        })?;
+                        target_element_cnt = target.next_element()?;
+                    }
+                    (Element::Way(source_element), Element::Way(target_element)) => {
+                        if source_element.id == target_element.id {
+                            if source_element != target_element {
+                                diff_csv.serialize(ElementDiff {
                                     element_type: ElementType::Way,
-                                    element_id: target_way.id,
-                                    diff_type: DiffType::Add,
-                                })
-                                .unwrap();
-                            target_element_cnt = target.next();
-                        }
-                        (Element::Relation(source_element), Element::Relation(target_element)) => {
-                            if source_element.id == target_element.id {
-                                if source_element != target_element {
-                                    diff_csv
-                                        .serialize(ElementDiff {
-                                            element_type: ElementType::Relation,
-                                            element_id: source_element.id,
-                                            diff_type: DiffType::Modify,
-                                        })
-                                        .unwrap();
-                                }
-                                source_element_cnt = source.next();
-                                target_element_cnt = target.next();
-                            } else if source_element.id < target_element.id {
-                                diff_csv
-                                    .serialize(ElementDiff {
-                                        element_type: ElementType::Relation,
-                                        element_id: source_element.id,
-                                        diff_type: DiffType::Delete,
-                                    })
-                                    .unwrap();
-                                source_element_cnt = source.next();
-                            } else {
-                                diff_csv
-                                    .serialize(ElementDiff {
-                                        element_type: ElementType::Relation,
-                                        element_id: target_element.id,
-                                        diff_type: DiffType::Add,
-                                    })
-                                    .unwrap();
-                                target_element_cnt = target.next();
+                                    element_id: source_element.id,
+                                    diff_type: DiffType::Modify,
+                                })?;
                             }
-                        }
-                        (Element::Relation(_), Element::Node(target_node)) => {
-                            diff_csv
-                                .serialize(ElementDiff {
-                                    element_type: ElementType::Node,
-                                    element_id: target_node.id,
-                                    diff_type: DiffType::Add,
-                                })
-                                .unwrap();
-                            target_element_cnt = target.next();
-                        }
-                        (Element::Node(source_node), Element::Relation(_)) => {
-                            diff_csv
-                                .serialize(ElementDiff {
-                                    element_type: ElementType::Node,
-                                    element_id: source_node.id,
-                                    diff_type: DiffType::Delete,
-                                })
-                                .unwrap();
-                            source_element_cnt = source.next();
+                            source_element_cnt = source.next_element()?;
+                            target_element_cnt = target.next_element()?;
+                        } else if source_element.id < target_element.id {
+                            diff_csv.serialize(ElementDiff {
+                                element_type: ElementType::Way,
+                                element_id: source_element.id,
+                                diff_type: DiffType::Delete,
+                            })?;
+                            source_element_cnt = source.next_element()?;
+                        } else {
+                            diff_csv.serialize(ElementDiff {
+                                element_type: ElementType::Way,
+                                element_id: target_element.id,
+                                diff_type: DiffType::Add,
+                            })?;
+                            target_element_cnt = target.next_element()?;
                         }
                     }
-                }
-                (Some(source_element), None) => {
-                    let (element_type, element_id) = source_element.get_meta();
-                    diff_csv
-                        .serialize(ElementDiff {
-                            element_type,
-                            element_id,
+                    (Element::Way(source_way), Element::Relation(_)) => {
+                        diff_csv.serialize(ElementDiff {
+                            element_type: ElementType::Way,
+                            element_id: source_way.id,
                             diff_type: DiffType::Delete,
-                        })
-                        .unwrap();
-                    source_element_cnt = source.next();
-                }
-                (None, Some(target_element)) => {
-                    let (element_type, element_id) = target_element.get_meta();
-                    diff_csv
-                        .serialize(ElementDiff {
-                            element_type,
-                            element_id,
+                        })?;
+                        source_element_cnt = source.next_element()?;
+                    }
+                    (Element::Relation(_), Element::Way(target_way)) => {
+                        diff_csv.serialize(ElementDiff {
+                            element_type: ElementType::Way,
+                            element_id: target_way.id,
+                            diff_type: DiffType::Add,
+                        })?;
+                        target_element_cnt = target.next_element()?;
+                    }
+                    (Element::Relation(source_element), Element::Relation(target_element)) => {
+                        if source_element.id == target_element.id {
+                            if source_element != target_element {
+                                diff_csv.serialize(ElementDiff {
+                                    element_type: ElementType::Relation,
+                                    element_id: source_element.id,
+                                    diff_type: DiffType::Modify,
+                                })?;
+                            }
+                            source_element_cnt = source.next_element()?;
+                            target_element_cnt = target.next_element()?;
+                        } else if source_element.id < target_element.id {
+                            diff_csv.serialize(ElementDiff {
+                                element_type: ElementType::Relation,
+                                element_id: source_element.id,
+                                diff_type: DiffType::Delete,
+                            })?;
+                            source_element_cnt = source.next_element()?;
+                        } else {
+                            diff_csv.serialize(ElementDiff {
+                                element_type: ElementType::Relation,
+                                element_id: target_element.id,
+                                diff_type: DiffType::Add,
+                            })?;
+                            target_element_cnt = target.next_element()?;
+                        }
+                    }
+                    (Element::Relation(_), Element::Node(target_node)) => {
+                        diff_csv.serialize(ElementDiff {
+                            element_type: ElementType::Node,
+                            element_id: target_node.id,
                             diff_type: DiffType::Add,
-                        })
-                        .unwrap();
-                    target_element_cnt = target.next();
+                        })?;
+                        target_element_cnt = target.next_element()?;
+                    }
+                    (Element::Node(source_node), Element::Relation(_)) => {
+                        diff_csv.serialize(ElementDiff {
+                            element_type: ElementType::Node,
+                            element_id: source_node.id,
+                            diff_type: DiffType::Delete,
+                        })?;
+                        source_element_cnt = source.next_element()?;
+                    }
                 }
-                (None, None) => break,
             }
+            (Some(source_element), None) => {
+                let (element_type, element_id) = source_element.get_meta();
+                diff_csv.serialize(ElementDiff {
+                    element_type,
+                    element_id,
+                    diff_type: DiffType::Delete,
+                })?;
+                source_element_cnt = source.next_element()?;
+            }
+            (None, Some(target_element)) => {
+                let (element_type, element_id) = target_element.get_meta();
+                diff_csv.serialize(ElementDiff {
+                    element_type,
+                    element_id,
+                    diff_type: DiffType::Add,
+                })?;
+                target_element_cnt = target.next_element()?;
+            }
+            (None, None) => break,
         }
-
-        diff_csv.flush().unwrap();
-        println!("Diff file created: ./{}", &self.output);
     }
+    Ok(())
 }
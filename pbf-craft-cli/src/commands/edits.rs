@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use clap::Args;
+use pbf_craft::filters::{extract_edits, MetadataFilter};
+use pbf_craft::writers::PbfWriter;
+
+use super::filter::parse_time;
+
+#[derive(Args)]
+pub struct EditsCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path
+    #[clap(short, long, value_parser)]
+    output: String,
+
+    /// extract the elements last edited in this changeset. It can be repeated.
+    #[clap(long, value_parser)]
+    changeset: Vec<i64>,
+
+    /// extract the elements last edited by this user. It can be repeated.
+    #[clap(long, value_parser)]
+    user: Vec<String>,
+
+    /// only extract elements edited at or after this time, e.g. "2024-01-31" or "2024-01-31T12:00:00Z"
+    #[clap(long, value_parser = parse_time)]
+    since: Option<DateTime<Utc>>,
+
+    /// only extract elements edited before this time
+    #[clap(long, value_parser = parse_time)]
+    until: Option<DateTime<Utc>>,
+}
+
+impl EditsCommand {
+    pub fn run(self) {
+        let mut filter = MetadataFilter::new();
+        if !self.changeset.is_empty() {
+            filter.set_changeset_ids(self.changeset.iter().copied());
+        }
+        if !self.user.is_empty() {
+            filter.set_user_names(self.user.iter().cloned());
+        }
+        if let Some(since) = self.since {
+            filter.set_since(since);
+        }
+        if let Some(until) = self.until {
+            filter.set_until(until);
+        }
+        if self.changeset.is_empty() && self.user.is_empty() {
+            eprintln!("At least one of --changeset or --user is required");
+            return;
+        }
+
+        blue!("Extracting the edits of ");
+        dark_yellow!("{}", self.file);
+        blue!(" to ");
+        dark_yellow!("{}", self.output);
+        println!(" ...");
+        let mut writer = PbfWriter::from_path(&self.output, true).expect("create pbf failed");
+        let extract = extract_edits(&self.file, &filter, &mut writer)
+            .unwrap_or_else(|err| panic!("Failed to extract the edits: {}", err));
+        println!(
+            "{} edited and {} referenced elements written",
+            extract.edited, extract.referenced
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use clap::Args;
 use pbf_craft::writers::PbfWriter;
 
+use super::copy;
 use crate::db::DatabaseReader;
 
 #[derive(Args)]
@@ -48,9 +49,6 @@ impl ExportCommand {
         let db_reader =
             DatabaseReader::new(self.host, self.port, self.dbname, self.user, self.password);
         let mut writer = PbfWriter::from_path(&self.output, true).unwrap();
-        db_reader
-            .read(|el_container| writer.write(el_container).expect("write error"))
-            .expect("read failed");
-        writer.finish().expect("finished error");
+        copy(db_reader.into_source(), &mut writer).expect("export failed");
     }
 }
@@ -0,0 +1,149 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::Args;
+use pbf_craft::filters::{KeepReferenced, MetadataFilter};
+use pbf_craft::models::{Element, ElementType, Tag};
+use pbf_craft::readers::IterableReader;
+use pbf_craft::writers::{ElementSink, PbfWriter};
+
+use super::AnonymizeArgs;
+
+#[derive(Args)]
+pub struct FilterCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path
+    #[clap(short, long, value_parser)]
+    output: String,
+
+    /// tag to match, either "key" or "key=value". Elements matching any of the tags are kept.
+    #[clap(long, value_parser)]
+    tag: Vec<String>,
+
+    /// only match elements edited at or after this time, e.g. "2024-01-31" or "2024-01-31T12:00:00Z"
+    #[clap(long, value_parser = parse_time)]
+    since: Option<DateTime<Utc>>,
+
+    /// only match elements edited before this time
+    #[clap(long, value_parser = parse_time)]
+    until: Option<DateTime<Utc>>,
+
+    /// only match elements last edited in this changeset. It can be repeated.
+    #[clap(long, value_parser)]
+    changeset: Vec<i64>,
+
+    /// only match elements last edited by this user. It can be repeated.
+    #[clap(long, value_parser)]
+    user: Vec<String>,
+
+    /// only match elements of this type: node, way, relation
+    #[clap(long, value_parser)]
+    eltype: Option<String>,
+
+    /// also keep the nodes and ways referenced by the matched elements, so that the output is self-contained
+    #[clap(long, action)]
+    keep_referenced: bool,
+
+    #[clap(flatten)]
+    anonymize: AnonymizeArgs,
+}
+
+impl FilterCommand {
+    pub fn run(self) {
+        blue!("Filtering ");
+        dark_yellow!("{}", self.file);
+        blue!(" to ");
+        dark_yellow!("{}", self.output);
+        println!(" ...");
+
+        let mut metadata_filter = MetadataFilter::new();
+        if let Some(since) = self.since {
+            metadata_filter.set_since(since);
+        }
+        if let Some(until) = self.until {
+            metadata_filter.set_until(until);
+        }
+        if !self.changeset.is_empty() {
+            metadata_filter.set_changeset_ids(self.changeset.iter().copied());
+        }
+        if !self.user.is_empty() {
+            metadata_filter.set_user_names(self.user.iter().cloned());
+        }
+        if self.tag.is_empty() && metadata_filter.is_empty() {
+            eprintln!("At least one of --tag, --since, --until, --changeset or --user is required");
+            return;
+        }
+
+        let element_type = match self.eltype.as_deref().map(ElementType::from_str) {
+            Some(Err(err)) => {
+                eprintln!("{}", err);
+                return;
+            }
+            Some(Ok(element_type)) => Some(element_type),
+            None => None,
+        };
+        let filters: Vec<(String, Option<String>)> = self
+            .tag
+            .iter()
+            .map(|tag| match tag.split_once('=') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (tag.clone(), None),
+            })
+            .collect();
+        let matches = |element: &Element| {
+            let (current_type, _) = element.get_meta();
+            if element_type.as_ref().is_some_and(|t| *t != current_type) {
+                return false;
+            }
+            let tags = match element {
+                Element::Node(node) => &node.tags,
+                Element::Way(way) => &way.tags,
+                Element::Relation(relation) => &relation.tags,
+            };
+            (filters.is_empty() || filters.iter().any(|filter| does_tag_match(tags, filter)))
+                && metadata_filter.matches(element)
+        };
+
+        let selection = if self.keep_referenced {
+            Some(KeepReferenced::from_path(&self.file, matches).expect("read pbf failed"))
+        } else {
+            None
+        };
+
+        let reader = IterableReader::from_path(&self.file).expect("read pbf failed");
+        let mut writer = self
+            .anonymize
+            .wrap(PbfWriter::from_path(&self.output, true).unwrap());
+        let mut count = 0;
+        for element in reader {
+            let is_kept = match &selection {
+                Some(selection) => selection.contains(&element),
+                None => matches(&element),
+            };
+            if is_kept {
+                writer.write(element).expect("write pbf failed");
+                count += 1;
+            }
+        }
+        writer.finish().expect("write pbf failed");
+        println!("{} elements written", count);
+    }
+}
+
+fn does_tag_match(tags: &[Tag], filter: &(String, Option<String>)) -> bool {
+    let (key, value) = filter;
+    tags.iter()
+        .any(|tag| tag.key == *key && value.as_ref().is_none_or(|v| tag.value == *v))
+}
+
+pub(super) fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| format!("invalid time: {}", value))
+}
@@ -0,0 +1,40 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use clap::Args;
+use pbf_craft::readers::NdjsonReader;
+use pbf_craft::writers::{PbfWriter, SortingWriter};
+
+use super::copy;
+
+#[derive(Args)]
+pub struct ImportJsonCommand {
+    /// input file with one JSON element per line, or "-" to read from stdin
+    #[clap(short, long, value_parser)]
+    input: String,
+
+    /// output path
+    #[clap(short, long, value_parser)]
+    output: String,
+}
+
+impl ImportJsonCommand {
+    pub fn run(self) {
+        blue!("Importing ");
+        dark_yellow!("{}", self.input);
+        blue!(" to ");
+        dark_yellow!("{}", self.output);
+        println!(" ...");
+
+        let input: Box<dyn BufRead> = if self.input == "-" {
+            Box::new(io::stdin().lock())
+        } else {
+            let file =
+                File::open(&self.input).unwrap_or_else(|_| panic!("No such file: {}", self.input));
+            Box::new(BufReader::new(file))
+        };
+        let pbf_writer = PbfWriter::from_path(&self.output, true).unwrap();
+        let mut writer = SortingWriter::new(pbf_writer);
+        copy(NdjsonReader::new(input), &mut writer).expect("import failed");
+    }
+}
@@ -1,10 +1,37 @@
+mod admin_boundaries;
 mod boundary;
+mod coastline;
+mod compact_history;
+mod conflate;
 mod diff;
+mod edits;
 mod export;
+mod filter;
+mod import_json;
+mod revert;
+mod sample;
 mod search;
+mod set_header;
+mod snapshot;
+mod stats;
+mod tag_regions;
+mod tiles;
+mod transit;
 mod with_deps;
 
-use clap::Subcommand;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+use clap::{Args, Subcommand};
+use colored_json::prelude::*;
+use pbf_craft::models::Element;
+use pbf_craft::readers::ElementSource;
+use pbf_craft::utils::SimplifyAlgorithm;
+use pbf_craft::writers::{
+    Anonymization, AnonymizingSink, ElementSink, NdjsonSchema, NdjsonWriter, OplWriter, XmlWriter,
+};
+use serde_json::json;
 
 #[derive(Subcommand)]
 pub enum Commands {
@@ -14,10 +41,40 @@ pub enum Commands {
     Search(search::SearchCommand),
     /// export database to a PBF file
     Export(export::ExportCommand),
+    /// write the elements matching tags to a new PBF file
+    Filter(filter::FilterCommand),
+    /// extract the elements last edited in changesets or by users, with their dependencies
+    Edits(edits::EditsCommand),
+    /// write an osc reverting a changeset of a history PBF file, reporting the conflicts
+    Revert(revert::RevertCommand),
+    /// import line-delimited JSON elements into a PBF file
+    ImportJson(import_json::ImportJsonCommand),
     /// an experimental feature
     Diff(diff::DiffCommand),
     /// get the boundary of a PBF file
     Boundary(boundary::BoundaryCommand),
+    /// write the administrative boundaries with their hierarchy as GeoJSON
+    AdminBoundaries(admin_boundaries::AdminBoundariesCommand),
+    /// assemble the coastline, report its gaps and write land or water polygons
+    Coastline(coastline::CoastlineCommand),
+    /// drop the versions of a history file which differ from the previous one only in metadata
+    CompactHistory(compact_history::CompactHistoryCommand),
+    /// match the POIs of two PBF files by distance and tags and write the pairs as CSV
+    Conflate(conflate::ConflateCommand),
+    /// write a down-sampled copy of a PBF file, keeping the elements referenced by the sample
+    Sample(sample::SampleCommand),
+    /// rewrite the header of a PBF file, e.g. its bounding box, copying the data blobs verbatim
+    SetHeader(set_header::SetHeaderCommand),
+    /// write the snapshot of a history file at an instant
+    Snapshot(snapshot::SnapshotCommand),
+    /// print element counts, the bounding box and the most used tags of a PBF file
+    Stats(stats::StatsCommand),
+    /// tag the elements with the region of a GeoJSON file they fall in
+    TagRegions(tag_regions::TagRegionsCommand),
+    /// write Mapbox Vector Tiles of roads, buildings and POIs to a directory
+    Tiles(tiles::TilesCommand),
+    /// export the stops and paths of public transport routes as JSON or CSV
+    Transit(transit::TransitCommand),
 }
 
 impl Commands {
@@ -32,10 +89,171 @@ impl Commands {
             Commands::Export(command) => {
                 command.run();
             }
+            Commands::Filter(command) => command.run(),
+            Commands::Edits(command) => command.run(),
+            Commands::Revert(command) => command.run(),
+            Commands::ImportJson(command) => command.run(),
             Commands::Diff(command) => {
                 command.run();
             }
             Commands::Boundary(command) => command.run(),
+            Commands::AdminBoundaries(command) => command.run(),
+            Commands::Coastline(command) => command.run(),
+            Commands::CompactHistory(command) => command.run(),
+            Commands::Conflate(command) => command.run(),
+            Commands::Sample(command) => command.run(),
+            Commands::SetHeader(command) => command.run(),
+            Commands::Snapshot(command) => command.run(),
+            Commands::Stats(command) => command.run(),
+            Commands::TagRegions(command) => command.run(),
+            Commands::Tiles(command) => command.run(),
+            Commands::Transit(command) => command.run(),
+        }
+    }
+}
+
+/// Copies all elements of a source into a sink and finishes the sink.
+fn copy<S: ElementSource, W: ElementSink>(mut source: S, sink: &mut W) -> anyhow::Result<()> {
+    while let Some(element) = source.next_element()? {
+        sink.write(element)?;
+    }
+    sink.finish()
+}
+
+/// The formats found elements can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    GeoJson,
+    Opl,
+    Xml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "geojson" => Ok(OutputFormat::GeoJson),
+            "opl" => Ok(OutputFormat::Opl),
+            "xml" => Ok(OutputFormat::Xml),
+            _ => Err(anyhow!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+/// Options for how found elements are written.
+#[derive(Args, Debug)]
+pub struct OutputArgs {
+    /// output format: json, geojson (a feature collection), opl or xml
+    #[clap(long, value_parser, default_value = "json")]
+    format: OutputFormat,
+
+    /// write the elements to this file instead of printing them
+    #[clap(short, long, value_parser)]
+    output: Option<String>,
+
+    /// simplify the geojson way geometries within this tolerance in meters
+    #[clap(long, value_parser)]
+    simplify: Option<f64>,
+}
+
+impl OutputArgs {
+    /// Returns whether the elements are printed in a format meant for piping, in which case
+    /// nothing else may be printed to stdout.
+    pub fn is_piped(&self) -> bool {
+        self.output.is_none() && self.format != OutputFormat::Json
+    }
+
+    /// Writes the elements to the output file, or prints them; JSON is printed colored if
+    /// stdout is a terminal.
+    pub fn write(&self, elements: Vec<Element>) -> anyhow::Result<()> {
+        match &self.output {
+            Some(path) => {
+                let count = elements.len();
+                let writer = BufWriter::new(File::create(path)?);
+                write_elements(writer, self.format, self.simplify, elements)?;
+                println!("{} elements written to {}", count, path);
+                Ok(())
+            }
+            None if self.format == OutputFormat::Json => {
+                let json = serde_json::to_string_pretty(&elements)?;
+                println!("{}", json.to_colored_json_auto()?);
+                Ok(())
+            }
+            None => write_elements(
+                std::io::stdout().lock(),
+                self.format,
+                self.simplify,
+                elements,
+            ),
+        }
+    }
+}
+
+fn write_elements<W: Write>(
+    mut writer: W,
+    format: OutputFormat,
+    simplify: Option<f64>,
+    elements: Vec<Element>,
+) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &elements)?;
+            writeln!(writer)?;
+            writer.flush()?;
+            Ok(())
+        }
+        OutputFormat::GeoJson => {
+            // The features are encoded by the GeoJSONSeq writer, then collected
+            let mut lines = Vec::new();
+            let mut geojson_writer = NdjsonWriter::new(&mut lines, NdjsonSchema::GeoJson);
+            geojson_writer.set_simplification(simplify, SimplifyAlgorithm::DouglasPeucker);
+            write_all(geojson_writer, elements)?;
+            let features = lines
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(serde_json::from_slice)
+                .collect::<Result<Vec<serde_json::Value>, _>>()?;
+            let collection = json!({ "type": "FeatureCollection", "features": features });
+            serde_json::to_writer_pretty(&mut writer, &collection)?;
+            writeln!(writer)?;
+            writer.flush()?;
+            Ok(())
+        }
+        OutputFormat::Opl => write_all(OplWriter::new(writer), elements),
+        OutputFormat::Xml => write_all(XmlWriter::new(writer), elements),
+    }
+}
+
+/// Writes all elements into a sink and finishes the sink.
+fn write_all<W: ElementSink>(mut sink: W, elements: Vec<Element>) -> anyhow::Result<()> {
+    for element in elements {
+        sink.write(element)?;
+    }
+    sink.finish()
+}
+
+/// Options for stripping personal data from the metadata of the written elements.
+#[derive(Args)]
+pub struct AnonymizeArgs {
+    /// remove or pseudonymize the user names, user ids and changeset ids: keep, remove or pseudonymize
+    #[clap(long, value_parser, default_value = "keep")]
+    anonymize: Anonymization,
+
+    /// round the timestamps down to a multiple of this number of seconds, e.g. 86400 to keep only the day
+    #[clap(long, value_parser = clap::value_parser!(i64).range(1..))]
+    round_timestamps: Option<i64>,
+}
+
+impl AnonymizeArgs {
+    /// Wraps a sink so that the elements written to it are anonymized as requested.
+    pub fn wrap<W: ElementSink>(&self, sink: W) -> AnonymizingSink<W> {
+        let mut sink = AnonymizingSink::new(sink, self.anonymize);
+        if let Some(seconds) = self.round_timestamps {
+            sink.set_timestamp_rounding(seconds);
         }
+        sink
     }
 }
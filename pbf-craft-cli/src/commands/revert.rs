@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use clap::Args;
+use pbf_craft::models::ChangesetRevert;
+
+#[derive(Args)]
+pub struct RevertCommand {
+    /// history pbf path, holding all versions of the elements
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// the changeset to revert
+    #[clap(long, value_parser)]
+    changeset: i64,
+
+    /// output osc path
+    #[clap(short, long, value_parser, default_value = "./revert.osc")]
+    output: String,
+}
+
+impl RevertCommand {
+    pub fn run(self) {
+        blue!("Reverting changeset ");
+        dark_yellow!("{}", self.changeset);
+        blue!(" of ");
+        dark_yellow!("{}", self.file);
+        println!(" ...");
+        let revert = ChangesetRevert::from_path(&self.file, self.changeset)
+            .unwrap_or_else(|err| panic!("Failed to revert the changeset: {}", err));
+        let change = revert.to_change();
+        let mut writer =
+            BufWriter::new(File::create(&self.output).expect("create output file failed"));
+        change.write_osc(&mut writer).expect("write osc failed");
+        println!(
+            "{} modified and {} deleted elements written to {}",
+            change.modify.len(),
+            change.delete.len(),
+            self.output
+        );
+        if !revert.conflicts().is_empty() {
+            println!(
+                "{} conflicting elements were left out:",
+                revert.conflicts().len()
+            );
+            for conflict in revert.conflicts() {
+                println!("  {}", conflict);
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::cell::Cell;
+
+use clap::Args;
+use pbf_craft::filters::KeepReferenced;
+use pbf_craft::models::Element;
+use pbf_craft::readers::IterableReader;
+use pbf_craft::writers::{ElementSink, PbfWriter};
+
+use super::AnonymizeArgs;
+
+#[derive(Args)]
+pub struct SampleCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path
+    #[clap(short, long, value_parser)]
+    output: String,
+
+    /// keep every Nth node, way and relation, counting each type separately
+    #[clap(long, value_parser, conflicts_with = "fraction")]
+    every: Option<u64>,
+
+    /// keep a random fraction of the nodes, ways and relations, e.g. 0.01
+    #[clap(long, value_parser)]
+    fraction: Option<f64>,
+
+    /// seed of the random sampling; the same seed selects the same elements
+    #[clap(long, value_parser, default_value_t = 0)]
+    seed: u64,
+
+    #[clap(flatten)]
+    anonymize: AnonymizeArgs,
+}
+
+impl SampleCommand {
+    pub fn run(self) {
+        match (self.every, self.fraction) {
+            (Some(0), _) => {
+                eprintln!("--every must be at least 1");
+                return;
+            }
+            (_, Some(fraction)) if !(0.0..=1.0).contains(&fraction) => {
+                eprintln!("--fraction must be between 0 and 1");
+                return;
+            }
+            (None, None) => {
+                eprintln!("One of --every or --fraction is required");
+                return;
+            }
+            _ => {}
+        }
+
+        blue!("Sampling ");
+        dark_yellow!("{}", self.file);
+        blue!(" to ");
+        dark_yellow!("{}", self.output);
+        println!(" ...");
+
+        // The counters of nodes, ways and relations seen so far
+        let counters = [Cell::new(0u64), Cell::new(0u64), Cell::new(0u64)];
+        let is_sampled = |element: &Element| {
+            let (element_type, id) = element.get_meta();
+            match (self.every, self.fraction) {
+                (Some(every), _) => {
+                    let counter = &counters[element_type as usize];
+                    let index = counter.get();
+                    counter.set(index + 1);
+                    index.is_multiple_of(every)
+                }
+                (None, Some(fraction)) => {
+                    let hash = splitmix64(self.seed ^ splitmix64(id as u64 ^ element_type as u64));
+                    (hash as f64 / u64::MAX as f64) < fraction
+                }
+                (None, None) => unreachable!(),
+            }
+        };
+        // The referenced elements are kept too, so that the sample is self-contained
+        let selection = KeepReferenced::from_path(&self.file, is_sampled).expect("read pbf failed");
+
+        let reader = IterableReader::from_path(&self.file).expect("read pbf failed");
+        let mut writer = self
+            .anonymize
+            .wrap(PbfWriter::from_path(&self.output, true).unwrap());
+        let mut count = 0;
+        for element in reader {
+            if selection.contains(&element) {
+                writer.write(element).expect("write pbf failed");
+                count += 1;
+            }
+        }
+        writer.finish().expect("write pbf failed");
+        println!("{} elements written", count);
+    }
+}
+
+/// Scrambles a value, so that the sampling by hash is uniform and stable across versions.
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
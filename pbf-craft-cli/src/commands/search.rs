@@ -1,10 +1,11 @@
-use std::str::FromStr;
-
 use clap::Args;
-use colored_json::prelude::*;
 
-use pbf_craft::models::{Element, ElementType, Tag};
+use pbf_craft::models::{Bound, Element, ElementType, ElementTypeSet, Tag};
+use pbf_craft::query::{ElementQuery, Query, QueryEngine, Statement, TagFilter};
 use pbf_craft::readers::{IndexedReader, PbfReader};
+use pbf_craft::writers::{NdjsonSchema, NdjsonWriter};
+
+use super::OutputArgs;
 
 #[derive(Args, Debug)]
 pub struct SearchCommand {
@@ -24,6 +25,14 @@ pub struct SearchCommand {
     #[clap(long, value_parser)]
     tagvalue: Option<String>,
 
+    /// tag value regular expression of a tag key, e.g. name~^Calle
+    #[clap(long, value_parser)]
+    tag_regex: Option<String>,
+
+    /// bounding box: min_lon,min_lat,max_lon,max_lat. Ways match when any of their nodes is inside, relations when any of their node or way members is.
+    #[clap(long, value_parser)]
+    bbox: Option<String>,
+
     #[clap(long, value_parser)]
     pair: Option<Vec<i64>>,
 
@@ -34,23 +43,37 @@ pub struct SearchCommand {
     /// The default value is true. If true, it will match exactly the only element. If false, all associated elements will be matched.
     #[clap(short, long, value_parser)]
     exact: Option<bool>,
+
+    /// Print the found elements as line-delimited JSON, one element per line, without any other output.
+    #[clap(long, action)]
+    ndjson: bool,
+
+    /// the maximum number of elements to output
+    #[clap(long, value_parser)]
+    limit: Option<usize>,
+
+    #[clap(flatten)]
+    output: OutputArgs,
 }
 
 impl SearchCommand {
     pub fn run(self) {
         let result = if let (Some(eltype), Some(elid)) = (&self.eltype, &self.elid) {
-            blue!("Searching ");
-            dark_yellow!("{} ", &self.file);
-            blue!("for ");
-            dark_yellow!("{}#{} ", eltype, elid);
-            println!("...");
-
-            let element_type_result = ElementType::from_str(eltype);
-            if let Err(err) = element_type_result {
-                eprintln!("{}", err);
-                return;
+            let element_type: ElementType = match eltype.parse() {
+                Ok(element_type) => element_type,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return;
+                }
+            };
+
+            if !self.ndjson && !self.output.is_piped() {
+                blue!("Searching ");
+                dark_yellow!("{} ", &self.file);
+                blue!("for ");
+                dark_yellow!("{}/{} ", element_type, elid);
+                println!("...");
             }
-            let element_type = element_type_result.unwrap();
 
             if self.exact.is_none() || self.exact.unwrap() == true {
                 let mut indexed_reader =
@@ -67,68 +90,112 @@ impl SearchCommand {
             } else {
                 let reader = PbfReader::from_path(&self.file).unwrap();
                 reader
-                    .par_find(None, |element| match (element, &element_type) {
-                        (Element::Node(node), ElementType::Node) => node.id == *elid,
-                        (Element::Way(way), ElementType::Node) => {
-                            for way_node in &way.way_nodes {
-                                if way_node.id == *elid {
-                                    return true;
+                    .par_find(ElementTypeSet::ALL, |element| {
+                        match (element, &element_type) {
+                            (Element::Node(node), ElementType::Node) => node.id == *elid,
+                            (Element::Way(way), ElementType::Node) => {
+                                for way_node in &way.way_nodes {
+                                    if way_node.id == *elid {
+                                        return true;
+                                    }
                                 }
+                                return false;
                             }
-                            return false;
-                        }
-                        (Element::Way(way), ElementType::Way) => way.id == *elid,
-                        (Element::Relation(relation), ElementType::Relation) => {
-                            relation.id == *elid
-                        }
-                        (Element::Relation(relation), _) => {
-                            for member in &relation.members {
-                                if member.member_id == *elid && member.member_type.eq(&element_type)
-                                {
-                                    return true;
+                            (Element::Way(way), ElementType::Way) => way.id == *elid,
+                            (Element::Relation(relation), ElementType::Relation) => {
+                                relation.id == *elid
+                            }
+                            (Element::Relation(relation), _) => {
+                                for member in &relation.members {
+                                    if member.member_id == *elid
+                                        && member.member_type.eq(&element_type)
+                                    {
+                                        return true;
+                                    }
                                 }
+                                return false;
                             }
-                            return false;
+                            _ => false,
                         }
-                        _ => false,
                     })
                     .expect("read pbf failed")
             }
-        } else if self.tagkey.is_some() || self.tagvalue.is_some() {
-            blue!("Searching ");
-            dark_yellow!("{} ", &self.file);
-            blue!("for ");
-            dark_yellow!(
-                "elements of tag key {:?} and tag value {:?} ",
-                &self.tagkey,
-                &self.tagvalue
-            );
-            println!("...");
-            let reader = PbfReader::from_path(&self.file).unwrap();
-            reader
-                .par_find(None, |element| match element {
+        } else if self.tagkey.is_some()
+            || self.tagvalue.is_some()
+            || self.tag_regex.is_some()
+            || self.bbox.is_some()
+        {
+            let query = match self.element_query() {
+                Ok(query) => query,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return;
+                }
+            };
+            if !self.ndjson && !self.output.is_piped() {
+                blue!("Searching ");
+                dark_yellow!("{} ", &self.file);
+                blue!("for ");
+                dark_yellow!(
+                    "elements of tag key {:?} and tag value {:?} ",
+                    &self.tagkey,
+                    &self.tagvalue
+                );
+                if let Some(tag_regex) = &self.tag_regex {
+                    blue!("matching ");
+                    dark_yellow!("{} ", tag_regex);
+                }
+                if let Some(bbox) = &self.bbox {
+                    blue!("within ");
+                    dark_yellow!("{} ", bbox);
+                }
+                println!("...");
+            }
+            let matches_substrings = |element: &Element| {
+                if self.tagkey.is_none() && self.tagvalue.is_none() {
+                    return true;
+                }
+                match element {
                     Element::Node(node) => does_tag_match(&node.tags, &self.tagkey, &self.tagvalue),
                     Element::Way(way) => does_tag_match(&way.tags, &self.tagkey, &self.tagvalue),
                     Element::Relation(relation) => {
                         does_tag_match(&relation.tags, &self.tagkey, &self.tagvalue)
                     }
-                })
-                .expect("read pbf failed")
+                }
+            };
+            if query.tags.is_empty() && query.bbox.is_none() {
+                let reader = PbfReader::from_path(&self.file).unwrap();
+                reader
+                    .par_find(ElementTypeSet::ALL, matches_substrings)
+                    .expect("read pbf failed")
+            } else {
+                // The query engine resolves the bounding box of ways and relations through
+                // their nodes, and returns the elements sorted by type and id
+                let mut engine = QueryEngine::from_path(&self.file).expect("read pbf failed");
+                let query = Query {
+                    statements: vec![Statement::Query(query)],
+                };
+                let mut elements = engine.execute(&query).expect("read pbf failed");
+                elements.retain(matches_substrings);
+                elements
+            }
         } else if self.pair.is_some() {
-            let node_ids = self.pair.unwrap();
+            let node_ids = self.pair.clone().unwrap();
             if node_ids.len() < 2 {
                 panic!("At least two nodes are required");
             }
             let first = node_ids[0];
             let second = node_ids[1];
-            blue!("Searching ");
-            dark_yellow!("{} ", &self.file);
-            blue!("for ");
-            dark_yellow!("ways containing the node pair of {} and {} ", first, second);
-            println!("...");
+            if !self.ndjson && !self.output.is_piped() {
+                blue!("Searching ");
+                dark_yellow!("{} ", &self.file);
+                blue!("for ");
+                dark_yellow!("ways containing the node pair of {} and {} ", first, second);
+                println!("...");
+            }
             let reader = PbfReader::from_path(&self.file).unwrap();
             reader
-                .par_find(Some(&ElementType::Way), |el| {
+                .par_find(ElementTypeSet::WAYS, |el| {
                     if let Element::Way(way) = el {
                         return way.way_nodes.iter().any(|ref_node| ref_node.id == first)
                             && way.way_nodes.iter().any(|ref_node| ref_node.id == second);
@@ -141,14 +208,66 @@ impl SearchCommand {
             Vec::with_capacity(0)
         };
 
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&result)
-                .unwrap()
-                .to_colored_json_auto()
-                .unwrap()
-        );
-        println!("{} elemets found", result.len());
+        let mut result = result;
+        if let Some(limit) = self.limit {
+            result.truncate(limit);
+        }
+
+        if self.ndjson {
+            let mut writer = NdjsonWriter::new(std::io::stdout().lock(), NdjsonSchema::Raw);
+            for element in result {
+                writer.write(element).expect("write ndjson failed");
+            }
+            writer.finish().expect("write ndjson failed");
+            return;
+        }
+
+        let count = result.len();
+        self.output.write(result).expect("write elements failed");
+        if !self.output.is_piped() {
+            println!("{} elemets found", count);
+        }
+    }
+
+    /// Builds the query of the `--tag-regex` and `--bbox` options.
+    fn element_query(&self) -> anyhow::Result<ElementQuery> {
+        let mut query = ElementQuery {
+            types: vec![ElementType::Node, ElementType::Way, ElementType::Relation],
+            tags: Vec::new(),
+            bbox: None,
+            ids: Vec::new(),
+        };
+        if let Some(tag_regex) = &self.tag_regex {
+            let (key, pattern) = tag_regex
+                .split_once('~')
+                .ok_or_else(|| anyhow!("Invalid tag regex {}, expected key~regex", tag_regex))?;
+            let regex = pattern
+                .parse()
+                .map_err(|err| anyhow!("Invalid regular expression {:?}: {}", pattern, err))?;
+            query.tags.push(TagFilter::Matches(key.to_string(), regex));
+        }
+        if let Some(bbox) = &self.bbox {
+            let coordinates = bbox
+                .split(',')
+                .map(|number| {
+                    let degrees: f64 = number
+                        .trim()
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid coordinate: {}", number))?;
+                    Ok((degrees * 1e9).round() as i64)
+                })
+                .collect::<anyhow::Result<Vec<i64>>>()?;
+            let [left, bottom, right, top] = coordinates[..] else {
+                bail!("A bounding box needs four coordinates: min_lon,min_lat,max_lon,max_lat");
+            };
+            query.bbox = Some(Bound {
+                left,
+                right,
+                top,
+                bottom,
+            });
+        }
+        Ok(query)
     }
 }
 
@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use clap::Args;
+use pbf_craft::proto::osmformat::HeaderBBox;
+use pbf_craft::writers::rewrite_header;
+
+use super::coastline::parse_bbox;
+use super::filter::parse_time;
+
+#[derive(Args)]
+pub struct SetHeaderCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path
+    #[clap(short, long, value_parser)]
+    output: String,
+
+    /// the bounding box: min_lon,min_lat,max_lon,max_lat
+    #[clap(long, value_parser, conflicts_with = "clear_bbox")]
+    bbox: Option<String>,
+
+    /// remove the bounding box
+    #[clap(long, action)]
+    clear_bbox: bool,
+
+    /// the replication timestamp, e.g. "2024-01-31T12:00:00Z"
+    #[clap(long, value_parser = parse_time)]
+    replication_timestamp: Option<DateTime<Utc>>,
+
+    /// the replication sequence number
+    #[clap(long, value_parser)]
+    replication_sequence_number: Option<i64>,
+
+    /// the replication base URL
+    #[clap(long, value_parser)]
+    replication_base_url: Option<String>,
+
+    /// the writing program
+    #[clap(long, value_parser)]
+    writing_program: Option<String>,
+}
+
+impl SetHeaderCommand {
+    pub fn run(self) {
+        let bbox = match self.bbox.as_deref().map(parse_bbox) {
+            Some(Ok(bbox)) => Some(bbox),
+            Some(Err(err)) => {
+                eprintln!("{}", err);
+                return;
+            }
+            None => None,
+        };
+
+        blue!("Rewriting the header of ");
+        dark_yellow!("{}", self.file);
+        blue!(" to ");
+        dark_yellow!("{}", self.output);
+        println!(" ...");
+        rewrite_header(&self.file, &self.output, |header| {
+            if let Some(bbox) = bbox {
+                let nanodegrees = |degrees: f64| (degrees * 1e9).round() as i64;
+                let mut header_bbox = HeaderBBox::new();
+                header_bbox.set_left(nanodegrees(bbox.min().x));
+                header_bbox.set_right(nanodegrees(bbox.max().x));
+                header_bbox.set_bottom(nanodegrees(bbox.min().y));
+                header_bbox.set_top(nanodegrees(bbox.max().y));
+                header.set_bbox(header_bbox);
+            }
+            if self.clear_bbox {
+                header.clear_bbox();
+            }
+            if let Some(timestamp) = self.replication_timestamp {
+                header.set_osmosis_replication_timestamp(timestamp.timestamp());
+            }
+            if let Some(sequence_number) = self.replication_sequence_number {
+                header.set_osmosis_replication_sequence_number(sequence_number);
+            }
+            if let Some(base_url) = self.replication_base_url {
+                header.set_osmosis_replication_base_url(base_url);
+            }
+            if let Some(writing_program) = self.writing_program {
+                header.set_writingprogram(writing_program);
+            }
+        })
+        .unwrap_or_else(|err| panic!("Failed to rewrite the header: {}", err));
+    }
+}
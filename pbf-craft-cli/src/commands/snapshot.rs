@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use clap::Args;
+use pbf_craft::history::snapshot_at_path;
+use pbf_craft::writers::PbfWriter;
+
+use super::filter::parse_time;
+
+#[derive(Args)]
+pub struct SnapshotCommand {
+    /// history file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path
+    #[clap(short, long, value_parser)]
+    output: String,
+
+    /// the instant of the snapshot, e.g. "2020-01-31" or "2020-01-31T12:00:00Z"
+    #[clap(long, value_parser = parse_time)]
+    at: DateTime<Utc>,
+}
+
+impl SnapshotCommand {
+    pub fn run(self) {
+        blue!("Writing the snapshot of ");
+        dark_yellow!("{}", self.file);
+        blue!(" at ");
+        dark_yellow!("{}", self.at);
+        blue!(" to ");
+        dark_yellow!("{}", self.output);
+        println!(" ...");
+        let mut writer = PbfWriter::from_path(&self.output, true).expect("create pbf failed");
+        let written = snapshot_at_path(&self.file, self.at, &mut writer)
+            .unwrap_or_else(|err| panic!("Failed to write the snapshot: {}", err));
+        println!("{} elements written", written);
+    }
+}
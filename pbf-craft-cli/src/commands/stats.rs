@@ -0,0 +1,136 @@
+use clap::Args;
+use colored_json::prelude::*;
+use serde_json::json;
+
+use pbf_craft::analysis::{stats, FileStats};
+use pbf_craft::readers::{IndexSummary, PbfIndex};
+
+#[derive(Args)]
+pub struct StatsCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    input: String,
+
+    /// number of the most used tag keys to print
+    #[clap(long, value_parser, default_value_t = 20)]
+    top_keys: usize,
+
+    /// number of the most used values to print for each key
+    #[clap(long, value_parser, default_value_t = 5)]
+    top_values: usize,
+
+    /// output format: text or json
+    #[clap(long, value_parser, default_value = "text")]
+    format: String,
+
+    /// also print the element counts of every blob, taken from the index of the file, which is
+    /// built if needed
+    #[clap(long, action)]
+    blobs: bool,
+}
+
+impl StatsCommand {
+    pub fn run(self) {
+        if self.format != "text" && self.format != "json" {
+            eprintln!("Unsupported format: {}", self.format);
+            return;
+        }
+        let stats =
+            stats(&self.input).unwrap_or_else(|err| panic!("Failed to compute the stats: {}", err));
+        let summary = self.blobs.then(|| {
+            PbfIndex::new(&self.input)
+                .unwrap_or_else(|err| panic!("Failed to index the file: {}", err))
+                .summary()
+        });
+        if self.format == "json" {
+            let json = self.to_json(&stats, summary.as_ref()).to_string();
+            println!("{}", json.to_colored_json_auto().unwrap());
+        } else {
+            self.print_text(&stats, summary.as_ref());
+        }
+    }
+
+    fn to_json(&self, stats: &FileStats, summary: Option<&IndexSummary>) -> serde_json::Value {
+        let keys: Vec<serde_json::Value> = stats
+            .top_keys(self.top_keys)
+            .into_iter()
+            .map(|(key, count)| {
+                let values: Vec<serde_json::Value> = stats
+                    .top_values(key, self.top_values)
+                    .into_iter()
+                    .map(|(value, count)| json!({ "value": value, "count": count }))
+                    .collect();
+                json!({ "key": key, "count": count, "values": values })
+            })
+            .collect();
+        let blobs = summary.map(|summary| {
+            summary
+                .blobs
+                .iter()
+                .map(|blob| {
+                    json!({
+                        "offset": blob.offset,
+                        "nodes": blob.nodes,
+                        "ways": blob.ways,
+                        "relations": blob.relations,
+                    })
+                })
+                .collect::<Vec<serde_json::Value>>()
+        });
+        json!({
+            "nodes": stats.nodes,
+            "ways": stats.ways,
+            "relations": stats.relations,
+            "bbox": stats.bbox.as_ref().map(|bbox| json!({
+                "left": degrees(bbox.left),
+                "bottom": degrees(bbox.bottom),
+                "right": degrees(bbox.right),
+                "top": degrees(bbox.top),
+            })),
+            "keys": keys,
+            "blobs": blobs,
+        })
+    }
+
+    fn print_text(&self, stats: &FileStats, summary: Option<&IndexSummary>) {
+        blue!("Nodes: ");
+        println!("{}", stats.nodes);
+        blue!("Ways: ");
+        println!("{}", stats.ways);
+        blue!("Relations: ");
+        println!("{}", stats.relations);
+        blue!("Bbox: ");
+        match &stats.bbox {
+            Some(bbox) => println!(
+                "{} {} {} {}",
+                degrees(bbox.left),
+                degrees(bbox.bottom),
+                degrees(bbox.right),
+                degrees(bbox.top)
+            ),
+            None => println!("none"),
+        }
+        if let Some(summary) = summary {
+            blue!("Blobs: ");
+            println!("{}", summary.blobs.len());
+            for blob in &summary.blobs {
+                println!(
+                    "    at {}: {} nodes, {} ways, {} relations",
+                    blob.offset, blob.nodes, blob.ways, blob.relations
+                );
+            }
+        }
+        dark_yellow_ln!("---------");
+        for (key, count) in stats.top_keys(self.top_keys) {
+            green!("{} ", key);
+            println!("{}", count);
+            for (value, count) in stats.top_values(key, self.top_values) {
+                println!("    {} {}", value, count);
+            }
+        }
+    }
+}
+
+fn degrees(nanodegrees: i64) -> f64 {
+    nanodegrees as f64 / 1_000_000_000.0
+}
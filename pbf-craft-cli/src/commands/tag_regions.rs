@@ -0,0 +1,48 @@
+use clap::Args;
+
+use pbf_craft::readers::IterableReader;
+use pbf_craft::spatial::{RegionIndex, RegionTaggingSink};
+use pbf_craft::writers::{ElementSink, PbfWriter};
+
+#[derive(Args)]
+pub struct TagRegionsCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// output path
+    #[clap(short, long, value_parser)]
+    output: String,
+
+    /// GeoJSON file of the region polygons
+    #[clap(short, long, value_parser)]
+    regions: String,
+
+    /// property of the GeoJSON features holding the region names
+    #[clap(short, long, value_parser)]
+    property: String,
+
+    /// key of the tag the region names are written to
+    #[clap(long, value_parser, default_value = "nb:region")]
+    key: String,
+}
+
+impl TagRegionsCommand {
+    pub fn run(self) {
+        let regions = RegionIndex::from_geojson_path(&self.regions, &self.property)
+            .unwrap_or_else(|err| panic!("Failed to read the regions: {}", err));
+        blue!("Tagging ");
+        dark_yellow!("{}", self.file);
+        blue!(" with {} regions to ", regions.len());
+        dark_yellow!("{}", self.output);
+        println!(" ...");
+
+        let reader = IterableReader::from_path(&self.file).expect("read pbf failed");
+        let writer = PbfWriter::from_path(&self.output, true).expect("create pbf failed");
+        let mut sink = RegionTaggingSink::new(writer, regions, &self.key);
+        for element in reader {
+            sink.write(element).expect("write pbf failed");
+        }
+        sink.finish().expect("write pbf failed");
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::Path;
+
+use clap::Args;
+
+use pbf_craft::tiles::{TileGenerator, TileLayer};
+
+#[derive(Args)]
+pub struct TilesCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// directory the tiles are written to as {z}/{x}/{y}.mvt
+    #[clap(short, long, value_parser)]
+    output: String,
+
+    /// lowest zoom level to generate
+    #[clap(long, value_parser, default_value_t = 12)]
+    min_zoom: u8,
+
+    /// highest zoom level to generate
+    #[clap(long, value_parser, default_value_t = 14)]
+    max_zoom: u8,
+
+    /// comma separated layers to generate: roads, buildings and pois
+    #[clap(long, value_parser, default_value = "roads,buildings,pois")]
+    layers: String,
+}
+
+impl TilesCommand {
+    pub fn run(self) {
+        let mut layers = Vec::new();
+        for name in self.layers.split(',').map(str::trim) {
+            match name {
+                "roads" => layers.push(TileLayer::roads()),
+                "buildings" => layers.push(TileLayer::buildings()),
+                "pois" => layers.push(TileLayer::pois()),
+                _ => {
+                    eprintln!("Unknown layer: {}", name);
+                    return;
+                }
+            }
+        }
+        let mut generator = TileGenerator::new(layers);
+        generator.set_zoom_range(self.min_zoom, self.max_zoom);
+
+        blue!("Generating the tiles of ");
+        dark_yellow!("{}", self.file);
+        println!(" ...");
+        let tiles = generator
+            .generate_from_path(&self.file)
+            .unwrap_or_else(|err| panic!("Failed to generate the tiles: {}", err));
+        for (tile_id, data) in &tiles {
+            let dir = Path::new(&self.output)
+                .join(tile_id.zoom.to_string())
+                .join(tile_id.x.to_string());
+            fs::create_dir_all(&dir).expect("create tile directory failed");
+            fs::write(dir.join(format!("{}.mvt", tile_id.y)), data).expect("write tile failed");
+        }
+        println!("{} tiles written to {}", tiles.len(), self.output);
+    }
+}
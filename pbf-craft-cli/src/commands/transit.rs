@@ -0,0 +1,105 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use clap::Args;
+use pbf_craft::transit::{TransitExtractor, TransitRoute};
+
+#[derive(Args)]
+pub struct TransitCommand {
+    /// file path
+    #[clap(short, long, value_parser)]
+    file: String,
+
+    /// route types to export, separated by commas
+    #[clap(
+        long,
+        value_parser,
+        value_delimiter = ',',
+        default_value = "bus,tram,train"
+    )]
+    routes: Vec<String>,
+
+    /// output format: json, or csv with one row per stop
+    #[clap(long, value_parser, default_value = "json")]
+    format: String,
+
+    /// output path; the routes are printed to stdout if omitted
+    #[clap(short, long, value_parser)]
+    output: Option<String>,
+}
+
+impl TransitCommand {
+    pub fn run(self) {
+        if self.format != "json" && self.format != "csv" {
+            eprintln!("Unsupported format: {}", self.format);
+            return;
+        }
+        let mut extractor = TransitExtractor::new();
+        extractor.set_route_types(self.routes.iter().cloned());
+        let routes = extractor
+            .extract_from_path(&self.file)
+            .unwrap_or_else(|err| panic!("Failed to extract the routes: {}", err));
+
+        let writer: Box<dyn Write> = match &self.output {
+            Some(output) => Box::new(BufWriter::new(
+                File::create(output).expect("create output file failed"),
+            )),
+            None => Box::new(std::io::stdout().lock()),
+        };
+        let result = if self.format == "json" {
+            write_json(writer, &routes)
+        } else {
+            write_csv(writer, &routes)
+        };
+        result.expect("write routes failed");
+        if let Some(output) = &self.output {
+            eprintln!("{} routes written to {}", routes.len(), output);
+        }
+    }
+}
+
+fn write_json<W: Write>(mut writer: W, routes: &[TransitRoute]) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut writer, routes)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the stops of the routes, in the order they are served.
+fn write_csv<W: Write>(writer: W, routes: &[TransitRoute]) -> anyhow::Result<()> {
+    let mut csv_writer = csv::WriterBuilder::new().from_writer(writer);
+    csv_writer.write_record([
+        "route_id",
+        "route",
+        "ref",
+        "route_name",
+        "sequence",
+        "stop_type",
+        "stop_id",
+        "role",
+        "stop_name",
+        "latitude",
+        "longitude",
+    ])?;
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+    let coordinate = |value: Option<f64>| value.map(|v| format!("{:.7}", v)).unwrap_or_default();
+    for route in routes {
+        for stop in &route.stops {
+            csv_writer.write_record([
+                route.relation_id.to_string(),
+                route.route.clone(),
+                optional(&route.route_ref),
+                optional(&route.name),
+                stop.sequence.to_string(),
+                stop.element_type.to_string(),
+                stop.id.to_string(),
+                stop.role.clone(),
+                optional(&stop.name),
+                coordinate(stop.latitude),
+                coordinate(stop.longitude),
+            ])?;
+        }
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
@@ -1,21 +1,20 @@
-use std::str::FromStr;
-
 use clap::Args;
-use colored_json::prelude::*;
-use serde_json;
 
-use pbf_craft::models::{Element, ElementType};
+use pbf_craft::models::{Element, ElementId, ElementType};
 use pbf_craft::readers::IndexedReader;
 
+use super::OutputArgs;
+
 #[derive(Args, Debug)]
 pub struct GetCommand {
-    /// element type: node, way, relation
+    /// element type of the ids without one: node, way, relation
     #[clap(long, value_parser)]
-    eltype: String,
+    eltype: Option<String>,
 
-    /// element id
-    #[clap(long, value_parser)]
-    elid: i64,
+    /// element id or reference, e.g. way/123, or a comma-separated list of them whose shared
+    /// dependencies are fetched once
+    #[clap(long, value_parser, value_delimiter = ',', required = true)]
+    elid: Vec<String>,
 
     /// file path
     #[clap(short, long, value_parser)]
@@ -24,6 +23,9 @@ pub struct GetCommand {
     /// cache size
     #[clap(short, long, value_parser, default_value_t = 1000)]
     cache_size: usize,
+
+    #[clap(flatten)]
+    output: OutputArgs,
 }
 
 impl GetCommand {
@@ -31,30 +33,51 @@ impl GetCommand {
         let mut indexed_reader = IndexedReader::from_path_with_cache(&self.file, self.cache_size)
             .expect("Indexed reader loading failed");
 
-        let element_type_result = ElementType::from_str(self.eltype.as_str());
-        if let Err(err) = element_type_result {
-            eprintln!("{}", err);
-            return;
+        let element_ids: Vec<ElementId> = match self
+            .elid
+            .iter()
+            .map(|elid| self.parse_element_id(elid))
+            .collect()
+        {
+            Ok(element_ids) => element_ids,
+            Err(err) => {
+                eprintln!("{}", err);
+                return;
+            }
+        };
+
+        if !self.output.is_piped() {
+            blue!("Searching ");
+            dark_yellow!("{} ", &self.file);
+            let references: Vec<String> = element_ids.iter().map(ElementId::to_string).collect();
+            blue!("for ");
+            dark_yellow!("{} ", references.join(","));
+            blue!("with dependencies");
+            println!("...");
+        }
+
+        let result: Vec<Element> = match element_ids.as_slice() {
+            [element_id] => indexed_reader.get_with_deps(*element_id),
+            element_ids => indexed_reader.get_many_with_deps(element_ids),
+        }
+        .unwrap();
+
+        self.output.write(result).expect("write elements failed");
+    }
+
+    /// Parses a reference like `way/123`, or an ID of the type given by `--eltype`.
+    fn parse_element_id(&self, elid: &str) -> anyhow::Result<ElementId> {
+        if elid.contains('/') {
+            return elid.parse();
         }
-        let element_type = element_type_result.unwrap();
-
-        blue!("Searching ");
-        dark_yellow!("{} ", &self.file);
-        blue!("for ");
-        dark_yellow!("{}#{} ", self.eltype, self.elid);
-        blue!("with dependencies");
-        println!("...");
-
-        let result: Vec<Element> = indexed_reader
-            .get_with_deps(&element_type, self.elid)
-            .unwrap();
-
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&result)
-                .unwrap()
-                .to_colored_json_auto()
-                .unwrap()
-        );
+        let Some(eltype) = &self.eltype else {
+            bail!("The element type of {} is missing, set --eltype", elid);
+        };
+        let element_type: ElementType = eltype.parse()?;
+        let id: i64 = elid
+            .parse()
+            .map_err(|_| anyhow!("Illegal element id: {}", elid))?;
+        ElementId::try_new(element_type, id)
+            .ok_or_else(|| anyhow!("Element ID {} is out of range", id))
     }
 }
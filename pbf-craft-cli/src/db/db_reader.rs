@@ -1,7 +1,10 @@
+use std::ops::ControlFlow;
+
 use crate::db::paging_cursor::PagingCursor;
+use crate::db::DatabaseSource;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use pbf_craft::models::{
-    Element, ElementType, Node, OsmUser, Relation, RelationMember, Tag, Way, WayNode,
+    intern, Element, ElementType, Node, OsmUser, Relation, RelationMember, Tag, Way, WayNode,
 };
 use postgres::config::Config;
 use postgres::NoTls;
@@ -44,23 +47,32 @@ impl DatabaseReader {
         Self { config }
     }
 
+    /// Reads the elements of the database, until the callback returns `ControlFlow::Break`.
     pub fn read<F>(&self, mut callback: F) -> anyhow::Result<()>
     where
-        F: FnMut(Element),
+        F: FnMut(Element) -> ControlFlow<()>,
     {
         blue_ln!("Exporting nodes ...");
-        self.read_nodes(&mut callback)?;
+        if self.read_nodes(&mut callback)?.is_break() {
+            return Ok(());
+        }
         blue_ln!("Exporting ways ...");
-        self.read_ways(&mut callback)?;
+        if self.read_ways(&mut callback)?.is_break() {
+            return Ok(());
+        }
         blue_ln!("Exporting relations ...");
-        self.read_relations(&mut callback)?;
+        // Nothing follows the relations, whether the callback stopped or not
+        self.read_relations(&mut callback).map(|_| ())
+    }
 
-        Ok(())
+    /// Turns the reader into an `ElementSource` which yields elements one by one.
+    pub fn into_source(self) -> DatabaseSource {
+        DatabaseSource::new(self)
     }
 
-    fn read_nodes<F>(&self, callback: &mut F) -> anyhow::Result<()>
+    fn read_nodes<F>(&self, callback: &mut F) -> anyhow::Result<ControlFlow<()>>
     where
-        F: FnMut(Element),
+        F: FnMut(Element) -> ControlFlow<()>,
     {
         let mut el_client = self.config.connect(NoTls)?;
         let node_cursor = PagingCursor::new(
@@ -100,7 +112,7 @@ impl DatabaseReader {
             let user_name: String = node_row.get(8);
             node.user = Some(OsmUser {
                 id: user_id as i32,
-                name: user_name,
+                name: intern(&user_name),
             });
 
             if node.id == current_tag_id && current_tag.is_some() {
@@ -124,15 +136,17 @@ impl DatabaseReader {
                 }
             }
             let el = Element::Node(node);
-            callback(el)
+            if callback(el).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
         }
 
-        Ok(())
+        Ok(ControlFlow::Continue(()))
     }
 
-    fn read_ways<F>(&self, callback: &mut F) -> anyhow::Result<()>
+    fn read_ways<F>(&self, callback: &mut F) -> anyhow::Result<ControlFlow<()>>
     where
-        F: FnMut(Element),
+        F: FnMut(Element) -> ControlFlow<()>,
     {
         let mut el_client = self.config.connect(NoTls)?;
         let el_cursor = PagingCursor::new(
@@ -176,7 +190,7 @@ impl DatabaseReader {
             let user_name: String = el_row.get(6);
             way.user = Some(OsmUser {
                 id: user_id as i32,
-                name: user_name,
+                name: intern(&user_name),
             });
 
             if current_tag_id == way.id && current_tag.is_some() {
@@ -226,15 +240,17 @@ impl DatabaseReader {
             }
 
             let el = Element::Way(way);
-            callback(el)
+            if callback(el).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
         }
 
-        Ok(())
+        Ok(ControlFlow::Continue(()))
     }
 
-    fn read_relations<F>(&self, callback: &mut F) -> anyhow::Result<()>
+    fn read_relations<F>(&self, callback: &mut F) -> anyhow::Result<ControlFlow<()>>
     where
-        F: FnMut(Element),
+        F: FnMut(Element) -> ControlFlow<()>,
     {
         let mut el_client = self.config.connect(NoTls)?;
         let el_cursor = PagingCursor::new(
@@ -278,7 +294,7 @@ impl DatabaseReader {
             let user_name: String = el_row.get(6);
             relation.user = Some(OsmUser {
                 id: user_id as i32,
-                name: user_name,
+                name: intern(&user_name),
             });
 
             if relation.id == current_tag_id && current_tag.is_some() {
@@ -320,7 +336,7 @@ impl DatabaseReader {
                 let member = RelationMember {
                     member_id,
                     member_type,
-                    role: member_role,
+                    role: intern(&member_role),
                 };
                 if current_mem_id == relation.id {
                     relation.members.push(member);
@@ -330,9 +346,11 @@ impl DatabaseReader {
             }
 
             let el = Element::Relation(relation);
-            callback(el)
+            if callback(el).is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
         }
 
-        Ok(())
+        Ok(ControlFlow::Continue(()))
     }
 }
@@ -0,0 +1,67 @@
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+
+use pbf_craft::models::Element;
+use pbf_craft::readers::ElementSource;
+
+use super::DatabaseReader;
+
+const CHANNEL_CAPACITY: usize = 32000;
+
+/// An `ElementSource` reading elements from the database.
+///
+/// The database is read on a background thread, which hands elements over through a bounded
+/// channel so the callback-based `DatabaseReader` can be consumed element by element. The
+/// thread stops reading when the source is dropped.
+pub struct DatabaseSource {
+    receiver: Option<Receiver<Element>>,
+    handle: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl DatabaseSource {
+    pub fn new(db_reader: DatabaseReader) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let handle = thread::spawn(move || {
+            db_reader.read(|element| match sender.send(element) {
+                Ok(()) => ControlFlow::Continue(()),
+                // The receiver is gone when the consumer stops early, nothing left to do then.
+                Err(_) => ControlFlow::Break(()),
+            })
+        });
+        Self {
+            receiver: Some(receiver),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl ElementSource for DatabaseSource {
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        let Some(receiver) = &self.receiver else {
+            return Ok(None);
+        };
+        match receiver.recv() {
+            Ok(element) => Ok(Some(element)),
+            Err(_) => {
+                if let Some(handle) = self.handle.take() {
+                    handle
+                        .join()
+                        .map_err(|_| anyhow!("database reader thread panicked"))??;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Drop for DatabaseSource {
+    fn drop(&mut self) {
+        // Disconnects the channel first, so that the thread stops at its next element
+        self.receiver.take();
+        if let Some(handle) = self.handle.take() {
+            // The outcome of an abandoned read doesn't matter anymore
+            let _ = handle.join();
+        }
+    }
+}
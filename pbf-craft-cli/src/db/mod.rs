@@ -1,4 +1,6 @@
 mod db_reader;
+mod db_source;
 mod paging_cursor;
 
 pub use db_reader::DatabaseReader;
+pub use db_source::DatabaseSource;
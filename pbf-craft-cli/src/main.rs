@@ -28,8 +28,9 @@ fn main() -> anyhow::Result<()> {
     cli.command.run();
 
     let end = Instant::now();
-    green!("Finished ");
-    println!(" in {:?}", end.duration_since(start));
+    // Printed to stderr, so that elements printed to stdout can be piped
+    e_green!("Finished ");
+    eprintln!(" in {:?}", end.duration_since(start));
 
     Ok(())
 }
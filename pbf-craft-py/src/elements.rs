@@ -0,0 +1,178 @@
+use chrono::DateTime;
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+
+use pbf_craft::models::{
+    intern, BasicElement, Element, ElementBase, ElementId, ElementType, Node, OsmUser, Relation,
+    RelationMember, Tag, Way, WayNode,
+};
+
+pub(crate) fn parse_element_type(element_type: &str) -> PyResult<ElementType> {
+    element_type
+        .parse()
+        .map_err(|_| PyValueError::new_err(format!("Unknown element type: {}", element_type)))
+}
+
+pub(crate) fn to_element_id(element_type: &ElementType, element_id: i64) -> PyResult<ElementId> {
+    ElementId::try_new(element_type.clone(), element_id)
+        .ok_or_else(|| PyValueError::new_err(format!("Element ID {} is out of range", element_id)))
+}
+
+fn element_type_name(element_type: &ElementType) -> &'static str {
+    match element_type {
+        ElementType::Node => "node",
+        ElementType::Way => "way",
+        ElementType::Relation => "relation",
+    }
+}
+
+fn base_to_dict<'py, E: BasicElement>(
+    py: Python<'py>,
+    element_type: &ElementType,
+    element: &E,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("type", element_type_name(element_type))?;
+    dict.set_item("id", element.get_id())?;
+    dict.set_item("version", element.get_version())?;
+    dict.set_item(
+        "timestamp",
+        element
+            .get_timestamp()
+            .map(|timestamp| timestamp.timestamp()),
+    )?;
+    dict.set_item("changeset", element.get_changeset_id())?;
+    dict.set_item("uid", element.get_user().map(|user| user.id))?;
+    dict.set_item("user", element.get_user().map(|user| &*user.name))?;
+    dict.set_item("visible", element.is_visible())?;
+    let tags = PyDict::new_bound(py);
+    for tag in element.get_tags() {
+        tags.set_item(&tag.key, &tag.value)?;
+    }
+    dict.set_item("tags", tags)?;
+    Ok(dict)
+}
+
+/// Converts an element to a dict.
+///
+/// Every element has `type` (`"node"`, `"way"` or `"relation"`), `id`, `version`, `timestamp`
+/// in seconds since the epoch, `changeset`, `uid`, `user`, `visible` and `tags`. Nodes add
+/// `lat` and `lon` in degrees, ways add the IDs of their `nodes`, and relations add `members`
+/// as `(type, ref, role)` tuples.
+pub(crate) fn to_dict<'py>(py: Python<'py>, element: &Element) -> PyResult<Bound<'py, PyDict>> {
+    match element {
+        Element::Node(node) => {
+            let dict = base_to_dict(py, &ElementType::Node, node)?;
+            dict.set_item("lat", node.latitude as f64 / 1e9)?;
+            dict.set_item("lon", node.longitude as f64 / 1e9)?;
+            Ok(dict)
+        }
+        Element::Way(way) => {
+            let dict = base_to_dict(py, &ElementType::Way, way)?;
+            let node_ids = way.way_nodes.iter().map(|way_node| way_node.id);
+            dict.set_item("nodes", PyList::new_bound(py, node_ids))?;
+            Ok(dict)
+        }
+        Element::Relation(relation) => {
+            let dict = base_to_dict(py, &ElementType::Relation, relation)?;
+            let members = PyList::empty_bound(py);
+            for member in &relation.members {
+                members.append(PyTuple::new_bound(
+                    py,
+                    [
+                        element_type_name(&member.member_type).into_py(py),
+                        member.member_id.into_py(py),
+                        (&*member.role).into_py(py),
+                    ],
+                ))?;
+            }
+            dict.set_item("members", members)?;
+            Ok(dict)
+        }
+    }
+}
+
+pub(crate) fn to_list<'py>(py: Python<'py>, elements: &[Element]) -> PyResult<Bound<'py, PyList>> {
+    let list = PyList::empty_bound(py);
+    for element in elements {
+        list.append(to_dict(py, element)?)?;
+    }
+    Ok(list)
+}
+
+/// Returns the value of a key, treating `None` like a missing key.
+fn get<'py, T: FromPyObject<'py>>(dict: &Bound<'py, PyDict>, key: &str) -> PyResult<Option<T>> {
+    match dict.get_item(key)? {
+        Some(value) if !value.is_none() => Ok(Some(value.extract()?)),
+        _ => Ok(None),
+    }
+}
+
+/// Converts a dict in the layout of `to_dict` to an element. Only `type` and `id` are
+/// required; the other fields default to empty values and `visible` to `True`.
+pub(crate) fn from_dict(dict: &Bound<'_, PyDict>) -> PyResult<Element> {
+    let element_type: String = get(dict, "type")?.ok_or_else(|| PyKeyError::new_err("type"))?;
+    let element_type = parse_element_type(&element_type)?;
+    let mut base = ElementBase {
+        id: get(dict, "id")?.ok_or_else(|| PyKeyError::new_err("id"))?,
+        version: get(dict, "version")?.unwrap_or_default(),
+        changeset_id: get(dict, "changeset")?.unwrap_or_default(),
+        visible: get(dict, "visible")?.unwrap_or(true),
+        ..Default::default()
+    };
+    if let Some(seconds) = get::<i64>(dict, "timestamp")? {
+        let timestamp = DateTime::from_timestamp(seconds, 0)
+            .ok_or_else(|| PyValueError::new_err(format!("Invalid timestamp: {}", seconds)))?;
+        base.timestamp = Some(timestamp);
+    }
+    if let Some(uid) = get(dict, "uid")? {
+        base.user = Some(OsmUser {
+            id: uid,
+            name: intern(&get::<String>(dict, "user")?.unwrap_or_default()),
+        });
+    }
+    if let Some(tags) = get::<Bound<PyDict>>(dict, "tags")? {
+        for (key, value) in tags.iter() {
+            base.tags.push(Tag {
+                key: key.extract()?,
+                value: value.extract()?,
+            });
+        }
+    }
+
+    let element = match element_type {
+        ElementType::Node => {
+            let mut node = Node::from(base);
+            node.latitude = to_nanodegrees(get(dict, "lat")?.unwrap_or_default());
+            node.longitude = to_nanodegrees(get(dict, "lon")?.unwrap_or_default());
+            Element::Node(node)
+        }
+        ElementType::Way => {
+            let mut way = Way::from(base);
+            let node_ids: Vec<i64> = get(dict, "nodes")?.unwrap_or_default();
+            way.way_nodes = node_ids
+                .into_iter()
+                .map(WayNode::new_without_coords)
+                .collect();
+            Element::Way(way)
+        }
+        ElementType::Relation => {
+            let mut relation = Relation::from(base);
+            let members: Vec<(String, i64, String)> = get(dict, "members")?.unwrap_or_default();
+            for (member_type, member_id, role) in members {
+                relation.members.push(RelationMember {
+                    member_id,
+                    member_type: parse_element_type(&member_type)?,
+                    role: intern(&role),
+                });
+            }
+            Element::Relation(relation)
+        }
+    };
+    Ok(element)
+}
+
+fn to_nanodegrees(degrees: f64) -> i64 {
+    (degrees * 1e9).round() as i64
+}
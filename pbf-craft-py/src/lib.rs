@@ -0,0 +1,39 @@
+//! Python bindings for pbf-craft.
+//!
+//! The `pbf_craft` Python module exposes `IterableReader`, `IndexedReader` and `PbfWriter`.
+//! Elements are passed as plain dicts, built directly from the decoded elements without an
+//! intermediate format:
+//!
+//! ```python
+//! import pbf_craft
+//!
+//! reader = pbf_craft.IndexedReader("resources/andorra-latest.osm.pbf")
+//! for element in reader.get_with_deps("way", 1055523837):
+//!     print(element["type"], element["id"], element["tags"])
+//! ```
+//!
+//! Build the module with `maturin develop --release` from this directory.
+
+// The code generated by `#[pymethods]` converts `PyResult` return values into themselves
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+mod elements;
+mod readers;
+mod writers;
+
+/// Converts an error of the library into a Python `RuntimeError`.
+pub(crate) fn to_py_err(err: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", err))
+}
+
+#[pymodule]
+#[pyo3(name = "pbf_craft")]
+fn pbf_craft_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<readers::PyIterableReader>()?;
+    m.add_class::<readers::PyIndexedReader>()?;
+    m.add_class::<writers::PyPbfWriter>()?;
+    Ok(())
+}
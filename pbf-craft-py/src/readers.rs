@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use pbf_craft::readers::{CachedReader, ElementSource, IndexedReader, IterableReader};
+
+use crate::elements::{parse_element_type, to_dict, to_element_id, to_list};
+use crate::to_py_err;
+
+/// Iterates over the elements of a PBF file as dicts.
+///
+/// ```python
+/// reader = pbf_craft.IterableReader("resources/andorra-latest.osm.pbf")
+/// reader.skip_to("way")
+/// highways = [way for way in reader if way["type"] == "way" and "highway" in way["tags"]]
+/// ```
+#[pyclass(name = "IterableReader", unsendable)]
+pub(crate) struct PyIterableReader {
+    reader: IterableReader<BufReader<File>>,
+}
+
+#[pymethods]
+impl PyIterableReader {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        let reader = IterableReader::from_path(path).map_err(to_py_err)?;
+        Ok(Self { reader })
+    }
+
+    /// Skips the blobs which contain no element of the given type or of a later one, without
+    /// decoding them. The types are ordered as `node`, `way` and `relation`.
+    fn skip_to(&mut self, element_type: &str) -> PyResult<()> {
+        let element_type = parse_element_type(element_type)?;
+        self.reader.skip_to(element_type).map_err(to_py_err)
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        match self.reader.next_element().map_err(to_py_err)? {
+            Some(element) => Ok(Some(to_dict(py, &element)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Finds elements by ID through an index file, which is created next to the PBF file on first
+/// use. Decoded blobs are cached, so looking up nearby elements is cheap.
+///
+/// ```python
+/// reader = pbf_craft.IndexedReader("resources/andorra-latest.osm.pbf", cache_capacity=1000)
+/// node = reader.find("node", 12345678)
+/// elements = reader.get_with_deps("relation", 2220322)
+/// ```
+#[pyclass(name = "IndexedReader", unsendable)]
+pub(crate) struct PyIndexedReader {
+    reader: IndexedReader<CachedReader>,
+}
+
+#[pymethods]
+impl PyIndexedReader {
+    #[new]
+    #[pyo3(signature = (path, cache_capacity = 1000))]
+    fn new(path: &str, cache_capacity: usize) -> PyResult<Self> {
+        let reader =
+            IndexedReader::from_path_with_cache(path, cache_capacity).map_err(to_py_err)?;
+        Ok(Self { reader })
+    }
+
+    /// Returns the element of the given type and ID, or `None` if there is none.
+    fn find<'py>(
+        &mut self,
+        py: Python<'py>,
+        element_type: &str,
+        element_id: i64,
+    ) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let element_type = parse_element_type(element_type)?;
+        match self
+            .reader
+            .find(&element_type, element_id)
+            .map_err(to_py_err)?
+        {
+            Some(element) => Ok(Some(to_dict(py, &element)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the element with the elements it references, recursively: the nodes of a way,
+    /// and the members of a relation with their own dependencies.
+    fn get_with_deps<'py>(
+        &mut self,
+        py: Python<'py>,
+        element_type: &str,
+        element_id: i64,
+    ) -> PyResult<Bound<'py, PyList>> {
+        let element_type = parse_element_type(element_type)?;
+        let element_id = to_element_id(&element_type, element_id)?;
+        let elements = self.reader.get_with_deps(element_id).map_err(to_py_err)?;
+        to_list(py, &elements)
+    }
+
+    /// Returns several elements of a type with their dependencies, each element once, ordered
+    /// by type and then ID.
+    fn get_many_with_deps<'py>(
+        &mut self,
+        py: Python<'py>,
+        element_type: &str,
+        element_ids: Vec<i64>,
+    ) -> PyResult<Bound<'py, PyList>> {
+        let element_type = parse_element_type(element_type)?;
+        let element_ids = element_ids
+            .into_iter()
+            .map(|element_id| to_element_id(&element_type, element_id))
+            .collect::<PyResult<Vec<_>>>()?;
+        let elements = self
+            .reader
+            .get_many_with_deps(&element_ids)
+            .map_err(to_py_err)?;
+        to_list(py, &elements)
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use pbf_craft::writers::PbfWriter;
+
+use crate::elements::from_dict;
+use crate::to_py_err;
+
+/// Writes elements, given as dicts in the layout returned by the readers, to a PBF file.
+///
+/// The elements must be written sorted by type and then ID. Used as a context manager, the
+/// writer is finished on exit:
+///
+/// ```python
+/// with pbf_craft.PbfWriter("output.osm.pbf") as writer:
+///     writer.write({"type": "node", "id": 1, "lat": 42.5, "lon": 1.5, "tags": {"amenity": "cafe"}})
+/// ```
+#[pyclass(name = "PbfWriter", unsendable)]
+pub(crate) struct PyPbfWriter {
+    /// The writer, until it's finished.
+    writer: Option<PbfWriter<BufWriter<File>>>,
+}
+
+impl PyPbfWriter {
+    fn writer(&mut self) -> PyResult<&mut PbfWriter<BufWriter<File>>> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| PyRuntimeError::new_err("The writer is already finished"))
+    }
+}
+
+#[pymethods]
+impl PyPbfWriter {
+    #[new]
+    #[pyo3(signature = (path, use_dense = true))]
+    fn new(path: &str, use_dense: bool) -> PyResult<Self> {
+        let writer = PbfWriter::from_path(path, use_dense).map_err(to_py_err)?;
+        Ok(Self {
+            writer: Some(writer),
+        })
+    }
+
+    fn write(&mut self, element: &Bound<'_, PyDict>) -> PyResult<()> {
+        let element = from_dict(element)?;
+        self.writer()?.write(element).map_err(to_py_err)
+    }
+
+    /// Writes the remaining elements and flushes the file. The writer can't be used afterwards.
+    fn finish(&mut self) -> PyResult<()> {
+        self.writer()?.finish().map_err(to_py_err)?;
+        self.writer = None;
+        Ok(())
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Finishes the writer, unless the block raised an exception.
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &mut self,
+        exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        if exc_type.is_none() && self.writer.is_some() {
+            self.finish()?;
+        }
+        Ok(false)
+    }
+}
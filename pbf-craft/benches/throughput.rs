@@ -0,0 +1,225 @@
+//! Throughput benchmarks of reading, scanning, looking up and writing PBF data.
+//!
+//! Run with `cargo bench -p pbf-craft --features testing`. Besides the bundled Andorra extract,
+//! the benchmarks run on a synthetic file of 10 million nodes, written to the temporary
+//! directory on first use. Set `PBF_CRAFT_BENCH_NODES` to change its size.
+//!
+//! The `block_decode` group is named after the protobuf backend, so that the backends can be
+//! compared by running it again with `--features testing,prost`.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use pbf_craft::models::{Element, ElementType};
+use pbf_craft::readers::{
+    DecodeErrorPolicy, DecodeOptions, DecodedBlob, IndexedReader, IterableReader, PbfIndex,
+    PbfReader, PrimitiveReader, ScanSchedule,
+};
+use pbf_craft::testing::{synthetic_elements, write_synthetic_pbf};
+use pbf_craft::writers::PbfWriter;
+
+const ANDORRA: &str = "resources/andorra-latest.osm.pbf";
+const DEFAULT_SYNTHETIC_NODES: u64 = 10_000_000;
+const BACKEND: &str = if cfg!(feature = "prost") {
+    "prost"
+} else {
+    "protobuf"
+};
+
+fn synthetic_node_count() -> u64 {
+    env::var("PBF_CRAFT_BENCH_NODES")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(DEFAULT_SYNTHETIC_NODES)
+}
+
+/// Returns the path of the synthetic file, writing it if it doesn't exist yet.
+fn synthetic_file() -> PathBuf {
+    let node_count = synthetic_node_count();
+    let path = env::temp_dir().join(format!("pbf-craft-bench-{}.osm.pbf", node_count));
+    if !path.exists() {
+        write_synthetic_pbf(&path, node_count).unwrap();
+    }
+    path
+}
+
+fn inputs() -> Vec<(&'static str, PathBuf)> {
+    vec![
+        ("andorra", PathBuf::from(ANDORRA)),
+        ("synthetic", synthetic_file()),
+    ]
+}
+
+fn file_throughput(path: &Path) -> Throughput {
+    Throughput::Bytes(path.metadata().unwrap().len())
+}
+
+fn sequential_read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sequential_read");
+    group.sample_size(10);
+    for (name, path) in inputs() {
+        group.throughput(file_throughput(&path));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &path, |b, path| {
+            b.iter(|| IterableReader::from_path(path).unwrap().count())
+        });
+    }
+    group.finish();
+}
+
+/// Parses the blocks of a file held in memory and decodes their elements, which is where the
+/// protobuf backends differ.
+fn block_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("block_decode");
+    group.sample_size(10);
+    for (name, path) in inputs() {
+        let data = fs::read(&path).unwrap();
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new(BACKEND, name), &data, |b, data| {
+            b.iter(|| {
+                let mut count = 0;
+                PbfReader::new(data.as_slice())
+                    .read_blocks(|block| {
+                        if let DecodedBlob::OsmData(block) = block {
+                            let reader = PrimitiveReader::new(block, DecodeErrorPolicy::Fail);
+                            let (nodes, ways, relations) = reader.get_all_elements().unwrap();
+                            count += nodes.len() + ways.len() + relations.len();
+                        }
+                    })
+                    .unwrap();
+                count
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Reads the elements with only their IDs and references, as for marking the referenced
+/// elements, comparing full decoding with skipping the other fields.
+fn reference_pass(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reference_pass");
+    group.sample_size(10);
+    let skip_all = DecodeOptions {
+        skip_tags: true,
+        skip_metadata: true,
+        skip_geometry: true,
+        ..Default::default()
+    };
+    for (name, path) in inputs() {
+        group.throughput(file_throughput(&path));
+        for (options_name, options) in [("full", DecodeOptions::default()), ("skip", skip_all)] {
+            let id = BenchmarkId::new(options_name, name);
+            group.bench_with_input(id, &path, |b, path| {
+                b.iter(|| {
+                    let mut reader = IterableReader::from_path(path).unwrap();
+                    reader.set_decode_options(options);
+                    reader.count()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn parallel_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parallel_scan");
+    group.sample_size(10);
+    for (name, path) in inputs() {
+        group.throughput(file_throughput(&path));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &path, |b, path| {
+            b.iter(|| {
+                PbfReader::from_path(path)
+                    .unwrap()
+                    .par_map_reduce(|_| 1u64, |a, b| a + b, || 0)
+                    .unwrap()
+            })
+        });
+        // The expensive blobs first, weighted by the element counts of the index
+        let summary = PbfIndex::new(path.to_str().unwrap()).unwrap().summary();
+        group.bench_with_input(BenchmarkId::new("largest_first", name), &path, |b, path| {
+            b.iter(|| {
+                let mut reader = PbfReader::from_path(path).unwrap();
+                reader.set_scan_schedule(ScanSchedule::LargestFirst { window: 64 });
+                reader.set_blob_inventory(&summary);
+                reader.par_map_reduce(|_| 1u64, |a, b| a + b, || 0).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn indexed_lookup(c: &mut Criterion) {
+    // Every 1000th node of the file, so that the lookups hit many different blobs
+    let node_ids: Vec<i64> = IterableReader::from_path(ANDORRA)
+        .unwrap()
+        .filter_map(|element| match element {
+            Element::Node(node) => Some(node.id),
+            _ => None,
+        })
+        .step_by(1000)
+        .collect();
+
+    let mut group = c.benchmark_group("indexed_lookup");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(node_ids.len() as u64));
+    group.bench_function("andorra", |b| {
+        let mut reader = IndexedReader::from_path(ANDORRA).unwrap();
+        b.iter(|| {
+            for id in &node_ids {
+                reader.find(&ElementType::Node, *id).unwrap().unwrap();
+            }
+        })
+    });
+    group.bench_function("andorra_cached", |b| {
+        let mut reader = IndexedReader::from_path_with_cache(ANDORRA, 1000).unwrap();
+        b.iter(|| {
+            for id in &node_ids {
+                reader.find_node_ref(*id).unwrap().unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let elements: Vec<Element> = IterableReader::from_path(ANDORRA).unwrap().collect();
+    let node_count = synthetic_node_count();
+
+    let mut group = c.benchmark_group("write");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(elements.len() as u64));
+    group.bench_function("andorra", |b| {
+        b.iter(|| {
+            let mut writer = PbfWriter::new(io::sink(), true);
+            for element in elements.iter().cloned() {
+                writer.write(element).unwrap();
+            }
+            writer.finish().unwrap();
+        })
+    });
+    group.throughput(Throughput::Elements(node_count));
+    group.bench_function("synthetic", |b| {
+        b.iter(|| {
+            let mut writer = PbfWriter::new(io::sink(), true);
+            for element in synthetic_elements(node_count) {
+                writer.write(element).unwrap();
+            }
+            writer.finish().unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    sequential_read,
+    block_decode,
+    reference_pass,
+    parallel_scan,
+    indexed_lookup,
+    write
+);
+criterion_main!(benches);
@@ -1,12 +1,17 @@
 use std::io::Write;
 
-static MOD_RS: &[u8] = b"
+static MOD_RS: &str = "
 pub mod fileformat;
 pub mod osmformat;
 ";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let proto_files = ["src/proto/fileformat.proto", "src/proto/osmformat.proto"];
+    let mut proto_files = vec!["src/proto/fileformat.proto", "src/proto/osmformat.proto"];
+    let mut mod_rs = MOD_RS.to_string();
+    if std::env::var_os("CARGO_FEATURE_TILES").is_some() {
+        proto_files.push("src/proto/vector_tile.proto");
+        mod_rs.push_str("pub mod vector_tile;\n");
+    }
     let out_dir = std::env::var("OUT_DIR")?;
 
     protobuf_codegen_pure::Codegen::new()
@@ -15,7 +20,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .include("src/proto")
         .run()?;
 
-    std::fs::File::create(out_dir + "/mod.rs")?.write_all(MOD_RS)?;
+    std::fs::File::create(out_dir + "/mod.rs")?.write_all(mod_rs.as_bytes())?;
 
     Ok(())
 }
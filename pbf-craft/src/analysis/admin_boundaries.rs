@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use geo::{Area, BoundingRect, Contains, Coord, InteriorPoint, LineString, MultiPolygon, Polygon};
+use serde_json::{json, Value};
+
+use crate::models::{Element, ElementType, Relation, Tag};
+use crate::readers::{ElementSource, IterableReader};
+use crate::utils::IdSet;
+
+/// An administrative boundary with its place in the hierarchy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdminBoundary {
+    pub relation_id: i64,
+    /// The `admin_level` tag, from 2 for countries up to 11 for the smallest subdivisions.
+    pub admin_level: u8,
+    pub name: Option<String>,
+    pub tags: Vec<Tag>,
+    /// The area in degrees, assembled from the `outer` and `inner` ways of the relation.
+    pub polygon: MultiPolygon<f64>,
+    /// The relation ID of the smallest boundary of a lower level containing this one.
+    pub parent_id: Option<i64>,
+}
+
+/// Administrative boundaries and their containment hierarchy, e.g. country, state and city, as
+/// needed to preprocess data for reverse geocoding.
+///
+/// The `boundary=administrative` relations are read with their ways and nodes and their
+/// rings are assembled into polygons. The parent of each boundary is the boundary with the
+/// highest `admin_level` below its own which contains an interior point of it, so boundaries
+/// sharing their border with their parent are handled as well. Levels may be skipped, e.g. a
+/// city may lie directly in a country.
+///
+/// Relations whose rings can't be closed, typically because some of their ways are missing
+/// from an extract, are left out and listed in `incomplete_relation_ids`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::AdminHierarchy;
+///
+/// let hierarchy = AdminHierarchy::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// for boundary in &hierarchy.boundaries {
+///     let names: Vec<_> = hierarchy
+///         .ancestors(boundary.relation_id)
+///         .iter()
+///         .map(|ancestor| ancestor.name.clone())
+///         .collect();
+///     println!("{:?} lies in {:?}", boundary.name, names);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AdminHierarchy {
+    /// The boundaries ordered by admin level, then by relation ID.
+    pub boundaries: Vec<AdminBoundary>,
+    pub incomplete_relation_ids: Vec<i64>,
+}
+
+impl AdminHierarchy {
+    /// Builds the hierarchy of the boundaries of a PBF file.
+    ///
+    /// The file is read three times, each time skipping the element types which aren't
+    /// needed: for the relations, for their ways and for the nodes of those ways.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let mut reader = IterableReader::from_path(&path)?;
+        reader.skip_to(ElementType::Relation)?;
+        let relations = read_boundary_relations(reader)?;
+
+        let mut wanted_ways = IdSet::new();
+        for (relation, _) in &relations {
+            for member in &relation.members {
+                if member.member_type == ElementType::Way {
+                    wanted_ways.insert(member.member_id);
+                }
+            }
+        }
+        let mut reader = IterableReader::from_path(&path)?;
+        reader.skip_to(ElementType::Way)?;
+        let way_node_ids = read_way_node_ids(reader, &wanted_ways)?;
+
+        let mut wanted_nodes = IdSet::new();
+        for node_ids in way_node_ids.values() {
+            for node_id in node_ids {
+                wanted_nodes.insert(*node_id);
+            }
+        }
+        let locations = read_locations(IterableReader::from_path(&path)?, &wanted_nodes)?;
+
+        let mut hierarchy = Self::default();
+        for (relation, admin_level) in relations {
+            match assemble_polygon(&relation, &way_node_ids, &locations) {
+                Some(polygon) => hierarchy.boundaries.push(AdminBoundary {
+                    relation_id: relation.id,
+                    admin_level,
+                    name: tag_value(&relation.tags, "name").map(|name| name.to_string()),
+                    tags: relation.tags,
+                    polygon,
+                    parent_id: None,
+                }),
+                None => hierarchy.incomplete_relation_ids.push(relation.id),
+            }
+        }
+        hierarchy.link_parents();
+        Ok(hierarchy)
+    }
+
+    /// Sets the parent of every boundary.
+    fn link_parents(&mut self) {
+        self.boundaries
+            .sort_by_key(|boundary| (boundary.admin_level, boundary.relation_id));
+        let areas: Vec<f64> = self
+            .boundaries
+            .iter()
+            .map(|boundary| boundary.polygon.unsigned_area())
+            .collect();
+        let rects: Vec<_> = self
+            .boundaries
+            .iter()
+            .map(|boundary| boundary.polygon.bounding_rect())
+            .collect();
+        for index in 0..self.boundaries.len() {
+            let Some(point) = self.boundaries[index].polygon.interior_point() else {
+                continue;
+            };
+            let level = self.boundaries[index].admin_level;
+            // The boundaries are sorted by level, so the candidates come before this one
+            let parent = (0..index)
+                .filter(|candidate| self.boundaries[*candidate].admin_level < level)
+                .filter(|candidate| rects[*candidate].is_some_and(|rect| rect.contains(&point)))
+                .filter(|candidate| self.boundaries[*candidate].polygon.contains(&point))
+                .max_by(|a, b| {
+                    let level_a = self.boundaries[*a].admin_level;
+                    let level_b = self.boundaries[*b].admin_level;
+                    // The highest level, then the smallest area
+                    level_a.cmp(&level_b).then(areas[*b].total_cmp(&areas[*a]))
+                });
+            self.boundaries[index].parent_id =
+                parent.map(|parent| self.boundaries[parent].relation_id);
+        }
+    }
+
+    /// Returns the boundary of a relation.
+    pub fn get(&self, relation_id: i64) -> Option<&AdminBoundary> {
+        self.boundaries
+            .iter()
+            .find(|boundary| boundary.relation_id == relation_id)
+    }
+
+    /// Returns the boundaries containing a boundary, from the lowest level, e.g. the country,
+    /// to its parent.
+    pub fn ancestors(&self, relation_id: i64) -> Vec<&AdminBoundary> {
+        let mut ancestors = Vec::new();
+        let mut current = self.get(relation_id);
+        while let Some(parent) = current
+            .and_then(|boundary| boundary.parent_id)
+            .and_then(|parent_id| self.get(parent_id))
+        {
+            ancestors.push(parent);
+            current = Some(parent);
+        }
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// Returns the boundaries as a GeoJSON `FeatureCollection` of `MultiPolygon` features, with
+    /// the tags, the `@admin_level` and the `@parent_id` of each boundary as properties.
+    pub fn to_geojson(&self) -> Value {
+        let features: Vec<Value> = self
+            .boundaries
+            .iter()
+            .map(|boundary| {
+                let mut properties = serde_json::Map::new();
+                for tag in &boundary.tags {
+                    properties.insert(tag.key.clone(), Value::from(tag.value.clone()));
+                }
+                properties.insert("@id".to_string(), Value::from(boundary.relation_id));
+                properties.insert(
+                    "@admin_level".to_string(),
+                    Value::from(boundary.admin_level),
+                );
+                properties.insert("@parent_id".to_string(), json!(boundary.parent_id));
+                let coordinates: Vec<Value> = boundary
+                    .polygon
+                    .iter()
+                    .map(|polygon| {
+                        let rings: Vec<Value> = std::iter::once(polygon.exterior())
+                            .chain(polygon.interiors())
+                            .map(|ring| {
+                                Value::from_iter(
+                                    ring.coords().map(|coord| json!([coord.x, coord.y])),
+                                )
+                            })
+                            .collect();
+                        Value::from(rings)
+                    })
+                    .collect();
+                json!({
+                    "type": "Feature",
+                    "id": format!("relation/{}", boundary.relation_id),
+                    "geometry": {
+                        "type": "MultiPolygon",
+                        "coordinates": coordinates,
+                    },
+                    "properties": properties,
+                })
+            })
+            .collect();
+        json!({
+            "type": "FeatureCollection",
+            "features": features,
+        })
+    }
+}
+
+fn tag_value<'a>(tags: &'a [Tag], key: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|tag| tag.key == key)
+        .map(|tag| tag.value.as_str())
+}
+
+/// Reads the administrative boundary relations with a valid admin level.
+fn read_boundary_relations<S: ElementSource>(mut source: S) -> anyhow::Result<Vec<(Relation, u8)>> {
+    let mut relations = Vec::new();
+    while let Some(element) = source.next_element()? {
+        if let Element::Relation(relation) = element {
+            if tag_value(&relation.tags, "boundary") != Some("administrative") {
+                continue;
+            }
+            let admin_level = tag_value(&relation.tags, "admin_level")
+                .and_then(|admin_level| admin_level.parse::<u8>().ok());
+            if let Some(admin_level) = admin_level {
+                relations.push((relation, admin_level));
+            }
+        }
+    }
+    Ok(relations)
+}
+
+fn read_way_node_ids<S: ElementSource>(
+    mut source: S,
+    wanted_ways: &IdSet,
+) -> anyhow::Result<HashMap<i64, Vec<i64>>> {
+    let mut way_node_ids = HashMap::new();
+    while let Some(element) = source.next_element()? {
+        match element {
+            Element::Way(way) if wanted_ways.contains(way.id) => {
+                let node_ids = way.way_nodes.iter().map(|wn| wn.id).collect();
+                way_node_ids.insert(way.id, node_ids);
+            }
+            // Relations come last, so there is nothing left to read
+            Element::Relation(_) => break,
+            _ => {}
+        }
+    }
+    Ok(way_node_ids)
+}
+
+fn read_locations<S: ElementSource>(
+    mut source: S,
+    wanted_nodes: &IdSet,
+) -> anyhow::Result<HashMap<i64, Coord<f64>>> {
+    let mut locations = HashMap::new();
+    while let Some(element) = source.next_element()? {
+        match element {
+            Element::Node(node) => {
+                if wanted_nodes.contains(node.id) {
+                    let coord = Coord {
+                        x: node.longitude as f64 / 1e9,
+                        y: node.latitude as f64 / 1e9,
+                    };
+                    locations.insert(node.id, coord);
+                }
+            }
+            // Nodes come first, so there is nothing left to read
+            _ => break,
+        }
+    }
+    Ok(locations)
+}
+
+/// Assembles the polygons of a relation, or returns `None` if a way or node is missing or a
+/// ring can't be closed.
+fn assemble_polygon(
+    relation: &Relation,
+    way_node_ids: &HashMap<i64, Vec<i64>>,
+    locations: &HashMap<i64, Coord<f64>>,
+) -> Option<MultiPolygon<f64>> {
+    let mut outer_ways = Vec::new();
+    let mut inner_ways = Vec::new();
+    for member in &relation.members {
+        if member.member_type != ElementType::Way {
+            continue;
+        }
+        let node_ids = way_node_ids.get(&member.member_id)?;
+        match &*member.role {
+            "inner" => inner_ways.push(node_ids.as_slice()),
+            // Untagged ways are treated as outer ways, as editors did in the past
+            "outer" | "" => outer_ways.push(node_ids.as_slice()),
+            _ => {}
+        }
+    }
+    let to_line_string = |ring: Vec<i64>| -> Option<LineString<f64>> {
+        ring.iter()
+            .map(|node_id| locations.get(node_id).copied())
+            .collect::<Option<Vec<Coord<f64>>>>()
+            .map(LineString::new)
+    };
+    let outers = build_rings(outer_ways)?
+        .into_iter()
+        .map(to_line_string)
+        .collect::<Option<Vec<_>>>()?;
+    let inners = build_rings(inner_ways)?
+        .into_iter()
+        .map(to_line_string)
+        .collect::<Option<Vec<_>>>()?;
+    if outers.is_empty() {
+        return None;
+    }
+
+    let mut polygons: Vec<Polygon<f64>> = outers
+        .into_iter()
+        .map(|outer| Polygon::new(outer, Vec::new()))
+        .collect();
+    for inner in inners {
+        let point = inner.0[0];
+        // Each inner ring belongs to the outer ring containing it
+        if let Some(polygon) = polygons
+            .iter_mut()
+            .find(|polygon| polygon.exterior().contains(&point) || polygon.contains(&point))
+        {
+            polygon.interiors_push(inner);
+        }
+    }
+    Some(MultiPolygon::new(polygons))
+}
+
+/// Joins ways sharing end nodes into closed rings, regardless of their order and direction.
+/// Returns `None` if a ring can't be closed.
+fn build_rings(ways: Vec<&[i64]>) -> Option<Vec<Vec<i64>>> {
+    let mut remaining: Vec<&[i64]> = ways.into_iter().filter(|way| way.len() >= 2).collect();
+    let mut rings = Vec::new();
+    while let Some(first) = remaining.pop() {
+        let mut ring = first.to_vec();
+        while ring.first() != ring.last() {
+            let end = *ring.last()?;
+            let position = remaining
+                .iter()
+                .position(|way| way[0] == end || way[way.len() - 1] == end)?;
+            let way = remaining.swap_remove(position);
+            if way[0] == end {
+                ring.extend_from_slice(&way[1..]);
+            } else {
+                ring.extend(way.iter().rev().skip(1));
+            }
+        }
+        if ring.len() >= 4 {
+            rings.push(ring);
+        }
+    }
+    Some(rings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_rings() {
+        let ways: Vec<&[i64]> = vec![&[1, 2], &[3, 2], &[3, 4, 1], &[5, 6, 7, 5]];
+        let mut rings = build_rings(ways).unwrap();
+        rings.sort();
+        assert_eq!(rings, vec![vec![3, 4, 1, 2, 3], vec![5, 6, 7, 5]]);
+
+        let unclosed: Vec<&[i64]> = vec![&[1, 2], &[2, 3]];
+        assert_eq!(build_rings(unclosed), None);
+    }
+
+    #[test]
+    fn test_from_path() {
+        let hierarchy = AdminHierarchy::from_path("./resources/andorra-latest.osm.pbf").unwrap();
+        let country = hierarchy
+            .boundaries
+            .iter()
+            .find(|boundary| {
+                boundary.admin_level == 2 && boundary.name.as_deref() == Some("Andorra")
+            })
+            .unwrap();
+        let parishes: Vec<&AdminBoundary> = hierarchy
+            .boundaries
+            .iter()
+            .filter(|boundary| boundary.admin_level == 7)
+            .collect();
+        assert_eq!(parishes.len(), 7);
+        for parish in parishes {
+            assert_eq!(parish.parent_id, Some(country.relation_id));
+            assert_eq!(hierarchy.ancestors(parish.relation_id), vec![country]);
+        }
+
+        let geojson = hierarchy.to_geojson();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), hierarchy.boundaries.len());
+        assert_eq!(features[0]["geometry"]["type"], "MultiPolygon");
+    }
+}
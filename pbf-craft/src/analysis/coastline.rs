@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use geo::{
+    BooleanOps, Contains, Coord, HaversineDistance, LineString, MultiPolygon, Point, Polygon, Rect,
+    Winding,
+};
+
+use crate::models::{Element, ElementType, Tag};
+use crate::readers::{ElementSource, IterableReader};
+use crate::utils::IdSet;
+
+/// Coastline ways joined end to end, in the direction of the ways.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoastlineChain {
+    pub way_ids: Vec<i64>,
+    /// The nodes of the chain. The first and last node are the same for closed rings.
+    pub node_ids: Vec<i64>,
+    /// The locations of the nodes in degrees.
+    pub coords: Vec<Coord<f64>>,
+}
+
+/// The open end of a coastline chain, where the coastline is broken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoastlineGap {
+    /// The last node of the chain which ends here.
+    pub node_id: i64,
+    pub coord: Coord<f64>,
+    /// The first node of the nearest chain which could continue the coastline, if any.
+    pub nearest_start_node_id: Option<i64>,
+    /// The distance to that node in meters.
+    pub distance: Option<f64>,
+}
+
+/// Assembles the `natural=coastline` ways of a file into rings, as renderers need them for
+/// land and water polygons.
+///
+/// The ways are joined in their direction regardless of the blobs they are stored in, since
+/// coastline ways run with the land on their left and reversing one would flip land and water.
+/// Closed chains become `rings`; chains that can't be closed, e.g. because a way is missing
+/// or points the wrong way, are kept as `open_chains` and their ends are reported by `gaps`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::Coastline;
+///
+/// let coastline = Coastline::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// for gap in coastline.gaps() {
+///     println!("The coastline is broken at node {}", gap.node_id);
+/// }
+/// let land = coastline.land_polygons();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Coastline {
+    pub rings: Vec<CoastlineChain>,
+    pub open_chains: Vec<CoastlineChain>,
+    /// The IDs of the coastline ways left out because the location of one of their nodes is
+    /// unknown.
+    pub incomplete_way_ids: Vec<i64>,
+}
+
+/// A coastline way and its node locations.
+struct CoastlineWay {
+    id: i64,
+    node_ids: Vec<i64>,
+    coords: Vec<Coord<f64>>,
+}
+
+impl Coastline {
+    /// Assembles the coastline of a PBF file.
+    ///
+    /// The file is read twice: for the coastline ways, skipping the nodes, and for their nodes.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let mut reader = IterableReader::from_path(&path)?;
+        reader.skip_to(ElementType::Way)?;
+        let ways = read_coastline_ways(reader)?;
+
+        let mut wanted_nodes = IdSet::new();
+        for (_, node_ids) in &ways {
+            for node_id in node_ids {
+                wanted_nodes.insert(*node_id);
+            }
+        }
+        let locations = read_locations(IterableReader::from_path(&path)?, &wanted_nodes)?;
+
+        let mut coastline = Self::default();
+        let mut located = Vec::new();
+        for (id, node_ids) in ways {
+            let coords: Option<Vec<Coord<f64>>> = node_ids
+                .iter()
+                .map(|node_id| locations.get(node_id).copied())
+                .collect();
+            match coords {
+                Some(coords) => located.push(CoastlineWay {
+                    id,
+                    node_ids,
+                    coords,
+                }),
+                None => coastline.incomplete_way_ids.push(id),
+            }
+        }
+        coastline.join(located);
+        Ok(coastline)
+    }
+
+    /// Joins the ways into chains, each ending where no unused way starts.
+    fn join(&mut self, ways: Vec<CoastlineWay>) {
+        let mut starting_at: HashMap<i64, Vec<usize>> = HashMap::new();
+        let mut ending_at: HashMap<i64, Vec<usize>> = HashMap::new();
+        for (index, way) in ways.iter().enumerate() {
+            starting_at.entry(way.node_ids[0]).or_default().push(index);
+            ending_at
+                .entry(way.node_ids[way.node_ids.len() - 1])
+                .or_default()
+                .push(index);
+        }
+        let mut used = vec![false; ways.len()];
+        let take = |candidates: Option<&Vec<usize>>, used: &mut Vec<bool>| {
+            let index = *candidates?.iter().find(|index| !used[**index])?;
+            used[index] = true;
+            Some(index)
+        };
+
+        for first in 0..ways.len() {
+            if used[first] {
+                continue;
+            }
+            used[first] = true;
+            let mut chain = vec![first];
+            let start = ways[first].node_ids[0];
+            let mut end = *ways[first].node_ids.last().unwrap();
+            while end != start {
+                match take(starting_at.get(&end), &mut used) {
+                    Some(next) => {
+                        chain.push(next);
+                        end = *ways[next].node_ids.last().unwrap();
+                    }
+                    None => break,
+                }
+            }
+            if end != start {
+                // Extends the open chain backwards, as its first way may not be the first one
+                let mut start = start;
+                while let Some(previous) = take(ending_at.get(&start), &mut used) {
+                    chain.insert(0, previous);
+                    start = ways[previous].node_ids[0];
+                    if start == end {
+                        break;
+                    }
+                }
+            }
+
+            let mut joined = CoastlineChain {
+                way_ids: Vec::new(),
+                node_ids: Vec::new(),
+                coords: Vec::new(),
+            };
+            for index in chain {
+                let way = &ways[index];
+                // The first node of a way is the last node of the previous one
+                let skip = usize::from(!joined.node_ids.is_empty());
+                joined.way_ids.push(way.id);
+                joined.node_ids.extend_from_slice(&way.node_ids[skip..]);
+                joined.coords.extend_from_slice(&way.coords[skip..]);
+            }
+            if joined.node_ids.len() >= 4 && joined.node_ids.first() == joined.node_ids.last() {
+                self.rings.push(joined);
+            } else {
+                self.open_chains.push(joined);
+            }
+        }
+    }
+
+    /// Returns the ends of the open chains, each with the nearest start of an open chain which
+    /// could continue it.
+    pub fn gaps(&self) -> Vec<CoastlineGap> {
+        self.open_chains
+            .iter()
+            .map(|chain| {
+                let coord = *chain.coords.last().unwrap();
+                let point = Point::from(coord);
+                let nearest = self
+                    .open_chains
+                    .iter()
+                    .map(|other| {
+                        let distance = point.haversine_distance(&Point::from(other.coords[0]));
+                        (other.node_ids[0], distance)
+                    })
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+                CoastlineGap {
+                    node_id: *chain.node_ids.last().unwrap(),
+                    coord,
+                    nearest_start_node_id: nearest.map(|(node_id, _)| node_id),
+                    distance: nearest.map(|(_, distance)| distance),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the land polygons of the closed rings.
+    ///
+    /// Counter-clockwise rings enclose land, as the land lies to the left of coastline ways.
+    /// Clockwise rings enclose water, e.g. inland seas, and become holes of the land polygon
+    /// containing them. Open chains are ignored.
+    pub fn land_polygons(&self) -> MultiPolygon<f64> {
+        let mut land = Vec::new();
+        let mut water = Vec::new();
+        for ring in &self.rings {
+            let line_string = LineString::new(ring.coords.clone());
+            if line_string.is_ccw() {
+                land.push(Polygon::new(line_string, Vec::new()));
+            } else {
+                water.push(line_string);
+            }
+        }
+        for hole in water {
+            let point = hole.0[0];
+            if let Some(polygon) = land
+                .iter_mut()
+                .find(|polygon| polygon.exterior().contains(&point) || polygon.contains(&point))
+            {
+                polygon.interiors_push(hole);
+            }
+        }
+        MultiPolygon::new(land)
+    }
+
+    /// Returns the water polygons within a bounding box in degrees, i.e. the box without the
+    /// land polygons. Without any ring, the whole box is water, even for inland areas.
+    pub fn water_polygons(&self, bbox: Rect<f64>) -> MultiPolygon<f64> {
+        MultiPolygon::new(vec![bbox.to_polygon()]).difference(&self.land_polygons())
+    }
+}
+
+fn is_coastline(tags: &[Tag]) -> bool {
+    tags.iter()
+        .any(|tag| tag.key == "natural" && tag.value == "coastline")
+}
+
+fn read_coastline_ways<S: ElementSource>(mut source: S) -> anyhow::Result<Vec<(i64, Vec<i64>)>> {
+    let mut ways = Vec::new();
+    while let Some(element) = source.next_element()? {
+        match element {
+            Element::Way(way) if way.way_nodes.len() >= 2 && is_coastline(&way.tags) => {
+                ways.push((way.id, way.way_nodes.iter().map(|wn| wn.id).collect()));
+            }
+            // Relations come last, so there is nothing left to read
+            Element::Relation(_) => break,
+            _ => {}
+        }
+    }
+    Ok(ways)
+}
+
+fn read_locations<S: ElementSource>(
+    mut source: S,
+    wanted_nodes: &IdSet,
+) -> anyhow::Result<HashMap<i64, Coord<f64>>> {
+    let mut locations = HashMap::new();
+    if wanted_nodes.is_empty() {
+        return Ok(locations);
+    }
+    while let Some(element) = source.next_element()? {
+        match element {
+            Element::Node(node) => {
+                if wanted_nodes.contains(node.id) {
+                    let coord = Coord {
+                        x: node.longitude as f64 / 1e9,
+                        y: node.latitude as f64 / 1e9,
+                    };
+                    locations.insert(node.id, coord);
+                }
+            }
+            // Nodes come first, so there is nothing left to read
+            _ => break,
+        }
+    }
+    Ok(locations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::Area;
+
+    fn way(id: i64, node_ids: &[i64]) -> CoastlineWay {
+        // Node n lies at (n % 10, n / 10) degrees
+        CoastlineWay {
+            id,
+            node_ids: node_ids.to_vec(),
+            coords: node_ids
+                .iter()
+                .map(|n| Coord {
+                    x: (n % 10) as f64,
+                    y: (n / 10) as f64,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_join() {
+        let mut coastline = Coastline::default();
+        coastline.join(vec![
+            // A counter-clockwise island split into three ways, stored out of order
+            way(2, &[2, 22]),
+            way(3, &[22, 20, 0]),
+            way(1, &[0, 2]),
+            // A broken coastline whose second way points the wrong way
+            way(4, &[55, 57]),
+            way(5, &[59, 57]),
+        ]);
+        assert_eq!(coastline.rings.len(), 1);
+        assert_eq!(coastline.rings[0].node_ids, vec![2, 22, 20, 0, 2]);
+        assert_eq!(coastline.rings[0].way_ids, vec![2, 3, 1]);
+        assert_eq!(coastline.open_chains.len(), 2);
+
+        let gaps = coastline.gaps();
+        assert_eq!(gaps.len(), 2);
+        assert_eq!(gaps[0].node_id, 57);
+        assert_eq!(gaps[0].nearest_start_node_id, Some(55));
+
+        let land = coastline.land_polygons();
+        assert_eq!(land.0.len(), 1);
+        assert_eq!(land.unsigned_area(), 4.0);
+        let bbox = Rect::new(Coord { x: -1.0, y: -1.0 }, Coord { x: 3.0, y: 3.0 });
+        assert_eq!(coastline.water_polygons(bbox).unsigned_area(), 12.0);
+    }
+
+    #[test]
+    fn test_from_path() {
+        // Andorra is landlocked
+        let coastline = Coastline::from_path("./resources/andorra-latest.osm.pbf").unwrap();
+        assert!(coastline.rings.is_empty());
+        assert!(coastline.gaps().is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+#[cfg(feature = "parallel")]
+use std::path::Path;
+
+use md5::{Digest, Md5};
+
+use crate::models::{BasicElement, Element, ElementType, Tag};
+#[cfg(feature = "parallel")]
+use crate::readers::PbfReader;
+
+/// A hasher of the logical content of elements, independent of their order and encoding.
+///
+/// Each element is hashed on its own and the hashes are summed, so the result is the same for
+/// any order of the elements, as if they were sorted. The hash covers the IDs, metadata, tags,
+/// coordinates of nodes, way nodes and relation members of the elements. The order of the tags
+/// is ignored, as are the coordinates stored on way nodes, which repeat those of the nodes.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::ContentHasher;
+/// use pbf_craft::readers::IterableReader;
+///
+/// let mut hasher = ContentHasher::new();
+/// for element in IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap() {
+///     hasher.add(&element);
+/// }
+/// println!("{}", hasher.finish());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ContentHasher {
+    count: u64,
+    sum: u128,
+}
+
+impl ContentHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an element to the hash.
+    pub fn add(&mut self, element: &Element) {
+        let mut hasher = Md5::new();
+        match element {
+            Element::Node(node) => {
+                hasher.update([0]);
+                write_base(&mut hasher, node);
+                hasher.update(node.latitude.to_le_bytes());
+                hasher.update(node.longitude.to_le_bytes());
+            }
+            Element::Way(way) => {
+                hasher.update([1]);
+                write_base(&mut hasher, way);
+                hasher.update((way.way_nodes.len() as u64).to_le_bytes());
+                for way_node in &way.way_nodes {
+                    hasher.update(way_node.id.to_le_bytes());
+                }
+            }
+            Element::Relation(relation) => {
+                hasher.update([2]);
+                write_base(&mut hasher, relation);
+                hasher.update((relation.members.len() as u64).to_le_bytes());
+                for member in &relation.members {
+                    hasher.update(member.member_id.to_le_bytes());
+                    hasher.update([match member.member_type {
+                        ElementType::Node => 0,
+                        ElementType::Way => 1,
+                        ElementType::Relation => 2,
+                    }]);
+                    write_str(&mut hasher, &member.role);
+                }
+            }
+        }
+        let digest: [u8; 16] = hasher.finalize().into();
+        self.count += 1;
+        self.sum = self.sum.wrapping_add(u128::from_le_bytes(digest));
+    }
+
+    /// Adds the elements added to another hasher.
+    pub fn merge(&mut self, other: &ContentHasher) {
+        self.count += other.count;
+        self.sum = self.sum.wrapping_add(other.sum);
+    }
+
+    /// Returns the number of elements added.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the hash as 32 lowercase hexadecimal digits.
+    pub fn finish(&self) -> String {
+        let mut hasher = Md5::new();
+        hasher.update(self.count.to_le_bytes());
+        hasher.update(self.sum.to_le_bytes());
+        let mut buf = [0u8; 32];
+        base16ct::lower::encode_str(&hasher.finalize(), &mut buf)
+            .expect("The buffer fits an MD5 hash")
+            .to_owned()
+    }
+}
+
+/// Writes the ID, metadata and tags of an element, the tags sorted.
+fn write_base<E: BasicElement>(hasher: &mut Md5, element: &E) {
+    hasher.update(element.get_id().to_le_bytes());
+    hasher.update(element.get_version().to_le_bytes());
+    match element.get_timestamp() {
+        Some(timestamp) => {
+            hasher.update([1]);
+            hasher.update(timestamp.timestamp_millis().to_le_bytes());
+        }
+        None => hasher.update([0]),
+    }
+    hasher.update(element.get_changeset_id().to_le_bytes());
+    match element.get_user() {
+        Some(user) => {
+            hasher.update([1]);
+            hasher.update(user.id.to_le_bytes());
+            write_str(hasher, &user.name);
+        }
+        None => hasher.update([0]),
+    }
+    hasher.update([element.is_visible() as u8]);
+    let mut tags: Vec<&Tag> = element.get_tags().iter().collect();
+    tags.sort_by(|a, b| a.key.cmp(&b.key).then(a.value.cmp(&b.value)));
+    hasher.update((tags.len() as u64).to_le_bytes());
+    for tag in tags {
+        write_str(hasher, &tag.key);
+        write_str(hasher, &tag.value);
+    }
+}
+
+/// Writes a string prefixed with its length, so that consecutive strings can't run together.
+fn write_str(hasher: &mut Md5, value: &str) {
+    hasher.update((value.len() as u64).to_le_bytes());
+    hasher.update(value.as_bytes());
+}
+
+/// Computes a hash of the logical content of a PBF file with `ContentHasher`, e.g. to compare
+/// the outputs of a pipeline.
+///
+/// Files with the same elements have the same hash, whatever their compression, node encoding,
+/// block boundaries, element order or header. The blobs are processed in parallel.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::content_hash;
+///
+/// let hash = content_hash("resources/andorra-latest.osm.pbf").unwrap();
+/// assert_eq!(hash.len(), 32);
+/// ```
+#[cfg(feature = "parallel")]
+pub fn content_hash<P: AsRef<Path>>(path: P) -> anyhow::Result<String> {
+    let hasher = PbfReader::from_path(path)?.par_fold_blocks(
+        |block| {
+            let mut hasher = ContentHasher::new();
+            block.for_each_element(|element| hasher.add(&element))?;
+            Ok(hasher)
+        },
+        |mut a, b| {
+            a.merge(&b);
+            a
+        },
+        ContentHasher::new,
+    )?;
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::readers::IterableReader;
+    use crate::writers::{BlobCompression, BlockComposition, PbfWriter};
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_content_hash() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let hash = content_hash(path).unwrap();
+        let elements: Vec<Element> = IterableReader::from_path(path).unwrap().collect();
+
+        let write = |output: &str, elements: &[Element]| {
+            let mut writer = PbfWriter::from_path(output, false).unwrap();
+            writer.set_compression(BlobCompression::None);
+            writer.set_block_composition(BlockComposition::Homogeneous);
+            for element in elements.iter().cloned() {
+                writer.write(element).unwrap();
+            }
+            writer.finish().unwrap();
+        };
+
+        // A different encoding of the same elements
+        let output = "./resources/test_content_hash.osm.pbf";
+        write(output, &elements);
+        assert_eq!(content_hash(output).unwrap(), hash);
+
+        // The order of the elements and tags doesn't matter
+        let mut hasher = ContentHasher::new();
+        for element in elements.iter().rev() {
+            let mut element = element.clone();
+            if let Element::Way(way) = &mut element {
+                way.tags.reverse();
+            }
+            hasher.add(&element);
+        }
+        assert_eq!(hasher.finish(), hash);
+        assert_eq!(hasher.count(), elements.len() as u64);
+
+        // A changed tag does
+        let mut changed = elements.clone();
+        let tagged = changed
+            .iter_mut()
+            .find_map(|element| match element {
+                Element::Node(node) if !node.tags.is_empty() => Some(node),
+                _ => None,
+            })
+            .unwrap();
+        tagged.tags[0].value.push('!');
+        write(output, &changed);
+        assert_ne!(content_hash(output).unwrap(), hash);
+        std::fs::remove_file(output).unwrap();
+    }
+}
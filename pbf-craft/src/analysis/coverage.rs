@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::Path;
+
+use geo::{BoundingRect, ConcaveHull, ConvexHull, Coord, MultiPoint, Polygon, Rect};
+
+use crate::codecs::block_decorators::PrimitiveReader;
+use crate::readers::PbfReader;
+
+const NANODEGREES: f64 = 1_000_000_000.0;
+
+/// The shape computed by `coverage`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoverageShape {
+    /// The bounding box of all nodes.
+    BoundingBox,
+    /// The convex hull of all nodes. It overstates the coverage of non-convex areas.
+    ConvexHull,
+    /// A concave hull following the outline of the nodes more closely.
+    ///
+    /// The nodes are first snapped to a grid whose cells are `resolution` degrees wide, which
+    /// keeps the number of points manageable for large files. `concavity` controls how closely
+    /// the hull follows the points: the lower, the more concave the hull; 2.0 is a good start.
+    ConcaveHull { concavity: f64, resolution: f64 },
+}
+
+/// Computes the area covered by the nodes of a PBF file, in degrees.
+///
+/// The nodes of the blobs are collected in parallel. Fails if the file contains no nodes.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::{coverage, CoverageShape};
+///
+/// let shape = CoverageShape::ConcaveHull {
+///     concavity: 2.0,
+///     resolution: 0.01,
+/// };
+/// let polygon = coverage("resources/andorra-latest.osm.pbf", shape).unwrap();
+/// assert!(!polygon.exterior().0.is_empty());
+/// ```
+pub fn coverage<P: AsRef<Path>>(path: P, shape: CoverageShape) -> anyhow::Result<Polygon<f64>> {
+    let reader = PbfReader::from_path(path)?;
+    let polygon = match shape {
+        CoverageShape::BoundingBox => bounding_box(reader)?.map(Polygon::from),
+        CoverageShape::ConvexHull => {
+            let hull = convex_hull(reader)?;
+            (!hull.is_empty()).then(|| MultiPoint::from(hull).convex_hull())
+        }
+        CoverageShape::ConcaveHull {
+            concavity,
+            resolution,
+        } => {
+            let cells = grid_cells(reader, resolution)?;
+            (!cells.is_empty()).then(|| {
+                let points: Vec<Coord<f64>> = cells
+                    .into_iter()
+                    .map(|(x, y)| Coord {
+                        x: (x as f64 + 0.5) * resolution,
+                        y: (y as f64 + 0.5) * resolution,
+                    })
+                    .collect();
+                MultiPoint::from(points).concave_hull(concavity)
+            })
+        }
+    };
+    polygon.ok_or_else(|| anyhow!("The file contains no nodes"))
+}
+
+fn node_coords(reader: &PrimitiveReader) -> anyhow::Result<Vec<Coord<f64>>> {
+    Ok(reader
+        .get_nodes()?
+        .into_iter()
+        .map(|node| Coord {
+            x: node.longitude as f64 / NANODEGREES,
+            y: node.latitude as f64 / NANODEGREES,
+        })
+        .collect())
+}
+
+fn bounding_box<R: Read + Send>(reader: PbfReader<R>) -> anyhow::Result<Option<Rect>> {
+    let merge = |a: Option<Rect>, b: Option<Rect>| match (a, b) {
+        (Some(a), Some(b)) => Some(Rect::new(
+            Coord {
+                x: a.min().x.min(b.min().x),
+                y: a.min().y.min(b.min().y),
+            },
+            Coord {
+                x: a.max().x.max(b.max().x),
+                y: a.max().y.max(b.max().y),
+            },
+        )),
+        (a, b) => a.or(b),
+    };
+    reader.par_fold_blocks(
+        |block| Ok(MultiPoint::from(node_coords(&block)?).bounding_rect()),
+        merge,
+        || None,
+    )
+}
+
+/// Returns the vertices of the convex hull of all nodes.
+fn convex_hull<R: Read + Send>(reader: PbfReader<R>) -> anyhow::Result<Vec<Coord>> {
+    let hull_vertices = |points: Vec<Coord>| -> Vec<Coord> {
+        if points.is_empty() {
+            return points;
+        }
+        MultiPoint::from(points).convex_hull().exterior().0.clone()
+    };
+    reader.par_fold_blocks(
+        |block| Ok(hull_vertices(node_coords(&block)?)),
+        |mut a, b| {
+            a.extend(b);
+            hull_vertices(a)
+        },
+        Vec::new,
+    )
+}
+
+fn grid_cells<R: Read + Send>(
+    reader: PbfReader<R>,
+    resolution: f64,
+) -> anyhow::Result<HashSet<(i64, i64)>> {
+    reader.par_fold_blocks(
+        |block| {
+            Ok(node_coords(&block)?
+                .into_iter()
+                .map(|coord| {
+                    (
+                        (coord.x / resolution).floor() as i64,
+                        (coord.y / resolution).floor() as i64,
+                    )
+                })
+                .collect())
+        },
+        |mut a, b| {
+            a.extend(b);
+            a
+        },
+        HashSet::new,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{Area, Contains, Point};
+
+    #[test]
+    fn test_coverage() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let bbox = coverage(path, CoverageShape::BoundingBox).unwrap();
+        let convex = coverage(path, CoverageShape::ConvexHull).unwrap();
+        let concave = coverage(
+            path,
+            CoverageShape::ConcaveHull {
+                concavity: 2.0,
+                resolution: 0.01,
+            },
+        )
+        .unwrap();
+
+        // Andorra la Vella
+        let capital = Point::new(1.5218, 42.5063);
+        for polygon in [&bbox, &convex, &concave] {
+            assert!(polygon.contains(&capital));
+        }
+        assert!(convex.unsigned_area() <= bbox.unsigned_area());
+        assert!(concave.unsigned_area() < convex.unsigned_area());
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{with_version, Element, ElementType, Node, OsmChange, Tag};
+use crate::readers::ElementSource;
+
+/// Nodes found to be duplicates of each other.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateNodeGroup {
+    /// The IDs of the duplicate nodes in ascending order. The first one is kept when merging.
+    pub node_ids: Vec<i64>,
+    /// The location of the first node in nanodegrees.
+    pub latitude: i64,
+    pub longitude: i64,
+}
+
+/// The result of `DuplicateNodeDetector`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DuplicateNodeReport {
+    pub groups: Vec<DuplicateNodeGroup>,
+}
+
+impl DuplicateNodeReport {
+    /// Returns the number of nodes that would be removed by merging all groups.
+    pub fn duplicate_count(&self) -> usize {
+        self.groups
+            .iter()
+            .map(|group| group.node_ids.len() - 1)
+            .sum()
+    }
+
+    /// Builds the changes that merge each group into its first node.
+    ///
+    /// The source must contain the same data that was analyzed. The other nodes of each group
+    /// are deleted, and ways and relations referencing them are modified to reference the kept
+    /// node instead. Consecutive references to the same node, which the merge may produce in
+    /// ways, are collapsed.
+    pub fn to_change<S: ElementSource>(&self, mut source: S) -> anyhow::Result<OsmChange> {
+        let mut replacements: HashMap<i64, i64> = HashMap::new();
+        for group in &self.groups {
+            for node_id in &group.node_ids[1..] {
+                replacements.insert(*node_id, group.node_ids[0]);
+            }
+        }
+
+        let mut change = OsmChange::default();
+        while let Some(element) = source.next_element()? {
+            match element {
+                Element::Node(node) => {
+                    if replacements.contains_key(&node.id) {
+                        let version = node.version + 1;
+                        change
+                            .delete
+                            .push(with_version(Element::Node(node), version, false));
+                    }
+                }
+                Element::Way(mut way) => {
+                    if way
+                        .way_nodes
+                        .iter()
+                        .any(|wn| replacements.contains_key(&wn.id))
+                    {
+                        for way_node in way.way_nodes.iter_mut() {
+                            if let Some(kept_id) = replacements.get(&way_node.id) {
+                                way_node.id = *kept_id;
+                            }
+                        }
+                        way.way_nodes.dedup_by_key(|wn| wn.id);
+                        let version = way.version + 1;
+                        change
+                            .modify
+                            .push(with_version(Element::Way(way), version, true));
+                    }
+                }
+                Element::Relation(mut relation) => {
+                    let mut is_modified = false;
+                    for member in relation.members.iter_mut() {
+                        if member.member_type == ElementType::Node {
+                            if let Some(kept_id) = replacements.get(&member.member_id) {
+                                member.member_id = *kept_id;
+                                is_modified = true;
+                            }
+                        }
+                    }
+                    if is_modified {
+                        let version = relation.version + 1;
+                        change.modify.push(with_version(
+                            Element::Relation(relation),
+                            version,
+                            true,
+                        ));
+                    }
+                }
+            }
+        }
+        // dependents must be deleted before the elements they refer to
+        change.delete.reverse();
+        Ok(change)
+    }
+}
+
+/// Detects nodes sharing the same location, optionally also requiring the same tags.
+///
+/// Nodes are hashed into a grid with cells of the size of the tolerance, so each node is only
+/// compared with the nodes of its own and the neighbouring cells. Two nodes are duplicates if
+/// both their latitudes and their longitudes differ by at most the tolerance. Duplicates are
+/// grouped transitively.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::DuplicateNodeDetector;
+/// use pbf_craft::readers::IterableReader;
+///
+/// let reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let report = DuplicateNodeDetector::new(0).detect(reader).unwrap();
+/// println!("{} duplicate nodes", report.duplicate_count());
+/// ```
+pub struct DuplicateNodeDetector {
+    tolerance: i64,
+    compare_tags: bool,
+    grid: HashMap<(i64, i64), Vec<NodeEntry>>,
+    parents: HashMap<i64, i64>,
+}
+
+struct NodeEntry {
+    id: i64,
+    latitude: i64,
+    longitude: i64,
+    tags_hash: u64,
+}
+
+impl DuplicateNodeDetector {
+    /// Creates a new detector. The tolerance is given in nanodegrees; 0 only matches nodes at
+    /// exactly the same location.
+    pub fn new(tolerance: i64) -> Self {
+        Self {
+            tolerance: tolerance.max(0),
+            compare_tags: true,
+            grid: HashMap::new(),
+            parents: HashMap::new(),
+        }
+    }
+
+    /// Sets whether duplicates must have the same tags. Defaults to `true`.
+    pub fn set_compare_tags(&mut self, compare_tags: bool) {
+        self.compare_tags = compare_tags;
+    }
+
+    fn cell_of(&self, latitude: i64, longitude: i64) -> (i64, i64) {
+        let cell_size = self.tolerance.max(1);
+        (
+            latitude.div_euclid(cell_size),
+            longitude.div_euclid(cell_size),
+        )
+    }
+
+    fn find(&mut self, id: i64) -> i64 {
+        let mut root = id;
+        while let Some(parent) = self.parents.get(&root) {
+            if *parent == root {
+                break;
+            }
+            root = *parent;
+        }
+        // compress the path
+        let mut current = id;
+        while current != root {
+            let parent = self.parents.insert(current, root).unwrap_or(root);
+            current = parent;
+        }
+        root
+    }
+
+    fn union(&mut self, a: i64, b: i64) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            let (root, child) = if root_a < root_b {
+                (root_a, root_b)
+            } else {
+                (root_b, root_a)
+            };
+            self.parents.insert(child, root);
+            self.parents.insert(root, root);
+        }
+    }
+
+    /// Adds a node to the analysis.
+    pub fn add_node(&mut self, node: &Node) {
+        let entry = NodeEntry {
+            id: node.id,
+            latitude: node.latitude,
+            longitude: node.longitude,
+            tags_hash: if self.compare_tags {
+                hash_tags(&node.tags)
+            } else {
+                0
+            },
+        };
+        let (cell_lat, cell_lon) = self.cell_of(node.latitude, node.longitude);
+        let mut matches = Vec::new();
+        for d_lat in -1..=1 {
+            for d_lon in -1..=1 {
+                if let Some(entries) = self.grid.get(&(cell_lat + d_lat, cell_lon + d_lon)) {
+                    for other in entries {
+                        if other.tags_hash == entry.tags_hash
+                            && (other.latitude - entry.latitude).abs() <= self.tolerance
+                            && (other.longitude - entry.longitude).abs() <= self.tolerance
+                        {
+                            matches.push(other.id);
+                        }
+                    }
+                }
+            }
+        }
+        for other_id in matches {
+            self.union(other_id, entry.id);
+        }
+        self.grid
+            .entry((cell_lat, cell_lon))
+            .or_default()
+            .push(entry);
+    }
+
+    /// Finishes the analysis and returns the groups of duplicates, sorted by their first node.
+    pub fn finish(mut self) -> DuplicateNodeReport {
+        let ids: Vec<i64> = self.parents.keys().copied().collect();
+        let mut groups: BTreeMap<i64, Vec<i64>> = BTreeMap::new();
+        for id in ids {
+            let root = self.find(id);
+            groups.entry(root).or_default().push(id);
+        }
+        let mut locations: HashMap<i64, (i64, i64)> = HashMap::new();
+        for entries in self.grid.values() {
+            for entry in entries {
+                if groups.contains_key(&entry.id) {
+                    locations.insert(entry.id, (entry.latitude, entry.longitude));
+                }
+            }
+        }
+        let groups = groups
+            .into_iter()
+            .map(|(root, mut node_ids)| {
+                node_ids.sort_unstable();
+                let (latitude, longitude) = locations[&root];
+                DuplicateNodeGroup {
+                    node_ids,
+                    latitude,
+                    longitude,
+                }
+            })
+            .collect();
+        DuplicateNodeReport { groups }
+    }
+
+    /// Runs the analysis over all nodes of a source.
+    pub fn detect<S: ElementSource>(
+        mut self,
+        mut source: S,
+    ) -> anyhow::Result<DuplicateNodeReport> {
+        while let Some(element) = source.next_element()? {
+            if let Element::Node(node) = element {
+                self.add_node(&node);
+            }
+        }
+        Ok(self.finish())
+    }
+}
+
+fn hash_tags(tags: &[Tag]) -> u64 {
+    let mut pairs: Vec<(&str, &str)> = tags
+        .iter()
+        .map(|tag| (tag.key.as_str(), tag.value.as_str()))
+        .collect();
+    pairs.sort_unstable();
+    let mut hasher = DefaultHasher::new();
+    pairs.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Way, WayNode};
+
+    fn node(id: i64, latitude: i64, longitude: i64, tags: &[(&str, &str)]) -> Element {
+        Element::Node(Node {
+            id,
+            latitude,
+            longitude,
+            version: 1,
+            visible: true,
+            tags: tags
+                .iter()
+                .map(|(k, v)| Tag {
+                    key: k.to_string(),
+                    value: v.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_detect_and_merge() {
+        let elements = vec![
+            node(1, 100, 100, &[("amenity", "bench")]),
+            node(2, 100, 100, &[("amenity", "bench")]),
+            node(3, 105, 98, &[("amenity", "bench")]),
+            node(4, 100, 100, &[("amenity", "cafe")]),
+            node(5, 500, 500, &[]),
+            Element::Way(Way {
+                id: 10,
+                version: 1,
+                way_nodes: vec![
+                    WayNode::new_without_coords(1),
+                    WayNode::new_without_coords(2),
+                    WayNode::new_without_coords(5),
+                ],
+                ..Default::default()
+            }),
+        ];
+
+        let report = DuplicateNodeDetector::new(0)
+            .detect(elements.clone().into_iter())
+            .unwrap();
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].node_ids, vec![1, 2]);
+
+        let report = DuplicateNodeDetector::new(10)
+            .detect(elements.clone().into_iter())
+            .unwrap();
+        assert_eq!(report.groups[0].node_ids, vec![1, 2, 3]);
+
+        let mut detector = DuplicateNodeDetector::new(0);
+        detector.set_compare_tags(false);
+        let report = detector.detect(elements.clone().into_iter()).unwrap();
+        assert_eq!(report.groups[0].node_ids, vec![1, 2, 4]);
+        assert_eq!(report.duplicate_count(), 2);
+
+        let change = report.to_change(elements.into_iter()).unwrap();
+        assert_eq!(change.delete.len(), 2);
+        let Element::Way(way) = &change.modify[0] else {
+            panic!("expected a modified way");
+        };
+        let ids: Vec<i64> = way.way_nodes.iter().map(|wn| wn.id).collect();
+        assert_eq!(ids, vec![1, 5]);
+        assert_eq!(way.version, 2);
+    }
+}
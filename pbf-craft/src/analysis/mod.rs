@@ -0,0 +1,27 @@
+#[cfg(feature = "fs")]
+mod admin_boundaries;
+#[cfg(feature = "fs")]
+mod coastline;
+#[cfg(feature = "fs")]
+mod content_hash;
+#[cfg(all(feature = "fs", feature = "parallel"))]
+mod coverage;
+mod duplicate_nodes;
+mod orphan_nodes;
+#[cfg(all(feature = "fs", feature = "parallel"))]
+mod stats;
+
+#[cfg(feature = "fs")]
+pub use admin_boundaries::{AdminBoundary, AdminHierarchy};
+#[cfg(feature = "fs")]
+pub use coastline::{Coastline, CoastlineChain, CoastlineGap};
+#[cfg(all(feature = "fs", feature = "parallel"))]
+pub use content_hash::content_hash;
+#[cfg(feature = "fs")]
+pub use content_hash::ContentHasher;
+#[cfg(all(feature = "fs", feature = "parallel"))]
+pub use coverage::{coverage, CoverageShape};
+pub use duplicate_nodes::{DuplicateNodeDetector, DuplicateNodeGroup, DuplicateNodeReport};
+pub use orphan_nodes::OrphanNodes;
+#[cfg(all(feature = "fs", feature = "parallel"))]
+pub use stats::{stats, stats_of, FileStats};
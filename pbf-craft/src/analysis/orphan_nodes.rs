@@ -0,0 +1,72 @@
+use crate::models::{Element, ElementType, Node};
+use crate::readers::ElementSource;
+use crate::utils::IdSet;
+
+/// Finds orphan nodes, i.e. untagged nodes which are neither part of a way nor a member of a
+/// relation.
+///
+/// Since ways and relations come after the nodes, finding orphans takes two passes: the first
+/// one, `OrphanNodes::scan`, marks the referenced nodes, and the second one checks each node
+/// with `is_orphan`, e.g. through `writers::OrphanPruningSink`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::OrphanNodes;
+/// use pbf_craft::readers::IterableReader;
+///
+/// let path = "resources/andorra-latest.osm.pbf";
+/// let orphans = OrphanNodes::scan(IterableReader::from_path(path).unwrap()).unwrap();
+/// let orphan_ids = orphans.find(IterableReader::from_path(path).unwrap()).unwrap();
+/// ```
+pub struct OrphanNodes {
+    referenced: IdSet,
+}
+
+impl OrphanNodes {
+    /// Marks all nodes referenced by the ways and relations of a source.
+    pub fn scan<S: ElementSource>(mut source: S) -> anyhow::Result<Self> {
+        let mut referenced = IdSet::new();
+        while let Some(element) = source.next_element()? {
+            match element {
+                Element::Node(_) => {}
+                Element::Way(way) => {
+                    for way_node in &way.way_nodes {
+                        referenced.insert(way_node.id);
+                    }
+                }
+                Element::Relation(relation) => {
+                    for member in &relation.members {
+                        if member.member_type == ElementType::Node {
+                            referenced.insert(member.member_id);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Self { referenced })
+    }
+
+    /// Returns the number of distinct nodes referenced by ways and relations.
+    pub fn referenced_count(&self) -> u64 {
+        self.referenced.len()
+    }
+
+    /// Checks whether a node is untagged and not referenced.
+    pub fn is_orphan(&self, node: &Node) -> bool {
+        node.tags.is_empty() && !self.referenced.contains(node.id)
+    }
+
+    /// Returns the IDs of the orphan nodes of a source in the order they are read.
+    pub fn find<S: ElementSource>(&self, mut source: S) -> anyhow::Result<Vec<i64>> {
+        let mut orphans = Vec::new();
+        while let Some(element) = source.next_element()? {
+            if let Element::Node(node) = element {
+                if self.is_orphan(&node) {
+                    orphans.push(node.id);
+                }
+            }
+        }
+        Ok(orphans)
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Bound, Element, ElementTypeSet, Tag};
+use crate::readers::PbfReader;
+
+/// The number of elements and the tag usage of a PBF file, as computed by `stats`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileStats {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+    /// The bounding box of the nodes, in nanodegrees, or `None` if the file has no nodes.
+    pub bbox: Option<Bound>,
+    /// The number of elements using each tag key.
+    pub keys: HashMap<String, u64>,
+    /// The number of elements using each value, by tag key.
+    pub values: HashMap<String, HashMap<String, u64>>,
+}
+
+impl FileStats {
+    /// Returns the total number of elements.
+    pub fn total(&self) -> u64 {
+        self.nodes + self.ways + self.relations
+    }
+
+    /// Returns the `limit` most used tag keys and their counts, the most used first.
+    pub fn top_keys(&self, limit: usize) -> Vec<(&str, u64)> {
+        top(&self.keys, limit)
+    }
+
+    /// Returns the `limit` most used values of a tag key and their counts, the most used first.
+    pub fn top_values(&self, key: &str, limit: usize) -> Vec<(&str, u64)> {
+        self.values
+            .get(key)
+            .map(|values| top(values, limit))
+            .unwrap_or_default()
+    }
+
+    fn add_tags(&mut self, tags: &[Tag]) {
+        for tag in tags {
+            *self.keys.entry(tag.key.clone()).or_default() += 1;
+            *self
+                .values
+                .entry(tag.key.clone())
+                .or_default()
+                .entry(tag.value.clone())
+                .or_default() += 1;
+        }
+    }
+
+    fn add_location(&mut self, latitude: i64, longitude: i64) {
+        let bbox = self.bbox.get_or_insert(Bound {
+            left: longitude,
+            right: longitude,
+            top: latitude,
+            bottom: latitude,
+        });
+        bbox.left = bbox.left.min(longitude);
+        bbox.right = bbox.right.max(longitude);
+        bbox.top = bbox.top.max(latitude);
+        bbox.bottom = bbox.bottom.min(latitude);
+    }
+
+    fn add(&mut self, element: &Element) {
+        match element {
+            Element::Node(node) => {
+                self.nodes += 1;
+                self.add_location(node.latitude, node.longitude);
+                self.add_tags(&node.tags);
+            }
+            Element::Way(way) => {
+                self.ways += 1;
+                self.add_tags(&way.tags);
+            }
+            Element::Relation(relation) => {
+                self.relations += 1;
+                self.add_tags(&relation.tags);
+            }
+        }
+    }
+
+    fn merge(mut self, other: FileStats) -> FileStats {
+        self.nodes += other.nodes;
+        self.ways += other.ways;
+        self.relations += other.relations;
+        if let Some(bbox) = other.bbox {
+            self.add_location(bbox.top, bbox.left);
+            self.add_location(bbox.bottom, bbox.right);
+        }
+        for (key, count) in other.keys {
+            *self.keys.entry(key).or_default() += count;
+        }
+        for (key, values) in other.values {
+            let merged = self.values.entry(key).or_default();
+            for (value, count) in values {
+                *merged.entry(value).or_default() += count;
+            }
+        }
+        self
+    }
+}
+
+/// Sorts counts descending, breaking ties by name, and keeps the first `limit`.
+fn top(counts: &HashMap<String, u64>, limit: usize) -> Vec<(&str, u64)> {
+    let mut counts: Vec<(&str, u64)> = counts
+        .iter()
+        .map(|(name, count)| (name.as_str(), *count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts.truncate(limit);
+    counts
+}
+
+/// Counts the elements of a PBF file, the bounding box of its nodes and the usage of every
+/// tag key and value.
+///
+/// The blobs are processed in parallel. The counts of all distinct values are kept in memory,
+/// which can be large for keys such as `name` in big files.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::stats;
+///
+/// let stats = stats("resources/andorra-latest.osm.pbf").unwrap();
+/// assert!(stats.nodes > 0);
+/// for (key, count) in stats.top_keys(10) {
+///     println!("{}: {}", key, count);
+/// }
+/// ```
+pub fn stats<P: AsRef<Path>>(path: P) -> anyhow::Result<FileStats> {
+    stats_of(path, ElementTypeSet::ALL)
+}
+
+/// Computes the stats of the elements of the given types only, like `stats`. The elements of
+/// the other types aren't decoded, so e.g. the tag usage of ways is computed without the cost
+/// of decoding the nodes.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::stats_of;
+/// use pbf_craft::models::ElementTypeSet;
+///
+/// let path = "resources/andorra-latest.osm.pbf";
+/// let stats = stats_of(path, ElementTypeSet::WAYS | ElementTypeSet::RELATIONS).unwrap();
+/// assert_eq!(stats.nodes, 0);
+/// assert!(stats.ways > 0);
+/// ```
+pub fn stats_of<P: AsRef<Path>>(path: P, types: ElementTypeSet) -> anyhow::Result<FileStats> {
+    PbfReader::from_path(path)?.par_fold_blocks(
+        |block| {
+            let mut stats = FileStats::default();
+            block.for_each_element_of(types, |element| stats.add(&element))?;
+            Ok(stats)
+        },
+        FileStats::merge,
+        FileStats::default,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writers::{CountingSink, ElementSink};
+
+    #[test]
+    fn test_stats() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let stats = stats(path).unwrap();
+
+        let mut counts = CountingSink::new();
+        let mut highways = 0;
+        PbfReader::from_path(path)
+            .unwrap()
+            .read(|_, element| {
+                if let Some(element) = element {
+                    let tags = match &element {
+                        Element::Node(node) => &node.tags,
+                        Element::Way(way) => &way.tags,
+                        Element::Relation(relation) => &relation.tags,
+                    };
+                    highways += tags.iter().filter(|tag| tag.key == "highway").count() as u64;
+                    counts.write(element).unwrap();
+                }
+            })
+            .unwrap();
+        assert_eq!(
+            (stats.nodes, stats.ways, stats.relations),
+            (counts.nodes, counts.ways, counts.relations)
+        );
+        assert_eq!(stats.keys["highway"], highways);
+
+        let bbox = stats.bbox.as_ref().unwrap();
+        assert!(bbox.left < 1_600_000_000 && bbox.right > 1_500_000_000);
+        assert!(bbox.bottom < 42_500_000_000 && bbox.top > 42_500_000_000);
+
+        let top_keys = stats.top_keys(5);
+        assert_eq!(top_keys.len(), 5);
+        assert!(top_keys.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        let (key, count) = top_keys[0];
+        let values = stats.top_values(key, usize::MAX);
+        assert_eq!(values.iter().map(|(_, count)| count).sum::<u64>(), count);
+
+        let ways = stats_of(path, ElementTypeSet::WAYS).unwrap();
+        assert_eq!((ways.nodes, ways.ways, ways.relations), (0, stats.ways, 0));
+        assert_eq!(ways.bbox, None);
+    }
+}
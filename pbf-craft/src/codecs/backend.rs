@@ -0,0 +1,62 @@
+//! The protobuf backend parsing data blocks, selected at compile time with the `prost` feature.
+//!
+//! The header blocks and the blocks written by `PbfWriter` always use the rust-protobuf messages.
+
+use crate::models::ElementType;
+
+#[cfg(not(feature = "prost"))]
+use crate::proto::osmformat::Relation_MemberType as MemberType;
+#[cfg(not(feature = "prost"))]
+pub use crate::proto::osmformat::{
+    DenseInfo, DenseNodes, Info, Node, PrimitiveBlock, Relation, Way,
+};
+
+#[cfg(feature = "prost")]
+use super::prost_osmformat::MemberType;
+#[cfg(feature = "prost")]
+pub use super::prost_osmformat::{
+    DenseInfo, DenseNodes, Info, Node, PrimitiveBlock, Relation, Way,
+};
+
+/// Parses the uncompressed data of a block.
+#[cfg(not(feature = "prost"))]
+pub fn parse_block(data: &[u8]) -> anyhow::Result<PrimitiveBlock> {
+    Ok(protobuf::Message::parse_from_bytes(data)?)
+}
+
+#[cfg(feature = "prost")]
+pub fn parse_block(data: &[u8]) -> anyhow::Result<PrimitiveBlock> {
+    Ok(prost::Message::decode(data)?)
+}
+
+/// Returns the type of a relation member.
+#[cfg(not(feature = "prost"))]
+pub fn member_type(member_type: &MemberType) -> anyhow::Result<ElementType> {
+    Ok(match member_type {
+        MemberType::NODE => ElementType::Node,
+        MemberType::WAY => ElementType::Way,
+        MemberType::RELATION => ElementType::Relation,
+    })
+}
+
+#[cfg(feature = "prost")]
+pub fn member_type(member_type: &i32) -> anyhow::Result<ElementType> {
+    match MemberType::try_from(*member_type) {
+        Ok(MemberType::Node) => Ok(ElementType::Node),
+        Ok(MemberType::Way) => Ok(ElementType::Way),
+        Ok(MemberType::Relation) => Ok(ElementType::Relation),
+        Err(_) => bail!("Unknown member type {}", member_type),
+    }
+}
+
+/// Converts a block built by `PrimitiveBuilder` to the message of the backend.
+#[cfg(all(test, not(feature = "prost")))]
+pub fn from_builder(block: crate::proto::osmformat::PrimitiveBlock) -> PrimitiveBlock {
+    block
+}
+
+#[cfg(all(test, feature = "prost"))]
+pub fn from_builder(block: crate::proto::osmformat::PrimitiveBlock) -> PrimitiveBlock {
+    let data = protobuf::Message::write_to_bytes(&block).unwrap();
+    parse_block(&data).unwrap()
+}
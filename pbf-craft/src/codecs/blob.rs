@@ -1,14 +1,65 @@
-use std::fs::File;
-use std::io::{BufReader, Read, Seek};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Seek};
+use std::ops::Range;
 
-use byteorder::{self, ReadBytesExt};
 use flate2::read::ZlibDecoder;
 
+use super::backend::{self, PrimitiveBlock};
+use super::decode_options::{strip_block, DecodeOptions};
 use crate::proto::fileformat::{Blob, BlobHeader};
-use crate::proto::osmformat::{HeaderBlock, PrimitiveBlock};
+use crate::proto::osmformat::HeaderBlock;
 
+/// The maximum size of a blob header allowed by the PBF specification.
+pub const MAX_BLOB_HEADER_SIZE: u64 = 64 * 1024;
+/// The maximum size of a blob, both compressed and uncompressed, allowed by the PBF
+/// specification.
+pub const MAX_BLOB_SIZE: u64 = 32 * 1024 * 1024;
+/// The start of the blob header of a data block: the `type` field with the string `OSMData`.
+const DATA_HEADER_MARKER: &[u8] = b"\x0a\x07OSMData";
+
+/// A typed error of reading PBF data, which can be told apart from other errors with
+/// `anyhow::Error::downcast_ref`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{PbfError, PbfReader};
+///
+/// let data = std::fs::read("resources/andorra-latest.osm.pbf").unwrap();
+/// let mut reader = PbfReader::new(&data[..data.len() / 2]);
+/// let err = reader.read(|_, _| {}).unwrap_err();
+/// match err.downcast_ref::<PbfError>() {
+///     Some(PbfError::Truncated { valid_bytes }) => println!("Only {} bytes are valid", valid_bytes),
+///     _ => panic!("{}", err),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PbfError {
+    /// The data ends in the middle of a blob, e.g. after an interrupted download. The blobs in
+    /// the first `valid_bytes` bytes are complete.
+    Truncated { valid_bytes: u64 },
+}
+
+impl std::fmt::Display for PbfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PbfError::Truncated { valid_bytes } => write!(
+                f,
+                "The PBF data is truncated: the blob at offset {} is incomplete",
+                valid_bytes
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PbfError {}
+
+/// The decoded protobuf message of a blob.
 pub enum DecodedBlob {
+    /// The header block, which comes first in the file.
     OsmHeader(HeaderBlock),
+    /// A block of elements, parsed by the backend selected with the `prost` feature.
     OsmData(PrimitiveBlock),
 }
 
@@ -16,97 +67,428 @@ pub enum DecodedBlob {
 pub struct RawBlob {
     header: BlobHeader,
     raw_blob: Vec<u8>,
+    enforce_size_limits: bool,
 }
 
 impl RawBlob {
+    /// Decodes the blob with all fields, as the header blobs are read by the file readers.
+    #[cfg(any(feature = "fs", test))]
     pub fn decode(&self) -> anyhow::Result<DecodedBlob> {
+        self.decode_with_options(&DecodeOptions::default())
+    }
+
+    /// Decodes the blob, leaving the fields skipped by the options out of data blocks.
+    pub fn decode_with_options(&self, options: &DecodeOptions) -> anyhow::Result<DecodedBlob> {
+        let _span = trace_span!("decode_blob", size = self.raw_blob.len());
         let decoded = match self.header.get_field_type() {
             "OSMHeader" => DecodedBlob::OsmHeader(self.decode_blob()?),
-            "OSMData" => DecodedBlob::OsmData(self.decode_blob()?),
+            "OSMData" if !options.skips_fields() => {
+                DecodedBlob::OsmData(backend::parse_block(&self.decompress_data()?)?)
+            }
+            "OSMData" => {
+                let data = strip_block(&self.decompress_data()?, options)?;
+                DecodedBlob::OsmData(backend::parse_block(&data)?)
+            }
             _ => bail!("Unsupported header type: {}", self.header.get_field_type()),
         };
         Ok(decoded)
     }
 
-    fn decode_blob<M: protobuf::Message>(&self) -> anyhow::Result<M> {
-        let blob: Blob = protobuf::Message::parse_from_bytes(self.raw_blob.as_slice())?;
-        let decoded: M = if blob.has_raw() {
-            protobuf::Message::parse_from_bytes(blob.get_raw())?
+    /// Returns the type of the blob, e.g. `OSMData`.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub fn blob_type(&self) -> &str {
+        self.header.get_field_type()
+    }
+
+    /// Returns the blob message as stored in the file, i.e. still compressed.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub fn data(&self) -> &[u8] {
+        &self.raw_blob
+    }
+
+    /// Returns whether the blob contains a primitive block.
+    pub fn is_osm_data(&self) -> bool {
+        self.header.get_field_type() == "OSMData"
+    }
+
+    /// Returns the uncompressed data of the blob, without parsing it.
+    pub fn decompress_data(&self) -> anyhow::Result<Vec<u8>> {
+        let mut blob: Blob = protobuf::Message::parse_from_bytes(self.raw_blob.as_slice())?;
+        if blob.has_raw() {
+            Ok(blob.take_raw())
         } else if blob.has_zlib_data() {
-            let mut decoder = ZlibDecoder::new(blob.get_zlib_data());
-            protobuf::Message::parse_from_reader(&mut decoder)?
+            self.decompress(ZlibDecoder::new(blob.get_zlib_data()))
+        } else if blob.has_OBSOLETE_bzip2_data() {
+            self.decompress(bzip2_decoder(blob.get_OBSOLETE_bzip2_data())?)
         } else {
             bail!("Unsupported blob data type")
+        }
+    }
+
+    fn decode_blob<M: protobuf::Message>(&self) -> anyhow::Result<M> {
+        let data = self.decompress_data()?;
+        Ok(protobuf::Message::parse_from_bytes(&data)?)
+    }
+
+    /// Decompresses the blob data, stopping at the size limit so that a corrupt or malicious
+    /// blob can't exhaust the memory.
+    fn decompress<D: Read>(&self, decoder: D) -> anyhow::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        if self.enforce_size_limits {
+            decoder.take(MAX_BLOB_SIZE + 1).read_to_end(&mut data)?;
+            if data.len() as u64 > MAX_BLOB_SIZE {
+                bail!(
+                    "The uncompressed blob exceeds the limit of {} bytes",
+                    MAX_BLOB_SIZE
+                );
+            }
+        } else {
+            let mut decoder = decoder;
+            decoder.read_to_end(&mut data)?;
+        }
+        Ok(data)
+    }
+}
+
+/// Decompresses the bzip2 compressed data of blobs written by tools predating zlib
+/// compression.
+#[cfg(feature = "bz2")]
+fn bzip2_decoder(data: &[u8]) -> anyhow::Result<Box<dyn Read + '_>> {
+    Ok(Box::new(bzip2::read::BzDecoder::new(data)))
+}
+
+#[cfg(not(feature = "bz2"))]
+fn bzip2_decoder(_data: &[u8]) -> anyhow::Result<Box<dyn Read + '_>> {
+    bail!("Reading bzip2 compressed blobs requires the bz2 feature")
+}
+
+/// A handler of blobs of a non-standard type, called with their uncompressed data.
+pub type BlobHandler = Box<dyn FnMut(&[u8]) -> anyhow::Result<()> + Send>;
+
+/// A reader into which bytes can be put back, and which can record the bytes read, so that a
+/// corrupt stream can be scanned again from within a broken blob.
+struct PushbackReader<R> {
+    inner: R,
+    pending: VecDeque<u8>,
+    recorded: Option<Vec<u8>>,
+}
+
+impl<R> PushbackReader<R> {
+    /// Puts bytes back, to be read again before the rest of the stream.
+    fn unread(&mut self, bytes: &[u8]) {
+        for &byte in bytes.iter().rev() {
+            self.pending.push_front(byte);
+        }
+    }
+}
+
+impl<R: Read> Read for PushbackReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = if self.pending.is_empty() {
+            self.inner.read(buf)?
+        } else {
+            self.pending.read(buf)?
         };
-        Ok(decoded)
+        if let Some(recorded) = &mut self.recorded {
+            recorded.extend_from_slice(&buf[..n]);
+        }
+        Ok(n)
     }
 }
 
 pub struct BlobReader<R: Read + Send> {
-    reader: R,
+    reader: PushbackReader<R>,
     pub offset: u64,
     pub eof: bool,
+    enforce_size_limits: bool,
+    resync: bool,
+    skipped_ranges: Vec<Range<u64>>,
+    allow_truncation: bool,
+    truncation: Option<u64>,
+    blob_handlers: HashMap<String, BlobHandler>,
 }
 
 impl<R: Read + Send> BlobReader<R> {
     pub fn new(reader: R) -> BlobReader<R> {
         Self {
-            reader,
+            reader: PushbackReader {
+                inner: reader,
+                pending: VecDeque::new(),
+                recorded: None,
+            },
             offset: 0,
             eof: false,
+            enforce_size_limits: true,
+            resync: false,
+            skipped_ranges: Vec::new(),
+            allow_truncation: false,
+            truncation: None,
+            blob_handlers: HashMap::new(),
         }
     }
 
-    fn next_blob(&mut self) -> anyhow::Result<Option<RawBlob>> {
-        let header_size = match self.reader.read_u32::<byteorder::BigEndian>() {
-            Ok(n) => {
-                self.offset += 4;
-                n as u64
+    /// Sets whether blobs exceeding the sizes allowed by the specification are rejected.
+    pub fn set_enforce_size_limits(&mut self, enforce_size_limits: bool) {
+        self.enforce_size_limits = enforce_size_limits;
+    }
+
+    /// Sets whether to scan forward for the next data blob when a blob can't be read, e.g.
+    /// because of a corrupt size, instead of failing.
+    pub fn set_resync(&mut self, resync: bool) {
+        self.resync = resync;
+    }
+
+    /// Returns the byte ranges skipped by resyncing, in the order of the stream.
+    pub fn skipped_ranges(&self) -> &[Range<u64>] {
+        &self.skipped_ranges
+    }
+
+    /// Sets whether data ending in the middle of a blob ends the stream after the last complete
+    /// blob instead of failing with `PbfError::Truncated`.
+    pub fn set_allow_truncation(&mut self, allow_truncation: bool) {
+        self.allow_truncation = allow_truncation;
+    }
+
+    /// Returns the number of valid bytes if the data was found truncated while truncation is
+    /// allowed.
+    pub fn truncation(&self) -> Option<u64> {
+        self.truncation
+    }
+
+    /// Registers a handler for the blobs of a non-standard type. Such blobs are passed to their
+    /// handler and skipped instead of failing the read.
+    pub fn register_blob_handler(&mut self, blob_type: &str, handler: BlobHandler) {
+        self.blob_handlers.insert(blob_type.to_string(), handler);
+    }
+
+    /// Reads the next blob, passing blobs of a non-standard type to their handler if one is
+    /// registered. Returns `Ok(None)` at the end of the stream.
+    pub(crate) fn next_blob(&mut self) -> anyhow::Result<Option<RawBlob>> {
+        loop {
+            let Some(raw_blob) = self.next_raw_blob()? else {
+                return Ok(None);
+            };
+            let blob_type = raw_blob.header.get_field_type();
+            if matches!(blob_type, "OSMHeader" | "OSMData") {
+                return Ok(Some(raw_blob));
+            }
+            match self.blob_handlers.get_mut(blob_type) {
+                Some(handler) => {
+                    debug!("Passing a {} blob to its handler", blob_type);
+                    handler(&raw_blob.decompress_data()?)?;
+                }
+                None => return Ok(Some(raw_blob)),
+            }
+        }
+    }
+
+    fn next_raw_blob(&mut self) -> anyhow::Result<Option<RawBlob>> {
+        let result = match self.resync {
+            true => self.resync_raw_blob(),
+            false => self.read_raw_blob(),
+        };
+        match result {
+            Err(err) if self.allow_truncation => match err.downcast_ref::<PbfError>() {
+                Some(&PbfError::Truncated { valid_bytes }) => {
+                    warn!(
+                        "Ending the read at the truncated blob at offset {}",
+                        valid_bytes
+                    );
+                    self.truncation = Some(valid_bytes);
+                    self.eof = true;
+                    Ok(None)
+                }
+                _ => Err(err),
+            },
+            result => result,
+        }
+    }
+
+    /// Reads the next blob, scanning forward for the next data blob while the blobs can't be
+    /// read.
+    fn resync_raw_blob(&mut self) -> anyhow::Result<Option<RawBlob>> {
+        loop {
+            let start = self.offset;
+            self.reader.recorded = Some(Vec::new());
+            let result = self.read_raw_blob();
+            let recorded = self.reader.recorded.take().unwrap_or_default();
+            let err = match result {
+                Ok(Some(raw_blob)) if !self.is_known_type(raw_blob.header.get_field_type()) => {
+                    anyhow!("Unknown blob type {:?}", raw_blob.header.get_field_type())
+                }
+                Ok(raw_blob) => return Ok(raw_blob),
+                // Nothing was read, so the stream itself failed
+                Err(err) if recorded.is_empty() => return Err(err),
+                Err(err) => err,
+            };
+            warn!("Resyncing after the blob at offset {}: {:#}", start, err);
+            // Scan again from the byte after the start of the broken blob
+            self.reader.unread(&recorded[1..]);
+            self.offset = start + 1;
+            let found = self.scan_to_data_header()?;
+            match self.skipped_ranges.last_mut() {
+                // A blob found by a previous scan was broken as well
+                Some(range) if range.end == start => range.end = self.offset,
+                _ => self.skipped_ranges.push(start..self.offset),
             }
-            Err(ref err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+            if !found {
                 self.eof = true;
                 return Ok(None);
             }
+        }
+    }
+
+    /// Returns whether blobs of a type can be read, as the types found in a corrupt blob are
+    /// unknown.
+    fn is_known_type(&self, blob_type: &str) -> bool {
+        matches!(blob_type, "OSMHeader" | "OSMData") || self.blob_handlers.contains_key(blob_type)
+    }
+
+    /// Scans forward for the size and start of a plausible blob header of a data block, and
+    /// puts them back to be read next. Returns `false` if the end of the stream is reached
+    /// first.
+    fn scan_to_data_header(&mut self) -> anyhow::Result<bool> {
+        let window_size = 4 + DATA_HEADER_MARKER.len();
+        let mut window: VecDeque<u8> = VecDeque::with_capacity(window_size + 1);
+        let mut byte = [0u8];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => {
+                    self.offset += window.len() as u64;
+                    return Ok(false);
+                }
+                Ok(_) => window.push_back(byte[0]),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => bail!(err),
+            }
+            if window.len() > window_size {
+                window.pop_front();
+                self.offset += 1;
+            }
+            if window.len() == window_size && window.range(4..).eq(DATA_HEADER_MARKER) {
+                let header_size = u32::from_be_bytes([window[0], window[1], window[2], window[3]]);
+                let plausible = (DATA_HEADER_MARKER.len() as u64..=MAX_BLOB_HEADER_SIZE)
+                    .contains(&(header_size as u64));
+                if plausible {
+                    self.reader.unread(window.make_contiguous());
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    fn read_raw_blob(&mut self) -> anyhow::Result<Option<RawBlob>> {
+        let start = self.offset;
+        let mut size_bytes = [0u8; 4];
+        let header_size = match self.read_up_to(&mut size_bytes) {
+            Ok(0) => {
+                self.eof = true;
+                return Ok(None);
+            }
+            Ok(4) => {
+                self.offset += 4;
+                u32::from_be_bytes(size_bytes) as u64
+            }
+            Ok(_) => return Err(PbfError::Truncated { valid_bytes: start }.into()),
             Err(_) => {
                 bail!("Unable to get next blob from PBF stream.");
             }
         };
 
-        let header = self.read_blob_header(header_size)?;
-        let raw_blob = self.read_blob(&header)?;
-        Ok(Some(RawBlob { header, raw_blob }))
+        let header = self.read_blob_header(header_size, start)?;
+        let raw_blob = self.read_blob(&header, start)?;
+        Ok(Some(RawBlob {
+            header,
+            raw_blob,
+            enforce_size_limits: self.enforce_size_limits,
+        }))
+    }
+
+    /// Reads until the buffer is full or the stream ends, returning the number of bytes read.
+    fn read_up_to(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(filled)
     }
 
-    fn read_blob_header(&mut self, header_size: u64) -> anyhow::Result<BlobHeader> {
-        let header: BlobHeader =
-            protobuf::Message::parse_from_reader(&mut self.reader.by_ref().take(header_size))?;
+    fn read_blob_header(&mut self, header_size: u64, start: u64) -> anyhow::Result<BlobHeader> {
+        if self.enforce_size_limits && header_size > MAX_BLOB_HEADER_SIZE {
+            bail!(
+                "The blob header at offset {} has a size of {} bytes, exceeding the limit of {} bytes",
+                self.offset - 4,
+                header_size,
+                MAX_BLOB_HEADER_SIZE
+            );
+        }
+        let mut bytes = Vec::with_capacity(header_size.min(MAX_BLOB_HEADER_SIZE) as usize);
+        self.reader
+            .by_ref()
+            .take(header_size)
+            .read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < header_size {
+            return Err(PbfError::Truncated { valid_bytes: start }.into());
+        }
+        let header: BlobHeader = protobuf::Message::parse_from_bytes(&bytes)?;
         self.offset += header_size;
         Ok(header)
     }
 
-    fn read_blob(&mut self, header: &BlobHeader) -> anyhow::Result<Vec<u8>> {
-        let data_size = header.get_datasize() as usize;
-        let mut bytes: Vec<u8> = Vec::with_capacity(data_size);
-        let mut r = self.reader.by_ref().take(data_size as u64);
+    fn read_blob(&mut self, header: &BlobHeader, start: u64) -> anyhow::Result<Vec<u8>> {
+        let Ok(data_size) = u64::try_from(header.get_datasize()) else {
+            bail!(
+                "The blob at offset {} has a negative size: {}",
+                self.offset,
+                header.get_datasize()
+            );
+        };
+        if self.enforce_size_limits && data_size > MAX_BLOB_SIZE {
+            bail!(
+                "The blob at offset {} has a size of {} bytes, exceeding the limit of {} bytes",
+                self.offset,
+                data_size,
+                MAX_BLOB_SIZE
+            );
+        }
+        // The buffer grows with the data actually read, so a corrupt size can't trigger a
+        // huge allocation up front
+        let mut bytes: Vec<u8> = Vec::with_capacity(data_size.min(MAX_BLOB_SIZE) as usize);
+        let mut r = self.reader.by_ref().take(data_size);
         match r.read_to_end(&mut bytes) {
-            Ok(_) => {
-                self.offset += data_size as u64;
+            Ok(_) if bytes.len() as u64 == data_size => {
+                self.offset += data_size;
                 Ok(bytes)
             }
+            Ok(_) => Err(
+                anyhow::Error::new(PbfError::Truncated { valid_bytes: start }).context(format!(
+                    "The blob at offset {} is truncated: expected {} bytes, got {}",
+                    self.offset,
+                    data_size,
+                    bytes.len()
+                )),
+            ),
             Err(e) => bail!(e),
         }
     }
 }
 
-impl BlobReader<BufReader<File>> {
+impl<R: Read + Seek + Send> BlobReader<R> {
     pub fn seek(&mut self, offset: u64) -> anyhow::Result<()> {
-        self.reader.seek(std::io::SeekFrom::Start(offset))?;
+        self.reader.inner.seek(std::io::SeekFrom::Start(offset))?;
+        self.reader.pending.clear();
         self.offset = offset;
         Ok(())
     }
 
     pub fn rewind(&mut self) -> anyhow::Result<()> {
-        self.reader.rewind()?;
+        self.reader.inner.rewind()?;
+        self.reader.pending.clear();
         self.offset = 0;
         Ok(())
     }
@@ -128,3 +510,161 @@ impl<R: Read + Send> Iterator for BlobReader<R> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protobuf::Message;
+
+    fn bzip2_header_blob() -> RawBlob {
+        let mut header_block = HeaderBlock::new();
+        header_block.set_writingprogram("osmosis".to_string());
+        let data = header_block.write_to_bytes().unwrap();
+
+        let mut blob = Blob::new();
+        blob.set_raw_size(data.len() as i32);
+        #[cfg(feature = "bz2")]
+        {
+            use std::io::Write;
+            let mut encoder =
+                bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            encoder.write_all(&data).unwrap();
+            blob.set_OBSOLETE_bzip2_data(encoder.finish().unwrap());
+        }
+        #[cfg(not(feature = "bz2"))]
+        blob.set_OBSOLETE_bzip2_data(data);
+
+        let mut header = BlobHeader::new();
+        header.set_field_type("OSMHeader".to_string());
+        RawBlob {
+            header,
+            raw_blob: blob.write_to_bytes().unwrap(),
+            enforce_size_limits: true,
+        }
+    }
+
+    #[cfg(feature = "bz2")]
+    #[test]
+    fn test_decode_bzip2() {
+        match bzip2_header_blob().decode().unwrap() {
+            DecodedBlob::OsmHeader(header_block) => {
+                assert_eq!(header_block.get_writingprogram(), "osmosis")
+            }
+            DecodedBlob::OsmData(_) => panic!("Expected a header block"),
+        }
+    }
+
+    #[cfg(not(feature = "bz2"))]
+    #[test]
+    fn test_decode_bzip2_unsupported() {
+        assert!(bzip2_header_blob().decode().is_err());
+    }
+
+    /// Returns a blob header announcing a blob of the given size, without the blob.
+    fn blob_header(data_size: i32) -> Vec<u8> {
+        let mut header = BlobHeader::new();
+        header.set_field_type("OSMData".to_string());
+        header.set_datasize(data_size);
+        let mut header_bytes = header.write_to_bytes().unwrap();
+        let mut data = (header_bytes.len() as u32).to_be_bytes().to_vec();
+        data.append(&mut header_bytes);
+        data
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let oversized = [
+            (MAX_BLOB_HEADER_SIZE as u32 + 1).to_be_bytes().to_vec(),
+            blob_header(MAX_BLOB_SIZE as i32 + 1),
+            blob_header(-1),
+        ];
+        for data in &oversized {
+            assert!(BlobReader::new(data.as_slice()).next_blob().is_err());
+        }
+
+        // Permissively, an absurd size still doesn't allocate more than the data available
+        let mut reader = BlobReader::new(oversized[1].as_slice());
+        reader.set_enforce_size_limits(false);
+        let err = reader.next_blob().unwrap_err();
+        assert!(err.to_string().contains("truncated"));
+    }
+
+    /// Returns the offsets of the blobs of a stream, followed by its length.
+    fn blob_offsets(data: &[u8]) -> Vec<u64> {
+        let mut reader = BlobReader::new(data);
+        let mut offsets = vec![0];
+        while reader.next_blob().unwrap().is_some() {
+            offsets.push(reader.offset);
+        }
+        offsets
+    }
+
+    fn read_resyncing(data: &[u8]) -> (usize, Vec<Range<u64>>) {
+        let mut reader = BlobReader::new(data);
+        reader.set_resync(true);
+        let count = reader.by_ref().count();
+        (count, reader.skipped_ranges().to_vec())
+    }
+
+    #[test]
+    fn test_resync() {
+        let data = std::fs::read("resources/andorra-latest.osm.pbf").unwrap();
+        let offsets = blob_offsets(&data);
+        let blob_count = offsets.len() - 1;
+        assert!(blob_count > 3);
+        let (start, end) = (offsets[2], offsets[3]);
+
+        // A corrupt header size
+        let mut corrupt = data.clone();
+        corrupt[start as usize..start as usize + 4].copy_from_slice(&[0, 0, 0xff, 0xff]);
+        let mut reader = BlobReader::new(corrupt.as_slice());
+        assert!((0..3)
+            .try_for_each(|_| reader.next_blob().map(drop))
+            .is_err());
+        let (count, skipped) = read_resyncing(&corrupt);
+        assert_eq!(count, blob_count - 1);
+        assert_eq!(skipped, vec![start..end]);
+
+        // Garbage between two blobs
+        let mut garbage = data.clone();
+        garbage.splice(start as usize..start as usize, [0x0a, 0x07, b'O', 1, 2, 3]);
+        let (count, skipped) = read_resyncing(&garbage);
+        assert_eq!(count, blob_count);
+        assert_eq!(skipped, vec![start..start + 6]);
+
+        // A truncated stream
+        let truncated = &data[..end as usize - 10];
+        let (count, skipped) = read_resyncing(truncated);
+        assert_eq!(count, 2);
+        assert_eq!(skipped, vec![start..end - 10]);
+    }
+
+    #[test]
+    fn test_truncation() {
+        let data = std::fs::read("resources/andorra-latest.osm.pbf").unwrap();
+        let offsets = blob_offsets(&data);
+        let start = offsets[2];
+        // Cut off in the blob size, the blob header and the blob
+        for end in [start + 2, start + 8, offsets[3] - 10] {
+            let truncated = &data[..end as usize];
+            let mut reader = BlobReader::new(truncated);
+            let err = (0..3)
+                .try_for_each(|_| reader.next_blob().map(drop))
+                .unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<PbfError>(),
+                Some(&PbfError::Truncated { valid_bytes: start })
+            );
+
+            let mut reader = BlobReader::new(truncated);
+            reader.set_allow_truncation(true);
+            assert_eq!(reader.by_ref().count(), 2);
+            assert_eq!(reader.truncation(), Some(start));
+        }
+
+        let mut reader = BlobReader::new(data.as_slice());
+        reader.set_allow_truncation(true);
+        assert_eq!(reader.by_ref().count(), offsets.len() - 1);
+        assert_eq!(reader.truncation(), None);
+    }
+}
@@ -1,11 +1,19 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
 use protobuf::RepeatedField;
 
-use super::field::FieldCodec;
-use crate::models::{Element, ElementType, Node, Relation, Tag, Way};
+use anyhow::Context;
+
+use super::field::{encode_delta, FieldCodec};
+use crate::models::{Element, ElementType, Node, OsmUser, Relation, Tag, Way};
 use crate::proto::osmformat;
 
+/// The smallest group of nodes encoded as dense nodes in the auto mode. The fixed cost of the
+/// `DenseNodes` and `DenseInfo` fields is outweighed by the delta coding from two nodes on,
+/// whether the nodes have metadata or not.
+const AUTO_DENSE_MIN_NODES: usize = 2;
+
 struct StringTableBuilder {
     strings: Vec<String>,
     id_map: HashMap<String, usize>,
@@ -13,10 +21,13 @@ struct StringTableBuilder {
 
 impl StringTableBuilder {
     pub fn new() -> Self {
-        Self {
+        let mut builder = Self {
             strings: Vec::new(),
             id_map: HashMap::new(),
-        }
+        };
+        // The index 0 is reserved, as it delimits the tags of dense nodes
+        builder.add(String::new());
+        builder
     }
     pub fn add(&mut self, string: String) -> i32 {
         if self.id_map.contains_key(&string) {
@@ -40,10 +51,58 @@ impl StringTableBuilder {
     }
 }
 
+/// How nodes are encoded in a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeEncoding {
+    /// All nodes are encoded as `DenseNodes`.
+    Dense,
+    /// All nodes are encoded as plain `Node` messages.
+    NonDense,
+    /// The encoding is chosen for each block: a single node is cheaper to encode as a plain
+    /// node, while larger groups benefit from the delta coding of dense nodes.
+    Auto,
+}
+
+impl NodeEncoding {
+    /// Whether a group of nodes is encoded as dense nodes.
+    fn use_dense(&self, nodes: &[Node]) -> bool {
+        match self {
+            NodeEncoding::Dense => true,
+            NodeEncoding::NonDense => false,
+            NodeEncoding::Auto => nodes.len() >= AUTO_DENSE_MIN_NODES,
+        }
+    }
+}
+
+/// Whether an element has any metadata. The `Info` of elements without metadata is omitted, as
+/// in files written without metadata.
+fn has_metadata(
+    version: i32,
+    changeset_id: i64,
+    timestamp: &Option<DateTime<Utc>>,
+    user: &Option<OsmUser>,
+    visible: bool,
+) -> bool {
+    version != 0 || changeset_id != 0 || timestamp.is_some() || user.is_some() || !visible
+}
+
+fn node_has_metadata(node: &Node) -> bool {
+    has_metadata(
+        node.version,
+        node.changeset_id,
+        &node.timestamp,
+        &node.user,
+        node.visible,
+    )
+}
+
 pub struct PrimitiveBuilder {
     block: osmformat::PrimitiveBlock,
     codec: FieldCodec,
     string_table: StringTableBuilder,
+    locations_on_ways: bool,
+    lossless_timestamps: bool,
+    anonymous_users_as_none: bool,
 }
 
 impl PrimitiveBuilder {
@@ -53,10 +112,57 @@ impl PrimitiveBuilder {
             codec: FieldCodec::new(block.get_granularity(), block.get_date_granularity()),
             block,
             string_table: StringTableBuilder::new(),
+            locations_on_ways: false,
+            lossless_timestamps: false,
+            anonymous_users_as_none: false,
+        }
+    }
+
+    /// Sets the unit of the timestamps of the block, in milliseconds.
+    pub fn set_date_granularity(&mut self, date_granularity: i32) {
+        self.block.set_date_granularity(date_granularity);
+        self.codec = FieldCodec::new(self.block.get_granularity(), date_granularity);
+    }
+
+    /// Sets whether encoding a timestamp which isn't a multiple of the date granularity fails
+    /// instead of truncating it.
+    pub fn set_lossless_timestamps(&mut self, lossless_timestamps: bool) {
+        self.lossless_timestamps = lossless_timestamps;
+    }
+
+    /// Encodes an optional timestamp, using 0 for missing ones.
+    fn encode_timestamp(&self, timestamp: Option<DateTime<Utc>>) -> anyhow::Result<i64> {
+        match timestamp {
+            Some(timestamp) if self.lossless_timestamps => {
+                self.codec.encode_timestamp_lossless(timestamp)
+            }
+            Some(timestamp) => Ok(self.codec.encode_timestamp(timestamp)),
+            None => Ok(0),
+        }
+    }
+
+    /// Sets whether the locations of way nodes are encoded. All way nodes must have a location
+    /// when it is set.
+    pub fn set_locations_on_ways(&mut self, locations_on_ways: bool) {
+        self.locations_on_ways = locations_on_ways;
+    }
+
+    /// Sets whether the users with uid 0 and an empty name are encoded like missing users, so
+    /// that elements without other metadata are written without it.
+    pub fn set_anonymous_users_as_none(&mut self, anonymous_users_as_none: bool) {
+        self.anonymous_users_as_none = anonymous_users_as_none;
+    }
+
+    /// Removes an anonymous user if `set_anonymous_users_as_none` asks for it.
+    fn clear_anonymous_user(&self, user: &mut Option<OsmUser>) {
+        if self.anonymous_users_as_none && user.as_ref().is_some_and(OsmUser::is_anonymous) {
+            *user = None;
         }
     }
 
-    fn encode_dense_nodes(&mut self, nodes: Vec<Node>) -> osmformat::DenseNodes {
+    fn encode_dense_nodes(&mut self, nodes: Vec<Node>) -> anyhow::Result<osmformat::DenseNodes> {
+        // The metadata of all nodes is omitted if none of them has any
+        let with_info = nodes.iter().any(node_has_metadata);
         let mut dense_info = osmformat::DenseInfo::new();
         let mut dense = osmformat::DenseNodes::new();
 
@@ -69,40 +175,39 @@ impl PrimitiveBuilder {
         let mut previous_sid = 0;
 
         for node in nodes {
-            dense.id.push(node.id - previous_id);
+            dense.id.push(
+                encode_delta(node.id, previous_id)
+                    .with_context(|| format!("Failed to encode the ID of node {}", node.id))?,
+            );
 
             let lat = self.codec.encode_latitude(node.latitude);
             let lon = self.codec.encode_longitude(node.longitude);
             dense.lat.push(lat - previous_lat);
             dense.lon.push(lon - previous_lon);
 
-            dense_info
-                .changeset
-                .push(node.changeset_id - previous_changeset);
-            dense_info.version.push(node.version);
-            dense_info.visible.push(true);
-
-            previous_timestamp = if let Some(timestamp) = node.timestamp {
-                let tt = self.codec.encode_timestamp(timestamp);
-                dense_info.timestamp.push(tt - previous_timestamp);
-                tt
-            } else {
-                let tt = 0i64;
-                dense_info.timestamp.push(tt - previous_timestamp);
-                tt
-            };
-
-            (previous_uid, previous_sid) = if let Some(user) = node.user {
-                dense_info.uid.push(user.id - previous_uid);
-                let user_sid = self.string_table.add(user.name);
-                dense_info.user_sid.push(user_sid - previous_sid);
-                (user.id, user_sid)
-            } else {
-                dense_info.uid.push(0 - previous_uid);
-                let user_sid = self.string_table.add("".to_string());
-                dense_info.user_sid.push(user_sid - previous_sid);
-                (0, user_sid)
-            };
+            if with_info {
+                dense_info
+                    .changeset
+                    .push(node.changeset_id - previous_changeset);
+                dense_info.version.push(node.version);
+                dense_info.visible.push(node.visible);
+
+                let timestamp = self.encode_timestamp(node.timestamp)?;
+                dense_info.timestamp.push(timestamp - previous_timestamp);
+                previous_timestamp = timestamp;
+
+                (previous_uid, previous_sid) = if let Some(user) = node.user {
+                    dense_info.uid.push(user.id - previous_uid);
+                    let user_sid = self.string_table.add(user.name.to_string());
+                    dense_info.user_sid.push(user_sid - previous_sid);
+                    (user.id, user_sid)
+                } else {
+                    dense_info.uid.push(0 - previous_uid);
+                    let user_sid = self.string_table.add("".to_string());
+                    dense_info.user_sid.push(user_sid - previous_sid);
+                    (0, user_sid)
+                };
+            }
 
             for tag in node.tags {
                 dense.keys_vals.push(self.string_table.add(tag.key));
@@ -115,8 +220,10 @@ impl PrimitiveBuilder {
             previous_lon = lon;
             previous_changeset = node.changeset_id;
         }
-        dense.set_denseinfo(dense_info);
-        dense
+        if with_info {
+            dense.set_denseinfo(dense_info);
+        }
+        Ok(dense)
     }
 
     fn encode_tags(&mut self, tags: Vec<Tag>) -> (Vec<u32>, Vec<u32>) {
@@ -129,10 +236,11 @@ impl PrimitiveBuilder {
         (keys, vals)
     }
 
-    fn encode_nodes(&mut self, nodes: Vec<Node>) -> Vec<osmformat::Node> {
+    fn encode_nodes(&mut self, nodes: Vec<Node>) -> anyhow::Result<Vec<osmformat::Node>> {
         nodes
             .into_iter()
-            .map(|node| -> osmformat::Node {
+            .map(|node| -> anyhow::Result<osmformat::Node> {
+                let with_info = node_has_metadata(&node);
                 let mut osm_node = osmformat::Node::new();
                 osm_node.set_id(node.id);
                 osm_node.set_lat(self.codec.encode_latitude(node.latitude));
@@ -141,78 +249,99 @@ impl PrimitiveBuilder {
                 let (keys, vals) = self.encode_tags(node.tags);
                 osm_node.set_keys(keys);
                 osm_node.set_vals(vals);
+                if !with_info {
+                    return Ok(osm_node);
+                }
 
                 let mut info = osmformat::Info::new();
                 info.set_changeset(node.changeset_id);
                 info.set_version(node.version);
                 info.set_visible(node.visible);
-                if let Some(timestamp) = node.timestamp {
-                    info.set_timestamp(self.codec.encode_timestamp(timestamp));
-                } else {
-                    info.set_timestamp(0);
-                }
+                info.set_timestamp(self.encode_timestamp(node.timestamp)?);
                 if let Some(user) = node.user {
                     info.set_uid(user.id);
-                    let sid = self.string_table.add(user.name);
+                    let sid = self.string_table.add(user.name.to_string());
                     info.set_user_sid(sid as u32);
                 } else {
                     info.set_uid(0);
                     let sid = self.string_table.add("".to_string());
                     info.set_user_sid(sid as u32);
                 }
+                osm_node.set_info(info);
 
-                osm_node
+                Ok(osm_node)
             })
             .collect()
     }
 
-    fn add_nodes(&mut self, nodes: Vec<Node>, use_dense: bool) {
+    fn add_nodes(&mut self, nodes: Vec<Node>, node_encoding: NodeEncoding) -> anyhow::Result<()> {
         let mut group = osmformat::PrimitiveGroup::new();
-        if use_dense {
-            let dense = self.encode_dense_nodes(nodes);
+        if node_encoding.use_dense(&nodes) {
+            let dense = self.encode_dense_nodes(nodes)?;
             group.set_dense(dense);
         } else {
-            let encoded_nodes = self.encode_nodes(nodes);
+            let encoded_nodes = self.encode_nodes(nodes)?;
             group.set_nodes(RepeatedField::from_vec(encoded_nodes))
         }
         self.block.primitivegroup.push(group);
+        Ok(())
     }
 
-    fn add_ways(&mut self, ways: Vec<Way>) {
-        let encoded_ways: Vec<osmformat::Way> = ways
+    fn add_ways(&mut self, ways: Vec<Way>) -> anyhow::Result<()> {
+        let encoded_ways = ways
             .into_iter()
             .map(|way| {
                 let mut osm_way = osmformat::Way::new();
                 osm_way.set_id(way.id);
 
+                if self.locations_on_ways {
+                    let mut prev_lat = 0;
+                    let mut prev_lon = 0;
+                    for way_node in &way.way_nodes {
+                        let lat = self
+                            .codec
+                            .encode_latitude(way_node.latitude.unwrap_or_default());
+                        let lon = self
+                            .codec
+                            .encode_longitude(way_node.longitude.unwrap_or_default());
+                        osm_way.lat.push(lat - prev_lat);
+                        osm_way.lon.push(lon - prev_lon);
+                        prev_lat = lat;
+                        prev_lon = lon;
+                    }
+                }
+
                 let mut prev_ref_id = 0;
-                osm_way.set_refs(
-                    way.way_nodes
-                        .into_iter()
-                        .map(|way_node| {
-                            let difference = way_node.id - prev_ref_id;
-                            prev_ref_id = way_node.id;
-                            difference
-                        })
-                        .collect(),
-                );
+                for way_node in way.way_nodes {
+                    let difference = encode_delta(way_node.id, prev_ref_id).with_context(|| {
+                        format!("Failed to encode the node references of way {}", way.id)
+                    })?;
+                    osm_way.refs.push(difference);
+                    prev_ref_id = way_node.id;
+                }
 
                 let (keys, vals) = self.encode_tags(way.tags);
                 osm_way.set_keys(keys);
                 osm_way.set_vals(vals);
 
+                if !has_metadata(
+                    way.version,
+                    way.changeset_id,
+                    &way.timestamp,
+                    &way.user,
+                    way.visible,
+                ) {
+                    return Ok(osm_way);
+                }
+
                 let mut info = osmformat::Info::new();
                 info.set_changeset(way.changeset_id);
                 info.set_version(way.version);
                 info.set_visible(way.visible);
-                if let Some(timestamp) = way.timestamp {
-                    info.set_timestamp(self.codec.encode_timestamp(timestamp));
-                } else {
-                    info.set_timestamp(0);
-                }
+                info.set_timestamp(self.encode_timestamp(way.timestamp)?);
                 if let Some(user) = way.user {
                     info.set_uid(user.id);
-                    let sid = self.string_table.add(user.name);
+                    let sid = self.string_table.add(user.name.to_string());
                     info.set_user_sid(sid as u32);
                 } else {
                     info.set_uid(0);
@@ -221,17 +350,18 @@ impl PrimitiveBuilder {
                 }
                 osm_way.set_info(info);
 
-                osm_way
+                Ok(osm_way)
             })
-            .collect();
+            .collect::<anyhow::Result<Vec<osmformat::Way>>>()?;
 
         let mut group = osmformat::PrimitiveGroup::new();
         group.set_ways(RepeatedField::from_vec(encoded_ways));
         self.block.primitivegroup.push(group);
+        Ok(())
     }
 
-    fn add_relations(&mut self, relations: Vec<Relation>) {
-        let encoded_relations: Vec<osmformat::Relation> = relations
+    fn add_relations(&mut self, relations: Vec<Relation>) -> anyhow::Result<()> {
+        let encoded_relations = relations
             .into_iter()
             .map(|relation| {
                 let mut osm_relation = osmformat::Relation::new();
@@ -239,12 +369,16 @@ impl PrimitiveBuilder {
 
                 let mut prev_member_id = 0i64;
                 for member in relation.members {
-                    osm_relation.memids.push(member.member_id - prev_member_id);
+                    let difference =
+                        encode_delta(member.member_id, prev_member_id).with_context(|| {
+                            format!("Failed to encode the members of relation {}", relation.id)
+                        })?;
+                    osm_relation.memids.push(difference);
                     prev_member_id = member.member_id;
 
                     osm_relation
                         .roles_sid
-                        .push(self.string_table.add(member.role));
+                        .push(self.string_table.add(member.role.to_string()));
                     let osm_member_type = match member.member_type {
                         ElementType::Node => osmformat::Relation_MemberType::NODE,
                         ElementType::Way => osmformat::Relation_MemberType::WAY,
@@ -257,18 +391,24 @@ impl PrimitiveBuilder {
                 osm_relation.set_keys(keys);
                 osm_relation.set_vals(vals);
 
+                if !has_metadata(
+                    relation.version,
+                    relation.changeset_id,
+                    &relation.timestamp,
+                    &relation.user,
+                    relation.visible,
+                ) {
+                    return Ok(osm_relation);
+                }
+
                 let mut info = osmformat::Info::new();
                 info.set_changeset(relation.changeset_id);
                 info.set_version(relation.version);
                 info.set_visible(relation.visible);
-                if let Some(timestamp) = relation.timestamp {
-                    info.set_timestamp(self.codec.encode_timestamp(timestamp));
-                } else {
-                    info.set_timestamp(0);
-                }
+                info.set_timestamp(self.encode_timestamp(relation.timestamp)?);
                 if let Some(user) = relation.user {
                     info.set_uid(user.id);
-                    let sid = self.string_table.add(user.name);
+                    let sid = self.string_table.add(user.name.to_string());
                     info.set_user_sid(sid as u32);
                 } else {
                     info.set_uid(0);
@@ -277,39 +417,53 @@ impl PrimitiveBuilder {
                 }
                 osm_relation.set_info(info);
 
-                osm_relation
+                Ok(osm_relation)
             })
-            .collect();
+            .collect::<anyhow::Result<Vec<osmformat::Relation>>>()?;
 
         let mut group = osmformat::PrimitiveGroup::new();
         group.set_relations(RepeatedField::from_vec(encoded_relations));
         self.block.primitivegroup.push(group);
+        Ok(())
     }
 
-    pub fn build(mut self, elements: Vec<Element>, use_dense: bool) -> osmformat::PrimitiveBlock {
+    pub fn build(
+        mut self,
+        elements: Vec<Element>,
+        node_encoding: NodeEncoding,
+    ) -> anyhow::Result<osmformat::PrimitiveBlock> {
         let mut nodes = Vec::new();
         let mut ways = Vec::new();
         let mut relations = Vec::new();
         for element in elements {
             match element {
-                Element::Node(node) => nodes.push(node),
-                Element::Way(way) => ways.push(way),
-                Element::Relation(relation) => relations.push(relation),
+                Element::Node(mut node) => {
+                    self.clear_anonymous_user(&mut node.user);
+                    nodes.push(node)
+                }
+                Element::Way(mut way) => {
+                    self.clear_anonymous_user(&mut way.user);
+                    ways.push(way)
+                }
+                Element::Relation(mut relation) => {
+                    self.clear_anonymous_user(&mut relation.user);
+                    relations.push(relation)
+                }
             }
         }
         if nodes.len() > 0 {
-            self.add_nodes(nodes, use_dense);
+            self.add_nodes(nodes, node_encoding)?;
         }
         if ways.len() > 0 {
-            self.add_ways(ways);
+            self.add_ways(ways)?;
         }
         if relations.len() > 0 {
-            self.add_relations(relations);
+            self.add_relations(relations)?;
         }
 
         self.block
             .set_stringtable(self.string_table.to_string_table());
-        self.block
+        Ok(self.block)
     }
 }
 
@@ -1,13 +1,18 @@
 use std::collections::HashMap;
 
-use super::field::FieldCodec;
+use anyhow::Context;
+use chrono::DateTime;
+
+use super::backend;
+use super::decode_options::DecodeOptions;
+use super::field::{decode_delta, FieldCodec};
 use crate::models::{
-    Bound, Element, ElementBase, ElementType, Node, OsmUser, Relation, RelationMember, Tag, Way,
-    WayNode,
+    intern, Bound, Element, ElementBase, ElementType, ElementTypeSet, FileHeader, Node, OsmUser,
+    PooledStr, Relation, RelationMember, Tag, Way, WayNode,
 };
 use crate::proto::osmformat;
-use crate::proto::osmformat::Relation_MemberType;
 
+#[derive(Clone)]
 pub struct HeaderReader {
     header: osmformat::HeaderBlock,
 }
@@ -17,6 +22,26 @@ impl HeaderReader {
         Self { header }
     }
 
+    pub fn required_features(&self) -> &[String] {
+        self.header.get_required_features()
+    }
+
+    pub fn optional_features(&self) -> &[String] {
+        self.header.get_optional_features()
+    }
+
+    pub fn writing_program(&self) -> Option<&str> {
+        if self.header.has_writingprogram() {
+            Some(self.header.get_writingprogram())
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn header_block(&self) -> &osmformat::HeaderBlock {
+        &self.header
+    }
+
     pub fn meta(&self) -> HashMap<String, String> {
         let supported_features: Vec<&str> = vec!["OsmSchema-V0.6", "DenseNodes"];
         let mut unsupported: Vec<String> = Vec::new();
@@ -53,111 +78,295 @@ impl HeaderReader {
                 right: bbox.get_right(),
                 top: bbox.get_top(),
                 bottom: bbox.get_bottom(),
-                origin: self.header.get_source().to_owned(),
             })
         } else {
             None
         }
     }
+
+    /// Returns the source of the data, e.g. `http://www.openstreetmap.org/api/0.6`.
+    pub fn source(&self) -> Option<&str> {
+        if self.header.has_source() {
+            Some(self.header.get_source())
+        } else {
+            None
+        }
+    }
+
+    /// Returns all the fields of the header.
+    pub fn file_header(&self) -> FileHeader {
+        let header = &self.header;
+        FileHeader {
+            bbox: self.bound(),
+            source: self.source().map(str::to_owned),
+            writing_program: self.writing_program().map(str::to_owned),
+            required_features: header.get_required_features().to_vec(),
+            optional_features: header.get_optional_features().to_vec(),
+            replication_timestamp: if header.has_osmosis_replication_timestamp() {
+                DateTime::from_timestamp(header.get_osmosis_replication_timestamp(), 0)
+            } else {
+                None
+            },
+            replication_sequence_number: if header.has_osmosis_replication_sequence_number() {
+                Some(header.get_osmosis_replication_sequence_number())
+            } else {
+                None
+            },
+            replication_base_url: if header.has_osmosis_replication_base_url() {
+                Some(header.get_osmosis_replication_base_url().to_owned())
+            } else {
+                None
+            },
+        }
+    }
 }
 
+/// What to do with an element that can't be decoded.
+///
+/// Elements can be broken e.g. by a reference to a string missing from the string table, a tag
+/// key without a value, or lists of IDs and coordinates of different lengths.
+///
+/// The default is `Fail`. It used to be substituting an empty string, which breaks the readers
+/// of files with such elements unless they set `Substitute(String::new())`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeErrorPolicy {
+    /// Fails decoding the whole block.
+    Fail,
+    /// Drops the element.
+    SkipElement,
+    /// Replaces the strings which can't be decoded, and the values missing from tags, with the
+    /// placeholder. Elements broken in other ways are dropped.
+    Substitute(String),
+}
+
+impl Default for DecodeErrorPolicy {
+    /// Fails, so that broken data isn't read as valid elements unnoticed.
+    fn default() -> Self {
+        DecodeErrorPolicy::Fail
+    }
+}
+
+/// A reader of the elements of a data block, decoding only what is asked for.
+///
+/// It's passed to the callbacks of `PbfReader::for_each_blob_parallel`.
 pub struct PrimitiveReader {
-    block: osmformat::PrimitiveBlock,
+    block: backend::PrimitiveBlock,
     decoder: FieldCodec,
+    policy: DecodeErrorPolicy,
+    options: DecodeOptions,
 }
 
 impl PrimitiveReader {
-    pub fn new(block: osmformat::PrimitiveBlock) -> Self {
+    /// Creates a reader of a block applying `policy` to the elements which can't be decoded.
+    /// The readers of this crate use the default policy, `DecodeErrorPolicy::Fail`, unless
+    /// another one is set.
+    pub fn new(block: backend::PrimitiveBlock, policy: DecodeErrorPolicy) -> Self {
+        Self::with_options(block, policy, DecodeOptions::default())
+    }
+
+    /// Creates a reader of a block decoded with `RawBlob::decode_with_options`, so that the
+    /// elements are built without the skipped fields.
+    pub fn with_options(
+        block: backend::PrimitiveBlock,
+        policy: DecodeErrorPolicy,
+        options: DecodeOptions,
+    ) -> Self {
         Self {
             decoder: FieldCodec::new_with_block(&block),
             block,
+            policy,
+            options,
         }
     }
 
-    pub fn get_nodes(&self) -> Vec<Node> {
+    /// Returns the raw protobuf message of the block, for custom decoding.
+    pub fn block(&self) -> &backend::PrimitiveBlock {
+        &self.block
+    }
+
+    /// Returns the granularity of the coordinates in nanodegrees.
+    pub fn granularity(&self) -> i32 {
+        self.decoder.granularity()
+    }
+
+    /// Returns the granularity of the timestamps in milliseconds.
+    pub fn date_granularity(&self) -> i32 {
+        self.decoder.date_granularity()
+    }
+
+    /// Returns the offset of the latitudes in nanodegrees.
+    pub fn lat_offset(&self) -> i64 {
+        self.decoder.lat_offset()
+    }
+
+    /// Returns the offset of the longitudes in nanodegrees.
+    pub fn lon_offset(&self) -> i64 {
+        self.decoder.lon_offset()
+    }
+
+    pub fn get_nodes(&self) -> anyhow::Result<Vec<Node>> {
         let mut nodes: Vec<Node> = Vec::new();
         for group in self.block.get_primitivegroup() {
             if group.has_dense() {
-                let mut gdn = self.process_dense(group.get_dense());
+                let mut gdn = self.process_dense(group.get_dense())?;
                 nodes.append(&mut gdn);
             }
-            let mut gn = self.process_nodes(group.get_nodes());
+            let mut gn = self.process_nodes(group.get_nodes())?;
             nodes.append(&mut gn);
         }
-        nodes
+        Ok(nodes)
     }
 
-    pub fn get_ways(&self) -> Vec<Way> {
+    pub fn get_ways(&self) -> anyhow::Result<Vec<Way>> {
         let mut ways: Vec<Way> = Vec::new();
         for group in self.block.get_primitivegroup() {
-            let mut gw = self.process_ways(group.get_ways());
+            let mut gw = self.process_ways(group.get_ways())?;
             ways.append(&mut gw);
         }
-        ways
+        Ok(ways)
     }
 
-    pub fn get_relations(&self) -> Vec<Relation> {
+    pub fn get_relations(&self) -> anyhow::Result<Vec<Relation>> {
         let mut relations: Vec<Relation> = Vec::new();
         for group in self.block.get_primitivegroup() {
-            let mut gr = self.process_relations(group.get_relations());
+            let mut gr = self.process_relations(group.get_relations())?;
             relations.append(&mut gr);
         }
-        relations
+        Ok(relations)
     }
 
-    pub fn get_all_elements(&self) -> (Vec<Node>, Vec<Way>, Vec<Relation>) {
+    pub fn get_all_elements(&self) -> anyhow::Result<(Vec<Node>, Vec<Way>, Vec<Relation>)> {
         let mut nodes: Vec<Node> = Vec::new();
         let mut ways: Vec<Way> = Vec::new();
         let mut relations: Vec<Relation> = Vec::new();
 
         for group in self.block.get_primitivegroup() {
             if group.has_dense() {
-                let mut gdn = self.process_dense(group.get_dense());
+                let mut gdn = self.process_dense(group.get_dense())?;
                 nodes.append(&mut gdn);
             }
-            let mut gn = self.process_nodes(group.get_nodes());
+            let mut gn = self.process_nodes(group.get_nodes())?;
             nodes.append(&mut gn);
 
-            let mut gw = self.process_ways(group.get_ways());
+            let mut gw = self.process_ways(group.get_ways())?;
             ways.append(&mut gw);
 
-            let mut gr = self.process_relations(group.get_relations());
+            let mut gr = self.process_relations(group.get_relations())?;
             relations.append(&mut gr);
         }
 
-        (nodes, ways, relations)
+        Ok((nodes, ways, relations))
+    }
+
+    pub fn for_each_element<F: FnMut(Element)>(&self, callback: F) -> anyhow::Result<()> {
+        self.for_each_element_of(ElementTypeSet::ALL, callback)
     }
 
-    pub fn for_each_element<F: FnMut(Element)>(&self, mut callback: F) {
+    /// Calls `callback` with the elements of the given types, in the order of the block. The
+    /// elements of the other types aren't decoded.
+    pub fn for_each_element_of<F: FnMut(Element)>(
+        &self,
+        types: ElementTypeSet,
+        mut callback: F,
+    ) -> anyhow::Result<()> {
         for group in self.block.get_primitivegroup() {
-            if group.has_dense() {
-                let nodes = self.process_dense(group.get_dense());
+            if types.contains(&ElementType::Node) {
+                if group.has_dense() {
+                    let nodes = self.process_dense(group.get_dense())?;
+                    for node in nodes {
+                        callback(Element::Node(node));
+                    }
+                }
+                let nodes = self.process_nodes(group.get_nodes())?;
                 for node in nodes {
                     callback(Element::Node(node));
                 }
             }
-            let nodes = self.process_nodes(group.get_nodes());
-            for node in nodes {
-                callback(Element::Node(node));
+
+            if types.contains(&ElementType::Way) {
+                let ways = self.process_ways(group.get_ways())?;
+                for way in ways {
+                    callback(Element::Way(way));
+                }
+            }
+
+            if types.contains(&ElementType::Relation) {
+                let relations = self.process_relations(group.get_relations())?;
+                for relation in relations {
+                    callback(Element::Relation(relation));
+                }
             }
+        }
+        Ok(())
+    }
 
-            let ways = self.process_ways(group.get_ways());
-            for way in ways {
-                callback(Element::Way(way));
+    /// Collects the decoded elements, applying the policy to the elements which failed.
+    fn collect_elements<T, I>(&self, elements: I) -> anyhow::Result<Vec<T>>
+    where
+        I: Iterator<Item = anyhow::Result<T>>,
+    {
+        let mut result = Vec::with_capacity(elements.size_hint().0);
+        for element in elements {
+            match element {
+                Ok(element) => result.push(element),
+                Err(err) if self.policy == DecodeErrorPolicy::Fail => return Err(err),
+                Err(err) => warn!("Skipping an element: {:#}", err),
             }
+        }
+        Ok(result)
+    }
 
-            let relations = self.process_relations(group.get_relations());
-            for relation in relations {
-                callback(Element::Relation(relation));
+    /// Returns the placeholder in place of a string which can't be decoded if the policy
+    /// allows it.
+    fn substitute(&self, err: anyhow::Error) -> anyhow::Result<String> {
+        match &self.policy {
+            DecodeErrorPolicy::Substitute(placeholder) => {
+                warn!("Substituting a string: {:#}", err);
+                Ok(placeholder.clone())
             }
+            _ => Err(err),
         }
     }
 
-    fn process_dense(&self, dense: &osmformat::DenseNodes) -> Vec<Node> {
+    fn decode_string(&self, string_id: usize) -> anyhow::Result<String> {
+        self.decoder
+            .decode_string(string_id)
+            .or_else(|err| self.substitute(err))
+    }
+
+    fn decode_pooled_string(&self, string_id: usize) -> anyhow::Result<PooledStr> {
+        self.decoder
+            .decode_pooled_string(string_id)
+            .or_else(|err| self.substitute(err).map(|s| intern(&s)))
+    }
+
+    fn decode_tag(&self, key_index: usize, value_index: Option<usize>) -> anyhow::Result<Tag> {
+        let key = self.decode_string(key_index)?;
+        let value = match value_index {
+            Some(value_index) => self.decode_string(value_index)?,
+            None => self.substitute(anyhow!("The tag key {} has no value", key))?,
+        };
+        Ok(Tag { key, value })
+    }
+
+    fn process_dense(&self, dense: &backend::DenseNodes) -> anyhow::Result<Vec<Node>> {
+        // Files without metadata may omit the dense info
+        let has_dense_info = dense.has_denseinfo();
         let mut dense_info_iter = DenseInfoIterator::new(dense.get_denseinfo());
         let mut id_iter = dense.get_id().into_iter();
-        let mut lat_iter = dense.get_lat().into_iter();
-        let mut lon_iter = dense.get_lon().into_iter();
+        // The coordinates may have been skipped
+        let missing_coords = match self.options.skip_geometry {
+            true => dense.get_id().len(),
+            false => 0,
+        };
+        let mut lat_iter = dense
+            .get_lat()
+            .iter()
+            .chain(std::iter::repeat_n(&0, missing_coords));
+        let mut lon_iter = dense
+            .get_lon()
+            .iter()
+            .chain(std::iter::repeat_n(&0, missing_coords));
 
         let mut kv_iter = dense.get_keys_vals().into_iter();
 
@@ -170,204 +379,262 @@ impl PrimitiveReader {
                 id_iter.next(),
                 lat_iter.next(),
                 lon_iter.next(),
-                dense_info_iter.next(),
+                match has_dense_info {
+                    true => dense_info_iter.next().map(Some),
+                    false => Some(None),
+                },
             ) {
-                (Some(id), Some(lat), Some(lon), Some(info)) => {
-                    node_id += id;
+                (Some(&id), Some(lat), Some(lon), Some(info)) => {
+                    // The IDs of the following nodes can't be told apart after an overflow
+                    node_id = decode_delta(node_id, id).context("Failed to decode the node IDs")?;
                     latitude += lat;
                     longitude += lon;
-                    let mut node = Node {
-                        id: node_id,
-                        version: info.version,
-                        timestamp: Some(self.decoder.decode_timestamp(info.timestamp)),
-                        changeset_id: info.changeset,
-                        user: Some(OsmUser {
-                            id: info.uid,
-                            name: self.decoder.decode_string(info.user_sid as usize),
+                    // The tags are read even if the node is broken, to stay in step with the
+                    // following nodes
+                    let tags = self.process_dense_tags(&mut kv_iter);
+                    let (node_latitude, node_longitude) = self.decode_coords(latitude, longitude);
+                    let node = match info {
+                        Some(info) => {
+                            self.decode_user(info.uid, info.user_sid as usize)
+                                .and_then(|user| {
+                                    Ok(Node {
+                                        id: node_id,
+                                        version: info.version,
+                                        timestamp: Some(
+                                            self.decoder.decode_timestamp(info.timestamp),
+                                        ),
+                                        changeset_id: info.changeset,
+                                        user,
+                                        latitude: node_latitude,
+                                        longitude: node_longitude,
+                                        visible: info.visible,
+                                        tags: tags?,
+                                    })
+                                })
+                        }
+                        None => tags.map(|tags| Node {
+                            id: node_id,
+                            latitude: node_latitude,
+                            longitude: node_longitude,
+                            visible: true,
+                            tags,
+                            ..Default::default()
                         }),
-                        latitude: self.decoder.decode_latitude(latitude),
-                        longitude: self.decoder.decode_longitude(longitude),
-                        visible: info.visible,
-                        tags: Vec::new(),
                     };
-
-                    loop {
-                        let key_index_op = kv_iter.next();
-                        let key = match key_index_op {
-                            None => break,
-                            Some(0) => break,
-                            Some(&key_index) => self.decoder.decode_string(key_index as usize),
-                        };
-                        let value_index_op = kv_iter.next();
-                        let value = match value_index_op {
-                            None => panic!("The PBF DenseInfo keys/values list contains a key with no corresponding value."),
-                            Some(&value_index) => self.decoder.decode_string(value_index as usize)
-                        };
-                        node.tags.push(Tag { key, value });
-                    }
-
-                    result.push(node);
+                    result.push(node.with_context(|| format!("Failed to decode node {}", node_id)));
                 }
-                (None, None, None, None) => break,
+                (None, None, None, None | Some(None)) => break,
                 _ => {
-                    panic!("dense size error");
+                    // The following nodes can't be told apart
+                    result.push(Err(anyhow!(
+                        "The IDs, coordinates and metadata of the dense nodes after node {} differ in length",
+                        node_id
+                    )));
+                    break;
+                }
+            }
+        }
+        self.collect_elements(result.into_iter())
+    }
+
+    /// Reads the tags of a dense node, up to the next 0 delimiter.
+    fn process_dense_tags(&self, kv_iter: &mut std::slice::Iter<i32>) -> anyhow::Result<Vec<Tag>> {
+        let mut tags = Vec::new();
+        let mut error = None;
+        loop {
+            let key_index = match kv_iter.next() {
+                None | Some(0) => break,
+                Some(&key_index) => key_index as usize,
+            };
+            let value_index = kv_iter.next().map(|&value_index| value_index as usize);
+            match self.decode_tag(key_index, value_index) {
+                Ok(tag) => tags.push(tag),
+                Err(err) => {
+                    error.get_or_insert(err);
                 }
             }
         }
-        result
+        match error {
+            Some(err) => Err(err),
+            None => Ok(tags),
+        }
     }
 
-    fn build_base_element(&self, id: i64, tags: Vec<Tag>, info: &osmformat::Info) -> ElementBase {
-        ElementBase {
+    /// Decodes the coordinates of a node, which are 0 if the geometry is skipped.
+    fn decode_coords(&self, latitude: i64, longitude: i64) -> (i64, i64) {
+        if self.options.skip_geometry {
+            return (0, 0);
+        }
+        (
+            self.decoder.decode_latitude(latitude),
+            self.decoder.decode_longitude(longitude),
+        )
+    }
+
+    /// Decodes the user of an element, which is `None` if it's anonymous and the options ask
+    /// for it.
+    fn decode_user(&self, uid: i32, user_sid: usize) -> anyhow::Result<Option<OsmUser>> {
+        let user = OsmUser {
+            id: uid,
+            name: self.decode_pooled_string(user_sid)?,
+        };
+        if self.options.anonymous_users_as_none && user.is_anonymous() {
+            return Ok(None);
+        }
+        Ok(Some(user))
+    }
+
+    fn build_base_element(
+        &self,
+        id: i64,
+        tags: Vec<Tag>,
+        info: &backend::Info,
+    ) -> anyhow::Result<ElementBase> {
+        Ok(ElementBase {
             id,
             tags,
             version: info.get_version(),
             timestamp: Some(self.decoder.decode_timestamp(info.get_timestamp())),
             changeset_id: info.get_changeset(),
-            user: Some(OsmUser {
-                id: info.get_uid(),
-                name: self.decoder.decode_string(info.get_user_sid() as usize),
-            }),
-            visible: true,
-        }
+            user: self.decode_user(info.get_uid(), info.get_user_sid() as usize)?,
+            // Elements are visible unless the flag says otherwise
+            visible: !info.has_visible() || info.get_visible(),
+        })
     }
 
-    fn process_tags(&self, keys: &[u32], vals: &[u32]) -> Vec<Tag> {
+    fn process_tags(&self, keys: &[u32], vals: &[u32]) -> anyhow::Result<Vec<Tag>> {
         let mut key_iter = keys.into_iter();
         let mut val_iter = vals.into_iter();
         let mut tags: Vec<Tag> = Vec::new();
         loop {
             match (key_iter.next(), val_iter.next()) {
-                (Some(&key_index), Some(&val_index)) => {
-                    let key = self.decoder.decode_string(key_index as usize);
-                    let value = self.decoder.decode_string(val_index as usize);
-                    tags.push(Tag { key, value })
-                }
+                (Some(&key_index), val_index) => tags.push(
+                    self.decode_tag(key_index as usize, val_index.map(|&index| index as usize))?,
+                ),
                 (None, None) => break,
-                _ => panic!("process_nodes key val size error"),
+                (None, Some(_)) => bail!("There are more tag values than keys"),
             }
         }
-        tags
-    }
-
-    fn process_nodes(&self, nodes: &[osmformat::Node]) -> Vec<Node> {
-        nodes
-            .into_iter()
-            .map(|elm| {
-                let tags = self.process_tags(elm.get_keys(), elm.get_vals());
-                let base_el = if elm.has_info() {
-                    let info = elm.get_info();
-                    self.build_base_element(elm.get_id(), tags, info)
-                } else {
-                    ElementBase::new_with_tags(elm.get_id(), tags)
-                };
-                let mut node: Node = base_el.into();
-                node.latitude = self.decoder.decode_latitude(elm.get_lat());
-                node.longitude = self.decoder.decode_longitude(elm.get_lon());
-                node
-            })
-            .collect()
-    }
-
-    fn process_ways(&self, ways: &[osmformat::Way]) -> Vec<Way> {
-        ways.into_iter()
-            .map(|elm| {
-                let tags = self.process_tags(elm.get_keys(), elm.get_vals());
-                let base_el = if elm.has_info() {
-                    let info = elm.get_info();
-                    self.build_base_element(elm.get_id(), tags, info)
-                } else {
-                    ElementBase::new_with_tags(elm.get_id(), tags)
-                };
-                let mut way: Way = base_el.into();
-
-                let mut node_id: i64 = 0;
-                let mut lat: i64 = 0;
-                let mut lon: i64 = 0;
-                let mut ref_iter = elm.get_refs().into_iter();
-                let mut lat_iter = elm.get_lat().into_iter();
-                let mut lon_iter = elm.get_lon().into_iter();
-                loop {
-                    match (ref_iter.next(), lat_iter.next(), lon_iter.next()) {
-                        (Some(&ref_delta), Some(&lat_delta), Some(&lon_delta)) => {
-                            node_id += ref_delta;
-                            lat += lat_delta;
-                            lon += lon_delta;
-                            way.way_nodes.push(WayNode::new(
-                                node_id,
-                                self.decoder.decode_latitude(lat),
-                                self.decoder.decode_longitude(lon),
-                            ));
-                        }
-                        (Some(&ref_delta), None, None) => {
-                            node_id += ref_delta;
-                            way.way_nodes.push(WayNode::new_without_coords(node_id));
-                        }
-                        (None, None, None) => break,
-                        _ => panic!("process_ways refs size error"),
-                    }
+        Ok(tags)
+    }
+
+    fn process_base_element(
+        &self,
+        id: i64,
+        keys: &[u32],
+        vals: &[u32],
+        info: Option<&backend::Info>,
+    ) -> anyhow::Result<ElementBase> {
+        let tags = self.process_tags(keys, vals)?;
+        match info {
+            Some(info) => self.build_base_element(id, tags, info),
+            None => Ok(ElementBase::new_with_tags(id, tags)),
+        }
+    }
+
+    fn process_nodes(&self, nodes: &[backend::Node]) -> anyhow::Result<Vec<Node>> {
+        let nodes = nodes.into_iter().map(|elm| {
+            let info = elm.has_info().then(|| elm.get_info());
+            let base_el =
+                self.process_base_element(elm.get_id(), elm.get_keys(), elm.get_vals(), info);
+            let mut node: Node = base_el
+                .with_context(|| format!("Failed to decode node {}", elm.get_id()))?
+                .into();
+            (node.latitude, node.longitude) = self.decode_coords(elm.get_lat(), elm.get_lon());
+            Ok(node)
+        });
+        self.collect_elements(nodes)
+    }
+
+    fn process_ways(&self, ways: &[backend::Way]) -> anyhow::Result<Vec<Way>> {
+        let ways = ways.into_iter().map(|elm| {
+            self.process_way(elm)
+                .with_context(|| format!("Failed to decode way {}", elm.get_id()))
+        });
+        self.collect_elements(ways)
+    }
+
+    fn process_way(&self, elm: &backend::Way) -> anyhow::Result<Way> {
+        let info = elm.has_info().then(|| elm.get_info());
+        let base_el =
+            self.process_base_element(elm.get_id(), elm.get_keys(), elm.get_vals(), info)?;
+        let mut way: Way = base_el.into();
+
+        let mut node_id: i64 = 0;
+        let mut lat: i64 = 0;
+        let mut lon: i64 = 0;
+        let mut ref_iter = elm.get_refs().into_iter();
+        let mut lat_iter = elm.get_lat().into_iter();
+        let mut lon_iter = elm.get_lon().into_iter();
+        loop {
+            match (ref_iter.next(), lat_iter.next(), lon_iter.next()) {
+                (Some(&ref_delta), Some(&lat_delta), Some(&lon_delta)) => {
+                    node_id = decode_delta(node_id, ref_delta)?;
+                    lat += lat_delta;
+                    lon += lon_delta;
+                    way.way_nodes.push(WayNode::new(
+                        node_id,
+                        self.decoder.decode_latitude(lat),
+                        self.decoder.decode_longitude(lon),
+                    ));
+                }
+                (Some(&ref_delta), None, None) => {
+                    node_id = decode_delta(node_id, ref_delta)?;
+                    way.way_nodes.push(WayNode::new_without_coords(node_id));
                 }
+                (None, None, None) => break,
+                _ => bail!("The node references and coordinates differ in length"),
+            }
+        }
 
-                way
-            })
-            .collect()
-    }
-
-    fn process_relations(&self, relations: &[osmformat::Relation]) -> Vec<Relation> {
-        relations
-            .into_iter()
-            .map(|elm| {
-                let tags = self.process_tags(elm.get_keys(), elm.get_vals());
-                let base_el = if elm.has_info() {
-                    let info = elm.get_info();
-                    self.build_base_element(elm.get_id(), tags, info)
-                } else {
-                    ElementBase::new_with_tags(elm.get_id(), tags)
-                };
-                let mut relation: Relation = base_el.into();
-                relation.members = self.build_relation_members(
-                    elm.get_memids(),
-                    elm.get_types(),
-                    elm.get_roles_sid(),
-                );
-                relation
-            })
-            .collect()
+        Ok(way)
+    }
+
+    fn process_relations(&self, relations: &[backend::Relation]) -> anyhow::Result<Vec<Relation>> {
+        let relations = relations.into_iter().map(|elm| {
+            let info = elm.has_info().then(|| elm.get_info());
+            let base_el = self
+                .process_base_element(elm.get_id(), elm.get_keys(), elm.get_vals(), info)
+                .and_then(|base_el| {
+                    let members = self.build_relation_members(elm)?;
+                    Ok((base_el, members))
+                });
+            let (base_el, members) =
+                base_el.with_context(|| format!("Failed to decode relation {}", elm.get_id()))?;
+            let mut relation: Relation = base_el.into();
+            relation.members = members;
+            Ok(relation)
+        });
+        self.collect_elements(relations)
     }
 
     fn build_relation_members(
         &self,
-        member_ids: &[i64],
-        member_types: &[Relation_MemberType],
-        member_roles: &[i32],
-    ) -> Vec<RelationMember> {
-        let mut mid_iter = member_ids.into_iter();
-        let mut role_iter = member_roles.into_iter();
-        let mut type_iter = member_types.into_iter();
+        elm: &backend::Relation,
+    ) -> anyhow::Result<Vec<RelationMember>> {
+        let mut mid_iter = elm.get_memids().into_iter();
+        let mut role_iter = elm.get_roles_sid().into_iter();
+        let mut type_iter = elm.get_types().into_iter();
 
         let mut result: Vec<RelationMember> = Vec::new();
         let mut member_id: i64 = 0;
         loop {
             match (mid_iter.next(), role_iter.next(), type_iter.next()) {
-                (Some(mid), Some(&role), Some(mem_type)) => {
-                    member_id += mid;
-                    let member_type = match mem_type {
-                        Relation_MemberType::NODE => ElementType::Node,
-                        Relation_MemberType::WAY => ElementType::Way,
-                        Relation_MemberType::RELATION => ElementType::Relation,
-                    };
+                (Some(&mid), Some(&role), Some(mem_type)) => {
+                    member_id = decode_delta(member_id, mid)?;
+                    let member_type = backend::member_type(mem_type)?;
                     let member = RelationMember {
                         member_id,
                         member_type,
-                        role: self.decoder.decode_string(role as usize),
+                        role: self.decode_pooled_string(role as usize)?,
                     };
                     result.push(member);
                 }
                 (None, None, None) => break,
-                _ => panic!("build_relation_members size error"),
+                _ => bail!("The member IDs, types and roles differ in length"),
             }
         }
-        result
+        Ok(result)
     }
 }
 
@@ -394,7 +661,7 @@ pub struct DenseInfoIterator<'a> {
 }
 
 impl<'a> DenseInfoIterator<'a> {
-    fn new(info: &'a osmformat::DenseInfo) -> DenseInfoIterator<'a> {
+    fn new(info: &'a backend::DenseInfo) -> DenseInfoIterator<'a> {
         DenseInfoIterator {
             version_iter: info.get_version().iter(),
             timestamp_iter: info.get_timestamp().iter(),
@@ -447,3 +714,98 @@ impl<'a> Iterator for DenseInfoIterator<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::block_builder::{NodeEncoding, PrimitiveBuilder};
+
+    /// Builds a block of two dense nodes tagged `a=b` and `c=d`, and lets `corrupt` break
+    /// the tags of the second one.
+    fn broken_block<F: FnOnce(&mut Vec<i32>)>(corrupt: F) -> osmformat::PrimitiveBlock {
+        let tagged_node = |id: i64, key: &str, value: &str| {
+            Element::Node(Node {
+                id,
+                tags: vec![Tag {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                }],
+                ..Default::default()
+            })
+        };
+        let elements = vec![tagged_node(1, "a", "b"), tagged_node(2, "c", "d")];
+        let mut block = PrimitiveBuilder::new()
+            .build(elements, NodeEncoding::Dense)
+            .unwrap();
+        let dense = block.mut_primitivegroup()[0].mut_dense();
+        corrupt(&mut dense.keys_vals);
+        block
+    }
+
+    fn decode(
+        block: osmformat::PrimitiveBlock,
+        policy: DecodeErrorPolicy,
+    ) -> anyhow::Result<Vec<Node>> {
+        PrimitiveReader::new(backend::from_builder(block), policy).get_nodes()
+    }
+
+    #[test]
+    fn test_decode_error_policy() {
+        assert_eq!(DecodeErrorPolicy::default(), DecodeErrorPolicy::Fail);
+
+        // The keys and values are [a, b, 0, c, d, 0]; point `d` out of the string table
+        let bad_index = || broken_block(|keys_vals| keys_vals[4] = 999);
+        // Drop `d` and the delimiter, leaving `c` without a value
+        let missing_value = || broken_block(|keys_vals| keys_vals.truncate(4));
+
+        for block in [bad_index(), missing_value()] {
+            assert!(decode(block, DecodeErrorPolicy::Fail).is_err());
+        }
+        for block in [bad_index(), missing_value()] {
+            let nodes = decode(block, DecodeErrorPolicy::SkipElement).unwrap();
+            assert_eq!(nodes.len(), 1);
+            assert_eq!(nodes[0].id, 1);
+        }
+        for block in [bad_index(), missing_value()] {
+            let policy = DecodeErrorPolicy::Substitute("?".to_string());
+            let nodes = decode(block, policy).unwrap();
+            assert_eq!(nodes.len(), 2);
+            assert_eq!(nodes[0].tags[0].value, "b");
+            assert_eq!(nodes[1].tags[0].key, "c");
+            assert_eq!(nodes[1].tags[0].value, "?");
+        }
+    }
+
+    #[test]
+    fn test_dense_length_mismatch() {
+        let mut block = broken_block(|_| {});
+        block.mut_primitivegroup()[0].mut_dense().mut_lat().pop();
+
+        assert!(decode(block.clone(), DecodeErrorPolicy::Fail).is_err());
+        let nodes = decode(block, DecodeErrorPolicy::SkipElement).unwrap();
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[test]
+    fn test_delta_overflow() {
+        // The node IDs are delta coded as [1, 1]
+        let mut block = broken_block(|_| {});
+        block.mut_primitivegroup()[0].mut_dense().mut_id()[1] = i64::MAX;
+        assert!(decode(block, DecodeErrorPolicy::SkipElement).is_err());
+
+        let way = Element::Way(Way {
+            id: 1,
+            way_nodes: vec![WayNode::new_without_coords(i64::MAX)],
+            ..Default::default()
+        });
+        let mut block = PrimitiveBuilder::new()
+            .build(vec![way], NodeEncoding::Dense)
+            .unwrap();
+        block.mut_primitivegroup()[0].mut_ways()[0]
+            .mut_refs()
+            .push(1);
+        let reader = PrimitiveReader::new(backend::from_builder(block), DecodeErrorPolicy::Fail);
+        let err = reader.get_ways().unwrap_err();
+        assert!(format!("{:#}", err).contains("overflows"));
+    }
+}
@@ -0,0 +1,195 @@
+use super::wire::{write_varint, FieldReader};
+
+/// The parts of the elements to leave out when decoding blocks, for passes which don't need
+/// them, e.g. marking the referenced elements.
+///
+/// The fields are skipped in the encoded block before the protobuf messages are built, so
+/// they aren't parsed at all. The elements then have no tags, no metadata, i.e. version 0 and
+/// no timestamp, user or changeset, or all coordinates set to 0 and no locations on ways. The
+/// string table is still parsed, as are the coordinates of the rare non-dense nodes, which are
+/// required fields.
+///
+/// With `anonymous_users_as_none`, the users with uid 0 and an empty name, which writers use
+/// for elements without a user, are decoded as `None` instead of `Some`. `PbfWriter` does the
+/// reverse with `set_anonymous_users_as_none`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{DecodeOptions, IterableReader};
+///
+/// let mut reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// reader.set_decode_options(DecodeOptions {
+///     skip_tags: true,
+///     skip_metadata: true,
+///     skip_geometry: true,
+///     ..Default::default()
+/// });
+/// let element_count = reader.count();
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeOptions {
+    pub skip_tags: bool,
+    pub skip_metadata: bool,
+    pub skip_geometry: bool,
+    pub anonymous_users_as_none: bool,
+}
+
+impl DecodeOptions {
+    /// Whether all fields are decoded as they are.
+    pub fn is_default(&self) -> bool {
+        !self.skips_fields() && !self.anonymous_users_as_none
+    }
+
+    /// Whether any fields are skipped, so that the blocks have to be stripped.
+    pub(crate) fn skips_fields(&self) -> bool {
+        self.skip_tags || self.skip_metadata || self.skip_geometry
+    }
+
+    /// Returns the numbers of the fields to skip in a message of the primitive group field
+    /// with the given number.
+    fn skipped_fields(&self, group_field: u32) -> Vec<u32> {
+        // The tag, metadata and coordinate fields of nodes, dense nodes, ways and relations
+        let (tags, metadata, geometry): (&[u32], &[u32], &[u32]) = match group_field {
+            1 => (&[2, 3], &[4], &[]),
+            2 => (&[10], &[5], &[8, 9]),
+            3 => (&[2, 3], &[4], &[9, 10]),
+            4 => (&[2, 3], &[4], &[]),
+            _ => (&[], &[], &[]),
+        };
+        let mut skipped = Vec::new();
+        if self.skip_tags {
+            skipped.extend_from_slice(tags);
+        }
+        if self.skip_metadata {
+            skipped.extend_from_slice(metadata);
+        }
+        if self.skip_geometry {
+            skipped.extend_from_slice(geometry);
+        }
+        skipped
+    }
+}
+
+/// Copies an uncompressed primitive block without the fields skipped by the options.
+pub(crate) fn strip_block(data: &[u8], options: &DecodeOptions) -> anyhow::Result<Vec<u8>> {
+    let skipped: Vec<Vec<u32>> = (0..5)
+        .map(|number| options.skipped_fields(number))
+        .collect();
+    let mut stripped = Vec::with_capacity(data.len());
+    let mut block = FieldReader::new(data);
+    loop {
+        let raw = block.remaining();
+        let Some((number, field)) = block.next_field()? else {
+            break;
+        };
+        // primitivegroup
+        if number != 2 {
+            stripped.extend_from_slice(&raw[..raw.len() - block.remaining().len()]);
+            continue;
+        }
+        let mut stripped_group = Vec::new();
+        let mut group = FieldReader::new(field.bytes()?);
+        loop {
+            let raw = group.remaining();
+            let Some((number, field)) = group.next_field()? else {
+                break;
+            };
+            match skipped.get(number as usize) {
+                Some(skipped) if !skipped.is_empty() => {
+                    let message = strip_message(field.bytes()?, skipped)?;
+                    write_bytes_field(&mut stripped_group, number, &message);
+                }
+                _ => stripped_group.extend_from_slice(&raw[..raw.len() - group.remaining().len()]),
+            }
+        }
+        write_bytes_field(&mut stripped, number, &stripped_group);
+    }
+    Ok(stripped)
+}
+
+/// Copies an encoded message without the skipped fields.
+fn strip_message(data: &[u8], skipped: &[u32]) -> anyhow::Result<Vec<u8>> {
+    let mut stripped = Vec::with_capacity(data.len());
+    let mut message = FieldReader::new(data);
+    loop {
+        let raw = message.remaining();
+        let Some((number, _)) = message.next_field()? else {
+            break;
+        };
+        if !skipped.contains(&number) {
+            stripped.extend_from_slice(&raw[..raw.len() - message.remaining().len()]);
+        }
+    }
+    Ok(stripped)
+}
+
+fn write_bytes_field(target: &mut Vec<u8>, number: u32, bytes: &[u8]) {
+    write_varint(target, ((number as u64) << 3) | 2);
+    write_varint(target, bytes.len() as u64);
+    target.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Element;
+    use crate::readers::IterableReader;
+
+    #[test]
+    fn test_decode_options() {
+        let path = "resources/andorra-latest.osm.pbf";
+        let mut reader = IterableReader::from_path(path).unwrap();
+        reader.set_decode_options(DecodeOptions {
+            skip_tags: true,
+            skip_metadata: true,
+            skip_geometry: true,
+            ..Default::default()
+        });
+        let mut count = 0;
+        for (full, stripped) in IterableReader::from_path(path).unwrap().zip(reader) {
+            count += 1;
+            match (full, stripped) {
+                (Element::Node(full), Element::Node(stripped)) => {
+                    assert_eq!(full.id, stripped.id);
+                    assert_eq!((stripped.latitude, stripped.longitude), (0, 0));
+                    assert!(stripped.tags.is_empty());
+                    assert!(stripped.timestamp.is_none() && stripped.visible);
+                }
+                (Element::Way(full), Element::Way(stripped)) => {
+                    assert_eq!(full.id, stripped.id);
+                    assert_eq!(full.way_nodes.len(), stripped.way_nodes.len());
+                    assert!(full
+                        .way_nodes
+                        .iter()
+                        .zip(&stripped.way_nodes)
+                        .all(|(a, b)| a.id == b.id && b.latitude.is_none()));
+                    assert!(stripped.tags.is_empty() && stripped.version == 0);
+                }
+                (Element::Relation(full), Element::Relation(stripped)) => {
+                    assert_eq!(full.id, stripped.id);
+                    assert_eq!(full.members, stripped.members);
+                    assert!(stripped.tags.is_empty() && stripped.user.is_none());
+                }
+                _ => panic!("The elements differ in type"),
+            }
+        }
+        assert!(count > 0);
+
+        // Only the tags are skipped
+        let mut reader = IterableReader::from_path(path).unwrap();
+        reader.set_decode_options(DecodeOptions {
+            skip_tags: true,
+            ..Default::default()
+        });
+        for (full, stripped) in IterableReader::from_path(path).unwrap().zip(reader) {
+            if let (Element::Node(full), Element::Node(stripped)) = (full, stripped) {
+                assert!(stripped.tags.is_empty());
+                assert_eq!(
+                    (full.latitude, full.version, full.timestamp),
+                    (stripped.latitude, stripped.version, stripped.timestamp)
+                );
+            }
+        }
+    }
+}
@@ -1,12 +1,37 @@
-use crate::proto::osmformat::PrimitiveBlock;
+#[cfg(feature = "interning")]
+use std::sync::OnceLock;
+
+use super::backend::PrimitiveBlock;
+use crate::models::PooledStr;
 use chrono::{DateTime, Utc};
 
+/// Returns the delta of `value` to `previous` for delta coding, failing instead of wrapping
+/// around if it doesn't fit into an `i64`, e.g. between extreme positive and negative IDs.
+pub fn encode_delta(value: i64, previous: i64) -> anyhow::Result<i64> {
+    value
+        .checked_sub(previous)
+        .ok_or_else(|| anyhow!("The delta from {} to {} overflows", previous, value))
+}
+
+/// Adds a delta to the previous value of a delta coded field, failing instead of wrapping
+/// around on overflow.
+pub fn decode_delta(previous: i64, delta: i64) -> anyhow::Result<i64> {
+    previous
+        .checked_add(delta)
+        .ok_or_else(|| anyhow!("The delta {} added to {} overflows", delta, previous))
+}
+
 pub struct FieldCodec {
     date_granularity: i32,
     granularity: i32,
     lat_offset: i64,
     lon_offset: i64,
-    string_table: Vec<String>,
+    /// The strings of the block; `None` for strings which aren't valid UTF-8.
+    string_table: Vec<Option<String>>,
+    /// The strings of the block interned by `decode_pooled_string`, so that the pool is looked
+    /// up once per string and block.
+    #[cfg(feature = "interning")]
+    pooled_strings: Vec<OnceLock<PooledStr>>,
 }
 
 impl FieldCodec {
@@ -17,6 +42,8 @@ impl FieldCodec {
             lat_offset: 0,
             lon_offset: 0,
             string_table: Vec::new(),
+            #[cfg(feature = "interning")]
+            pooled_strings: Vec::new(),
         }
     }
 
@@ -27,24 +54,36 @@ impl FieldCodec {
         } else {
             bytes_array
                 .into_iter()
-                .map(|bytes| match String::from_utf8(bytes.clone()) {
-                    Ok(str) => str,
-                    Err(err) => {
-                        eprintln!("{}", err);
-                        String::new()
-                    }
-                })
-                .collect::<Vec<String>>()
+                .map(|bytes| String::from_utf8(bytes.clone()).ok())
+                .collect::<Vec<Option<String>>>()
         };
         Self {
             date_granularity: block.get_date_granularity(),
             granularity: block.get_granularity(),
             lat_offset: block.get_lat_offset(),
             lon_offset: block.get_lon_offset(),
+            #[cfg(feature = "interning")]
+            pooled_strings: (0..string_table.len()).map(|_| OnceLock::new()).collect(),
             string_table,
         }
     }
 
+    pub fn granularity(&self) -> i32 {
+        self.granularity
+    }
+
+    pub fn date_granularity(&self) -> i32 {
+        self.date_granularity
+    }
+
+    pub fn lat_offset(&self) -> i64 {
+        self.lat_offset
+    }
+
+    pub fn lon_offset(&self) -> i64 {
+        self.lon_offset
+    }
+
     pub fn encode_latitude(&self, latitude: i64) -> i64 {
         (latitude - self.lat_offset) / self.granularity as i64
     }
@@ -65,18 +104,54 @@ impl FieldCodec {
         time.timestamp_millis() / self.date_granularity as i64
     }
 
+    /// Encodes a timestamp, failing if it isn't a multiple of the date granularity and would be
+    /// truncated.
+    pub fn encode_timestamp_lossless(&self, time: DateTime<Utc>) -> anyhow::Result<i64> {
+        let millis = time.timestamp_millis();
+        if !time.timestamp_subsec_nanos().is_multiple_of(1_000_000)
+            || millis % self.date_granularity as i64 != 0
+        {
+            bail!(
+                "The timestamp {} can't be represented exactly with a date granularity of {} ms",
+                time.to_rfc3339(),
+                self.date_granularity
+            );
+        }
+        Ok(millis / self.date_granularity as i64)
+    }
+
     pub fn decode_timestamp(&self, raw_timestamp: i64) -> DateTime<Utc> {
         let timestamp = self.date_granularity as i64 * raw_timestamp;
         return DateTime::from_timestamp_millis(timestamp).expect("invalid timestamp");
     }
 
-    pub fn decode_string(&self, string_id: usize) -> String {
+    fn get_string(&self, string_id: usize) -> anyhow::Result<&str> {
         match self.string_table.get(string_id) {
-            None => {
-                eprintln!("no matched string table id: {}", string_id);
-                String::new()
-            }
-            Some(s) => s.to_owned(),
+            None => bail!("No matched string table id: {}", string_id),
+            Some(None) => bail!(
+                "The string {} of the string table isn't valid UTF-8",
+                string_id
+            ),
+            Some(Some(s)) => Ok(s),
         }
     }
+
+    pub fn decode_string(&self, string_id: usize) -> anyhow::Result<String> {
+        self.get_string(string_id).map(str::to_owned)
+    }
+
+    /// Decodes a user name or role, interned with the `interning` feature.
+    #[cfg(feature = "interning")]
+    pub fn decode_pooled_string(&self, string_id: usize) -> anyhow::Result<PooledStr> {
+        let s = self.get_string(string_id)?;
+        Ok(self.pooled_strings[string_id]
+            .get_or_init(|| crate::models::intern(s))
+            .clone())
+    }
+
+    /// Decodes a user name or role, interned with the `interning` feature.
+    #[cfg(not(feature = "interning"))]
+    pub fn decode_pooled_string(&self, string_id: usize) -> anyhow::Result<PooledStr> {
+        self.get_string(string_id).map(PooledStr::from)
+    }
 }
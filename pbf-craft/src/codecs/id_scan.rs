@@ -0,0 +1,188 @@
+//! A minimal decoder of primitive blocks which reads only the IDs and references of elements.
+//!
+//! The block is walked field by field without building the protobuf messages, so the string
+//! table, tags, coordinates and metadata are skipped over instead of being decoded.
+
+use super::field::decode_delta;
+use super::wire::FieldReader;
+use crate::models::{ElementId, ElementType};
+
+/// The IDs and references of the elements of a block, decoded without tags and metadata.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockIds {
+    /// The byte offset of the blob in the file.
+    pub offset: u64,
+    pub node_ids: Vec<i64>,
+    pub way_ids: Vec<i64>,
+    /// The node IDs of each way, in the order of `way_ids`.
+    pub way_refs: Vec<Vec<i64>>,
+    pub relation_ids: Vec<i64>,
+    /// The members of each relation, in the order of `relation_ids`.
+    pub relation_members: Vec<Vec<ElementId>>,
+}
+
+impl BlockIds {
+    pub fn is_empty(&self) -> bool {
+        self.node_ids.is_empty() && self.way_ids.is_empty() && self.relation_ids.is_empty()
+    }
+}
+
+/// Decodes the IDs and references of an uncompressed primitive block.
+pub fn decode_block_ids(data: &[u8], offset: u64) -> anyhow::Result<BlockIds> {
+    let mut block_ids = BlockIds {
+        offset,
+        ..Default::default()
+    };
+    let mut block = FieldReader::new(data);
+    while let Some((number, field)) = block.next_field()? {
+        // primitivegroup
+        if number == 2 {
+            decode_group(field.bytes()?, &mut block_ids)?;
+        }
+    }
+    Ok(block_ids)
+}
+
+fn decode_group(data: &[u8], block_ids: &mut BlockIds) -> anyhow::Result<()> {
+    let mut group = FieldReader::new(data);
+    while let Some((number, field)) = group.next_field()? {
+        match number {
+            1 => {
+                let id = message_field(field.bytes()?, 1)?.unwrap_or(0);
+                block_ids.node_ids.push(zigzag(id));
+            }
+            2 => {
+                let mut dense = FieldReader::new(field.bytes()?);
+                while let Some((number, field)) = dense.next_field()? {
+                    if number == 1 {
+                        delta_decode(&field.packed()?, &mut block_ids.node_ids)?;
+                    }
+                }
+            }
+            3 => {
+                let mut id = 0;
+                let mut refs = Vec::new();
+                let mut way = FieldReader::new(field.bytes()?);
+                while let Some((number, field)) = way.next_field()? {
+                    match number {
+                        1 => id = field.varint()? as i64,
+                        8 => delta_decode(&field.packed()?, &mut refs)?,
+                        _ => {}
+                    }
+                }
+                block_ids.way_ids.push(id);
+                block_ids.way_refs.push(refs);
+            }
+            4 => {
+                let mut id = 0;
+                let mut member_ids = Vec::new();
+                let mut member_types = Vec::new();
+                let mut relation = FieldReader::new(field.bytes()?);
+                while let Some((number, field)) = relation.next_field()? {
+                    match number {
+                        1 => id = field.varint()? as i64,
+                        9 => delta_decode(&field.packed()?, &mut member_ids)?,
+                        10 => member_types.extend(field.packed()?),
+                        _ => {}
+                    }
+                }
+                if member_ids.len() != member_types.len() {
+                    bail!(
+                        "Relation {} has {} member IDs but {} member types",
+                        id,
+                        member_ids.len(),
+                        member_types.len()
+                    );
+                }
+                let members = member_types
+                    .into_iter()
+                    .zip(member_ids)
+                    .map(|(member_type, member_id)| {
+                        let member_type = match member_type {
+                            0 => ElementType::Node,
+                            1 => ElementType::Way,
+                            2 => ElementType::Relation,
+                            _ => bail!("Unknown member type {} in relation {}", member_type, id),
+                        };
+                        ElementId::try_new(member_type, member_id).ok_or_else(|| {
+                            anyhow!("Member ID {} of relation {} is out of range", member_id, id)
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                block_ids.relation_ids.push(id);
+                block_ids.relation_members.push(members);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Reads the varint field with the given number of a message, skipping all other fields.
+fn message_field(data: &[u8], wanted: u32) -> anyhow::Result<Option<u64>> {
+    let mut message = FieldReader::new(data);
+    while let Some((number, field)) = message.next_field()? {
+        if number == wanted {
+            return Ok(Some(field.varint()?));
+        }
+    }
+    Ok(None)
+}
+
+/// Appends the values of a packed, delta coded `sint64` field.
+fn delta_decode(values: &[u64], target: &mut Vec<i64>) -> anyhow::Result<()> {
+    let mut value = 0i64;
+    target.reserve(values.len());
+    for delta in values {
+        value = decode_delta(value, zigzag(*delta))?;
+        target.push(value);
+    }
+    Ok(())
+}
+
+fn zigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::RelationMember;
+    use crate::readers::PbfReader;
+
+    #[test]
+    fn test_read_ids() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let mut reader = PbfReader::from_path(path).unwrap();
+        let mut id_reader = PbfReader::from_path(path).unwrap();
+        while let Some(blob_data) = reader.read_next_blob().unwrap() {
+            let block_ids = id_reader.read_next_block_ids().unwrap().unwrap();
+            assert_eq!(block_ids.offset, blob_data.offset);
+
+            let node_ids: Vec<i64> = blob_data.nodes.iter().map(|node| node.id).collect();
+            assert_eq!(block_ids.node_ids, node_ids);
+
+            let way_refs: Vec<Vec<i64>> = blob_data
+                .ways
+                .iter()
+                .map(|way| way.way_nodes.iter().map(|way_node| way_node.id).collect())
+                .collect();
+            assert_eq!(block_ids.way_refs, way_refs);
+
+            let relation_members: Vec<Vec<ElementId>> = blob_data
+                .relations
+                .iter()
+                .map(|relation| {
+                    relation
+                        .members
+                        .iter()
+                        .map(RelationMember::element_id)
+                        .collect::<Option<_>>()
+                        .unwrap()
+                })
+                .collect();
+            assert_eq!(block_ids.relation_members, relation_members);
+        }
+        assert!(id_reader.read_next_block_ids().unwrap().is_none());
+    }
+}
@@ -1,4 +1,11 @@
+pub mod backend;
 pub mod blob;
 pub mod block_builder;
 pub mod block_decorators;
+pub mod decode_options;
 pub mod field;
+pub mod id_scan;
+pub mod o5m;
+#[cfg(feature = "prost")]
+pub mod prost_osmformat;
+pub mod wire;
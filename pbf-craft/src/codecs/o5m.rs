@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+pub const DATASET_NODE: u8 = 0x10;
+pub const DATASET_WAY: u8 = 0x11;
+pub const DATASET_RELATION: u8 = 0x12;
+pub const DATASET_BOUNDING_BOX: u8 = 0xdb;
+pub const DATASET_HEADER: u8 = 0xe0;
+pub const DATASET_END_OF_FILE: u8 = 0xfe;
+pub const DATASET_RESET: u8 = 0xff;
+
+pub const HEADER_MAGIC: &[u8] = b"o5m2";
+
+const STRING_TABLE_SIZE: u64 = 15000;
+const MAX_TABLE_STRING_LENGTH: usize = 250;
+
+/// o5m stores coordinates in units of 100 nanodegrees.
+pub const COORDINATE_UNIT: i64 = 100;
+
+pub fn write_uvarint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+pub fn write_svarint(buf: &mut Vec<u8>, value: i64) {
+    write_uvarint(buf, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+pub fn read_uvarint(data: &[u8], pos: &mut usize) -> anyhow::Result<u64> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = match data.get(*pos) {
+            Some(byte) => *byte,
+            None => bail!("Unexpected end of o5m dataset while reading a number"),
+        };
+        *pos += 1;
+        if shift >= 64 {
+            bail!("o5m number is too long");
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+pub fn read_svarint(data: &[u8], pos: &mut usize) -> anyhow::Result<i64> {
+    let value = read_uvarint(data, pos)?;
+    Ok(((value >> 1) as i64) ^ -((value & 1) as i64))
+}
+
+/// The reference table of recently used strings and string pairs.
+///
+/// Entries are kept as their raw encoding, i.e. each string followed by a zero byte, so string
+/// pairs and single strings share one table as the format requires.
+pub struct StringTable {
+    entries: Vec<Vec<u8>>,
+    positions: HashMap<Vec<u8>, u64>,
+    count: u64,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            positions: HashMap::new(),
+            count: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.entries.clear();
+        self.positions.clear();
+        self.count = 0;
+    }
+
+    fn add(&mut self, raw: Vec<u8>) {
+        let slot = (self.count % STRING_TABLE_SIZE) as usize;
+        if slot < self.entries.len() {
+            let old = std::mem::replace(&mut self.entries[slot], raw.clone());
+            if self.positions.get(&old) == Some(&(self.count - STRING_TABLE_SIZE)) {
+                self.positions.remove(&old);
+            }
+        } else {
+            self.entries.push(raw.clone());
+        }
+        self.positions.insert(raw, self.count);
+        self.count += 1;
+    }
+
+    /// Encodes strings either as a back reference or inline, updating the table.
+    pub fn write(&mut self, buf: &mut Vec<u8>, strings: &[&[u8]]) {
+        let mut raw = Vec::new();
+        for s in strings {
+            raw.extend_from_slice(s);
+            raw.push(0);
+        }
+        if let Some(position) = self.positions.get(&raw) {
+            let distance = self.count - position;
+            if distance <= STRING_TABLE_SIZE {
+                write_uvarint(buf, distance);
+                return;
+            }
+        }
+        buf.push(0);
+        buf.extend_from_slice(&raw);
+        if raw.len() - strings.len() <= MAX_TABLE_STRING_LENGTH {
+            self.add(raw);
+        }
+    }
+
+    /// Decodes `number` zero-terminated strings either inline or from the table.
+    pub fn read(
+        &mut self,
+        data: &[u8],
+        pos: &mut usize,
+        number: usize,
+    ) -> anyhow::Result<Vec<Vec<u8>>> {
+        let raw = if data.get(*pos) == Some(&0) {
+            *pos += 1;
+            let start = *pos;
+            for _ in 0..number {
+                match data[*pos..].iter().position(|b| *b == 0) {
+                    Some(length) => *pos += length + 1,
+                    None => bail!("Unterminated string in o5m dataset"),
+                }
+            }
+            let raw = data[start..*pos].to_vec();
+            if raw.len() - number <= MAX_TABLE_STRING_LENGTH {
+                self.add(raw.clone());
+            }
+            raw
+        } else {
+            let distance = read_uvarint(data, pos)?;
+            if distance == 0 || distance > STRING_TABLE_SIZE || distance > self.count {
+                bail!("Invalid o5m string reference: {}", distance);
+            }
+            let slot = ((self.count - distance) % STRING_TABLE_SIZE) as usize;
+            self.entries[slot].clone()
+        };
+        let mut strings: Vec<Vec<u8>> = raw
+            .split(|b| *b == 0)
+            .map(|s| s.to_vec())
+            .collect::<Vec<Vec<u8>>>();
+        // the trailing terminator leaves an empty piece behind
+        strings.pop();
+        if strings.len() != number {
+            bail!("Malformed o5m string reference");
+        }
+        Ok(strings)
+    }
+}
+
+pub fn encode_uid(uid: i32) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if uid != 0 {
+        write_uvarint(&mut buf, uid as u32 as u64);
+    }
+    buf
+}
+
+pub fn decode_uid(bytes: &[u8]) -> anyhow::Result<i32> {
+    if bytes.is_empty() {
+        return Ok(0);
+    }
+    let mut pos = 0;
+    Ok(read_uvarint(bytes, &mut pos)? as u32 as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        for value in [
+            0i64,
+            1,
+            -1,
+            63,
+            -64,
+            64,
+            1 << 40,
+            -(1 << 40),
+            i64::MAX,
+            i64::MIN,
+        ] {
+            let mut buf = Vec::new();
+            write_svarint(&mut buf, value);
+            let mut pos = 0;
+            assert_eq!(read_svarint(&buf, &mut pos).unwrap(), value);
+            assert_eq!(pos, buf.len());
+        }
+        let mut buf = Vec::new();
+        write_svarint(&mut buf, -1);
+        assert_eq!(buf, vec![0x01]);
+    }
+
+    #[test]
+    fn test_string_table() {
+        let mut writer_table = StringTable::new();
+        let mut buf = Vec::new();
+        writer_table.write(&mut buf, &[b"highway", b"primary"]);
+        writer_table.write(&mut buf, &[b"name", b"x"]);
+        writer_table.write(&mut buf, &[b"highway", b"primary"]);
+        assert_eq!(buf.last(), Some(&2));
+
+        let mut reader_table = StringTable::new();
+        let mut pos = 0;
+        for expected in [
+            ["highway", "primary"],
+            ["name", "x"],
+            ["highway", "primary"],
+        ] {
+            let strings = reader_table.read(&buf, &mut pos, 2).unwrap();
+            assert_eq!(strings[0], expected[0].as_bytes());
+            assert_eq!(strings[1], expected[1].as_bytes());
+        }
+        assert_eq!(pos, buf.len());
+    }
+}
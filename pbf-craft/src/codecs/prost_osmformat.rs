@@ -0,0 +1,408 @@
+//! The messages of data blocks for decoding with prost, following `osmformat.proto`.
+//!
+//! They are written out instead of generated, so that the build doesn't need `protoc`. Besides
+//! their fields, they have the getters of the messages generated by rust-protobuf which are used
+//! by the decoders, so that the decoders don't depend on the backend.
+
+use prost::{Enumeration, Message};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PrimitiveBlock {
+    #[prost(message, required, tag = "1")]
+    pub stringtable: StringTable,
+    #[prost(message, repeated, tag = "2")]
+    pub primitivegroup: Vec<PrimitiveGroup>,
+    #[prost(int32, optional, tag = "17", default = "100")]
+    pub granularity: Option<i32>,
+    #[prost(int64, optional, tag = "19", default = "0")]
+    pub lat_offset: Option<i64>,
+    #[prost(int64, optional, tag = "20", default = "0")]
+    pub lon_offset: Option<i64>,
+    #[prost(int32, optional, tag = "18", default = "1000")]
+    pub date_granularity: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PrimitiveGroup {
+    #[prost(message, repeated, tag = "1")]
+    pub nodes: Vec<Node>,
+    #[prost(message, optional, tag = "2")]
+    pub dense: Option<DenseNodes>,
+    #[prost(message, repeated, tag = "3")]
+    pub ways: Vec<Way>,
+    #[prost(message, repeated, tag = "4")]
+    pub relations: Vec<Relation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StringTable {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub s: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Info {
+    #[prost(int32, optional, tag = "1", default = "-1")]
+    pub version: Option<i32>,
+    #[prost(int64, optional, tag = "2")]
+    pub timestamp: Option<i64>,
+    #[prost(int64, optional, tag = "3")]
+    pub changeset: Option<i64>,
+    #[prost(int32, optional, tag = "4")]
+    pub uid: Option<i32>,
+    #[prost(uint32, optional, tag = "5")]
+    pub user_sid: Option<u32>,
+    #[prost(bool, optional, tag = "6")]
+    pub visible: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DenseInfo {
+    #[prost(int32, repeated, packed = "true", tag = "1")]
+    pub version: Vec<i32>,
+    #[prost(sint64, repeated, packed = "true", tag = "2")]
+    pub timestamp: Vec<i64>,
+    #[prost(sint64, repeated, packed = "true", tag = "3")]
+    pub changeset: Vec<i64>,
+    #[prost(sint32, repeated, packed = "true", tag = "4")]
+    pub uid: Vec<i32>,
+    #[prost(sint32, repeated, packed = "true", tag = "5")]
+    pub user_sid: Vec<i32>,
+    #[prost(bool, repeated, packed = "true", tag = "6")]
+    pub visible: Vec<bool>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Node {
+    #[prost(sint64, required, tag = "1")]
+    pub id: i64,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    pub keys: Vec<u32>,
+    #[prost(uint32, repeated, packed = "true", tag = "3")]
+    pub vals: Vec<u32>,
+    #[prost(message, optional, tag = "4")]
+    pub info: Option<Info>,
+    #[prost(sint64, required, tag = "8")]
+    pub lat: i64,
+    #[prost(sint64, required, tag = "9")]
+    pub lon: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DenseNodes {
+    #[prost(sint64, repeated, packed = "true", tag = "1")]
+    pub id: Vec<i64>,
+    #[prost(message, optional, tag = "5")]
+    pub denseinfo: Option<DenseInfo>,
+    #[prost(sint64, repeated, packed = "true", tag = "8")]
+    pub lat: Vec<i64>,
+    #[prost(sint64, repeated, packed = "true", tag = "9")]
+    pub lon: Vec<i64>,
+    #[prost(int32, repeated, packed = "true", tag = "10")]
+    pub keys_vals: Vec<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Way {
+    #[prost(int64, required, tag = "1")]
+    pub id: i64,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    pub keys: Vec<u32>,
+    #[prost(uint32, repeated, packed = "true", tag = "3")]
+    pub vals: Vec<u32>,
+    #[prost(message, optional, tag = "4")]
+    pub info: Option<Info>,
+    #[prost(sint64, repeated, packed = "true", tag = "8")]
+    pub refs: Vec<i64>,
+    #[prost(sint64, repeated, packed = "true", tag = "9")]
+    pub lat: Vec<i64>,
+    #[prost(sint64, repeated, packed = "true", tag = "10")]
+    pub lon: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Relation {
+    #[prost(int64, required, tag = "1")]
+    pub id: i64,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    pub keys: Vec<u32>,
+    #[prost(uint32, repeated, packed = "true", tag = "3")]
+    pub vals: Vec<u32>,
+    #[prost(message, optional, tag = "4")]
+    pub info: Option<Info>,
+    #[prost(int32, repeated, packed = "true", tag = "8")]
+    pub roles_sid: Vec<i32>,
+    #[prost(sint64, repeated, packed = "true", tag = "9")]
+    pub memids: Vec<i64>,
+    #[prost(enumeration = "MemberType", repeated, packed = "true", tag = "10")]
+    pub types: Vec<i32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Enumeration)]
+#[repr(i32)]
+pub enum MemberType {
+    Node = 0,
+    Way = 1,
+    Relation = 2,
+}
+
+static EMPTY_INFO: Info = Info {
+    version: None,
+    timestamp: None,
+    changeset: None,
+    uid: None,
+    user_sid: None,
+    visible: None,
+};
+
+static EMPTY_DENSE_INFO: DenseInfo = DenseInfo {
+    version: Vec::new(),
+    timestamp: Vec::new(),
+    changeset: Vec::new(),
+    uid: Vec::new(),
+    user_sid: Vec::new(),
+    visible: Vec::new(),
+};
+
+static EMPTY_DENSE: DenseNodes = DenseNodes {
+    id: Vec::new(),
+    denseinfo: None,
+    lat: Vec::new(),
+    lon: Vec::new(),
+    keys_vals: Vec::new(),
+};
+
+impl PrimitiveBlock {
+    pub fn get_stringtable(&self) -> &StringTable {
+        &self.stringtable
+    }
+
+    pub fn get_primitivegroup(&self) -> &[PrimitiveGroup] {
+        &self.primitivegroup
+    }
+
+    pub fn get_granularity(&self) -> i32 {
+        self.granularity()
+    }
+
+    pub fn get_lat_offset(&self) -> i64 {
+        self.lat_offset()
+    }
+
+    pub fn get_lon_offset(&self) -> i64 {
+        self.lon_offset()
+    }
+
+    pub fn get_date_granularity(&self) -> i32 {
+        self.date_granularity()
+    }
+}
+
+impl PrimitiveGroup {
+    pub fn get_nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn has_dense(&self) -> bool {
+        self.dense.is_some()
+    }
+
+    pub fn get_dense(&self) -> &DenseNodes {
+        self.dense.as_ref().unwrap_or(&EMPTY_DENSE)
+    }
+
+    pub fn get_ways(&self) -> &[Way] {
+        &self.ways
+    }
+
+    pub fn get_relations(&self) -> &[Relation] {
+        &self.relations
+    }
+}
+
+impl StringTable {
+    pub fn get_s(&self) -> &[Vec<u8>] {
+        &self.s
+    }
+}
+
+impl Info {
+    pub fn get_version(&self) -> i32 {
+        self.version()
+    }
+
+    pub fn get_timestamp(&self) -> i64 {
+        self.timestamp()
+    }
+
+    pub fn get_changeset(&self) -> i64 {
+        self.changeset()
+    }
+
+    pub fn get_uid(&self) -> i32 {
+        self.uid()
+    }
+
+    pub fn get_user_sid(&self) -> u32 {
+        self.user_sid()
+    }
+
+    pub fn has_visible(&self) -> bool {
+        self.visible.is_some()
+    }
+
+    pub fn get_visible(&self) -> bool {
+        self.visible()
+    }
+}
+
+impl DenseInfo {
+    pub fn get_version(&self) -> &[i32] {
+        &self.version
+    }
+
+    pub fn get_timestamp(&self) -> &[i64] {
+        &self.timestamp
+    }
+
+    pub fn get_changeset(&self) -> &[i64] {
+        &self.changeset
+    }
+
+    pub fn get_uid(&self) -> &[i32] {
+        &self.uid
+    }
+
+    pub fn get_user_sid(&self) -> &[i32] {
+        &self.user_sid
+    }
+
+    pub fn get_visible(&self) -> &[bool] {
+        &self.visible
+    }
+}
+
+impl Node {
+    pub fn get_id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn get_keys(&self) -> &[u32] {
+        &self.keys
+    }
+
+    pub fn get_vals(&self) -> &[u32] {
+        &self.vals
+    }
+
+    pub fn has_info(&self) -> bool {
+        self.info.is_some()
+    }
+
+    pub fn get_info(&self) -> &Info {
+        self.info.as_ref().unwrap_or(&EMPTY_INFO)
+    }
+
+    pub fn get_lat(&self) -> i64 {
+        self.lat
+    }
+
+    pub fn get_lon(&self) -> i64 {
+        self.lon
+    }
+}
+
+impl DenseNodes {
+    pub fn get_id(&self) -> &[i64] {
+        &self.id
+    }
+
+    pub fn has_denseinfo(&self) -> bool {
+        self.denseinfo.is_some()
+    }
+
+    pub fn get_denseinfo(&self) -> &DenseInfo {
+        self.denseinfo.as_ref().unwrap_or(&EMPTY_DENSE_INFO)
+    }
+
+    pub fn get_lat(&self) -> &[i64] {
+        &self.lat
+    }
+
+    pub fn get_lon(&self) -> &[i64] {
+        &self.lon
+    }
+
+    pub fn get_keys_vals(&self) -> &[i32] {
+        &self.keys_vals
+    }
+}
+
+impl Way {
+    pub fn get_id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn get_keys(&self) -> &[u32] {
+        &self.keys
+    }
+
+    pub fn get_vals(&self) -> &[u32] {
+        &self.vals
+    }
+
+    pub fn has_info(&self) -> bool {
+        self.info.is_some()
+    }
+
+    pub fn get_info(&self) -> &Info {
+        self.info.as_ref().unwrap_or(&EMPTY_INFO)
+    }
+
+    pub fn get_refs(&self) -> &[i64] {
+        &self.refs
+    }
+
+    pub fn get_lat(&self) -> &[i64] {
+        &self.lat
+    }
+
+    pub fn get_lon(&self) -> &[i64] {
+        &self.lon
+    }
+}
+
+impl Relation {
+    pub fn get_id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn get_keys(&self) -> &[u32] {
+        &self.keys
+    }
+
+    pub fn get_vals(&self) -> &[u32] {
+        &self.vals
+    }
+
+    pub fn has_info(&self) -> bool {
+        self.info.is_some()
+    }
+
+    pub fn get_info(&self) -> &Info {
+        self.info.as_ref().unwrap_or(&EMPTY_INFO)
+    }
+
+    pub fn get_roles_sid(&self) -> &[i32] {
+        &self.roles_sid
+    }
+
+    pub fn get_memids(&self) -> &[i64] {
+        &self.memids
+    }
+
+    /// Returns the member types, as the numbers of `MemberType`.
+    pub fn get_types(&self) -> &[i32] {
+        &self.types
+    }
+}
@@ -0,0 +1,109 @@
+//! Reading and writing the protobuf wire format, for walking encoded messages field by field.
+
+pub(crate) enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+impl<'a> Field<'a> {
+    pub(crate) fn varint(&self) -> anyhow::Result<u64> {
+        match self {
+            Field::Varint(value) => Ok(*value),
+            _ => bail!("Expected a varint field"),
+        }
+    }
+
+    pub(crate) fn bytes(&self) -> anyhow::Result<&'a [u8]> {
+        match self {
+            Field::Bytes(bytes) => Ok(bytes),
+            _ => bail!("Expected a length-delimited field"),
+        }
+    }
+
+    /// Reads the values of a repeated varint field, which may be packed or not.
+    pub(crate) fn packed(&self) -> anyhow::Result<Vec<u64>> {
+        match self {
+            Field::Varint(value) => Ok(vec![*value]),
+            Field::Bytes(bytes) => {
+                let mut reader = FieldReader::new(bytes);
+                let mut values = Vec::new();
+                while !reader.data.is_empty() {
+                    values.push(reader.read_varint()?);
+                }
+                Ok(values)
+            }
+            Field::Fixed => bail!("Expected a repeated varint field"),
+        }
+    }
+}
+
+/// Iterates over the fields of an encoded protobuf message without copying them.
+pub(crate) struct FieldReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> FieldReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn next_field(&mut self) -> anyhow::Result<Option<(u32, Field<'a>)>> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+        let key = self.read_varint()?;
+        let field = match key & 0x7 {
+            0 => Field::Varint(self.read_varint()?),
+            1 => {
+                self.skip(8)?;
+                Field::Fixed
+            }
+            2 => {
+                let length = self.read_varint()? as usize;
+                Field::Bytes(self.skip(length)?)
+            }
+            5 => {
+                self.skip(4)?;
+                Field::Fixed
+            }
+            wire_type => bail!("Unsupported wire type {}", wire_type),
+        };
+        Ok(Some(((key >> 3) as u32, field)))
+    }
+
+    /// Returns the fields not read yet.
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn read_varint(&mut self) -> anyhow::Result<u64> {
+        let mut value = 0u64;
+        for (index, byte) in self.data.iter().enumerate().take(10) {
+            value |= ((byte & 0x7f) as u64) << (7 * index);
+            if byte & 0x80 == 0 {
+                self.data = &self.data[index + 1..];
+                return Ok(value);
+            }
+        }
+        bail!("Invalid varint")
+    }
+
+    fn skip(&mut self, length: usize) -> anyhow::Result<&'a [u8]> {
+        if length > self.data.len() {
+            bail!("Truncated field of {} bytes", length);
+        }
+        let (skipped, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(skipped)
+    }
+}
+
+/// Appends a varint to an encoded message.
+pub(crate) fn write_varint(target: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        target.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    target.push(value as u8);
+}
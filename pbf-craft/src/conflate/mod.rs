@@ -0,0 +1,385 @@
+mod scorers;
+
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use geo::{Centroid, Coord, HaversineDistance, LineString, Point};
+use rstar::primitives::GeomWithData;
+use rstar::{RTree, AABB};
+
+use crate::models::Element;
+use crate::readers::ElementSource;
+#[cfg(feature = "fs")]
+use crate::readers::IterableReader;
+use crate::utils::LocationIndex;
+
+pub use scorers::{DistanceScorer, NameScorer, TagMatcher, TagScorer};
+
+/// The length of a degree of latitude in meters.
+const METERS_PER_DEGREE: f64 = 111_195.08;
+
+/// An element taking part in a conflation, with the location it is matched by.
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub element: Element,
+    /// The location of a node or the centroid of the nodes of a way, in degrees.
+    pub location: Coord<f64>,
+}
+
+/// Decides whether two features may match at all, e.g. only POIs of the same kind.
+pub trait Matcher: Send + Sync {
+    fn matches(&self, a: &Feature, b: &Feature) -> bool;
+}
+
+/// Rates the similarity of two features within the maximum distance.
+pub trait Scorer: Send + Sync {
+    /// Returns a score from 0 (different) to 1 (the same), or `None` if the scorer doesn't
+    /// apply to the features, e.g. a name scorer to features without names. The distance
+    /// between the features is given in meters.
+    fn score(&self, a: &Feature, b: &Feature, distance: f64) -> Option<f64>;
+}
+
+/// A pair of features found to be the same.
+#[derive(Debug, Clone)]
+pub struct FeatureMatch {
+    pub a: Feature,
+    pub b: Feature,
+    /// The distance between the features in meters.
+    pub distance: f64,
+    pub score: f64,
+}
+
+/// The result of `Conflator::conflate`.
+#[derive(Debug, Clone, Default)]
+pub struct Conflation {
+    pub matches: Vec<FeatureMatch>,
+    /// The features of the first dataset without a match.
+    pub unmatched_a: Vec<Feature>,
+    /// The features of the second dataset without a match.
+    pub unmatched_b: Vec<Feature>,
+}
+
+/// Matches the features of two datasets by their distance and the similarity of their tags, e.g.
+/// to merge POIs from another provider with OpenStreetMap.
+///
+/// The candidates of a feature are the features of the other dataset within the maximum
+/// distance which pass all matchers. The score of a candidate is the weighted mean of the
+/// scores of the scorers which apply to it, and candidates scoring below the minimum score are
+/// dropped. The pairs are then matched one-to-one, the highest scoring pairs first.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::conflate::{load_features, Conflator, TagMatcher};
+/// use pbf_craft::readers::IterableReader;
+///
+/// let is_poi = |element: &pbf_craft::models::Element| match element {
+///     pbf_craft::models::Element::Node(node) => node.tags.iter().any(|tag| tag.key == "amenity"),
+///     _ => false,
+/// };
+/// let path = "resources/andorra-latest.osm.pbf";
+/// let a = load_features(IterableReader::from_path(path).unwrap(), is_poi).unwrap();
+/// let b = load_features(IterableReader::from_path(path).unwrap(), is_poi).unwrap();
+///
+/// let mut conflator = Conflator::with_default_scorers(30.0);
+/// conflator.add_matcher(TagMatcher::new("amenity"));
+/// let conflation = conflator.conflate(a, b);
+/// assert!(conflation.unmatched_a.is_empty());
+/// ```
+pub struct Conflator {
+    max_distance: f64,
+    min_score: f64,
+    matchers: Vec<Box<dyn Matcher>>,
+    scorers: Vec<(f64, Box<dyn Scorer>)>,
+}
+
+impl Conflator {
+    /// Creates a conflator matching features up to a distance in meters, without any matchers
+    /// or scorers. The minimum score is 0.5.
+    pub fn new(max_distance: f64) -> Self {
+        Self {
+            max_distance,
+            min_score: 0.5,
+            matchers: Vec::new(),
+            scorers: Vec::new(),
+        }
+    }
+
+    /// Creates a conflator scoring the distance and, with twice the weight, the similarity of
+    /// the names.
+    pub fn with_default_scorers(max_distance: f64) -> Self {
+        let mut conflator = Self::new(max_distance);
+        conflator.add_scorer(1.0, DistanceScorer::new(max_distance));
+        conflator.add_scorer(2.0, NameScorer::default());
+        conflator
+    }
+
+    /// Sets the minimum score of a match, from 0 to 1.
+    pub fn set_min_score(&mut self, min_score: f64) {
+        self.min_score = min_score;
+    }
+
+    /// Adds a matcher all candidates have to pass.
+    pub fn add_matcher<M: Matcher + 'static>(&mut self, matcher: M) {
+        self.matchers.push(Box::new(matcher));
+    }
+
+    /// Adds a scorer with its weight in the mean score.
+    pub fn add_scorer<S: Scorer + 'static>(&mut self, weight: f64, scorer: S) {
+        self.scorers.push((weight, Box::new(scorer)));
+    }
+
+    /// Matches the features of two datasets.
+    pub fn conflate(&self, a: Vec<Feature>, b: Vec<Feature>) -> Conflation {
+        let tree: RTree<GeomWithData<[f64; 2], usize>> = RTree::bulk_load(
+            b.iter()
+                .enumerate()
+                .map(|(index, feature)| {
+                    GeomWithData::new([feature.location.x, feature.location.y], index)
+                })
+                .collect(),
+        );
+
+        // The candidate pairs as (score, distance, index in a, index in b)
+        let mut pairs: Vec<(f64, f64, usize, usize)> = Vec::new();
+        for (a_index, feature) in a.iter().enumerate() {
+            let latitude_delta = self.max_distance / METERS_PER_DEGREE;
+            let longitude_delta = latitude_delta / feature.location.y.to_radians().cos().max(1e-6);
+            let envelope = AABB::from_corners(
+                [
+                    feature.location.x - longitude_delta,
+                    feature.location.y - latitude_delta,
+                ],
+                [
+                    feature.location.x + longitude_delta,
+                    feature.location.y + latitude_delta,
+                ],
+            );
+            let point = Point::from(feature.location);
+            for candidate in tree.locate_in_envelope(&envelope) {
+                let b_index = candidate.data;
+                let distance = point.haversine_distance(&Point::from(b[b_index].location));
+                if distance > self.max_distance {
+                    continue;
+                }
+                if let Some(score) = self.score(feature, &b[b_index], distance) {
+                    if score >= self.min_score {
+                        pairs.push((score, distance, a_index, b_index));
+                    }
+                }
+            }
+        }
+        pairs.sort_by(|x, y| {
+            y.0.total_cmp(&x.0)
+                .then(x.1.total_cmp(&y.1))
+                .then((x.2, x.3).cmp(&(y.2, y.3)))
+        });
+
+        let mut a: Vec<Option<Feature>> = a.into_iter().map(Some).collect();
+        let mut b: Vec<Option<Feature>> = b.into_iter().map(Some).collect();
+        let mut conflation = Conflation::default();
+        for (score, distance, a_index, b_index) in pairs {
+            if a[a_index].is_none() || b[b_index].is_none() {
+                continue;
+            }
+            conflation.matches.push(FeatureMatch {
+                a: a[a_index].take().unwrap(),
+                b: b[b_index].take().unwrap(),
+                distance,
+                score,
+            });
+        }
+        conflation.unmatched_a = a.into_iter().flatten().collect();
+        conflation.unmatched_b = b.into_iter().flatten().collect();
+        conflation
+    }
+
+    /// Loads the features matching a filter from two PBF files and matches them.
+    #[cfg(feature = "fs")]
+    pub fn conflate_paths<P, Q, F>(&self, a: P, b: Q, filter: F) -> anyhow::Result<Conflation>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+        F: Fn(&Element) -> bool,
+    {
+        let a = load_features(IterableReader::from_path(a)?, &filter)?;
+        let b = load_features(IterableReader::from_path(b)?, &filter)?;
+        Ok(self.conflate(a, b))
+    }
+
+    /// Returns the weighted mean score of a candidate pair, or `None` if a matcher rejects it
+    /// or no scorer applies to it.
+    fn score(&self, a: &Feature, b: &Feature, distance: f64) -> Option<f64> {
+        if !self.matchers.iter().all(|matcher| matcher.matches(a, b)) {
+            return None;
+        }
+        let mut total = 0.0;
+        let mut total_weight = 0.0;
+        for (weight, scorer) in &self.scorers {
+            if let Some(score) = scorer.score(a, b, distance) {
+                total += weight * score;
+                total_weight += weight;
+            }
+        }
+        if total_weight > 0.0 {
+            Some(total / total_weight)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads the nodes and ways matching a filter from a source as features. The nodes have to come
+/// before the ways, and ways without any located node are skipped.
+pub fn load_features<S, F>(mut source: S, filter: F) -> anyhow::Result<Vec<Feature>>
+where
+    S: ElementSource,
+    F: Fn(&Element) -> bool,
+{
+    let mut locations = LocationIndex::new();
+    let mut features = Vec::new();
+    while let Some(element) = source.next_element()? {
+        let location = match &element {
+            Element::Node(node) => {
+                locations.insert(node.id, node.latitude, node.longitude);
+                Some(to_coord(node.latitude, node.longitude))
+            }
+            Element::Way(way) => {
+                let line_string: LineString<f64> = way
+                    .way_nodes
+                    .iter()
+                    .filter_map(|way_node| match (way_node.latitude, way_node.longitude) {
+                        (Some(latitude), Some(longitude)) => Some(to_coord(latitude, longitude)),
+                        _ => locations
+                            .get(way_node.id)
+                            .map(|(latitude, longitude)| to_coord(latitude, longitude)),
+                    })
+                    .collect();
+                line_string.centroid().map(Coord::from)
+            }
+            Element::Relation(_) => break,
+        };
+        if let Some(location) = location {
+            if filter(&element) {
+                features.push(Feature { element, location });
+            }
+        }
+    }
+    Ok(features)
+}
+
+fn to_coord(latitude: i64, longitude: i64) -> Coord<f64> {
+    Coord {
+        x: longitude as f64 / 1e9,
+        y: latitude as f64 / 1e9,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Tag};
+
+    fn poi(id: i64, latitude: f64, longitude: f64, tags: &[(&str, &str)]) -> Feature {
+        Feature {
+            element: Element::Node(Node {
+                id,
+                tags: tags
+                    .iter()
+                    .map(|(key, value)| Tag {
+                        key: key.to_string(),
+                        value: value.to_string(),
+                    })
+                    .collect(),
+                ..Default::default()
+            }),
+            location: Coord {
+                x: longitude,
+                y: latitude,
+            },
+        }
+    }
+
+    fn ids(features: &[Feature]) -> Vec<i64> {
+        features
+            .iter()
+            .map(|feature| feature.element.get_meta().1)
+            .collect()
+    }
+
+    #[test]
+    fn test_conflate() {
+        let a = vec![
+            poi(
+                1,
+                42.5,
+                1.5,
+                &[("amenity", "cafe"), ("name", "Café Central")],
+            ),
+            poi(2, 42.5, 1.5001, &[("amenity", "bank"), ("name", "Andbank")]),
+            poi(3, 42.6, 1.5, &[("amenity", "cafe"), ("name", "Far Away")]),
+        ];
+        let b = vec![
+            // 11 m from 1, with a similar name
+            poi(
+                11,
+                42.5001,
+                1.5,
+                &[("amenity", "cafe"), ("name", "Cafe Central")],
+            ),
+            // 3 m from 1, but a different kind of POI
+            poi(
+                12,
+                42.50002,
+                1.5,
+                &[("amenity", "pharmacy"), ("name", "Central")],
+            ),
+            // 8 m from 2
+            poi(
+                13,
+                42.5,
+                1.5002,
+                &[("amenity", "bank"), ("name", "Andbank")],
+            ),
+        ];
+        let mut conflator = Conflator::with_default_scorers(30.0);
+        conflator.add_matcher(TagMatcher::new("amenity"));
+        let conflation = conflator.conflate(a.clone(), b.clone());
+
+        let matches: Vec<(i64, i64)> = conflation
+            .matches
+            .iter()
+            .map(|m| (m.a.element.get_meta().1, m.b.element.get_meta().1))
+            .collect();
+        assert_eq!(matches, vec![(2, 13), (1, 11)]);
+        assert!(conflation.matches[1].distance > 10.0 && conflation.matches[1].distance < 12.0);
+        assert_eq!(ids(&conflation.unmatched_a), vec![3]);
+        assert_eq!(ids(&conflation.unmatched_b), vec![12]);
+
+        // Without the matcher, the distance alone pairs 1 with the pharmacy
+        let mut conflator = Conflator::new(30.0);
+        conflator.add_scorer(1.0, DistanceScorer::new(30.0));
+        let conflation = conflator.conflate(a, b);
+        let matches: Vec<(i64, i64)> = conflation
+            .matches
+            .iter()
+            .map(|m| (m.a.element.get_meta().1, m.b.element.get_meta().1))
+            .collect();
+        assert_eq!(matches, vec![(1, 12), (2, 13)]);
+    }
+
+    #[test]
+    fn test_load_features() {
+        let reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+        let features = load_features(reader, |element| match element {
+            Element::Way(way) => way.tags.iter().any(|tag| tag.key == "building"),
+            _ => false,
+        })
+        .unwrap();
+        assert!(!features.is_empty());
+        for feature in &features {
+            assert!((42.4..42.7).contains(&feature.location.y));
+            assert!((1.4..1.8).contains(&feature.location.x));
+        }
+    }
+}
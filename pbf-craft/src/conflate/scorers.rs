@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use super::{Feature, Matcher, Scorer};
+use crate::models::{Element, Tag};
+
+/// Scores the distance between two features, linearly from 1 at the same location to 0 at the
+/// maximum distance.
+#[derive(Debug, Clone)]
+pub struct DistanceScorer {
+    max_distance: f64,
+}
+
+impl DistanceScorer {
+    /// Creates a scorer for a maximum distance in meters.
+    pub fn new(max_distance: f64) -> Self {
+        Self { max_distance }
+    }
+}
+
+impl Scorer for DistanceScorer {
+    fn score(&self, _a: &Feature, _b: &Feature, distance: f64) -> Option<f64> {
+        Some((1.0 - distance / self.max_distance).clamp(0.0, 1.0))
+    }
+}
+
+/// Scores the similarity of the names of two features as the Dice coefficient of the character
+/// bigrams of their lowercase letters and digits, so that case, spaces and punctuation don't
+/// matter. It doesn't apply if a feature has no name.
+#[derive(Debug, Clone)]
+pub struct NameScorer {
+    key: String,
+}
+
+impl NameScorer {
+    /// Creates a scorer comparing the values of a tag key, e.g. `name:en`.
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+        }
+    }
+}
+
+impl Default for NameScorer {
+    fn default() -> Self {
+        Self::new("name")
+    }
+}
+
+impl Scorer for NameScorer {
+    fn score(&self, a: &Feature, b: &Feature, _distance: f64) -> Option<f64> {
+        let a = tag_value(&a.element, &self.key)?;
+        let b = tag_value(&b.element, &self.key)?;
+        Some(name_similarity(a, b))
+    }
+}
+
+/// Scores the share of tag keys whose values are the same in two features, among the given
+/// keys found in either of them. It doesn't apply if neither feature has any of the keys.
+#[derive(Debug, Clone)]
+pub struct TagScorer {
+    keys: Vec<String>,
+}
+
+impl TagScorer {
+    pub fn new<I: IntoIterator<Item = String>>(keys: I) -> Self {
+        Self {
+            keys: keys.into_iter().collect(),
+        }
+    }
+}
+
+impl Scorer for TagScorer {
+    fn score(&self, a: &Feature, b: &Feature, _distance: f64) -> Option<f64> {
+        let mut present = 0;
+        let mut same = 0;
+        for key in &self.keys {
+            let a = tag_value(&a.element, key);
+            let b = tag_value(&b.element, key);
+            if a.is_none() && b.is_none() {
+                continue;
+            }
+            present += 1;
+            if a == b {
+                same += 1;
+            }
+        }
+        if present == 0 {
+            return None;
+        }
+        Some(same as f64 / present as f64)
+    }
+}
+
+/// Only lets features match which have the same value of a tag key, e.g. `amenity`.
+#[derive(Debug, Clone)]
+pub struct TagMatcher {
+    key: String,
+}
+
+impl TagMatcher {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+        }
+    }
+}
+
+impl Matcher for TagMatcher {
+    fn matches(&self, a: &Feature, b: &Feature) -> bool {
+        match tag_value(&a.element, &self.key) {
+            Some(value) => tag_value(&b.element, &self.key) == Some(value),
+            None => false,
+        }
+    }
+}
+
+fn tag_value<'a>(element: &'a Element, key: &str) -> Option<&'a str> {
+    let tags: &[Tag] = match element {
+        Element::Node(node) => &node.tags,
+        Element::Way(way) => &way.tags,
+        Element::Relation(relation) => &relation.tags,
+    };
+    tags.iter()
+        .find(|tag| tag.key == key)
+        .map(|tag| tag.value.as_str())
+}
+
+/// Returns the Dice coefficient of the bigrams of two names, from 0 to 1.
+fn name_similarity(a: &str, b: &str) -> f64 {
+    let normalize = |name: &str| -> Vec<char> {
+        name.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (normalize(a), normalize(b));
+    if a == b {
+        return 1.0;
+    }
+    if a.len() < 2 || b.len() < 2 {
+        return 0.0;
+    }
+    let mut bigrams: HashMap<(char, char), usize> = HashMap::new();
+    for pair in a.windows(2) {
+        *bigrams.entry((pair[0], pair[1])).or_default() += 1;
+    }
+    let mut shared = 0;
+    for pair in b.windows(2) {
+        if let Some(count) = bigrams.get_mut(&(pair[0], pair[1])) {
+            if *count > 0 {
+                *count -= 1;
+                shared += 1;
+            }
+        }
+    }
+    2.0 * shared as f64 / (a.len() + b.len() - 2) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_similarity() {
+        assert_eq!(name_similarity("Café Central", "café-central"), 1.0);
+        assert_eq!(name_similarity("Cafe Central", "Café Central"), 0.8);
+        assert_eq!(name_similarity("Andbank", "Pharmacy"), 0.0);
+        assert_eq!(name_similarity("A", "B"), 0.0);
+    }
+}
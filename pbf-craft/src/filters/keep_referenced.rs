@@ -0,0 +1,232 @@
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::models::{Element, ElementType};
+use crate::readers::ElementSource;
+#[cfg(feature = "fs")]
+use crate::readers::{BlockIds, IterableReader, PbfReader};
+use crate::utils::IdSet;
+
+/// Selects the elements matching a predicate together with everything they reference, so that
+/// the selection is self-contained.
+///
+/// Besides the matching elements, the selection contains the nodes of matching ways, the node
+/// and way members of matching relations and the nodes of those ways. Relation members of
+/// relations are not added.
+///
+/// The selection is built with the standard marking passes over the data: `mark` marks the
+/// matching elements and their references, and, if a relation references ways which were not
+/// marked before, `resolve_ways` marks the nodes of those ways. A final pass then emits the
+/// elements for which `contains` returns true. `KeepReferenced::from_path` runs the marking
+/// passes over a PBF file.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::filters::KeepReferenced;
+/// use pbf_craft::models::Element;
+/// use pbf_craft::readers::IterableReader;
+///
+/// let path = "resources/andorra-latest.osm.pbf";
+/// let selection = KeepReferenced::from_path(path, |element| match element {
+///     Element::Way(way) => way.tags.iter().any(|tag| tag.key == "highway"),
+///     _ => false,
+/// })
+/// .unwrap();
+/// for element in IterableReader::from_path(path).unwrap() {
+///     if selection.contains(&element) {
+///         // Write the element
+///     }
+/// }
+/// ```
+#[derive(Default)]
+pub struct KeepReferenced {
+    nodes: IdSet,
+    ways: IdSet,
+    relations: IdSet,
+    unresolved_ways: IdSet,
+}
+
+impl KeepReferenced {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the marking passes over a PBF file, reading it once or twice.
+    #[cfg(feature = "fs")]
+    pub fn from_path<P, F>(path: P, keep: F) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(&Element) -> bool,
+    {
+        let mut selection = Self::new();
+        selection.mark(IterableReader::from_path(&path)?, keep)?;
+        if selection.needs_way_pass() {
+            // Only the node IDs of ways are needed, so tags and metadata aren't decoded
+            PbfReader::from_path(&path)?
+                .read_ids(|block_ids| selection.resolve_block_ways(block_ids))?;
+            selection.unresolved_ways = IdSet::new();
+        }
+        Ok(selection)
+    }
+
+    /// The first pass: marks the elements matching `keep` and the elements they reference.
+    pub fn mark<S, F>(&mut self, mut source: S, keep: F) -> anyhow::Result<()>
+    where
+        S: ElementSource,
+        F: Fn(&Element) -> bool,
+    {
+        while let Some(element) = source.next_element()? {
+            if !keep(&element) {
+                continue;
+            }
+            match element {
+                Element::Node(node) => {
+                    self.nodes.insert(node.id);
+                }
+                Element::Way(way) => {
+                    self.ways.insert(way.id);
+                    for way_node in &way.way_nodes {
+                        self.nodes.insert(way_node.id);
+                    }
+                }
+                Element::Relation(relation) => {
+                    self.relations.insert(relation.id);
+                    for member in &relation.members {
+                        match member.member_type {
+                            ElementType::Node => {
+                                self.nodes.insert(member.member_id);
+                            }
+                            ElementType::Way => {
+                                if self.ways.insert(member.member_id) {
+                                    self.unresolved_ways.insert(member.member_id);
+                                }
+                            }
+                            ElementType::Relation => {}
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether relations referenced ways whose nodes still have to be marked by `resolve_ways`.
+    pub fn needs_way_pass(&self) -> bool {
+        !self.unresolved_ways.is_empty()
+    }
+
+    /// The second pass: marks the nodes of the ways added as relation members.
+    pub fn resolve_ways<S: ElementSource>(&mut self, mut source: S) -> anyhow::Result<()> {
+        while let Some(element) = source.next_element()? {
+            if let Element::Way(way) = element {
+                if self.unresolved_ways.contains(way.id) {
+                    for way_node in &way.way_nodes {
+                        self.nodes.insert(way_node.id);
+                    }
+                }
+            }
+        }
+        self.unresolved_ways = IdSet::new();
+        Ok(())
+    }
+
+    #[cfg(feature = "fs")]
+    fn resolve_block_ways(&mut self, block_ids: &BlockIds) {
+        for (way_id, way_refs) in block_ids.way_ids.iter().zip(&block_ids.way_refs) {
+            if self.unresolved_ways.contains(*way_id) {
+                for node_id in way_refs {
+                    self.nodes.insert(*node_id);
+                }
+            }
+        }
+    }
+
+    /// Checks whether an element belongs to the selection.
+    pub fn contains(&self, element: &Element) -> bool {
+        match element {
+            Element::Node(node) => self.nodes.contains(node.id),
+            Element::Way(way) => self.ways.contains(way.id),
+            Element::Relation(relation) => self.relations.contains(relation.id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Relation, RelationMember, Tag, Way, WayNode};
+
+    #[test]
+    fn test_keep_referenced() {
+        let tagged = vec![Tag {
+            key: "route".to_string(),
+            value: "bus".to_string(),
+        }];
+        let elements: Vec<Element> = vec![
+            Element::Node(Node {
+                id: 1,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 2,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 3,
+                ..Default::default()
+            }),
+            Element::Way(Way {
+                id: 10,
+                way_nodes: vec![
+                    WayNode::new_without_coords(1),
+                    WayNode::new_without_coords(2),
+                ],
+                ..Default::default()
+            }),
+            Element::Way(Way {
+                id: 11,
+                way_nodes: vec![WayNode::new_without_coords(3)],
+                ..Default::default()
+            }),
+            Element::Relation(Relation {
+                id: 100,
+                tags: tagged,
+                members: vec![RelationMember {
+                    member_id: 10,
+                    member_type: ElementType::Way,
+                    role: "".into(),
+                }],
+                ..Default::default()
+            }),
+        ];
+
+        let mut selection = KeepReferenced::new();
+        selection
+            .mark(elements.clone().into_iter(), |element| match element {
+                Element::Relation(relation) => !relation.tags.is_empty(),
+                _ => false,
+            })
+            .unwrap();
+        assert!(selection.needs_way_pass());
+        selection
+            .resolve_ways(elements.clone().into_iter())
+            .unwrap();
+        assert!(!selection.needs_way_pass());
+
+        let kept: Vec<(ElementType, i64)> = elements
+            .iter()
+            .filter(|element| selection.contains(element))
+            .map(|element| element.get_meta())
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                (ElementType::Node, 1),
+                (ElementType::Node, 2),
+                (ElementType::Way, 10),
+                (ElementType::Relation, 100),
+            ]
+        );
+    }
+}
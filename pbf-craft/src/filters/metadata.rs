@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+
+use crate::models::{Element, OsmUser};
+
+/// Selects elements by their metadata: the time they were last edited, the changeset and the
+/// user who edited them.
+///
+/// All criteria that are set must match. Elements lacking the metadata a criterion needs, e.g.
+/// elements without a timestamp when `set_since` is used, never match.
+///
+/// The filter can be set on an `IterableReader` with `set_metadata_filter`, or be used as a
+/// predicate, e.g. with `PbfReader::par_find`.
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::{Duration, Utc};
+/// use pbf_craft::filters::MetadataFilter;
+/// use pbf_craft::readers::IterableReader;
+///
+/// // All elements edited in the last 30 days by these users
+/// let mut filter = MetadataFilter::new();
+/// filter.set_since(Utc::now() - Duration::days(30));
+/// filter.set_user_names(["alice".to_string(), "bob".to_string()]);
+///
+/// let mut reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// reader.set_metadata_filter(filter);
+/// for element in reader {
+///     // Process the element
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MetadataFilter {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    changeset_ids: Option<HashSet<i64>>,
+    user_names: Option<HashSet<String>>,
+}
+
+impl MetadataFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only matches elements edited at or after the given time.
+    pub fn set_since(&mut self, since: DateTime<Utc>) {
+        self.since = Some(since);
+    }
+
+    /// Only matches elements edited before the given time.
+    pub fn set_until(&mut self, until: DateTime<Utc>) {
+        self.until = Some(until);
+    }
+
+    /// Only matches elements last edited in one of the given changesets.
+    pub fn set_changeset_ids<I: IntoIterator<Item = i64>>(&mut self, changeset_ids: I) {
+        self.changeset_ids = Some(changeset_ids.into_iter().collect());
+    }
+
+    /// Only matches elements last edited by one of the given users.
+    pub fn set_user_names<I: IntoIterator<Item = String>>(&mut self, user_names: I) {
+        self.user_names = Some(user_names.into_iter().collect());
+    }
+
+    /// Whether no criterion is set, in which case all elements match.
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none()
+            && self.until.is_none()
+            && self.changeset_ids.is_none()
+            && self.user_names.is_none()
+    }
+
+    /// Checks whether an element matches all criteria.
+    pub fn matches(&self, element: &Element) -> bool {
+        let (timestamp, changeset_id, user) = match element {
+            Element::Node(node) => (node.timestamp, node.changeset_id, &node.user),
+            Element::Way(way) => (way.timestamp, way.changeset_id, &way.user),
+            Element::Relation(relation) => {
+                (relation.timestamp, relation.changeset_id, &relation.user)
+            }
+        };
+        if let Some(since) = self.since {
+            if timestamp.is_none_or(|timestamp| timestamp < since) {
+                return false;
+            }
+        }
+        if let Some(until) = self.until {
+            if timestamp.is_none_or(|timestamp| timestamp >= until) {
+                return false;
+            }
+        }
+        if let Some(changeset_ids) = &self.changeset_ids {
+            if !changeset_ids.contains(&changeset_id) {
+                return false;
+            }
+        }
+        if let Some(user_names) = &self.user_names {
+            if !user
+                .as_ref()
+                .is_some_and(|user: &OsmUser| user_names.contains(&*user.name))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Way};
+
+    #[test]
+    fn test_matches() {
+        let edited_at = |timestamp: i64, changeset_id: i64, user_name: &str| {
+            Element::Node(Node {
+                timestamp: DateTime::from_timestamp(timestamp, 0),
+                changeset_id,
+                user: Some(OsmUser {
+                    id: 1,
+                    name: user_name.into(),
+                }),
+                ..Default::default()
+            })
+        };
+
+        let mut filter = MetadataFilter::new();
+        assert!(filter.is_empty());
+        assert!(filter.matches(&Element::Way(Way::default())));
+
+        filter.set_since(DateTime::from_timestamp(1000, 0).unwrap());
+        filter.set_until(DateTime::from_timestamp(2000, 0).unwrap());
+        assert!(filter.matches(&edited_at(1000, 1, "alice")));
+        assert!(!filter.matches(&edited_at(999, 1, "alice")));
+        assert!(!filter.matches(&edited_at(2000, 1, "alice")));
+        assert!(!filter.matches(&Element::Way(Way::default())));
+
+        filter.set_changeset_ids([1, 2]);
+        filter.set_user_names(["alice".to_string()]);
+        assert!(filter.matches(&edited_at(1500, 2, "alice")));
+        assert!(!filter.matches(&edited_at(1500, 3, "alice")));
+        assert!(!filter.matches(&edited_at(1500, 2, "bob")));
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(feature = "fs")]
+mod edits;
+mod keep_referenced;
+mod metadata;
+
+#[cfg(feature = "fs")]
+pub use edits::{extract_edits, EditExtract};
+pub use keep_referenced::KeepReferenced;
+pub use metadata::MetadataFilter;
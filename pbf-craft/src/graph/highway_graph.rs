@@ -0,0 +1,300 @@
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use geo::{HaversineDistance, Point};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Element, Tag, Way};
+use crate::readers::ElementSource;
+#[cfg(feature = "fs")]
+use crate::readers::IterableReader;
+use crate::utils::LocationIndex;
+
+/// An edge of a highway graph: a way, or a part of a way between two intersections.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Edge {
+    pub way_id: i64,
+    pub from_node_id: i64,
+    pub to_node_id: i64,
+    /// The IDs of all nodes along the edge, including its first and last node.
+    pub node_ids: Vec<i64>,
+    /// The length along the nodes in meters.
+    pub length: f64,
+    /// The tags of the way, e.g. to derive the speed or whether the edge is oneway.
+    pub tags: Vec<Tag>,
+}
+
+/// The result of `HighwayGraphBuilder`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HighwayGraph {
+    /// The edges in the order of their ways, and of their position within each way.
+    pub edges: Vec<Edge>,
+    /// The IDs of the highway ways left out because the location of one of their nodes is
+    /// unknown, e.g. ways cut at the border of an extract.
+    pub skipped_way_ids: Vec<i64>,
+}
+
+/// A highway way kept until all ways are known, when intersections can be told apart.
+struct HighwayWay {
+    id: i64,
+    node_ids: Vec<i64>,
+    /// The latitudes and longitudes of the nodes in nanodegrees.
+    locations: Vec<(i64, i64)>,
+    tags: Vec<Tag>,
+}
+
+/// Builds a routing-ready edge list from the ways tagged with `highway`.
+///
+/// The data is read in a single pass: the node locations are collected in a `LocationIndex`
+/// and the highway ways, which come after the nodes, are resolved against it. Locations
+/// stored in the ways themselves, as in files with locations on ways, are used as they are.
+/// Ways tagged with `area=yes` are left out, as they aren't routable along their outline.
+///
+/// By default, each way becomes one edge from its first to its last node. When splitting at
+/// intersections is enabled, ways are split at every node they share with another highway
+/// way, or which they pass more than once, so that the edges meet only at their ends.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::graph::HighwayGraphBuilder;
+///
+/// let mut builder = HighwayGraphBuilder::new();
+/// builder.set_split_at_intersections(true);
+/// builder.set_highway_values(["primary", "secondary", "residential"]);
+/// let graph = builder
+///     .build_from_path("resources/andorra-latest.osm.pbf")
+///     .unwrap();
+/// for edge in &graph.edges {
+///     // Add the edge to the routing graph
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HighwayGraphBuilder {
+    split_at_intersections: bool,
+    highway_values: Option<HashSet<String>>,
+}
+
+impl HighwayGraphBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether ways are split at intersections. Defaults to `false`.
+    pub fn set_split_at_intersections(&mut self, split_at_intersections: bool) {
+        self.split_at_intersections = split_at_intersections;
+    }
+
+    /// Only uses the ways whose `highway` tag has one of the given values, e.g. to leave out
+    /// footways. All highway ways are used by default.
+    pub fn set_highway_values<I, V>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.highway_values = Some(values.into_iter().map(Into::into).collect());
+    }
+
+    /// Builds the graph of a PBF file.
+    #[cfg(feature = "fs")]
+    pub fn build_from_path<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<HighwayGraph> {
+        self.build(IterableReader::from_path(path)?)
+    }
+
+    /// Builds the graph of a source whose nodes come before its ways.
+    pub fn build<S: ElementSource>(&self, mut source: S) -> anyhow::Result<HighwayGraph> {
+        let mut location_index = LocationIndex::new();
+        let mut ways = Vec::new();
+        let mut graph = HighwayGraph::default();
+        while let Some(element) = source.next_element()? {
+            match element {
+                Element::Node(node) => {
+                    location_index.insert(node.id, node.latitude, node.longitude)
+                }
+                Element::Way(way) => {
+                    if !self.is_routable(&way) {
+                        continue;
+                    }
+                    match locate(&way, &location_index) {
+                        Some(locations) => ways.push(HighwayWay {
+                            id: way.id,
+                            node_ids: way.way_nodes.iter().map(|wn| wn.id).collect(),
+                            locations,
+                            tags: way.tags,
+                        }),
+                        None => graph.skipped_way_ids.push(way.id),
+                    }
+                }
+                // Relations come last, so there is nothing left to read
+                Element::Relation(_) => break,
+            }
+        }
+
+        let intersections = if self.split_at_intersections {
+            find_intersections(&ways)
+        } else {
+            HashSet::new()
+        };
+        for way in &ways {
+            split_way(way, &intersections, &mut graph.edges);
+        }
+        Ok(graph)
+    }
+
+    fn is_routable(&self, way: &Way) -> bool {
+        if way.way_nodes.len() < 2 {
+            return false;
+        }
+        let value_of = |key: &str| {
+            way.tags
+                .iter()
+                .find(|tag| tag.key == key)
+                .map(|tag| tag.value.as_str())
+        };
+        if value_of("area") == Some("yes") {
+            return false;
+        }
+        match (value_of("highway"), &self.highway_values) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(highway), Some(values)) => values.contains(highway),
+        }
+    }
+}
+
+/// Returns the locations of the nodes of a way, or `None` if one of them is unknown.
+fn locate(way: &Way, location_index: &LocationIndex) -> Option<Vec<(i64, i64)>> {
+    way.way_nodes
+        .iter()
+        .map(|way_node| match (way_node.latitude, way_node.longitude) {
+            (Some(latitude), Some(longitude)) => Some((latitude, longitude)),
+            _ => location_index.get(way_node.id),
+        })
+        .collect()
+}
+
+/// Returns the nodes referenced more than once by all ways together.
+fn find_intersections(ways: &[HighwayWay]) -> HashSet<i64> {
+    let mut reference_counts: HashMap<i64, u32> = HashMap::new();
+    for way in ways {
+        for node_id in &way.node_ids {
+            *reference_counts.entry(*node_id).or_insert(0) += 1;
+        }
+    }
+    reference_counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(node_id, _)| node_id)
+        .collect()
+}
+
+/// Adds the edges of a way, split at the given intersections, to `edges`.
+fn split_way(way: &HighwayWay, intersections: &HashSet<i64>, edges: &mut Vec<Edge>) {
+    let last = way.node_ids.len() - 1;
+    let mut start = 0;
+    let mut length = 0.0;
+    for index in 1..=last {
+        length += distance(way.locations[index - 1], way.locations[index]);
+        if index == last || intersections.contains(&way.node_ids[index]) {
+            edges.push(Edge {
+                way_id: way.id,
+                from_node_id: way.node_ids[start],
+                to_node_id: way.node_ids[index],
+                node_ids: way.node_ids[start..=index].to_vec(),
+                length,
+                tags: way.tags.clone(),
+            });
+            start = index;
+            length = 0.0;
+        }
+    }
+}
+
+/// Returns the great-circle distance in meters between two locations in nanodegrees.
+fn distance(from: (i64, i64), to: (i64, i64)) -> f64 {
+    let point = |(latitude, longitude): (i64, i64)| {
+        Point::new(longitude as f64 * 1e-9, latitude as f64 * 1e-9)
+    };
+    point(from).haversine_distance(&point(to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, WayNode};
+
+    fn node(id: i64, latitude: i64, longitude: i64) -> Element {
+        Element::Node(Node {
+            id,
+            latitude,
+            longitude,
+            ..Default::default()
+        })
+    }
+
+    fn way(id: i64, highway: &str, node_ids: &[i64]) -> Element {
+        Element::Way(Way {
+            id,
+            tags: vec![Tag {
+                key: "highway".to_string(),
+                value: highway.to_string(),
+            }],
+            way_nodes: node_ids
+                .iter()
+                .map(|id| WayNode::new_without_coords(*id))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn elements() -> Vec<Element> {
+        // Nodes 1 to 3 run north along a meridian, 1 km apart, and node 4 lies east of node 2
+        vec![
+            node(1, 0, 0),
+            node(2, 8_993_216, 0),
+            node(3, 17_986_432, 0),
+            node(4, 8_993_216, 8_993_216),
+            way(10, "primary", &[1, 2, 3]),
+            way(11, "footway", &[2, 4]),
+            way(12, "residential", &[3, 5]),
+            Element::Way(Way {
+                id: 13,
+                way_nodes: vec![WayNode::new_without_coords(1)],
+                ..Default::default()
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_build() {
+        let builder = HighwayGraphBuilder::new();
+        let graph = builder.build(elements().into_iter()).unwrap();
+        assert_eq!(graph.skipped_way_ids, vec![12]);
+        let edges: Vec<(i64, i64, i64)> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.way_id, edge.from_node_id, edge.to_node_id))
+            .collect();
+        assert_eq!(edges, vec![(10, 1, 3), (11, 2, 4)]);
+        assert!((graph.edges[0].length - 2000.0).abs() < 1.0);
+        assert_eq!(graph.edges[0].node_ids, vec![1, 2, 3]);
+        assert_eq!(graph.edges[0].tags[0].value, "primary");
+
+        let mut builder = HighwayGraphBuilder::new();
+        builder.set_split_at_intersections(true);
+        let graph = builder.build(elements().into_iter()).unwrap();
+        let edges: Vec<(i64, i64, i64)> = graph
+            .edges
+            .iter()
+            .map(|edge| (edge.way_id, edge.from_node_id, edge.to_node_id))
+            .collect();
+        assert_eq!(edges, vec![(10, 1, 2), (10, 2, 3), (11, 2, 4)]);
+        assert!((graph.edges[1].length - 1000.0).abs() < 1.0);
+
+        builder.set_highway_values(["primary"]);
+        let graph = builder.build(elements().into_iter()).unwrap();
+        assert_eq!(graph.edges.len(), 1);
+        assert!(graph.skipped_way_ids.is_empty());
+    }
+}
@@ -0,0 +1,3 @@
+mod highway_graph;
+
+pub use highway_graph::{Edge, HighwayGraph, HighwayGraphBuilder};
@@ -0,0 +1,188 @@
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+
+use crate::models::Element;
+use crate::readers::ElementSource;
+#[cfg(feature = "fs")]
+use crate::readers::IterableReader;
+use crate::writers::ElementSink;
+
+/// Reduces a history file to the snapshot of the data valid at an instant, e.g. to analyze how
+/// the map evolved, and writes the elements to a sink, which is finished afterwards. Returns the
+/// number of elements written.
+///
+/// For each element, the latest version with a timestamp at or before the instant is written
+/// if it is visible. Elements created later or deleted by then are left out, as are versions
+/// without a timestamp. The versions of an element are expected to come one after another, as
+/// in history files, so the output keeps the order of the input.
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::DateTime;
+/// use pbf_craft::history::snapshot_at;
+/// use pbf_craft::readers::IterableReader;
+/// use pbf_craft::writers::PbfWriter;
+///
+/// let reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let timestamp = DateTime::parse_from_rfc3339("2020-01-01T00:00:00Z").unwrap().to_utc();
+/// let mut writer = PbfWriter::new(Vec::new(), true);
+/// let written = snapshot_at(reader, timestamp, &mut writer).unwrap();
+/// println!("{} elements were valid at {}", written, timestamp);
+/// ```
+pub fn snapshot_at<S, W>(source: S, timestamp: DateTime<Utc>, sink: &mut W) -> anyhow::Result<u64>
+where
+    S: ElementSource,
+    W: ElementSink,
+{
+    let mut history = Versions::new(source);
+    let mut written = 0;
+    while let Some(versions) = history.next_versions()? {
+        let valid = versions.into_iter().rfind(|element| {
+            let version_timestamp = match element {
+                Element::Node(node) => node.timestamp,
+                Element::Way(way) => way.timestamp,
+                Element::Relation(relation) => relation.timestamp,
+            };
+            version_timestamp.is_some_and(|version_timestamp| version_timestamp <= timestamp)
+        });
+        if let Some(element) = valid {
+            if history_meta(&element).2 {
+                sink.write(element)?;
+                written += 1;
+            }
+        }
+    }
+    sink.finish()?;
+    Ok(written)
+}
+
+/// Reads the snapshot at an instant of a history PBF file, see `snapshot_at`.
+#[cfg(feature = "fs")]
+pub fn snapshot_at_path<P, W>(
+    path: P,
+    timestamp: DateTime<Utc>,
+    sink: &mut W,
+) -> anyhow::Result<u64>
+where
+    P: AsRef<Path>,
+    W: ElementSink,
+{
+    snapshot_at(IterableReader::from_path(path)?, timestamp, sink)
+}
+
+/// Groups the consecutive versions of each element of a source.
+pub(crate) struct Versions<S: ElementSource> {
+    source: S,
+    next: Option<Element>,
+}
+
+impl<S: ElementSource> Versions<S> {
+    pub(crate) fn new(source: S) -> Self {
+        Self { source, next: None }
+    }
+
+    /// Returns the versions of the next element, sorted by version.
+    pub(crate) fn next_versions(&mut self) -> anyhow::Result<Option<Vec<Element>>> {
+        let first = match self.next.take() {
+            Some(element) => element,
+            None => match self.source.next_element()? {
+                Some(element) => element,
+                None => return Ok(None),
+            },
+        };
+        let meta = first.get_meta();
+        let mut versions = vec![first];
+        while let Some(element) = self.source.next_element()? {
+            if element.get_meta() != meta {
+                self.next = Some(element);
+                break;
+            }
+            versions.push(element);
+        }
+        versions.sort_by_key(|element| history_meta(element).0);
+        Ok(Some(versions))
+    }
+}
+
+/// Returns the version, the changeset ID and the visibility of an element.
+pub(crate) fn history_meta(element: &Element) -> (i32, i64, bool) {
+    match element {
+        Element::Node(node) => (node.version, node.changeset_id, node.visible),
+        Element::Way(way) => (way.version, way.changeset_id, way.visible),
+        Element::Relation(relation) => (relation.version, relation.changeset_id, relation.visible),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Way, WayNode};
+
+    struct VecSink(Vec<Element>);
+
+    impl ElementSink for VecSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.0.push(element);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_snapshot_at() {
+        let at = |seconds: i64| DateTime::from_timestamp(seconds, 0);
+        let node = |id: i64, version: i32, seconds: i64, visible: bool| {
+            Element::Node(Node {
+                id,
+                version,
+                timestamp: at(seconds),
+                visible,
+                ..Default::default()
+            })
+        };
+        let history = vec![
+            node(1, 1, 100, true),
+            node(1, 2, 200, true),
+            node(1, 3, 300, true),
+            // Deleted before the instant
+            node(2, 1, 100, true),
+            node(2, 2, 150, false),
+            // Created after the instant
+            node(3, 1, 250, true),
+            // Deleted after the instant
+            node(4, 1, 100, true),
+            node(4, 2, 300, false),
+            Element::Way(Way {
+                id: 1,
+                version: 1,
+                timestamp: at(200),
+                visible: true,
+                way_nodes: vec![
+                    WayNode::new_without_coords(1),
+                    WayNode::new_without_coords(4),
+                ],
+                ..Default::default()
+            }),
+        ];
+
+        let mut sink = VecSink(Vec::new());
+        let written = snapshot_at(history.into_iter(), at(200).unwrap(), &mut sink).unwrap();
+        assert_eq!(written, 3);
+        let versions: Vec<(i64, i32)> = sink
+            .0
+            .iter()
+            .map(|element| match element {
+                Element::Node(node) => (node.id, node.version),
+                Element::Way(way) => (way.id + 100, way.version),
+                Element::Relation(_) => unreachable!(),
+            })
+            .collect();
+        assert_eq!(versions, vec![(1, 2), (4, 1), (101, 1)]);
+    }
+}
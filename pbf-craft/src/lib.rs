@@ -7,6 +7,29 @@
 //! Since this crate uses the btree_cursors feature, it requires you to use the **nightly**
 //! version of rust.
 //!
+//! # Features
+//!
+//! * `fs` (default) - Reads and writes files by path, e.g. with `PbfReader::from_path`, and
+//!   enables `IndexedReader` and the analyses which read a file several times.
+//! * `parallel` (default) - Decodes blobs in parallel with rayon, e.g. in `PbfReader::par_find`.
+//! * `bz2` - Reads the bzip2 compressed blobs of files written before 2011.
+//! * `tracing` - Emits logs and spans through the `tracing` crate.
+//! * `testing` - Exposes the `testing` module generating synthetic datasets.
+//! * `datafusion` - Exposes the `sql` module, whose DataFusion tables run SQL queries over a
+//!   PBF file, with the filters on tags and bounding boxes pushed down into the scan.
+//! * `tiles` - Exposes the `tiles` module generating Mapbox Vector Tiles.
+//! * `prost` - Parses the data blocks with prost instead of rust-protobuf. The messages of
+//!   `DecodedBlob::OsmData` and `PrimitiveReader::block` are then those of
+//!   `proto::prost_osmformat`.
+//! * `proj` - Projects the coordinates of GeoJSON outputs, e.g. to web mercator meters, with
+//!   `utils::Projection`.
+//! * `interning` - Interns user names and relation roles in the global `models::StringPool`,
+//!   so that the elements of all blobs share one `Arc<str>` per distinct string instead of
+//!   allocating them one by one. The pool only grows: it never evicts strings.
+//!
+//! Without them, the crate builds for `wasm32-unknown-unknown`, so that browser tools can read
+//! small PBF data held in memory with `PbfReader::from_bytes` or `IterableReader::from_bytes`.
+//!
 //! # Example
 //!
 //! Read PBF data from a file:
@@ -28,12 +51,13 @@
 //! Read PBF data with dependencies:
 //!
 //! ```rust
-//! use pbf_craft::models::ElementType;
+//! use pbf_craft::models::{ElementId, ElementType};
 //! use pbf_craft::readers::IndexedReader;
 //!
 //! let mut indexed_reader =
 //!     IndexedReader::from_path_with_cache("resources/andorra-latest.osm.pbf", 1000).unwrap();
-//! let element_list = indexed_reader.get_with_deps(&ElementType::Way, 12345678).unwrap();
+//! let way_id = ElementId::new(ElementType::Way, 12345678);
+//! let element_list = indexed_reader.get_with_deps(way_id).unwrap();
 //! ```
 //!
 //! Write PBF data to a file:
@@ -53,17 +77,58 @@
 
 extern crate test;
 
+#[macro_use]
+mod logging;
+
+/// Contains analyses of OpenStreetMap data.
+pub mod analysis;
 mod codecs;
+/// Contains the matching of elements across two datasets by geometry and tags.
+pub mod conflate;
+/// Contains filters selecting elements from a stream of elements.
+pub mod filters;
+/// Contains the extraction of routing graphs from highway ways.
+pub mod graph;
+/// Contains transforms of full-history files.
+pub mod history;
 /// Contains models for elements of OpenStreetMap data.
 pub mod models;
+/// Contains a bounded pipeline reading, processing and writing elements on separate threads.
+#[cfg(feature = "parallel")]
+pub mod pipeline;
+/// Contains an evaluator for a subset of OverpassQL.
+pub mod query;
 /// Contains readers for reading PBF data.
 pub mod readers;
-mod utils;
+/// Contains spatial indexes and joins of elements with polygons.
+pub mod spatial;
+/// Contains DataFusion tables for running SQL queries over PBF files.
+#[cfg(feature = "datafusion")]
+pub mod sql;
+/// Contains generators of synthetic datasets for tests and benchmarks.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+/// Contains the generation of Mapbox Vector Tiles.
+#[cfg(feature = "tiles")]
+pub mod tiles;
+/// Contains the extraction of public transport routes.
+#[cfg(feature = "fs")]
+pub mod transit;
+/// Contains utilities such as compact indexes of element IDs and node locations.
+pub mod utils;
+/// Contains rules for validating elements.
+pub mod validation;
 /// Contains writers for writing PBF data.
 pub mod writers;
 
-mod proto {
+/// Contains the protobuf messages of the PBF format, generated from its `.proto` files.
+pub mod proto {
     include!(concat!(env!("OUT_DIR"), "/mod.rs"));
+
+    /// The messages of data blocks decoded with prost, as returned by `PrimitiveReader::block`
+    /// with the `prost` feature.
+    #[cfg(feature = "prost")]
+    pub use crate::codecs::prost_osmformat;
 }
 
 #[macro_use]
@@ -0,0 +1,55 @@
+//! Logging macros forwarding to the `tracing` crate when the `tracing` feature is enabled.
+//!
+//! Without the feature, events are discarded and spans are no-ops, so the crate never writes to
+//! stderr on its own. Events take a format string; spans take a name and `field = value` pairs.
+
+// Some macros are only used by code behind optional features
+#![allow(unused_macros)]
+
+/// A stand-in for an entered span when the `tracing` feature is disabled.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoopSpan;
+
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+macro_rules! span {
+    ($level:ident, $name:expr $(, $field:ident = $value:expr)*) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::$level!($name $(, $field = $value)*).entered();
+        #[cfg(not(feature = "tracing"))]
+        let span = {
+            $(let _ = &$value;)*
+            crate::logging::NoopSpan
+        };
+        span
+    }};
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => { event!(warn, $($arg)+) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { event!(debug, $($arg)+) };
+}
+
+macro_rules! trace {
+    ($($arg:tt)+) => { event!(trace, $($arg)+) };
+}
+
+/// Enters a span at the debug level until the returned guard is dropped.
+macro_rules! debug_span {
+    ($($arg:tt)+) => { span!(debug_span, $($arg)+) };
+}
+
+/// Enters a span at the trace level until the returned guard is dropped.
+macro_rules! trace_span {
+    ($($arg:tt)+) => { span!(trace_span, $($arg)+) };
+}
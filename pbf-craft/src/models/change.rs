@@ -0,0 +1,84 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use super::Element;
+use crate::utils::xml;
+
+/// A set of changes in the spirit of an OsmChange (`.osc`) document.
+///
+/// Each list is kept in the order the changes should be applied: creations and modifications
+/// come nodes first, deletions come relations first so that no element is deleted while it is
+/// still referenced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OsmChange {
+    pub create: Vec<Element>,
+    pub modify: Vec<Element>,
+    pub delete: Vec<Element>,
+}
+
+impl OsmChange {
+    /// Returns the total number of changed elements.
+    pub fn len(&self) -> usize {
+        self.create.len() + self.modify.len() + self.delete.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Writes the changes as an OsmChange XML document.
+    pub fn write_osc<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            writer,
+            "<osmChange version=\"0.6\" generator=\"pbf-craft {}\">",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        for (action, elements) in [
+            ("create", &self.create),
+            ("modify", &self.modify),
+            ("delete", &self.delete),
+        ] {
+            if elements.is_empty() {
+                continue;
+            }
+            writeln!(writer, "  <{}>", action)?;
+            for element in elements {
+                xml::write_element(writer, element, "    ")?;
+            }
+            writeln!(writer, "  </{}>", action)?;
+        }
+        writeln!(writer, "</osmChange>")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Returns true if two elements carry the same data, ignoring their metadata
+/// (version, timestamp, user and changeset).
+pub(crate) fn has_same_content(a: &Element, b: &Element) -> bool {
+    match (a, b) {
+        (Element::Node(a), Element::Node(b)) => {
+            a.id == b.id
+                && a.latitude == b.latitude
+                && a.longitude == b.longitude
+                && a.visible == b.visible
+                && a.tags == b.tags
+        }
+        (Element::Way(a), Element::Way(b)) => {
+            a.id == b.id
+                && a.visible == b.visible
+                && a.tags == b.tags
+                && a.way_nodes.len() == b.way_nodes.len()
+                && a.way_nodes
+                    .iter()
+                    .zip(b.way_nodes.iter())
+                    .all(|(x, y)| x.id == y.id)
+        }
+        (Element::Relation(a), Element::Relation(b)) => {
+            a.id == b.id && a.visible == b.visible && a.tags == b.tags && a.members == b.members
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::BTreeMap;
+
+use super::change::{has_same_content, OsmChange};
+use super::{BasicElement, Element, ElementType, Node, Relation, Way};
+use crate::readers::ElementSource;
+use crate::utils::{EstimatedSize, MemoryBudget, MemoryReservation};
+use crate::writers::ElementSink;
+
+/// An in-memory store of OSM elements keyed by their IDs.
+///
+/// `OsmDataset` is meant for small-area editing workflows: load an extract, mutate it and write
+/// it back. Elements are kept sorted by ID, so writing the dataset always produces the canonical
+/// order required by the PBF specification.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, ElementType, OsmDataset, Tag};
+/// use pbf_craft::readers::IterableReader;
+/// use pbf_craft::writers::PbfWriter;
+///
+/// let reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let mut dataset = OsmDataset::from_reader(reader).unwrap();
+///
+/// let mut way = dataset.way(1055523837).unwrap().clone();
+/// way.tags.push(Tag { key: "note".to_string(), value: "checked".to_string() });
+/// dataset.update(Element::Way(way)).unwrap();
+///
+/// let mut writer = PbfWriter::new(Vec::new(), true);
+/// dataset.to_writer(&mut writer).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OsmDataset {
+    nodes: BTreeMap<i64, Node>,
+    ways: BTreeMap<i64, Way>,
+    relations: BTreeMap<i64, Relation>,
+    reservation: Option<MemoryReservation>,
+}
+
+impl OsmDataset {
+    /// Creates an empty dataset.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a dataset holding all elements read from the source.
+    ///
+    /// If the source contains an element more than once, the last one wins.
+    pub fn from_reader<S: ElementSource>(mut source: S) -> anyhow::Result<Self> {
+        let mut dataset = Self::new();
+        while let Some(element) = source.next_element()? {
+            dataset.insert(element);
+        }
+        Ok(dataset)
+    }
+
+    /// Charges the memory of the elements to a budget, see `MemoryBudget`. The dataset can't
+    /// give memory back, so it's charged even if this exceeds the budget.
+    ///
+    /// Elements changed through the `_mut` accessors keep the size they were inserted with.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        let mut reservation = budget.reservation();
+        reservation.grow(
+            self.nodes.values().map(Node::estimated_size).sum::<usize>()
+                + self.ways.values().map(Way::estimated_size).sum::<usize>()
+                + self
+                    .relations
+                    .values()
+                    .map(Relation::estimated_size)
+                    .sum::<usize>(),
+        );
+        self.reservation = Some(reservation);
+    }
+
+    /// Writes all elements to the sink in the canonical order and finishes the sink.
+    pub fn to_writer<S: ElementSink>(&self, sink: &mut S) -> anyhow::Result<()> {
+        for node in self.nodes.values() {
+            sink.write(Element::Node(node.clone()))?;
+        }
+        for way in self.ways.values() {
+            sink.write(Element::Way(way.clone()))?;
+        }
+        for relation in self.relations.values() {
+            sink.write(Element::Relation(relation.clone()))?;
+        }
+        sink.finish()
+    }
+
+    /// Inserts an element, returning the element previously stored under the same type and ID.
+    pub fn insert(&mut self, element: Element) -> Option<Element> {
+        if let Some(reservation) = &mut self.reservation {
+            reservation.grow(element.estimated_size());
+        }
+        let previous = match element {
+            Element::Node(node) => self.nodes.insert(node.id, node).map(Element::Node),
+            Element::Way(way) => self.ways.insert(way.id, way).map(Element::Way),
+            Element::Relation(relation) => self
+                .relations
+                .insert(relation.id, relation)
+                .map(Element::Relation),
+        };
+        self.release(previous.as_ref());
+        previous
+    }
+
+    /// Replaces an existing element and returns the previous version.
+    ///
+    /// Returns an error if there is no element of the same type and ID in the dataset.
+    pub fn update(&mut self, element: Element) -> anyhow::Result<Element> {
+        let (element_type, element_id) = element.get_meta();
+        if !self.contains(&element_type, element_id) {
+            bail!(
+                "{:?}#{} does not exist in the dataset",
+                element_type,
+                element_id
+            );
+        }
+        Ok(self.insert(element).unwrap())
+    }
+
+    /// Removes an element and returns it, if it exists.
+    pub fn delete(&mut self, element_type: &ElementType, element_id: i64) -> Option<Element> {
+        let deleted = match element_type {
+            ElementType::Node => self.nodes.remove(&element_id).map(Element::Node),
+            ElementType::Way => self.ways.remove(&element_id).map(Element::Way),
+            ElementType::Relation => self.relations.remove(&element_id).map(Element::Relation),
+        };
+        self.release(deleted.as_ref());
+        deleted
+    }
+
+    fn release(&mut self, element: Option<&Element>) {
+        if let (Some(reservation), Some(element)) = (&mut self.reservation, element) {
+            reservation.shrink(element.estimated_size());
+        }
+    }
+
+    /// Returns true if the dataset contains an element of the given type and ID.
+    pub fn contains(&self, element_type: &ElementType, element_id: i64) -> bool {
+        match element_type {
+            ElementType::Node => self.nodes.contains_key(&element_id),
+            ElementType::Way => self.ways.contains_key(&element_id),
+            ElementType::Relation => self.relations.contains_key(&element_id),
+        }
+    }
+
+    /// Returns a copy of the element of the given type and ID.
+    pub fn get(&self, element_type: &ElementType, element_id: i64) -> Option<Element> {
+        match element_type {
+            ElementType::Node => self.node(element_id).cloned().map(Element::Node),
+            ElementType::Way => self.way(element_id).cloned().map(Element::Way),
+            ElementType::Relation => self.relation(element_id).cloned().map(Element::Relation),
+        }
+    }
+
+    pub fn node(&self, node_id: i64) -> Option<&Node> {
+        self.nodes.get(&node_id)
+    }
+
+    pub fn node_mut(&mut self, node_id: i64) -> Option<&mut Node> {
+        self.nodes.get_mut(&node_id)
+    }
+
+    pub fn way(&self, way_id: i64) -> Option<&Way> {
+        self.ways.get(&way_id)
+    }
+
+    pub fn way_mut(&mut self, way_id: i64) -> Option<&mut Way> {
+        self.ways.get_mut(&way_id)
+    }
+
+    pub fn relation(&self, relation_id: i64) -> Option<&Relation> {
+        self.relations.get(&relation_id)
+    }
+
+    pub fn relation_mut(&mut self, relation_id: i64) -> Option<&mut Relation> {
+        self.relations.get_mut(&relation_id)
+    }
+
+    /// Returns an iterator over the nodes in ascending ID order.
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.values()
+    }
+
+    /// Returns an iterator over the ways in ascending ID order.
+    pub fn ways(&self) -> impl Iterator<Item = &Way> {
+        self.ways.values()
+    }
+
+    /// Returns an iterator over the relations in ascending ID order.
+    pub fn relations(&self) -> impl Iterator<Item = &Relation> {
+        self.relations.values()
+    }
+
+    /// Returns the total number of elements.
+    pub fn len(&self) -> usize {
+        self.nodes.len() + self.ways.len() + self.relations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the nodes of a way which exist in the dataset, in the order of the way.
+    pub fn way_nodes(&self, way_id: i64) -> Vec<&Node> {
+        match self.ways.get(&way_id) {
+            Some(way) => way
+                .way_nodes
+                .iter()
+                .filter_map(|way_node| self.nodes.get(&way_node.id))
+                .collect(),
+            None => Vec::with_capacity(0),
+        }
+    }
+
+    /// Returns the ways which contain the given node.
+    pub fn ways_of_node(&self, node_id: i64) -> Vec<&Way> {
+        self.ways
+            .values()
+            .filter(|way| way.way_nodes.iter().any(|way_node| way_node.id == node_id))
+            .collect()
+    }
+
+    /// Returns the relations which have the given element as a member.
+    pub fn relations_of(&self, element_type: &ElementType, element_id: i64) -> Vec<&Relation> {
+        self.relations
+            .values()
+            .filter(|relation| {
+                relation.members.iter().any(|member| {
+                    member.member_id == element_id && member.member_type == *element_type
+                })
+            })
+            .collect()
+    }
+
+    /// Returns true if any way or relation in the dataset refers to the given element.
+    pub fn is_referenced(&self, element_type: &ElementType, element_id: i64) -> bool {
+        if *element_type == ElementType::Node && !self.ways_of_node(element_id).is_empty() {
+            return true;
+        }
+        !self.relations_of(element_type, element_id).is_empty()
+    }
+
+    /// Computes the changes which turn this dataset into `target`.
+    ///
+    /// Elements are compared by their data only, so a difference in metadata alone is not a
+    /// modification. Modified and deleted elements get the version of the element in this
+    /// dataset plus one, deleted ones are also marked invisible. Created elements keep their
+    /// version, or get version 1 if they have none.
+    pub fn diff(&self, target: &OsmDataset) -> OsmChange {
+        let mut change = OsmChange::default();
+        diff_maps(&self.nodes, &target.nodes, Element::Node, &mut change);
+        diff_maps(&self.ways, &target.ways, Element::Way, &mut change);
+        diff_maps(
+            &self.relations,
+            &target.relations,
+            Element::Relation,
+            &mut change,
+        );
+        // dependents must be deleted before the elements they refer to
+        change.delete.reverse();
+        change
+    }
+}
+
+fn diff_maps<T: BasicElement + Clone>(
+    base: &BTreeMap<i64, T>,
+    target: &BTreeMap<i64, T>,
+    wrap: fn(T) -> Element,
+    change: &mut OsmChange,
+) {
+    for (id, target_element) in target {
+        match base.get(id) {
+            None => {
+                let version = target_element.get_version().max(1);
+                change
+                    .create
+                    .push(with_version(wrap(target_element.clone()), version, true));
+            }
+            Some(base_element) => {
+                let base_wrapped = wrap(base_element.clone());
+                let target_wrapped = wrap(target_element.clone());
+                if !has_same_content(&base_wrapped, &target_wrapped) {
+                    let version = base_element.get_version() + 1;
+                    let visible = target_element.is_visible();
+                    change
+                        .modify
+                        .push(with_version(target_wrapped, version, visible));
+                }
+            }
+        }
+    }
+    for (id, base_element) in base {
+        if !target.contains_key(id) {
+            let version = base_element.get_version() + 1;
+            change
+                .delete
+                .push(with_version(wrap(base_element.clone()), version, false));
+        }
+    }
+}
+
+pub(crate) fn with_version(mut element: Element, version: i32, visible: bool) -> Element {
+    match &mut element {
+        Element::Node(node) => {
+            node.version = version;
+            node.visible = visible;
+        }
+        Element::Way(way) => {
+            way.version = version;
+            way.visible = visible;
+        }
+        Element::Relation(relation) => {
+            relation.version = version;
+            relation.visible = visible;
+        }
+    }
+    element
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Tag, WayNode};
+    use crate::readers::{IterableReader, PbfReader};
+    use crate::writers::{CountingSink, PbfWriter};
+
+    #[test]
+    fn test_load_and_write() {
+        let reader = IterableReader::from_path("./resources/andorra-latest.osm.pbf").unwrap();
+        let mut dataset = OsmDataset::from_reader(reader).unwrap();
+
+        let mut counter = CountingSink::new();
+        dataset.to_writer(&mut counter).unwrap();
+        assert_eq!(counter.total() as usize, dataset.len());
+
+        let mut way = dataset.way(1055523837).unwrap().clone();
+        way.tags.push(Tag {
+            key: "note".to_string(),
+            value: "checked".to_string(),
+        });
+        dataset.update(Element::Way(way)).unwrap();
+
+        let mut writer = PbfWriter::new(Vec::new(), true);
+        dataset.to_writer(&mut writer).unwrap();
+    }
+
+    #[test]
+    fn test_edit_and_lookup() {
+        let mut dataset = OsmDataset::new();
+        for id in 1..=3 {
+            dataset.insert(Element::Node(Node {
+                id,
+                ..Default::default()
+            }));
+        }
+        dataset.insert(Element::Way(Way {
+            id: 10,
+            way_nodes: vec![
+                WayNode::new_without_coords(1),
+                WayNode::new_without_coords(2),
+            ],
+            ..Default::default()
+        }));
+
+        assert_eq!(dataset.len(), 4);
+        assert_eq!(dataset.way_nodes(10).len(), 2);
+        assert_eq!(dataset.ways_of_node(2)[0].id, 10);
+        assert!(dataset.is_referenced(&ElementType::Node, 1));
+        assert!(!dataset.is_referenced(&ElementType::Node, 3));
+
+        assert!(dataset
+            .update(Element::Relation(Relation::default()))
+            .is_err());
+        assert!(dataset.delete(&ElementType::Node, 3).is_some());
+        assert!(!dataset.contains(&ElementType::Node, 3));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut dataset = OsmDataset::new();
+        dataset.insert(Element::Node(Node {
+            id: 1,
+            visible: true,
+            ..Default::default()
+        }));
+        let mut output = Vec::new();
+        {
+            let mut writer = PbfWriter::new(&mut output, true);
+            dataset.to_writer(&mut writer).unwrap();
+        }
+        let reader = IterableReader::new(PbfReader::new(output.as_slice()));
+        let reloaded = OsmDataset::from_reader(reader).unwrap();
+        assert_eq!(reloaded.len(), 1);
+        assert!(reloaded.node(1).is_some());
+    }
+
+    #[test]
+    fn test_diff() {
+        let node = |id: i64, latitude: i64| Node {
+            id,
+            version: 1,
+            latitude,
+            visible: true,
+            ..Default::default()
+        };
+        let mut base = OsmDataset::new();
+        base.insert(Element::Node(node(1, 0)));
+        base.insert(Element::Node(node(2, 0)));
+        base.insert(Element::Way(Way {
+            id: 10,
+            version: 3,
+            visible: true,
+            way_nodes: vec![
+                WayNode::new_without_coords(1),
+                WayNode::new_without_coords(2),
+            ],
+            ..Default::default()
+        }));
+
+        let mut target = base.clone();
+        target.delete(&ElementType::Way, 10);
+        target.update(Element::Node(node(1, 100))).unwrap();
+        target.insert(Element::Node(Node {
+            id: -1,
+            visible: true,
+            ..Default::default()
+        }));
+        let mut unchanged = target.node(2).unwrap().clone();
+        unchanged.version = 7;
+        target.update(Element::Node(unchanged)).unwrap();
+
+        let change = base.diff(&target);
+        assert_eq!(change.create.len(), 1);
+        assert_eq!(change.modify.len(), 1);
+        assert_eq!(change.delete.len(), 1);
+        match &change.modify[0] {
+            Element::Node(node) => assert_eq!((node.id, node.version), (1, 2)),
+            _ => panic!("unexpected element"),
+        }
+        match &change.delete[0] {
+            Element::Way(way) => assert_eq!((way.version, way.visible), (4, false)),
+            _ => panic!("unexpected element"),
+        }
+        assert_eq!(change.create[0].get_meta(), (ElementType::Node, -1));
+
+        let mut osc = Vec::new();
+        change.write_osc(&mut osc).unwrap();
+        let osc = String::from_utf8(osc).unwrap();
+        assert!(osc.contains("<node id=\"1\" version=\"2\" lat=\"0.0000001\" lon=\"0.0000000\"/>"));
+        assert!(osc.contains("<delete>"));
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::ElementType;
+
+/// The number of bits of the packed ID holding the element ID.
+const ID_BITS: u32 = 62;
+/// The bias added to element IDs so that negative IDs sort before positive ones.
+const ID_BIAS: i64 = 1 << (ID_BITS - 1);
+const ID_MASK: u64 = (1 << ID_BITS) - 1;
+
+/// The type and ID of an element, e.g. `way/123`, packed into 8 bytes.
+///
+/// The type is stored in the two highest bits and the ID in the others, so IDs between
+/// `-2^61` and `2^61 - 1` can be represented, which covers all IDs used by OSM and editors.
+/// IDs sort by type and then by ID, like the elements of a PBF file.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{ElementId, ElementType};
+///
+/// let id = ElementId::new(ElementType::Way, 123);
+/// assert_eq!(id.element_type(), ElementType::Way);
+/// assert_eq!(id.id(), 123);
+/// assert_eq!(id.to_string(), "way/123");
+/// assert_eq!("way/123".parse::<ElementId>().unwrap(), id);
+/// assert!(ElementId::new(ElementType::Node, 5) < ElementId::new(ElementType::Way, -5));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ElementId(u64);
+
+impl ElementId {
+    /// Creates the ID of an element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ID is out of the range which can be packed, see `try_new`.
+    pub fn new(element_type: ElementType, id: i64) -> Self {
+        match Self::try_new(element_type.clone(), id) {
+            Some(element_id) => element_id,
+            None => panic!("The ID of {}/{} is out of range", element_type, id),
+        }
+    }
+
+    /// Creates the ID of an element, or returns `None` if the ID isn't between `-2^61` and
+    /// `2^61 - 1`.
+    pub fn try_new(element_type: ElementType, id: i64) -> Option<Self> {
+        if !(-ID_BIAS..ID_BIAS).contains(&id) {
+            return None;
+        }
+        let element_type = match element_type {
+            ElementType::Node => 0,
+            ElementType::Way => 1,
+            ElementType::Relation => 2,
+        };
+        Some(Self((element_type << ID_BITS) | (id + ID_BIAS) as u64))
+    }
+
+    pub fn element_type(&self) -> ElementType {
+        match self.0 >> ID_BITS {
+            0 => ElementType::Node,
+            1 => ElementType::Way,
+            _ => ElementType::Relation,
+        }
+    }
+
+    pub fn id(&self) -> i64 {
+        (self.0 & ID_MASK) as i64 - ID_BIAS
+    }
+
+    /// Returns the packed representation, e.g. to store the ID in an index file.
+    pub fn packed(&self) -> u64 {
+        self.0
+    }
+
+    /// Unpacks an ID returned by `packed`, or returns `None` if it isn't a valid packed ID.
+    pub fn from_packed(packed: u64) -> Option<Self> {
+        (packed >> ID_BITS < 3).then_some(Self(packed))
+    }
+}
+
+impl From<ElementId> for (ElementType, i64) {
+    fn from(id: ElementId) -> Self {
+        (id.element_type(), id.id())
+    }
+}
+
+impl fmt::Display for ElementId {
+    /// Formats the ID as a reference, e.g. `way/123`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.element_type(), self.id())
+    }
+}
+
+impl fmt::Debug for ElementId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ElementId({})", self)
+    }
+}
+
+impl FromStr for ElementId {
+    type Err = anyhow::Error;
+
+    /// Parses a reference to an element, e.g. `node/123`, as written by the `Display` of
+    /// `ElementId` and `Element`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (element_type, id) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Illegal element reference: {}", s))?;
+        let id = id
+            .parse()
+            .map_err(|_| anyhow!("Illegal element reference: {}", s))?;
+        Self::try_new(element_type.parse()?, id)
+            .ok_or_else(|| anyhow!("The ID of {} is out of range", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Element, Node, RelationMember};
+
+    #[test]
+    fn test_element_id() {
+        for element_type in [ElementType::Node, ElementType::Way, ElementType::Relation] {
+            for id in [0, 1, -1, 1055523837, ID_BIAS - 1, -ID_BIAS] {
+                let element_id = ElementId::new(element_type.clone(), id);
+                assert_eq!(
+                    (element_id.element_type(), element_id.id()),
+                    (element_type.clone(), id)
+                );
+                assert_eq!(
+                    ElementId::from_packed(element_id.packed()),
+                    Some(element_id)
+                );
+            }
+            assert_eq!(ElementId::try_new(element_type.clone(), ID_BIAS), None);
+            assert_eq!(ElementId::try_new(element_type, i64::MIN), None);
+        }
+        assert_eq!(ElementId::from_packed(u64::MAX), None);
+
+        // The packed IDs sort like the pairs of type and ID
+        let mut pairs = vec![
+            (ElementType::Way, 3),
+            (ElementType::Node, 7),
+            (ElementType::Relation, -2),
+            (ElementType::Node, -7),
+            (ElementType::Way, -3),
+        ];
+        let mut ids: Vec<ElementId> = pairs
+            .iter()
+            .map(|(element_type, id)| ElementId::new(element_type.clone(), *id))
+            .collect();
+        pairs.sort();
+        ids.sort();
+        let unpacked: Vec<(ElementType, i64)> = ids.into_iter().map(Into::into).collect();
+        assert_eq!(unpacked, pairs);
+
+        assert_eq!(
+            format!("{:?}", ElementId::new(ElementType::Relation, 9)),
+            "ElementId(relation/9)"
+        );
+        assert!("node/4611686018427387904".parse::<ElementId>().is_err());
+
+        // Elements read from files may have IDs which can't be packed
+        let node = Element::Node(Node {
+            id: i64::MAX,
+            ..Default::default()
+        });
+        assert_eq!(node.element_id(), None);
+        let member = RelationMember {
+            member_id: -3,
+            member_type: ElementType::Way,
+            role: "outer".into(),
+        };
+        assert_eq!(
+            member.element_id(),
+            Some(ElementId::new(ElementType::Way, -3))
+        );
+    }
+}
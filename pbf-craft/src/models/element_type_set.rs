@@ -0,0 +1,127 @@
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+use super::ElementType;
+
+/// A set of element types, e.g. `ElementTypeSet::NODES | ElementTypeSet::WAYS`, selecting the
+/// elements a scan decodes.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{ElementType, ElementTypeSet};
+///
+/// let types = ElementTypeSet::NODES | ElementTypeSet::WAYS;
+/// assert!(types.contains(&ElementType::Way));
+/// assert!(!types.contains(&ElementType::Relation));
+/// assert_eq!(ElementTypeSet::from(ElementType::Way), ElementTypeSet::WAYS);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ElementTypeSet(u8);
+
+impl ElementTypeSet {
+    pub const EMPTY: Self = Self(0);
+    pub const NODES: Self = Self(1);
+    pub const WAYS: Self = Self(1 << 1);
+    pub const RELATIONS: Self = Self(1 << 2);
+    pub const ALL: Self = Self(0b111);
+
+    /// Returns `true` if the set contains the element type.
+    pub fn contains(&self, element_type: &ElementType) -> bool {
+        self.0 & Self::from(element_type.clone()).0 != 0
+    }
+
+    /// Adds an element type to the set.
+    pub fn insert(&mut self, element_type: ElementType) {
+        *self |= Self::from(element_type);
+    }
+
+    /// Returns `true` if the set contains no element types.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if the set contains all element types.
+    pub fn is_all(&self) -> bool {
+        *self == Self::ALL
+    }
+
+    /// Returns the element types of the set, in the order nodes, ways, relations.
+    pub fn iter(&self) -> impl Iterator<Item = ElementType> + '_ {
+        [ElementType::Node, ElementType::Way, ElementType::Relation]
+            .into_iter()
+            .filter(|element_type| self.contains(element_type))
+    }
+}
+
+impl Default for ElementTypeSet {
+    /// Returns the set of all element types.
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl From<ElementType> for ElementTypeSet {
+    fn from(element_type: ElementType) -> Self {
+        match element_type {
+            ElementType::Node => Self::NODES,
+            ElementType::Way => Self::WAYS,
+            ElementType::Relation => Self::RELATIONS,
+        }
+    }
+}
+
+impl FromIterator<ElementType> for ElementTypeSet {
+    fn from_iter<I: IntoIterator<Item = ElementType>>(iter: I) -> Self {
+        let mut set = Self::EMPTY;
+        for element_type in iter {
+            set.insert(element_type);
+        }
+        set
+    }
+}
+
+impl BitOr for ElementTypeSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ElementTypeSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl fmt::Debug for ElementTypeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_element_type_set() {
+        let mut types = ElementTypeSet::EMPTY;
+        assert!(types.is_empty());
+        types.insert(ElementType::Relation);
+        types |= ElementTypeSet::NODES;
+        assert_eq!(
+            types.iter().collect::<Vec<_>>(),
+            [ElementType::Node, ElementType::Relation]
+        );
+        assert!(!types.contains(&ElementType::Way));
+        assert_eq!(format!("{:?}", types), "{Node, Relation}");
+
+        let all: ElementTypeSet = [ElementType::Node, ElementType::Way, ElementType::Relation]
+            .into_iter()
+            .collect();
+        assert!(all.is_all());
+        assert_eq!(all, ElementTypeSet::default());
+    }
+}
@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Bound;
+
+/// The header of an OSM file: its bounding box, where its data comes from, the program which
+/// wrote it, the features needed to read it and how it's kept up to date.
+///
+/// It's read with `HeaderReader::file_header` and written with `PbfWriter::set_header`, so a
+/// header read from one file can be written to another as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHeader {
+    pub bbox: Option<Bound>,
+    /// The source of the data, e.g. `http://www.openstreetmap.org/api/0.6`.
+    pub source: Option<String>,
+    pub writing_program: Option<String>,
+    /// The features a reader must support, e.g. `DenseNodes`.
+    pub required_features: Vec<String>,
+    /// The features a reader may use, e.g. `Sort.Type_then_ID`.
+    pub optional_features: Vec<String>,
+    /// The time of the replication diff the data is up to date with.
+    pub replication_timestamp: Option<DateTime<Utc>>,
+    pub replication_sequence_number: Option<i64>,
+    /// The URL of the replication diffs, e.g.
+    /// `https://planet.openstreetmap.org/replication/minute`.
+    pub replication_base_url: Option<String>,
+}
+
+impl FileHeader {
+    /// Returns `true` if the feature is required or optional.
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.required_features
+            .iter()
+            .chain(&self.optional_features)
+            .any(|f| f == feature)
+    }
+}
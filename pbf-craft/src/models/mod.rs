@@ -1,21 +1,50 @@
+mod change;
+mod dataset;
+mod element_id;
+mod element_type_set;
+mod header;
+mod revert;
+mod string_pool;
+
+use std::fmt;
 use std::str::FromStr;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) use change::has_same_content;
+pub use change::OsmChange;
+pub(crate) use dataset::with_version;
+pub use dataset::OsmDataset;
+pub use element_id::ElementId;
+pub use element_type_set::ElementTypeSet;
+pub use header::FileHeader;
+pub use revert::{ChangesetRevert, RevertConflict, RevertConflictKind};
+#[cfg(feature = "interning")]
+pub use string_pool::StringPool;
+pub use string_pool::{intern, PooledStr};
+
+/// A bounding box in nanodegrees.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Bound {
     pub left: i64,
     pub right: i64,
     pub top: i64,
     pub bottom: i64,
-    pub origin: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct OsmUser {
     pub id: i32,
-    pub name: String,
+    pub name: PooledStr,
+}
+
+impl OsmUser {
+    /// Whether the user has uid 0 and an empty name, as written for anonymous edits and for
+    /// elements without a user.
+    pub fn is_anonymous(&self) -> bool {
+        self.id == 0 && self.name.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,15 +63,55 @@ impl Element {
             Element::Relation(e) => (ElementType::Relation, e.id),
         }
     }
+
+    /// Returns the type and ID of the element, or `None` if the ID is out of the range of
+    /// `ElementId`, which valid files may contain.
+    pub fn element_id(&self) -> Option<ElementId> {
+        let (element_type, id) = self.get_meta();
+        ElementId::try_new(element_type, id)
+    }
+
+    /// Parses an element from its JSON representation, e.g.
+    /// `{"type":"Node","id":1,"latitude":425000000,"longitude":15000000}`.
+    ///
+    /// Fields which are missing, such as the metadata, are set to their default values.
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+impl fmt::Display for Element {
+    /// Formats the reference to the element, e.g. `way/123`, which parses as an `ElementId`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (element_type, id) = self.get_meta();
+        write!(f, "{}/{}", element_type, id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ElementType {
     Node,
     Way,
     Relation,
 }
 
+impl ElementType {
+    /// Returns the name of the type, e.g. `node`, as used in OSM XML and references.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ElementType::Node => "node",
+            ElementType::Way => "way",
+            ElementType::Relation => "relation",
+        }
+    }
+}
+
+impl fmt::Display for ElementType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl FromStr for ElementType {
     type Err = anyhow::Error;
 
@@ -84,7 +153,15 @@ pub struct Tag {
     pub value: String,
 }
 
+impl fmt::Display for Tag {
+    /// Formats the tag as `key=value`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
 pub struct Node {
     pub id: i64,
     pub version: i32,
@@ -93,6 +170,7 @@ pub struct Node {
     pub changeset_id: i64,
     pub latitude: i64,
     pub longitude: i64,
+    #[serde(default = "default_visible")]
     pub visible: bool,
     pub tags: Vec<Tag>,
 }
@@ -114,12 +192,14 @@ impl From<ElementBase> for Node {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
 pub struct Way {
     pub id: i64,
     pub version: i32,
     pub timestamp: Option<DateTime<Utc>>,
     pub user: Option<OsmUser>,
     pub changeset_id: i64,
+    #[serde(default = "default_visible")]
     pub visible: bool,
     pub tags: Vec<Tag>,
     pub way_nodes: Vec<WayNode>,
@@ -166,12 +246,14 @@ impl WayNode {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default)]
 pub struct Relation {
     pub id: i64,
     pub version: i32,
     pub timestamp: Option<DateTime<Utc>>,
     pub user: Option<OsmUser>,
     pub changeset_id: i64,
+    #[serde(default = "default_visible")]
     pub visible: bool,
     pub tags: Vec<Tag>,
     pub members: Vec<RelationMember>,
@@ -196,7 +278,19 @@ impl From<ElementBase> for Relation {
 pub struct RelationMember {
     pub member_id: i64,
     pub member_type: ElementType,
-    pub role: String,
+    pub role: PooledStr,
+}
+
+impl RelationMember {
+    /// Returns the type and ID of the member, or `None` if the ID is out of the range of
+    /// `ElementId`.
+    pub fn element_id(&self) -> Option<ElementId> {
+        ElementId::try_new(self.member_type.clone(), self.member_id)
+    }
+}
+
+fn default_visible() -> bool {
+    true
 }
 
 pub trait BasicElement {
@@ -298,3 +392,35 @@ impl BasicElement for Relation {
         self.user.as_ref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_parse() {
+        assert_eq!(ElementType::Relation.to_string(), "relation");
+        let way = Element::Way(Way {
+            id: 123,
+            ..Default::default()
+        });
+        assert_eq!(way.to_string(), "way/123");
+        assert_eq!(
+            way.to_string().parse::<ElementId>().unwrap(),
+            way.element_id().unwrap()
+        );
+        assert_eq!(
+            "node/-5".parse::<ElementId>().unwrap(),
+            ElementId::new(ElementType::Node, -5)
+        );
+        assert!("area/1".parse::<ElementId>().is_err());
+        assert!("node/x".parse::<ElementId>().is_err());
+        assert!("way#123".parse::<ElementId>().is_err());
+
+        let tag = Tag {
+            key: "note".to_string(),
+            value: "a=b".to_string(),
+        };
+        assert_eq!(tag.to_string(), "note=a=b");
+    }
+}
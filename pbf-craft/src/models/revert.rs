@@ -0,0 +1,467 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::change::{has_same_content, OsmChange};
+use super::dataset::with_version;
+use super::{Element, ElementType};
+use crate::history::{history_meta, Versions};
+use crate::readers::ElementSource;
+#[cfg(feature = "fs")]
+use crate::readers::IterableReader;
+
+/// Why an element touched by a changeset can't be reverted automatically.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RevertConflictKind {
+    /// The element was edited again after the changeset, the latest version being the given
+    /// one of another changeset.
+    EditedLater { version: i32, changeset_id: i64 },
+    /// The versions of the element before the changeset are missing from the source, e.g.
+    /// because it isn't a history file.
+    MissingHistory,
+    /// The element was created by the changeset and is referenced by the given way or
+    /// relation, which the revert doesn't delete.
+    StillReferenced {
+        element_type: ElementType,
+        element_id: i64,
+    },
+    /// The element would be restored referencing the given element, which doesn't exist after
+    /// the revert.
+    MissingReference {
+        element_type: ElementType,
+        element_id: i64,
+    },
+}
+
+/// An element left out of the changes reverting a changeset.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevertConflict {
+    pub element_type: ElementType,
+    pub element_id: i64,
+    pub kind: RevertConflictKind,
+}
+
+impl fmt::Display for RevertConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: ", type_name(&self.element_type), self.element_id)?;
+        match &self.kind {
+            RevertConflictKind::EditedLater {
+                version,
+                changeset_id,
+            } => write!(
+                f,
+                "edited later in version {} by changeset {}",
+                version, changeset_id
+            ),
+            RevertConflictKind::MissingHistory => write!(f, "previous versions are missing"),
+            RevertConflictKind::StillReferenced {
+                element_type,
+                element_id,
+            } => write!(
+                f,
+                "still referenced by {} {}",
+                type_name(element_type),
+                element_id
+            ),
+            RevertConflictKind::MissingReference {
+                element_type,
+                element_id,
+            } => write!(
+                f,
+                "references the missing {} {}",
+                type_name(element_type),
+                element_id
+            ),
+        }
+    }
+}
+
+/// The change reverting an element.
+#[derive(Debug, Clone)]
+enum Revert {
+    /// The version before the changeset, with the next version number.
+    Restore(Element),
+    /// The element created by the changeset, with the next version number.
+    Delete(Element),
+}
+
+/// Builds the changes reverting the effects of a changeset, e.g. to clean up vandalism.
+///
+/// The source must be a history file, holding all versions of the elements sorted by type, ID
+/// and version. Each element whose latest version comes from the changeset is restored to its
+/// version before the changeset, or deleted if the changeset created it. The reverting changes
+/// get the latest version plus one, as with `OsmDataset::diff`.
+///
+/// Elements which can't be reverted without touching newer edits are reported as conflicts and
+/// left out of the change: elements edited again after the changeset, created elements still
+/// referenced by later edits, and restored elements referencing elements deleted since. Leaving
+/// out an element doesn't cascade to the elements referencing it, so the conflicts should be
+/// reviewed by hand.
+///
+/// Like `KeepReferenced`, the revert is built in two passes: `collect` finds the elements of
+/// the changeset and `check_references` finds the conflicting references.
+/// `ChangesetRevert::from_path` runs both passes over a PBF file.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::ChangesetRevert;
+///
+/// let revert =
+///     ChangesetRevert::from_path("resources/andorra-latest.osm.pbf", 123456789).unwrap();
+/// for conflict in revert.conflicts() {
+///     println!("{}", conflict);
+/// }
+/// let mut osc = Vec::new();
+/// revert.to_change().write_osc(&mut osc).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChangesetRevert {
+    changeset_id: i64,
+    reverts: BTreeMap<(ElementType, i64), Revert>,
+    conflicts: Vec<RevertConflict>,
+}
+
+impl ChangesetRevert {
+    pub fn new(changeset_id: i64) -> Self {
+        Self {
+            changeset_id,
+            reverts: BTreeMap::new(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    /// Runs both passes over a history PBF file, reading it twice.
+    #[cfg(feature = "fs")]
+    pub fn from_path<P: AsRef<Path>>(path: P, changeset_id: i64) -> anyhow::Result<Self> {
+        let mut revert = Self::new(changeset_id);
+        revert.collect(IterableReader::from_path(&path)?)?;
+        revert.check_references(IterableReader::from_path(&path)?)?;
+        Ok(revert)
+    }
+
+    /// The first pass: finds the elements touched by the changeset and how to revert them.
+    pub fn collect<S: ElementSource>(&mut self, source: S) -> anyhow::Result<()> {
+        let mut history = Versions::new(source);
+        while let Some(versions) = history.next_versions()? {
+            let Some(first) = versions
+                .iter()
+                .position(|element| history_meta(element).1 == self.changeset_id)
+            else {
+                continue;
+            };
+            let latest = &versions[versions.len() - 1];
+            let (version, changeset_id, visible) = history_meta(latest);
+            let (element_type, element_id) = latest.get_meta();
+            if changeset_id != self.changeset_id {
+                self.conflicts.push(RevertConflict {
+                    element_type,
+                    element_id,
+                    kind: RevertConflictKind::EditedLater {
+                        version,
+                        changeset_id,
+                    },
+                });
+                continue;
+            }
+            let revert = match first.checked_sub(1).map(|index| &versions[index]) {
+                Some(previous) if history_meta(previous).2 => {
+                    if has_same_content(previous, latest) {
+                        continue;
+                    }
+                    Revert::Restore(with_version(previous.clone(), version + 1, true))
+                }
+                None if history_meta(&versions[first]).0 > 1 => {
+                    self.conflicts.push(RevertConflict {
+                        element_type,
+                        element_id,
+                        kind: RevertConflictKind::MissingHistory,
+                    });
+                    continue;
+                }
+                // Created by the changeset, or recreated after a deletion
+                _ => {
+                    if !visible {
+                        continue;
+                    }
+                    Revert::Delete(with_version(latest.clone(), version + 1, false))
+                }
+            };
+            self.reverts.insert((element_type, element_id), revert);
+        }
+        Ok(())
+    }
+
+    /// The second pass: finds the deleted elements which are still referenced and the restored
+    /// elements whose references are gone, and leaves them out of the change.
+    pub fn check_references<S: ElementSource>(&mut self, source: S) -> anyhow::Result<()> {
+        // The references of the restored elements to elements the revert doesn't restore
+        let mut required: BTreeMap<(ElementType, i64), Vec<(ElementType, i64)>> = BTreeMap::new();
+        for (key, revert) in &self.reverts {
+            if let Revert::Restore(element) = revert {
+                for reference in references(element) {
+                    if !matches!(self.reverts.get(&reference), Some(Revert::Restore(_))) {
+                        required.entry(reference).or_default().push(key.clone());
+                    }
+                }
+            }
+        }
+
+        let mut referenced: BTreeMap<(ElementType, i64), (ElementType, i64)> = BTreeMap::new();
+        let mut history = Versions::new(source);
+        while let Some(versions) = history.next_versions()? {
+            let latest = &versions[versions.len() - 1];
+            let key = latest.get_meta();
+            if self.reverts.contains_key(&key) || !history_meta(latest).2 {
+                continue;
+            }
+            required.remove(&key);
+            for reference in references(latest) {
+                if let Some(Revert::Delete(_)) = self.reverts.get(&reference) {
+                    if let Entry::Vacant(entry) = referenced.entry(reference) {
+                        entry.insert(key.clone());
+                    }
+                }
+            }
+        }
+
+        let mut conflicts: Vec<RevertConflict> = Vec::new();
+        for ((element_type, element_id), referrer) in referenced {
+            conflicts.push(RevertConflict {
+                element_type,
+                element_id,
+                kind: RevertConflictKind::StillReferenced {
+                    element_type: referrer.0,
+                    element_id: referrer.1,
+                },
+            });
+        }
+        for (reference, referrers) in required {
+            for (element_type, element_id) in referrers {
+                conflicts.push(RevertConflict {
+                    element_type,
+                    element_id,
+                    kind: RevertConflictKind::MissingReference {
+                        element_type: reference.0.clone(),
+                        element_id: reference.1,
+                    },
+                });
+            }
+        }
+        for conflict in conflicts {
+            self.reverts
+                .remove(&(conflict.element_type.clone(), conflict.element_id));
+            self.conflicts.push(conflict);
+        }
+        self.conflicts
+            .sort_by(|a, b| (&a.element_type, a.element_id).cmp(&(&b.element_type, b.element_id)));
+        Ok(())
+    }
+
+    /// The ID of the reverted changeset.
+    pub fn changeset_id(&self) -> i64 {
+        self.changeset_id
+    }
+
+    /// The elements left out of the change, sorted by type and ID.
+    pub fn conflicts(&self) -> &[RevertConflict] {
+        &self.conflicts
+    }
+
+    /// Returns the changes reverting the changeset, except for the conflicting elements.
+    pub fn to_change(&self) -> OsmChange {
+        let mut change = OsmChange::default();
+        for revert in self.reverts.values() {
+            match revert {
+                Revert::Restore(element) => change.modify.push(element.clone()),
+                Revert::Delete(element) => change.delete.push(element.clone()),
+            }
+        }
+        // dependents must be deleted before the elements they refer to
+        change.delete.reverse();
+        change
+    }
+}
+
+/// Returns the elements an element references.
+fn references(element: &Element) -> Vec<(ElementType, i64)> {
+    match element {
+        Element::Node(_) => Vec::new(),
+        Element::Way(way) => way
+            .way_nodes
+            .iter()
+            .map(|way_node| (ElementType::Node, way_node.id))
+            .collect(),
+        Element::Relation(relation) => relation
+            .members
+            .iter()
+            .map(|member| (member.member_type.clone(), member.member_id))
+            .collect(),
+    }
+}
+
+fn type_name(element_type: &ElementType) -> &'static str {
+    match element_type {
+        ElementType::Node => "node",
+        ElementType::Way => "way",
+        ElementType::Relation => "relation",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Tag, Way, WayNode};
+
+    fn node(id: i64, version: i32, changeset_id: i64, visible: bool) -> Element {
+        Element::Node(Node {
+            id,
+            version,
+            changeset_id,
+            latitude: id * 1000,
+            longitude: version as i64,
+            visible,
+            ..Default::default()
+        })
+    }
+
+    fn way(id: i64, version: i32, changeset_id: i64, node_ids: &[i64]) -> Element {
+        Element::Way(Way {
+            id,
+            version,
+            changeset_id,
+            visible: true,
+            tags: vec![Tag {
+                key: "highway".to_string(),
+                value: format!("v{}", version),
+            }],
+            way_nodes: node_ids
+                .iter()
+                .map(|id| WayNode::new_without_coords(*id))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_revert() {
+        // Changeset 10 moves node 1, deletes node 2, creates nodes 3 and 4 and adds node 3 to
+        // way 20. Node 4 is then added to way 21 by changeset 11, and node 5, deleted by
+        // changeset 10 too, is then recreated by changeset 12.
+        let history = vec![
+            node(1, 1, 1, true),
+            node(1, 2, 10, true),
+            node(2, 1, 1, true),
+            node(2, 2, 10, false),
+            node(3, 1, 10, true),
+            node(4, 1, 10, true),
+            node(5, 1, 1, true),
+            node(5, 2, 10, false),
+            node(5, 3, 12, true),
+            node(6, 2, 10, true),
+            way(20, 1, 1, &[1, 2]),
+            way(20, 2, 10, &[1, 2, 3]),
+            way(21, 1, 1, &[1, 5]),
+            way(21, 2, 11, &[1, 4, 5]),
+        ];
+        let mut revert = ChangesetRevert::new(10);
+        revert.collect(history.clone().into_iter()).unwrap();
+        revert.check_references(history.into_iter()).unwrap();
+
+        assert_eq!(
+            revert.conflicts(),
+            &[
+                RevertConflict {
+                    element_type: ElementType::Node,
+                    element_id: 4,
+                    kind: RevertConflictKind::StillReferenced {
+                        element_type: ElementType::Way,
+                        element_id: 21,
+                    },
+                },
+                RevertConflict {
+                    element_type: ElementType::Node,
+                    element_id: 5,
+                    kind: RevertConflictKind::EditedLater {
+                        version: 3,
+                        changeset_id: 12,
+                    },
+                },
+                RevertConflict {
+                    element_type: ElementType::Node,
+                    element_id: 6,
+                    kind: RevertConflictKind::MissingHistory,
+                },
+            ]
+        );
+        assert_eq!(
+            revert.conflicts()[0].to_string(),
+            "node 4: still referenced by way 21"
+        );
+
+        let change = revert.to_change();
+        assert!(change.create.is_empty());
+        let modified: Vec<(ElementType, i64)> =
+            change.modify.iter().map(Element::get_meta).collect();
+        assert_eq!(
+            modified,
+            vec![
+                (ElementType::Node, 1),
+                (ElementType::Node, 2),
+                (ElementType::Way, 20)
+            ]
+        );
+        // The version before the changeset, with the next version number
+        match &change.modify[2] {
+            Element::Way(way) => {
+                assert_eq!(way.version, 3);
+                assert_eq!(way.way_nodes.len(), 2);
+            }
+            _ => unreachable!(),
+        }
+        match &change.modify[1] {
+            Element::Node(node) => assert!(node.visible && node.version == 3),
+            _ => unreachable!(),
+        }
+        let deleted: Vec<(ElementType, i64)> =
+            change.delete.iter().map(Element::get_meta).collect();
+        assert_eq!(deleted, vec![(ElementType::Node, 3)]);
+    }
+
+    #[test]
+    fn test_missing_reference() {
+        // Changeset 10 deletes way 20, whose node 2 is then deleted by changeset 11
+        let history = vec![
+            node(1, 1, 1, true),
+            node(2, 1, 1, true),
+            node(2, 2, 11, false),
+            way(20, 1, 1, &[1, 2]),
+            Element::Way(Way {
+                id: 20,
+                version: 2,
+                changeset_id: 10,
+                visible: false,
+                ..Default::default()
+            }),
+        ];
+        let mut revert = ChangesetRevert::new(10);
+        revert.collect(history.clone().into_iter()).unwrap();
+        revert.check_references(history.into_iter()).unwrap();
+        assert_eq!(
+            revert.conflicts(),
+            &[RevertConflict {
+                element_type: ElementType::Way,
+                element_id: 20,
+                kind: RevertConflictKind::MissingReference {
+                    element_type: ElementType::Node,
+                    element_id: 2,
+                },
+            }]
+        );
+        assert!(revert.to_change().is_empty());
+    }
+}
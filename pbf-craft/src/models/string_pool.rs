@@ -0,0 +1,160 @@
+use std::sync::Arc;
+#[cfg(feature = "interning")]
+use std::{
+    collections::{hash_map::RandomState, HashSet},
+    hash::BuildHasher,
+    sync::{Mutex, OnceLock},
+};
+
+/// The type of the user names and relation roles of elements.
+///
+/// It's the same with or without the `interning` feature, which only decides whether `intern`
+/// shares the strings through the global `StringPool`. It converts from `&str` and `String`
+/// with `into`, and dereferences to `str`.
+pub type PooledStr = Arc<str>;
+
+/// Returns a user name or role for elements: the shared copy of the global `StringPool` with
+/// the `interning` feature, and a new allocation otherwise.
+#[cfg(feature = "interning")]
+pub fn intern(s: &str) -> PooledStr {
+    StringPool::global().intern(s)
+}
+
+/// Returns a user name or role for elements: the shared copy of the global `StringPool` with
+/// the `interning` feature, and a new allocation otherwise.
+#[cfg(not(feature = "interning"))]
+pub fn intern(s: &str) -> PooledStr {
+    Arc::from(s)
+}
+
+/// The number of independently locked parts of a pool, which lets threads decoding blobs in
+/// parallel intern strings without waiting for each other.
+#[cfg(feature = "interning")]
+const SHARDS: usize = 16;
+
+/// A thread-safe set of shared strings, which lets the elements decoded from all blobs share
+/// one allocation per distinct user name or relation role.
+///
+/// The pool only grows: strings stay in it until `clear` is called, which doesn't affect the
+/// elements holding them.
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use pbf_craft::models::StringPool;
+///
+/// let pool = StringPool::new();
+/// let outer = pool.intern("outer");
+/// assert!(Arc::ptr_eq(&outer, &pool.intern("outer")));
+/// assert_eq!(pool.len(), 1);
+/// ```
+#[cfg(feature = "interning")]
+pub struct StringPool {
+    hasher: RandomState,
+    shards: Vec<Mutex<HashSet<Arc<str>>>>,
+}
+
+#[cfg(feature = "interning")]
+impl StringPool {
+    pub fn new() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    /// Returns the pool the readers intern user names and roles in.
+    ///
+    /// It lives as long as the process and never evicts strings, so it holds every distinct
+    /// name and role read until `clear` is called.
+    pub fn global() -> &'static StringPool {
+        static GLOBAL: OnceLock<StringPool> = OnceLock::new();
+        GLOBAL.get_or_init(StringPool::new)
+    }
+
+    /// Returns the shared copy of a string, adding it to the pool if it isn't there yet.
+    pub fn intern(&self, s: &str) -> Arc<str> {
+        let shard = self.hasher.hash_one(s) as usize % SHARDS;
+        let mut strings = self.shards[shard]
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if let Some(interned) = strings.get(s) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(s);
+        strings.insert(interned.clone());
+        interned
+    }
+
+    /// Returns the number of distinct strings in the pool.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(|err| err.into_inner()).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all strings from the pool. Elements keep the strings they hold, but strings
+    /// interned afterwards aren't shared with them.
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().unwrap_or_else(|err| err.into_inner()).clear();
+        }
+    }
+}
+
+#[cfg(feature = "interning")]
+impl Default for StringPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        // The type doesn't depend on the feature, only the sharing does
+        let role: Arc<str> = intern("outer");
+        assert_eq!(&*role, "outer");
+        assert_eq!(
+            Arc::ptr_eq(&role, &intern("outer")),
+            cfg!(feature = "interning")
+        );
+    }
+
+    #[cfg(feature = "interning")]
+    #[test]
+    fn test_string_pool() {
+        let pool = Arc::new(StringPool::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    (0..100)
+                        .map(|i| pool.intern(&format!("role{}", i % 10)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let interned: Vec<Vec<Arc<str>>> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(pool.len(), 10);
+        assert!(Arc::ptr_eq(&interned[0][3], &interned[3][13]));
+        assert_eq!(&*interned[2][7], "role7");
+
+        pool.clear();
+        assert!(pool.is_empty());
+        assert!(!Arc::ptr_eq(&pool.intern("role3"), &interned[0][3]));
+    }
+}
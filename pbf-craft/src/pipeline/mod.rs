@@ -0,0 +1,441 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::codecs::blob::{DecodedBlob, RawBlob};
+use crate::codecs::block_decorators::{DecodeErrorPolicy, HeaderReader, PrimitiveReader};
+use crate::codecs::decode_options::DecodeOptions;
+use crate::models::{Element, ElementTypeSet, FileHeader};
+use crate::readers::PbfReader;
+use crate::writers::ElementSink;
+
+/// A three-stage pipeline reading the blobs of a `PbfReader`, decoding and processing their
+/// elements on worker threads, and writing the results to a sink in the order of the file.
+///
+/// The stages are connected by bounded channels and the reader only reads ahead a limited
+/// number of blobs of the writer, so the memory of the pipeline stays bounded when the sink is
+/// slower than the reader, e.g. a network upload. The reader and the writer are a single thread
+/// each, as the file and the sink are sequential; the processing stage has as many threads as
+/// set with `set_workers`. The sink runs on the calling thread, so it doesn't need to be `Send`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::Element;
+/// use pbf_craft::pipeline::Pipeline;
+/// use pbf_craft::readers::PbfReader;
+/// use pbf_craft::writers::PbfWriter;
+///
+/// let reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let mut writer = PbfWriter::new(Vec::new(), true);
+/// let mut pipeline = Pipeline::new();
+/// pipeline.set_workers(4);
+/// pipeline.set_max_in_flight(16);
+/// let metrics = pipeline
+///     .run(
+///         reader,
+///         |element| match &element {
+///             Element::Way(way) if way.tags.iter().any(|tag| tag.key == "highway") => {
+///                 Ok(Some(element))
+///             }
+///             _ => Ok(None),
+///         },
+///         &mut writer,
+///     )
+///     .unwrap();
+/// assert!(metrics.elements_written > 0);
+/// assert!(metrics.peak_in_flight <= 16);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    workers: usize,
+    max_in_flight: usize,
+    element_types: ElementTypeSet,
+}
+
+/// What a `Pipeline` did, returned by `Pipeline::run`.
+///
+/// The durations tell which stage limits the throughput: a reader often blocked and a writer
+/// rarely idle mean that the sink is the bottleneck.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineMetrics {
+    /// The number of blobs read, including the header.
+    pub blobs: u64,
+    /// The number of elements decoded and passed to the processor.
+    pub elements_read: u64,
+    /// The number of elements returned by the processor and written to the sink.
+    pub elements_written: u64,
+    /// The largest number of blobs read but not yet written at any time.
+    pub peak_in_flight: usize,
+    /// How long the reader waited for the writer to catch up.
+    pub reader_blocked: Duration,
+    /// How long the workers spent decoding and processing, summed over the workers.
+    pub processing: Duration,
+    /// How long the writer waited for the next blob to be processed.
+    pub writer_idle: Duration,
+    /// How long the writer spent in the sink.
+    pub writing: Duration,
+}
+
+/// The elements of a blob after processing, or the header of the header blob.
+enum Batch {
+    Header(FileHeader),
+    Elements { read: u64, elements: Vec<Element> },
+}
+
+/// The progress of the writer, which the reader waits on when it is too far ahead.
+#[derive(Default)]
+struct Window {
+    written: usize,
+    aborted: bool,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        let workers = thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1);
+        Self {
+            workers,
+            max_in_flight: workers * 2,
+            element_types: ElementTypeSet::ALL,
+        }
+    }
+}
+
+impl Pipeline {
+    /// Creates a pipeline with a worker per core, holding up to twice as many blobs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of threads decoding and processing the blobs.
+    pub fn set_workers(&mut self, workers: usize) {
+        self.workers = workers.max(1);
+    }
+
+    /// Sets the number of blobs which may be read before the previous ones are written,
+    /// bounding the memory of the pipeline to about that many decoded blobs. A value below
+    /// the number of workers leaves some of them idle.
+    pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+        self.max_in_flight = max_in_flight.max(1);
+    }
+
+    /// Sets the types of the elements passed to the processor. The elements of the other types
+    /// are dropped without being decoded. All types are processed by default.
+    pub fn set_element_types(&mut self, element_types: ElementTypeSet) {
+        self.element_types = element_types;
+    }
+
+    /// Runs the pipeline, passing each element of the reader to `process` and writing the
+    /// elements it returns to the sink, which is finished afterwards. Elements for which it
+    /// returns `None` are dropped.
+    ///
+    /// The header is set on the sink before the elements are written.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the first error of reading or decoding a blob, of `process`
+    /// or of the sink. The pipeline stops at the first error, but elements of earlier blobs
+    /// may have been written to the sink.
+    pub fn run<R, P, S>(
+        &self,
+        reader: PbfReader<R>,
+        process: P,
+        sink: &mut S,
+    ) -> anyhow::Result<PipelineMetrics>
+    where
+        R: Read + Send,
+        P: Fn(Element) -> anyhow::Result<Option<Element>> + Sync,
+        S: ElementSink,
+    {
+        let (mut blob_reader, policy, options) = reader.into_parts();
+        let max_in_flight = self.max_in_flight;
+        let window = Mutex::new(Window::default());
+        let progressed = Condvar::new();
+        let processing = AtomicU64::new(0);
+        let mut metrics = PipelineMetrics::default();
+
+        let result = thread::scope(|scope| {
+            let (blob_tx, blob_rx) = sync_channel::<(usize, RawBlob)>(max_in_flight);
+            let (batch_tx, batch_rx) = sync_channel(max_in_flight);
+
+            let (window, progressed) = (&window, &progressed);
+            let reader_stage = scope.spawn(move || {
+                let mut blocked = Duration::ZERO;
+                let mut peak = 0;
+                let mut index = 0;
+                while let Some(blob) = blob_reader.next_blob()? {
+                    let mut state = window.lock().unwrap();
+                    if index >= state.written + max_in_flight {
+                        let start = Instant::now();
+                        while !state.aborted && index >= state.written + max_in_flight {
+                            state = progressed.wait(state).unwrap();
+                        }
+                        blocked += start.elapsed();
+                    }
+                    if state.aborted {
+                        break;
+                    }
+                    peak = peak.max(index + 1 - state.written);
+                    drop(state);
+                    // The window keeps the channel from filling up, so this doesn't block
+                    if blob_tx.send((index, blob)).is_err() {
+                        break;
+                    }
+                    index += 1;
+                }
+                anyhow::Ok((blocked, peak))
+            });
+
+            // The workers share the receiver, which is dropped with the last of them
+            let blob_rx = Arc::new(Mutex::new(blob_rx));
+            for _ in 0..self.workers {
+                let blob_rx = blob_rx.clone();
+                let batch_tx = batch_tx.clone();
+                let (policy, process, processing) = (&policy, &process, &processing);
+                let element_types = self.element_types;
+                scope.spawn(move || loop {
+                    let Ok((index, blob)) = blob_rx.lock().unwrap().recv() else {
+                        break;
+                    };
+                    let start = Instant::now();
+                    let batch = process_blob(&blob, policy, options, element_types, process);
+                    processing.fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                    if batch_tx.send((index, batch)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop((blob_rx, batch_tx));
+
+            let written = write_batches(batch_rx, sink, window, progressed, &mut metrics);
+            if written.is_err() {
+                window.lock().unwrap().aborted = true;
+                progressed.notify_all();
+            }
+            let read = reader_stage.join().unwrap();
+            written?;
+            let (blocked, peak) = read?;
+            metrics.reader_blocked = blocked;
+            metrics.peak_in_flight = peak;
+            anyhow::Ok(())
+        });
+        result?;
+        metrics.processing = Duration::from_nanos(processing.into_inner());
+        let start = Instant::now();
+        sink.finish()?;
+        metrics.writing += start.elapsed();
+        Ok(metrics)
+    }
+}
+
+/// Decodes a blob and passes its elements to the processor.
+fn process_blob<P>(
+    blob: &RawBlob,
+    policy: &DecodeErrorPolicy,
+    options: DecodeOptions,
+    element_types: ElementTypeSet,
+    process: &P,
+) -> anyhow::Result<Batch>
+where
+    P: Fn(Element) -> anyhow::Result<Option<Element>>,
+{
+    let block = match blob.decode_with_options(&options)? {
+        DecodedBlob::OsmHeader(header) => {
+            return Ok(Batch::Header(HeaderReader::new(header).file_header()))
+        }
+        DecodedBlob::OsmData(block) => block,
+    };
+    let mut read = 0;
+    let mut elements = Vec::new();
+    let mut result = Ok(());
+    let block = PrimitiveReader::with_options(block, policy.clone(), options);
+    block.for_each_element_of(element_types, |element| {
+        read += 1;
+        if result.is_ok() {
+            match process(element) {
+                Ok(Some(element)) => elements.push(element),
+                Ok(None) => {}
+                Err(err) => result = Err(err),
+            }
+        }
+    })?;
+    result?;
+    Ok(Batch::Elements { read, elements })
+}
+
+/// Writes the processed blobs to the sink in the order of the file, holding the blobs which
+/// finish early until their turn, and advances the window of the reader.
+fn write_batches<S: ElementSink>(
+    batch_rx: Receiver<(usize, anyhow::Result<Batch>)>,
+    sink: &mut S,
+    window: &Mutex<Window>,
+    progressed: &Condvar,
+    metrics: &mut PipelineMetrics,
+) -> anyhow::Result<()> {
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    loop {
+        let start = Instant::now();
+        let Ok((index, batch)) = batch_rx.recv() else {
+            break;
+        };
+        metrics.writer_idle += start.elapsed();
+        pending.insert(index, batch?);
+        while let Some(batch) = pending.remove(&next) {
+            let start = Instant::now();
+            metrics.blobs += 1;
+            match batch {
+                Batch::Header(header) => sink.set_header(header),
+                Batch::Elements { read, elements } => {
+                    metrics.elements_read += read;
+                    metrics.elements_written += elements.len() as u64;
+                    for element in elements {
+                        sink.write(element)?;
+                    }
+                }
+            }
+            metrics.writing += start.elapsed();
+            next += 1;
+            window.lock().unwrap().written = next;
+            progressed.notify_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::models::ElementType;
+    use crate::readers::IterableReader;
+
+    const PATH: &str = "./resources/andorra-latest.osm.pbf";
+
+    struct VecSink(Vec<Element>);
+
+    impl ElementSink for VecSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.0.push(element);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A sink slower than the reader, e.g. a network upload.
+    struct SlowSink(u64);
+
+    impl ElementSink for SlowSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.0 += 1;
+            if let Element::Relation(_) = element {
+                thread::sleep(Duration::from_micros(100));
+            }
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let mut pipeline = Pipeline::new();
+        pipeline.set_workers(3);
+        pipeline.set_max_in_flight(4);
+        let mut sink = VecSink(Vec::new());
+        let metrics = pipeline
+            .run(
+                PbfReader::from_path(PATH).unwrap(),
+                |element| Ok((element.get_meta().0 != ElementType::Node).then_some(element)),
+                &mut sink,
+            )
+            .unwrap();
+
+        // The elements are written in the order of the file
+        let expected: Vec<(ElementType, i64)> = IterableReader::from_path(PATH)
+            .unwrap()
+            .map(|element| element.get_meta())
+            .filter(|(element_type, _)| *element_type != ElementType::Node)
+            .collect();
+        let written: Vec<(ElementType, i64)> =
+            sink.0.iter().map(|element| element.get_meta()).collect();
+        assert_eq!(written, expected);
+        assert_eq!(metrics.elements_written, expected.len() as u64);
+        assert_eq!(
+            metrics.elements_read,
+            IterableReader::from_path(PATH).unwrap().count() as u64
+        );
+        assert!(metrics.peak_in_flight <= 4);
+
+        // Filtering by type gives the same elements without decoding the nodes
+        pipeline.set_element_types(ElementTypeSet::WAYS | ElementTypeSet::RELATIONS);
+        let mut filtered = VecSink(Vec::new());
+        let metrics = pipeline
+            .run(
+                PbfReader::from_path(PATH).unwrap(),
+                |element| Ok(Some(element)),
+                &mut filtered,
+            )
+            .unwrap();
+        let filtered: Vec<(ElementType, i64)> = filtered
+            .0
+            .iter()
+            .map(|element| element.get_meta())
+            .collect();
+        assert_eq!(filtered, expected);
+        assert_eq!(metrics.elements_read, expected.len() as u64);
+    }
+
+    #[test]
+    fn test_pipeline_backpressure() {
+        let mut pipeline = Pipeline::new();
+        pipeline.set_workers(2);
+        pipeline.set_max_in_flight(2);
+        let mut sink = SlowSink(0);
+        let metrics = pipeline
+            .run(
+                PbfReader::from_path(PATH).unwrap(),
+                |element| Ok(Some(element)),
+                &mut sink,
+            )
+            .unwrap();
+        assert_eq!(metrics.elements_written, sink.0);
+        assert!(metrics.peak_in_flight <= 2);
+        assert!(metrics.reader_blocked > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_pipeline_error() {
+        let processed = AtomicUsize::new(0);
+        let mut pipeline = Pipeline::new();
+        pipeline.set_workers(2);
+        pipeline.set_max_in_flight(2);
+        let result = pipeline.run(
+            PbfReader::from_path(PATH).unwrap(),
+            |element| {
+                if processed.fetch_add(1, Ordering::Relaxed) == 10000 {
+                    bail!("Failed to process {:?}", element.get_meta());
+                }
+                Ok(Some(element))
+            },
+            &mut VecSink(Vec::new()),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Failed to process"));
+    }
+}
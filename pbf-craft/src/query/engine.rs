@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+use super::parser::{ElementQuery, Query, Recursion, Statement};
+use crate::models::{Bound, Element, ElementType, ElementTypeSet};
+use crate::readers::{CachedReader, IndexedReader, PbfReader};
+
+type ResultSet = BTreeMap<(ElementType, i64), Element>;
+
+/// Evaluates a `Query` against a local PBF file.
+///
+/// Full scans are done with the parallel `PbfReader::par_find`, while lookups by id and the
+/// `>` recursion go through an `IndexedReader`, so an index file is created next to the PBF
+/// file if it does not exist yet.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::query::{Query, QueryEngine};
+///
+/// let mut engine = QueryEngine::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let query: Query = "way(1055523837); >; out;".parse().unwrap();
+/// let elements = engine.execute(&query).unwrap();
+/// ```
+pub struct QueryEngine {
+    pbf_file: PathBuf,
+    indexed_reader: IndexedReader<CachedReader>,
+}
+
+impl QueryEngine {
+    /// Creates a new `QueryEngine` for a PBF file.
+    pub fn from_path(pbf_file: &str) -> anyhow::Result<Self> {
+        let indexed_reader = IndexedReader::from_path_with_cache(pbf_file, 1000)?;
+        Ok(Self {
+            pbf_file: PathBuf::from(pbf_file),
+            indexed_reader,
+        })
+    }
+
+    /// Executes a query.
+    ///
+    /// Returns the elements of all `out` statements in the order of nodes, ways and relations,
+    /// each sorted by id. If the query has no `out` statement, the result of the last statement
+    /// is returned.
+    pub fn execute(&mut self, query: &Query) -> anyhow::Result<Vec<Element>> {
+        let mut current = ResultSet::new();
+        let mut output = ResultSet::new();
+        let mut has_output = false;
+        for statement in &query.statements {
+            if *statement == Statement::Out {
+                has_output = true;
+                output.extend(current.clone());
+            } else {
+                current = self.evaluate(statement, current)?;
+            }
+        }
+        if !has_output {
+            output = current;
+        }
+        Ok(output.into_values().collect())
+    }
+
+    fn evaluate(&mut self, statement: &Statement, input: ResultSet) -> anyhow::Result<ResultSet> {
+        match statement {
+            Statement::Query(query) => self.evaluate_query(query),
+            Statement::Recurse(Recursion::Down) => self.recurse_down(&input),
+            Statement::Recurse(Recursion::Up) => self.recurse_up(&input),
+            Statement::Union(statements) => {
+                let mut current = input;
+                let mut result = ResultSet::new();
+                for statement in statements {
+                    if *statement != Statement::Out {
+                        current = self.evaluate(statement, current)?;
+                        result.extend(current.clone());
+                    }
+                }
+                Ok(result)
+            }
+            Statement::Out => Ok(input),
+        }
+    }
+
+    fn scan<F>(&self, types: ElementTypeSet, predicate: F) -> anyhow::Result<Vec<Element>>
+    where
+        F: Fn(&Element) -> bool + Send + Sync,
+    {
+        let reader = PbfReader::from_path(&self.pbf_file)?;
+        reader.par_find(types, predicate)
+    }
+
+    fn evaluate_query(&mut self, query: &ElementQuery) -> anyhow::Result<ResultSet> {
+        let spatial = match &query.bbox {
+            Some(bbox) => Some(self.spatial_filter(bbox, &query.types)?),
+            None => None,
+        };
+        let matches = |element: &Element| {
+            let tags_match = match element {
+                Element::Node(node) => query.matches_tags(node),
+                Element::Way(way) => query.matches_tags(way),
+                Element::Relation(relation) => query.matches_tags(relation),
+            };
+            tags_match
+                && spatial
+                    .as_ref()
+                    .is_none_or(|spatial| spatial.contains(element))
+        };
+
+        let mut result = ResultSet::new();
+        for element_type in &query.types {
+            let elements = if query.ids.is_empty() {
+                self.scan(element_type.clone().into(), matches)?
+            } else {
+                let mut elements = Vec::new();
+                for id in &query.ids {
+                    if let Some(element) = self.indexed_reader.find(element_type, *id)? {
+                        if matches(&element) {
+                            elements.push(element);
+                        }
+                    }
+                }
+                elements
+            };
+            for element in elements {
+                result.insert(element.get_meta(), element);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Collects the ids needed to decide whether an element lies within a bounding box.
+    ///
+    /// Like Overpass, a way matches when any of its nodes is inside the box and a relation
+    /// matches when any of its node or way members does.
+    fn spatial_filter(&self, bbox: &Bound, types: &[ElementType]) -> anyhow::Result<SpatialFilter> {
+        let bbox = bbox.clone();
+        let mut filter = SpatialFilter {
+            bbox: bbox.clone(),
+            node_ids: HashSet::new(),
+            way_ids: HashSet::new(),
+        };
+        if types.iter().all(|t| *t == ElementType::Node) {
+            return Ok(filter);
+        }
+        filter.node_ids = self
+            .scan(ElementTypeSet::NODES, move |element| match element {
+                Element::Node(node) => in_bbox(&bbox, node.latitude, node.longitude),
+                _ => false,
+            })?
+            .iter()
+            .map(|element| element.get_meta().1)
+            .collect();
+        if types.contains(&ElementType::Relation) {
+            let node_ids = &filter.node_ids;
+            filter.way_ids = self
+                .scan(ElementTypeSet::WAYS, |element| match element {
+                    Element::Way(way) => way.way_nodes.iter().any(|wn| node_ids.contains(&wn.id)),
+                    _ => false,
+                })?
+                .iter()
+                .map(|element| element.get_meta().1)
+                .collect();
+        }
+        Ok(filter)
+    }
+
+    fn recurse_down(&mut self, input: &ResultSet) -> anyhow::Result<ResultSet> {
+        let mut node_ids = Vec::new();
+        let mut way_ids = Vec::new();
+        for element in input.values() {
+            match element {
+                Element::Node(_) => {}
+                Element::Way(way) => node_ids.extend(way.way_nodes.iter().map(|wn| wn.id)),
+                Element::Relation(relation) => {
+                    for member in &relation.members {
+                        match member.member_type {
+                            ElementType::Node => node_ids.push(member.member_id),
+                            ElementType::Way => way_ids.push(member.member_id),
+                            ElementType::Relation => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut result = ResultSet::new();
+        way_ids.sort_unstable();
+        way_ids.dedup();
+        for way in self.indexed_reader.find_ways(&way_ids)? {
+            node_ids.extend(way.way_nodes.iter().map(|wn| wn.id));
+            result.insert((ElementType::Way, way.id), Element::Way(way));
+        }
+        node_ids.sort_unstable();
+        node_ids.dedup();
+        for node in self.indexed_reader.find_nodes(&node_ids)? {
+            result.insert((ElementType::Node, node.id), Element::Node(node));
+        }
+        Ok(result)
+    }
+
+    fn recurse_up(&mut self, input: &ResultSet) -> anyhow::Result<ResultSet> {
+        let mut node_ids = HashSet::new();
+        let mut way_ids = HashSet::new();
+        let mut relation_ids = HashSet::new();
+        for (element_type, id) in input.keys() {
+            match element_type {
+                ElementType::Node => node_ids.insert(*id),
+                ElementType::Way => way_ids.insert(*id),
+                ElementType::Relation => relation_ids.insert(*id),
+            };
+        }
+
+        let mut result = ResultSet::new();
+        if !node_ids.is_empty() {
+            let ways = self.scan(ElementTypeSet::WAYS, |element| match element {
+                Element::Way(way) => way.way_nodes.iter().any(|wn| node_ids.contains(&wn.id)),
+                _ => false,
+            })?;
+            for way in ways {
+                let key = way.get_meta();
+                way_ids.insert(key.1);
+                result.insert(key, way);
+            }
+        }
+        if !node_ids.is_empty() || !way_ids.is_empty() || !relation_ids.is_empty() {
+            let relations = self.scan(ElementTypeSet::RELATIONS, |element| match element {
+                Element::Relation(relation) => {
+                    relation
+                        .members
+                        .iter()
+                        .any(|member| match member.member_type {
+                            ElementType::Node => node_ids.contains(&member.member_id),
+                            ElementType::Way => way_ids.contains(&member.member_id),
+                            ElementType::Relation => relation_ids.contains(&member.member_id),
+                        })
+                }
+                _ => false,
+            })?;
+            for relation in relations {
+                result.insert(relation.get_meta(), relation);
+            }
+        }
+        Ok(result)
+    }
+}
+
+struct SpatialFilter {
+    bbox: Bound,
+    node_ids: HashSet<i64>,
+    way_ids: HashSet<i64>,
+}
+
+impl SpatialFilter {
+    fn contains(&self, element: &Element) -> bool {
+        match element {
+            Element::Node(node) => in_bbox(&self.bbox, node.latitude, node.longitude),
+            Element::Way(way) => way
+                .way_nodes
+                .iter()
+                .any(|wn| self.node_ids.contains(&wn.id)),
+            Element::Relation(relation) => {
+                relation
+                    .members
+                    .iter()
+                    .any(|member| match member.member_type {
+                        ElementType::Node => self.node_ids.contains(&member.member_id),
+                        ElementType::Way => self.way_ids.contains(&member.member_id),
+                        ElementType::Relation => false,
+                    })
+            }
+        }
+    }
+}
+
+fn in_bbox(bbox: &Bound, latitude: i64, longitude: i64) -> bool {
+    latitude >= bbox.bottom
+        && latitude <= bbox.top
+        && longitude >= bbox.left
+        && longitude <= bbox.right
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        let mut engine = QueryEngine::from_path("./resources/andorra-latest.osm.pbf").unwrap();
+
+        let query: Query = "way(1055523837); out; >; out;".parse().unwrap();
+        let elements = engine.execute(&query).unwrap();
+        let Element::Way(way) = elements.last().unwrap() else {
+            panic!("expected the way to come last");
+        };
+        assert_eq!(way.id, 1055523837);
+        let node_ids: HashSet<i64> = way.way_nodes.iter().map(|wn| wn.id).collect();
+        assert_eq!(elements.len(), node_ids.len() + 1);
+
+        let query: Query = "node(4254529698); <;".parse().unwrap();
+        let ways = engine.execute(&query).unwrap();
+        assert!(!ways.is_empty());
+        assert!(ways.iter().all(|element| match element {
+            Element::Way(way) => way.way_nodes.iter().any(|wn| wn.id == 4254529698),
+            Element::Relation(_) => true,
+            Element::Node(_) => false,
+        }));
+
+        let query: Query = "way[highway=primary](42.50,1.50,42.52,1.54);"
+            .parse()
+            .unwrap();
+        let ways = engine.execute(&query).unwrap();
+        assert!(!ways.is_empty());
+        for element in ways {
+            let Element::Way(way) = element else {
+                panic!("expected only ways");
+            };
+            assert!(way
+                .tags
+                .iter()
+                .any(|tag| tag.key == "highway" && tag.value == "primary"));
+        }
+    }
+}
@@ -0,0 +1,7 @@
+#[cfg(all(feature = "fs", feature = "parallel"))]
+mod engine;
+mod parser;
+
+#[cfg(all(feature = "fs", feature = "parallel"))]
+pub use engine::QueryEngine;
+pub use parser::{ElementQuery, Query, Recursion, Statement, TagFilter};
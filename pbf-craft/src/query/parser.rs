@@ -0,0 +1,398 @@
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+use regex::Regex;
+
+use crate::models::{BasicElement, Bound, ElementType, Tag};
+
+/// A tag predicate, e.g. `[highway]`, `[!name]`, `[highway=primary]`, `[access!=no]` or
+/// `[name~"^Calle"]`.
+#[derive(Debug, Clone)]
+pub enum TagFilter {
+    Exists(String),
+    NotExists(String),
+    Equals(String, String),
+    NotEquals(String, String),
+    /// The tag exists and its value matches the regular expression anywhere, unless anchored.
+    Matches(String, Regex),
+}
+
+impl TagFilter {
+    /// Checks whether the tags satisfy the predicate.
+    pub fn matches(&self, tags: &[Tag]) -> bool {
+        let value_of = |key: &str| tags.iter().find(|tag| tag.key == key).map(|tag| &tag.value);
+        match self {
+            TagFilter::Exists(key) => value_of(key).is_some(),
+            TagFilter::NotExists(key) => value_of(key).is_none(),
+            TagFilter::Equals(key, value) => value_of(key) == Some(value),
+            TagFilter::NotEquals(key, value) => value_of(key) != Some(value),
+            TagFilter::Matches(key, regex) => value_of(key).is_some_and(|v| regex.is_match(v)),
+        }
+    }
+}
+
+impl PartialEq for TagFilter {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TagFilter::Exists(a), TagFilter::Exists(b)) => a == b,
+            (TagFilter::NotExists(a), TagFilter::NotExists(b)) => a == b,
+            (TagFilter::Equals(a, x), TagFilter::Equals(b, y)) => a == b && x == y,
+            (TagFilter::NotEquals(a, x), TagFilter::NotEquals(b, y)) => a == b && x == y,
+            // Regexes compare by their pattern
+            (TagFilter::Matches(a, x), TagFilter::Matches(b, y)) => {
+                a == b && x.as_str() == y.as_str()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A query statement selecting elements of one or more types, e.g. `way[highway=primary](bbox)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementQuery {
+    /// The element types to select. `nwr` selects all three types.
+    pub types: Vec<ElementType>,
+    pub tags: Vec<TagFilter>,
+    /// The bounding box in nanodegrees.
+    pub bbox: Option<Bound>,
+    /// The ids given with `(id)` or `(id:1,2,3)`. An empty list selects elements of any id.
+    pub ids: Vec<i64>,
+}
+
+impl ElementQuery {
+    /// Checks the tag predicates of an element.
+    pub fn matches_tags<E: BasicElement>(&self, element: &E) -> bool {
+        self.tags
+            .iter()
+            .all(|filter| filter.matches(element.get_tags()))
+    }
+}
+
+/// The recursion statements `>` and `<`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recursion {
+    /// `>`: the nodes of ways, and the nodes and ways of relations (with the nodes of those ways).
+    Down,
+    /// `<`: the ways containing nodes, and the relations having any of the elements as members.
+    Up,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Query(ElementQuery),
+    Recurse(Recursion),
+    /// `( ... );` collects the results of all inner statements.
+    Union(Vec<Statement>),
+    /// `out;` outputs the current result set. Output modifiers are accepted and ignored.
+    Out,
+}
+
+/// A parsed query written in a small subset of OverpassQL.
+///
+/// The supported subset consists of query statements for `node`, `way`, `relation` (or `rel`)
+/// and `nwr` with tag predicates (`[k]`, `[!k]`, `[k=v]`, `[k!=v]`, `[k~regex]`), a bounding
+/// box filter `(south,west,north,east)` and id filters `(id)` / `(id:1,2)`, the recursion
+/// statements `>` and `<`, unions `( ... );` and `out;`. Settings such as `[out:json];` are
+/// ignored.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::query::Query;
+///
+/// let query: Query = "way[highway=primary](42.5,1.5,42.6,1.6); >; out;".parse().unwrap();
+/// assert_eq!(query.statements.len(), 3);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub statements: Vec<Statement>,
+}
+
+impl FromStr for Query {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.chars().peekable(),
+        };
+        let statements = parser.parse_statements(false)?;
+        Ok(Query { statements })
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().copied()
+    }
+
+    fn consume(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> anyhow::Result<()> {
+        if !self.consume(expected) {
+            match self.peek() {
+                Some(c) => bail!("Expected '{}' but found '{}'", expected, c),
+                None => bail!("Expected '{}' but reached the end of the query", expected),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_word(&mut self) -> String {
+        self.skip_whitespace();
+        let mut word = String::new();
+        while let Some(c) = self.chars.peek() {
+            if !(c.is_alphanumeric() || matches!(c, '_' | ':' | '.' | '-')) {
+                break;
+            }
+            word.push(*c);
+            self.chars.next();
+        }
+        word
+    }
+
+    /// Parses a bare word or a quoted string.
+    fn parse_string(&mut self) -> anyhow::Result<String> {
+        let quote = match self.peek() {
+            Some(c @ ('"' | '\'')) => c,
+            _ => {
+                let word = self.parse_word();
+                if word.is_empty() {
+                    bail!("Expected a key or value");
+                }
+                return Ok(word);
+            }
+        };
+        self.chars.next();
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some('\\') => match self.chars.next() {
+                    Some(c) => value.push(c),
+                    None => bail!("Unterminated string"),
+                },
+                Some(c) if c == quote => return Ok(value),
+                Some(c) => value.push(c),
+                None => bail!("Unterminated string"),
+            }
+        }
+    }
+
+    fn parse_statements(&mut self, in_union: bool) -> anyhow::Result<Vec<Statement>> {
+        let mut statements = Vec::new();
+        loop {
+            match self.peek() {
+                None if in_union => bail!("Unterminated union"),
+                None => return Ok(statements),
+                Some(')') if in_union => {
+                    self.chars.next();
+                    return Ok(statements);
+                }
+                Some('[') => {
+                    // settings like [out:json] or [timeout:25]
+                    for c in self.chars.by_ref() {
+                        if c == ']' {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                Some(_) => {
+                    if let Some(statement) = self.parse_statement()? {
+                        statements.push(statement);
+                    }
+                }
+            }
+            self.expect(';')?;
+        }
+    }
+
+    fn parse_statement(&mut self) -> anyhow::Result<Option<Statement>> {
+        if self.consume('(') {
+            return Ok(Some(Statement::Union(self.parse_statements(true)?)));
+        }
+        if self.consume('>') {
+            return Ok(Some(Statement::Recurse(Recursion::Down)));
+        }
+        if self.consume('<') {
+            return Ok(Some(Statement::Recurse(Recursion::Up)));
+        }
+        let word = self.parse_word();
+        let types = match word.as_str() {
+            "node" => vec![ElementType::Node],
+            "way" => vec![ElementType::Way],
+            "relation" | "rel" => vec![ElementType::Relation],
+            "nwr" => vec![ElementType::Node, ElementType::Way, ElementType::Relation],
+            "out" => {
+                // output modifiers such as `out body;` or `out meta;`
+                while !matches!(self.peek(), Some(';') | None) {
+                    self.chars.next();
+                }
+                return Ok(Some(Statement::Out));
+            }
+            "" => match self.peek() {
+                Some(';') => return Ok(None),
+                Some(c) => bail!("Unexpected character '{}'", c),
+                None => bail!("Unexpected end of the query"),
+            },
+            _ => bail!("Unsupported statement: {}", word),
+        };
+        let mut query = ElementQuery {
+            types,
+            tags: Vec::new(),
+            bbox: None,
+            ids: Vec::new(),
+        };
+        loop {
+            if self.consume('[') {
+                query.tags.push(self.parse_tag_filter()?);
+                self.expect(']')?;
+            } else if self.consume('(') {
+                self.parse_parenthesized_filter(&mut query)?;
+                self.expect(')')?;
+            } else {
+                return Ok(Some(Statement::Query(query)));
+            }
+        }
+    }
+
+    fn parse_tag_filter(&mut self) -> anyhow::Result<TagFilter> {
+        if self.consume('!') {
+            return Ok(TagFilter::NotExists(self.parse_string()?));
+        }
+        let key = self.parse_string()?;
+        if self.consume('=') {
+            Ok(TagFilter::Equals(key, self.parse_string()?))
+        } else if self.consume('~') {
+            let pattern = self.parse_string()?;
+            let regex = Regex::new(&pattern)
+                .map_err(|err| anyhow!("Invalid regular expression {:?}: {}", pattern, err))?;
+            Ok(TagFilter::Matches(key, regex))
+        } else if self.consume('!') {
+            self.expect('=')?;
+            Ok(TagFilter::NotEquals(key, self.parse_string()?))
+        } else {
+            Ok(TagFilter::Exists(key))
+        }
+    }
+
+    fn parse_parenthesized_filter(&mut self, query: &mut ElementQuery) -> anyhow::Result<()> {
+        let mut numbers = Vec::new();
+        let mut is_id_list = false;
+        loop {
+            let word = self.parse_word();
+            match word.strip_prefix("id:") {
+                Some(id) if numbers.is_empty() => {
+                    is_id_list = true;
+                    numbers.push(id.to_string());
+                }
+                _ => numbers.push(word),
+            }
+            if !self.consume(',') {
+                break;
+            }
+        }
+        if is_id_list || numbers.len() == 1 {
+            for number in numbers {
+                query.ids.push(
+                    number
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid element id: {}", number))?,
+                );
+            }
+            return Ok(());
+        }
+        if numbers.len() != 4 {
+            bail!("A bounding box needs four coordinates: south,west,north,east");
+        }
+        let mut coordinates = [0i64; 4];
+        for (coordinate, number) in coordinates.iter_mut().zip(numbers.iter()) {
+            let degrees: f64 = number
+                .parse()
+                .map_err(|_| anyhow!("Invalid coordinate: {}", number))?;
+            *coordinate = (degrees * 1e9).round() as i64;
+        }
+        let [south, west, north, east] = coordinates;
+        query.bbox = Some(Bound {
+            left: west,
+            right: east,
+            top: north,
+            bottom: south,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let query: Query =
+            "[out:json]; ( way[highway=\"primary\"][!oneway](42.5,1.5,42.6,1.6); node(id:1,2); ); >; out body;"
+                .parse()
+                .unwrap();
+        assert_eq!(query.statements.len(), 3);
+        let Statement::Union(inner) = &query.statements[0] else {
+            panic!("expected a union");
+        };
+        let Statement::Query(ways) = &inner[0] else {
+            panic!("expected a query");
+        };
+        assert_eq!(ways.types, vec![ElementType::Way]);
+        assert_eq!(
+            ways.tags,
+            vec![
+                TagFilter::Equals("highway".to_string(), "primary".to_string()),
+                TagFilter::NotExists("oneway".to_string()),
+            ]
+        );
+        assert_eq!(ways.bbox.as_ref().unwrap().left, 1_500_000_000);
+        assert_eq!(ways.bbox.as_ref().unwrap().top, 42_600_000_000);
+        let Statement::Query(nodes) = &inner[1] else {
+            panic!("expected a query");
+        };
+        assert_eq!(nodes.ids, vec![1, 2]);
+        assert_eq!(query.statements[1], Statement::Recurse(Recursion::Down));
+        assert_eq!(query.statements[2], Statement::Out);
+
+        let query: Query = "way[name~\"^Calle\"];".parse().unwrap();
+        let Statement::Query(ways) = &query.statements[0] else {
+            panic!("expected a query");
+        };
+        let name = |value: &str| {
+            vec![Tag {
+                key: "name".to_string(),
+                value: value.to_string(),
+            }]
+        };
+        assert!(ways.tags[0].matches(&name("Calle Mayor")));
+        assert!(!ways.tags[0].matches(&name("La Calle")));
+        assert!(!ways.tags[0].matches(&[]));
+
+        assert!("way[highway".parse::<Query>().is_err());
+        assert!("way[name~\"(\"];".parse::<Query>().is_err());
+        assert!("area[name=x];".parse::<Query>().is_err());
+    }
+}
@@ -1,35 +1,205 @@
-use std::{fs::File, io::BufReader, ops::Deref, rc::Rc};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::{fs::File, io::BufReader, ops::Deref, sync::Arc, thread};
 
-use quick_cache::unsync::Cache;
+use quick_cache::{unsync::Cache, Weighter};
 
 use super::raw_reader::PbfReader;
 use super::traits::{BlobData, PbfRandomRead};
+use crate::utils::{EstimatedSize, MemoryBudget, MemoryReservation};
 
+/// Weighs the cached blobs by count, or by size when the cache has a memory budget.
+#[derive(Clone)]
+enum BlobWeighter {
+    Count,
+    Size,
+}
+
+impl Weighter<u64, Arc<BlobData>> for BlobWeighter {
+    fn weight(&self, _offset: &u64, blob: &Arc<BlobData>) -> u64 {
+        match self {
+            BlobWeighter::Count => 1,
+            BlobWeighter::Size => blob.estimated_size() as u64,
+        }
+    }
+}
+
+/// A thread decoding the blobs to prefetch with its own reader of the file.
+struct Prefetcher {
+    requests: Sender<u64>,
+    blobs: Receiver<(u64, anyhow::Result<Arc<BlobData>>)>,
+    /// The offsets requested and not received yet.
+    pending: HashSet<u64>,
+}
+
+impl Prefetcher {
+    fn spawn(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = PbfReader::from_path(path)?;
+        let (requests, offsets) = mpsc::channel::<u64>();
+        let (sender, blobs) = mpsc::channel();
+        thread::Builder::new()
+            .name("pbf-prefetch".to_string())
+            .spawn(move || {
+                // Ends when the reader, and with it the sender of the requests, is dropped
+                for offset in offsets {
+                    let blob = reader.read_blob_by_offset(offset);
+                    if sender.send((offset, blob)).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        Ok(Self {
+            requests,
+            blobs,
+            pending: HashSet::new(),
+        })
+    }
+}
+
+/// A reader of a PBF file which keeps the blobs it decoded in a cache.
+///
+/// A reader created with `from_path` also decodes the blobs passed to `prefetch` on a
+/// background thread, so they are in the cache by the time they are read.
 pub struct CachedReader {
     reader: PbfReader<BufReader<File>>,
-    blob_cache: Cache<u64, Rc<BlobData>>,
+    blob_cache: Cache<u64, Arc<BlobData>, BlobWeighter>,
+    cache_capacity: usize,
+    reservation: Option<MemoryReservation>,
+    path: Option<PathBuf>,
+    prefetcher: Option<Prefetcher>,
+    prefetched: usize,
 }
 
 impl CachedReader {
     pub fn new(reader: PbfReader<BufReader<File>>, cache_capacity: usize) -> Self {
         Self {
             reader,
-            blob_cache: Cache::new(cache_capacity),
+            blob_cache: Cache::with_weighter(
+                cache_capacity,
+                cache_capacity as u64,
+                BlobWeighter::Count,
+            ),
+            cache_capacity,
+            reservation: None,
+            path: None,
+            prefetcher: None,
+            prefetched: 0,
         }
     }
+
+    /// Creates a reader of a PBF file caching up to `cache_capacity` blobs, which can prefetch
+    /// blobs.
+    pub fn from_path<P: AsRef<Path>>(path: P, cache_capacity: usize) -> anyhow::Result<Self> {
+        let mut reader = Self::new(PbfReader::from_path(path.as_ref())?, cache_capacity);
+        reader.path = Some(path.as_ref().to_path_buf());
+        Ok(reader)
+    }
+
+    /// Limits the cache by the memory the other components sharing the budget leave instead of
+    /// by the number of blobs, see `MemoryBudget`. Blobs are evicted when a missed blob is read
+    /// and the cache doesn't fit anymore. The cache is emptied.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        let reservation = budget.reservation();
+        self.blob_cache = Cache::with_weighter(
+            self.cache_capacity,
+            reservation.max_size() as u64,
+            BlobWeighter::Size,
+        );
+        self.reservation = Some(reservation);
+    }
+
+    /// Returns the number of blobs requested from the prefetching thread.
+    pub fn prefetched(&self) -> usize {
+        self.prefetched
+    }
+
+    fn insert(&mut self, offset: u64, blob: Arc<BlobData>) {
+        if let Some(reservation) = &self.reservation {
+            self.blob_cache.set_capacity(reservation.max_size() as u64);
+        }
+        self.blob_cache.insert(offset, blob);
+        if let Some(reservation) = &mut self.reservation {
+            reservation.resize(self.blob_cache.weight() as usize);
+        }
+    }
+
+    /// Moves the blobs decoded by the prefetching thread into the cache. If `wait_for` is
+    /// pending, waits until it's decoded and returns it.
+    fn receive_prefetched(&mut self, wait_for: Option<u64>) -> Option<Arc<BlobData>> {
+        let prefetcher = self.prefetcher.as_mut()?;
+        let mut received = Vec::new();
+        let mut waited = None;
+        while let Some(wanted) = wait_for.filter(|offset| prefetcher.pending.contains(offset)) {
+            let Ok((offset, blob)) = prefetcher.blobs.recv() else {
+                break;
+            };
+            prefetcher.pending.remove(&offset);
+            if offset == wanted {
+                // A failed prefetch is read again, which returns the error
+                waited = blob.ok();
+            } else {
+                received.push((offset, blob));
+            }
+        }
+        while let Ok((offset, blob)) = prefetcher.blobs.try_recv() {
+            prefetcher.pending.remove(&offset);
+            received.push((offset, blob));
+        }
+        for (offset, blob) in received {
+            match blob {
+                Ok(blob) => self.insert(offset, blob),
+                Err(err) => trace!("Unable to prefetch the blob at offset {}: {}", offset, err),
+            }
+        }
+        waited
+    }
 }
 
 impl PbfRandomRead for CachedReader {
-    fn read_blob_by_offset(&mut self, offset: u64) -> anyhow::Result<Rc<BlobData>> {
+    fn read_blob_by_offset(&mut self, offset: u64) -> anyhow::Result<Arc<BlobData>> {
+        if let Some(blob) = self.receive_prefetched(Some(offset)) {
+            trace!("Blob prefetched at offset {}", offset);
+            self.insert(offset, blob.clone());
+            return Ok(blob);
+        }
         match self.blob_cache.get(&offset) {
-            Some(blob) => Ok(blob.clone()),
+            Some(blob) => {
+                trace!("Blob cache hit at offset {}", offset);
+                Ok(blob.clone())
+            }
             None => {
+                trace!("Blob cache miss at offset {}", offset);
                 let blob = self.reader.read_blob_by_offset(offset)?;
-                self.blob_cache.insert(offset, blob.clone());
+                self.insert(offset, blob.clone());
                 Ok(blob)
             }
         }
     }
+
+    fn prefetch(&mut self, offset: u64) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if self.blob_cache.contains_key(&offset) {
+            return;
+        }
+        if self.prefetcher.is_none() {
+            match Prefetcher::spawn(path) {
+                Ok(prefetcher) => self.prefetcher = Some(prefetcher),
+                Err(err) => {
+                    warn!("Unable to start prefetching: {}", err);
+                    self.path = None;
+                    return;
+                }
+            }
+        }
+        let prefetcher = self.prefetcher.as_mut().unwrap();
+        if !prefetcher.pending.contains(&offset) && prefetcher.requests.send(offset).is_ok() {
+            prefetcher.pending.insert(offset);
+            self.prefetched += 1;
+        }
+    }
 }
 
 impl Deref for CachedReader {
@@ -39,3 +209,72 @@ impl Deref for CachedReader {
         &self.reader
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let path = "resources/andorra-latest.osm.pbf";
+        let mut sizes = Vec::new();
+        let mut pbf_reader = PbfReader::from_path(path).unwrap();
+        while let Some(blob) = pbf_reader.read_next_blob().unwrap() {
+            sizes.push((blob.offset, blob.estimated_size()));
+        }
+        assert!(sizes.len() >= 4);
+
+        let limit: usize = sizes[..3].iter().map(|(_, size)| size).sum();
+        let budget = MemoryBudget::new(limit);
+        let mut reader = CachedReader::new(PbfReader::from_path(path).unwrap(), 1);
+        reader.set_memory_budget(budget.clone());
+        for (offset, _) in &sizes[..3] {
+            reader.read_blob_by_offset(*offset).unwrap();
+        }
+        // The budget, not the capacity of one blob, limits the cache
+        assert_eq!(reader.blob_cache.len(), 3);
+        assert_eq!(budget.used(), limit);
+
+        // The cache gives way to another component taking most of the budget
+        let mut other = budget.reservation();
+        other.grow(limit - sizes[3].1);
+        reader.read_blob_by_offset(sizes[3].0).unwrap();
+        assert!(budget.used() <= limit);
+        assert!(reader.blob_cache.len() < 3);
+
+        drop(reader);
+        assert_eq!(budget.used(), other.size());
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let path = "resources/andorra-latest.osm.pbf";
+        let mut offsets = Vec::new();
+        let mut pbf_reader = PbfReader::from_path(path).unwrap();
+        while let Some(blob) = pbf_reader.read_next_blob().unwrap() {
+            offsets.push(blob.offset);
+        }
+
+        // Readers created from a reader can't prefetch
+        let mut reader = CachedReader::new(PbfReader::from_path(path).unwrap(), 10);
+        reader.prefetch(offsets[0]);
+        assert_eq!(reader.prefetched(), 0);
+
+        let mut reader = CachedReader::from_path(path, 10).unwrap();
+        for offset in &offsets[..3] {
+            reader.prefetch(*offset);
+        }
+        reader.prefetch(offsets[0]);
+        assert_eq!(reader.prefetched(), 3);
+        for offset in &offsets[..3] {
+            let blob = reader.read_blob_by_offset(*offset).unwrap();
+            assert_eq!(blob.offset, *offset);
+        }
+        assert!(reader.prefetcher.as_ref().unwrap().pending.is_empty());
+        assert_eq!(reader.blob_cache.len(), 3);
+
+        // Cached blobs aren't prefetched again
+        reader.prefetch(offsets[1]);
+        assert_eq!(reader.prefetched(), 3);
+    }
+}
@@ -1,5 +1,5 @@
-use std::collections::{BTreeMap, HashSet};
-use std::fs::File;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{self, File};
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::ops::Bound;
 use std::str;
@@ -9,9 +9,11 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use super::cached_reader::CachedReader;
 use super::raw_reader::PbfReader;
-use super::traits::PbfRandomRead;
-use crate::models::{Element, ElementType, Node, Relation, Way};
-use crate::utils::file;
+use super::traits::{BlobData, BlobElement, ElementRef, PbfRandomRead};
+use crate::codecs::blob::{BlobReader, DecodedBlob};
+use crate::models::{Element, ElementId, ElementType, Node, Relation, Way};
+use crate::utils::{file, IdSet, MemoryBudget};
+use crate::writers::{PbfWriter, WriteReport};
 
 fn get_index_path_from_pbf_path(pbf_path: &str) -> String {
     let mut index_path = pbf_path.to_owned();
@@ -20,13 +22,173 @@ fn get_index_path_from_pbf_path(pbf_path: &str) -> String {
     return index_path;
 }
 
-struct PbfIndex {
+/// What `IndexedReader` does when the blob the index points to doesn't contain the element
+/// looked up.
+///
+/// The index only stores the last ID of every blob, so it relies on the elements of each type
+/// being sorted by ID. In files with interleaved IDs, an element may sit in another blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexMissPolicy {
+    /// Treats the element as absent.
+    Absent,
+    /// Also scans up to this many blobs of the same type before and after the blob the index
+    /// points to, which finds elements slightly out of order.
+    ScanNeighbors(usize),
+    /// Fails, so that lookups of elements which must exist, e.g. the references of an
+    /// extract, don't silently miss them.
+    Strict,
+}
+
+impl Default for IndexMissPolicy {
+    fn default() -> Self {
+        IndexMissPolicy::ScanNeighbors(1)
+    }
+}
+
+/// A change to an element, as applied by `IndexedReader::apply_edits`.
+#[derive(Debug, Clone)]
+pub enum ElementEdit {
+    /// Replaces the element of the same type and ID, or inserts it if there is none.
+    Replace(Element),
+    /// Deletes an element. Deleting an element which doesn't exist does nothing.
+    Delete(ElementType, i64),
+}
+
+impl ElementEdit {
+    fn element_meta(&self) -> (ElementType, i64) {
+        match self {
+            ElementEdit::Replace(element) => element.get_meta(),
+            ElementEdit::Delete(element_type, id) => (element_type.clone(), *id),
+        }
+    }
+}
+
+/// The number of blobs indexed between two checkpoints.
+const CHECKPOINT_INTERVAL: usize = 1000;
+/// The number of ascending lookups in a row after which the following blobs are prefetched.
+const ASCENDING_LOOKUPS_TO_PREFETCH: usize = 3;
+/// The number of blobs prefetched ahead of ascending lookups by default.
+const DEFAULT_PREFETCH_BLOBS: usize = 2;
+
+/// The IDs of all elements of a PBF file, by type.
+#[derive(Clone, Default)]
+struct IdSets {
+    nodes: IdSet,
+    ways: IdSet,
+    relations: IdSet,
+}
+
+impl IdSets {
+    fn build(pbf_file: &str) -> anyhow::Result<Self> {
+        let mut id_sets = IdSets::default();
+        let mut reader = PbfReader::from_path(pbf_file)?;
+        while let Some(block_ids) = reader.read_next_block_ids()? {
+            id_sets.nodes.extend(block_ids.node_ids);
+            id_sets.ways.extend(block_ids.way_ids);
+            id_sets.relations.extend(block_ids.relation_ids);
+        }
+        Ok(id_sets)
+    }
+
+    fn get_mut(&mut self, element_type: &ElementType) -> &mut IdSet {
+        match element_type {
+            ElementType::Node => &mut self.nodes,
+            ElementType::Way => &mut self.ways,
+            ElementType::Relation => &mut self.relations,
+        }
+    }
+}
+
+/// The number of elements of each type in a data blob, as stored in the index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlobCounts {
+    /// The byte offset of the blob in the file.
+    pub offset: u64,
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+}
+
+impl BlobCounts {
+    /// Returns the number of elements of all types.
+    pub fn elements(&self) -> u64 {
+        self.nodes + self.ways + self.relations
+    }
+}
+
+/// The data blobs of an indexed PBF file and their element counts, as returned by
+/// `PbfIndex::summary`.
+///
+/// Unlike the number of blobs, the counts show how much work each blob is, e.g. for reporting
+/// the progress of a scan or balancing blobs between workers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexSummary {
+    /// The blobs in the order of the file. Blobs without elements are left out.
+    pub blobs: Vec<BlobCounts>,
+}
+
+impl IndexSummary {
+    pub fn nodes(&self) -> u64 {
+        self.blobs.iter().map(|blob| blob.nodes).sum()
+    }
+
+    pub fn ways(&self) -> u64 {
+        self.blobs.iter().map(|blob| blob.ways).sum()
+    }
+
+    pub fn relations(&self) -> u64 {
+        self.blobs.iter().map(|blob| blob.relations).sum()
+    }
+
+    /// Returns the total number of elements.
+    pub fn elements(&self) -> u64 {
+        self.blobs.iter().map(BlobCounts::elements).sum()
+    }
+
+    /// Returns the fraction of the elements, from 0 to 1, in the blobs before an offset, e.g.
+    /// the position of a reader.
+    pub fn progress(&self, offset: u64) -> f64 {
+        let total = self.elements();
+        if total == 0 {
+            return 1.0;
+        }
+        let done: u64 = self
+            .blobs
+            .iter()
+            .take_while(|blob| blob.offset < offset)
+            .map(BlobCounts::elements)
+            .sum();
+        done as f64 / total as f64
+    }
+}
+
+/// The index of a PBF file, stored next to it with the extension `.pif`.
+///
+/// It maps the last ID of each type in every blob to the offset of the blob, and stores the
+/// element counts of every blob.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::PbfIndex;
+///
+/// let pbf_index = PbfIndex::new("resources/andorra-latest.osm.pbf").unwrap();
+/// let summary = pbf_index.summary();
+/// println!("{} elements in {} blobs", summary.elements(), summary.blobs.len());
+/// ```
+pub struct PbfIndex {
     node_index: BTreeMap<i64, u64>,
     way_index: BTreeMap<i64, u64>,
     relation_index: BTreeMap<i64, u64>,
+    /// The IDs of all elements, if built; saved after the index entries.
+    id_sets: Option<IdSets>,
+    /// The element counts of the blobs by offset; `None` for index files written before they
+    /// were added, which are rebuilt.
+    blob_counts: Option<BTreeMap<u64, BlobCounts>>,
 }
 
 impl PbfIndex {
+    /// Loads the index of a PBF file, building it first if it doesn't exist or is outdated.
     pub fn new(pbf_file: &str) -> anyhow::Result<Self> {
         if !pbf_file.ends_with(".pbf") {
             bail!("It's not a .pbf file")
@@ -39,87 +201,126 @@ impl PbfIndex {
         if file::exists(&index_file_path) {
             // PBF index file already exists
             let (pi, checksum_in_file) = PbfIndex::load_from_file(&index_file_path)?;
-            if checksum.eq(&checksum_in_file) {
+            if checksum.eq(&checksum_in_file) && pi.blob_counts.is_some() {
                 // The checksum is consistent. The index loading is complete
+                debug!("Loaded the index file {}", index_file_path);
                 return Ok(pi);
             }
+            debug!("The index file {} is outdated", index_file_path);
         }
 
-        let pbf_index = PbfIndex::load_from_pbf_file(pbf_file)?;
+        let checkpoint = IndexCheckpoint {
+            path: format!("{}.partial", index_file_path),
+            checksum: checksum.clone(),
+            interval: CHECKPOINT_INTERVAL,
+        };
+        let pbf_index = PbfIndex::build(pbf_file, Some(&checkpoint))?;
         pbf_index.persist(&index_file_path, &checksum)?;
+        checkpoint.remove()?;
 
         Ok(pbf_index)
     }
 
-    fn load_from_file(index_path: &str) -> anyhow::Result<(PbfIndex, String)> {
-        let mut node_index: BTreeMap<i64, u64> = BTreeMap::new();
-        let mut way_index: BTreeMap<i64, u64> = BTreeMap::new();
-        let mut relation_index: BTreeMap<i64, u64> = BTreeMap::new();
+    fn empty() -> PbfIndex {
+        PbfIndex {
+            node_index: BTreeMap::new(),
+            way_index: BTreeMap::new(),
+            relation_index: BTreeMap::new(),
+            id_sets: None,
+            blob_counts: Some(BTreeMap::new()),
+        }
+    }
 
+    /// Returns the data blobs of the file and their element counts.
+    pub fn summary(&self) -> IndexSummary {
+        IndexSummary {
+            blobs: self
+                .blob_counts
+                .iter()
+                .flat_map(|blob_counts| blob_counts.values().copied())
+                .collect(),
+        }
+    }
+
+    fn load_from_file(index_path: &str) -> anyhow::Result<(PbfIndex, String)> {
         let index_file = File::open(index_path)?;
         let mut reader = BufReader::new(index_file);
 
-        let mut md5_buf = [0u8; 32];
-        reader.read_exact(&mut md5_buf)?;
-        let checksum = str::from_utf8(&md5_buf)?;
-
-        loop {
-            let write_type = reader.read_u8()?;
-            if write_type == 0 {
-                break;
-            }
-
-            let id = reader.read_i64::<LittleEndian>()?;
-            let offset = reader.read_u64::<LittleEndian>()?;
-            match write_type {
-                1 => node_index.insert(id, offset),
-                2 => way_index.insert(id, offset),
-                3 => relation_index.insert(id, offset),
-                _ => bail!("Unsupported write type"),
-            };
-        }
-
-        Ok((
-            PbfIndex {
-                node_index,
-                way_index,
-                relation_index,
-            },
-            checksum.to_string(),
-        ))
+        let checksum = Self::read_checksum(&mut reader)?;
+        let mut pbf_index = Self::read_entries(&mut reader)?;
+        pbf_index.read_sections(&mut reader)?;
+        Ok((pbf_index, checksum))
     }
 
-    fn load_from_pbf_file(pbf_file_path: &str) -> anyhow::Result<PbfIndex> {
-        // Indexing...
-        let mut node_index: BTreeMap<i64, u64> = BTreeMap::new();
-        let mut way_index: BTreeMap<i64, u64> = BTreeMap::new();
-        let mut relation_index: BTreeMap<i64, u64> = BTreeMap::new();
-
+    /// Indexes a PBF file, saving the partial index to the checkpoint every few blobs.
+    ///
+    /// If the checkpoint holds a partial index of the same file, e.g. because an earlier run was
+    /// interrupted, indexing resumes from the offset recorded in it.
+    fn build(
+        pbf_file_path: &str,
+        checkpoint: Option<&IndexCheckpoint>,
+    ) -> anyhow::Result<PbfIndex> {
+        let _span = debug_span!("build_index", path = pbf_file_path);
         let mut reader = PbfReader::from_path(pbf_file_path)?;
-        while let Some(blob_data) = reader.read_next_blob() {
-            if blob_data.nodes.len() > 0 {
-                let last = blob_data.nodes.last().unwrap();
-                node_index.insert(last.id, blob_data.offset);
+
+        let mut index_instance = match checkpoint.map(IndexCheckpoint::load).transpose()? {
+            Some(Some((pbf_index, offset))) => {
+                debug!(
+                    "Resuming indexing of {} at offset {}",
+                    pbf_file_path, offset
+                );
+                reader.seek(offset)?;
+                pbf_index
             }
-            if blob_data.ways.len() > 0 {
-                let last = blob_data.ways.last().unwrap();
-                way_index.insert(last.id, blob_data.offset);
+            _ => {
+                debug!("Indexing {}", pbf_file_path);
+                PbfIndex::empty()
             }
-            if blob_data.relations.len() > 0 {
-                let last = blob_data.relations.last().unwrap();
-                relation_index.insert(last.id, blob_data.offset);
+        };
+
+        let mut blob_count = 0;
+        while let Some(block_ids) = reader.read_next_block_ids()? {
+            if let Some(last) = block_ids.node_ids.last() {
+                index_instance.node_index.insert(*last, block_ids.offset);
+            }
+            if let Some(last) = block_ids.way_ids.last() {
+                index_instance.way_index.insert(*last, block_ids.offset);
+            }
+            if let Some(last) = block_ids.relation_ids.last() {
+                index_instance
+                    .relation_index
+                    .insert(*last, block_ids.offset);
+            }
+
+            let blob_counts = BlobCounts {
+                offset: block_ids.offset,
+                nodes: block_ids.node_ids.len() as u64,
+                ways: block_ids.way_ids.len() as u64,
+                relations: block_ids.relation_ids.len() as u64,
+            };
+            if blob_counts.elements() > 0 {
+                index_instance.insert_counts(blob_counts);
+            }
+
+            blob_count += 1;
+            if let Some(checkpoint) = checkpoint {
+                if blob_count % checkpoint.interval == 0 {
+                    checkpoint.save(&index_instance, reader.position())?;
+                }
             }
         }
 
-        let index_instance = PbfIndex {
-            node_index,
-            way_index,
-            relation_index,
-        };
-        // Indexing completed
+        debug!(
+            "Indexed {} node, {} way and {} relation blobs",
+            index_instance.node_index.len(),
+            index_instance.way_index.len(),
+            index_instance.relation_index.len()
+        );
         Ok(index_instance)
     }
 
+    /// Returns the offset of the blob containing an element, if the file has elements of the
+    /// type with an ID at least as high.
     pub fn get_offset(&self, element_type: &ElementType, element_id: i64) -> Option<u64> {
         let cursor = match element_type {
             ElementType::Node => self.node_index.lower_bound(Bound::Included(&element_id)),
@@ -134,6 +335,115 @@ impl PbfIndex {
         }
     }
 
+    /// Returns whether the file contains an element, or `None` if the ID sets aren't built.
+    fn contains(&self, element_type: &ElementType, element_id: i64) -> Option<bool> {
+        let id_sets = self.id_sets.as_ref()?;
+        let ids = match element_type {
+            ElementType::Node => &id_sets.nodes,
+            ElementType::Way => &id_sets.ways,
+            ElementType::Relation => &id_sets.relations,
+        };
+        Some(ids.contains(element_id))
+    }
+
+    /// Returns the offset of the blob containing the elements of a type with the highest IDs.
+    fn get_last_offset(&self, element_type: &ElementType) -> Option<u64> {
+        let index = match element_type {
+            ElementType::Node => &self.node_index,
+            ElementType::Way => &self.way_index,
+            ElementType::Relation => &self.relation_index,
+        };
+        index.values().next_back().copied()
+    }
+
+    /// Returns the indexed elements of every blob, i.e. the last element of each type.
+    fn entries_by_offset(&self) -> HashMap<u64, Vec<(ElementType, i64)>> {
+        let mut entries: HashMap<u64, Vec<(ElementType, i64)>> = HashMap::new();
+        let indexes = [
+            (ElementType::Node, &self.node_index),
+            (ElementType::Way, &self.way_index),
+            (ElementType::Relation, &self.relation_index),
+        ];
+        for (element_type, index) in indexes {
+            for (id, offset) in index {
+                entries
+                    .entry(*offset)
+                    .or_default()
+                    .push((element_type.clone(), *id));
+            }
+        }
+        entries
+    }
+
+    fn insert(&mut self, element_type: &ElementType, element_id: i64, offset: u64) {
+        let index = match element_type {
+            ElementType::Node => &mut self.node_index,
+            ElementType::Way => &mut self.way_index,
+            ElementType::Relation => &mut self.relation_index,
+        };
+        index.insert(element_id, offset);
+    }
+
+    fn insert_counts(&mut self, blob_counts: BlobCounts) {
+        self.blob_counts
+            .get_or_insert_with(BTreeMap::new)
+            .insert(blob_counts.offset, blob_counts);
+    }
+
+    /// Returns the element counts of the blob at an offset.
+    fn get_counts(&self, offset: u64) -> Option<&BlobCounts> {
+        self.blob_counts.as_ref()?.get(&offset)
+    }
+
+    /// Returns the offsets of up to `count` blobs following the blob `get_offset` returns.
+    fn get_next_offsets(
+        &self,
+        element_type: &ElementType,
+        element_id: i64,
+        count: usize,
+    ) -> Vec<u64> {
+        let index = match element_type {
+            ElementType::Node => &self.node_index,
+            ElementType::Way => &self.way_index,
+            ElementType::Relation => &self.relation_index,
+        };
+        index
+            .range(element_id..)
+            .map(|(_, offset)| *offset)
+            .skip(1)
+            .take(count)
+            .collect()
+    }
+
+    /// Returns the offsets of up to `count` blobs before and after the blob `get_offset` returns,
+    /// nearest first. If the ID is beyond the last blob, the last blobs are returned.
+    fn get_neighbor_offsets(
+        &self,
+        element_type: &ElementType,
+        element_id: i64,
+        count: usize,
+    ) -> Vec<u64> {
+        let index = match element_type {
+            ElementType::Node => &self.node_index,
+            ElementType::Way => &self.way_index,
+            ElementType::Relation => &self.relation_index,
+        };
+        let mut after = index.range(element_id..).map(|(_, offset)| *offset);
+        let before = index.range(..element_id).rev().map(|(_, offset)| *offset);
+        let before: Vec<u64> = match after.next() {
+            Some(_) => before.take(count).collect(),
+            // The last blob takes the place of the blob pointed to
+            None => before.take(count + 1).collect(),
+        };
+        let after: Vec<u64> = after.take(count).collect();
+        let mut offsets = Vec::with_capacity(before.len() + after.len());
+        for i in 0..=count {
+            offsets.extend(before.get(i));
+            offsets.extend(after.get(i));
+        }
+        offsets
+    }
+
     fn persist(&self, index_path: &str, checksum: &str) -> anyhow::Result<()> {
         // Saving the index to file...
         let index_file = File::create(index_path)?;
@@ -141,19 +451,105 @@ impl PbfIndex {
         // write checksum
         writer.write_all(checksum.as_bytes())?;
         // write index
-        Self::persist_index_map(&mut writer, &self.node_index, 1)?;
-        Self::persist_index_map(&mut writer, &self.way_index, 2)?;
-        Self::persist_index_map(&mut writer, &self.relation_index, 3)?;
+        self.write_entries(&mut writer)?;
+        if let Some(id_sets) = &self.id_sets {
+            writer.write_u8(1)?;
+            id_sets.nodes.write_to(&mut writer)?;
+            id_sets.ways.write_to(&mut writer)?;
+            id_sets.relations.write_to(&mut writer)?;
+        }
+        self.write_blob_counts(&mut writer)?;
+        writer.flush()?;
+        // Saving completed
+        Ok(())
+    }
+
+    fn read_checksum<R: Read>(reader: &mut R) -> anyhow::Result<String> {
+        let mut md5_buf = [0u8; 32];
+        reader.read_exact(&mut md5_buf)?;
+        Ok(str::from_utf8(&md5_buf)?.to_string())
+    }
+
+    fn read_entries<R: Read>(reader: &mut R) -> anyhow::Result<PbfIndex> {
+        let mut pbf_index = PbfIndex::empty();
+        loop {
+            let write_type = reader.read_u8()?;
+            if write_type == 0 {
+                break;
+            }
+
+            let id = reader.read_i64::<LittleEndian>()?;
+            let offset = reader.read_u64::<LittleEndian>()?;
+            match write_type {
+                1 => pbf_index.node_index.insert(id, offset),
+                2 => pbf_index.way_index.insert(id, offset),
+                3 => pbf_index.relation_index.insert(id, offset),
+                _ => bail!("Unsupported write type"),
+            };
+        }
+        Ok(pbf_index)
+    }
+
+    /// Reads the optional sections following the entries, i.e. the ID sets and the blob
+    /// counts, until the end of the file. Older index files lack some or all of them.
+    fn read_sections<R: Read>(&mut self, reader: &mut R) -> anyhow::Result<()> {
+        self.id_sets = None;
+        self.blob_counts = None;
+        loop {
+            match reader.read_u8() {
+                Ok(1) => {
+                    self.id_sets = Some(IdSets {
+                        nodes: IdSet::read_from(reader)?,
+                        ways: IdSet::read_from(reader)?,
+                        relations: IdSet::read_from(reader)?,
+                    })
+                }
+                Ok(2) => {
+                    let mut blob_counts = BTreeMap::new();
+                    for _ in 0..reader.read_u64::<LittleEndian>()? {
+                        let counts = BlobCounts {
+                            offset: reader.read_u64::<LittleEndian>()?,
+                            nodes: reader.read_u64::<LittleEndian>()?,
+                            ways: reader.read_u64::<LittleEndian>()?,
+                            relations: reader.read_u64::<LittleEndian>()?,
+                        };
+                        blob_counts.insert(counts.offset, counts);
+                    }
+                    self.blob_counts = Some(blob_counts);
+                }
+                Ok(section) => bail!("Unsupported index section: {}", section),
+                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn write_blob_counts<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        if let Some(blob_counts) = &self.blob_counts {
+            writer.write_u8(2)?;
+            writer.write_u64::<LittleEndian>(blob_counts.len() as u64)?;
+            for counts in blob_counts.values() {
+                writer.write_u64::<LittleEndian>(counts.offset)?;
+                writer.write_u64::<LittleEndian>(counts.nodes)?;
+                writer.write_u64::<LittleEndian>(counts.ways)?;
+                writer.write_u64::<LittleEndian>(counts.relations)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_entries<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        Self::persist_index_map(writer, &self.node_index, 1)?;
+        Self::persist_index_map(writer, &self.way_index, 2)?;
+        Self::persist_index_map(writer, &self.relation_index, 3)?;
 
         // write an end symbol
         writer.write_u8(0)?;
-        writer.flush()?;
-        // Saving completed
         Ok(())
     }
 
-    fn persist_index_map(
-        writer: &mut BufWriter<File>,
+    fn persist_index_map<W: Write>(
+        writer: &mut W,
         index_map: &BTreeMap<i64, u64>,
         write_type: u8,
     ) -> anyhow::Result<()> {
@@ -166,9 +562,78 @@ impl PbfIndex {
     }
 }
 
+/// A partially built index saved while indexing, so that an interrupted run can resume.
+///
+/// The checkpoint file holds the checksum of the PBF file, the offset up to which it has been
+/// scanned and the index entries and blob counts found so far, in the format of the index file.
+struct IndexCheckpoint {
+    path: String,
+    checksum: String,
+    interval: usize,
+}
+
+impl IndexCheckpoint {
+    /// Loads the partial index and the offset to resume at. Returns `Ok(None)` if there is no
+    /// checkpoint or it belongs to another version of the PBF file.
+    fn load(&self) -> anyhow::Result<Option<(PbfIndex, u64)>> {
+        if !file::exists(&self.path) {
+            return Ok(None);
+        }
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        if PbfIndex::read_checksum(&mut reader)? != self.checksum {
+            debug!("The index checkpoint {} is outdated", self.path);
+            return Ok(None);
+        }
+        let offset = reader.read_u64::<LittleEndian>()?;
+        let mut pbf_index = PbfIndex::read_entries(&mut reader)?;
+        pbf_index.read_sections(&mut reader)?;
+        if pbf_index.blob_counts.is_none() {
+            debug!("The index checkpoint {} has no blob counts", self.path);
+            return Ok(None);
+        }
+        Ok(Some((pbf_index, offset)))
+    }
+
+    /// Saves the partial index. The file is replaced atomically, so an interruption while saving
+    /// leaves the previous checkpoint intact.
+    fn save(&self, pbf_index: &PbfIndex, offset: u64) -> anyhow::Result<()> {
+        let temp_path = format!("{}.tmp", self.path);
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        writer.write_all(self.checksum.as_bytes())?;
+        writer.write_u64::<LittleEndian>(offset)?;
+        pbf_index.write_entries(&mut writer)?;
+        pbf_index.write_blob_counts(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&temp_path, &self.path)?;
+        trace!(
+            "Saved the index checkpoint {} at offset {}",
+            self.path,
+            offset
+        );
+        Ok(())
+    }
+
+    fn remove(&self) -> anyhow::Result<()> {
+        if file::exists(&self.path) {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// A PBF file and its index.
+struct IndexedFile<T: PbfRandomRead> {
+    path: String,
+    pbf_reader: T,
+    pbf_index: PbfIndex,
+}
+
 /// A reader that provides indexed access to PBF file.
 ///
 /// The `IndexedReader` struct allows for efficient random access to PBF file by using an index.
+/// It can also present several files as one, e.g. files split by element type or tiled
+/// extracts, each file being indexed separately.
 /// It is generic over a type `T` that implements the `PbfRandomRead` trait, which provides the
 /// necessary methods for reading PBF data.
 ///
@@ -179,8 +644,11 @@ impl PbfIndex {
 ///
 /// # Fields
 ///
-/// * `pbf_reader` - An instance of type `T` that is used to read the PBF data.
-/// * `pbf_index` - An instance of `PbfIndex` that provides the index for efficient random access.
+/// * `files` - The PBF files, each read by an instance of type `T` and indexed by a `PbfIndex`.
+/// * `miss_policy` - What to do when an element isn't in the blob the index points to.
+/// * `prefetch_blobs` - The number of blobs prefetched ahead of ascending lookups.
+/// * `last_lookup` - The type and ID of the last element looked up alone.
+/// * `ascending_lookups` - The number of lookups in a row with ascending IDs.
 ///
 /// # Example
 ///
@@ -199,26 +667,68 @@ impl PbfIndex {
 /// will make reading more efficient
 ///
 /// ```rust
-/// use pbf_craft::models::ElementType;
+/// use pbf_craft::models::{ElementId, ElementType};
 /// use pbf_craft::readers::IndexedReader;
 ///
 /// let mut indexed_reader = IndexedReader::from_path_with_cache("resources/andorra-latest.osm.pbf", 1000).unwrap();
-/// let element_list = indexed_reader.get_with_deps(&ElementType::Way, 1055523837).unwrap();
+/// let way_id = ElementId::new(ElementType::Way, 1055523837);
+/// let element_list = indexed_reader.get_with_deps(way_id).unwrap();
+/// ```
+///
+/// The cached version also prefetches blobs: once elements are looked up by ascending IDs,
+/// e.g. ways assembled in ID order, the blobs following the current one are decoded ahead on a
+/// background thread, see `set_prefetch_blobs`. So are the blobs of batch lookups.
+///
+/// Sharded data can be read without merging it first. The files are searched in the given
+/// order, and an element present in several files, e.g. a node on the border of two tiles, is
+/// returned once.
+///
+/// ```rust
+/// use pbf_craft::models::{ElementId, ElementType};
+/// use pbf_craft::readers::IndexedReader;
+///
+/// let mut indexed_reader = IndexedReader::from_paths(&["resources/andorra-latest.osm.pbf"]).unwrap();
+/// let way_id = ElementId::new(ElementType::Way, 1055523837);
+/// let element_list = indexed_reader.get_with_deps(way_id).unwrap();
 /// ```
 ///
 pub struct IndexedReader<T: PbfRandomRead> {
-    pbf_reader: T,
-    pbf_index: PbfIndex,
+    files: Vec<IndexedFile<T>>,
+    miss_policy: IndexMissPolicy,
+    prefetch_blobs: usize,
+    last_lookup: Option<(ElementType, i64)>,
+    ascending_lookups: usize,
 }
 
 impl IndexedReader<PbfReader<BufReader<File>>> {
     /// Creates a new `IndexedReader` instance from a PBF file.
     pub fn from_path(pbf_file: &str) -> anyhow::Result<IndexedReader<PbfReader<BufReader<File>>>> {
-        let pbf_index = PbfIndex::new(pbf_file)?;
-        let pbf_reader = PbfReader::from_path(pbf_file)?;
+        Self::from_paths(&[pbf_file])
+    }
+
+    /// Creates a new `IndexedReader` instance presenting several PBF files as one.
+    ///
+    /// Every file gets its own index, which is built or loaded like the index of a single file.
+    pub fn from_paths(
+        pbf_files: &[&str],
+    ) -> anyhow::Result<IndexedReader<PbfReader<BufReader<File>>>> {
+        if pbf_files.is_empty() {
+            bail!("No PBF files given");
+        }
+        let mut files = Vec::with_capacity(pbf_files.len());
+        for pbf_file in pbf_files {
+            files.push(IndexedFile {
+                path: pbf_file.to_string(),
+                pbf_index: PbfIndex::new(pbf_file)?,
+                pbf_reader: PbfReader::from_path(pbf_file)?,
+            });
+        }
         Ok(IndexedReader {
-            pbf_index,
-            pbf_reader,
+            files,
+            miss_policy: IndexMissPolicy::default(),
+            prefetch_blobs: DEFAULT_PREFETCH_BLOBS,
+            last_lookup: None,
+            ascending_lookups: 0,
         })
     }
 }
@@ -238,29 +748,131 @@ impl IndexedReader<CachedReader> {
         cache_capacity: usize,
     ) -> anyhow::Result<IndexedReader<CachedReader>> {
         let pbf_index = PbfIndex::new(pbf_file)?;
-        let pbf_reader = PbfReader::from_path(pbf_file)?;
-        let cached_reader = CachedReader::new(pbf_reader, cache_capacity);
+        let cached_reader = CachedReader::from_path(pbf_file, cache_capacity)?;
         Ok(IndexedReader {
-            pbf_index,
-            pbf_reader: cached_reader,
+            files: vec![IndexedFile {
+                path: pbf_file.to_string(),
+                pbf_index,
+                pbf_reader: cached_reader,
+            }],
+            miss_policy: IndexMissPolicy::default(),
+            prefetch_blobs: DEFAULT_PREFETCH_BLOBS,
+            last_lookup: None,
+            ascending_lookups: 0,
         })
     }
+
+    /// Limits the cache by a memory budget shared with other components instead of by the
+    /// number of blobs, see `MemoryBudget`.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        for file in &mut self.files {
+            file.pbf_reader.set_memory_budget(budget.clone());
+        }
+    }
 }
 
 impl<T: PbfRandomRead> IndexedReader<T> {
-    /// Finds an node by its ID.
-    pub fn find_node(&mut self, node_id: i64) -> anyhow::Result<Option<Node>> {
-        let has_offset = self.pbf_index.get_offset(&ElementType::Node, node_id);
-        if has_offset.is_none() {
-            return Ok(None);
+    /// Sets what to do when an element isn't in the blob the index points to. By default, the
+    /// neighboring blob on each side is scanned too.
+    pub fn set_miss_policy(&mut self, miss_policy: IndexMissPolicy) {
+        self.miss_policy = miss_policy;
+    }
+
+    /// Sets the number of blobs prefetched ahead of lookups by ascending IDs, 2 by default.
+    /// 0 disables prefetching, including the blobs of batch lookups. Only readers which can
+    /// prefetch, like the one of `from_path_with_cache`, do.
+    pub fn set_prefetch_blobs(&mut self, prefetch_blobs: usize) {
+        self.prefetch_blobs = prefetch_blobs;
+    }
+
+    /// Records a lookup and returns the number of blobs to prefetch after the one of the
+    /// element, which is non-zero once enough lookups in a row had ascending IDs.
+    fn observe_lookup(&mut self, element_type: &ElementType, element_id: i64) -> usize {
+        let ascending = matches!(
+            &self.last_lookup,
+            Some((last_type, last_id)) if last_type == element_type && *last_id < element_id
+        );
+        self.ascending_lookups = if ascending {
+            self.ascending_lookups + 1
+        } else {
+            0
+        };
+        self.last_lookup = Some((element_type.clone(), element_id));
+        if self.ascending_lookups >= ASCENDING_LOOKUPS_TO_PREFETCH {
+            self.prefetch_blobs
+        } else {
+            0
+        }
+    }
+
+    /// Builds the sets of the IDs of all elements and saves them with the index, unless they
+    /// are already saved, in which case they were loaded with it.
+    ///
+    /// With the ID sets, `contains` doesn't decode any blob, and looking up elements which
+    /// aren't in the file, e.g. nodes referenced across the border of an extract, returns early.
+    /// They take about one bit per ID for the dense ID ranges of nodes.
+    pub fn build_id_sets(&mut self) -> anyhow::Result<()> {
+        for file in &mut self.files {
+            if file.pbf_index.id_sets.is_none() {
+                file.pbf_index.id_sets = Some(IdSets::build(&file.path)?);
+                file.pbf_index.persist(
+                    &get_index_path_from_pbf_path(&file.path),
+                    &file::checksum(&file.path)?,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether an element exists.
+    ///
+    /// It's answered from the ID sets if they are built, see `build_id_sets`; otherwise the
+    /// element is looked up, regardless of a strict miss policy.
+    pub fn contains(
+        &mut self,
+        element_type: &ElementType,
+        element_id: i64,
+    ) -> anyhow::Result<bool> {
+        let found: Vec<Option<bool>> = self
+            .files
+            .iter()
+            .map(|file| file.pbf_index.contains(element_type, element_id))
+            .collect();
+        if found.contains(&Some(true)) {
+            return Ok(true);
         }
-        let offset = has_offset.unwrap();
-        let blob_data = self.pbf_reader.read_blob_by_offset(offset)?;
-        let node = blob_data.nodes.iter().find(|node| node.id == node_id);
-        match node {
-            Some(n) => Ok(Some(n.clone())),
-            None => Ok(None),
+        if found.iter().all(|found| *found == Some(false)) {
+            return Ok(false);
         }
+        let miss_policy = self.miss_policy;
+        if miss_policy == IndexMissPolicy::Strict {
+            self.miss_policy = IndexMissPolicy::default();
+        }
+        let element = self.find(element_type, element_id);
+        self.miss_policy = miss_policy;
+        Ok(element?.is_some())
+    }
+
+    /// Returns whether the ID sets of all files are built and don't contain the element.
+    fn is_known_absent(&self, element_type: &ElementType, element_id: i64) -> bool {
+        self.files
+            .iter()
+            .all(|file| file.pbf_index.contains(element_type, element_id) == Some(false))
+    }
+
+    /// Finds an node by its ID.
+    pub fn find_node(&mut self, node_id: i64) -> anyhow::Result<Option<Node>> {
+        Ok(self
+            .find_node_ref(node_id)?
+            .map(|node| node.to_owned_element()))
+    }
+
+    /// Finds a node by its ID without copying it out of its blob.
+    ///
+    /// Combined with `IndexedReader::from_path_with_cache`, repeated lookups share the cached
+    /// blob instead of cloning the node every time.
+    pub fn find_node_ref(&mut self, node_id: i64) -> anyhow::Result<Option<ElementRef<Node>>> {
+        self.find_ref(node_id)
     }
 
     /// Finds nodes by their IDs.
@@ -268,39 +880,22 @@ impl<T: PbfRandomRead> IndexedReader<T> {
     /// `find_nodes` is more efficient than calling `find_node` multiple times when you have a batch of node IDs.
     ///
     pub fn find_nodes(&mut self, node_ids: &[i64]) -> anyhow::Result<Vec<Node>> {
-        let offsets: HashSet<u64> = node_ids
-            .into_iter()
-            .filter_map(|id| self.pbf_index.get_offset(&ElementType::Node, *id))
-            .collect();
-        let result: Vec<Node> = offsets
-            .into_iter()
-            .flat_map(|offset| {
-                let blob_data = self.pbf_reader.read_blob_by_offset(offset).unwrap();
-                let nodes: Vec<Node> = blob_data
-                    .nodes
-                    .iter()
-                    .filter(|node| node_ids.contains(&node.id))
-                    .map(|node| node.clone())
-                    .collect();
-                nodes
-            })
-            .collect();
-        Ok(result)
+        Ok(to_owned_elements(self.find_refs(node_ids)?))
+    }
+
+    /// Finds nodes by their IDs without copying them out of their blobs.
+    pub fn find_node_refs(&mut self, node_ids: &[i64]) -> anyhow::Result<Vec<ElementRef<Node>>> {
+        self.find_refs(node_ids)
     }
 
     /// Finds a way by its ID.
     pub fn find_way(&mut self, way_id: i64) -> anyhow::Result<Option<Way>> {
-        let has_offset = self.pbf_index.get_offset(&ElementType::Way, way_id);
-        if has_offset.is_none() {
-            return Ok(None);
-        }
-        let offset = has_offset.unwrap();
-        let blob_data = self.pbf_reader.read_blob_by_offset(offset)?;
-        let way = blob_data.ways.iter().find(|way| way.id == way_id);
-        match way {
-            Some(w) => Ok(Some(w.clone())),
-            None => Ok(None),
-        }
+        Ok(self.find_way_ref(way_id)?.map(|way| way.to_owned_element()))
+    }
+
+    /// Finds a way by its ID without copying it out of its blob.
+    pub fn find_way_ref(&mut self, way_id: i64) -> anyhow::Result<Option<ElementRef<Way>>> {
+        self.find_ref(way_id)
     }
 
     /// Finds ways by their IDs.
@@ -308,44 +903,83 @@ impl<T: PbfRandomRead> IndexedReader<T> {
     /// `find_ways` is more efficient than calling `find_way` multiple times when you have a batch of way IDs.
     ///
     pub fn find_ways(&mut self, way_ids: &[i64]) -> anyhow::Result<Vec<Way>> {
-        let offsets: HashSet<u64> = way_ids
-            .into_iter()
-            .filter_map(|id| self.pbf_index.get_offset(&ElementType::Way, *id))
+        Ok(to_owned_elements(self.find_refs(way_ids)?))
+    }
+
+    /// Finds ways by their IDs without copying them out of their blobs.
+    pub fn find_way_refs(&mut self, way_ids: &[i64]) -> anyhow::Result<Vec<ElementRef<Way>>> {
+        self.find_refs(way_ids)
+    }
+
+    /// Finds ways and sets the locations of their nodes, e.g. to assemble their geometries.
+    ///
+    /// The nodes of all ways are looked up at once: their IDs are deduplicated and sorted, and
+    /// each blob containing some of them is read once, in file order, which keeps the reads
+    /// sequential and lets the cache and prefetching work. Way nodes which already have a
+    /// location keep it, and those whose node isn't in the files keep none.
+    ///
+    /// The ways are returned in the order of `way_ids`; IDs of ways which don't exist are
+    /// skipped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::IndexedReader;
+    ///
+    /// let mut indexed_reader =
+    ///     IndexedReader::from_path_with_cache("resources/andorra-latest.osm.pbf", 1000).unwrap();
+    /// let ways = indexed_reader.resolve_way_geometries(&[1055523837]).unwrap();
+    /// assert!(ways[0].way_nodes.iter().all(|way_node| way_node.latitude.is_some()));
+    /// ```
+    pub fn resolve_way_geometries(&mut self, way_ids: &[i64]) -> anyhow::Result<Vec<Way>> {
+        let mut ways = self.find_ways(way_ids)?;
+        let positions: HashMap<i64, usize> = way_ids
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(position, way_id)| (*way_id, position))
             .collect();
-        let result: Vec<Way> = offsets
-            .into_iter()
-            .flat_map(|offset| {
-                let blob_data = self.pbf_reader.read_blob_by_offset(offset).unwrap();
-                let ways: Vec<Way> = blob_data
-                    .ways
-                    .iter()
-                    .filter(|way| way_ids.contains(&way.id))
-                    .map(|way| way.clone())
-                    .collect();
-                ways
-            })
+        ways.sort_by_key(|way| positions[&way.id]);
+
+        let mut node_ids: Vec<i64> = ways
+            .iter()
+            .flat_map(|way| &way.way_nodes)
+            .filter(|way_node| way_node.latitude.is_none() || way_node.longitude.is_none())
+            .map(|way_node| way_node.id)
             .collect();
-        Ok(result)
+        node_ids.sort_unstable();
+        node_ids.dedup();
+        let locations: HashMap<i64, (i64, i64)> = self
+            .find_node_refs(&node_ids)?
+            .iter()
+            .map(|node| (node.id, (node.latitude, node.longitude)))
+            .collect();
+
+        for way_node in ways.iter_mut().flat_map(|way| way.way_nodes.iter_mut()) {
+            if way_node.latitude.is_some() && way_node.longitude.is_some() {
+                continue;
+            }
+            if let Some((latitude, longitude)) = locations.get(&way_node.id) {
+                way_node.latitude = Some(*latitude);
+                way_node.longitude = Some(*longitude);
+            }
+        }
+        Ok(ways)
     }
 
     /// Finds a relation by its ID.
     pub fn find_relation(&mut self, relation_id: i64) -> anyhow::Result<Option<Relation>> {
-        let has_offset = self
-            .pbf_index
-            .get_offset(&ElementType::Relation, relation_id);
-        if has_offset.is_none() {
-            return Ok(None);
-        }
-        let offset = has_offset.unwrap();
-        let blob_data = self.pbf_reader.read_blob_by_offset(offset)?;
-        let rel = blob_data
-            .relations
-            .iter()
-            .find(|relation| relation.id == relation_id);
-        match rel {
-            Some(r) => Ok(Some(r.clone())),
-            None => Ok(None),
-        }
+        Ok(self
+            .find_relation_ref(relation_id)?
+            .map(|relation| relation.to_owned_element()))
+    }
+
+    /// Finds a relation by its ID without copying it out of its blob.
+    pub fn find_relation_ref(
+        &mut self,
+        relation_id: i64,
+    ) -> anyhow::Result<Option<ElementRef<Relation>>> {
+        self.find_ref(relation_id)
     }
 
     /// Finds relations by their IDs.
@@ -353,26 +987,263 @@ impl<T: PbfRandomRead> IndexedReader<T> {
     /// `find_relations` is more efficient than calling `find_relation` multiple times when you have a batch of relation IDs.
     ///
     pub fn find_relations(&mut self, relation_ids: &[i64]) -> anyhow::Result<Vec<Relation>> {
-        let offsets: HashSet<u64> = relation_ids
-            .into_iter()
-            .filter_map(|id| self.pbf_index.get_offset(&ElementType::Relation, *id))
-            .collect();
-        let result: Vec<Relation> = offsets
-            .into_iter()
-            .flat_map(|offset| {
-                let blob_data = self.pbf_reader.read_blob_by_offset(offset).unwrap();
-                let relations: Vec<Relation> = blob_data
-                    .relations
-                    .iter()
-                    .filter(|relation| relation_ids.contains(&relation.id))
-                    .map(|relation| relation.clone())
-                    .collect();
-                relations
-            })
+        Ok(to_owned_elements(self.find_refs(relation_ids)?))
+    }
+
+    /// Finds relations by their IDs without copying them out of their blobs.
+    pub fn find_relation_refs(
+        &mut self,
+        relation_ids: &[i64],
+    ) -> anyhow::Result<Vec<ElementRef<Relation>>> {
+        self.find_refs(relation_ids)
+    }
+
+    fn find_ref<E: BlobElement>(
+        &mut self,
+        element_id: i64,
+    ) -> anyhow::Result<Option<ElementRef<E>>> {
+        if self.miss_policy != IndexMissPolicy::Strict
+            && self.is_known_absent(&E::ELEMENT_TYPE, element_id)
+        {
+            return Ok(None);
+        }
+        let prefetch_blobs = self.observe_lookup(&E::ELEMENT_TYPE, element_id);
+        for file in &mut self.files {
+            if file.pbf_index.contains(&E::ELEMENT_TYPE, element_id) == Some(false) {
+                continue;
+            }
+            if let Some(offset) = file.pbf_index.get_offset(&E::ELEMENT_TYPE, element_id) {
+                for next_offset in
+                    file.pbf_index
+                        .get_next_offsets(&E::ELEMENT_TYPE, element_id, prefetch_blobs)
+                {
+                    file.pbf_reader.prefetch(next_offset);
+                }
+                let blob_data = file.pbf_reader.read_blob_by_offset(offset)?;
+                if let Some(element) = ElementRef::find(blob_data, element_id) {
+                    return Ok(Some(element));
+                }
+            }
+        }
+        Ok(self.find_misses(&[element_id])?.pop())
+    }
+
+    /// Looks up the elements which aren't in the blobs the index points to, according to the
+    /// miss policy.
+    fn find_misses<E: BlobElement>(
+        &mut self,
+        element_ids: &[i64],
+    ) -> anyhow::Result<Vec<ElementRef<E>>> {
+        if element_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let count = match self.miss_policy {
+            IndexMissPolicy::Absent => return Ok(Vec::new()),
+            IndexMissPolicy::Strict => bail!(
+                "{}/{} is not in the blob the index points to",
+                E::ELEMENT_TYPE,
+                element_ids[0]
+            ),
+            IndexMissPolicy::ScanNeighbors(count) => count,
+        };
+        let mut missing: HashSet<i64> = element_ids.iter().copied().collect();
+        let mut result = Vec::new();
+        for file in &mut self.files {
+            let mut offsets: Vec<u64> = Vec::new();
+            for id in element_ids {
+                for offset in file
+                    .pbf_index
+                    .get_neighbor_offsets(&E::ELEMENT_TYPE, *id, count)
+                {
+                    if !offsets.contains(&offset) {
+                        offsets.push(offset);
+                    }
+                }
+            }
+            for offset in offsets {
+                if missing.is_empty() {
+                    return Ok(result);
+                }
+                let blob_data = file.pbf_reader.read_blob_by_offset(offset)?;
+                let found = ElementRef::filter(blob_data, |element: &E| {
+                    missing.contains(&element.element_id())
+                });
+                for element in &found {
+                    missing.remove(&element.element_id());
+                }
+                result.extend(found);
+            }
+        }
+        Ok(result)
+    }
+
+    fn find_refs<E: BlobElement>(
+        &mut self,
+        element_ids: &[i64],
+    ) -> anyhow::Result<Vec<ElementRef<E>>> {
+        let strict = self.miss_policy == IndexMissPolicy::Strict;
+        let mut wanted: HashSet<i64> = element_ids
+            .iter()
+            .copied()
+            .filter(|id| strict || !self.is_known_absent(&E::ELEMENT_TYPE, *id))
             .collect();
+        let mut result = Vec::new();
+        for file in &mut self.files {
+            if wanted.is_empty() {
+                break;
+            }
+            let offsets: BTreeSet<u64> = wanted
+                .iter()
+                .filter(|id| file.pbf_index.contains(&E::ELEMENT_TYPE, **id) != Some(false))
+                .filter_map(|id| file.pbf_index.get_offset(&E::ELEMENT_TYPE, *id))
+                .collect();
+            // The blobs after the first one are decoded ahead while it is read
+            if self.prefetch_blobs > 0 {
+                for offset in offsets.iter().skip(1) {
+                    file.pbf_reader.prefetch(*offset);
+                }
+            }
+            let mut found = Vec::new();
+            for offset in offsets {
+                let blob_data = file.pbf_reader.read_blob_by_offset(offset)?;
+                found.extend(ElementRef::filter(blob_data, |element: &E| {
+                    wanted.contains(&element.element_id())
+                }));
+            }
+            // Elements found in one file aren't looked up in the next ones
+            for element in &found {
+                wanted.remove(&element.element_id());
+            }
+            result.extend(found);
+        }
+        let mut misses: Vec<i64> = wanted.into_iter().collect();
+        misses.sort_unstable();
+        result.extend(self.find_misses(&misses)?);
         Ok(result)
     }
 
+    /// Writes a copy of the PBF file with some elements replaced, inserted or deleted to
+    /// `output`, and the index of the copy next to it.
+    ///
+    /// Only the blobs containing edited elements are decoded and encoded again; all other blobs
+    /// are copied as they are, which makes small edits to big files cheap. An inserted element
+    /// is added to the blob the index points to for its ID, so the file stays sorted. Readers of
+    /// several files and files with locations on ways aren't supported.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::ElementType;
+    /// use pbf_craft::readers::{ElementEdit, IndexedReader};
+    ///
+    /// let mut indexed_reader = IndexedReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let edits = vec![ElementEdit::Delete(ElementType::Way, 1055523837)];
+    /// indexed_reader.apply_edits(edits, "resources/andorra-edited.osm.pbf").unwrap();
+    /// # std::fs::remove_file("resources/andorra-edited.osm.pbf").unwrap();
+    /// # std::fs::remove_file("resources/andorra-edited.osm.pif").unwrap();
+    /// ```
+    pub fn apply_edits(&mut self, edits: Vec<ElementEdit>, output: &str) -> anyhow::Result<()> {
+        let [file] = self.files.as_mut_slice() else {
+            bail!("Edits can only be applied to a single file");
+        };
+        if !output.ends_with(".pbf") {
+            bail!("It's not a .pbf file")
+        }
+
+        let mut id_sets = file.pbf_index.id_sets.clone();
+        let mut edits_by_offset: HashMap<u64, Vec<ElementEdit>> = HashMap::new();
+        for edit in edits {
+            let (element_type, id) = edit.element_meta();
+            if let Some(id_sets) = &mut id_sets {
+                match edit {
+                    ElementEdit::Replace(_) => id_sets.get_mut(&element_type).insert(id),
+                    ElementEdit::Delete(..) => id_sets.get_mut(&element_type).remove(id),
+                };
+            }
+            let offset = file
+                .pbf_index
+                .get_offset(&element_type, id)
+                .or_else(|| file.pbf_index.get_last_offset(&element_type))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The file has no blob of {}s to insert {}/{} into",
+                        element_type,
+                        element_type,
+                        id
+                    )
+                })?;
+            edits_by_offset.entry(offset).or_default().push(edit);
+        }
+
+        let entries_by_offset = file.pbf_index.entries_by_offset();
+        let mut output_index = PbfIndex::empty();
+        output_index.id_sets = id_sets;
+        let mut writer = PbfWriter::from_path(output, true)?;
+        let mut blob_reader = BlobReader::new(BufReader::new(File::open(&file.path)?));
+        loop {
+            let offset = blob_reader.offset;
+            let Some(raw_blob) = blob_reader.next_blob()? else {
+                break;
+            };
+            let Some(edits) = edits_by_offset.remove(&offset) else {
+                if raw_blob.blob_type() == "OSMHeader" {
+                    if let DecodedBlob::OsmHeader(header) = raw_blob.decode()? {
+                        let has_feature = |features: &[String], feature: &str| {
+                            features.iter().any(|f| f == feature)
+                        };
+                        if has_feature(header.get_optional_features(), "LocationsOnWays") {
+                            bail!("Editing files with locations on ways isn't supported");
+                        }
+                        writer.set_historical_information(has_feature(
+                            header.get_required_features(),
+                            "HistoricalInformation",
+                        ));
+                    }
+                }
+                let output_offset = writer.copy_blob(&raw_blob)?;
+                for (element_type, id) in entries_by_offset.get(&offset).into_iter().flatten() {
+                    output_index.insert(element_type, *id, output_offset);
+                }
+                if let Some(counts) = file.pbf_index.get_counts(offset) {
+                    output_index.insert_counts(BlobCounts {
+                        offset: output_offset,
+                        ..*counts
+                    });
+                }
+                continue;
+            };
+
+            // The edited elements may not fit into one block anymore
+            let blob_data = file.pbf_reader.read_blob_by_offset(offset)?;
+            let mut block_elements = Vec::new();
+            for element in edit_blob(&blob_data, edits) {
+                let blocks = writer.report().blocks.len();
+                block_elements.push(element.get_meta());
+                writer.write(element)?;
+                if writer.report().blocks.len() > blocks {
+                    index_block(&mut output_index, &mut block_elements, writer.report());
+                }
+            }
+            writer.flush_block()?;
+            if !block_elements.is_empty() {
+                index_block(&mut output_index, &mut block_elements, writer.report());
+            }
+        }
+        if let Some(offset) = edits_by_offset.keys().next() {
+            bail!(
+                "The index points to no blob at offset {}; it's outdated",
+                offset
+            );
+        }
+        writer.finish()?;
+
+        output_index.persist(
+            &get_index_path_from_pbf_path(output),
+            &file::checksum(output)?,
+        )?;
+        Ok(())
+    }
+
     /// Finds an element by its type and ID.
     pub fn find(
         &mut self,
@@ -415,25 +1286,100 @@ impl<T: PbfRandomRead> IndexedReader<T> {
     /// It is highly recommended to use `IndexedReader::from_path_with_cache` to create an `IndexedReader` instance
     /// when you need to read elements with dependencies frequently.
     ///
-    pub fn get_with_deps(
-        &mut self,
-        element_type: &ElementType,
-        element_id: i64,
-    ) -> anyhow::Result<Vec<Element>> {
-        match element_type {
+    pub fn get_with_deps(&mut self, element_id: ElementId) -> anyhow::Result<Vec<Element>> {
+        match element_id.element_type() {
             ElementType::Node => {
-                let node = self.find_node(element_id)?;
+                let node = self.find_node(element_id.id())?;
                 if node.is_none() {
                     return Ok(Vec::with_capacity(0));
                 }
                 let node = node.unwrap();
                 Ok(vec![Element::Node(node)])
             }
-            ElementType::Way => self.get_way_with_deps(element_id),
-            ElementType::Relation => self.get_relation_with_deps(element_id),
+            ElementType::Way => self.get_way_with_deps(element_id.id()),
+            ElementType::Relation => self.get_relation_with_deps(element_id.id()),
         }
     }
 
+    /// Finds several elements with their dependencies. The elements may be of different types.
+    ///
+    /// Unlike calling `get_with_deps` for each element, the dependencies shared by several
+    /// elements are read once, and the elements needed for a batch are looked up together, so
+    /// each blob is read once per batch. The result contains every element once, ordered by
+    /// type and then ID, like a PBF file.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::{ElementId, ElementType};
+    /// use pbf_craft::readers::IndexedReader;
+    ///
+    /// let mut indexed_reader = IndexedReader::from_path_with_cache("resources/andorra-latest.osm.pbf", 1000).unwrap();
+    /// let ids = [
+    ///     ElementId::new(ElementType::Way, 1055523837),
+    ///     ElementId::new(ElementType::Way, 1055523838),
+    /// ];
+    /// let elements = indexed_reader.get_many_with_deps(&ids).unwrap();
+    /// ```
+    pub fn get_many_with_deps(
+        &mut self,
+        element_ids: &[ElementId],
+    ) -> anyhow::Result<Vec<Element>> {
+        let mut node_ids: BTreeSet<i64> = BTreeSet::new();
+        let mut way_ids: BTreeSet<i64> = BTreeSet::new();
+        let mut relation_ids: BTreeSet<i64> = BTreeSet::new();
+        for element_id in element_ids {
+            match element_id.element_type() {
+                ElementType::Node => node_ids.insert(element_id.id()),
+                ElementType::Way => way_ids.insert(element_id.id()),
+                ElementType::Relation => relation_ids.insert(element_id.id()),
+            };
+        }
+
+        // The member relations are read level by level; relations seen before are skipped, which
+        // also ends reference cycles
+        let mut relations = Vec::new();
+        let mut pending: Vec<i64> = relation_ids.iter().copied().collect();
+        while !pending.is_empty() {
+            let found = self.find_relations(&pending)?;
+            pending.clear();
+            for relation in &found {
+                for member in &relation.members {
+                    match member.member_type {
+                        ElementType::Node => {
+                            node_ids.insert(member.member_id);
+                        }
+                        ElementType::Way => {
+                            way_ids.insert(member.member_id);
+                        }
+                        ElementType::Relation => {
+                            if relation_ids.insert(member.member_id) {
+                                pending.push(member.member_id);
+                            }
+                        }
+                    }
+                }
+            }
+            relations.extend(found);
+        }
+
+        let way_ids: Vec<i64> = way_ids.into_iter().collect();
+        let mut ways = self.find_ways(&way_ids)?;
+        for way in &ways {
+            node_ids.extend(way.way_nodes.iter().map(|way_node| way_node.id));
+        }
+        let node_ids: Vec<i64> = node_ids.into_iter().collect();
+        let mut nodes = self.find_nodes(&node_ids)?;
+
+        nodes.sort_by_key(|node| node.id);
+        ways.sort_by_key(|way| way.id);
+        relations.sort_by_key(|relation| relation.id);
+        let nodes = nodes.into_iter().map(Element::Node);
+        let ways = ways.into_iter().map(Element::Way);
+        let relations = relations.into_iter().map(Element::Relation);
+        Ok(nodes.chain(ways).chain(relations).collect())
+    }
+
     fn get_way_with_deps(&mut self, way_id: i64) -> anyhow::Result<Vec<Element>> {
         let way = self.find_way(way_id)?;
         if way.is_none() {
@@ -515,15 +1461,85 @@ impl<T: PbfRandomRead> IndexedReader<T> {
     }
 }
 
+/// Applies edits to the elements of a blob, keeping the elements of each type sorted by ID.
+fn edit_blob(blob_data: &BlobData, edits: Vec<ElementEdit>) -> Vec<Element> {
+    let mut nodes = blob_data.nodes.clone();
+    let mut ways = blob_data.ways.clone();
+    let mut relations = blob_data.relations.clone();
+    for edit in edits {
+        match edit {
+            ElementEdit::Replace(Element::Node(node)) => replace_element(&mut nodes, node),
+            ElementEdit::Replace(Element::Way(way)) => replace_element(&mut ways, way),
+            ElementEdit::Replace(Element::Relation(relation)) => {
+                replace_element(&mut relations, relation)
+            }
+            ElementEdit::Delete(ElementType::Node, id) => nodes.retain(|node| node.id != id),
+            ElementEdit::Delete(ElementType::Way, id) => ways.retain(|way| way.id != id),
+            ElementEdit::Delete(ElementType::Relation, id) => {
+                relations.retain(|relation| relation.id != id)
+            }
+        }
+    }
+    let nodes = nodes.into_iter().map(Element::Node);
+    let ways = ways.into_iter().map(Element::Way);
+    let relations = relations.into_iter().map(Element::Relation);
+    nodes.chain(ways).chain(relations).collect()
+}
+
+fn replace_element<E: BlobElement>(elements: &mut Vec<E>, element: E) {
+    let id = element.element_id();
+    match elements.iter().position(|e| e.element_id() == id) {
+        Some(index) => elements[index] = element,
+        None => {
+            let index = elements.partition_point(|e| e.element_id() < id);
+            elements.insert(index, element);
+        }
+    }
+}
+
+/// Indexes the elements of the block last written, i.e. the last element of each type.
+fn index_block(
+    pbf_index: &mut PbfIndex,
+    block_elements: &mut Vec<(ElementType, i64)>,
+    report: &WriteReport,
+) {
+    let offset = report.blocks.last().map_or(0, |block| block.offset);
+    if let Some(block) = report.blocks.last() {
+        pbf_index.insert_counts(BlobCounts {
+            offset: block.offset,
+            nodes: block.nodes,
+            ways: block.ways,
+            relations: block.relations,
+        });
+    }
+    for element_type in [ElementType::Node, ElementType::Way, ElementType::Relation] {
+        if let Some((_, id)) = block_elements
+            .iter()
+            .rev()
+            .find(|(t, _)| *t == element_type)
+        {
+            pbf_index.insert(&element_type, *id, offset);
+        }
+    }
+    block_elements.clear();
+}
+
+fn to_owned_elements<E: BlobElement + Clone>(refs: Vec<ElementRef<E>>) -> Vec<E> {
+    refs.iter().map(ElementRef::to_owned_element).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::{Tag, WayNode};
+    use crate::writers::PbfWriter;
+    use std::sync::Arc;
     use test::{black_box, Bencher};
 
     #[test]
     fn test_index_from_pbf_file() {
         let pbf_file = "./resources/andorra-latest.osm.pbf";
-        let index_file = PbfIndex::load_from_pbf_file(pbf_file).unwrap();
+        let index_file = PbfIndex::build(pbf_file, None).unwrap();
 
         let r1 = index_file.get_offset(&ElementType::Node, 52263877);
         let r2 = index_file.get_offset(&ElementType::Node, 52263878);
@@ -543,6 +1559,100 @@ mod tests {
         assert_eq!(r2, Some(49494));
     }
 
+    #[test]
+    fn test_index_summary() {
+        let pbf_file = "./resources/andorra-latest.osm.pbf";
+        let summary = PbfIndex::build(pbf_file, None).unwrap().summary();
+        let mut counts = (0, 0, 0);
+        PbfReader::from_path(pbf_file)
+            .unwrap()
+            .read(|_, element| match element {
+                Some(Element::Node(_)) => counts.0 += 1,
+                Some(Element::Way(_)) => counts.1 += 1,
+                Some(Element::Relation(_)) => counts.2 += 1,
+                None => {}
+            })
+            .unwrap();
+        assert_eq!(
+            (summary.nodes(), summary.ways(), summary.relations()),
+            counts
+        );
+        assert_eq!(summary.blobs[0].offset, 171);
+        assert!(summary.blobs.windows(2).all(|w| w[0].offset < w[1].offset));
+
+        assert_eq!(summary.progress(0), 0.0);
+        assert_eq!(summary.progress(u64::MAX), 1.0);
+        let second = summary.blobs[1].offset;
+        assert_eq!(
+            summary.progress(second),
+            summary.blobs[0].elements() as f64 / summary.elements() as f64
+        );
+
+        // Index files without blob counts are rebuilt
+        let index_file = "./resources/test_index_summary.pif";
+        let mut pbf_index = PbfIndex::build(pbf_file, None).unwrap();
+        pbf_index.blob_counts = None;
+        pbf_index
+            .persist(index_file, "0".repeat(32).as_str())
+            .unwrap();
+        let (loaded_index, _) = PbfIndex::load_from_file(index_file).unwrap();
+        assert!(loaded_index.blob_counts.is_none());
+        pbf_index.blob_counts = Some(BTreeMap::new());
+        pbf_index
+            .persist(index_file, "0".repeat(32).as_str())
+            .unwrap();
+        let (loaded_index, _) = PbfIndex::load_from_file(index_file).unwrap();
+        assert_eq!(loaded_index.summary(), IndexSummary::default());
+        fs::remove_file(index_file).unwrap();
+    }
+
+    #[test]
+    fn test_index_checkpoint() {
+        let pbf_file = "./resources/andorra-latest.osm.pbf";
+        let checkpoint = IndexCheckpoint {
+            path: "./resources/test_index_checkpoint.pif.partial".to_string(),
+            checksum: file::checksum(pbf_file).unwrap(),
+            interval: 2,
+        };
+        let full_index = PbfIndex::build(pbf_file, None).unwrap();
+
+        // Simulates an interrupted run that indexed the first three blobs
+        let mut reader = PbfReader::from_path(pbf_file).unwrap();
+        let mut partial_index = PbfIndex::empty();
+        for _ in 0..3 {
+            let blob_data = reader.read_next_blob().unwrap().unwrap();
+            if let Some(last) = blob_data.nodes.last() {
+                partial_index.node_index.insert(last.id, blob_data.offset);
+            }
+            if !blob_data.nodes.is_empty() {
+                partial_index.insert_counts(BlobCounts {
+                    offset: blob_data.offset,
+                    nodes: blob_data.nodes.len() as u64,
+                    ..Default::default()
+                });
+            }
+        }
+        checkpoint.save(&partial_index, reader.position()).unwrap();
+        let (loaded_index, offset) = checkpoint.load().unwrap().unwrap();
+        assert_eq!(offset, reader.position());
+        assert_eq!(loaded_index.node_index, partial_index.node_index);
+
+        let resumed_index = PbfIndex::build(pbf_file, Some(&checkpoint)).unwrap();
+        assert_eq!(resumed_index.node_index, full_index.node_index);
+        assert_eq!(resumed_index.way_index, full_index.way_index);
+        assert_eq!(resumed_index.relation_index, full_index.relation_index);
+        assert_eq!(resumed_index.summary(), full_index.summary());
+
+        // A checkpoint of another file is ignored
+        let other_checkpoint = IndexCheckpoint {
+            checksum: "0".repeat(32),
+            ..checkpoint
+        };
+        assert!(other_checkpoint.load().unwrap().is_none());
+        other_checkpoint.remove().unwrap();
+        assert!(!file::exists(&other_checkpoint.path));
+    }
+
     #[test]
     fn test_index_reader_read() {
         let pbf_file = "./resources/andorra-latest.osm.pbf";
@@ -564,6 +1674,361 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_index_miss_policy() {
+        // The node 9000 is written at the end of the first blob, so the index points to it
+        // for the nodes 8000 to 8999 of the second blob
+        let pbf_file = "./resources/test_index_miss_policy.osm.pbf";
+        let mut writer = PbfWriter::from_path(pbf_file, true).unwrap();
+        let ids = (1..8000).chain([9000]).chain(8000..9000).chain(9001..16000);
+        for id in ids {
+            writer
+                .write(Element::Node(Node {
+                    id,
+                    ..Default::default()
+                }))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+
+        let mut indexed_reader = IndexedReader::from_path(pbf_file).unwrap();
+        assert_eq!(indexed_reader.find_node(8500).unwrap().unwrap().id, 8500);
+        assert_eq!(
+            indexed_reader
+                .find_nodes(&[8500, 8501, 20000])
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(indexed_reader.find_node(20000).unwrap().is_none());
+
+        indexed_reader.set_miss_policy(IndexMissPolicy::Absent);
+        assert!(indexed_reader.find_node(8500).unwrap().is_none());
+        assert_eq!(indexed_reader.find_node(9000).unwrap().unwrap().id, 9000);
+
+        indexed_reader.set_miss_policy(IndexMissPolicy::Strict);
+        assert!(indexed_reader.find_node(8500).is_err());
+        assert!(indexed_reader.find_nodes(&[1, 8500]).is_err());
+
+        fs::remove_file(pbf_file).unwrap();
+        fs::remove_file(get_index_path_from_pbf_path(pbf_file)).unwrap();
+    }
+
+    #[test]
+    fn test_from_paths() {
+        let node = |id: i64| {
+            Element::Node(Node {
+                id,
+                visible: true,
+                ..Default::default()
+            })
+        };
+        let way = |id: i64, node_ids: &[i64]| {
+            Element::Way(Way {
+                id,
+                visible: true,
+                way_nodes: node_ids
+                    .iter()
+                    .map(|id| WayNode {
+                        id: *id,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+        };
+        // Two tiles sharing the node 10
+        let tiles = [
+            (
+                "./resources/test_from_paths_1.osm.pbf",
+                (1..=10)
+                    .map(node)
+                    .chain([way(1, &[9, 10, 11])])
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "./resources/test_from_paths_2.osm.pbf",
+                (10..=20).map(node).chain([way(2, &[10, 20])]).collect(),
+            ),
+        ];
+        for (path, elements) in &tiles {
+            let mut writer = PbfWriter::from_path(path, true).unwrap();
+            for element in elements {
+                writer.write(element.clone()).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+
+        let paths: Vec<&str> = tiles.iter().map(|(path, _)| *path).collect();
+        let mut indexed_reader = IndexedReader::from_paths(&paths).unwrap();
+        assert_eq!(indexed_reader.find_node(15).unwrap().unwrap().id, 15);
+        assert_eq!(indexed_reader.find_way(2).unwrap().unwrap().id, 2);
+        assert!(indexed_reader.find_node(21).unwrap().is_none());
+        let nodes = indexed_reader.find_nodes(&[5, 10, 15]).unwrap();
+        assert_eq!(nodes.len(), 3);
+        let elements = indexed_reader
+            .get_with_deps(ElementId::new(ElementType::Way, 1))
+            .unwrap();
+        assert_eq!(elements.len(), 4);
+        assert!(IndexedReader::from_paths(&[]).is_err());
+
+        for path in paths {
+            fs::remove_file(path).unwrap();
+            fs::remove_file(get_index_path_from_pbf_path(path)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let pbf_file = "./resources/test_apply_edits.osm.pbf";
+        let output = "./resources/test_apply_edits_output.osm.pbf";
+        let mut writer = PbfWriter::from_path(pbf_file, true).unwrap();
+        for id in 1..=30000 {
+            writer
+                .write(Element::Node(Node {
+                    id,
+                    visible: true,
+                    ..Default::default()
+                }))
+                .unwrap();
+        }
+        for id in 1..=3 {
+            writer
+                .write(Element::Way(Way {
+                    id,
+                    visible: true,
+                    ..Default::default()
+                }))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+
+        let tagged = Node {
+            id: 5,
+            visible: true,
+            tags: vec![Tag {
+                key: "name".to_string(),
+                value: "edited".to_string(),
+            }],
+            ..Default::default()
+        };
+        let inserted = Node {
+            id: 40000,
+            visible: true,
+            ..Default::default()
+        };
+        let edits = vec![
+            ElementEdit::Replace(Element::Node(tagged)),
+            ElementEdit::Delete(ElementType::Node, 10000),
+            ElementEdit::Replace(Element::Node(inserted)),
+            ElementEdit::Delete(ElementType::Way, 1),
+        ];
+        let mut indexed_reader = IndexedReader::from_path(pbf_file).unwrap();
+        indexed_reader.apply_edits(edits, output).unwrap();
+
+        // The index written with the output is the same as one built from scratch
+        let (output_index, _) =
+            PbfIndex::load_from_file(&get_index_path_from_pbf_path(output)).unwrap();
+        let built_index = PbfIndex::build(output, None).unwrap();
+        assert_eq!(output_index.node_index, built_index.node_index);
+        assert_eq!(output_index.way_index, built_index.way_index);
+        assert_eq!(output_index.summary(), built_index.summary());
+        assert_eq!(output_index.summary().nodes(), 30000);
+
+        let mut edited_reader = IndexedReader::from_path(output).unwrap();
+        edited_reader.set_miss_policy(IndexMissPolicy::Strict);
+        assert_eq!(
+            edited_reader.find_node(5).unwrap().unwrap().tags[0].value,
+            "edited"
+        );
+        assert_eq!(edited_reader.find_node(20000).unwrap().unwrap().id, 20000);
+        assert_eq!(edited_reader.find_node(40000).unwrap().unwrap().id, 40000);
+        assert_eq!(edited_reader.find_way(2).unwrap().unwrap().id, 2);
+        edited_reader.set_miss_policy(IndexMissPolicy::Absent);
+        assert!(edited_reader.find_node(10000).unwrap().is_none());
+        assert!(edited_reader.find_way(1).unwrap().is_none());
+
+        let mut count = 0;
+        PbfReader::from_path(output)
+            .unwrap()
+            .read(|_, element| count += element.is_some() as usize)
+            .unwrap();
+        assert_eq!(count, 30000 + 2);
+
+        for path in [pbf_file, output] {
+            fs::remove_file(path).unwrap();
+            fs::remove_file(get_index_path_from_pbf_path(path)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_id_sets() {
+        let pbf_file = "./resources/test_id_sets.osm.pbf";
+        let mut writer = PbfWriter::from_path(pbf_file, true).unwrap();
+        for id in (1..20000).step_by(2) {
+            writer
+                .write(Element::Node(Node {
+                    id,
+                    visible: true,
+                    ..Default::default()
+                }))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+
+        let mut indexed_reader = IndexedReader::from_path(pbf_file).unwrap();
+        indexed_reader.set_miss_policy(IndexMissPolicy::Strict);
+        assert!(indexed_reader.contains(&ElementType::Node, 3).unwrap());
+        assert!(!indexed_reader.contains(&ElementType::Node, 4).unwrap());
+        indexed_reader.build_id_sets().unwrap();
+        assert!(indexed_reader.contains(&ElementType::Node, 19999).unwrap());
+        assert!(!indexed_reader.contains(&ElementType::Way, 1).unwrap());
+
+        // The ID sets are loaded with the index
+        let mut indexed_reader = IndexedReader::from_path(pbf_file).unwrap();
+        assert!(indexed_reader.files[0].pbf_index.id_sets.is_some());
+        assert!(!indexed_reader.contains(&ElementType::Node, 20001).unwrap());
+        assert!(indexed_reader.find_node(4).unwrap().is_none());
+        assert_eq!(indexed_reader.find_nodes(&[3, 4, 5]).unwrap().len(), 2);
+        indexed_reader.set_miss_policy(IndexMissPolicy::Strict);
+        assert!(indexed_reader.find_node(4).is_err());
+
+        fs::remove_file(pbf_file).unwrap();
+        fs::remove_file(get_index_path_from_pbf_path(pbf_file)).unwrap();
+    }
+
+    #[test]
+    fn test_get_many_with_deps() {
+        let pbf_file = "./resources/andorra-latest.osm.pbf";
+        let mut way_ids = Vec::new();
+        let mut relation_ids = Vec::new();
+        PbfReader::from_path(pbf_file)
+            .unwrap()
+            .read(|_, element| match element {
+                Some(Element::Way(way)) if way_ids.len() < 50 => way_ids.push(way.id),
+                Some(Element::Relation(relation)) if relation_ids.len() < 5 => {
+                    relation_ids.push(relation.id)
+                }
+                _ => {}
+            })
+            .unwrap();
+
+        let mut indexed_reader = IndexedReader::from_path_with_cache(pbf_file, 100).unwrap();
+        let ids: Vec<ElementId> = way_ids
+            .into_iter()
+            .map(|id| ElementId::new(ElementType::Way, id))
+            .chain(
+                relation_ids
+                    .into_iter()
+                    .map(|id| ElementId::new(ElementType::Relation, id)),
+            )
+            .collect();
+        let mut expected = BTreeSet::new();
+        for id in &ids {
+            let elements = indexed_reader.get_with_deps(*id).unwrap();
+            expected.extend(elements.iter().map(|element| element.element_id().unwrap()));
+        }
+        let elements = indexed_reader.get_many_with_deps(&ids).unwrap();
+        let found: Vec<ElementId> = elements
+            .iter()
+            .map(|element| element.element_id().unwrap())
+            .collect();
+        assert_eq!(found, expected.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_find_refs() {
+        let pbf_file = "./resources/andorra-latest.osm.pbf";
+        let mut indexed_reader = IndexedReader::from_path_with_cache(pbf_file, 10).unwrap();
+
+        let node_ref = indexed_reader.find_node_ref(4254529698).unwrap().unwrap();
+        assert_eq!(node_ref.id, 4254529698);
+        assert_eq!(
+            node_ref.to_owned_element(),
+            indexed_reader.find_node(4254529698).unwrap().unwrap()
+        );
+
+        // Lookups in a cached blob share it
+        let node_refs = indexed_reader.find_node_refs(&[4254529698, -1]).unwrap();
+        assert_eq!(node_refs.len(), 1);
+        assert!(Arc::ptr_eq(node_refs[0].blob(), node_ref.blob()));
+
+        let way_ref = indexed_reader.find_way_ref(1055523837).unwrap().unwrap();
+        assert_eq!(way_ref.id, 1055523837);
+        assert!(indexed_reader.find_way_ref(-1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_resolve_way_geometries() {
+        let pbf_file = "./resources/andorra-latest.osm.pbf";
+        let mut indexed_reader = IndexedReader::from_path_with_cache(pbf_file, 100).unwrap();
+        let mut way_ids: Vec<i64> = Vec::new();
+        let mut pbf_reader = PbfReader::from_path(pbf_file).unwrap();
+        while let Some(blob) = pbf_reader.read_next_blob().unwrap() {
+            way_ids.extend(blob.ways.iter().step_by(500).map(|way| way.id));
+        }
+        way_ids.reverse();
+        way_ids.insert(1, -1);
+        way_ids.push(way_ids[0]);
+
+        let ways = indexed_reader.resolve_way_geometries(&way_ids).unwrap();
+        let expected: Vec<i64> = way_ids[..way_ids.len() - 1]
+            .iter()
+            .copied()
+            .filter(|way_id| *way_id != -1)
+            .collect();
+        assert_eq!(ways.iter().map(|way| way.id).collect::<Vec<_>>(), expected);
+        for way in &ways {
+            let node_ids: Vec<i64> = way.way_nodes.iter().map(|way_node| way_node.id).collect();
+            let nodes = indexed_reader.find_nodes(&node_ids).unwrap();
+            for way_node in &way.way_nodes {
+                let node = nodes.iter().find(|node| node.id == way_node.id).unwrap();
+                assert_eq!(way_node.latitude, Some(node.latitude));
+                assert_eq!(way_node.longitude, Some(node.longitude));
+            }
+        }
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let pbf_file = "./resources/andorra-latest.osm.pbf";
+        let mut way_ids = Vec::new();
+        let mut pbf_reader = PbfReader::from_path(pbf_file).unwrap();
+        while let Some(blob) = pbf_reader.read_next_blob().unwrap() {
+            way_ids.extend(blob.ways.iter().step_by(1000).map(|way| way.id));
+        }
+        assert!(way_ids.len() > ASCENDING_LOOKUPS_TO_PREFETCH);
+
+        let mut indexed_reader = IndexedReader::from_path_with_cache(pbf_file, 100).unwrap();
+        for way_id in &way_ids {
+            assert_eq!(
+                indexed_reader.find_way(*way_id).unwrap().unwrap().id,
+                *way_id
+            );
+        }
+        assert!(indexed_reader.files[0].pbf_reader.prefetched() > 0);
+
+        // Lookups which aren't ascending don't prefetch
+        let mut indexed_reader = IndexedReader::from_path_with_cache(pbf_file, 100).unwrap();
+        for way_id in way_ids.iter().rev() {
+            assert!(indexed_reader.find_way(*way_id).unwrap().is_some());
+        }
+        assert_eq!(indexed_reader.files[0].pbf_reader.prefetched(), 0);
+
+        indexed_reader.set_prefetch_blobs(0);
+        for way_id in &way_ids {
+            assert!(indexed_reader.find_way(*way_id).unwrap().is_some());
+        }
+        assert_eq!(
+            indexed_reader.find_ways(&way_ids).unwrap().len(),
+            way_ids.len()
+        );
+        assert_eq!(indexed_reader.files[0].pbf_reader.prefetched(), 0);
+    }
+
     #[bench]
     fn bench_find_without_cache(b: &mut Bencher) {
         let pbf_file = "./resources/andorra-latest.osm.pbf";
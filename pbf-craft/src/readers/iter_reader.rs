@@ -1,9 +1,18 @@
+#[cfg(feature = "fs")]
 use std::fs::File;
-use std::io::{BufReader, Read};
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::io::{Cursor, Read};
+use std::ops::Range;
+#[cfg(feature = "fs")]
 use std::path::Path;
+use std::sync::Arc;
 
 use super::raw_reader::PbfReader;
-use super::traits::BlobData;
+use super::traits::{BlobData, ElementSource, Provenance};
+use crate::codecs::block_decorators::DecodeErrorPolicy;
+use crate::codecs::decode_options::DecodeOptions;
+use crate::filters::MetadataFilter;
 use crate::models::{Element, ElementType};
 
 /// A reader that provides an iterable interface for reading PBF data.
@@ -16,6 +25,12 @@ use crate::models::{Element, ElementType};
 ///
 /// * `R` - A type that implements the `Read` and `Send` traits, providing methods for reading PBF data.
 ///
+/// # Panics
+///
+/// Iterating panics if a blob can't be decoded, or if an element can't be decoded and the
+/// `DecodeErrorPolicy` is `Fail`. Read through `ElementSource::next_element` to get an error
+/// instead.
+///
 /// # Example
 ///
 /// ```rust
@@ -29,30 +44,295 @@ use crate::models::{Element, ElementType};
 /// ```
 pub struct IterableReader<R: Read + Send> {
     pbf_reader: PbfReader<R>,
+    started: bool,
     current_blob: Option<BlobData>,
     current_element_type: ElementType,
     current_element_index: usize,
+    metadata_filter: Option<MetadataFilter>,
+    source_name: Arc<str>,
+    /// The end offset of the current blob.
+    current_blob_end: u64,
+    /// The number of elements read, including those filtered out.
+    elements_read: u64,
 }
 
 impl<R: Read + Send> IterableReader<R> {
     /// Creates a new `IterableReader` from a raw pbf reader.
-    pub fn new(mut pbf_reader: PbfReader<R>) -> Self {
+    pub fn new(pbf_reader: PbfReader<R>) -> Self {
         Self {
-            current_blob: pbf_reader.read_next_blob(),
+            started: false,
+            current_blob: None,
             current_element_type: ElementType::Node,
             current_element_index: 0,
+            metadata_filter: None,
+            source_name: Arc::from(""),
+            current_blob_end: 0,
+            elements_read: 0,
             pbf_reader,
         }
     }
 
-    fn next_element(&mut self) -> Option<Element> {
+    /// Sets the name of the source reported by `ElementSource::provenance`. Readers created by
+    /// `from_path` use the path.
+    pub fn set_source_name(&mut self, source_name: &str) {
+        self.source_name = Arc::from(source_name);
+    }
+
+    /// Turns the reader into an iterator over the elements paired with the offset of the blob
+    /// containing them.
+    ///
+    /// The offset can be recorded by external indexes and later be passed to
+    /// `PbfRandomRead::read_blob_by_offset` to read the element again.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::{IterableReader, PbfRandomRead, PbfReader};
+    ///
+    /// let path = "resources/andorra-latest.osm.pbf";
+    /// let reader = IterableReader::from_path(path).unwrap();
+    /// let (element, offset) = reader.with_offsets().last().unwrap();
+    ///
+    /// let mut pbf_reader = PbfReader::from_path(path).unwrap();
+    /// let blob = pbf_reader.read_blob_by_offset(offset).unwrap();
+    /// let (_, id) = element.get_meta();
+    /// assert!(blob.relations.iter().any(|relation| relation.id == id));
+    /// ```
+    pub fn with_offsets(mut self) -> impl Iterator<Item = (Element, u64)> {
+        std::iter::from_fn(move || {
+            let element = self.next()?;
+            // The blob an element is taken from stays current until it is exhausted
+            let offset = self.current_blob.as_ref()?.offset;
+            Some((element, offset))
+        })
+    }
+
+    /// Turns the reader into an iterator over batches of `size` elements, to amortize per-call
+    /// costs like database inserts. The last batch may be smaller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is 0.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::IterableReader;
+    ///
+    /// let reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// for batch in reader.chunks(1000) {
+    ///     assert!(batch.len() <= 1000);
+    ///     // Insert the batch
+    /// }
+    /// ```
+    pub fn chunks(mut self, size: usize) -> impl Iterator<Item = Vec<Element>> {
+        assert!(size > 0, "The chunk size must be greater than 0");
+        std::iter::from_fn(move || {
+            let chunk: Vec<Element> = self.by_ref().take(size).collect();
+            if chunk.is_empty() {
+                None
+            } else {
+                Some(chunk)
+            }
+        })
+    }
+
+    /// Turns the reader into an iterator over the elements of each blob, which avoids cloning
+    /// the elements one by one. Blobs without elements, or without elements matching the
+    /// metadata filter, are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::IterableReader;
+    ///
+    /// let reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// for batch in reader.blob_chunks() {
+    ///     assert!(!batch.is_empty());
+    /// }
+    /// ```
+    pub fn blob_chunks(mut self) -> impl Iterator<Item = Vec<Element>> {
+        std::iter::from_fn(move || loop {
+            self.start().unwrap_or_else(|err| panic_on_error(err));
+            let blob = self.current_blob.take()?;
+            let skipped = self.current_element_index;
+            let mut chunk: Vec<Element> = match self.current_element_type {
+                ElementType::Node => {
+                    let nodes = blob.nodes.into_iter().skip(skipped).map(Element::Node);
+                    let ways = blob.ways.into_iter().map(Element::Way);
+                    let relations = blob.relations.into_iter().map(Element::Relation);
+                    nodes.chain(ways).chain(relations).collect()
+                }
+                ElementType::Way => {
+                    let ways = blob.ways.into_iter().skip(skipped).map(Element::Way);
+                    let relations = blob.relations.into_iter().map(Element::Relation);
+                    ways.chain(relations).collect()
+                }
+                ElementType::Relation => blob
+                    .relations
+                    .into_iter()
+                    .skip(skipped)
+                    .map(Element::Relation)
+                    .collect(),
+            };
+            self.read_next_blob()
+                .unwrap_or_else(|err| panic_on_error(err));
+            chunk.retain(|element| !self.is_filtered_out(element));
+            if !chunk.is_empty() {
+                return Some(chunk);
+            }
+        })
+    }
+
+    /// Fast-forwards past the elements of the types before the given type.
+    ///
+    /// Blobs which only contain elements of earlier types are skipped without decoding their
+    /// elements, so e.g. iterating over all relations doesn't decode every node first. It
+    /// expects the elements to be sorted by type, as elements of earlier types coming after the
+    /// first element of the given type are not skipped. If the reader is already past the given
+    /// type, nothing happens.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::{Element, ElementType};
+    /// use pbf_craft::readers::IterableReader;
+    ///
+    /// let mut reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// reader.skip_to(ElementType::Relation).unwrap();
+    /// for element in reader {
+    ///     assert!(matches!(element, Element::Relation(_)));
+    /// }
+    /// ```
+    pub fn skip_to(&mut self, element_type: ElementType) -> anyhow::Result<()> {
+        self.start()?;
+        if self.current_element_type >= element_type {
+            return Ok(());
+        }
+        if let Some(blob) = &self.current_blob {
+            let has_remaining = match element_type {
+                ElementType::Node => true,
+                ElementType::Way => !blob.ways.is_empty() || !blob.relations.is_empty(),
+                ElementType::Relation => !blob.relations.is_empty(),
+            };
+            if !has_remaining {
+                self.current_blob = self.pbf_reader.read_next_blob_from(&element_type)?;
+                self.current_blob_end = self.pbf_reader.position();
+            }
+        }
+        self.current_element_type = element_type;
+        self.current_element_index = 0;
+        Ok(())
+    }
+
+    /// Sets what to do with elements which can't be decoded, failing by default like
+    /// `PbfReader`. Set it before reading the first element, as blobs already read aren't
+    /// decoded again.
+    pub fn set_decode_error_policy(&mut self, policy: DecodeErrorPolicy) {
+        self.pbf_reader.set_decode_error_policy(policy);
+    }
+
+    /// Sets the parts of the elements to leave out when decoding. Set it before reading the
+    /// first element. A metadata filter doesn't match elements read without their metadata.
+    pub fn set_decode_options(&mut self, options: DecodeOptions) {
+        self.pbf_reader.set_decode_options(options);
+    }
+
+    /// Sets whether to recover from a corrupt stream by skipping to the next readable blob, as
+    /// with `PbfReader::set_resync`.
+    pub fn set_resync(&mut self, resync: bool) {
+        self.pbf_reader.set_resync(resync);
+    }
+
+    /// Returns the byte ranges skipped so far because of a corrupt stream.
+    pub fn skipped_ranges(&self) -> &[Range<u64>] {
+        self.pbf_reader.skipped_ranges()
+    }
+
+    /// Sets whether truncated data is read up to its last complete blob instead of failing, as
+    /// with `PbfReader::set_allow_truncation`.
+    pub fn set_allow_truncation(&mut self, allow_truncation: bool) {
+        self.pbf_reader.set_allow_truncation(allow_truncation);
+    }
+
+    /// Returns the number of valid bytes if the data was found truncated while truncation is
+    /// allowed.
+    pub fn truncation(&self) -> Option<u64> {
+        self.pbf_reader.truncation()
+    }
+
+    /// Sets a filter on the metadata of the elements, so that only the elements matching it are
+    /// read.
+    pub fn set_metadata_filter(&mut self, filter: MetadataFilter) {
+        self.metadata_filter = Some(filter);
+    }
+
+    fn is_filtered_out(&self, element: &Element) -> bool {
+        self.metadata_filter
+            .as_ref()
+            .is_some_and(|filter| !filter.matches(element))
+    }
+
+    /// Sets whether blobs exceeding the sizes allowed by the specification are rejected. See
+    /// `PbfReader::set_enforce_size_limits`.
+    pub fn set_enforce_size_limits(&mut self, enforce_size_limits: bool) {
+        self.pbf_reader.set_enforce_size_limits(enforce_size_limits);
+    }
+
+    /// Registers a handler for the blobs of a non-standard type. See
+    /// `PbfReader::register_blob_handler`.
+    pub fn register_blob_handler<F>(&mut self, blob_type: &str, handler: F)
+    where
+        F: FnMut(&[u8]) -> anyhow::Result<()> + Send + 'static,
+    {
+        self.pbf_reader.register_blob_handler(blob_type, handler);
+    }
+
+    /// Reads the first blob if no blob has been read yet.
+    fn start(&mut self) -> anyhow::Result<()> {
+        if !self.started {
+            self.started = true;
+            self.read_next_blob()?;
+        }
+        Ok(())
+    }
+
+    fn read_next_blob(&mut self) -> anyhow::Result<()> {
+        self.current_blob = self.pbf_reader.read_next_blob()?;
+        self.current_blob_end = self.pbf_reader.position();
+        self.current_element_type = ElementType::Node;
+        self.current_element_index = 0;
+        Ok(())
+    }
+
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        loop {
+            let Some(element) = self.next_unfiltered_element()? else {
+                return Ok(None);
+            };
+            if !self.is_filtered_out(&element) {
+                return Ok(Some(element));
+            }
+        }
+    }
+
+    fn next_unfiltered_element(&mut self) -> anyhow::Result<Option<Element>> {
+        let element = self.read_element()?;
+        if element.is_some() {
+            self.elements_read += 1;
+        }
+        Ok(element)
+    }
+
+    fn read_element(&mut self) -> anyhow::Result<Option<Element>> {
+        self.start()?;
         loop {
             if let Some(blob) = &self.current_blob {
                 if ElementType::Node == self.current_element_type {
                     if self.current_element_index < blob.nodes.len() {
                         let node = blob.nodes.get(self.current_element_index).unwrap();
                         self.current_element_index += 1;
-                        return Some(Element::Node(node.clone()));
+                        return Ok(Some(Element::Node(node.clone())));
                     } else {
                         self.current_element_type = ElementType::Way;
                         self.current_element_index = 0;
@@ -62,7 +342,7 @@ impl<R: Read + Send> IterableReader<R> {
                     if self.current_element_index < blob.ways.len() {
                         let way = blob.ways.get(self.current_element_index).unwrap();
                         self.current_element_index += 1;
-                        return Some(Element::Way(way.clone()));
+                        return Ok(Some(Element::Way(way.clone())));
                     } else {
                         self.current_element_type = ElementType::Relation;
                         self.current_element_index = 0;
@@ -72,15 +352,13 @@ impl<R: Read + Send> IterableReader<R> {
                     if self.current_element_index < blob.relations.len() {
                         let relation = blob.relations.get(self.current_element_index).unwrap();
                         self.current_element_index += 1;
-                        return Some(Element::Relation(relation.clone()));
+                        return Ok(Some(Element::Relation(relation.clone())));
                     } else {
-                        self.current_blob = self.pbf_reader.read_next_blob();
-                        self.current_element_type = ElementType::Node;
-                        self.current_element_index = 0;
+                        self.read_next_blob()?;
                     }
                 }
             } else {
-                return None;
+                return Ok(None);
             }
         }
     }
@@ -91,13 +369,215 @@ impl<R: Read + Send> Iterator for IterableReader<R> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.next_element()
+            .unwrap_or_else(|err| panic_on_error(err))
     }
 }
 
+impl<R: Read + Send> ElementSource for IterableReader<R> {
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        IterableReader::next_element(self)
+    }
+
+    fn provenance(&self) -> Option<Provenance> {
+        // The blob an element is taken from stays current until it is exhausted
+        let blob = self.current_blob.as_ref()?;
+        Some(Provenance {
+            source: self.source_name.clone(),
+            blob_offset: blob.offset,
+            blob_size: self.current_blob_end - blob.offset,
+            index: self.elements_read.checked_sub(1)?,
+        })
+    }
+}
+
+fn panic_on_error(err: anyhow::Error) -> ! {
+    panic!("Failed to read the PBF data: {:#}", err)
+}
+
+#[cfg(feature = "fs")]
 impl IterableReader<BufReader<File>> {
     /// Creates a new `IterableReader` from a file path.
     pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
-        let pbf_reader = PbfReader::from_path(path)?;
-        Ok(Self::new(pbf_reader))
+        let pbf_reader = PbfReader::from_path(&path)?;
+        let mut reader = Self::new(pbf_reader);
+        reader.set_source_name(&path.as_ref().to_string_lossy());
+        Ok(reader)
+    }
+}
+
+impl<'a> IterableReader<Cursor<&'a [u8]>> {
+    /// Creates a new `IterableReader` reading PBF data held in memory.
+    pub fn from_bytes(data: &'a [u8]) -> Self {
+        Self::new(PbfReader::from_bytes(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::readers::PbfRandomRead;
+    use crate::writers::{ElementSink, SortingWriter};
+
+    #[test]
+    fn test_metadata_filter() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let (_, changeset_id) = IterableReader::from_path(path)
+            .unwrap()
+            .map(|element| match element {
+                Element::Way(way) => (way.id, way.changeset_id),
+                _ => (0, 0),
+            })
+            .max()
+            .unwrap();
+        let mut filter = MetadataFilter::new();
+        filter.set_changeset_ids([changeset_id]);
+        let expected = IterableReader::from_path(path)
+            .unwrap()
+            .filter(|element| filter.matches(element))
+            .count();
+        assert!(expected > 0);
+
+        let mut reader = IterableReader::from_path(path).unwrap();
+        reader.set_metadata_filter(filter.clone());
+        assert_eq!(reader.count(), expected);
+
+        let mut reader = IterableReader::from_path(path).unwrap();
+        reader.set_metadata_filter(filter);
+        let count: usize = reader.blob_chunks().map(|chunk| chunk.len()).sum();
+        assert_eq!(count, expected);
+    }
+
+    /// Records the provenance of the elements written to it.
+    #[derive(Default)]
+    struct ProvenanceSink(Vec<((ElementType, i64), Provenance)>);
+
+    impl ElementSink for ProvenanceSink {
+        fn write(&mut self, _element: Element) -> anyhow::Result<()> {
+            bail!("The provenance is missing")
+        }
+
+        fn write_with_provenance(
+            &mut self,
+            element: Element,
+            provenance: Option<Provenance>,
+        ) -> anyhow::Result<()> {
+            let provenance = provenance.ok_or_else(|| anyhow!("The provenance is missing"))?;
+            self.0.push((element.get_meta(), provenance));
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_provenance() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let mut reader = IterableReader::from_path(path).unwrap();
+        assert!(reader.provenance().is_none());
+        // The provenance is passed on through a transform reordering the elements
+        let mut sink = SortingWriter::new(ProvenanceSink::default());
+        while let Some(element) = reader.next_element().unwrap() {
+            sink.write_with_provenance(element, reader.provenance())
+                .unwrap();
+        }
+        sink.finish().unwrap();
+        let records = sink.into_inner().0;
+
+        let mut pbf_reader = PbfReader::from_path(path).unwrap();
+        let mut indexes = Vec::new();
+        for ((element_type, id), provenance) in records.iter().step_by(1000) {
+            assert_eq!(&*provenance.source, path);
+            let blob = pbf_reader
+                .read_blob_by_offset(provenance.blob_offset)
+                .unwrap();
+            let found = match element_type {
+                ElementType::Node => blob.nodes.iter().any(|node| node.id == *id),
+                ElementType::Way => blob.ways.iter().any(|way| way.id == *id),
+                ElementType::Relation => blob.relations.iter().any(|relation| relation.id == *id),
+            };
+            assert!(found);
+            assert_eq!(
+                pbf_reader.position(),
+                provenance.blob_offset + provenance.blob_size
+            );
+            indexes.push(provenance.index);
+        }
+        assert!(indexes.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(records.last().unwrap().1.index, records.len() as u64 - 1);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let total = IterableReader::from_path(path).unwrap().count();
+
+        let chunks: Vec<Vec<Element>> = IterableReader::from_path(path)
+            .unwrap()
+            .chunks(1000)
+            .collect();
+        assert!(chunks[..chunks.len() - 1]
+            .iter()
+            .all(|chunk| chunk.len() == 1000));
+        assert_eq!(chunks.iter().map(|chunk| chunk.len()).sum::<usize>(), total);
+
+        let mut reader = IterableReader::from_path(path).unwrap();
+        let first = reader.next().unwrap();
+        let blob_chunks: Vec<Vec<Element>> = reader.blob_chunks().collect();
+        assert!(blob_chunks.len() > 1);
+        assert_eq!(
+            blob_chunks.iter().map(|chunk| chunk.len()).sum::<usize>(),
+            total - 1
+        );
+        assert_ne!(blob_chunks[0][0].get_meta(), first.get_meta());
+    }
+
+    #[test]
+    fn test_skip_to() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let count = |element_type: ElementType| {
+            IterableReader::from_path(path)
+                .unwrap()
+                .filter(|element| element.get_meta().0 >= element_type)
+                .count()
+        };
+
+        for element_type in [ElementType::Way, ElementType::Relation] {
+            let mut reader = IterableReader::from_path(path).unwrap();
+            reader.skip_to(element_type.clone()).unwrap();
+            let elements: Vec<Element> = reader.collect();
+            assert!(elements
+                .iter()
+                .all(|element| element.get_meta().0 >= element_type));
+            assert_eq!(elements.len(), count(element_type));
+        }
+
+        // Skipping back to an earlier type does nothing
+        let mut reader = IterableReader::from_path(path).unwrap();
+        reader.skip_to(ElementType::Relation).unwrap();
+        reader.skip_to(ElementType::Way).unwrap();
+        assert!(matches!(reader.next(), Some(Element::Relation(_))));
+    }
+
+    #[test]
+    fn test_with_offsets() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let mut pbf_reader = PbfReader::from_path(path).unwrap();
+        let mut previous_offset = 0;
+        for (element, offset) in IterableReader::from_path(path).unwrap().with_offsets() {
+            assert!(offset >= previous_offset);
+            if offset != previous_offset {
+                let blob = pbf_reader.read_blob_by_offset(offset).unwrap();
+                let found = match &element {
+                    Element::Node(node) => blob.nodes.first() == Some(node),
+                    Element::Way(way) => blob.ways.first() == Some(way),
+                    Element::Relation(relation) => blob.relations.first() == Some(relation),
+                };
+                assert!(found);
+                previous_offset = offset;
+            }
+        }
+        assert!(previous_offset > 0);
     }
 }
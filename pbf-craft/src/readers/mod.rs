@@ -1,10 +1,29 @@
+#[cfg(feature = "fs")]
 mod cached_reader;
+#[cfg(feature = "fs")]
 mod indexed_reader;
 mod iter_reader;
+mod ndjson_reader;
+mod o5m_reader;
 mod raw_reader;
+mod scan_schedule;
+mod sorted_source;
 mod traits;
 
+pub use crate::codecs::blob::{DecodedBlob, PbfError};
+pub use crate::codecs::block_decorators::{DecodeErrorPolicy, HeaderReader, PrimitiveReader};
+pub use crate::codecs::decode_options::DecodeOptions;
+pub use crate::codecs::id_scan::BlockIds;
+#[cfg(feature = "fs")]
 pub use cached_reader::CachedReader;
-pub use indexed_reader::IndexedReader;
+#[cfg(feature = "fs")]
+pub use indexed_reader::{
+    BlobCounts, ElementEdit, IndexMissPolicy, IndexSummary, IndexedReader, PbfIndex,
+};
 pub use iter_reader::IterableReader;
-pub use raw_reader::PbfReader;
+pub use ndjson_reader::NdjsonReader;
+pub use o5m_reader::O5mReader;
+pub use raw_reader::{PbfReader, TagMatch};
+pub use scan_schedule::ScanSchedule;
+pub use sorted_source::SortedSource;
+pub use traits::{BlobData, BlobElement, ElementRef, ElementSource, PbfRandomRead, Provenance};
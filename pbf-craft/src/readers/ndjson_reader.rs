@@ -0,0 +1,111 @@
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use super::traits::ElementSource;
+use crate::models::Element;
+
+/// A reader of line-delimited JSON with one element per line.
+///
+/// Each line holds an `Element` in the layout written by `NdjsonWriter` with
+/// `NdjsonSchema::Raw`. Fields missing from a line take their default values, so hand-written
+/// or stripped-down elements can be read as well. Blank lines are skipped.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{ElementSource, NdjsonReader};
+///
+/// let data = r#"{"type":"Node","id":1,"latitude":425000000,"longitude":15000000}"#;
+/// let mut reader = NdjsonReader::new(data.as_bytes());
+/// while let Some(element) = reader.next_element().unwrap() {
+///     // Process the element
+/// }
+/// ```
+pub struct NdjsonReader<R: BufRead> {
+    reader: R,
+    line: String,
+    line_number: usize,
+}
+
+#[cfg(feature = "fs")]
+impl NdjsonReader<BufReader<File>> {
+    /// Creates a new `NdjsonReader` from a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let f = File::open(path)?;
+        Ok(Self::new(BufReader::new(f)))
+    }
+}
+
+impl<R: BufRead> NdjsonReader<R> {
+    /// Creates a new `NdjsonReader` with the specified reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+            line_number: 0,
+        }
+    }
+}
+
+impl<R: BufRead> ElementSource for NdjsonReader<R> {
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            let line = self.line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            return Element::from_json(line)
+                .map(Some)
+                .map_err(|err| anyhow!("Invalid element at line {}: {}", self.line_number, err));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::writers::{NdjsonSchema, NdjsonWriter};
+
+    #[test]
+    fn test_round_trip() {
+        let elements: Vec<Element> =
+            crate::readers::IterableReader::from_path("./resources/andorra-latest.osm.pbf")
+                .unwrap()
+                .take(20000)
+                .collect();
+        let mut data = Vec::new();
+        let mut writer = NdjsonWriter::new(&mut data, NdjsonSchema::Raw);
+        for element in elements.iter() {
+            writer.write(element.clone()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = NdjsonReader::new(data.as_slice());
+        for expected in elements.iter() {
+            let actual = reader.next_element().unwrap().unwrap();
+            assert_eq!(
+                serde_json::to_value(expected).unwrap(),
+                serde_json::to_value(actual).unwrap()
+            );
+        }
+        assert!(reader.next_element().unwrap().is_none());
+
+        let mut reader = NdjsonReader::new("{\"type\":\"Way\",\"id\":1}\n\nnot json\n".as_bytes());
+        let Element::Way(way) = reader.next_element().unwrap().unwrap() else {
+            panic!("expected a way");
+        };
+        assert!(way.visible);
+        let err = reader.next_element().unwrap_err();
+        assert!(err.to_string().contains("line 3"));
+    }
+}
@@ -0,0 +1,271 @@
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::io::{ErrorKind, Read};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use byteorder::ReadBytesExt;
+use chrono::DateTime;
+
+use super::traits::ElementSource;
+use crate::codecs::o5m::{self, StringTable};
+use crate::models::{
+    intern, Bound, Element, ElementBase, ElementType, Node, OsmUser, Relation, RelationMember, Tag,
+    Way, WayNode,
+};
+
+/// A reader for the o5m format.
+///
+/// o5m is a compact binary format used by tools such as osmconvert and osmfilter. The reader
+/// yields the same `Element` model as the PBF readers, so o5m data can be fed into any
+/// utility accepting an `ElementSource`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{ElementSource, O5mReader};
+///
+/// # let mut data = Vec::new();
+/// # pbf_craft::writers::O5mWriter::new(&mut data).finish().unwrap();
+/// let mut reader = O5mReader::new(data.as_slice());
+/// while let Some(element) = reader.next_element().unwrap() {
+///     // Process the element
+/// }
+/// ```
+pub struct O5mReader<R: Read> {
+    reader: R,
+    string_table: StringTable,
+    id: i64,
+    timestamp: i64,
+    changeset: i64,
+    latitude: i64,
+    longitude: i64,
+    references: [i64; 3],
+    bound: Option<Bound>,
+    eof: bool,
+}
+
+#[cfg(feature = "fs")]
+impl O5mReader<BufReader<File>> {
+    /// Creates a new `O5mReader` from a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let f = File::open(path)?;
+        Ok(Self::new(BufReader::new(f)))
+    }
+}
+
+impl<R: Read> O5mReader<R> {
+    /// Creates a new `O5mReader` with the specified reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            string_table: StringTable::new(),
+            id: 0,
+            timestamp: 0,
+            changeset: 0,
+            latitude: 0,
+            longitude: 0,
+            references: [0; 3],
+            bound: None,
+            eof: false,
+        }
+    }
+
+    /// Returns the bounding box of the file, once it has been read.
+    ///
+    /// The bounding box is stored at the beginning of the file, so it is available as soon as
+    /// the first element has been read.
+    pub fn bound(&self) -> Option<&Bound> {
+        self.bound.as_ref()
+    }
+
+    fn reset(&mut self) {
+        self.string_table.reset();
+        self.id = 0;
+        self.timestamp = 0;
+        self.changeset = 0;
+        self.latitude = 0;
+        self.longitude = 0;
+        self.references = [0; 3];
+    }
+
+    fn read_dataset(&mut self) -> anyhow::Result<Option<(u8, Vec<u8>)>> {
+        loop {
+            let dataset_type = match self.reader.read_u8() {
+                Ok(byte) => byte,
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => bail!(err),
+            };
+            match dataset_type {
+                o5m::DATASET_RESET => self.reset(),
+                o5m::DATASET_END_OF_FILE => return Ok(None),
+                // datasets 0xf0 to 0xff carry no length and no data
+                0xf0..=0xff => continue,
+                _ => {
+                    let length = self.read_length()?;
+                    let mut data = Vec::with_capacity(length as usize);
+                    (&mut self.reader).take(length).read_to_end(&mut data)?;
+                    if data.len() as u64 != length {
+                        bail!("Unexpected end of o5m file");
+                    }
+                    return Ok(Some((dataset_type, data)));
+                }
+            }
+        }
+    }
+
+    fn read_length(&mut self) -> anyhow::Result<u64> {
+        let mut buf = Vec::new();
+        loop {
+            let byte = self.reader.read_u8()?;
+            buf.push(byte);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        o5m::read_uvarint(&buf, &mut 0)
+    }
+
+    fn read_element(&mut self) -> anyhow::Result<Option<Element>> {
+        while let Some((dataset_type, data)) = self.read_dataset()? {
+            match dataset_type {
+                o5m::DATASET_NODE => return self.decode_node(&data).map(Some),
+                o5m::DATASET_WAY => return self.decode_way(&data).map(Some),
+                o5m::DATASET_RELATION => return self.decode_relation(&data).map(Some),
+                o5m::DATASET_HEADER if data != o5m::HEADER_MAGIC && data != b"o5c2" => {
+                    bail!(
+                        "Unsupported o5m header: {:?}",
+                        String::from_utf8_lossy(&data)
+                    );
+                }
+                o5m::DATASET_BOUNDING_BOX => {
+                    let mut pos = 0;
+                    let left = o5m::read_svarint(&data, &mut pos)?;
+                    let bottom = o5m::read_svarint(&data, &mut pos)?;
+                    let right = o5m::read_svarint(&data, &mut pos)?;
+                    let top = o5m::read_svarint(&data, &mut pos)?;
+                    self.bound = Some(Bound {
+                        left: left * o5m::COORDINATE_UNIT,
+                        right: right * o5m::COORDINATE_UNIT,
+                        top: top * o5m::COORDINATE_UNIT,
+                        bottom: bottom * o5m::COORDINATE_UNIT,
+                    });
+                }
+                // file timestamps, sync and jump datasets are not needed
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Decodes the id and the author information shared by all element types.
+    ///
+    /// Returns the base element and the position after the author information.
+    fn decode_base(&mut self, data: &[u8]) -> anyhow::Result<(ElementBase, usize)> {
+        let mut pos = 0;
+        self.id += o5m::read_svarint(data, &mut pos)?;
+        let mut base = ElementBase::new_with_tags(self.id, Vec::new());
+        if pos < data.len() {
+            base.version = o5m::read_uvarint(data, &mut pos)? as i32;
+            if base.version != 0 {
+                self.timestamp += o5m::read_svarint(data, &mut pos)?;
+                if self.timestamp != 0 {
+                    base.timestamp = DateTime::from_timestamp(self.timestamp, 0);
+                    self.changeset += o5m::read_svarint(data, &mut pos)?;
+                    base.changeset_id = self.changeset;
+                    let strings = self.string_table.read(data, &mut pos, 2)?;
+                    base.user = Some(OsmUser {
+                        id: o5m::decode_uid(&strings[0])?,
+                        name: intern(std::str::from_utf8(&strings[1])?),
+                    });
+                }
+            }
+        }
+        // an object without any data after the author information has been deleted
+        base.visible = pos < data.len();
+        Ok((base, pos))
+    }
+
+    fn decode_tags(&mut self, data: &[u8], mut pos: usize) -> anyhow::Result<Vec<Tag>> {
+        let mut tags = Vec::new();
+        while pos < data.len() {
+            let mut strings = self.string_table.read(data, &mut pos, 2)?;
+            let value = String::from_utf8(strings.pop().unwrap())?;
+            let key = String::from_utf8(strings.pop().unwrap())?;
+            tags.push(Tag { key, value });
+        }
+        Ok(tags)
+    }
+
+    fn decode_node(&mut self, data: &[u8]) -> anyhow::Result<Element> {
+        let (base, mut pos) = self.decode_base(data)?;
+        let mut node: Node = base.into();
+        if node.visible {
+            self.longitude += o5m::read_svarint(data, &mut pos)?;
+            self.latitude += o5m::read_svarint(data, &mut pos)?;
+            node.longitude = self.longitude * o5m::COORDINATE_UNIT;
+            node.latitude = self.latitude * o5m::COORDINATE_UNIT;
+            node.tags = self.decode_tags(data, pos)?;
+        }
+        Ok(Element::Node(node))
+    }
+
+    fn decode_way(&mut self, data: &[u8]) -> anyhow::Result<Element> {
+        let (base, mut pos) = self.decode_base(data)?;
+        let mut way: Way = base.into();
+        if way.visible {
+            let length = o5m::read_uvarint(data, &mut pos)? as usize;
+            let end = pos + length;
+            while pos < end {
+                self.references[0] += o5m::read_svarint(data, &mut pos)?;
+                way.way_nodes
+                    .push(WayNode::new_without_coords(self.references[0]));
+            }
+            way.tags = self.decode_tags(data, pos)?;
+        }
+        Ok(Element::Way(way))
+    }
+
+    fn decode_relation(&mut self, data: &[u8]) -> anyhow::Result<Element> {
+        let (base, mut pos) = self.decode_base(data)?;
+        let mut relation: Relation = base.into();
+        if relation.visible {
+            let length = o5m::read_uvarint(data, &mut pos)? as usize;
+            let end = pos + length;
+            while pos < end {
+                let delta = o5m::read_svarint(data, &mut pos)?;
+                let mut strings = self.string_table.read(data, &mut pos, 1)?;
+                let type_and_role = strings.pop().unwrap();
+                let (member_type, index) = match type_and_role.first() {
+                    Some(b'0') => (ElementType::Node, 0),
+                    Some(b'1') => (ElementType::Way, 1),
+                    Some(b'2') => (ElementType::Relation, 2),
+                    _ => bail!("Invalid o5m relation member type"),
+                };
+                self.references[index] += delta;
+                relation.members.push(RelationMember {
+                    member_id: self.references[index],
+                    member_type,
+                    role: intern(std::str::from_utf8(&type_and_role[1..])?),
+                });
+            }
+            relation.tags = self.decode_tags(data, pos)?;
+        }
+        Ok(Element::Relation(relation))
+    }
+}
+
+impl<R: Read> ElementSource for O5mReader<R> {
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        if self.eof {
+            return Ok(None);
+        }
+        let element = self.read_element()?;
+        if element.is_none() {
+            self.eof = true;
+        }
+        Ok(element)
+    }
+}
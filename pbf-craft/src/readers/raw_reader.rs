@@ -1,15 +1,44 @@
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
+#[cfg(feature = "parallel")]
+use rayon::{ThreadPool, ThreadPoolBuilder};
 
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
 use std::fs::File;
-use std::io::{BufReader, Read};
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::io::{Cursor, Read, Seek};
+use std::ops::Range;
+#[cfg(feature = "fs")]
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::Arc;
 
-use super::traits::{BlobData, PbfRandomRead};
+#[cfg(feature = "fs")]
+use super::indexed_reader::IndexSummary;
+use super::iter_reader::IterableReader;
+#[cfg(feature = "fs")]
+use super::scan_schedule::estimated_cost;
+use super::scan_schedule::ScanSchedule;
+#[cfg(feature = "parallel")]
+use super::scan_schedule::ScheduledBlobs;
+use super::traits::{BlobData, ElementSource, PbfRandomRead};
+use crate::codecs::backend::PrimitiveBlock;
 use crate::codecs::blob::{BlobReader, DecodedBlob};
-use crate::codecs::block_decorators::{HeaderReader, PrimitiveReader};
+use crate::codecs::block_decorators::{DecodeErrorPolicy, HeaderReader, PrimitiveReader};
+use crate::codecs::decode_options::DecodeOptions;
+use crate::codecs::id_scan::{decode_block_ids, BlockIds};
+#[cfg(feature = "parallel")]
+use crate::models::ElementTypeSet;
 use crate::models::{Element, ElementType};
 
+/// Whether `PbfReader::find_all_by_tags` requires all of the given tags or any of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagMatch {
+    All,
+    Any,
+}
+
 /// A foundamental reader for PBF data.
 ///
 /// The `PbfReader` struct provides functionality to read and process PBF files,
@@ -38,6 +67,14 @@ use crate::models::{Element, ElementType};
 /// ```
 pub struct PbfReader<R: Read + Send> {
     blob_reader: BlobReader<R>,
+    decode_error_policy: DecodeErrorPolicy,
+    decode_options: DecodeOptions,
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    scan_schedule: ScanSchedule,
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    blob_costs: HashMap<u64, u64>,
+    #[cfg(feature = "parallel")]
+    thread_pool: Option<Arc<ThreadPool>>,
 }
 
 impl<R: Read + Send> PbfReader<R> {
@@ -45,38 +82,333 @@ impl<R: Read + Send> PbfReader<R> {
     pub fn new(reader: R) -> PbfReader<R> {
         Self {
             blob_reader: BlobReader::new(reader),
+            decode_error_policy: DecodeErrorPolicy::default(),
+            decode_options: DecodeOptions::default(),
+            scan_schedule: ScanSchedule::default(),
+            blob_costs: HashMap::new(),
+            #[cfg(feature = "parallel")]
+            thread_pool: None,
         }
     }
 
-    pub fn read_next_blob(&mut self) -> Option<BlobData> {
+    /// Sets what to do with elements which can't be decoded. By default, decoding the block
+    /// fails.
+    ///
+    /// This is a breaking change: the readers used to substitute an empty string for the
+    /// strings which can't be decoded. Set `DecodeErrorPolicy::Substitute(String::new())` to
+    /// keep reading such files as before.
+    pub fn set_decode_error_policy(&mut self, policy: DecodeErrorPolicy) {
+        self.decode_error_policy = policy;
+    }
+
+    /// Sets the parts of the elements to leave out when decoding, e.g. the tags and metadata
+    /// for a pass which only needs the references. By default, everything is decoded.
+    pub fn set_decode_options(&mut self, options: DecodeOptions) {
+        self.decode_options = options;
+    }
+
+    /// Sets how the parallel scans, such as `par_find` and `for_each_blob_parallel`, hand the
+    /// blobs to the worker threads. By default, they are handed out in the order of the file.
+    ///
+    /// `ScanSchedule::LargestFirst` starts the expensive blobs of each window first, estimating
+    /// their cost from their compressed size, or from the element counts of the index if they
+    /// are set with `set_blob_inventory`. It shortens scans whose slowest blobs come last, as
+    /// the relations do in sorted files, when there are several cores.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::{Element, ElementTypeSet};
+    /// use pbf_craft::readers::{PbfIndex, PbfReader, ScanSchedule};
+    ///
+    /// let path = "resources/andorra-latest.osm.pbf";
+    /// let mut reader = PbfReader::from_path(path).unwrap();
+    /// reader.set_scan_schedule(ScanSchedule::LargestFirst { window: 64 });
+    /// reader.set_blob_inventory(&PbfIndex::new(path).unwrap().summary());
+    /// let highways = reader
+    ///     .par_find(ElementTypeSet::WAYS, |element| match element {
+    ///         Element::Way(way) => way.tags.iter().any(|tag| tag.key == "highway"),
+    ///         _ => false,
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn set_scan_schedule(&mut self, scan_schedule: ScanSchedule) {
+        self.scan_schedule = scan_schedule;
+    }
+
+    /// Sets the rayon thread pool running the parallel scans, such as `par_find` and
+    /// `for_each_blob_parallel`. By default, they run on the global pool.
+    ///
+    /// A pool of its own keeps the scans from competing with other rayon work of the process,
+    /// e.g. in a server handling requests on the global pool, and caps the cores they use. The
+    /// pool can be shared by several readers.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    ///
+    /// use pbf_craft::models::Element;
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap());
+    /// let mut reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// reader.set_thread_pool(pool);
+    /// let nodes = reader
+    ///     .par_map_reduce(
+    ///         |element| matches!(element, Element::Node(_)) as u64,
+    ///         |a, b| a + b,
+    ///         || 0,
+    ///     )
+    ///     .unwrap();
+    /// assert!(nodes > 0);
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn set_thread_pool(&mut self, thread_pool: Arc<ThreadPool>) {
+        self.thread_pool = Some(thread_pool);
+    }
+
+    /// Runs the parallel scans on a new thread pool with the given number of threads, see
+    /// `set_thread_pool`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the threads can't be spawned.
+    #[cfg(feature = "parallel")]
+    pub fn set_num_threads(&mut self, num_threads: usize) -> anyhow::Result<()> {
+        let thread_pool = ThreadPoolBuilder::new().num_threads(num_threads).build()?;
+        self.thread_pool = Some(Arc::new(thread_pool));
+        Ok(())
+    }
+
+    /// Sets the element counts of the blobs, from the index of the file, to estimate the costs
+    /// of the blobs for `ScanSchedule::LargestFirst`. Ways and relations take longer to decode
+    /// than nodes, which the compressed sizes of the blobs don't reflect.
+    #[cfg(feature = "fs")]
+    pub fn set_blob_inventory(&mut self, summary: &IndexSummary) {
+        self.blob_costs = summary
+            .blobs
+            .iter()
+            .map(|blob| {
+                (
+                    blob.offset,
+                    estimated_cost(blob.nodes, blob.ways, blob.relations),
+                )
+            })
+            .collect();
+    }
+
+    /// Sets whether blobs exceeding the sizes allowed by the specification, 64 KiB for blob
+    /// headers and 32 MiB for blobs, are rejected. It's enabled by default; disable it only for
+    /// files known to violate the limits.
+    pub fn set_enforce_size_limits(&mut self, enforce_size_limits: bool) {
+        self.blob_reader
+            .set_enforce_size_limits(enforce_size_limits);
+    }
+
+    /// Sets whether to recover from a corrupt stream, e.g. a truncated or damaged download, by
+    /// scanning forward for the next blob header of a data block when a blob can't be read. The
+    /// skipped bytes are reported by `skipped_ranges`. It's disabled by default, failing the read
+    /// instead.
+    ///
+    /// Only the framing of the blobs is recovered; a blob whose header is intact but whose data
+    /// is corrupt still fails to decode.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::{IterableReader, PbfReader};
+    ///
+    /// let data = std::fs::read("resources/andorra-latest.osm.pbf").unwrap();
+    /// // A download cut off in the middle of a blob
+    /// let truncated = &data[..data.len() / 2];
+    ///
+    /// let mut reader = PbfReader::new(truncated);
+    /// reader.set_resync(true);
+    /// let mut reader = IterableReader::new(reader);
+    /// let count = reader.by_ref().count();
+    /// for range in reader.skipped_ranges() {
+    ///     println!("Skipped bytes {} to {}", range.start, range.end);
+    /// }
+    /// ```
+    pub fn set_resync(&mut self, resync: bool) {
+        self.blob_reader.set_resync(resync);
+    }
+
+    /// Returns the byte ranges skipped so far because of a corrupt stream, when resyncing is
+    /// enabled with `set_resync`.
+    pub fn skipped_ranges(&self) -> &[Range<u64>] {
+        self.blob_reader.skipped_ranges()
+    }
+
+    /// Sets whether data ending in the middle of a blob, e.g. after an interrupted download, is
+    /// read up to its last complete blob. By default, the read fails with
+    /// `PbfError::Truncated`, so that an incomplete file isn't mistaken for a complete one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// let data = std::fs::read("resources/andorra-latest.osm.pbf").unwrap();
+    /// let mut reader = PbfReader::new(&data[..data.len() / 2]);
+    /// reader.set_allow_truncation(true);
+    /// let mut element_count = 0;
+    /// reader.read(|_, element| element_count += element.is_some() as usize).unwrap();
+    /// if let Some(valid_bytes) = reader.truncation() {
+    ///     println!("Read {} elements from the first {} bytes", element_count, valid_bytes);
+    /// }
+    /// ```
+    pub fn set_allow_truncation(&mut self, allow_truncation: bool) {
+        self.blob_reader.set_allow_truncation(allow_truncation);
+    }
+
+    /// Returns the number of valid bytes if the data was found truncated while truncation is
+    /// allowed with `set_allow_truncation`.
+    pub fn truncation(&self) -> Option<u64> {
+        self.blob_reader.truncation()
+    }
+
+    /// Registers a handler for the blobs of a non-standard type, such as vendor-specific blobs.
+    ///
+    /// Blobs of an unknown type fail the read, unless a handler is registered for their type. The
+    /// handler is called with the uncompressed data of each such blob, which is then skipped. The
+    /// standard `OSMHeader` and `OSMData` blobs are never passed to handlers.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// let mut reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// reader.register_blob_handler("OSMFeatures", |data| {
+    ///     println!("Found {} bytes of features", data.len());
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn register_blob_handler<F>(&mut self, blob_type: &str, handler: F)
+    where
+        F: FnMut(&[u8]) -> anyhow::Result<()> + Send + 'static,
+    {
+        self.blob_reader
+            .register_blob_handler(blob_type, Box::new(handler));
+    }
+
+    /// Returns the byte offset of the next blob to be read.
+    pub(crate) fn position(&self) -> u64 {
+        self.blob_reader.offset
+    }
+
+    /// Reads and decodes the next blob. Returns `Ok(None)` at the end of the file.
+    pub fn read_next_blob(&mut self) -> anyhow::Result<Option<BlobData>> {
         if self.blob_reader.eof {
-            None
+            Ok(None)
         } else {
             let offset = self.blob_reader.offset;
-            match self.blob_reader.next() {
-                Some(blob) => match blob.decode().expect("Failed to decode block.") {
+            match self.blob_reader.next_blob()? {
+                Some(blob) => match blob.decode_with_options(&self.decode_options)? {
                     DecodedBlob::OsmHeader(_) => {
-                        return Some(BlobData {
+                        let block = PrimitiveBlock::default();
+                        Ok(Some(BlobData {
                             nodes: Vec::with_capacity(0),
                             ways: Vec::with_capacity(0),
                             relations: Vec::with_capacity(0),
                             offset,
-                        })
-                    }
-                    DecodedBlob::OsmData(data) => {
-                        let decorator = PrimitiveReader::new(data);
-                        let (nodes, ways, relations) = decorator.get_all_elements();
-                        return Some(BlobData {
-                            nodes,
-                            ways,
-                            relations,
-                            offset,
-                        });
+                            granularity: block.get_granularity(),
+                            date_granularity: block.get_date_granularity(),
+                            lat_offset: block.get_lat_offset(),
+                            lon_offset: block.get_lon_offset(),
+                        }))
                     }
+                    DecodedBlob::OsmData(data) => Ok(Some(decode_blob_data(
+                        data,
+                        offset,
+                        &self.decode_error_policy,
+                        self.decode_options,
+                    )?)),
                 },
-                None => None,
+                None => Ok(None),
+            }
+        }
+    }
+
+    /// Reads the next blob containing elements of the given type or of a later type.
+    ///
+    /// The blobs before it are skipped without decoding their elements, which makes it cheap to
+    /// skip e.g. all the nodes of a file sorted by type. The elements of earlier types in the
+    /// returned blob are still included.
+    pub fn read_next_blob_from(
+        &mut self,
+        element_type: &ElementType,
+    ) -> anyhow::Result<Option<BlobData>> {
+        loop {
+            let offset = self.blob_reader.offset;
+            let Some(blob) = self.blob_reader.next_blob()? else {
+                return Ok(None);
+            };
+            if let DecodedBlob::OsmData(data) = blob.decode_with_options(&self.decode_options)? {
+                if contains_types_from(&data, element_type) {
+                    let blob_data = decode_blob_data(
+                        data,
+                        offset,
+                        &self.decode_error_policy,
+                        self.decode_options,
+                    )?;
+                    return Ok(Some(blob_data));
+                }
+            }
+        }
+    }
+
+    /// Reads only the IDs and references of the elements of the next blob, skipping the
+    /// decoding of the string table, tags, coordinates and metadata. Header blobs yield an empty
+    /// `BlockIds`. Returns `Ok(None)` at the end of the file.
+    pub fn read_next_block_ids(&mut self) -> anyhow::Result<Option<BlockIds>> {
+        if self.blob_reader.eof {
+            return Ok(None);
+        }
+        let offset = self.blob_reader.offset;
+        match self.blob_reader.next_blob()? {
+            Some(blob) if blob.is_osm_data() => {
+                let _span = trace_span!("decode_block_ids", offset = offset);
+                Ok(Some(decode_block_ids(&blob.decompress_data()?, offset)?))
+            }
+            Some(_) => Ok(Some(BlockIds {
+                offset,
+                ..Default::default()
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Scans the IDs and references of all elements, one block at a time.
+    ///
+    /// This is much faster than `read` for passes which only need the topology of the data,
+    /// such as indexing or marking referenced elements, as tags and metadata aren't decoded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// let mut reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let mut way_node_count = 0;
+    /// reader
+    ///     .read_ids(|block_ids| {
+    ///         way_node_count += block_ids.way_refs.iter().map(Vec::len).sum::<usize>();
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn read_ids<F>(&mut self, mut callback: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&BlockIds),
+    {
+        while let Some(block_ids) = self.read_next_block_ids()? {
+            if !block_ids.is_empty() {
+                callback(&block_ids);
             }
         }
+        Ok(())
     }
 
     /// Reads and processes header and elements using the provided callback function.
@@ -118,29 +450,65 @@ impl<R: Read + Send> PbfReader<R> {
     where
         F: FnMut(Option<HeaderReader>, Option<Element>),
     {
-        for blob in &mut self.blob_reader {
-            match blob.decode()? {
+        while let Some(blob) = self.blob_reader.next_blob()? {
+            match blob.decode_with_options(&self.decode_options)? {
                 DecodedBlob::OsmHeader(b) => {
                     let header_reader = HeaderReader::new(b);
                     callback(Some(header_reader), None);
                 }
                 DecodedBlob::OsmData(data) => {
-                    let decorator = PrimitiveReader::new(data);
-                    decorator.for_each_element(|el| callback(None, Some(el)));
+                    let decorator = PrimitiveReader::with_options(
+                        data,
+                        self.decode_error_policy.clone(),
+                        self.decode_options,
+                    );
+                    decorator.for_each_element(|el| callback(None, Some(el)))?;
                 }
             }
         }
         Ok(())
     }
 
+    /// Reads the blocks as the decoded protobuf messages, without converting them into elements.
+    ///
+    /// It's meant for custom decoding, e.g. reading only the string tables, which is cheaper
+    /// than converting every element. The messages are defined in the `proto` module.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::{DecodedBlob, PbfReader};
+    ///
+    /// let mut reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let mut dense_node_count = 0;
+    /// reader
+    ///     .read_blocks(|block| {
+    ///         if let DecodedBlob::OsmData(block) = block {
+    ///             for group in block.get_primitivegroup() {
+    ///                 dense_node_count += group.get_dense().get_id().len();
+    ///             }
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn read_blocks<F>(&mut self, mut callback: F) -> anyhow::Result<()>
+    where
+        F: FnMut(DecodedBlob),
+    {
+        while let Some(blob) = self.blob_reader.next_blob()? {
+            callback(blob.decode_with_options(&self.decode_options)?);
+        }
+        Ok(())
+    }
+
     /// Finds elements in parallel.
     ///
     /// # Arguments
     ///
-    /// * `inclination` - An optional reference to an `ElementType` that specifies the type of elements to find.
-    ///                   If `None`, all element types are considered.
+    /// * `types` - The types of elements to find, e.g. `ElementTypeSet::NODES | ElementTypeSet::WAYS`.
+    ///   The elements of the other types aren't decoded.
     /// * `callback` - A closure that takes a reference to an `Element` and returns a boolean indicating
-    ///                whether the element should be included in the result. The closure must be `Send` and `Sync`.
+    ///   whether the element should be included in the result. The closure must be `Send` and `Sync`.
     ///
     /// # Returns
     ///
@@ -154,90 +522,474 @@ impl<R: Read + Send> PbfReader<R> {
     /// # Example
     ///
     /// ```rust
-    /// use pbf_craft::models::ElementType;
+    /// use pbf_craft::models::ElementTypeSet;
     /// use pbf_craft::readers::PbfReader;
     ///
     /// let mut reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
-    /// let elements = reader.par_find(Some(&ElementType::Node), |element| {
+    /// let elements = reader.par_find(ElementTypeSet::NODES, |element| {
     ///     // Filter logic for nodes
     ///     true
     /// }).unwrap();
     /// ```
-    pub fn par_find<F>(
+    #[cfg(feature = "parallel")]
+    pub fn par_find<F>(self, types: ElementTypeSet, callback: F) -> anyhow::Result<Vec<Element>>
+    where
+        F: Fn(&Element) -> bool + Send + Sync,
+    {
+        let thread_pool = self.thread_pool.clone();
+        install(thread_pool.as_deref(), || {
+            let policy = &self.decode_error_policy;
+            let options = self.decode_options;
+            ScheduledBlobs::new(self.blob_reader, self.scan_schedule, self.blob_costs)
+                .par_bridge()
+                .filter_map(|(_, blob)| match blob.decode_with_options(&options) {
+                    Ok(DecodedBlob::OsmHeader(_)) => None,
+                    Ok(DecodedBlob::OsmData(b)) => Some(find_in_block(
+                        PrimitiveReader::with_options(b, policy.clone(), options),
+                        types,
+                        &callback,
+                    )),
+                    Err(err) => Some(Err(err)),
+                })
+                .try_reduce(Vec::new, |mut a, mut b| {
+                    a.append(&mut b);
+                    Ok(a)
+                })
+        })
+    }
+
+    /// Finds elements in parallel like `par_find`, but returns them in the order they are
+    /// stored in the file.
+    ///
+    /// `par_find` returns the elements in the order the blobs finish decoding, which differs
+    /// from run to run. This method tags the results with the index of their blob and sorts
+    /// them, so repeated runs yield the same output, at the cost of holding the results of all
+    /// blobs until the end.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any PBF decoding fails.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::{Element, ElementTypeSet};
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// let reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let ways = reader
+    ///     .par_find_ordered(ElementTypeSet::WAYS, |element| match element {
+    ///         Element::Way(way) => way.tags.iter().any(|tag| tag.key == "highway"),
+    ///         _ => false,
+    ///     })
+    ///     .unwrap();
+    /// assert!(ways.windows(2).all(|pair| pair[0].get_meta() < pair[1].get_meta()));
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn par_find_ordered<F>(
         self,
-        inclination: Option<&ElementType>,
+        types: ElementTypeSet,
         callback: F,
     ) -> anyhow::Result<Vec<Element>>
     where
         F: Fn(&Element) -> bool + Send + Sync,
     {
-        let result = self
-            .blob_reader
-            .par_bridge()
-            .filter_map(
-                |blob| match blob.decode().expect("decode raw blob failed.") {
-                    DecodedBlob::OsmHeader(_) => None,
-                    DecodedBlob::OsmData(b) => Some(PrimitiveReader::new(b)),
-                },
-            )
-            .filter_map(|p| {
-                if let Some(element_type) = inclination {
-                    let result = match element_type {
-                        ElementType::Node => p
-                            .get_nodes()
-                            .into_iter()
-                            .map(|i| Element::Node(i))
-                            .filter(&callback)
-                            .collect::<Vec<Element>>(),
-                        ElementType::Way => p
-                            .get_ways()
-                            .into_iter()
-                            .map(|i| Element::Way(i))
-                            .filter(&callback)
-                            .collect::<Vec<Element>>(),
-                        ElementType::Relation => p
-                            .get_relations()
-                            .into_iter()
-                            .map(|i| Element::Relation(i))
-                            .filter(&callback)
-                            .collect::<Vec<Element>>(),
-                    };
-                    Some(result)
-                } else {
-                    let (nodes, ways, relations) = p.get_all_elements();
-                    let mut filterd_nodes: Vec<Element> = nodes
-                        .into_iter()
-                        .map(|i| Element::Node(i))
-                        .filter(&callback)
-                        .collect();
-                    let mut filterd_ways: Vec<Element> = ways
-                        .into_iter()
-                        .map(|i| Element::Way(i))
-                        .filter(&callback)
-                        .collect();
-                    let mut filterd_relations: Vec<Element> = relations
-                        .into_iter()
-                        .map(|i| Element::Relation(i))
-                        .filter(&callback)
-                        .collect();
-
-                    filterd_nodes.append(&mut filterd_ways);
-                    filterd_nodes.append(&mut filterd_relations);
-                    Some(filterd_nodes)
-                }
+        let thread_pool = self.thread_pool.clone();
+        install(thread_pool.as_deref(), || {
+            let policy = &self.decode_error_policy;
+            let options = self.decode_options;
+            let mut results =
+                ScheduledBlobs::new(self.blob_reader, self.scan_schedule, self.blob_costs)
+                    .par_bridge()
+                    .filter_map(|(index, blob)| match blob.decode_with_options(&options) {
+                        Ok(DecodedBlob::OsmHeader(_)) => None,
+                        Ok(DecodedBlob::OsmData(b)) => Some(
+                            find_in_block(
+                                PrimitiveReader::with_options(b, policy.clone(), options),
+                                types,
+                                &callback,
+                            )
+                            .map(|elements| (index, elements)),
+                        ),
+                        Err(err) => Some(Err(err)),
+                    })
+                    .collect::<anyhow::Result<Vec<(usize, Vec<Element>)>>>()?;
+            results.sort_unstable_by_key(|(index, _)| *index);
+            Ok(results
+                .into_iter()
+                .flat_map(|(_, elements)| elements)
+                .collect())
+        })
+    }
+
+    /// Returns an iterator over the elements of a type and ID, e.g. all versions of an element
+    /// in a history file.
+    ///
+    /// The blobs before the first element of the type are skipped without decoding their
+    /// elements and the iteration stops after the last element of the type, as the elements
+    /// are expected to be sorted by type. For repeated lookups, use an `IndexedReader`.
+    ///
+    /// A read or decode error is returned as the last item.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::ElementType;
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// let reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let ways = reader
+    ///     .find_all_by_id(ElementType::Way, 1055523837)
+    ///     .collect::<anyhow::Result<Vec<_>>>()
+    ///     .unwrap();
+    /// assert_eq!(ways.len(), 1);
+    /// ```
+    pub fn find_all_by_id(
+        self,
+        element_type: ElementType,
+        id: i64,
+    ) -> impl Iterator<Item = anyhow::Result<Element>> {
+        let mut reader = IterableReader::new(self);
+        let skipped = reader.skip_to(element_type.clone());
+        let last_type = element_type.clone();
+        skipped
+            .err()
+            .map(Err)
+            .into_iter()
+            .chain(try_elements(reader))
+            .take_while(move |element| {
+                element
+                    .as_ref()
+                    .map_or(true, |element| element.get_meta().0 <= last_type)
             })
-            .reduce(
-                || Vec::new(),
-                |mut a, mut b| {
-                    a.append(&mut b);
-                    a
-                },
-            );
+            .filter(move |element| {
+                element.as_ref().map_or(true, |element| {
+                    element.get_meta() == (element_type.clone(), id)
+                })
+            })
+    }
+
+    /// Returns an iterator over the elements having a tag with the given key and value.
+    ///
+    /// A read or decode error is returned as the last item.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// let reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// for element in reader.find_all_by_tag("highway", "primary") {
+    ///     let element = element.unwrap();
+    ///     // Process the element
+    /// }
+    /// ```
+    pub fn find_all_by_tag(
+        self,
+        key: &str,
+        value: &str,
+    ) -> impl Iterator<Item = anyhow::Result<Element>> {
+        self.find_all_by_tags(vec![(key.to_string(), value.to_string())], TagMatch::All)
+    }
 
-        Ok(result)
+    /// Returns an iterator over the elements having all, or any, of the given tags, depending
+    /// on `tag_match`.
+    ///
+    /// A read or decode error is returned as the last item.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::{PbfReader, TagMatch};
+    ///
+    /// let reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let roads = reader
+    ///     .find_all_by_tags(
+    ///         vec![("highway", "primary"), ("highway", "secondary")],
+    ///         TagMatch::Any,
+    ///     )
+    ///     .collect::<anyhow::Result<Vec<_>>>()
+    ///     .unwrap();
+    /// assert!(!roads.is_empty());
+    /// ```
+    pub fn find_all_by_tags<K, V>(
+        self,
+        tags: Vec<(K, V)>,
+        tag_match: TagMatch,
+    ) -> impl Iterator<Item = anyhow::Result<Element>>
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let tags: Vec<(String, String)> = tags
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        try_elements(IterableReader::new(self)).filter(move |element| {
+            let Ok(element) = element else {
+                return true;
+            };
+            let element_tags = match element {
+                Element::Node(node) => &node.tags,
+                Element::Way(way) => &way.tags,
+                Element::Relation(relation) => &relation.tags,
+            };
+            let has_tag = |(key, value): &(String, String)| {
+                element_tags
+                    .iter()
+                    .any(|tag| tag.key == *key && tag.value == *value)
+            };
+            match tag_match {
+                TagMatch::All => tags.iter().all(has_tag),
+                TagMatch::Any => tags.iter().any(has_tag),
+            }
+        })
+    }
+
+    /// Maps every element to a value and reduces the values, processing the blobs in parallel.
+    ///
+    /// It allows aggregations like counts, histograms or bounding boxes to run in parallel
+    /// without collecting the elements first. `identity` creates the neutral value of the
+    /// reduction; it may be called several times and the values may be reduced in any order, so
+    /// `reduce_fn` should be associative.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any PBF decoding fails.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::Element;
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// // Counts the ways tagged with `highway`
+    /// let reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let highways = reader
+    ///     .par_map_reduce(
+    ///         |element| match element {
+    ///             Element::Way(way) if way.tags.iter().any(|tag| tag.key == "highway") => 1,
+    ///             _ => 0,
+    ///         },
+    ///         |a, b| a + b,
+    ///         || 0u64,
+    ///     )
+    ///     .unwrap();
+    /// assert!(highways > 0);
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn par_map_reduce<T, M, F, I>(
+        self,
+        map_fn: M,
+        reduce_fn: F,
+        identity: I,
+    ) -> anyhow::Result<T>
+    where
+        T: Send,
+        M: Fn(Element) -> T + Send + Sync,
+        F: Fn(T, T) -> T + Send + Sync,
+        I: Fn() -> T + Send + Sync,
+    {
+        self.par_fold_blocks(
+            |block| {
+                let (nodes, ways, relations) = block.get_all_elements()?;
+                let elements = nodes
+                    .into_iter()
+                    .map(Element::Node)
+                    .chain(ways.into_iter().map(Element::Way))
+                    .chain(relations.into_iter().map(Element::Relation));
+                Ok(elements.fold(identity(), |acc, element| reduce_fn(acc, map_fn(element))))
+            },
+            &reduce_fn,
+            &identity,
+        )
+    }
+
+    /// Calls a function with the reader of each data block, processing the blobs in parallel,
+    /// and returns the results in the order of the blobs.
+    ///
+    /// It's the building block for custom parallel pipelines: the function decides what to
+    /// decode from each block, e.g. only the ways with `PrimitiveReader::get_ways`, or the raw
+    /// protobuf message with `PrimitiveReader::block`. Header blobs are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading or decompressing a blob fails. Errors of
+    /// the function itself can be returned as part of `T`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::PbfReader;
+    ///
+    /// // The number of ways of each blob, without decoding the nodes and relations
+    /// let reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let way_counts = reader
+    ///     .for_each_blob_parallel(|block| block.get_ways().map(|ways| ways.len()))
+    ///     .unwrap();
+    /// let total: usize = way_counts.into_iter().map(|count| count.unwrap()).sum();
+    /// assert!(total > 0);
+    /// ```
+    #[cfg(feature = "parallel")]
+    pub fn for_each_blob_parallel<T, F>(self, callback: F) -> anyhow::Result<Vec<T>>
+    where
+        T: Send,
+        F: Fn(PrimitiveReader) -> T + Send + Sync,
+    {
+        let thread_pool = self.thread_pool.clone();
+        install(thread_pool.as_deref(), || {
+            let policy = &self.decode_error_policy;
+            let options = self.decode_options;
+            let mut results =
+                ScheduledBlobs::new(self.blob_reader, self.scan_schedule, self.blob_costs)
+                    .par_bridge()
+                    .filter_map(|(index, blob)| match blob.decode_with_options(&options) {
+                        Ok(DecodedBlob::OsmHeader(_)) => None,
+                        Ok(DecodedBlob::OsmData(block)) => Some(Ok((
+                            index,
+                            callback(PrimitiveReader::with_options(
+                                block,
+                                policy.clone(),
+                                options,
+                            )),
+                        ))),
+                        Err(err) => Some(Err(err)),
+                    })
+                    .collect::<anyhow::Result<Vec<(usize, T)>>>()?;
+            results.sort_unstable_by_key(|(index, _)| *index);
+            Ok(results.into_iter().map(|(_, result)| result).collect())
+        })
+    }
+
+    /// Returns the blob reader and the decoding settings, for pipelines reading the blobs on
+    /// their own.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn into_parts(self) -> (BlobReader<R>, DecodeErrorPolicy, DecodeOptions) {
+        (
+            self.blob_reader,
+            self.decode_error_policy,
+            self.decode_options,
+        )
+    }
+
+    /// Folds each data block into a value and reduces the values, processing the blobs in
+    /// parallel. Decoding only the elements needed from a block is up to `fold_fn`.
+    #[cfg(feature = "parallel")]
+    pub(crate) fn par_fold_blocks<T, M, F, I>(
+        self,
+        fold_fn: M,
+        reduce_fn: F,
+        identity: I,
+    ) -> anyhow::Result<T>
+    where
+        T: Send,
+        M: Fn(PrimitiveReader) -> anyhow::Result<T> + Send + Sync,
+        F: Fn(T, T) -> T + Send + Sync,
+        I: Fn() -> T + Send + Sync,
+    {
+        let thread_pool = self.thread_pool.clone();
+        install(thread_pool.as_deref(), || {
+            let policy = &self.decode_error_policy;
+            let options = self.decode_options;
+            ScheduledBlobs::new(self.blob_reader, self.scan_schedule, self.blob_costs)
+                .par_bridge()
+                .map(|(_, blob)| match blob.decode_with_options(&options)? {
+                    DecodedBlob::OsmHeader(_) => Ok(identity()),
+                    DecodedBlob::OsmData(block) => fold_fn(PrimitiveReader::with_options(
+                        block,
+                        policy.clone(),
+                        options,
+                    )),
+                })
+                .try_reduce(&identity, |a, b| Ok(reduce_fn(a, b)))
+        })
     }
 }
 
+/// Runs an operation on a thread pool, or on the current one if there is none.
+#[cfg(feature = "parallel")]
+fn install<T, F>(thread_pool: Option<&ThreadPool>, op: F) -> T
+where
+    T: Send,
+    F: FnOnce() -> T + Send,
+{
+    match thread_pool {
+        Some(thread_pool) => thread_pool.install(op),
+        None => op(),
+    }
+}
+
+/// Returns the elements of the given types of a block matching a predicate.
+#[cfg(feature = "parallel")]
+fn find_in_block<F>(
+    p: PrimitiveReader,
+    types: ElementTypeSet,
+    callback: &F,
+) -> anyhow::Result<Vec<Element>>
+where
+    F: Fn(&Element) -> bool,
+{
+    let mut elements = Vec::new();
+    p.for_each_element_of(types, |element| {
+        if callback(&element) {
+            elements.push(element);
+        }
+    })?;
+    Ok(elements)
+}
+
+fn decode_blob_data(
+    block: PrimitiveBlock,
+    offset: u64,
+    policy: &DecodeErrorPolicy,
+    options: DecodeOptions,
+) -> anyhow::Result<BlobData> {
+    let _span = trace_span!("decode_block", offset = offset);
+    let decorator = PrimitiveReader::with_options(block, policy.clone(), options);
+    let (nodes, ways, relations) = decorator.get_all_elements()?;
+    Ok(BlobData {
+        nodes,
+        ways,
+        relations,
+        offset,
+        granularity: decorator.granularity(),
+        date_granularity: decorator.date_granularity(),
+        lat_offset: decorator.lat_offset(),
+        lon_offset: decorator.lon_offset(),
+    })
+}
+
+/// Checks whether a block contains elements of the given type or of a later type.
+fn contains_types_from(block: &PrimitiveBlock, element_type: &ElementType) -> bool {
+    block.get_primitivegroup().iter().any(|group| {
+        let has_relations = !group.get_relations().is_empty();
+        let has_ways = !group.get_ways().is_empty();
+        let has_nodes = group.has_dense() || !group.get_nodes().is_empty();
+        match element_type {
+            ElementType::Node => has_nodes || has_ways || has_relations,
+            ElementType::Way => has_ways || has_relations,
+            ElementType::Relation => has_relations,
+        }
+    })
+}
+
+/// Iterates over the elements of a reader like its `Iterator`, but returns a read or decode
+/// error as the last item instead of panicking.
+fn try_elements<R: Read + Send>(
+    mut reader: IterableReader<R>,
+) -> impl Iterator<Item = anyhow::Result<Element>> {
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let next = reader.next_element().transpose();
+        failed = matches!(next, Some(Err(_)));
+        next
+    })
+}
+
+#[cfg(feature = "fs")]
 impl PbfReader<BufReader<File>> {
     /// Creates a new `PbfReader` instance with the specified file path.
     pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
@@ -245,20 +997,369 @@ impl PbfReader<BufReader<File>> {
         let reader = BufReader::new(f);
         Ok(Self::new(reader))
     }
+}
 
-    /// Rewinds the reader to the beginning of the file.
+impl<'a> PbfReader<Cursor<&'a [u8]>> {
+    /// Creates a new `PbfReader` instance reading PBF data held in memory, e.g. a file loaded
+    /// by a browser.
+    pub fn from_bytes(data: &'a [u8]) -> Self {
+        Self::new(Cursor::new(data))
+    }
+}
+
+impl<R: Read + Seek + Send> PbfReader<R> {
+    /// Rewinds the reader to the beginning of the data.
     pub fn rewind(&mut self) -> anyhow::Result<()> {
         self.blob_reader.rewind()
     }
+
+    /// Moves the reader to the blob starting at the given byte offset.
+    pub fn seek(&mut self, offset: u64) -> anyhow::Result<()> {
+        self.blob_reader.seek(offset)
+    }
 }
 
-impl PbfRandomRead for PbfReader<BufReader<File>> {
-    fn read_blob_by_offset(&mut self, offset: u64) -> anyhow::Result<Rc<BlobData>> {
+impl<R: Read + Seek + Send> PbfRandomRead for PbfReader<R> {
+    fn read_blob_by_offset(&mut self, offset: u64) -> anyhow::Result<Arc<BlobData>> {
         self.blob_reader.seek(offset)?;
         let data = self
-            .read_next_blob()
+            .read_next_blob()?
             .ok_or(anyhow!("no blob data found."))?;
-        Ok(Rc::new(data))
+        Ok(Arc::new(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_find_ordered() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let predicate = |element: &Element| match element {
+            Element::Node(node) => !node.tags.is_empty(),
+            _ => true,
+        };
+        let mut expected = Vec::new();
+        PbfReader::from_path(path)
+            .unwrap()
+            .read(|_, element| {
+                if let Some(element) = element.filter(predicate) {
+                    expected.push(element.get_meta());
+                }
+            })
+            .unwrap();
+
+        for _ in 0..2 {
+            let found: Vec<(ElementType, i64)> = PbfReader::from_path(path)
+                .unwrap()
+                .par_find_ordered(ElementTypeSet::ALL, predicate)
+                .unwrap()
+                .iter()
+                .map(|element| element.get_meta())
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_read_blocks() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let mut header_count = 0;
+        let mut node_count = 0;
+        PbfReader::from_path(path)
+            .unwrap()
+            .read_blocks(|block| match block {
+                DecodedBlob::OsmHeader(_) => header_count += 1,
+                DecodedBlob::OsmData(block) => {
+                    node_count += PrimitiveReader::new(block, DecodeErrorPolicy::Fail)
+                        .get_nodes()
+                        .unwrap()
+                        .len()
+                }
+            })
+            .unwrap();
+
+        let mut expected_node_count = 0;
+        PbfReader::from_path(path)
+            .unwrap()
+            .read(|_, element| {
+                if let Some(Element::Node(_)) = element {
+                    expected_node_count += 1;
+                }
+            })
+            .unwrap();
+        assert_eq!(header_count, 1);
+        assert_eq!(node_count, expected_node_count);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let data = std::fs::read(path).unwrap();
+        let mut reader = PbfReader::from_bytes(&data);
+        let first = reader.read_next_blob().unwrap().unwrap();
+        let second = reader.read_next_blob().unwrap().unwrap();
+        assert!(!second.nodes.is_empty());
+
+        // In-memory data can be read at random like a file
+        let blob = reader.read_blob_by_offset(second.offset).unwrap();
+        assert_eq!(blob.nodes.len(), second.nodes.len());
+        reader.rewind().unwrap();
+        assert_eq!(
+            reader.read_next_blob().unwrap().unwrap().offset,
+            first.offset
+        );
+
+        let mut count = 0;
+        PbfReader::from_bytes(&data)
+            .read(|_, element| count += usize::from(element.is_some()))
+            .unwrap();
+        let mut expected = 0;
+        PbfReader::from_path(path)
+            .unwrap()
+            .read(|_, element| expected += usize::from(element.is_some()))
+            .unwrap();
+        assert_eq!(count, expected);
+    }
+
+    #[test]
+    fn test_blob_handlers() {
+        use crate::proto::fileformat;
+        use protobuf::Message;
+        use std::sync::Mutex;
+
+        let mut data = std::fs::read("./resources/andorra-latest.osm.pbf").unwrap();
+        // Appends a blob of a vendor-specific type
+        let mut blob = fileformat::Blob::new();
+        blob.set_raw(b"features".to_vec());
+        let blob_bytes = blob.write_to_bytes().unwrap();
+        let mut header = fileformat::BlobHeader::new();
+        header.set_field_type("OSMFeatures".to_string());
+        header.set_datasize(blob_bytes.len() as i32);
+        let header_bytes = header.write_to_bytes().unwrap();
+        data.extend((header_bytes.len() as u32).to_be_bytes());
+        data.extend(header_bytes);
+        data.extend(blob_bytes);
+
+        assert!(PbfReader::new(data.as_slice()).read(|_, _| {}).is_err());
+
+        let features = Arc::new(Mutex::new(Vec::new()));
+        let mut reader = PbfReader::new(data.as_slice());
+        let handler_features = features.clone();
+        reader.register_blob_handler("OSMFeatures", move |data| {
+            handler_features.lock().unwrap().push(data.to_vec());
+            Ok(())
+        });
+        reader.read(|_, _| {}).unwrap();
+        assert_eq!(*features.lock().unwrap(), vec![b"features".to_vec()]);
+    }
+
+    #[test]
+    fn test_default_decode_error_policy() {
+        use crate::codecs::block_builder::{NodeEncoding, PrimitiveBuilder};
+        use crate::models::{Node, Tag};
+        use crate::proto::fileformat;
+        use protobuf::Message;
+
+        // Two nodes tagged `a=b` and `c=d`, whose value `d` points out of the string table
+        let nodes = [(1, "a", "b"), (2, "c", "d")].map(|(id, key, value)| {
+            Element::Node(Node {
+                id,
+                tags: vec![Tag {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                }],
+                ..Default::default()
+            })
+        });
+        let mut block = PrimitiveBuilder::new()
+            .build(nodes.to_vec(), NodeEncoding::Dense)
+            .unwrap();
+        block.mut_primitivegroup()[0].mut_dense().mut_keys_vals()[4] = 999;
+        let mut blob = fileformat::Blob::new();
+        blob.set_raw(block.write_to_bytes().unwrap());
+        let blob_bytes = blob.write_to_bytes().unwrap();
+        let mut header = fileformat::BlobHeader::new();
+        header.set_field_type("OSMData".to_string());
+        header.set_datasize(blob_bytes.len() as i32);
+        let header_bytes = header.write_to_bytes().unwrap();
+        let mut data = (header_bytes.len() as u32).to_be_bytes().to_vec();
+        data.extend(header_bytes);
+        data.extend(blob_bytes);
+
+        // The default is to fail, while the readers used to substitute an empty string
+        assert!(PbfReader::new(data.as_slice()).read(|_, _| {}).is_err());
+        let mut reader = PbfReader::new(data.as_slice());
+        reader.set_decode_error_policy(DecodeErrorPolicy::Substitute(String::new()));
+        let mut values = Vec::new();
+        reader
+            .read(|_, element| {
+                if let Some(Element::Node(node)) = element {
+                    values.push(node.tags[0].value.clone());
+                }
+            })
+            .unwrap();
+        assert_eq!(values, vec!["b", ""]);
+    }
+
+    #[test]
+    fn test_find_all_by_tags() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let primary = PbfReader::from_path(path)
+            .unwrap()
+            .find_all_by_tag("highway", "primary")
+            .count();
+        let secondary = PbfReader::from_path(path)
+            .unwrap()
+            .find_all_by_tag("highway", "secondary")
+            .count();
+        assert!(primary > 0 && secondary > 0);
+
+        let tags = vec![("highway", "primary"), ("highway", "secondary")];
+        let any = PbfReader::from_path(path)
+            .unwrap()
+            .find_all_by_tags(tags.clone(), TagMatch::Any)
+            .count();
+        assert_eq!(any, primary + secondary);
+        let all = PbfReader::from_path(path)
+            .unwrap()
+            .find_all_by_tags(tags, TagMatch::All)
+            .count();
+        assert_eq!(all, 0);
+
+        let relation = PbfReader::from_path(path)
+            .unwrap()
+            .find_all_by_tag("type", "route")
+            .last()
+            .unwrap()
+            .unwrap();
+        let (element_type, id) = relation.get_meta();
+        let found: Vec<(ElementType, i64)> = PbfReader::from_path(path)
+            .unwrap()
+            .find_all_by_id(element_type.clone(), id)
+            .map(|element| element.unwrap().get_meta())
+            .collect();
+        assert_eq!(found, vec![(element_type, id)]);
+
+        // A truncated file ends the iteration with an error instead of panicking
+        let data = std::fs::read(path).unwrap();
+        let truncated = &data[..data.len() / 2];
+        let results: Vec<_> = PbfReader::from_bytes(truncated)
+            .find_all_by_tag("highway", "primary")
+            .collect();
+        assert!(results.last().unwrap().is_err());
+        assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+        let last = PbfReader::from_bytes(truncated)
+            .find_all_by_id(ElementType::Relation, id)
+            .last()
+            .unwrap();
+        assert!(last.is_err());
+    }
+
+    #[test]
+    fn test_for_each_blob_parallel() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let first_ids = PbfReader::from_path(path)
+            .unwrap()
+            .for_each_blob_parallel(|block| {
+                let (nodes, ways, relations) = block.get_all_elements().unwrap();
+                let first = nodes.first().map(|node| node.id);
+                let first = first.or_else(|| ways.first().map(|way| way.id));
+                first.or_else(|| relations.first().map(|relation| relation.id))
+            })
+            .unwrap();
+
+        let mut expected = Vec::new();
+        let mut reader = PbfReader::from_path(path).unwrap();
+        while let Some(blob) = reader.read_next_blob().unwrap() {
+            if blob.nodes.is_empty() && blob.ways.is_empty() && blob.relations.is_empty() {
+                // The header blob
+                continue;
+            }
+            let first = blob.nodes.first().map(|node| node.id);
+            let first = first.or_else(|| blob.ways.first().map(|way| way.id));
+            expected.push(first.or_else(|| blob.relations.first().map(|relation| relation.id)));
+        }
+        assert_eq!(first_ids, expected);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_thread_pool() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let mut reader = PbfReader::from_path(path).unwrap();
+        reader.set_num_threads(3).unwrap();
+        let pool_sizes = reader
+            .for_each_blob_parallel(|_| {
+                assert!(rayon::current_thread_index().is_some());
+                rayon::current_num_threads()
+            })
+            .unwrap();
+        assert!(!pool_sizes.is_empty());
+        assert!(pool_sizes.iter().all(|size| *size == 3));
+
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let mut reader = PbfReader::from_path(path).unwrap();
+        reader.set_thread_pool(pool);
+        let ways = reader
+            .par_find_ordered(ElementTypeSet::WAYS, |_| rayon::current_num_threads() == 1)
+            .unwrap();
+        let expected = PbfReader::from_path(path)
+            .unwrap()
+            .par_find_ordered(ElementTypeSet::WAYS, |_| true)
+            .unwrap();
+        let ids = |elements: Vec<Element>| -> Vec<(ElementType, i64)> {
+            elements.iter().map(|element| element.get_meta()).collect()
+        };
+        assert_eq!(ids(ways), ids(expected));
+    }
+
+    #[test]
+    fn test_par_map_reduce() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let reader = PbfReader::from_path(path).unwrap();
+        let counts = reader
+            .par_map_reduce(
+                |element| match element {
+                    Element::Node(_) => [1, 0, 0],
+                    Element::Way(_) => [0, 1, 0],
+                    Element::Relation(_) => [0, 0, 1],
+                },
+                |a, b| [a[0] + b[0], a[1] + b[1], a[2] + b[2]],
+                || [0u64; 3],
+            )
+            .unwrap();
+
+        let mut expected = [0u64; 3];
+        PbfReader::from_path(path)
+            .unwrap()
+            .read(|_, element| match element {
+                Some(Element::Node(_)) => expected[0] += 1,
+                Some(Element::Way(_)) => expected[1] += 1,
+                Some(Element::Relation(_)) => expected[2] += 1,
+                None => {}
+            })
+            .unwrap();
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_blob_precision() {
+        let mut reader = PbfReader::from_path("./resources/andorra-latest.osm.pbf").unwrap();
+        // The first blob is the header
+        reader.read_next_blob().unwrap();
+        let blob = reader.read_next_blob().unwrap().unwrap();
+        assert_eq!(blob.granularity, 100);
+        assert_eq!(blob.date_granularity, 1000);
+        assert_eq!((blob.lat_offset, blob.lon_offset), (0, 0));
+        for node in &blob.nodes {
+            let raw_latitude = (node.latitude - blob.lat_offset) / blob.granularity as i64;
+            assert_eq!(
+                blob.lat_offset + raw_latitude * blob.granularity as i64,
+                node.latitude
+            );
+        }
     }
 }
 
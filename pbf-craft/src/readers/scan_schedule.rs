@@ -0,0 +1,182 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+
+use crate::codecs::blob::{BlobReader, RawBlob};
+
+/// The relative costs of decoding an element of each type. Measured on extracts, a way takes
+/// about eight times as long as a node and a relation about thirty times.
+const NODE_COST: u64 = 1;
+const WAY_COST: u64 = 8;
+const RELATION_COST: u64 = 32;
+
+/// How the parallel scans of `PbfReader` hand the blobs to the worker threads, see
+/// `PbfReader::set_scan_schedule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScanSchedule {
+    /// Hands out the blobs in the order of the file as they are read. The slow blobs of ways
+    /// and relations come last in sorted files, so one worker may still decode them while the
+    /// others are idle.
+    #[default]
+    FileOrder,
+    /// Reads `window` blobs ahead and hands out the most expensive of them first, so that slow
+    /// blobs start early and the cheap ones fill the gaps at the end. The blobs of a window are
+    /// held in memory until they are handed out.
+    LargestFirst { window: usize },
+}
+
+/// Returns the estimated cost of decoding a blob with the given element counts.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) fn estimated_cost(nodes: u64, ways: u64, relations: u64) -> u64 {
+    nodes * NODE_COST + ways * WAY_COST + relations * RELATION_COST
+}
+
+/// The blobs of a reader with their index in the file, in the order of a `ScanSchedule`.
+///
+/// The cost of a blob is taken from `costs` by its offset, or estimated from its compressed
+/// size if there are no costs.
+#[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+pub(crate) struct ScheduledBlobs<R: Read + Send> {
+    blob_reader: BlobReader<R>,
+    schedule: ScanSchedule,
+    costs: HashMap<u64, u64>,
+    next_index: usize,
+    pending: VecDeque<(usize, RawBlob)>,
+}
+
+#[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+impl<R: Read + Send> ScheduledBlobs<R> {
+    pub(crate) fn new(
+        blob_reader: BlobReader<R>,
+        schedule: ScanSchedule,
+        costs: HashMap<u64, u64>,
+    ) -> Self {
+        Self {
+            blob_reader,
+            schedule,
+            costs,
+            next_index: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn fill_window(&mut self) {
+        let window = match self.schedule {
+            ScanSchedule::FileOrder => 1,
+            ScanSchedule::LargestFirst { window } => window.max(1),
+        };
+        let mut blobs = Vec::with_capacity(window);
+        while blobs.len() < window {
+            let offset = self.blob_reader.offset;
+            let Some(blob) = self.blob_reader.next() else {
+                break;
+            };
+            let cost = if self.costs.is_empty() {
+                blob.data().len() as u64
+            } else {
+                self.costs.get(&offset).copied().unwrap_or(0)
+            };
+            blobs.push((cost, self.next_index, blob));
+            self.next_index += 1;
+        }
+        // The sort is stable, so blobs of the same cost keep the order of the file
+        blobs.sort_by_key(|(cost, _, _)| Reverse(*cost));
+        self.pending
+            .extend(blobs.into_iter().map(|(_, index, blob)| (index, blob)));
+    }
+}
+
+impl<R: Read + Send> Iterator for ScheduledBlobs<R> {
+    type Item = (usize, RawBlob);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_empty() {
+            self.fill_window();
+        }
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufReader;
+
+    use super::*;
+    use crate::readers::PbfIndex;
+    #[cfg(feature = "parallel")]
+    use crate::readers::PbfReader;
+
+    const PATH: &str = "./resources/andorra-latest.osm.pbf";
+
+    fn blob_reader() -> BlobReader<BufReader<File>> {
+        BlobReader::new(BufReader::new(File::open(PATH).unwrap()))
+    }
+
+    #[test]
+    fn test_largest_first() {
+        let in_file_order: Vec<(usize, usize)> =
+            ScheduledBlobs::new(blob_reader(), ScanSchedule::FileOrder, HashMap::new())
+                .map(|(index, blob)| (index, blob.data().len()))
+                .collect();
+        assert!(in_file_order
+            .iter()
+            .enumerate()
+            .all(|(i, (index, _))| i == *index));
+
+        // Within each window, the largest blobs come first
+        let largest_first: Vec<(usize, usize)> = ScheduledBlobs::new(
+            blob_reader(),
+            ScanSchedule::LargestFirst { window: 16 },
+            HashMap::new(),
+        )
+        .map(|(index, blob)| (index, blob.data().len()))
+        .collect();
+        for (window, expected) in largest_first.chunks(16).zip(in_file_order.chunks(16)) {
+            assert!(window.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+            let mut indexes: Vec<usize> = window.iter().map(|(index, _)| *index).collect();
+            indexes.sort_unstable();
+            assert_eq!(
+                indexes,
+                expected.iter().map(|(index, _)| *index).collect::<Vec<_>>()
+            );
+        }
+
+        // With the element counts of the index, the way blobs outweigh the node blobs
+        let summary = PbfIndex::new(PATH).unwrap().summary();
+        let costs = summary
+            .blobs
+            .iter()
+            .map(|blob| {
+                let cost = estimated_cost(blob.nodes, blob.ways, blob.relations);
+                (blob.offset, cost)
+            })
+            .collect();
+        let (first, _) = ScheduledBlobs::new(
+            blob_reader(),
+            ScanSchedule::LargestFirst { window: 1000 },
+            costs,
+        )
+        .next()
+        .unwrap();
+        let ways_blob = summary.blobs.iter().position(|blob| blob.ways == 8000);
+        // The index counts the header blob, which has no entry in the summary
+        assert_eq!(Some(first - 1), ways_blob);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_scheduled_scan() {
+        let count_ways = |schedule: ScanSchedule| {
+            let mut reader = PbfReader::from_path(PATH).unwrap();
+            reader.set_scan_schedule(schedule);
+            reader
+                .for_each_blob_parallel(|block| block.get_ways().unwrap().len())
+                .unwrap()
+        };
+        assert_eq!(
+            count_ways(ScanSchedule::LargestFirst { window: 4 }),
+            count_ways(ScanSchedule::FileOrder)
+        );
+    }
+}
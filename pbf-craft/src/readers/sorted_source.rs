@@ -0,0 +1,126 @@
+use super::traits::{ElementSource, Provenance};
+use crate::models::{Element, ElementType};
+
+/// An `ElementSource` adapter which verifies that elements come in the canonical order.
+///
+/// The canonical order is all nodes, then all ways, then all relations, and within each type
+/// the IDs are strictly ascending. Utilities such as diff and merge rely on this order and
+/// silently produce wrong results when it is violated. Wrapping their inputs in a
+/// `SortedSource` turns such a violation into an error naming the offending pair.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node};
+/// use pbf_craft::readers::{ElementSource, SortedSource};
+///
+/// let elements = vec![
+///     Element::Node(Node { id: 2, ..Default::default() }),
+///     Element::Node(Node { id: 1, ..Default::default() }),
+/// ];
+/// let mut source = SortedSource::new(elements.into_iter());
+/// assert!(source.next_element().is_ok());
+/// assert!(source.next_element().is_err());
+/// ```
+pub struct SortedSource<T: ElementSource> {
+    source: T,
+    previous: Option<(ElementType, i64)>,
+}
+
+impl<T: ElementSource> SortedSource<T> {
+    /// Creates a new `SortedSource` wrapping the given source.
+    pub fn new(source: T) -> Self {
+        Self {
+            source,
+            previous: None,
+        }
+    }
+
+    /// Consumes the adapter and returns the wrapped source.
+    pub fn into_inner(self) -> T {
+        self.source
+    }
+}
+
+impl<T: ElementSource> ElementSource for SortedSource<T> {
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        let element = match self.source.next_element()? {
+            Some(element) => element,
+            None => return Ok(None),
+        };
+        let current = element.get_meta();
+        if let Some(previous) = &self.previous {
+            if *previous >= current {
+                bail!(
+                    "Elements are not in canonical order: {:?}#{} is followed by {:?}#{}",
+                    previous.0,
+                    previous.1,
+                    current.0,
+                    current.1
+                );
+            }
+        }
+        self.previous = Some(current);
+        Ok(Some(element))
+    }
+
+    fn provenance(&self) -> Option<Provenance> {
+        self.source.provenance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Way};
+    use crate::readers::IterableReader;
+
+    #[test]
+    fn test_sorted_file() {
+        let reader = IterableReader::from_path("./resources/andorra-latest.osm.pbf").unwrap();
+        let mut source = SortedSource::new(reader);
+        let mut count = 0;
+        while source.next_element().unwrap().is_some() {
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_unsorted_types() {
+        let elements = vec![
+            Element::Way(Way {
+                id: 1,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 2,
+                ..Default::default()
+            }),
+        ];
+        let mut source = SortedSource::new(elements.into_iter());
+        assert!(source.next_element().unwrap().is_some());
+        let err = source.next_element().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Elements are not in canonical order: Way#1 is followed by Node#2"
+        );
+    }
+
+    #[test]
+    fn test_duplicated_ids() {
+        let elements = vec![
+            Element::Node(Node {
+                id: 1,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 1,
+                ..Default::default()
+            }),
+        ];
+        let mut source = SortedSource::new(elements.into_iter());
+        assert!(source.next_element().is_ok());
+        assert!(source.next_element().is_err());
+    }
+}
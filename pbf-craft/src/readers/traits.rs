@@ -1,14 +1,243 @@
-use std::rc::Rc;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
 
-use crate::models::{Node, Relation, Way};
+use crate::models::{Element, ElementType, Node, Relation, Way};
 
+/// The elements of a blob together with the precision they were stored with.
+///
+/// The coordinates of the elements are given in nanodegrees. The fixed-point value stored in
+/// the file is `(latitude - lat_offset) / granularity`, and likewise for longitudes.
 pub struct BlobData {
     pub nodes: Vec<Node>,
     pub ways: Vec<Way>,
     pub relations: Vec<Relation>,
+    /// The offset of the blob in the file.
     pub offset: u64,
+    /// The granularity of the coordinates in nanodegrees.
+    pub granularity: i32,
+    /// The granularity of the timestamps in milliseconds.
+    pub date_granularity: i32,
+    /// The offset of the latitudes in nanodegrees.
+    pub lat_offset: i64,
+    /// The offset of the longitudes in nanodegrees.
+    pub lon_offset: i64,
 }
 
+/// A reader which can read the blob at a given offset of a PBF file.
+///
+/// The blob is shared behind an `Arc`, so caches can hand out the same decoded blob to many
+/// lookups, possibly on other threads, without copying its elements.
 pub trait PbfRandomRead {
-    fn read_blob_by_offset(&mut self, offset: u64) -> anyhow::Result<Rc<BlobData>>;
+    fn read_blob_by_offset(&mut self, offset: u64) -> anyhow::Result<Arc<BlobData>>;
+
+    /// Hints that the blob at an offset will be read soon. Readers which can decode it ahead,
+    /// like `CachedReader`, start doing so; the others ignore the hint.
+    fn prefetch(&mut self, _offset: u64) {}
+}
+
+/// An element type stored in a `BlobData`.
+pub trait BlobElement: Sized {
+    const ELEMENT_TYPE: ElementType;
+
+    /// The elements of this type in the blob.
+    fn elements_of(blob: &BlobData) -> &[Self];
+
+    fn element_id(&self) -> i64;
+}
+
+impl BlobElement for Node {
+    const ELEMENT_TYPE: ElementType = ElementType::Node;
+
+    fn elements_of(blob: &BlobData) -> &[Self] {
+        &blob.nodes
+    }
+
+    fn element_id(&self) -> i64 {
+        self.id
+    }
+}
+
+impl BlobElement for Way {
+    const ELEMENT_TYPE: ElementType = ElementType::Way;
+
+    fn elements_of(blob: &BlobData) -> &[Self] {
+        &blob.ways
+    }
+
+    fn element_id(&self) -> i64 {
+        self.id
+    }
+}
+
+impl BlobElement for Relation {
+    const ELEMENT_TYPE: ElementType = ElementType::Relation;
+
+    fn elements_of(blob: &BlobData) -> &[Self] {
+        &blob.relations
+    }
+
+    fn element_id(&self) -> i64 {
+        self.id
+    }
+}
+
+/// A handle to an element inside a shared `BlobData`.
+///
+/// The handle keeps the blob alive and dereferences to the element, so looking an element up
+/// costs a reference count increment instead of a deep copy of its tags and members. Use
+/// `to_owned_element` when an owned copy is needed, e.g. to modify it.
+pub struct ElementRef<T: BlobElement> {
+    blob: Arc<BlobData>,
+    index: usize,
+    _element: PhantomData<fn() -> T>,
+}
+
+impl<T: BlobElement> ElementRef<T> {
+    /// Finds the element with the given ID in the blob.
+    ///
+    /// Blobs are normally sorted by ID, so a binary search is tried first; unsorted blobs fall
+    /// back to a linear scan.
+    pub fn find(blob: Arc<BlobData>, id: i64) -> Option<Self> {
+        let elements = T::elements_of(&blob);
+        let index = match elements.binary_search_by_key(&id, T::element_id) {
+            Ok(index) => Some(index),
+            Err(_) => elements
+                .iter()
+                .position(|element| element.element_id() == id),
+        }?;
+        Some(Self::new(blob, index))
+    }
+
+    /// Returns handles to all elements of the blob matching the predicate.
+    pub fn filter<P: FnMut(&T) -> bool>(blob: Arc<BlobData>, mut predicate: P) -> Vec<Self> {
+        T::elements_of(&blob)
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| predicate(element))
+            .map(|(index, _)| Self::new(blob.clone(), index))
+            .collect()
+    }
+
+    fn new(blob: Arc<BlobData>, index: usize) -> Self {
+        Self {
+            blob,
+            index,
+            _element: PhantomData,
+        }
+    }
+
+    /// The blob containing the element.
+    pub fn blob(&self) -> &Arc<BlobData> {
+        &self.blob
+    }
+
+    /// Copies the element out of the blob.
+    pub fn to_owned_element(&self) -> T
+    where
+        T: Clone,
+    {
+        (**self).clone()
+    }
+}
+
+impl<T: BlobElement> Deref for ElementRef<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &T::elements_of(&self.blob)[self.index]
+    }
+}
+
+impl<T: BlobElement> Clone for ElementRef<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.blob.clone(), self.index)
+    }
+}
+
+impl<T: BlobElement + fmt::Debug> fmt::Debug for ElementRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// Where an element was read from, to trace an output element back to its input.
+///
+/// Sources which know it return it from `ElementSource::provenance`, and pipelines pass it on
+/// with `ElementSink::write_with_provenance` through transforms to the sinks which record it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// The name of the source, usually the path of the file read.
+    pub source: Arc<str>,
+    /// The byte offset of the blob containing the element.
+    pub blob_offset: u64,
+    /// The size of the blob in bytes, including its header.
+    pub blob_size: u64,
+    /// The index of the element in the source, counting all elements read from the start.
+    pub index: u64,
+}
+
+/// A source from which elements can be read one by one.
+///
+/// `ElementSource` abstracts over input formats so that merge, diff and filter utilities can
+/// accept any kind of input, e.g. a PBF file or elements already held in memory.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node};
+/// use pbf_craft::readers::{ElementSource, IterableReader};
+///
+/// fn count<S: ElementSource>(mut source: S) -> anyhow::Result<usize> {
+///     let mut count = 0;
+///     while let Some(_) = source.next_element()? {
+///         count += 1;
+///     }
+///     Ok(count)
+/// }
+///
+/// let reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// assert!(count(reader).unwrap() > 0);
+///
+/// let elements = vec![Element::Node(Node::default())];
+/// assert_eq!(count(elements.into_iter()).unwrap(), 1);
+/// ```
+pub trait ElementSource {
+    /// Reads the next element from the source.
+    ///
+    /// Returns `Ok(None)` when the source is exhausted.
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>>;
+
+    /// Returns the provenance of the element last returned by `next_element`, if the source
+    /// tracks it. Sources don't track it by default.
+    fn provenance(&self) -> Option<Provenance> {
+        None
+    }
+}
+
+impl ElementSource for std::vec::IntoIter<Element> {
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        Ok(self.next())
+    }
+}
+
+impl<S: ElementSource + ?Sized> ElementSource for Box<S> {
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        (**self).next_element()
+    }
+
+    fn provenance(&self) -> Option<Provenance> {
+        (**self).provenance()
+    }
+}
+
+impl<S: ElementSource + ?Sized> ElementSource for &mut S {
+    fn next_element(&mut self) -> anyhow::Result<Option<Element>> {
+        (**self).next_element()
+    }
+
+    fn provenance(&self) -> Option<Provenance> {
+        (**self).provenance()
+    }
 }
@@ -0,0 +1,9 @@
+#[cfg(feature = "fs")]
+mod nearest;
+mod regions;
+mod rtree_index;
+
+#[cfg(feature = "fs")]
+pub use nearest::NearestFinder;
+pub use regions::{RegionIndex, RegionTaggingSink};
+pub use rtree_index::RTreeIndex;
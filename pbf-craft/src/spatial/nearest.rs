@@ -0,0 +1,191 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use geo::{Closest, Coord, HaversineClosestPoint, HaversineDistance, Line, Point};
+
+use super::RTreeIndex;
+use crate::models::{Node, Way};
+use crate::readers::{CachedReader, IndexedReader, PbfRandomRead};
+
+/// The mean radius of the earth in meters, as used by the haversine distance of `geo`.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Finds the elements nearest to a location by their haversine distance, e.g. for map-matching
+/// experiments run directly against a PBF file.
+///
+/// The candidates come from an `RTreeIndex` and are loaded through an `IndexedReader`, so the
+/// filters can look at the tags of the elements. The candidates are checked by ascending planar
+/// distance until none of the remaining ones can be nearer than the nearest matching one.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::spatial::NearestFinder;
+///
+/// let mut finder = NearestFinder::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let cafe = finder
+///     .find_nearest_node(42.5063, 1.5218, |node| {
+///         node.tags.iter().any(|tag| tag.key == "amenity" && tag.value == "cafe")
+///     })
+///     .unwrap();
+/// if let Some((node, distance)) = cafe {
+///     println!("The nearest cafe is node {}, {:.0} m away", node.id, distance);
+/// }
+/// let road = finder
+///     .find_nearest_way(42.5063, 1.5218, |way| way.tags.iter().any(|tag| tag.key == "highway"))
+///     .unwrap();
+/// ```
+pub struct NearestFinder<T: PbfRandomRead> {
+    index: RTreeIndex,
+    reader: IndexedReader<T>,
+}
+
+impl NearestFinder<CachedReader> {
+    /// Builds an `RTreeIndex` of all nodes and ways of a PBF file, and opens it with an
+    /// `IndexedReader` caching 1000 blobs.
+    pub fn from_path(pbf_file: &str) -> anyhow::Result<Self> {
+        let index = RTreeIndex::from_path(pbf_file)?;
+        let reader = IndexedReader::from_path_with_cache(pbf_file, 1000)?;
+        Ok(Self::new(index, reader))
+    }
+}
+
+impl<T: PbfRandomRead> NearestFinder<T> {
+    /// Creates a finder from an index and a reader of the same data, e.g. an index of a subset
+    /// of the elements loaded with `RTreeIndex::load`.
+    pub fn new(index: RTreeIndex, reader: IndexedReader<T>) -> Self {
+        Self { index, reader }
+    }
+
+    /// Returns the nearest node matching a filter, with its distance in meters.
+    pub fn find_nearest_node<F: Fn(&Node) -> bool>(
+        &mut self,
+        latitude: f64,
+        longitude: f64,
+        filter: F,
+    ) -> anyhow::Result<Option<(Node, f64)>> {
+        let point = Point::new(longitude, latitude);
+        let mut nearest: Option<(Node, f64)> = None;
+        for (node_id, location, degrees) in self.index.node_candidates(point.into()) {
+            let best = nearest.as_ref().map(|(_, distance)| *distance);
+            if best.is_some_and(|best| min_distance(latitude, degrees) > best) {
+                break;
+            }
+            let distance = point.haversine_distance(&Point::from(location));
+            if best.is_some_and(|best| distance >= best) {
+                continue;
+            }
+            if let Some(node) = self.reader.find_node(node_id)? {
+                if filter(&node) {
+                    nearest = Some((node, distance));
+                }
+            }
+        }
+        Ok(nearest)
+    }
+
+    /// Returns the nearest way matching a filter, with its distance in meters to the nearest
+    /// point of its segments.
+    pub fn find_nearest_way<F: Fn(&Way) -> bool>(
+        &mut self,
+        latitude: f64,
+        longitude: f64,
+        filter: F,
+    ) -> anyhow::Result<Option<(Way, f64)>> {
+        let point = Point::new(longitude, latitude);
+        // The ways checked so far, `None` for those not matching the filter
+        let mut ways: HashMap<i64, Option<Way>> = HashMap::new();
+        let mut nearest: Option<(i64, f64)> = None;
+        for (way_id, segment, degrees) in self.index.segment_candidates(point.into()) {
+            let best = nearest.map(|(_, distance)| distance);
+            if best.is_some_and(|best| min_distance(latitude, degrees) > best) {
+                break;
+            }
+            let line = Line::new(Coord::from(segment.from), Coord::from(segment.to));
+            let distance = match line.haversine_closest_point(&point) {
+                Closest::Intersection(closest) | Closest::SinglePoint(closest) => {
+                    point.haversine_distance(&closest)
+                }
+                Closest::Indeterminate => continue,
+            };
+            if best.is_some_and(|best| distance >= best) {
+                continue;
+            }
+            let way = match ways.entry(way_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(self.reader.find_way(way_id)?.filter(|way| filter(way)))
+                }
+            };
+            if way.is_some() {
+                nearest = Some((way_id, distance));
+            }
+        }
+        Ok(nearest.and_then(|(way_id, distance)| {
+            let way = ways.remove(&way_id)??;
+            Some((way, distance))
+        }))
+    }
+}
+
+/// Returns a lower bound of the distance in meters to the locations within a planar distance in
+/// degrees from a latitude, as a degree of longitude shrinks towards the poles.
+fn min_distance(latitude: f64, degrees: f64) -> f64 {
+    let max_latitude = (latitude.abs() + degrees).min(90.0);
+    degrees.to_radians() * EARTH_RADIUS * max_latitude.to_radians().cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Element, Tag};
+    use crate::readers::IterableReader;
+
+    #[test]
+    fn test_find_nearest() {
+        let pbf_file = "resources/andorra-latest.osm.pbf";
+        let (latitude, longitude) = (42.5063, 1.5218);
+        let point = Point::new(longitude, latitude);
+        let is_cafe = |tags: &[Tag]| {
+            tags.iter()
+                .any(|tag| tag.key == "amenity" && tag.value == "cafe")
+        };
+
+        // The nearest cafe by brute force
+        let mut expected: Option<(i64, f64)> = None;
+        for element in IterableReader::from_path(pbf_file).unwrap() {
+            if let Element::Node(node) = element {
+                if is_cafe(&node.tags) {
+                    let location =
+                        Point::new(node.longitude as f64 / 1e9, node.latitude as f64 / 1e9);
+                    let distance = point.haversine_distance(&location);
+                    if expected.is_none_or(|(_, best)| distance < best) {
+                        expected = Some((node.id, distance));
+                    }
+                }
+            }
+        }
+
+        let mut finder = NearestFinder::from_path(pbf_file).unwrap();
+        let (node, distance) = finder
+            .find_nearest_node(latitude, longitude, |node| is_cafe(&node.tags))
+            .unwrap()
+            .unwrap();
+        let (expected_id, expected_distance) = expected.unwrap();
+        assert_eq!(node.id, expected_id);
+        assert!((distance - expected_distance).abs() < 1e-6);
+
+        let (way, distance) = finder
+            .find_nearest_way(latitude, longitude, |way| {
+                way.tags.iter().any(|tag| tag.key == "highway")
+            })
+            .unwrap()
+            .unwrap();
+        assert!(way.tags.iter().any(|tag| tag.key == "highway"));
+        assert!(distance < 100.0);
+        assert!(finder
+            .find_nearest_way(latitude, longitude, |_| false)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,356 @@
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use geo::{
+    Area, BoundingRect, Centroid, Contains, Coord, LineString, MultiPolygon, Point, Polygon,
+};
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::RTree;
+use serde_json::Value;
+
+use crate::models::{Element, Tag};
+use crate::utils::LocationIndex;
+use crate::writers::ElementSink;
+
+/// A named polygon of a `RegionIndex`.
+struct Region {
+    name: String,
+    polygon: MultiPolygon<f64>,
+    area: f64,
+}
+
+/// An index of named polygons, e.g. administrative regions, looking up the region a location
+/// falls in.
+///
+/// The bounding boxes of the polygons are kept in an R-tree, so a lookup only tests the few
+/// polygons whose box contains the location. Where regions overlap, the smallest one containing
+/// the location is returned, so that nested regions take precedence over their parents.
+///
+/// # Example
+///
+/// ```rust
+/// use geo::Coord;
+/// use pbf_craft::spatial::RegionIndex;
+///
+/// let geojson = r#"{"type": "FeatureCollection", "features": [{
+///     "type": "Feature",
+///     "properties": {"code": "AD-07"},
+///     "geometry": {"type": "Polygon", "coordinates": [[[1.4, 42.4], [1.6, 42.4], [1.6, 42.6], [1.4, 42.4]]]}
+/// }]}"#;
+/// let regions = RegionIndex::from_geojson(geojson, "code").unwrap();
+/// assert_eq!(regions.region_at(Coord { x: 1.55, y: 42.45 }), Some("AD-07"));
+/// ```
+pub struct RegionIndex {
+    regions: Vec<Region>,
+    tree: RTree<GeomWithData<Rectangle<[f64; 2]>, usize>>,
+}
+
+impl RegionIndex {
+    /// Creates an index of polygons in degrees, each with the name of its region.
+    pub fn new<I: IntoIterator<Item = (String, MultiPolygon<f64>)>>(regions: I) -> Self {
+        let regions: Vec<Region> = regions
+            .into_iter()
+            .map(|(name, polygon)| Region {
+                name,
+                area: polygon.unsigned_area(),
+                polygon,
+            })
+            .collect();
+        let boxes = regions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, region)| {
+                let rect = region.polygon.bounding_rect()?;
+                let rectangle = Rectangle::from_corners(rect.min().into(), rect.max().into());
+                Some(GeomWithData::new(rectangle, index))
+            })
+            .collect();
+        Self {
+            regions,
+            tree: RTree::bulk_load(boxes),
+        }
+    }
+
+    /// Creates an index of the `Polygon` and `MultiPolygon` features of a GeoJSON
+    /// `FeatureCollection`, named by the value of one of their properties.
+    pub fn from_geojson(geojson: &str, property: &str) -> anyhow::Result<Self> {
+        let value: Value = serde_json::from_str(geojson)?;
+        let features = match value["type"].as_str() {
+            Some("FeatureCollection") => value["features"]
+                .as_array()
+                .ok_or_else(|| anyhow!("The feature collection has no features"))?
+                .iter()
+                .collect(),
+            Some("Feature") => vec![&value],
+            _ => bail!("Expected a GeoJSON FeatureCollection or Feature"),
+        };
+        let mut regions = Vec::with_capacity(features.len());
+        for (index, feature) in features.into_iter().enumerate() {
+            let name = match &feature["properties"][property] {
+                Value::String(name) => name.clone(),
+                Value::Number(number) => number.to_string(),
+                _ => bail!("Feature {} has no property {}", index, property),
+            };
+            let geometry = &feature["geometry"];
+            let polygon = match geometry["type"].as_str() {
+                Some("Polygon") => {
+                    MultiPolygon::new(vec![parse_polygon(&geometry["coordinates"])?])
+                }
+                Some("MultiPolygon") => MultiPolygon::new(
+                    geometry["coordinates"]
+                        .as_array()
+                        .ok_or_else(|| anyhow!("Invalid coordinates of feature {}", index))?
+                        .iter()
+                        .map(parse_polygon)
+                        .collect::<anyhow::Result<_>>()?,
+                ),
+                _ => bail!("Feature {} is not a Polygon or MultiPolygon", index),
+            };
+            regions.push((name, polygon));
+        }
+        Ok(Self::new(regions))
+    }
+
+    /// Creates an index of the polygons of a GeoJSON file, see `from_geojson`.
+    #[cfg(feature = "fs")]
+    pub fn from_geojson_path<P: AsRef<Path>>(path: P, property: &str) -> anyhow::Result<Self> {
+        Self::from_geojson(&std::fs::read_to_string(path)?, property)
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Returns the name of the smallest region containing a location in degrees.
+    pub fn region_at(&self, coord: Coord<f64>) -> Option<&str> {
+        let point = Point::from(coord);
+        self.tree
+            .locate_all_at_point(&[coord.x, coord.y])
+            .map(|entry| &self.regions[entry.data])
+            .filter(|region| region.polygon.contains(&point))
+            .min_by(|a, b| a.area.total_cmp(&b.area))
+            .map(|region| region.name.as_str())
+    }
+}
+
+fn parse_polygon(coordinates: &Value) -> anyhow::Result<Polygon<f64>> {
+    let mut rings = coordinates
+        .as_array()
+        .ok_or_else(|| anyhow!("Invalid polygon coordinates"))?
+        .iter()
+        .map(parse_ring)
+        .collect::<anyhow::Result<Vec<LineString<f64>>>>()?
+        .into_iter();
+    let exterior = rings
+        .next()
+        .ok_or_else(|| anyhow!("A polygon needs an exterior ring"))?;
+    Ok(Polygon::new(exterior, rings.collect()))
+}
+
+fn parse_ring(coordinates: &Value) -> anyhow::Result<LineString<f64>> {
+    coordinates
+        .as_array()
+        .ok_or_else(|| anyhow!("Invalid ring coordinates"))?
+        .iter()
+        .map(
+            |position| match (position[0].as_f64(), position[1].as_f64()) {
+                (Some(x), Some(y)) => Ok(Coord { x, y }),
+                _ => Err(anyhow!("Invalid position: {}", position)),
+            },
+        )
+        .collect()
+}
+
+/// A sink that tags the elements with the region they fall in before passing them on to the
+/// wrapped sink, e.g. to attach region codes to an extract.
+///
+/// Nodes are tagged with the region of their location, and ways with the region of the
+/// centroid of their nodes. Their locations are taken from the way nodes, or else from the
+/// nodes written before the way. Elements outside all regions, ways of unknown location and
+/// relations are passed on unchanged. An existing tag with the same key is replaced.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{ElementSource, IterableReader};
+/// use pbf_craft::spatial::{RegionIndex, RegionTaggingSink};
+/// use pbf_craft::writers::{ElementSink, PbfWriter};
+///
+/// // Usually loaded with `RegionIndex::from_geojson_path("regions.geojson", "code")`
+/// let regions = RegionIndex::new(Vec::new());
+/// let mut reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let writer = PbfWriter::new(Vec::new(), true);
+/// let mut sink = RegionTaggingSink::new(writer, regions, "nb:region");
+/// while let Some(element) = reader.next_element().unwrap() {
+///     sink.write(element).unwrap();
+/// }
+/// sink.finish().unwrap();
+/// ```
+pub struct RegionTaggingSink<S: ElementSink> {
+    sink: S,
+    regions: RegionIndex,
+    key: String,
+    locations: LocationIndex,
+}
+
+impl<S: ElementSink> RegionTaggingSink<S> {
+    pub fn new(sink: S, regions: RegionIndex, key: &str) -> Self {
+        Self {
+            sink,
+            regions,
+            key: key.to_string(),
+            locations: LocationIndex::new(),
+        }
+    }
+
+    /// Consumes the `RegionTaggingSink` and returns the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    fn set_region(&self, tags: &mut Vec<Tag>, coord: Option<Coord<f64>>) {
+        let Some(region) = coord.and_then(|coord| self.regions.region_at(coord)) else {
+            return;
+        };
+        match tags.iter_mut().find(|tag| tag.key == self.key) {
+            Some(tag) => tag.value = region.to_string(),
+            None => tags.push(Tag {
+                key: self.key.clone(),
+                value: region.to_string(),
+            }),
+        }
+    }
+}
+
+impl<S: ElementSink> ElementSink for RegionTaggingSink<S> {
+    fn write(&mut self, mut element: Element) -> anyhow::Result<()> {
+        match &mut element {
+            Element::Node(node) => {
+                self.locations
+                    .insert(node.id, node.latitude, node.longitude);
+                let coord = to_coord(node.latitude, node.longitude);
+                self.set_region(&mut node.tags, Some(coord));
+            }
+            Element::Way(way) => {
+                let coords: Option<LineString<f64>> = way
+                    .way_nodes
+                    .iter()
+                    .map(|way_node| match (way_node.latitude, way_node.longitude) {
+                        (Some(latitude), Some(longitude)) => Some(to_coord(latitude, longitude)),
+                        _ => self
+                            .locations
+                            .get(way_node.id)
+                            .map(|(latitude, longitude)| to_coord(latitude, longitude)),
+                    })
+                    .collect();
+                let centroid = coords
+                    .and_then(|line_string| line_string.centroid())
+                    .map(Coord::from);
+                self.set_region(&mut way.tags, centroid);
+            }
+            Element::Relation(_) => {}
+        }
+        self.sink.write(element)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.sink.finish()
+    }
+}
+
+fn to_coord(latitude: i64, longitude: i64) -> Coord<f64> {
+    Coord {
+        x: longitude as f64 / 1e9,
+        y: latitude as f64 / 1e9,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Way, WayNode};
+
+    #[derive(Default)]
+    struct VecSink(Vec<Element>);
+
+    impl ElementSink for VecSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.0.push(element);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    const GEOJSON: &str = r#"{"type": "FeatureCollection", "features": [
+        {"type": "Feature", "properties": {"code": "A"}, "geometry": {"type": "Polygon",
+            "coordinates": [[[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]]]}},
+        {"type": "Feature", "properties": {"code": "B"}, "geometry": {"type": "MultiPolygon",
+            "coordinates": [[[[2, 2], [4, 2], [4, 4], [2, 4], [2, 2]]], [[[20, 0], [30, 0], [30, 10], [20, 0]]]]}}
+    ]}"#;
+
+    #[test]
+    fn test_region_at() {
+        let regions = RegionIndex::from_geojson(GEOJSON, "code").unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions.region_at(Coord { x: 1.0, y: 1.0 }), Some("A"));
+        // B is nested in A, and smaller
+        assert_eq!(regions.region_at(Coord { x: 3.0, y: 3.0 }), Some("B"));
+        assert_eq!(regions.region_at(Coord { x: 29.0, y: 1.0 }), Some("B"));
+        // Within the bounding box of B, but outside its triangle
+        assert_eq!(regions.region_at(Coord { x: 21.0, y: 9.0 }), None);
+        assert!(RegionIndex::from_geojson(GEOJSON, "name").is_err());
+    }
+
+    #[test]
+    fn test_region_tagging_sink() {
+        let regions = RegionIndex::from_geojson(GEOJSON, "code").unwrap();
+        let mut sink = RegionTaggingSink::new(VecSink::default(), regions, "nb:region");
+        let node = |id: i64, longitude: i64, latitude: i64| {
+            Element::Node(Node {
+                id,
+                latitude: latitude * 1_000_000_000,
+                longitude: longitude * 1_000_000_000,
+                ..Default::default()
+            })
+        };
+        sink.write(node(1, 1, 1)).unwrap();
+        sink.write(node(2, 15, 5)).unwrap();
+        sink.write(node(3, 5, 5)).unwrap();
+        sink.write(Element::Way(Way {
+            id: 10,
+            way_nodes: vec![
+                WayNode::new_without_coords(1),
+                WayNode::new_without_coords(3),
+            ],
+            tags: vec![Tag {
+                key: "nb:region".to_string(),
+                value: "outdated".to_string(),
+            }],
+            ..Default::default()
+        }))
+        .unwrap();
+        sink.finish().unwrap();
+
+        let elements = sink.into_inner().0;
+        let regions: Vec<Option<&str>> = elements
+            .iter()
+            .map(|element| {
+                let tags = match element {
+                    Element::Node(node) => &node.tags,
+                    Element::Way(way) => &way.tags,
+                    Element::Relation(relation) => &relation.tags,
+                };
+                assert!(tags.len() <= 1);
+                tags.first().map(|tag| tag.value.as_str())
+            })
+            .collect();
+        assert_eq!(regions, vec![Some("A"), None, Some("A"), Some("B")]);
+    }
+}
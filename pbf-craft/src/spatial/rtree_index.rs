@@ -0,0 +1,375 @@
+use std::collections::BTreeSet;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::{BufReader, BufWriter};
+use std::io::{Read, Write};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use geo::{Coord, Rect};
+use rstar::primitives::{GeomWithData, Line};
+use rstar::{RTree, AABB};
+
+use crate::models::Element;
+use crate::readers::ElementSource;
+#[cfg(feature = "fs")]
+use crate::readers::IterableReader;
+use crate::utils::LocationIndex;
+
+type NodeEntry = GeomWithData<[f64; 2], i64>;
+type SegmentEntry = GeomWithData<Line<[f64; 2]>, i64>;
+
+/// An R-tree of the locations of nodes and the segments of ways, for repeated spatial queries
+/// such as snapping to roads or reverse geocoding.
+///
+/// The queries return element IDs, which an `IndexedReader` can turn into elements. Locations
+/// are in degrees, and the nearest elements are found by their planar distance in degrees,
+/// which favours east-west neighbours away from the equator.
+///
+/// The index is bulk-loaded in a single pass over the data, with the node locations kept in a
+/// `LocationIndex` to resolve the ways. It can be saved with `save` and loaded again with
+/// `load`, which is much faster than building it again.
+///
+/// # Example
+///
+/// ```rust
+/// use geo::Coord;
+/// use pbf_craft::models::{Element, ElementId, ElementType};
+/// use pbf_craft::readers::IndexedReader;
+/// use pbf_craft::spatial::RTreeIndex;
+///
+/// // Index the highways only
+/// let index = RTreeIndex::from_path_with_filter("resources/andorra-latest.osm.pbf", |element| {
+///     matches!(element, Element::Way(way) if way.tags.iter().any(|tag| tag.key == "highway"))
+/// })
+/// .unwrap();
+/// let way_ids = index.nearest_ways(Coord { x: 1.5218, y: 42.5063 }, 3);
+///
+/// let mut indexed_reader =
+///     IndexedReader::from_path_with_cache("resources/andorra-latest.osm.pbf", 1000).unwrap();
+/// let way_ids: Vec<ElementId> = way_ids
+///     .into_iter()
+///     .map(|id| ElementId::new(ElementType::Way, id))
+///     .collect();
+/// let elements = indexed_reader.get_many_with_deps(&way_ids).unwrap();
+/// ```
+pub struct RTreeIndex {
+    nodes: RTree<NodeEntry>,
+    segments: RTree<SegmentEntry>,
+}
+
+impl RTreeIndex {
+    /// Builds an index of all nodes and ways of a source whose nodes come before its ways.
+    pub fn from_source<S: ElementSource>(source: S) -> anyhow::Result<Self> {
+        Self::from_source_with_filter(source, |_| true)
+    }
+
+    /// Builds an index of the nodes and ways of a source matching a filter. The ways are
+    /// resolved with the locations of all nodes, matching or not. Ways with a node of unknown
+    /// location are left out.
+    pub fn from_source_with_filter<S, F>(mut source: S, filter: F) -> anyhow::Result<Self>
+    where
+        S: ElementSource,
+        F: Fn(&Element) -> bool,
+    {
+        let mut location_index = LocationIndex::new();
+        let mut nodes = Vec::new();
+        let mut segments = Vec::new();
+        while let Some(element) = source.next_element()? {
+            match &element {
+                Element::Node(node) => {
+                    location_index.insert(node.id, node.latitude, node.longitude);
+                    if filter(&element) {
+                        nodes.push(NodeEntry::new(
+                            to_point(node.latitude, node.longitude),
+                            node.id,
+                        ));
+                    }
+                }
+                Element::Way(way) => {
+                    if !filter(&element) {
+                        continue;
+                    }
+                    let points: Option<Vec<[f64; 2]>> = way
+                        .way_nodes
+                        .iter()
+                        .map(|way_node| match (way_node.latitude, way_node.longitude) {
+                            (Some(latitude), Some(longitude)) => {
+                                Some(to_point(latitude, longitude))
+                            }
+                            _ => location_index
+                                .get(way_node.id)
+                                .map(|(latitude, longitude)| to_point(latitude, longitude)),
+                        })
+                        .collect();
+                    let Some(points) = points else {
+                        continue;
+                    };
+                    match points.len() {
+                        0 => {}
+                        // A segment of zero length keeps a single node way findable
+                        1 => segments
+                            .push(SegmentEntry::new(Line::new(points[0], points[0]), way.id)),
+                        _ => segments.extend(
+                            points
+                                .windows(2)
+                                .map(|pair| SegmentEntry::new(Line::new(pair[0], pair[1]), way.id)),
+                        ),
+                    }
+                }
+                // Relations come last, so there is nothing left to read
+                Element::Relation(_) => break,
+            }
+        }
+        Ok(Self {
+            nodes: RTree::bulk_load(nodes),
+            segments: RTree::bulk_load(segments),
+        })
+    }
+
+    /// Builds an index of all nodes and ways of a PBF file.
+    #[cfg(feature = "fs")]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::from_source(IterableReader::from_path(path)?)
+    }
+
+    /// Builds an index of the nodes and ways of a PBF file matching a filter, see
+    /// `from_source_with_filter`.
+    #[cfg(feature = "fs")]
+    pub fn from_path_with_filter<P, F>(path: P, filter: F) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(&Element) -> bool,
+    {
+        Self::from_source_with_filter(IterableReader::from_path(path)?, filter)
+    }
+
+    /// Returns the number of indexed nodes.
+    pub fn node_count(&self) -> usize {
+        self.nodes.size()
+    }
+
+    /// Returns the number of indexed way segments.
+    pub fn segment_count(&self) -> usize {
+        self.segments.size()
+    }
+
+    /// Returns the IDs of the nodes within a bounding box in degrees, in ascending order.
+    pub fn nodes_in(&self, bbox: Rect<f64>) -> Vec<i64> {
+        let ids: BTreeSet<i64> = self
+            .nodes
+            .locate_in_envelope(&to_envelope(bbox))
+            .map(|entry| entry.data)
+            .collect();
+        ids.into_iter().collect()
+    }
+
+    /// Returns the IDs of the ways with a segment whose bounding box intersects a bounding box
+    /// in degrees, in ascending order.
+    pub fn ways_in(&self, bbox: Rect<f64>) -> Vec<i64> {
+        let ids: BTreeSet<i64> = self
+            .segments
+            .locate_in_envelope_intersecting(&to_envelope(bbox))
+            .map(|entry| entry.data)
+            .collect();
+        ids.into_iter().collect()
+    }
+
+    /// Returns the IDs of the nodes nearest to a location in degrees, the nearest first.
+    pub fn nearest_nodes(&self, coord: Coord<f64>, count: usize) -> Vec<i64> {
+        self.nodes
+            .nearest_neighbor_iter(&[coord.x, coord.y])
+            .take(count)
+            .map(|entry| entry.data)
+            .collect()
+    }
+
+    /// Returns the IDs of the ways nearest to a location in degrees, the nearest first.
+    pub fn nearest_ways(&self, coord: Coord<f64>, count: usize) -> Vec<i64> {
+        let mut ids = Vec::with_capacity(count);
+        for entry in self.segments.nearest_neighbor_iter(&[coord.x, coord.y]) {
+            if ids.len() == count {
+                break;
+            }
+            if !ids.contains(&entry.data) {
+                ids.push(entry.data);
+            }
+        }
+        ids
+    }
+
+    /// Returns the indexed nodes with their locations, by ascending planar distance in degrees
+    /// to a location.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn node_candidates(
+        &self,
+        coord: Coord<f64>,
+    ) -> impl Iterator<Item = (i64, [f64; 2], f64)> + '_ {
+        self.nodes
+            .nearest_neighbor_iter_with_distance_2(&[coord.x, coord.y])
+            .map(|(entry, distance_2)| (entry.data, *entry.geom(), distance_2.sqrt()))
+    }
+
+    /// Returns the indexed way segments with the IDs of their ways, by ascending planar distance
+    /// in degrees to a location.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn segment_candidates(
+        &self,
+        coord: Coord<f64>,
+    ) -> impl Iterator<Item = (i64, Line<[f64; 2]>, f64)> + '_ {
+        self.segments
+            .nearest_neighbor_iter_with_distance_2(&[coord.x, coord.y])
+            .map(|(entry, distance_2)| (entry.data, *entry.geom(), distance_2.sqrt()))
+    }
+
+    /// Writes the index in a binary format read by `read_from`.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u64::<LittleEndian>(self.nodes.size() as u64)?;
+        for entry in self.nodes.iter() {
+            writer.write_i64::<LittleEndian>(entry.data)?;
+            write_point(writer, entry.geom())?;
+        }
+        writer.write_u64::<LittleEndian>(self.segments.size() as u64)?;
+        for entry in self.segments.iter() {
+            writer.write_i64::<LittleEndian>(entry.data)?;
+            write_point(writer, &entry.geom().from)?;
+            write_point(writer, &entry.geom().to)?;
+        }
+        Ok(())
+    }
+
+    /// Reads an index written by `write_to`, bulk-loading its R-trees.
+    pub fn read_from<R: Read>(reader: &mut R) -> anyhow::Result<Self> {
+        let node_count = reader.read_u64::<LittleEndian>()?;
+        let mut nodes = Vec::new();
+        for _ in 0..node_count {
+            let id = reader.read_i64::<LittleEndian>()?;
+            nodes.push(NodeEntry::new(read_point(reader)?, id));
+        }
+        let segment_count = reader.read_u64::<LittleEndian>()?;
+        let mut segments = Vec::new();
+        for _ in 0..segment_count {
+            let id = reader.read_i64::<LittleEndian>()?;
+            let line = Line::new(read_point(reader)?, read_point(reader)?);
+            segments.push(SegmentEntry::new(line, id));
+        }
+        Ok(Self {
+            nodes: RTree::bulk_load(nodes),
+            segments: RTree::bulk_load(segments),
+        })
+    }
+
+    /// Saves the index to a file.
+    #[cfg(feature = "fs")]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Loads an index saved with `save`.
+    #[cfg(feature = "fs")]
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+fn to_point(latitude: i64, longitude: i64) -> [f64; 2] {
+    [longitude as f64 / 1e9, latitude as f64 / 1e9]
+}
+
+fn to_envelope(bbox: Rect<f64>) -> AABB<[f64; 2]> {
+    AABB::from_corners([bbox.min().x, bbox.min().y], [bbox.max().x, bbox.max().y])
+}
+
+fn write_point<W: Write>(writer: &mut W, point: &[f64; 2]) -> anyhow::Result<()> {
+    writer.write_f64::<LittleEndian>(point[0])?;
+    writer.write_f64::<LittleEndian>(point[1])?;
+    Ok(())
+}
+
+fn read_point<R: Read>(reader: &mut R) -> anyhow::Result<[f64; 2]> {
+    Ok([
+        reader.read_f64::<LittleEndian>()?,
+        reader.read_f64::<LittleEndian>()?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Way, WayNode};
+
+    fn elements() -> Vec<Element> {
+        let node = |id: i64, longitude: f64, latitude: f64| {
+            Element::Node(Node {
+                id,
+                latitude: (latitude * 1e9) as i64,
+                longitude: (longitude * 1e9) as i64,
+                ..Default::default()
+            })
+        };
+        let way = |id: i64, node_ids: &[i64]| {
+            Element::Way(Way {
+                id,
+                way_nodes: node_ids
+                    .iter()
+                    .map(|id| WayNode::new_without_coords(*id))
+                    .collect(),
+                ..Default::default()
+            })
+        };
+        vec![
+            node(1, 0.0, 0.0),
+            node(2, 1.0, 0.0),
+            node(3, 1.0, 1.0),
+            node(4, 5.0, 5.0),
+            node(5, 6.0, 5.0),
+            way(10, &[1, 2, 3]),
+            way(11, &[4, 5]),
+            // Node 6 is missing
+            way(12, &[1, 6]),
+        ]
+    }
+
+    fn bbox(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Rect<f64> {
+        Rect::new(Coord { x: min_x, y: min_y }, Coord { x: max_x, y: max_y })
+    }
+
+    #[test]
+    fn test_queries() {
+        let index = RTreeIndex::from_source(elements().into_iter()).unwrap();
+        assert_eq!(index.node_count(), 5);
+        assert_eq!(index.segment_count(), 3);
+        assert_eq!(index.nodes_in(bbox(0.5, -1.0, 2.0, 2.0)), vec![2, 3]);
+        assert_eq!(index.ways_in(bbox(0.5, 0.5, 2.0, 2.0)), vec![10]);
+        assert_eq!(index.ways_in(bbox(-1.0, -1.0, 10.0, 10.0)), vec![10, 11]);
+        assert_eq!(index.nearest_nodes(Coord { x: 4.0, y: 4.0 }, 2), vec![4, 5]);
+        assert_eq!(
+            index.nearest_ways(Coord { x: 2.0, y: 0.5 }, 5),
+            vec![10, 11]
+        );
+
+        let filtered = RTreeIndex::from_source_with_filter(
+            elements().into_iter(),
+            |element| matches!(element, Element::Way(way) if way.id == 11),
+        )
+        .unwrap();
+        assert_eq!(filtered.node_count(), 0);
+        assert_eq!(filtered.nearest_ways(Coord { x: 0.0, y: 0.0 }, 5), vec![11]);
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let index = RTreeIndex::from_source(elements().into_iter()).unwrap();
+        let mut data = Vec::new();
+        index.write_to(&mut data).unwrap();
+        let index = RTreeIndex::read_from(&mut data.as_slice()).unwrap();
+        assert_eq!(index.node_count(), 5);
+        assert_eq!(index.segment_count(), 3);
+        assert_eq!(index.nearest_ways(Coord { x: 2.0, y: 0.5 }, 1), vec![10]);
+    }
+}
@@ -0,0 +1,5 @@
+mod pushdown;
+mod table;
+
+pub use pushdown::bbox_contains;
+pub use table::{register_pbf, PbfTable};
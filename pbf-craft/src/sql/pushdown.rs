@@ -0,0 +1,190 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, BooleanArray};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::cast::as_float64_array;
+use datafusion::common::ScalarValue;
+use datafusion::error::Result;
+use datafusion::logical_expr::expr::ScalarFunction;
+use datafusion::logical_expr::{
+    BinaryExpr, ColumnarValue, Expr, Operator, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl,
+    Signature, TableProviderFilterPushDown, Volatility,
+};
+use regex::Regex;
+
+use crate::models::{Element, Tag};
+use crate::query::TagFilter;
+
+/// Returns the `bbox_contains(lat, lon, south, west, north, east)` function, which checks
+/// whether a location lies within a bounding box, all in degrees and bounds included.
+///
+/// `register_pbf` registers it. On the `nodes` table, its calls with the `lat` and `lon`
+/// columns and constant bounds are pushed down into the scan.
+pub fn bbox_contains() -> ScalarUDF {
+    ScalarUDF::new_from_impl(BboxContains {
+        signature: Signature::uniform(6, vec![DataType::Float64], Volatility::Immutable),
+    })
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct BboxContains {
+    signature: Signature,
+}
+
+impl ScalarUDFImpl for BboxContains {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "bbox_contains"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        let arrays = ColumnarValue::values_to_arrays(&args.args)?;
+        let values = arrays
+            .iter()
+            .map(|array| as_float64_array(array))
+            .collect::<Result<Vec<_>>>()?;
+        let result: BooleanArray = (0..args.number_rows)
+            .map(|row| {
+                if values.iter().any(|array| array.is_null(row)) {
+                    return None;
+                }
+                let [lat, lon, south, west, north, east] =
+                    std::array::from_fn(|i| values[i].value(row));
+                Some(in_bbox(&[south, west, north, east], lat, lon))
+            })
+            .collect();
+        Ok(ColumnarValue::Array(Arc::new(result)))
+    }
+}
+
+fn in_bbox(bbox: &[f64; 4], lat: f64, lon: f64) -> bool {
+    let [south, west, north, east] = *bbox;
+    lat >= south && lat <= north && lon >= west && lon <= east
+}
+
+/// A filter of a query which the scan of a table applies while reading the file.
+pub(super) enum ScanFilter {
+    Tag(TagFilter),
+    /// The bounds of `bbox_contains(lat, lon, south, west, north, east)`, in degrees.
+    Bbox([f64; 4]),
+}
+
+impl ScanFilter {
+    /// Translates a filter of a query, along with whether the scan applies it exactly or only
+    /// removes rows which can't match.
+    ///
+    /// `tags['k'] != 'v'` is inexact, since `TagFilter::NotEquals` also keeps the elements
+    /// without the tag, while SQL compares their NULL value to `'v'` as unknown.
+    pub(super) fn from_expr(expr: &Expr) -> Option<(ScanFilter, TableProviderFilterPushDown)> {
+        let (filter, exact) = match expr {
+            Expr::IsNotNull(expr) => (TagFilter::Exists(tag_key(expr)?), true),
+            Expr::IsNull(expr) => (TagFilter::NotExists(tag_key(expr)?), true),
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let (key, value) = match (tag_key(left), string_literal(right)) {
+                    (Some(key), Some(value)) => (key, value),
+                    // The planner may put the literal first, e.g. for `'v' = tags['k']`
+                    _ => (tag_key(right)?, string_literal(left)?),
+                };
+                match op {
+                    Operator::Eq => (TagFilter::Equals(key, value), true),
+                    Operator::NotEq => (TagFilter::NotEquals(key, value), false),
+                    Operator::RegexMatch if tag_key(left).is_some() => {
+                        (TagFilter::Matches(key, Regex::new(&value).ok()?), true)
+                    }
+                    _ => return None,
+                }
+            }
+            Expr::ScalarFunction(ScalarFunction { func, args })
+                if func.name() == "bbox_contains" =>
+            {
+                let [lat, lon, bounds @ ..] = args.as_slice() else {
+                    return None;
+                };
+                if !is_column(lat, "lat") || !is_column(lon, "lon") {
+                    return None;
+                }
+                let bounds = bounds
+                    .iter()
+                    .map(float_literal)
+                    .collect::<Option<Vec<_>>>()?;
+                return Some((
+                    ScanFilter::Bbox(bounds.try_into().ok()?),
+                    TableProviderFilterPushDown::Exact,
+                ));
+            }
+            _ => return None,
+        };
+        let pushdown = if exact {
+            TableProviderFilterPushDown::Exact
+        } else {
+            TableProviderFilterPushDown::Inexact
+        };
+        Some((ScanFilter::Tag(filter), pushdown))
+    }
+
+    pub(super) fn matches(&self, element: &Element, tags: &[Tag]) -> bool {
+        match self {
+            ScanFilter::Tag(filter) => filter.matches(tags),
+            ScanFilter::Bbox(bbox) => match element {
+                Element::Node(node) => in_bbox(
+                    bbox,
+                    node.latitude as f64 / 1e9,
+                    node.longitude as f64 / 1e9,
+                ),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Returns the key of `tags['key']`, which is planned as `get_field(tags, 'key')`.
+fn tag_key(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::ScalarFunction(ScalarFunction { func, args }) if func.name() == "get_field" => {
+            match args.as_slice() {
+                [column, key] if is_column(column, "tags") => string_literal(key),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_column(expr: &Expr, name: &str) -> bool {
+    matches!(expr, Expr::Column(column) if column.name == name)
+}
+
+fn string_literal(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Literal(value, _) => value.try_as_str().flatten().map(str::to_string),
+        _ => None,
+    }
+}
+
+fn float_literal(expr: &Expr) -> Option<f64> {
+    let value = match expr {
+        Expr::Literal(value, _) => value,
+        // Integer bounds are cast to the Float64 of the signature
+        Expr::Cast(cast) => match cast.expr.as_ref() {
+            Expr::Literal(value, _) => value,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match value.cast_to(&DataType::Float64).ok()? {
+        ScalarValue::Float64(value) => value,
+        _ => None,
+    }
+}
@@ -0,0 +1,454 @@
+use std::any::Any;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::array::{
+    Array, ArrayRef, BooleanArray, Float64Array, Int32Array, Int64Array, Int64Builder, ListBuilder,
+    MapBuilder, StringArray, StringBuilder, StructBuilder, TimestampMillisecondArray,
+};
+use datafusion::arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use datafusion::catalog::{Session, TableProvider};
+use datafusion::datasource::memory::MemorySourceConfig;
+use datafusion::datasource::TableType;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{Expr, TableProviderFilterPushDown};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionContext;
+
+use super::pushdown::{bbox_contains, ScanFilter};
+use crate::models::{BasicElement, Element, ElementType};
+use crate::readers::PbfReader;
+
+/// The number of rows of the record batches returned by a scan.
+const BATCH_SIZE: usize = 8192;
+
+/// Registers the `nodes`, `ways` and `relations` tables of a PBF file, and the
+/// `bbox_contains` function, in a DataFusion session.
+///
+/// # Example
+///
+/// ```rust
+/// use datafusion::prelude::SessionContext;
+/// use pbf_craft::sql::register_pbf;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let ctx = SessionContext::new();
+/// register_pbf(&ctx, "resources/andorra-latest.osm.pbf").unwrap();
+/// let cafes = ctx
+///     .sql(
+///         "SELECT id, tags['name'] FROM nodes \
+///          WHERE bbox_contains(lat, lon, 42.50, 1.50, 42.52, 1.54) AND tags['amenity'] = 'cafe'",
+///     )
+///     .await
+///     .unwrap()
+///     .collect()
+///     .await
+///     .unwrap();
+/// # });
+/// ```
+pub fn register_pbf<P: AsRef<Path>>(ctx: &SessionContext, pbf_file: P) -> anyhow::Result<()> {
+    let pbf_file = pbf_file.as_ref();
+    for (name, element_type) in [
+        ("nodes", ElementType::Node),
+        ("ways", ElementType::Way),
+        ("relations", ElementType::Relation),
+    ] {
+        ctx.register_table(name, Arc::new(PbfTable::from_path(pbf_file, element_type)?))?;
+    }
+    ctx.register_udf(bbox_contains());
+    Ok(())
+}
+
+/// A DataFusion table of the nodes, ways or relations of a PBF file.
+///
+/// All tables have the columns `id`, `version`, `timestamp`, `changeset`, `uid`, `user`,
+/// `visible` and `tags`, a map from keys to values. Nodes add `lat` and `lon` in degrees, ways
+/// add `nodes`, the list of their node ids, and relations add `members`, a list of structs
+/// with the `type`, `ref` and `role` of each member.
+///
+/// Each scan reads the file with `PbfReader::par_find`. The filters on tags, i.e.
+/// `tags['k'] = 'v'`, `tags['k'] != 'v'`, `tags['k'] ~ 'regex'`, `tags['k'] IS NULL` and
+/// `tags['k'] IS NOT NULL`, and the `bbox_contains(lat, lon, ...)` filters of nodes are pushed
+/// down into it, so that only the matching elements are converted to record batches. They're
+/// held in memory until the query is done.
+#[derive(Debug)]
+pub struct PbfTable {
+    pbf_file: PathBuf,
+    element_type: ElementType,
+    schema: SchemaRef,
+}
+
+impl PbfTable {
+    /// Creates the table of the elements of a type in a PBF file.
+    pub fn from_path<P: AsRef<Path>>(
+        pbf_file: P,
+        element_type: ElementType,
+    ) -> anyhow::Result<Self> {
+        let pbf_file = pbf_file.as_ref();
+        if !pbf_file.is_file() {
+            bail!("{} is not a file", pbf_file.display());
+        }
+        Ok(Self {
+            pbf_file: pbf_file.to_path_buf(),
+            schema: Arc::new(table_schema(&element_type)),
+            element_type,
+        })
+    }
+
+    fn read(&self, filters: Vec<ScanFilter>) -> anyhow::Result<Vec<Element>> {
+        let reader = PbfReader::from_path(&self.pbf_file)?;
+        let mut elements = reader.par_find(self.element_type.clone().into(), |element| {
+            let tags = base(element).get_tags();
+            filters.iter().all(|filter| filter.matches(element, tags))
+        })?;
+        // par_find returns the blobs in the order they finish decoding
+        elements.sort_unstable_by_key(|element| element.get_meta().1);
+        Ok(elements)
+    }
+}
+
+#[async_trait]
+impl TableProvider for PbfTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|expr| match ScanFilter::from_expr(expr) {
+                Some((_, pushdown)) => pushdown,
+                None => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let filters = filters
+            .iter()
+            .filter_map(|expr| ScanFilter::from_expr(expr).map(|(filter, _)| filter))
+            .collect();
+        let mut elements = self
+            .read(filters)
+            .map_err(|err| DataFusionError::External(err.into()))?;
+        if let Some(limit) = limit {
+            elements.truncate(limit);
+        }
+
+        let schema = match projection {
+            Some(projection) => Arc::new(self.schema.project(projection)?),
+            None => self.schema.clone(),
+        };
+        let batches = elements
+            .chunks(BATCH_SIZE)
+            .map(|chunk| {
+                let columns = schema
+                    .fields()
+                    .iter()
+                    .map(|field| build_column(field.name(), chunk))
+                    .collect();
+                // A projection may have no columns, e.g. for `count(*)`
+                let options = RecordBatchOptions::new().with_row_count(Some(chunk.len()));
+                RecordBatch::try_new_with_options(schema.clone(), columns, &options)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(MemorySourceConfig::try_new_exec(&[batches], schema, None)?)
+    }
+}
+
+fn table_schema(element_type: &ElementType) -> Schema {
+    let mut fields = vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("version", DataType::Int32, false),
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            true,
+        ),
+        Field::new("changeset", DataType::Int64, false),
+        Field::new("uid", DataType::Int32, true),
+        Field::new("user", DataType::Utf8, true),
+        Field::new("visible", DataType::Boolean, false),
+        Field::new("tags", tags_builder().finish().data_type().clone(), false),
+    ];
+    match element_type {
+        ElementType::Node => {
+            fields.push(Field::new("lat", DataType::Float64, false));
+            fields.push(Field::new("lon", DataType::Float64, false));
+        }
+        ElementType::Way => fields.push(Field::new(
+            "nodes",
+            DataType::new_list(DataType::Int64, true),
+            false,
+        )),
+        ElementType::Relation => fields.push(Field::new(
+            "members",
+            DataType::new_list(DataType::Struct(member_fields()), true),
+            false,
+        )),
+    }
+    Schema::new(fields)
+}
+
+fn member_fields() -> Fields {
+    Fields::from(vec![
+        Field::new("type", DataType::Utf8, false),
+        Field::new("ref", DataType::Int64, false),
+        Field::new("role", DataType::Utf8, false),
+    ])
+}
+
+fn tags_builder() -> MapBuilder<StringBuilder, StringBuilder> {
+    MapBuilder::new(None, StringBuilder::new(), StringBuilder::new())
+}
+
+fn base(element: &Element) -> &dyn BasicElement {
+    match element {
+        Element::Node(node) => node,
+        Element::Way(way) => way,
+        Element::Relation(relation) => relation,
+    }
+}
+
+fn build_column(name: &str, elements: &[Element]) -> ArrayRef {
+    let bases = elements.iter().map(base);
+    match name {
+        "id" => Arc::new(Int64Array::from_iter_values(bases.map(|e| e.get_id()))),
+        "version" => Arc::new(Int32Array::from_iter_values(bases.map(|e| e.get_version()))),
+        "timestamp" => Arc::new(
+            TimestampMillisecondArray::from_iter(
+                bases.map(|e| e.get_timestamp().map(|t| t.timestamp_millis())),
+            )
+            .with_timezone("UTC"),
+        ),
+        "changeset" => Arc::new(Int64Array::from_iter_values(
+            bases.map(|e| e.get_changeset_id()),
+        )),
+        "uid" => Arc::new(Int32Array::from_iter(
+            bases.map(|e| e.get_user().map(|user| user.id)),
+        )),
+        "user" => Arc::new(StringArray::from_iter(
+            bases.map(|e| e.get_user().map(|user| user.name.to_string())),
+        )),
+        "visible" => Arc::new(BooleanArray::from_iter(bases.map(|e| Some(e.is_visible())))),
+        "tags" => {
+            let mut builder = tags_builder();
+            for element in bases {
+                for tag in element.get_tags() {
+                    builder.keys().append_value(&tag.key);
+                    builder.values().append_value(&tag.value);
+                }
+                builder
+                    .append(true)
+                    .expect("keys and values have the same length");
+            }
+            Arc::new(builder.finish())
+        }
+        "lat" | "lon" => Arc::new(Float64Array::from_iter_values(elements.iter().map(
+            |element| match element {
+                Element::Node(node) if name == "lat" => node.latitude as f64 / 1e9,
+                Element::Node(node) => node.longitude as f64 / 1e9,
+                _ => unreachable!("only nodes have coordinates"),
+            },
+        ))),
+        "nodes" => {
+            let mut builder = ListBuilder::new(Int64Builder::new());
+            for element in elements {
+                if let Element::Way(way) = element {
+                    builder
+                        .values()
+                        .append_slice(&way.way_nodes.iter().map(|wn| wn.id).collect::<Vec<_>>());
+                }
+                builder.append(true);
+            }
+            Arc::new(builder.finish())
+        }
+        "members" => {
+            let mut builder = ListBuilder::new(StructBuilder::from_fields(member_fields(), 0));
+            for element in elements {
+                if let Element::Relation(relation) = element {
+                    let members = builder.values();
+                    for member in &relation.members {
+                        members
+                            .field_builder::<StringBuilder>(0)
+                            .unwrap()
+                            .append_value(member.member_type.as_str());
+                        members
+                            .field_builder::<Int64Builder>(1)
+                            .unwrap()
+                            .append_value(member.member_id);
+                        members
+                            .field_builder::<StringBuilder>(2)
+                            .unwrap()
+                            .append_value(&*member.role);
+                        members.append(true);
+                    }
+                }
+                builder.append(true);
+            }
+            Arc::new(builder.finish())
+        }
+        _ => unreachable!("unknown column {}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::AsArray;
+    use datafusion::arrow::datatypes::{Int64Type, UInt64Type};
+
+    use super::*;
+    use crate::readers::IterableReader;
+
+    const PBF_FILE: &str = "./resources/andorra-latest.osm.pbf";
+
+    async fn query(sql: &str) -> Vec<RecordBatch> {
+        let ctx = SessionContext::new();
+        register_pbf(&ctx, PBF_FILE).unwrap();
+        ctx.sql(sql).await.unwrap().collect().await.unwrap()
+    }
+
+    fn ids(batches: &[RecordBatch]) -> Vec<i64> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_pushdown() {
+        let sql = "SELECT id, tags['name'] FROM nodes \
+                   WHERE bbox_contains(lat, lon, 42.50, 1.50, 42.52, 1.54) \
+                   AND tags['amenity'] = 'cafe' ORDER BY id";
+        let ctx = SessionContext::new();
+        register_pbf(&ctx, PBF_FILE).unwrap();
+        let plan = ctx
+            .sql(&format!("EXPLAIN {}", sql))
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let plan = datafusion::arrow::util::pretty::pretty_format_batches(&plan)
+            .unwrap()
+            .to_string();
+        // Both filters are applied by the scan, so no filter is left in the plans
+        assert!(!plan.contains("Filter"), "{}", plan);
+
+        let mut expected = Vec::new();
+        for element in IterableReader::from_path(PBF_FILE).unwrap() {
+            if let Element::Node(node) = element {
+                let (lat, lon) = (node.latitude as f64 / 1e9, node.longitude as f64 / 1e9);
+                if (42.50..=42.52).contains(&lat)
+                    && (1.50..=1.54).contains(&lon)
+                    && node
+                        .tags
+                        .iter()
+                        .any(|tag| tag.key == "amenity" && tag.value == "cafe")
+                {
+                    expected.push(node.id);
+                }
+            }
+        }
+        assert!(!expected.is_empty());
+        assert_eq!(ids(&query(sql).await), expected);
+    }
+
+    #[tokio::test]
+    async fn test_inexact_and_unsupported_filters() {
+        // `!=` is re-checked by DataFusion, which drops the ways without the tag
+        let batches = query(
+            "SELECT id, tags['highway'] FROM ways \
+             WHERE tags['highway'] != 'primary' AND id % 2 = 0",
+        )
+        .await;
+        let mut count = 0;
+        for batch in &batches {
+            let highways = batch.column(1).as_string::<i32>();
+            assert_eq!(highways.null_count(), 0);
+            assert!(highways.iter().all(|highway| highway != Some("primary")));
+            count += batch.num_rows();
+        }
+        assert!(count > 0);
+
+        let batches = query("SELECT count(*) FROM relations WHERE tags['type'] IS NULL").await;
+        let expected = IterableReader::from_path(PBF_FILE)
+            .unwrap()
+            .filter(|element| match element {
+                Element::Relation(relation) => !relation.tags.iter().any(|tag| tag.key == "type"),
+                _ => false,
+            })
+            .count();
+        assert_eq!(ids(&batches), vec![expected as i64]);
+    }
+
+    #[tokio::test]
+    async fn test_nested_columns() {
+        let batches = query(
+            "SELECT id, array_length(nodes) FROM ways \
+             WHERE tags['name'] ~ '^Carrer' ORDER BY id LIMIT 5",
+        )
+        .await;
+        let mut expected: Vec<(i64, u64)> = IterableReader::from_path(PBF_FILE)
+            .unwrap()
+            .filter_map(|element| match element {
+                Element::Way(way)
+                    if way
+                        .tags
+                        .iter()
+                        .any(|tag| tag.key == "name" && tag.value.starts_with("Carrer")) =>
+                {
+                    Some((way.id, way.way_nodes.len() as u64))
+                }
+                _ => None,
+            })
+            .collect();
+        expected.sort_unstable();
+        expected.truncate(5);
+        let lengths = batches.iter().flat_map(|batch| {
+            batch
+                .column(1)
+                .as_primitive::<UInt64Type>()
+                .values()
+                .to_vec()
+        });
+        let actual: Vec<(i64, u64)> = ids(&batches).into_iter().zip(lengths).collect();
+        assert_eq!(actual, expected);
+
+        let batches = query(
+            "SELECT DISTINCT unnest(members)['type'] AS member_type FROM relations \
+             ORDER BY member_type",
+        )
+        .await;
+        let member_types: Vec<&str> = batches
+            .iter()
+            .flat_map(|batch| batch.column(0).as_string::<i32>().iter().flatten())
+            .collect();
+        assert_eq!(member_types, vec!["node", "relation", "way"]);
+    }
+}
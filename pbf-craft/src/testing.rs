@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use chrono::DateTime;
+
+use crate::models::{Element, Node, Tag, Way, WayNode};
+use crate::writers::PbfWriter;
+
+/// The distance between neighbouring nodes of the synthetic grid, in nanodegrees.
+const GRID_SPACING: i64 = 100_000;
+/// The maximum number of nodes of a synthetic way.
+const WAY_LENGTH: u64 = 50;
+
+/// Generates a synthetic dataset of `node_count` nodes followed by ways connecting them.
+///
+/// The nodes are laid out row by row on a square grid, and every row is cut into ways of up to
+/// 50 nodes. Every 100th node and every way carry a tag. IDs start at 1 and the elements are
+/// sorted by type and ID, as in a planet extract, so the dataset can be written as it is.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::Element;
+/// use pbf_craft::testing::synthetic_elements;
+///
+/// let elements: Vec<Element> = synthetic_elements(100).collect();
+/// assert_eq!(elements.len(), 100 + 10);
+/// ```
+pub fn synthetic_elements(node_count: u64) -> impl Iterator<Item = Element> {
+    let side = ((node_count as f64).sqrt().ceil() as u64).max(1);
+    let coordinates = move |index: u64| {
+        (
+            (index / side) as i64 * GRID_SPACING,
+            (index % side) as i64 * GRID_SPACING,
+        )
+    };
+
+    let nodes = (0..node_count).map(move |index| {
+        let (latitude, longitude) = coordinates(index);
+        let tags = if index % 100 == 0 {
+            vec![Tag {
+                key: "amenity".to_string(),
+                value: "bench".to_string(),
+            }]
+        } else {
+            Vec::new()
+        };
+        Element::Node(Node {
+            id: index as i64 + 1,
+            latitude,
+            longitude,
+            tags,
+            ..synthetic_node_base()
+        })
+    });
+
+    let way_ranges = (0..node_count)
+        .step_by(side as usize)
+        .flat_map(move |row_start| {
+            let row_end = (row_start + side).min(node_count);
+            (row_start..row_end)
+                .step_by(WAY_LENGTH as usize)
+                .map(move |start| start..(start + WAY_LENGTH).min(row_end))
+        })
+        .filter(|range| range.end - range.start >= 2);
+    let ways = way_ranges.enumerate().map(|(way_index, range)| {
+        let way_nodes = range
+            .map(|index| WayNode::new_without_coords(index as i64 + 1))
+            .collect();
+        let base = synthetic_node_base();
+        Element::Way(Way {
+            id: way_index as i64 + 1,
+            version: base.version,
+            timestamp: base.timestamp,
+            changeset_id: base.changeset_id,
+            visible: true,
+            tags: vec![Tag {
+                key: "highway".to_string(),
+                value: "residential".to_string(),
+            }],
+            way_nodes,
+            ..Default::default()
+        })
+    });
+
+    nodes.chain(ways)
+}
+
+/// Writes the dataset of `synthetic_elements` to a PBF file with dense nodes.
+#[cfg(feature = "fs")]
+pub fn write_synthetic_pbf<P: AsRef<Path>>(path: P, node_count: u64) -> anyhow::Result<()> {
+    let mut writer = PbfWriter::from_path(path, true)?;
+    for element in synthetic_elements(node_count) {
+        writer.write(element)?;
+    }
+    writer.finish()
+}
+
+fn synthetic_node_base() -> Node {
+    Node {
+        version: 1,
+        timestamp: DateTime::from_timestamp(1_700_000_000, 0),
+        changeset_id: 1,
+        visible: true,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::readers::IterableReader;
+
+    #[test]
+    fn test_synthetic_pbf() {
+        let path = "./resources/test_synthetic.osm.pbf";
+        write_synthetic_pbf(path, 1000).unwrap();
+
+        let reader = IterableReader::from_path(path).unwrap();
+        let mut node_count = 0;
+        let mut way_node_count = 0;
+        for element in reader {
+            match element {
+                Element::Node(_) => node_count += 1,
+                Element::Way(way) => {
+                    assert!(way.way_nodes.len() >= 2);
+                    way_node_count += way.way_nodes.len();
+                }
+                Element::Relation(_) => unreachable!(),
+            }
+        }
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(node_count, 1000);
+        assert_eq!(way_node_count, 1000);
+    }
+}
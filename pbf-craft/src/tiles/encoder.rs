@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use crate::models::Tag;
+use crate::proto::vector_tile::{Tile_Feature, Tile_GeomType, Tile_Layer, Tile_Value};
+
+const MOVE_TO: u32 = 1;
+const LINE_TO: u32 = 2;
+const CLOSE_PATH: u32 = 7;
+
+fn command(id: u32, count: usize) -> u32 {
+    (id & 0x7) | ((count as u32) << 3)
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+/// Encodes geometries into the command stream of a vector tile feature. The coordinates are
+/// relative to the top left corner of the tile, in units of its extent.
+#[derive(Default)]
+pub(crate) struct GeometryEncoder {
+    commands: Vec<u32>,
+    cursor: (i32, i32),
+}
+
+impl GeometryEncoder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn push_points(&mut self, points: &[(i32, i32)]) {
+        for &(x, y) in points {
+            self.commands.push(zigzag(x - self.cursor.0));
+            self.commands.push(zigzag(y - self.cursor.1));
+            self.cursor = (x, y);
+        }
+    }
+
+    pub(crate) fn add_points(&mut self, points: &[(i32, i32)]) {
+        if points.is_empty() {
+            return;
+        }
+        self.commands.push(command(MOVE_TO, points.len()));
+        self.push_points(points);
+    }
+
+    /// Adds a line. Lines with less than two points are skipped.
+    pub(crate) fn add_line(&mut self, points: &[(i32, i32)]) {
+        if points.len() < 2 {
+            return;
+        }
+        self.commands.push(command(MOVE_TO, 1));
+        self.push_points(&points[..1]);
+        self.commands.push(command(LINE_TO, points.len() - 1));
+        self.push_points(&points[1..]);
+    }
+
+    /// Adds a ring given without its closing point.
+    pub(crate) fn add_ring(&mut self, points: &[(i32, i32)]) {
+        if points.len() < 3 {
+            return;
+        }
+        self.add_line(points);
+        self.commands.push(command(CLOSE_PATH, 1));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub(crate) fn into_commands(self) -> Vec<u32> {
+        self.commands
+    }
+}
+
+/// Collects the features of a layer of one tile, sharing the keys and values of their tags.
+#[derive(Default)]
+pub(crate) struct LayerBuilder {
+    features: Vec<Tile_Feature>,
+    keys: Vec<String>,
+    key_indexes: HashMap<String, u32>,
+    values: Vec<String>,
+    value_indexes: HashMap<String, u32>,
+}
+
+fn index_of(items: &mut Vec<String>, indexes: &mut HashMap<String, u32>, item: &str) -> u32 {
+    if let Some(index) = indexes.get(item) {
+        return *index;
+    }
+    let index = items.len() as u32;
+    items.push(item.to_string());
+    indexes.insert(item.to_string(), index);
+    index
+}
+
+impl LayerBuilder {
+    pub(crate) fn add_feature(
+        &mut self,
+        id: i64,
+        tags: &[Tag],
+        geom_type: Tile_GeomType,
+        geometry: GeometryEncoder,
+    ) {
+        let mut feature = Tile_Feature::new();
+        // Feature IDs are unsigned, so negative IDs of new elements are left out
+        if let Ok(id) = u64::try_from(id) {
+            feature.set_id(id);
+        }
+        for tag in tags {
+            let key = index_of(&mut self.keys, &mut self.key_indexes, &tag.key);
+            let value = index_of(&mut self.values, &mut self.value_indexes, &tag.value);
+            feature.mut_tags().extend([key, value]);
+        }
+        feature.set_field_type(geom_type);
+        feature.set_geometry(geometry.into_commands());
+        self.features.push(feature);
+    }
+
+    pub(crate) fn into_layer(self, name: &str, extent: u32) -> Tile_Layer {
+        let mut layer = Tile_Layer::new();
+        layer.set_version(2);
+        layer.set_name(name.to_string());
+        layer.set_extent(extent);
+        layer.set_features(self.features.into());
+        layer.set_keys(self.keys.into());
+        layer.set_values(
+            self.values
+                .into_iter()
+                .map(|value| {
+                    let mut tile_value = Tile_Value::new();
+                    tile_value.set_string_value(value);
+                    tile_value
+                })
+                .collect(),
+        );
+        layer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_geometry() {
+        // The examples of section 4.3.5 of the specification
+        let mut encoder = GeometryEncoder::new();
+        encoder.add_points(&[(25, 17)]);
+        assert_eq!(encoder.into_commands(), vec![9, 50, 34]);
+
+        let mut encoder = GeometryEncoder::new();
+        encoder.add_line(&[(2, 2), (2, 10), (10, 10)]);
+        assert_eq!(encoder.into_commands(), vec![9, 4, 4, 18, 0, 16, 16, 0]);
+
+        let mut encoder = GeometryEncoder::new();
+        encoder.add_ring(&[(3, 6), (8, 12), (20, 34)]);
+        assert_eq!(
+            encoder.into_commands(),
+            vec![9, 6, 12, 18, 10, 12, 24, 44, 15]
+        );
+    }
+}
@@ -0,0 +1,633 @@
+use std::collections::{BTreeMap, HashSet};
+use std::f64::consts::PI;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use geo::orient::Direction;
+use geo::{
+    BooleanOps, BoundingRect, Coord, LineString, MapCoords, MultiLineString, Orient, Polygon, Rect,
+};
+use protobuf::Message;
+
+use super::encoder::{GeometryEncoder, LayerBuilder};
+use crate::models::{Element, Tag, Way};
+use crate::proto::vector_tile::{Tile, Tile_GeomType};
+use crate::readers::ElementSource;
+#[cfg(feature = "fs")]
+use crate::readers::IterableReader;
+use crate::utils::{simplify_planar, LocationIndex, SimplifyAlgorithm};
+
+/// The latitude beyond which the Web Mercator projection is cut off.
+const MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// The address of a tile in the XYZ scheme of Web Mercator tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TileId {
+    pub zoom: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileId {
+    /// Returns the tile containing a location in degrees.
+    pub fn from_lon_lat(longitude: f64, latitude: f64, zoom: u8) -> Self {
+        let tiles = 1u64 << zoom;
+        let world = project(
+            Coord {
+                x: longitude,
+                y: latitude,
+            },
+            tiles as f64,
+        );
+        let clamp = |value: f64| (value.floor().max(0.0) as u64).min(tiles - 1) as u32;
+        Self {
+            zoom,
+            x: clamp(world.x),
+            y: clamp(world.y),
+        }
+    }
+
+    /// Returns the bounds of the tile in degrees.
+    pub fn bounds(&self) -> Rect<f64> {
+        let tiles = (1u64 << self.zoom) as f64;
+        let longitude = |x: f64| x / tiles * 360.0 - 180.0;
+        let latitude = |y: f64| (PI * (1.0 - 2.0 * y / tiles)).sinh().atan().to_degrees();
+        Rect::new(
+            Coord {
+                x: longitude(self.x as f64),
+                y: latitude(self.y as f64 + 1.0),
+            },
+            Coord {
+                x: longitude(self.x as f64 + 1.0),
+                y: latitude(self.y as f64),
+            },
+        )
+    }
+}
+
+/// Projects a location in degrees to Web Mercator, scaled so that the world spans `scale`
+/// units from the top left corner.
+fn project(coord: Coord<f64>, scale: f64) -> Coord<f64> {
+    let latitude = coord.y.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    Coord {
+        x: (coord.x + 180.0) / 360.0 * scale,
+        y: (1.0 - (latitude.tan() + 1.0 / latitude.cos()).ln() / PI) / 2.0 * scale,
+    }
+}
+
+/// The geometry type of the features of a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerKind {
+    /// Nodes become points.
+    Points,
+    /// Ways become lines.
+    Lines,
+    /// Closed ways become polygons.
+    Polygons,
+}
+
+/// A layer of the generated tiles, with the elements which have one of its tag keys.
+#[derive(Debug, Clone)]
+pub struct TileLayer {
+    name: String,
+    kind: LayerKind,
+    keys: HashSet<String>,
+}
+
+impl TileLayer {
+    pub fn new<I, K>(name: &str, kind: LayerKind, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        Self {
+            name: name.to_string(),
+            kind,
+            keys: keys.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// The `roads` layer of the ways tagged with `highway`.
+    pub fn roads() -> Self {
+        Self::new("roads", LayerKind::Lines, ["highway"])
+    }
+
+    /// The `buildings` layer of the closed ways tagged with `building`.
+    pub fn buildings() -> Self {
+        Self::new("buildings", LayerKind::Polygons, ["building"])
+    }
+
+    /// The `pois` layer of the nodes tagged with `amenity`, `shop` or `tourism`.
+    pub fn pois() -> Self {
+        Self::new("pois", LayerKind::Points, ["amenity", "shop", "tourism"])
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn matches(&self, tags: &[Tag]) -> bool {
+        tags.iter().any(|tag| self.keys.contains(&tag.key))
+    }
+}
+
+/// The geometry of a feature in degrees.
+enum SourceGeometry {
+    Point(Coord<f64>),
+    Line(LineString<f64>),
+    Polygon(Polygon<f64>),
+}
+
+struct SourceFeature {
+    id: i64,
+    layer: usize,
+    tags: Vec<Tag>,
+    geometry: SourceGeometry,
+}
+
+/// Generates Mapbox Vector Tiles of a set of layers, e.g. to preview a small extract without a
+/// tile server.
+///
+/// The data is read in a single pass: the node locations are collected in a `LocationIndex`
+/// and the ways, which come after the nodes, are resolved against it. Ways with a node of
+/// unknown location and relations are left out.
+///
+/// At each zoom level, lines and polygons are simplified with the Douglas-Peucker algorithm, or
+/// the one set with `set_simplify_algorithm`, within a tolerance of tile units, so the tiles at low zoom levels stay small. Features are
+/// clipped to the tiles with a buffer around them, and carry the tags of their elements.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::tiles::{TileGenerator, TileLayer};
+///
+/// let mut generator =
+///     TileGenerator::new(vec![TileLayer::roads(), TileLayer::buildings(), TileLayer::pois()]);
+/// generator.set_zoom_range(10, 12);
+/// let tiles = generator
+///     .generate_from_path("resources/andorra-latest.osm.pbf")
+///     .unwrap();
+/// for (tile_id, data) in &tiles {
+///     // Write the tile to `{zoom}/{x}/{y}.mvt`
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TileGenerator {
+    layers: Vec<TileLayer>,
+    min_zoom: u8,
+    max_zoom: u8,
+    extent: u32,
+    buffer: u32,
+    tolerance: f64,
+    algorithm: SimplifyAlgorithm,
+}
+
+impl TileGenerator {
+    /// Creates a generator of the tiles from zoom level 12 to 14, with an extent of 4096 units,
+    /// a buffer of 64 units and a simplification tolerance of 1 unit.
+    pub fn new(layers: Vec<TileLayer>) -> Self {
+        Self {
+            layers,
+            min_zoom: 12,
+            max_zoom: 14,
+            extent: 4096,
+            buffer: 64,
+            tolerance: 1.0,
+            algorithm: SimplifyAlgorithm::DouglasPeucker,
+        }
+    }
+
+    /// Sets the zoom levels to generate, including both ends.
+    pub fn set_zoom_range(&mut self, min_zoom: u8, max_zoom: u8) {
+        self.min_zoom = min_zoom;
+        self.max_zoom = max_zoom;
+    }
+
+    /// Sets the size of a tile in the units of its coordinates.
+    pub fn set_extent(&mut self, extent: u32) {
+        self.extent = extent;
+    }
+
+    /// Sets the width of the margin around a tile which features are clipped to, in tile units.
+    pub fn set_buffer(&mut self, buffer: u32) {
+        self.buffer = buffer;
+    }
+
+    /// Sets the simplification tolerance in tile units. 0 disables the simplification.
+    pub fn set_tolerance(&mut self, tolerance: f64) {
+        self.tolerance = tolerance;
+    }
+
+    /// Sets the algorithm lines and polygons are simplified with.
+    pub fn set_simplify_algorithm(&mut self, algorithm: SimplifyAlgorithm) {
+        self.algorithm = algorithm;
+    }
+
+    /// Generates the tiles of a PBF file.
+    #[cfg(feature = "fs")]
+    pub fn generate_from_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> anyhow::Result<BTreeMap<TileId, Vec<u8>>> {
+        self.generate(IterableReader::from_path(path)?)
+    }
+
+    /// Generates the tiles of a source whose nodes come before its ways. Returns the encoded
+    /// tiles which contain at least one feature.
+    pub fn generate<S: ElementSource>(
+        &self,
+        source: S,
+    ) -> anyhow::Result<BTreeMap<TileId, Vec<u8>>> {
+        if self.min_zoom > self.max_zoom || self.max_zoom > 24 {
+            bail!(
+                "Invalid zoom range {}-{}: the levels must be ordered and at most 24",
+                self.min_zoom,
+                self.max_zoom
+            );
+        }
+        let features = self.collect_features(source)?;
+
+        let mut tiles: BTreeMap<TileId, BTreeMap<usize, LayerBuilder>> = BTreeMap::new();
+        for zoom in self.min_zoom..=self.max_zoom {
+            for feature in &features {
+                self.add_feature(feature, zoom, &mut tiles);
+            }
+        }
+
+        let mut encoded = BTreeMap::new();
+        for (tile_id, layers) in tiles {
+            let mut tile = Tile::new();
+            for (index, layer) in layers {
+                tile.mut_layers()
+                    .push(layer.into_layer(&self.layers[index].name, self.extent));
+            }
+            encoded.insert(tile_id, tile.write_to_bytes()?);
+        }
+        Ok(encoded)
+    }
+
+    fn collect_features<S: ElementSource>(
+        &self,
+        mut source: S,
+    ) -> anyhow::Result<Vec<SourceFeature>> {
+        let mut location_index = LocationIndex::new();
+        let mut features = Vec::new();
+        while let Some(element) = source.next_element()? {
+            match element {
+                Element::Node(node) => {
+                    location_index.insert(node.id, node.latitude, node.longitude);
+                    let coord = Coord {
+                        x: node.longitude as f64 / 1e9,
+                        y: node.latitude as f64 / 1e9,
+                    };
+                    for (index, layer) in self.layers.iter().enumerate() {
+                        if layer.kind == LayerKind::Points && layer.matches(&node.tags) {
+                            features.push(SourceFeature {
+                                id: node.id,
+                                layer: index,
+                                tags: node.tags.clone(),
+                                geometry: SourceGeometry::Point(coord),
+                            });
+                        }
+                    }
+                }
+                Element::Way(way) => {
+                    let mut coords = None;
+                    for (index, layer) in self.layers.iter().enumerate() {
+                        if layer.kind == LayerKind::Points || !layer.matches(&way.tags) {
+                            continue;
+                        }
+                        if coords.is_none() {
+                            coords = Some(locate(&way, &location_index));
+                        }
+                        let Some(Some(coords)) = &coords else {
+                            break;
+                        };
+                        let line_string = LineString::new(coords.clone());
+                        let geometry = if layer.kind == LayerKind::Lines {
+                            SourceGeometry::Line(line_string)
+                        } else if coords.len() >= 4 && line_string.is_closed() {
+                            SourceGeometry::Polygon(Polygon::new(line_string, Vec::new()))
+                        } else {
+                            continue;
+                        };
+                        features.push(SourceFeature {
+                            id: way.id,
+                            layer: index,
+                            tags: way.tags.clone(),
+                            geometry,
+                        });
+                    }
+                }
+                // Relations come last, so there is nothing left to read
+                Element::Relation(_) => break,
+            }
+        }
+        Ok(features)
+    }
+
+    /// Adds a feature to the tiles of a zoom level it overlaps.
+    fn add_feature(
+        &self,
+        feature: &SourceFeature,
+        zoom: u8,
+        tiles: &mut BTreeMap<TileId, BTreeMap<usize, LayerBuilder>>,
+    ) {
+        let scale = (1u64 << zoom) as f64 * self.extent as f64;
+        let to_world = |coord: Coord<f64>| project(coord, scale);
+        let (world, bounds) = match &feature.geometry {
+            SourceGeometry::Point(coord) => {
+                let point = to_world(*coord);
+                (WorldGeometry::Point(point), Rect::new(point, point))
+            }
+            SourceGeometry::Line(line_string) => {
+                let line_string = self.simplify(line_string.map_coords(to_world));
+                let Some(bounds) = line_string.bounding_rect() else {
+                    return;
+                };
+                (WorldGeometry::Line(line_string), bounds)
+            }
+            SourceGeometry::Polygon(polygon) => {
+                let polygon = polygon.map_coords(to_world);
+                let polygon = Polygon::new(
+                    self.simplify(polygon.exterior().clone()),
+                    polygon
+                        .interiors()
+                        .iter()
+                        .map(|interior| self.simplify(interior.clone()))
+                        .collect(),
+                )
+                .orient(Direction::Default);
+                let Some(bounds) = polygon.bounding_rect() else {
+                    return;
+                };
+                (WorldGeometry::Polygon(polygon), bounds)
+            }
+        };
+
+        let extent = self.extent as f64;
+        let buffer = self.buffer as f64;
+        let last_tile = (1u64 << zoom) - 1;
+        let tile_range = |min: f64, max: f64| {
+            let first = ((min - buffer) / extent).floor().max(0.0) as u64;
+            let last = (((max + buffer) / extent).floor().max(0.0) as u64).min(last_tile);
+            first..=last
+        };
+        for x in tile_range(bounds.min().x, bounds.max().x) {
+            for y in tile_range(bounds.min().y, bounds.max().y) {
+                let origin = Coord {
+                    x: x as f64 * extent,
+                    y: y as f64 * extent,
+                };
+                let (geom_type, encoder) = self.encode(&world, &bounds, origin);
+                if encoder.is_empty() {
+                    continue;
+                }
+                let tile_id = TileId {
+                    zoom,
+                    x: x as u32,
+                    y: y as u32,
+                };
+                tiles
+                    .entry(tile_id)
+                    .or_default()
+                    .entry(feature.layer)
+                    .or_default()
+                    .add_feature(feature.id, &feature.tags, geom_type, encoder);
+            }
+        }
+    }
+
+    fn simplify(&self, line_string: LineString<f64>) -> LineString<f64> {
+        LineString::new(simplify_planar(
+            &line_string.0,
+            self.tolerance,
+            self.algorithm,
+        ))
+    }
+
+    /// Encodes the part of a geometry within the buffered tile at `origin`.
+    fn encode(
+        &self,
+        world: &WorldGeometry,
+        bounds: &Rect<f64>,
+        origin: Coord<f64>,
+    ) -> (Tile_GeomType, GeometryEncoder) {
+        let buffer = self.buffer as f64;
+        let clip = Rect::new(
+            Coord {
+                x: origin.x - buffer,
+                y: origin.y - buffer,
+            },
+            Coord {
+                x: origin.x + self.extent as f64 + buffer,
+                y: origin.y + self.extent as f64 + buffer,
+            },
+        );
+        let is_within = clip.min().x <= bounds.min().x
+            && clip.min().y <= bounds.min().y
+            && bounds.max().x <= clip.max().x
+            && bounds.max().y <= clip.max().y;
+        let to_tile = |coords: &[Coord<f64>]| -> Vec<(i32, i32)> {
+            let mut points: Vec<(i32, i32)> = Vec::with_capacity(coords.len());
+            for coord in coords {
+                let point = (
+                    (coord.x - origin.x).round() as i32,
+                    (coord.y - origin.y).round() as i32,
+                );
+                if points.last() != Some(&point) {
+                    points.push(point);
+                }
+            }
+            points
+        };
+
+        let mut encoder = GeometryEncoder::new();
+        match world {
+            WorldGeometry::Point(point) => {
+                if is_within {
+                    encoder.add_points(&to_tile(&[*point]));
+                }
+                (Tile_GeomType::POINT, encoder)
+            }
+            WorldGeometry::Line(line_string) => {
+                let lines = if is_within {
+                    vec![line_string.clone()]
+                } else {
+                    clip.to_polygon()
+                        .clip(&MultiLineString::new(vec![line_string.clone()]), false)
+                        .0
+                };
+                for line in lines {
+                    encoder.add_line(&to_tile(&line.0));
+                }
+                (Tile_GeomType::LINESTRING, encoder)
+            }
+            WorldGeometry::Polygon(polygon) => {
+                let polygons = if is_within {
+                    vec![polygon.clone()]
+                } else {
+                    polygon
+                        .intersection(&clip.to_polygon())
+                        .orient(Direction::Default)
+                        .0
+                };
+                for polygon in polygons {
+                    let exterior = ring_points(to_tile(&polygon.exterior().0));
+                    if exterior.is_empty() {
+                        continue;
+                    }
+                    encoder.add_ring(&exterior);
+                    for interior in polygon.interiors() {
+                        encoder.add_ring(&ring_points(to_tile(&interior.0)));
+                    }
+                }
+                (Tile_GeomType::POLYGON, encoder)
+            }
+        }
+    }
+}
+
+/// A geometry projected to the tile units of a zoom level.
+enum WorldGeometry {
+    Point(Coord<f64>),
+    Line(LineString<f64>),
+    Polygon(Polygon<f64>),
+}
+
+/// Returns the points of a closed ring without its closing point, or none if rounding
+/// collapsed the ring.
+fn ring_points(mut points: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    let doubled_area: i64 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 as i64 * b.1 as i64 - b.0 as i64 * a.1 as i64)
+        .sum();
+    if points.len() < 3 || doubled_area == 0 {
+        return Vec::new();
+    }
+    points
+}
+
+/// Returns the locations of the nodes of a way in degrees, or `None` if one is unknown.
+fn locate(way: &Way, location_index: &LocationIndex) -> Option<Vec<Coord<f64>>> {
+    way.way_nodes
+        .iter()
+        .map(|way_node| {
+            let (latitude, longitude) = match (way_node.latitude, way_node.longitude) {
+                (Some(latitude), Some(longitude)) => (latitude, longitude),
+                _ => location_index.get(way_node.id)?,
+            };
+            Some(Coord {
+                x: longitude as f64 / 1e9,
+                y: latitude as f64 / 1e9,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, WayNode};
+
+    #[test]
+    fn test_tile_id() {
+        let tile_id = TileId::from_lon_lat(1.5218, 42.5063, 14);
+        assert_eq!((tile_id.x, tile_id.y), (8261, 6050));
+        let bounds = tile_id.bounds();
+        assert!(bounds.min().x <= 1.5218 && 1.5218 <= bounds.max().x);
+        assert!(bounds.min().y <= 42.5063 && 42.5063 <= bounds.max().y);
+        assert_eq!(
+            TileId::from_lon_lat(180.0, -90.0, 1),
+            TileId {
+                zoom: 1,
+                x: 1,
+                y: 1
+            }
+        );
+    }
+
+    fn node(id: i64, longitude: f64, latitude: f64, tags: Vec<Tag>) -> Element {
+        Element::Node(Node {
+            id,
+            latitude: (latitude * 1e9) as i64,
+            longitude: (longitude * 1e9) as i64,
+            visible: true,
+            tags,
+            ..Default::default()
+        })
+    }
+
+    fn tag(key: &str, value: &str) -> Tag {
+        Tag {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let way = |id: i64, node_ids: &[i64], tags: Vec<Tag>| {
+            Element::Way(Way {
+                id,
+                visible: true,
+                tags,
+                way_nodes: node_ids
+                    .iter()
+                    .map(|id| WayNode::new_without_coords(*id))
+                    .collect(),
+                ..Default::default()
+            })
+        };
+        let elements = vec![
+            node(1, 0.001, 0.001, vec![tag("amenity", "cafe")]),
+            node(2, 0.002, 0.001, Vec::new()),
+            node(3, 0.002, 0.002, Vec::new()),
+            node(4, 0.001, 0.002, Vec::new()),
+            // A road crossing the prime meridian
+            node(5, -0.001, 0.0015, Vec::new()),
+            way(10, &[1, 2, 3, 4, 1], vec![tag("building", "yes")]),
+            way(11, &[5, 2], vec![tag("highway", "residential")]),
+            // Node 6 is missing, so the way is left out
+            way(12, &[1, 6], vec![tag("highway", "service")]),
+        ];
+        let mut generator = TileGenerator::new(vec![
+            TileLayer::roads(),
+            TileLayer::buildings(),
+            TileLayer::pois(),
+        ]);
+        generator.set_zoom_range(14, 14);
+        let tiles = generator.generate(elements.into_iter()).unwrap();
+        // Only the road reaches the tile west of the prime meridian
+        let tile_ids: Vec<(u32, u32)> = tiles.keys().map(|id| (id.x, id.y)).collect();
+        assert_eq!(tile_ids, vec![(8191, 8191), (8192, 8191)]);
+
+        let tile = Tile::parse_from_bytes(
+            &tiles[&TileId {
+                zoom: 14,
+                x: 8192,
+                y: 8191,
+            }],
+        )
+        .unwrap();
+        let names: Vec<&str> = tile
+            .get_layers()
+            .iter()
+            .map(|layer| layer.get_name())
+            .collect();
+        assert_eq!(names, vec!["roads", "buildings", "pois"]);
+        let roads = &tile.get_layers()[0];
+        assert_eq!(roads.get_features().len(), 1);
+        assert_eq!(roads.get_features()[0].get_id(), 11);
+        assert_eq!(roads.get_keys(), ["highway"]);
+        let buildings = &tile.get_layers()[1];
+        assert_eq!(
+            buildings.get_features()[0].get_field_type(),
+            Tile_GeomType::POLYGON
+        );
+    }
+}
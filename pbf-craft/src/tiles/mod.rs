@@ -0,0 +1,4 @@
+mod encoder;
+mod generator;
+
+pub use generator::{LayerKind, TileGenerator, TileId, TileLayer};
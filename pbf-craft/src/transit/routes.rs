@@ -0,0 +1,379 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Element, ElementType, Relation, Tag};
+use crate::readers::{ElementSource, IterableReader};
+use crate::utils::IdSet;
+
+/// A stop or platform of a route, in the order it is served.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitStop {
+    /// The position of the stop in the route, starting at 0.
+    pub sequence: usize,
+    pub element_type: ElementType,
+    pub id: i64,
+    /// The role of the member, e.g. `stop`, `stop_entry_only` or `platform`.
+    pub role: String,
+    pub name: Option<String>,
+    /// The location in degrees. Platforms mapped as ways are located at the mean of their
+    /// nodes. Stops whose location is unknown, e.g. outside of an extract, have none.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// A public transport route assembled from a `type=route` relation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitRoute {
+    pub relation_id: i64,
+    /// The mode of transport, e.g. `bus`, `tram` or `train`.
+    pub route: String,
+    pub name: Option<String>,
+    #[serde(rename = "ref")]
+    pub route_ref: Option<String>,
+    pub operator: Option<String>,
+    pub network: Option<String>,
+    pub colour: Option<String>,
+    pub stops: Vec<TransitStop>,
+    /// The path of the route as `[longitude, latitude]` pairs in degrees, joined from the ways
+    /// of the relation in their order. A new segment starts wherever two consecutive ways
+    /// don't connect.
+    pub geometry: Vec<Vec<[f64; 2]>>,
+    /// The number of members which are not in the data, e.g. because they lie outside of an
+    /// extract.
+    pub missing_members: usize,
+}
+
+/// Extracts public transport routes, with their stop sequences and way geometries, from the
+/// route relations of a PBF file.
+///
+/// Stops are the members with a role starting with `stop` or `platform`, as in the public
+/// transport schema, and the path is assembled from the other way members. The routes can be
+/// serialized to JSON directly, e.g. to derive GTFS `stops.txt` and `shapes.txt` files.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::transit::TransitExtractor;
+///
+/// let mut extractor = TransitExtractor::new();
+/// extractor.set_route_types(["bus", "trolleybus"]);
+/// let routes = extractor
+///     .extract_from_path("resources/andorra-latest.osm.pbf")
+///     .unwrap();
+/// for route in &routes {
+///     println!("{:?} has {} stops", route.name, route.stops.len());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TransitExtractor {
+    route_types: HashSet<String>,
+}
+
+impl Default for TransitExtractor {
+    fn default() -> Self {
+        Self {
+            route_types: ["bus", "tram", "train"]
+                .iter()
+                .map(|route_type| route_type.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// The data a route needs from the ways and nodes of the file.
+#[derive(Default)]
+struct Members {
+    way_node_ids: HashMap<i64, Vec<i64>>,
+    /// The latitudes and longitudes of the needed nodes in nanodegrees.
+    locations: HashMap<i64, (i64, i64)>,
+    names: BTreeMap<(ElementType, i64), String>,
+}
+
+impl TransitExtractor {
+    /// Creates an extractor of the `bus`, `tram` and `train` routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the values of the `route` tag of the relations to extract.
+    pub fn set_route_types<I, V>(&mut self, route_types: I)
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<String>,
+    {
+        self.route_types = route_types.into_iter().map(Into::into).collect();
+    }
+
+    /// Extracts the routes of a PBF file, ordered by relation ID.
+    ///
+    /// The file is read three times, each time skipping the element types which aren't
+    /// needed: for the relations, for the ways they reference and for the nodes of both.
+    pub fn extract_from_path<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<Vec<TransitRoute>> {
+        let mut reader = IterableReader::from_path(&path)?;
+        reader.skip_to(ElementType::Relation)?;
+        let relations = self.read_relations(reader)?;
+
+        let mut members = Members::default();
+        let mut wanted_ways = IdSet::new();
+        for relation in &relations {
+            for member in &relation.members {
+                if member.member_type == ElementType::Way {
+                    wanted_ways.insert(member.member_id);
+                }
+            }
+        }
+        let mut reader = IterableReader::from_path(&path)?;
+        reader.skip_to(ElementType::Way)?;
+        read_ways(reader, &wanted_ways, &mut members)?;
+
+        let mut wanted_nodes = IdSet::new();
+        for relation in &relations {
+            for member in &relation.members {
+                if member.member_type == ElementType::Node {
+                    wanted_nodes.insert(member.member_id);
+                }
+            }
+        }
+        for node_ids in members.way_node_ids.values() {
+            for node_id in node_ids {
+                wanted_nodes.insert(*node_id);
+            }
+        }
+        read_nodes(
+            IterableReader::from_path(&path)?,
+            &wanted_nodes,
+            &mut members,
+        )?;
+
+        Ok(relations
+            .iter()
+            .map(|relation| assemble(relation, &members))
+            .collect())
+    }
+
+    fn read_relations<S: ElementSource>(&self, mut source: S) -> anyhow::Result<Vec<Relation>> {
+        let mut relations = Vec::new();
+        while let Some(element) = source.next_element()? {
+            if let Element::Relation(relation) = element {
+                let is_route = tag_value(&relation.tags, "type") == Some("route")
+                    && tag_value(&relation.tags, "route")
+                        .is_some_and(|route| self.route_types.contains(route));
+                if is_route {
+                    relations.push(relation);
+                }
+            }
+        }
+        relations.sort_by_key(|relation| relation.id);
+        Ok(relations)
+    }
+}
+
+fn read_ways<S: ElementSource>(
+    mut source: S,
+    wanted_ways: &IdSet,
+    members: &mut Members,
+) -> anyhow::Result<()> {
+    while let Some(element) = source.next_element()? {
+        match element {
+            Element::Way(way) if wanted_ways.contains(way.id) => {
+                if let Some(name) = tag_value(&way.tags, "name") {
+                    members
+                        .names
+                        .insert((ElementType::Way, way.id), name.to_string());
+                }
+                let node_ids = way.way_nodes.iter().map(|wn| wn.id).collect();
+                members.way_node_ids.insert(way.id, node_ids);
+            }
+            // Relations come last, so there is nothing left to read
+            Element::Relation(_) => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn read_nodes<S: ElementSource>(
+    mut source: S,
+    wanted_nodes: &IdSet,
+    members: &mut Members,
+) -> anyhow::Result<()> {
+    while let Some(element) = source.next_element()? {
+        match element {
+            Element::Node(node) => {
+                if !wanted_nodes.contains(node.id) {
+                    continue;
+                }
+                if let Some(name) = tag_value(&node.tags, "name") {
+                    members
+                        .names
+                        .insert((ElementType::Node, node.id), name.to_string());
+                }
+                members
+                    .locations
+                    .insert(node.id, (node.latitude, node.longitude));
+            }
+            // Nodes come first, so there is nothing left to read
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+fn tag_value<'a>(tags: &'a [Tag], key: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|tag| tag.key == key)
+        .map(|tag| tag.value.as_str())
+}
+
+fn to_degrees((latitude, longitude): (i64, i64)) -> [f64; 2] {
+    [longitude as f64 / 1e9, latitude as f64 / 1e9]
+}
+
+/// Builds a route from its relation and the data of its members.
+fn assemble(relation: &Relation, members: &Members) -> TransitRoute {
+    let tag = |key: &str| tag_value(&relation.tags, key).map(|value| value.to_string());
+    let mut stops = Vec::new();
+    let mut paths = Vec::new();
+    let mut missing_members = 0;
+    for member in &relation.members {
+        let is_stop = member.role.starts_with("stop") || member.role.starts_with("platform");
+        let location = match member.member_type {
+            ElementType::Node => members.locations.get(&member.member_id).copied(),
+            ElementType::Way => members
+                .way_node_ids
+                .get(&member.member_id)
+                .and_then(|node_ids| mean_location(node_ids, members)),
+            // Stop areas and other nested relations aren't resolved
+            ElementType::Relation => continue,
+        };
+        if location.is_none() {
+            missing_members += 1;
+        }
+        if is_stop {
+            let location = location.map(to_degrees);
+            stops.push(TransitStop {
+                sequence: stops.len(),
+                element_type: member.member_type.clone(),
+                id: member.member_id,
+                role: member.role.to_string(),
+                name: members
+                    .names
+                    .get(&(member.member_type.clone(), member.member_id))
+                    .cloned(),
+                latitude: location.map(|[_, latitude]| latitude),
+                longitude: location.map(|[longitude, _]| longitude),
+            });
+        } else if member.member_type == ElementType::Way {
+            if let Some(node_ids) = members.way_node_ids.get(&member.member_id) {
+                paths.push(node_ids.as_slice());
+            }
+        }
+    }
+
+    let geometry = join_paths(&paths)
+        .into_iter()
+        .map(|node_ids| {
+            node_ids
+                .iter()
+                .filter_map(|node_id| members.locations.get(node_id).copied())
+                .map(to_degrees)
+                .collect()
+        })
+        .collect();
+    TransitRoute {
+        relation_id: relation.id,
+        route: tag("route").unwrap_or_default(),
+        name: tag("name"),
+        route_ref: tag("ref"),
+        operator: tag("operator"),
+        network: tag("network"),
+        colour: tag("colour"),
+        stops,
+        geometry,
+        missing_members,
+    }
+}
+
+/// Returns the mean location of the known nodes, or `None` if none is known.
+fn mean_location(node_ids: &[i64], members: &Members) -> Option<(i64, i64)> {
+    let locations: Vec<(i64, i64)> = node_ids
+        .iter()
+        .filter_map(|node_id| members.locations.get(node_id).copied())
+        .collect();
+    if locations.is_empty() {
+        return None;
+    }
+    let count = locations.len() as i64;
+    let latitude: i64 = locations.iter().map(|(latitude, _)| latitude).sum();
+    let longitude: i64 = locations.iter().map(|(_, longitude)| longitude).sum();
+    Some((latitude / count, longitude / count))
+}
+
+/// Joins consecutive node paths sharing an end node, reversing them where needed. Paths which
+/// don't connect to the previous one start a new segment.
+fn join_paths(paths: &[&[i64]]) -> Vec<Vec<i64>> {
+    let mut segments: Vec<Vec<i64>> = Vec::new();
+    // Whether the last segment consists of a single path, which may still be reversed
+    let mut is_single = false;
+    for path in paths {
+        let (Some(first), Some(last)) = (path.first(), path.last()) else {
+            continue;
+        };
+        if let Some(segment) = segments.last_mut() {
+            let end = *segment.last().unwrap();
+            if is_single && end != *first && end != *last && [*first, *last].contains(&segment[0]) {
+                // The first way of a segment runs against the direction of the route
+                segment.reverse();
+            }
+            let end = *segment.last().unwrap();
+            if end == *first {
+                segment.extend_from_slice(&path[1..]);
+                is_single = false;
+                continue;
+            }
+            if end == *last {
+                segment.extend(path.iter().rev().skip(1));
+                is_single = false;
+                continue;
+            }
+        }
+        segments.push(path.to_vec());
+        is_single = true;
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_paths() {
+        let paths: Vec<&[i64]> = vec![&[2, 1], &[2, 3, 4], &[5, 4], &[7, 8], &[8, 9]];
+        assert_eq!(join_paths(&paths), vec![vec![1, 2, 3, 4, 5], vec![7, 8, 9]]);
+    }
+
+    #[test]
+    fn test_extract_from_path() {
+        let routes = TransitExtractor::new()
+            .extract_from_path("./resources/andorra-latest.osm.pbf")
+            .unwrap();
+        assert!(!routes.is_empty());
+        let route = routes
+            .iter()
+            .find(|route| route.relation_id == 11892035)
+            .unwrap();
+        assert_eq!(route.route, "bus");
+        assert_eq!(route.name.as_deref(), Some("L1"));
+        assert_eq!(route.stops[0].id, 8119501623);
+        assert!(route
+            .stops
+            .iter()
+            .enumerate()
+            .all(|(sequence, stop)| stop.sequence == sequence && stop.latitude.is_some()));
+        assert!(!route.geometry.is_empty());
+        assert!(route.geometry.iter().all(|segment| segment.len() >= 2));
+    }
+}
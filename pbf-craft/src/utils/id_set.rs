@@ -0,0 +1,427 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+const CHUNK_BITS: u32 = 16;
+const CHUNK_MASK: i64 = (1 << CHUNK_BITS) - 1;
+const WORDS_PER_BITMAP: usize = (1 << CHUNK_BITS) / 64;
+/// A sorted array chunk is turned into a bitmap once it holds more ids than this, the point at
+/// which the array would take more memory than the bitmap.
+const MAX_ARRAY_LENGTH: usize = 4096;
+
+#[derive(Clone)]
+enum Chunk {
+    Array(Vec<u16>),
+    Bitmap(Box<[u64; WORDS_PER_BITMAP]>),
+}
+
+impl Chunk {
+    fn len(&self) -> u64 {
+        match self {
+            Chunk::Array(values) => values.len() as u64,
+            Chunk::Bitmap(bitmap) => bitmap.iter().map(|word| word.count_ones() as u64).sum(),
+        }
+    }
+
+    fn contains(&self, low: u16) -> bool {
+        match self {
+            Chunk::Array(values) => values.binary_search(&low).is_ok(),
+            Chunk::Bitmap(bitmap) => bitmap[low as usize / 64] & (1 << (low % 64)) != 0,
+        }
+    }
+
+    fn values(&self) -> Vec<u16> {
+        match self {
+            Chunk::Array(values) => values.clone(),
+            Chunk::Bitmap(bitmap) => {
+                let mut values = Vec::new();
+                for (index, word) in bitmap.iter().enumerate() {
+                    let mut word = *word;
+                    while word != 0 {
+                        values.push((index * 64) as u16 + word.trailing_zeros() as u16);
+                        word &= word - 1;
+                    }
+                }
+                values
+            }
+        }
+    }
+
+    /// Builds a chunk from sorted values, choosing the smaller representation.
+    fn from_values(values: Vec<u16>) -> Self {
+        if values.len() <= MAX_ARRAY_LENGTH {
+            return Chunk::Array(values);
+        }
+        let mut bitmap = Box::new([0u64; WORDS_PER_BITMAP]);
+        for value in values {
+            bitmap[value as usize / 64] |= 1 << (value % 64);
+        }
+        Chunk::Bitmap(bitmap)
+    }
+
+    fn from_bitmap(bitmap: Box<[u64; WORDS_PER_BITMAP]>) -> Self {
+        let chunk = Chunk::Bitmap(bitmap);
+        if chunk.len() as usize <= MAX_ARRAY_LENGTH {
+            Chunk::Array(chunk.values())
+        } else {
+            chunk
+        }
+    }
+
+    fn union(&self, other: &Chunk) -> Chunk {
+        match (self, other) {
+            (Chunk::Bitmap(a), Chunk::Bitmap(b)) => {
+                let mut bitmap = a.clone();
+                for (word, other) in bitmap.iter_mut().zip(b.iter()) {
+                    *word |= other;
+                }
+                Chunk::Bitmap(bitmap)
+            }
+            (Chunk::Bitmap(bitmap), Chunk::Array(values))
+            | (Chunk::Array(values), Chunk::Bitmap(bitmap)) => {
+                let mut bitmap = bitmap.clone();
+                for value in values {
+                    bitmap[*value as usize / 64] |= 1 << (value % 64);
+                }
+                Chunk::Bitmap(bitmap)
+            }
+            (Chunk::Array(a), Chunk::Array(b)) => {
+                let mut values = Vec::with_capacity(a.len() + b.len());
+                let (mut i, mut j) = (0, 0);
+                while i < a.len() && j < b.len() {
+                    if a[i] < b[j] {
+                        values.push(a[i]);
+                        i += 1;
+                    } else if a[i] > b[j] {
+                        values.push(b[j]);
+                        j += 1;
+                    } else {
+                        values.push(a[i]);
+                        i += 1;
+                        j += 1;
+                    }
+                }
+                values.extend_from_slice(&a[i..]);
+                values.extend_from_slice(&b[j..]);
+                Chunk::from_values(values)
+            }
+        }
+    }
+
+    fn intersection(&self, other: &Chunk) -> Chunk {
+        match (self, other) {
+            (Chunk::Bitmap(a), Chunk::Bitmap(b)) => {
+                let mut bitmap = a.clone();
+                for (word, other) in bitmap.iter_mut().zip(b.iter()) {
+                    *word &= other;
+                }
+                Chunk::from_bitmap(bitmap)
+            }
+            (Chunk::Array(values), other) | (other, Chunk::Array(values)) => Chunk::Array(
+                values
+                    .iter()
+                    .copied()
+                    .filter(|value| other.contains(*value))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A compact set of element ids for marking passes over large files.
+///
+/// Ids are split into chunks of 65536 consecutive ids. A chunk with few ids stores them as a
+/// sorted array, and a chunk with many ids as a bitmap of 8 KiB, so dense id ranges like those
+/// of OSM nodes take about one bit per id, while sparse ids take two bytes each. A
+/// `HashSet<i64>` needs several times as much memory, which matters when marking billions of
+/// nodes.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::utils::IdSet;
+///
+/// let mut ways: IdSet = [1, 2, 3].into_iter().collect();
+/// let relations: IdSet = [3, 4].into_iter().collect();
+/// ways.insert(5);
+/// assert!(ways.contains(5));
+/// assert_eq!(ways.intersection(&relations).iter().collect::<Vec<i64>>(), vec![3]);
+/// assert_eq!(ways.union(&relations).len(), 5);
+/// ```
+#[derive(Clone, Default)]
+pub struct IdSet {
+    chunks: BTreeMap<i64, Chunk>,
+    len: u64,
+}
+
+fn split(id: i64) -> (i64, u16) {
+    (id >> CHUNK_BITS, (id & CHUNK_MASK) as u16)
+}
+
+impl IdSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an id, returning whether it was newly inserted.
+    pub fn insert(&mut self, id: i64) -> bool {
+        let (high, low) = split(id);
+        let chunk = self
+            .chunks
+            .entry(high)
+            .or_insert_with(|| Chunk::Array(Vec::new()));
+        let inserted = match chunk {
+            Chunk::Array(values) => match values.binary_search(&low) {
+                Ok(_) => false,
+                Err(index) => {
+                    values.insert(index, low);
+                    if values.len() > MAX_ARRAY_LENGTH {
+                        *chunk = Chunk::from_values(std::mem::take(values));
+                    }
+                    true
+                }
+            },
+            Chunk::Bitmap(bitmap) => {
+                let word = &mut bitmap[low as usize / 64];
+                let mask = 1 << (low % 64);
+                let inserted = *word & mask == 0;
+                *word |= mask;
+                inserted
+            }
+        };
+        if inserted {
+            self.len += 1;
+        }
+        inserted
+    }
+
+    /// Removes an id, returning whether it was present.
+    pub fn remove(&mut self, id: i64) -> bool {
+        let (high, low) = split(id);
+        let Some(chunk) = self.chunks.get_mut(&high) else {
+            return false;
+        };
+        let removed = match chunk {
+            Chunk::Array(values) => match values.binary_search(&low) {
+                Ok(index) => {
+                    values.remove(index);
+                    true
+                }
+                Err(_) => false,
+            },
+            Chunk::Bitmap(bitmap) => {
+                let word = &mut bitmap[low as usize / 64];
+                let mask = 1 << (low % 64);
+                let removed = *word & mask != 0;
+                *word &= !mask;
+                removed
+            }
+        };
+        if removed {
+            self.len -= 1;
+            if matches!(chunk, Chunk::Array(values) if values.is_empty()) {
+                self.chunks.remove(&high);
+            }
+        }
+        removed
+    }
+
+    pub fn contains(&self, id: i64) -> bool {
+        let (high, low) = split(id);
+        self.chunks
+            .get(&high)
+            .is_some_and(|chunk| chunk.contains(low))
+    }
+
+    /// Returns the number of ids in the set.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.len = 0;
+    }
+
+    /// Returns the ids in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        self.chunks.iter().flat_map(|(high, chunk)| {
+            chunk
+                .values()
+                .into_iter()
+                .map(move |low| (high << CHUNK_BITS) | low as i64)
+        })
+    }
+
+    /// Returns a set with the ids contained in either set.
+    pub fn union(&self, other: &IdSet) -> IdSet {
+        let mut result = self.clone();
+        result.union_with(other);
+        result
+    }
+
+    /// Adds all ids of another set.
+    pub fn union_with(&mut self, other: &IdSet) {
+        for (high, other_chunk) in &other.chunks {
+            let chunk = match self.chunks.get(high) {
+                Some(chunk) => chunk.union(other_chunk),
+                None => other_chunk.clone(),
+            };
+            self.chunks.insert(*high, chunk);
+        }
+        self.len = self.chunks.values().map(|chunk| chunk.len()).sum();
+    }
+
+    /// Writes the set in a binary format, e.g. to save it with an index.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        writer.write_u64::<LittleEndian>(self.chunks.len() as u64)?;
+        for (high, chunk) in &self.chunks {
+            writer.write_i64::<LittleEndian>(*high)?;
+            match chunk {
+                Chunk::Array(values) => {
+                    writer.write_u8(0)?;
+                    writer.write_u16::<LittleEndian>(values.len() as u16)?;
+                    for value in values {
+                        writer.write_u16::<LittleEndian>(*value)?;
+                    }
+                }
+                Chunk::Bitmap(bitmap) => {
+                    writer.write_u8(1)?;
+                    for word in bitmap.iter() {
+                        writer.write_u64::<LittleEndian>(*word)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a set written by `write_to`.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn read_from<R: Read>(reader: &mut R) -> anyhow::Result<IdSet> {
+        let mut set = IdSet::new();
+        for _ in 0..reader.read_u64::<LittleEndian>()? {
+            let high = reader.read_i64::<LittleEndian>()?;
+            let chunk = match reader.read_u8()? {
+                0 => {
+                    let length = reader.read_u16::<LittleEndian>()?;
+                    let mut values = Vec::with_capacity(length as usize);
+                    for _ in 0..length {
+                        values.push(reader.read_u16::<LittleEndian>()?);
+                    }
+                    Chunk::Array(values)
+                }
+                1 => {
+                    let mut bitmap = Box::new([0u64; WORDS_PER_BITMAP]);
+                    for word in bitmap.iter_mut() {
+                        *word = reader.read_u64::<LittleEndian>()?;
+                    }
+                    Chunk::Bitmap(bitmap)
+                }
+                kind => bail!("Unsupported id set chunk kind: {}", kind),
+            };
+            set.len += chunk.len();
+            set.chunks.insert(high, chunk);
+        }
+        Ok(set)
+    }
+
+    /// Returns a set with the ids contained in both sets.
+    pub fn intersection(&self, other: &IdSet) -> IdSet {
+        let mut chunks = BTreeMap::new();
+        let mut len = 0;
+        for (high, chunk) in &self.chunks {
+            if let Some(other_chunk) = other.chunks.get(high) {
+                let chunk = chunk.intersection(other_chunk);
+                let chunk_len = chunk.len();
+                if chunk_len > 0 {
+                    len += chunk_len;
+                    chunks.insert(*high, chunk);
+                }
+            }
+        }
+        IdSet { chunks, len }
+    }
+}
+
+impl FromIterator<i64> for IdSet {
+    fn from_iter<T: IntoIterator<Item = i64>>(iter: T) -> Self {
+        let mut set = IdSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<i64> for IdSet {
+    fn extend<T: IntoIterator<Item = i64>>(&mut self, iter: T) {
+        for id in iter {
+            self.insert(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_contains() {
+        let mut set = IdSet::new();
+        assert!(set.insert(-5));
+        assert!(!set.insert(-5));
+        for id in 0..10_000 {
+            set.insert(id * 3);
+        }
+        assert_eq!(set.len(), 10_001);
+        assert!(set.contains(-5));
+        assert!(set.contains(29_997));
+        assert!(!set.contains(29_998));
+        assert!(!set.contains(-4));
+        assert!(!set.contains(1 << 40));
+
+        assert!(set.remove(-5));
+        assert!(!set.remove(-5));
+        assert!(set.remove(3));
+        assert_eq!(set.len(), 9_999);
+        assert_eq!(set.iter().take(3).collect::<Vec<i64>>(), vec![0, 6, 9]);
+    }
+
+    #[test]
+    fn test_set_operations() {
+        // a dense chunk, a sparse chunk and a negative id
+        let a: IdSet = (0..10_000).chain([100_000, -1]).collect();
+        let b: IdSet = (5_000..5_010).chain([100_000, 200_000]).collect();
+
+        let union = a.union(&b);
+        assert_eq!(union.len(), 10_003);
+        assert_eq!(union.iter().next(), Some(-1));
+        assert_eq!(union.iter().last(), Some(200_000));
+
+        let intersection = a.intersection(&b);
+        let expected: Vec<i64> = (5_000..5_010).chain([100_000]).collect();
+        assert_eq!(intersection.iter().collect::<Vec<i64>>(), expected);
+        assert_eq!(intersection.len(), 11);
+
+        let dense: IdSet = (0..8_000).collect();
+        let other_dense: IdSet = (7_990..16_000).collect();
+        assert_eq!(dense.intersection(&other_dense).len(), 10);
+        assert_eq!(dense.union(&other_dense).len(), 16_000);
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let set: IdSet = (0..10_000).chain([100_000, -1]).collect();
+        let mut data = Vec::new();
+        set.write_to(&mut data).unwrap();
+        let read = IdSet::read_from(&mut data.as_slice()).unwrap();
+        assert_eq!(read.len(), set.len());
+        assert!(read.iter().eq(set.iter()));
+        assert!(IdSet::read_from(&mut &data[..data.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::{BufReader, BufWriter};
+use std::io::{ErrorKind, Read, Write};
+use std::mem::size_of;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use super::{MemoryBudget, MemoryReservation};
+use crate::models::{Element, Way};
+use crate::readers::ElementSource;
+#[cfg(feature = "fs")]
+use crate::readers::IterableReader;
+
+/// The precision the locations are stored with, in nanodegrees. It matches the default
+/// granularity of PBF files, so storing a location loses nothing a PBF file would keep.
+const PRECISION: i64 = 100;
+/// The coordinate osmium marks undefined locations with.
+const OSMIUM_UNDEFINED: i32 = i32::MAX;
+/// The memory taken by a location in the index, including the control byte of the hash map.
+const ENTRY_SIZE: usize = size_of::<(i64, (i32, i32))>() + 1;
+
+/// The node location file formats of osmium, which can be shared with osmium pipelines, e.g.
+/// the files of `osmium add-locations-to-ways --index-type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OsmiumIndexFormat {
+    /// `dense_file_array`: the locations of all node IDs from 0 up to the largest one, with
+    /// undefined locations for missing nodes. Suited to planet-sized data.
+    DenseFileArray,
+    /// `sparse_file_array`: the pairs of node ID and location, sorted by ID. Suited to extracts.
+    SparseFileArray,
+}
+
+/// An index from node IDs to node locations, used to add the locations of nodes to ways.
+///
+/// Locations are stored with a precision of 100 nanodegrees, which lets both coordinates of a
+/// node fit into 8 bytes.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::Element;
+/// use pbf_craft::readers::IterableReader;
+/// use pbf_craft::utils::LocationIndex;
+///
+/// let path = "resources/andorra-latest.osm.pbf";
+/// let index = LocationIndex::from_path(path).unwrap();
+/// for element in IterableReader::from_path(path).unwrap() {
+///     if let Element::Way(mut way) = element {
+///         index.fill_way(&mut way).unwrap();
+///         assert!(way.way_nodes.iter().all(|way_node| way_node.latitude.is_some()));
+///     }
+/// }
+/// ```
+#[derive(Default)]
+pub struct LocationIndex {
+    locations: HashMap<i64, (i32, i32)>,
+    reservation: Option<MemoryReservation>,
+}
+
+impl LocationIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds an index of the locations of all nodes of a source.
+    pub fn from_source<S: ElementSource>(mut source: S) -> anyhow::Result<Self> {
+        let mut index = Self::new();
+        while let Some(element) = source.next_element()? {
+            match element {
+                Element::Node(node) => index.insert(node.id, node.latitude, node.longitude),
+                // Nodes come first, so there is nothing left to index
+                _ => break,
+            }
+        }
+        Ok(index)
+    }
+
+    /// Builds an index of the locations of all nodes of a PBF file.
+    #[cfg(feature = "fs")]
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::from_source(IterableReader::from_path(path)?)
+    }
+
+    /// Charges the memory of the index to a budget, see `MemoryBudget`. The index can't give
+    /// memory back, so it's charged even if this exceeds the budget.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        self.reservation = Some(budget.reservation());
+        self.update_reservation();
+    }
+
+    fn update_reservation(&mut self) {
+        if let Some(reservation) = &mut self.reservation {
+            reservation.resize(self.locations.capacity() * ENTRY_SIZE);
+        }
+    }
+
+    /// Adds or replaces the location of a node, given in nanodegrees.
+    pub fn insert(&mut self, id: i64, latitude: i64, longitude: i64) {
+        self.locations.insert(
+            id,
+            (
+                (latitude / PRECISION) as i32,
+                (longitude / PRECISION) as i32,
+            ),
+        );
+        self.update_reservation();
+    }
+
+    /// Returns the latitude and longitude of a node in nanodegrees.
+    pub fn get(&self, id: i64) -> Option<(i64, i64)> {
+        self.locations.get(&id).map(|(latitude, longitude)| {
+            (*latitude as i64 * PRECISION, *longitude as i64 * PRECISION)
+        })
+    }
+
+    /// Reads a node location file written by osmium. The file stores native-endian values, so it
+    /// must come from a little-endian machine.
+    #[cfg(feature = "fs")]
+    pub fn from_osmium_file<P: AsRef<Path>>(
+        path: P,
+        format: OsmiumIndexFormat,
+    ) -> anyhow::Result<Self> {
+        Self::read_osmium(BufReader::new(File::open(path)?), format)
+    }
+
+    /// Reads node locations in one of the osmium formats.
+    pub fn read_osmium<R: Read>(mut reader: R, format: OsmiumIndexFormat) -> anyhow::Result<Self> {
+        let mut index = Self::new();
+        let mut next_id = 0u64;
+        loop {
+            let id = match format {
+                OsmiumIndexFormat::DenseFileArray => {
+                    next_id += 1;
+                    next_id - 1
+                }
+                OsmiumIndexFormat::SparseFileArray => match reader.read_u64::<LittleEndian>() {
+                    Ok(id) => id,
+                    Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+                    Err(err) => return Err(err.into()),
+                },
+            };
+            let longitude = match reader.read_i32::<LittleEndian>() {
+                Ok(longitude) => longitude,
+                Err(err)
+                    if err.kind() == ErrorKind::UnexpectedEof
+                        && format == OsmiumIndexFormat::DenseFileArray =>
+                {
+                    break
+                }
+                Err(err) => return Err(err.into()),
+            };
+            let latitude = reader.read_i32::<LittleEndian>()?;
+            if longitude != OSMIUM_UNDEFINED || latitude != OSMIUM_UNDEFINED {
+                index
+                    .locations
+                    .insert(i64::try_from(id)?, (latitude, longitude));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Writes the index to a node location file readable by osmium.
+    #[cfg(feature = "fs")]
+    pub fn write_osmium_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: OsmiumIndexFormat,
+    ) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_osmium(&mut writer, format)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the node locations in one of the osmium formats. Fails if the index contains
+    /// negative node IDs, which osmium can't store.
+    pub fn write_osmium<W: Write>(
+        &self,
+        writer: &mut W,
+        format: OsmiumIndexFormat,
+    ) -> anyhow::Result<()> {
+        let mut ids: Vec<i64> = self.locations.keys().copied().collect();
+        ids.sort_unstable();
+        if let Some(id) = ids.first().filter(|id| **id < 0) {
+            bail!("Node {} has a negative ID, which osmium can't store", id);
+        }
+
+        let mut next_id = 0;
+        for id in ids {
+            let (latitude, longitude) = self.locations[&id];
+            match format {
+                OsmiumIndexFormat::DenseFileArray => {
+                    while next_id < id {
+                        writer.write_i32::<LittleEndian>(OSMIUM_UNDEFINED)?;
+                        writer.write_i32::<LittleEndian>(OSMIUM_UNDEFINED)?;
+                        next_id += 1;
+                    }
+                    next_id += 1;
+                }
+                OsmiumIndexFormat::SparseFileArray => {
+                    writer.write_u64::<LittleEndian>(id as u64)?
+                }
+            }
+            writer.write_i32::<LittleEndian>(longitude)?;
+            writer.write_i32::<LittleEndian>(latitude)?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Sets the locations of the way nodes that have none.
+    ///
+    /// Fails if the index does not know the location of one of them, in which case the way
+    /// may have been partially filled.
+    pub fn fill_way(&self, way: &mut Way) -> anyhow::Result<()> {
+        for way_node in way.way_nodes.iter_mut() {
+            if way_node.latitude.is_some() && way_node.longitude.is_some() {
+                continue;
+            }
+            match self.get(way_node.id) {
+                Some((latitude, longitude)) => {
+                    way_node.latitude = Some(latitude);
+                    way_node.longitude = Some(longitude);
+                }
+                None => bail!(
+                    "The location of node {} of way {} is unknown",
+                    way_node.id,
+                    way.id
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WayNode;
+
+    #[test]
+    fn test_fill_way() {
+        let mut index = LocationIndex::new();
+        index.insert(1, 42_512_345_678, 1_523_456_789);
+        index.insert(2, -33_900_000_000, -151_200_000_000);
+        assert_eq!(index.get(1), Some((42_512_345_600, 1_523_456_700)));
+        assert_eq!(index.get(3), None);
+
+        let mut way = Way {
+            id: 10,
+            way_nodes: vec![
+                WayNode::new_without_coords(1),
+                WayNode::new(2, 5, 5),
+                WayNode::new_without_coords(2),
+            ],
+            ..Default::default()
+        };
+        index.fill_way(&mut way).unwrap();
+        assert_eq!(way.way_nodes[0].latitude, Some(42_512_345_600));
+        assert_eq!(way.way_nodes[1].latitude, Some(5));
+        assert_eq!(way.way_nodes[2].longitude, Some(-151_200_000_000));
+
+        way.way_nodes.push(WayNode::new_without_coords(3));
+        assert!(index.fill_way(&mut way).is_err());
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = MemoryBudget::new(1024);
+        let mut index = LocationIndex::new();
+        index.insert(1, 0, 0);
+        index.set_memory_budget(budget.clone());
+        let used = budget.used();
+        assert!(used >= ENTRY_SIZE);
+        for id in 2..200 {
+            index.insert(id, 0, 0);
+        }
+        // The index is charged in full even if this exceeds the budget
+        assert!(budget.used() > used);
+        assert!(budget.is_exceeded());
+        drop(index);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn test_osmium_formats() {
+        let mut index = LocationIndex::new();
+        index.insert(2, 42_512_345_600, 1_523_456_700);
+        index.insert(5, -33_900_000_000, -151_200_000_000);
+
+        let mut dense = Vec::new();
+        index
+            .write_osmium(&mut dense, OsmiumIndexFormat::DenseFileArray)
+            .unwrap();
+        // IDs 0 to 5, with the longitude first in units of 100 nanodegrees
+        assert_eq!(dense.len(), 6 * 8);
+        assert_eq!(dense[0..4], i32::MAX.to_le_bytes());
+        assert_eq!(dense[16..20], 15_234_567i32.to_le_bytes());
+        assert_eq!(dense[20..24], 425_123_456i32.to_le_bytes());
+
+        let mut sparse = Vec::new();
+        index
+            .write_osmium(&mut sparse, OsmiumIndexFormat::SparseFileArray)
+            .unwrap();
+        assert_eq!(sparse.len(), 2 * 16);
+        assert_eq!(sparse[16..24], 5u64.to_le_bytes());
+
+        for (data, format) in [
+            (dense, OsmiumIndexFormat::DenseFileArray),
+            (sparse, OsmiumIndexFormat::SparseFileArray),
+        ] {
+            let read = LocationIndex::read_osmium(data.as_slice(), format).unwrap();
+            assert_eq!(read.len(), 2);
+            assert_eq!(read.get(2), index.get(2));
+            assert_eq!(read.get(5), index.get(5));
+        }
+
+        index.insert(-1, 0, 0);
+        let mut data = Vec::new();
+        assert!(index
+            .write_osmium(&mut data, OsmiumIndexFormat::SparseFileArray)
+            .is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::models::{Element, Node, OsmUser, Relation, RelationMember, Tag, Way, WayNode};
+use crate::readers::BlobData;
+
+/// A limit on the memory used by the components sharing it, in bytes.
+///
+/// A budget is a handle which is cheap to clone. Each component given one takes a
+/// `MemoryReservation` from it and keeps the reservation as large as the memory it holds:
+///
+/// * `CachedReader`, and so `IndexedReader::from_path_with_cache`, evicts blobs so that its
+///   cache fits in what the others leave.
+/// * `SortingWriter` spills the elements it holds to a temporary file when the budget is
+///   exhausted and merges the spilled runs when finished.
+/// * `LocationIndex` and `OsmDataset` can't give memory back, so they are charged in full and
+///   the components above give way to them. `is_exceeded` tells whether they went over.
+///
+/// The sizes are estimates of the heap memory of elements and indexes, not measurements.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::OsmDataset;
+/// use pbf_craft::readers::IterableReader;
+/// use pbf_craft::utils::{LocationIndex, MemoryBudget};
+///
+/// let path = "resources/andorra-latest.osm.pbf";
+/// let budget = MemoryBudget::new(512 * 1024 * 1024);
+/// let mut index = LocationIndex::from_path(path).unwrap();
+/// index.set_memory_budget(budget.clone());
+/// let mut dataset = OsmDataset::from_reader(IterableReader::from_path(path).unwrap()).unwrap();
+/// dataset.set_memory_budget(budget.clone());
+/// assert!(!budget.is_exceeded());
+///
+/// let used = budget.used();
+/// drop(dataset);
+/// assert!(budget.used() > 0 && budget.used() < used);
+/// ```
+#[derive(Clone)]
+pub struct MemoryBudget {
+    inner: Arc<BudgetInner>,
+}
+
+struct BudgetInner {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Creates a budget of `limit` bytes.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            inner: Arc::new(BudgetInner {
+                limit,
+                used: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Creates a budget without a limit, which only accounts for the memory used.
+    pub fn unlimited() -> Self {
+        Self::new(usize::MAX)
+    }
+
+    pub fn limit(&self) -> usize {
+        self.inner.limit
+    }
+
+    /// Returns the bytes held by all reservations of the budget.
+    pub fn used(&self) -> usize {
+        self.inner.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns the bytes which can still be reserved without exceeding the limit.
+    pub fn available(&self) -> usize {
+        self.limit().saturating_sub(self.used())
+    }
+
+    /// Returns `true` if the reservations hold more than the limit.
+    pub fn is_exceeded(&self) -> bool {
+        self.used() > self.limit()
+    }
+
+    /// Creates an empty reservation, which is released when dropped.
+    pub fn reservation(&self) -> MemoryReservation {
+        MemoryReservation {
+            budget: self.clone(),
+            size: 0,
+        }
+    }
+
+    fn try_acquire(&self, bytes: usize) -> bool {
+        self.inner
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(bytes).filter(|used| *used <= self.limit())
+            })
+            .is_ok()
+    }
+
+    fn acquire(&self, bytes: usize) {
+        self.inner.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn release(&self, bytes: usize) {
+        self.inner.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("limit", &self.limit())
+            .field("used", &self.used())
+            .finish()
+    }
+}
+
+/// The part of a `MemoryBudget` held by one component.
+///
+/// A clone reserves the same size again, as for a clone of the data it accounts for.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: MemoryBudget,
+    size: usize,
+}
+
+impl MemoryReservation {
+    pub fn budget(&self) -> &MemoryBudget {
+        &self.budget
+    }
+
+    /// Returns the bytes held by the reservation.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the size the reservation can have without exceeding the limit, given what the
+    /// other reservations of the budget hold.
+    pub fn max_size(&self) -> usize {
+        let others = self.budget.used().saturating_sub(self.size);
+        self.budget.limit().saturating_sub(others)
+    }
+
+    /// Grows the reservation by `bytes` if the budget has them available, and returns whether
+    /// it did.
+    pub fn try_grow(&mut self, bytes: usize) -> bool {
+        let granted = self.budget.try_acquire(bytes);
+        if granted {
+            self.size += bytes;
+        }
+        granted
+    }
+
+    /// Grows the reservation by `bytes`, even if this exceeds the limit.
+    pub fn grow(&mut self, bytes: usize) {
+        self.budget.acquire(bytes);
+        self.size += bytes;
+    }
+
+    /// Shrinks the reservation by `bytes`, or to zero if it holds less.
+    pub fn shrink(&mut self, bytes: usize) {
+        let bytes = bytes.min(self.size);
+        self.budget.release(bytes);
+        self.size -= bytes;
+    }
+
+    /// Grows or shrinks the reservation to `size` bytes, even if this exceeds the limit.
+    pub fn resize(&mut self, size: usize) {
+        if size > self.size {
+            self.grow(size - self.size);
+        } else {
+            self.shrink(self.size - size);
+        }
+    }
+}
+
+impl Clone for MemoryReservation {
+    fn clone(&self) -> Self {
+        let mut reservation = self.budget.reservation();
+        reservation.grow(self.size);
+        reservation
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget.release(self.size);
+    }
+}
+
+/// An estimate of the memory used by a value, including its heap allocations, in bytes.
+pub(crate) trait EstimatedSize {
+    fn estimated_size(&self) -> usize;
+}
+
+fn tags_size(tags: &[Tag]) -> usize {
+    tags.iter()
+        .map(|tag| size_of::<Tag>() + tag.key.len() + tag.value.len())
+        .sum()
+}
+
+fn user_size(user: &Option<OsmUser>) -> usize {
+    user.as_ref().map_or(0, |user| user.name.len())
+}
+
+impl EstimatedSize for Node {
+    fn estimated_size(&self) -> usize {
+        size_of::<Node>() + tags_size(&self.tags) + user_size(&self.user)
+    }
+}
+
+impl EstimatedSize for Way {
+    fn estimated_size(&self) -> usize {
+        size_of::<Way>()
+            + tags_size(&self.tags)
+            + user_size(&self.user)
+            + self.way_nodes.len() * size_of::<WayNode>()
+    }
+}
+
+impl EstimatedSize for Relation {
+    fn estimated_size(&self) -> usize {
+        size_of::<Relation>()
+            + tags_size(&self.tags)
+            + user_size(&self.user)
+            + self
+                .members
+                .iter()
+                .map(|member| size_of::<RelationMember>() + member.role.len())
+                .sum::<usize>()
+    }
+}
+
+impl EstimatedSize for Element {
+    fn estimated_size(&self) -> usize {
+        match self {
+            Element::Node(node) => node.estimated_size(),
+            Element::Way(way) => way.estimated_size(),
+            Element::Relation(relation) => relation.estimated_size(),
+        }
+    }
+}
+
+impl EstimatedSize for BlobData {
+    fn estimated_size(&self) -> usize {
+        size_of::<BlobData>()
+            + self.nodes.iter().map(Node::estimated_size).sum::<usize>()
+            + self.ways.iter().map(Way::estimated_size).sum::<usize>()
+            + self
+                .relations
+                .iter()
+                .map(Relation::estimated_size)
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = MemoryBudget::new(100);
+        let mut first = budget.reservation();
+        let mut second = budget.reservation();
+        assert!(first.try_grow(60));
+        assert!(!second.try_grow(60));
+        assert!(second.try_grow(40));
+        assert_eq!((budget.used(), budget.available()), (100, 0));
+        assert_eq!(first.max_size(), 60);
+
+        // Forced growth goes over the limit and leaves nothing to the others
+        second.grow(50);
+        assert!(budget.is_exceeded());
+        assert_eq!(first.max_size(), 10);
+
+        let third = second.clone();
+        assert_eq!(budget.used(), 240);
+        drop(third);
+        second.resize(10);
+        first.shrink(100);
+        assert_eq!((first.size(), budget.used()), (0, 10));
+        drop(second);
+        assert_eq!(budget.used(), 0);
+        assert!(!budget.is_exceeded());
+    }
+}
@@ -1 +1,17 @@
-pub mod file;
+#[cfg(feature = "fs")]
+pub(crate) mod file;
+mod id_set;
+mod location_index;
+mod memory_budget;
+#[cfg(feature = "proj")]
+mod projection;
+mod simplify;
+pub(crate) mod xml;
+
+pub use id_set::IdSet;
+pub use location_index::{LocationIndex, OsmiumIndexFormat};
+pub(crate) use memory_budget::EstimatedSize;
+pub use memory_budget::{MemoryBudget, MemoryReservation};
+#[cfg(feature = "proj")]
+pub use projection::Projection;
+pub use simplify::{simplify, simplify_planar, SimplifyAlgorithm};
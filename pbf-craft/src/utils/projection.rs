@@ -0,0 +1,105 @@
+use proj4rs::transform::transform;
+use proj4rs::Proj;
+
+/// The definition of the WGS84 coordinates of OpenStreetMap data.
+const WGS84: &str = "+proj=longlat +datum=WGS84 +no_defs";
+/// The definition of the web mercator projection, EPSG:3857.
+const WEB_MERCATOR: &str = "+proj=merc +a=6378137 +b=6378137 +lat_ts=0 +lon_0=0 +x_0=0 +y_0=0 +k=1 +units=m +nadgrids=@null +wktext +no_defs";
+
+/// A transform of the WGS84 coordinates of elements to another coordinate reference system,
+/// e.g. web mercator meters, for outputs consumed by analytics tools.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::utils::Projection;
+///
+/// let projection = Projection::web_mercator();
+/// let (x, y) = projection.project(42_500_000_000, 1_500_000_000).unwrap();
+/// assert!((x - 166_979.2).abs() < 0.1);
+/// assert!((y - 5_236_173.8).abs() < 0.1);
+/// ```
+pub struct Projection {
+    source: Proj,
+    target: Proj,
+}
+
+impl Projection {
+    /// Creates a projection to a coordinate reference system given by a PROJ string, such as
+    /// `+proj=utm +zone=31 +datum=WGS84`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the definition can't be parsed or has no forward
+    /// projection.
+    pub fn from_proj_string(definition: &str) -> anyhow::Result<Self> {
+        let target = Proj::from_proj_string(definition)
+            .map_err(|err| anyhow!("Invalid projection {:?}: {}", definition, err))?;
+        if !target.has_forward() {
+            bail!("The projection {:?} can't be projected to", definition);
+        }
+        Ok(Self {
+            source: Proj::from_proj_string(WGS84).expect("The WGS84 definition is valid"),
+            target,
+        })
+    }
+
+    /// Creates a projection to web mercator meters, EPSG:3857.
+    pub fn web_mercator() -> Self {
+        Self::from_proj_string(WEB_MERCATOR).expect("The web mercator definition is valid")
+    }
+
+    /// Projects a location in nanodegrees to the `(x, y)` coordinates of the target system,
+    /// e.g. easting and northing. Geographic targets return degrees.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the location is outside of the domain of the
+    /// projection, e.g. a pole in web mercator.
+    pub fn project(&self, latitude: i64, longitude: i64) -> anyhow::Result<(f64, f64)> {
+        let mut point = (
+            (longitude as f64 / 1e9).to_radians(),
+            (latitude as f64 / 1e9).to_radians(),
+            0.0,
+        );
+        transform(&self.source, &self.target, &mut point).map_err(|err| {
+            anyhow!(
+                "Unable to project latitude {} and longitude {}: {}",
+                latitude,
+                longitude,
+                err
+            )
+        })?;
+        if self.target.is_latlong() {
+            Ok((point.0.to_degrees(), point.1.to_degrees()))
+        } else {
+            Ok((point.0, point.1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projection() {
+        let mercator = Projection::web_mercator();
+        let (x, y) = mercator.project(0, 0).unwrap();
+        assert!(x.abs() < 1e-6 && y.abs() < 1e-6);
+        let (x, _) = mercator.project(0, 180_000_000_000).unwrap();
+        assert!((x - 20_037_508.34).abs() < 0.01);
+        assert!(mercator.project(90_000_000_000, 0).is_err());
+
+        // UTM zone 31 has its central meridian at 3 degrees east
+        let utm = Projection::from_proj_string("+proj=utm +zone=31 +datum=WGS84").unwrap();
+        let (x, y) = utm.project(0, 3_000_000_000).unwrap();
+        assert!((x - 500_000.0).abs() < 0.01 && y.abs() < 0.01);
+
+        let longlat = Projection::from_proj_string("+proj=longlat +datum=WGS84").unwrap();
+        let (x, y) = longlat.project(42_500_000_000, 1_500_000_000).unwrap();
+        assert!((x - 1.5).abs() < 1e-9 && (y - 42.5).abs() < 1e-9);
+
+        assert!(Projection::from_proj_string("+proj=unknown").is_err());
+    }
+}
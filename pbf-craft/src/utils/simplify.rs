@@ -0,0 +1,150 @@
+use geo::{Coord, LineString, SimplifyIdx, SimplifyVwIdx};
+
+/// The mean radius of the earth in meters, as used by the haversine distance of `geo`.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// The algorithms `simplify` can thin out lines with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SimplifyAlgorithm {
+    /// Douglas-Peucker: keeps the points farther than the tolerance from the simplified line.
+    /// It preserves the shape closely.
+    #[default]
+    DouglasPeucker,
+    /// Visvalingam-Whyatt: removes the points which form a triangle with their neighbours
+    /// smaller than the square of the tolerance. It smooths out small wiggles.
+    VisvalingamWhyatt,
+}
+
+/// Simplifies a line of locations in degrees, e.g. the resolved geometry of a way, within a
+/// tolerance in meters. The kept locations are returned unchanged.
+///
+/// The distances are measured in a local equirectangular projection around the middle latitude
+/// of the line, which is accurate for the extent of a way. A closed line which would lose its
+/// area keeps all its points.
+///
+/// # Example
+///
+/// ```rust
+/// use geo::Coord;
+/// use pbf_craft::utils::{simplify, SimplifyAlgorithm};
+///
+/// let coords = vec![
+///     Coord { x: 1.5, y: 42.5 },
+///     Coord { x: 1.50001, y: 42.500001 },
+///     Coord { x: 1.5001, y: 42.5 },
+/// ];
+/// let simplified = simplify(&coords, 1.0, SimplifyAlgorithm::DouglasPeucker);
+/// assert_eq!(simplified, vec![coords[0], coords[2]]);
+/// ```
+pub fn simplify(
+    coords: &[Coord<f64>],
+    tolerance: f64,
+    algorithm: SimplifyAlgorithm,
+) -> Vec<Coord<f64>> {
+    if coords.len() < 3 {
+        return coords.to_vec();
+    }
+    let (min_latitude, max_latitude) = coords
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(min, max), coord| {
+            (min.min(coord.y), max.max(coord.y))
+        });
+    let meters_per_degree = EARTH_RADIUS.to_radians();
+    let x_scale = meters_per_degree * ((min_latitude + max_latitude) / 2.0).to_radians().cos();
+    let projected: Vec<Coord<f64>> = coords
+        .iter()
+        .map(|coord| Coord {
+            x: coord.x * x_scale,
+            y: coord.y * meters_per_degree,
+        })
+        .collect();
+    simplify_indexes(&projected, tolerance, algorithm)
+        .into_iter()
+        .map(|index| coords[index])
+        .collect()
+}
+
+/// Simplifies a line of planar coordinates, e.g. projected ones, within a tolerance in their
+/// units. The kept coordinates are returned unchanged.
+pub fn simplify_planar(
+    coords: &[Coord<f64>],
+    tolerance: f64,
+    algorithm: SimplifyAlgorithm,
+) -> Vec<Coord<f64>> {
+    simplify_indexes(coords, tolerance, algorithm)
+        .into_iter()
+        .map(|index| coords[index])
+        .collect()
+}
+
+/// Returns the indexes of the coordinates kept by the simplification.
+fn simplify_indexes(
+    coords: &[Coord<f64>],
+    tolerance: f64,
+    algorithm: SimplifyAlgorithm,
+) -> Vec<usize> {
+    let line_string = LineString::from(coords.to_vec());
+    let indexes = match algorithm {
+        SimplifyAlgorithm::DouglasPeucker => line_string.simplify_idx(&tolerance),
+        SimplifyAlgorithm::VisvalingamWhyatt => {
+            line_string.simplify_vw_idx(&(tolerance * tolerance))
+        }
+    };
+    if line_string.is_closed() && indexes.len() < 4 {
+        return (0..coords.len()).collect();
+    }
+    indexes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simplify() {
+        // A line along the equator, with a point 5 m and one 50 m off it
+        let meters = |x: f64, y: f64| Coord {
+            x: x / EARTH_RADIUS.to_radians(),
+            y: y / EARTH_RADIUS.to_radians(),
+        };
+        let coords = vec![
+            meters(0.0, 0.0),
+            meters(100.0, 5.0),
+            meters(200.0, 0.0),
+            meters(300.0, 50.0),
+            meters(400.0, 0.0),
+        ];
+        for algorithm in [
+            SimplifyAlgorithm::DouglasPeucker,
+            SimplifyAlgorithm::VisvalingamWhyatt,
+        ] {
+            assert_eq!(simplify(&coords, 1.0, algorithm), coords);
+        }
+        assert_eq!(
+            simplify(&coords, 40.0, SimplifyAlgorithm::DouglasPeucker),
+            vec![coords[0], coords[3], coords[4]]
+        );
+        assert_eq!(
+            simplify(&coords, 100.0, SimplifyAlgorithm::DouglasPeucker),
+            vec![coords[0], coords[4]]
+        );
+        // The triangle of the 5 m offset is 500 m² large, the others 5000 m²
+        assert_eq!(
+            simplify(&coords, 40.0, SimplifyAlgorithm::VisvalingamWhyatt),
+            vec![coords[0], coords[2], coords[3], coords[4]]
+        );
+
+        // A small square keeps its points rather than collapsing
+        let square = vec![
+            meters(0.0, 0.0),
+            meters(1.0, 0.0),
+            meters(1.0, 1.0),
+            meters(0.0, 1.0),
+            meters(0.0, 0.0),
+        ];
+        assert_eq!(
+            simplify(&square, 10.0, SimplifyAlgorithm::DouglasPeucker),
+            square
+        );
+    }
+}
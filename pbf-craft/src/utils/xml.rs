@@ -0,0 +1,129 @@
+use std::io::Write;
+
+use chrono::SecondsFormat;
+
+use crate::models::{BasicElement, Element};
+
+/// Escapes a string for use in an XML attribute value.
+pub(crate) fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Formats a coordinate in nanodegrees as decimal degrees with 7 decimal places.
+pub(crate) fn format_coordinate(nanodegrees: i64) -> String {
+    let sign = if nanodegrees < 0 { "-" } else { "" };
+    let units = (nanodegrees.unsigned_abs() + 50) / 100;
+    format!("{}{}.{:07}", sign, units / 10_000_000, units % 10_000_000)
+}
+
+fn write_attributes<W: Write, E: BasicElement>(writer: &mut W, element: &E) -> anyhow::Result<()> {
+    write!(writer, " id=\"{}\"", element.get_id())?;
+    if element.get_version() > 0 {
+        write!(writer, " version=\"{}\"", element.get_version())?;
+    }
+    if let Some(timestamp) = element.get_timestamp() {
+        write!(
+            writer,
+            " timestamp=\"{}\"",
+            timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+        )?;
+    }
+    if let Some(user) = element.get_user() {
+        write!(
+            writer,
+            " uid=\"{}\" user=\"{}\"",
+            user.id,
+            escape(&user.name)
+        )?;
+    }
+    if element.get_changeset_id() > 0 {
+        write!(writer, " changeset=\"{}\"", element.get_changeset_id())?;
+    }
+    Ok(())
+}
+
+fn write_tags<W: Write, E: BasicElement>(
+    writer: &mut W,
+    element: &E,
+    indent: &str,
+) -> anyhow::Result<()> {
+    for tag in element.get_tags() {
+        writeln!(
+            writer,
+            "{}  <tag k=\"{}\" v=\"{}\"/>",
+            indent,
+            escape(&tag.key),
+            escape(&tag.value)
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes an element as an OSM XML element, indented by `indent`.
+pub(crate) fn write_element<W: Write>(
+    writer: &mut W,
+    element: &Element,
+    indent: &str,
+) -> anyhow::Result<()> {
+    match element {
+        Element::Node(node) => {
+            write!(writer, "{}<node", indent)?;
+            write_attributes(writer, node)?;
+            write!(
+                writer,
+                " lat=\"{}\" lon=\"{}\"",
+                format_coordinate(node.latitude),
+                format_coordinate(node.longitude)
+            )?;
+            if node.tags.is_empty() {
+                writeln!(writer, "/>")?;
+            } else {
+                writeln!(writer, ">")?;
+                write_tags(writer, node, indent)?;
+                writeln!(writer, "{}</node>", indent)?;
+            }
+        }
+        Element::Way(way) => {
+            write!(writer, "{}<way", indent)?;
+            write_attributes(writer, way)?;
+            writeln!(writer, ">")?;
+            for way_node in &way.way_nodes {
+                writeln!(writer, "{}  <nd ref=\"{}\"/>", indent, way_node.id)?;
+            }
+            write_tags(writer, way, indent)?;
+            writeln!(writer, "{}</way>", indent)?;
+        }
+        Element::Relation(relation) => {
+            write!(writer, "{}<relation", indent)?;
+            write_attributes(writer, relation)?;
+            writeln!(writer, ">")?;
+            for member in &relation.members {
+                writeln!(
+                    writer,
+                    "{}  <member type=\"{}\" ref=\"{}\" role=\"{}\"/>",
+                    indent,
+                    member.member_type,
+                    member.member_id,
+                    escape(&member.role)
+                )?;
+            }
+            write_tags(writer, relation, indent)?;
+            writeln!(writer, "{}</relation>", indent)?;
+        }
+    }
+    Ok(())
+}
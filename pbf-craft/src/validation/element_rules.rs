@@ -0,0 +1,315 @@
+use super::{ValidationIssue, ValidationRule};
+use crate::models::{BasicElement, Element};
+
+/// The limits of coordinates in nanodegrees.
+const MAX_LATITUDE: i64 = 90_000_000_000;
+const MAX_LONGITUDE: i64 = 180_000_000_000;
+/// The maximum length of tag keys and values accepted by the OSM API, in characters.
+const API_MAX_TAG_LENGTH: usize = 255;
+
+fn issue(element: &Element, rule: &dyn ValidationRule, message: String) -> ValidationIssue {
+    let (element_type, element_id) = element.get_meta();
+    ValidationIssue {
+        element_type,
+        element_id,
+        rule: rule.name().to_string(),
+        message,
+    }
+}
+
+fn base(element: &Element) -> &dyn BasicElement {
+    match element {
+        Element::Node(node) => node,
+        Element::Way(way) => way,
+        Element::Relation(relation) => relation,
+    }
+}
+
+fn in_range(latitude: i64, longitude: i64) -> bool {
+    (-MAX_LATITUDE..=MAX_LATITUDE).contains(&latitude)
+        && (-MAX_LONGITUDE..=MAX_LONGITUDE).contains(&longitude)
+}
+
+/// Flags nodes, and way nodes with locations, whose latitude isn't within ±90 degrees or
+/// whose longitude isn't within ±180 degrees.
+pub struct CoordinateRangeRule;
+
+impl ValidationRule for CoordinateRangeRule {
+    fn name(&self) -> &'static str {
+        "coordinate-range"
+    }
+
+    fn check(&self, element: &Element, issues: &mut Vec<ValidationIssue>) {
+        match element {
+            Element::Node(node) if node.visible && !in_range(node.latitude, node.longitude) => {
+                issues.push(issue(
+                    element,
+                    self,
+                    format!(
+                        "node is at latitude {} and longitude {} nanodegrees, out of range",
+                        node.latitude, node.longitude
+                    ),
+                ));
+            }
+            Element::Way(way) => {
+                for way_node in &way.way_nodes {
+                    if let (Some(latitude), Some(longitude)) =
+                        (way_node.latitude, way_node.longitude)
+                    {
+                        if !in_range(latitude, longitude) {
+                            issues.push(issue(
+                                element,
+                                self,
+                                format!(
+                                    "node {} is at latitude {} and longitude {} nanodegrees, out of range",
+                                    way_node.id, latitude, longitude
+                                ),
+                            ));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Flags tags whose key or value is longer than `max_key` or `max_value` characters, or whose
+/// key is empty.
+///
+/// The default limits are 255 characters, the limits of the OSM API.
+pub struct TagLengthRule {
+    pub max_key: usize,
+    pub max_value: usize,
+}
+
+impl Default for TagLengthRule {
+    fn default() -> Self {
+        Self {
+            max_key: API_MAX_TAG_LENGTH,
+            max_value: API_MAX_TAG_LENGTH,
+        }
+    }
+}
+
+impl ValidationRule for TagLengthRule {
+    fn name(&self) -> &'static str {
+        "tag-length"
+    }
+
+    fn check(&self, element: &Element, issues: &mut Vec<ValidationIssue>) {
+        for tag in base(element).get_tags() {
+            let key_length = tag.key.chars().count();
+            let value_length = tag.value.chars().count();
+            if key_length == 0 {
+                issues.push(issue(element, self, "a tag has an empty key".to_string()));
+            } else if key_length > self.max_key {
+                issues.push(issue(
+                    element,
+                    self,
+                    format!(
+                        "the key {:?} has {} characters, at most {} are allowed",
+                        tag.key, key_length, self.max_key
+                    ),
+                ));
+            }
+            if value_length > self.max_value {
+                issues.push(issue(
+                    element,
+                    self,
+                    format!(
+                        "the value of {:?} has {} characters, at most {} are allowed",
+                        tag.key, value_length, self.max_value
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Flags tags, relation roles and user names containing characters which aren't allowed in
+/// XML, such as control characters, or the replacement character left by decoding invalid
+/// UTF-8 lossily.
+pub struct TextSanityRule;
+
+impl TextSanityRule {
+    fn check_text(
+        &self,
+        element: &Element,
+        what: &str,
+        text: &str,
+        issues: &mut Vec<ValidationIssue>,
+    ) {
+        let invalid = text.chars().find(|c| match c {
+            '\t' | '\n' | '\r' => false,
+            '\u{fffd}' | '\u{fffe}' | '\u{ffff}' => true,
+            c => (*c as u32) < 0x20,
+        });
+        if let Some(c) = invalid {
+            issues.push(issue(
+                element,
+                self,
+                format!("{} {:?} contains the character {:?}", what, text, c),
+            ));
+        }
+    }
+}
+
+impl ValidationRule for TextSanityRule {
+    fn name(&self) -> &'static str {
+        "text-sanity"
+    }
+
+    fn check(&self, element: &Element, issues: &mut Vec<ValidationIssue>) {
+        for tag in base(element).get_tags() {
+            self.check_text(element, "the key", &tag.key, issues);
+            self.check_text(element, "the value", &tag.value, issues);
+        }
+        if let Some(user) = base(element).get_user() {
+            self.check_text(element, "the user name", &user.name, issues);
+        }
+        if let Element::Relation(relation) = element {
+            for member in &relation.members {
+                self.check_text(element, "the role", &member.role, issues);
+            }
+        }
+    }
+}
+
+/// Flags elements with an ID which isn't positive, and ways and relations referencing one.
+///
+/// Negative IDs denote new elements in editors, so files with them can't be applied to the
+/// OSM database as they are, e.g. by an upload.
+pub struct PositiveIdRule;
+
+impl ValidationRule for PositiveIdRule {
+    fn name(&self) -> &'static str {
+        "positive-id"
+    }
+
+    fn check(&self, element: &Element, issues: &mut Vec<ValidationIssue>) {
+        let (_, id) = element.get_meta();
+        if id <= 0 {
+            issues.push(issue(
+                element,
+                self,
+                format!("the ID {} isn't positive", id),
+            ));
+        }
+        let references: Vec<i64> = match element {
+            Element::Node(_) => Vec::new(),
+            Element::Way(way) => way.way_nodes.iter().map(|way_node| way_node.id).collect(),
+            Element::Relation(relation) => relation
+                .members
+                .iter()
+                .map(|member| member.member_id)
+                .collect(),
+        };
+        if let Some(reference) = references.into_iter().find(|reference| *reference <= 0) {
+            issues.push(issue(
+                element,
+                self,
+                format!("the reference to {} isn't positive", reference),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ElementType, Node, OsmUser, Relation, RelationMember, Tag, Way, WayNode};
+    use crate::validation::Validator;
+
+    fn node(id: i64, latitude: i64, longitude: i64, tags: &[(&str, &str)]) -> Element {
+        Element::Node(Node {
+            id,
+            latitude,
+            longitude,
+            visible: true,
+            tags: tags
+                .iter()
+                .map(|(key, value)| Tag {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn rules(element: &Element) -> Vec<String> {
+        let mut validator = Validator::new();
+        validator.add_rule(CoordinateRangeRule);
+        validator.add_rule(TagLengthRule::default());
+        validator.add_rule(TextSanityRule);
+        validator.add_rule(PositiveIdRule);
+        validator
+            .validate(element)
+            .into_iter()
+            .map(|issue| issue.rule)
+            .collect()
+    }
+
+    #[test]
+    fn test_element_rules() {
+        let valid = node(
+            1,
+            42_500_000_000,
+            1_500_000_000,
+            &[("name", "Andorra\tla Vella")],
+        );
+        assert!(rules(&valid).is_empty());
+
+        assert_eq!(
+            rules(&node(1, 91_000_000_000, 0, &[])),
+            ["coordinate-range"]
+        );
+        assert_eq!(
+            rules(&node(1, 0, -180_000_000_001, &[])),
+            ["coordinate-range"]
+        );
+        let way = Element::Way(Way {
+            id: 1,
+            way_nodes: vec![WayNode::new(1, 0, 0), WayNode::new(2, 0, 200_000_000_000)],
+            ..Default::default()
+        });
+        assert_eq!(rules(&way), ["coordinate-range"]);
+
+        let long = "a".repeat(256);
+        assert_eq!(rules(&node(1, 0, 0, &[("name", &long)])), ["tag-length"]);
+        assert_eq!(rules(&node(1, 0, 0, &[(&long, "yes")])), ["tag-length"]);
+        assert_eq!(rules(&node(1, 0, 0, &[("", "yes")])), ["tag-length"]);
+        // The limits count characters, not bytes
+        assert!(rules(&node(1, 0, 0, &[("name", &"é".repeat(255))])).is_empty());
+
+        assert_eq!(
+            rules(&node(1, 0, 0, &[("name", "a\u{0}b")])),
+            ["text-sanity"]
+        );
+        assert_eq!(
+            rules(&node(1, 0, 0, &[("name", "a\u{fffd}")])),
+            ["text-sanity"]
+        );
+        let mut user_node = node(1, 0, 0, &[]);
+        if let Element::Node(node) = &mut user_node {
+            node.user = Some(OsmUser {
+                id: 1,
+                name: "bad\u{7}name".into(),
+            });
+        }
+        assert_eq!(rules(&user_node), ["text-sanity"]);
+
+        assert_eq!(rules(&node(-1, 0, 0, &[])), ["positive-id"]);
+        let relation = Element::Relation(Relation {
+            id: 1,
+            members: vec![RelationMember {
+                member_id: -5,
+                member_type: ElementType::Way,
+                role: "outer\u{1}".into(),
+            }],
+            ..Default::default()
+        });
+        assert_eq!(rules(&relation), ["text-sanity", "positive-id"]);
+    }
+}
@@ -0,0 +1,140 @@
+mod element_rules;
+mod way_rules;
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Element, ElementType};
+use crate::readers::ElementSource;
+
+pub use element_rules::{CoordinateRangeRule, PositiveIdRule, TagLengthRule, TextSanityRule};
+pub use way_rules::{DuplicateConsecutiveNodesRule, SelfIntersectionRule, WayNodeCountRule};
+
+/// A problem found by a `ValidationRule`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    pub element_type: ElementType,
+    pub element_id: i64,
+    /// The name of the rule which found the issue.
+    pub rule: String,
+    pub message: String,
+}
+
+/// What `PbfWriter` does with an element a rule found an issue with, see
+/// `PbfWriter::add_validation_rule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationPolicy {
+    /// Fails the write with the issue.
+    Reject,
+    /// Leaves the element out of the output.
+    Skip,
+    /// Writes the element anyway.
+    Warn,
+}
+
+/// A rule checking single elements.
+pub trait ValidationRule: Send + Sync {
+    /// The name of the rule, used in the issues it reports.
+    fn name(&self) -> &'static str;
+
+    /// Checks an element and appends the issues found to `issues`.
+    fn check(&self, element: &Element, issues: &mut Vec<ValidationIssue>);
+
+    /// Whether the rule needs the coordinates of way nodes.
+    ///
+    /// If any rule needs them, `Validator::validate_source` fills in missing way node
+    /// coordinates from the nodes read before the way.
+    fn needs_locations(&self) -> bool {
+        false
+    }
+}
+
+/// Checks elements against a set of rules.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::IterableReader;
+/// use pbf_craft::validation::{DuplicateConsecutiveNodesRule, Validator, WayNodeCountRule};
+///
+/// let mut validator = Validator::new();
+/// validator.add_rule(WayNodeCountRule::default());
+/// validator.add_rule(DuplicateConsecutiveNodesRule);
+/// let reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let issues = validator.validate_source(reader).unwrap();
+/// for issue in issues {
+///     println!("{:?}#{}: {}", issue.element_type, issue.element_id, issue.message);
+/// }
+/// ```
+#[derive(Default)]
+pub struct Validator {
+    rules: Vec<Box<dyn ValidationRule>>,
+}
+
+impl Validator {
+    /// Creates a validator without any rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a validator with the rules for ways: the node count limits, duplicate
+    /// consecutive nodes and, if `check_geometry` is set, self-intersections.
+    pub fn with_way_rules(check_geometry: bool) -> Self {
+        let mut validator = Self::new();
+        validator.add_rule(WayNodeCountRule::default());
+        validator.add_rule(DuplicateConsecutiveNodesRule);
+        if check_geometry {
+            validator.add_rule(SelfIntersectionRule);
+        }
+        validator
+    }
+
+    /// Adds a rule.
+    pub fn add_rule<R: ValidationRule + 'static>(&mut self, rule: R) {
+        self.rules.push(Box::new(rule));
+    }
+
+    /// Checks a single element against all rules.
+    pub fn validate(&self, element: &Element) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        for rule in &self.rules {
+            rule.check(element, &mut issues);
+        }
+        issues
+    }
+
+    /// Checks all elements of a source against all rules.
+    pub fn validate_source<S: ElementSource>(
+        &self,
+        mut source: S,
+    ) -> anyhow::Result<Vec<ValidationIssue>> {
+        let needs_locations = self.rules.iter().any(|rule| rule.needs_locations());
+        let mut locations: HashMap<i64, (i64, i64)> = HashMap::new();
+        let mut issues = Vec::new();
+        while let Some(mut element) = source.next_element()? {
+            if needs_locations {
+                match &mut element {
+                    Element::Node(node) => {
+                        locations.insert(node.id, (node.latitude, node.longitude));
+                    }
+                    Element::Way(way) => {
+                        for way_node in way.way_nodes.iter_mut() {
+                            if way_node.latitude.is_none() || way_node.longitude.is_none() {
+                                if let Some((latitude, longitude)) = locations.get(&way_node.id) {
+                                    way_node.latitude = Some(*latitude);
+                                    way_node.longitude = Some(*longitude);
+                                }
+                            }
+                        }
+                    }
+                    Element::Relation(_) => {}
+                }
+            }
+            for rule in &self.rules {
+                rule.check(&element, &mut issues);
+            }
+        }
+        Ok(issues)
+    }
+}
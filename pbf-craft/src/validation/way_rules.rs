@@ -0,0 +1,203 @@
+use super::{ValidationIssue, ValidationRule};
+use crate::models::{Element, ElementType, Way};
+
+/// The maximum number of nodes in a way accepted by the OSM API.
+const API_MAX_WAY_NODES: usize = 2000;
+
+fn issue(way: &Way, rule: &dyn ValidationRule, message: String) -> ValidationIssue {
+    ValidationIssue {
+        element_type: ElementType::Way,
+        element_id: way.id,
+        rule: rule.name().to_string(),
+        message,
+    }
+}
+
+/// Flags ways with fewer than `min` or more than `max` nodes.
+///
+/// The default limits are 2 and 2000, the number of nodes a way needs to be valid and the
+/// maximum number of nodes accepted by the OSM API.
+pub struct WayNodeCountRule {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for WayNodeCountRule {
+    fn default() -> Self {
+        Self {
+            min: 2,
+            max: API_MAX_WAY_NODES,
+        }
+    }
+}
+
+impl ValidationRule for WayNodeCountRule {
+    fn name(&self) -> &'static str {
+        "way-node-count"
+    }
+
+    fn check(&self, element: &Element, issues: &mut Vec<ValidationIssue>) {
+        let Element::Way(way) = element else {
+            return;
+        };
+        let count = way.way_nodes.len();
+        if count < self.min {
+            issues.push(issue(
+                way,
+                self,
+                format!(
+                    "way has {} nodes, at least {} are required",
+                    count, self.min
+                ),
+            ));
+        } else if count > self.max {
+            issues.push(issue(
+                way,
+                self,
+                format!("way has {} nodes, at most {} are allowed", count, self.max),
+            ));
+        }
+    }
+}
+
+/// Flags ways referencing the same node twice in a row.
+pub struct DuplicateConsecutiveNodesRule;
+
+impl ValidationRule for DuplicateConsecutiveNodesRule {
+    fn name(&self) -> &'static str {
+        "duplicate-consecutive-nodes"
+    }
+
+    fn check(&self, element: &Element, issues: &mut Vec<ValidationIssue>) {
+        let Element::Way(way) = element else {
+            return;
+        };
+        for (index, pair) in way.way_nodes.windows(2).enumerate() {
+            if pair[0].id == pair[1].id {
+                issues.push(issue(
+                    way,
+                    self,
+                    format!(
+                        "node {} is repeated at positions {} and {}",
+                        pair[0].id,
+                        index,
+                        index + 1
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Flags ways whose segments cross or touch each other, apart from the shared end points of
+/// neighbouring segments and the closing point of closed ways.
+///
+/// The rule needs the coordinates of the way nodes. Ways with missing coordinates are skipped.
+pub struct SelfIntersectionRule;
+
+impl ValidationRule for SelfIntersectionRule {
+    fn name(&self) -> &'static str {
+        "self-intersection"
+    }
+
+    fn check(&self, element: &Element, issues: &mut Vec<ValidationIssue>) {
+        let Element::Way(way) = element else {
+            return;
+        };
+        let points: Option<Vec<(i64, i64)>> = way
+            .way_nodes
+            .iter()
+            .map(|way_node| Some((way_node.longitude?, way_node.latitude?)))
+            .collect();
+        let Some(points) = points else {
+            return;
+        };
+        let segment_count = points.len().saturating_sub(1);
+        let is_closed = points.len() > 3 && way.way_nodes.first() == way.way_nodes.last();
+        for i in 0..segment_count {
+            for j in (i + 2)..segment_count {
+                if is_closed && i == 0 && j == segment_count - 1 {
+                    continue;
+                }
+                if segments_intersect(points[i], points[i + 1], points[j], points[j + 1]) {
+                    issues.push(issue(
+                        way,
+                        self,
+                        format!("segment {} intersects segment {}", i, j),
+                    ));
+                    return;
+                }
+            }
+        }
+    }
+
+    fn needs_locations(&self) -> bool {
+        true
+    }
+}
+
+fn orientation(a: (i64, i64), b: (i64, i64), c: (i64, i64)) -> i32 {
+    let value =
+        (b.0 - a.0) as i128 * (c.1 - a.1) as i128 - (b.1 - a.1) as i128 * (c.0 - a.0) as i128;
+    value.signum() as i32
+}
+
+fn on_segment(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> bool {
+    p.0 >= a.0.min(b.0) && p.0 <= a.0.max(b.0) && p.1 >= a.1.min(b.1) && p.1 <= a.1.max(b.1)
+}
+
+fn segments_intersect(a: (i64, i64), b: (i64, i64), c: (i64, i64), d: (i64, i64)) -> bool {
+    let o1 = orientation(a, b, c);
+    let o2 = orientation(a, b, d);
+    let o3 = orientation(c, d, a);
+    let o4 = orientation(c, d, b);
+    if o1 != o2 && o3 != o4 {
+        return true;
+    }
+    (o1 == 0 && on_segment(a, b, c))
+        || (o2 == 0 && on_segment(a, b, d))
+        || (o3 == 0 && on_segment(c, d, a))
+        || (o4 == 0 && on_segment(c, d, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WayNode;
+    use crate::validation::Validator;
+
+    fn way(nodes: &[(i64, i64, i64)]) -> Element {
+        Element::Way(Way {
+            id: 1,
+            way_nodes: nodes
+                .iter()
+                .map(|(id, lat, lon)| WayNode::new(*id, *lat, *lon))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_way_rules() {
+        let validator = Validator::with_way_rules(true);
+
+        let square = way(&[(1, 0, 0), (2, 0, 10), (3, 10, 10), (4, 10, 0), (1, 0, 0)]);
+        assert!(validator.validate(&square).is_empty());
+
+        let bowtie = way(&[(1, 0, 0), (2, 10, 10), (3, 0, 10), (4, 10, 0), (1, 0, 0)]);
+        let issues = validator.validate(&bowtie);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].rule, "self-intersection");
+
+        let single = way(&[(1, 0, 0)]);
+        assert_eq!(validator.validate(&single)[0].rule, "way-node-count");
+
+        let repeated = way(&[(1, 0, 0), (2, 0, 10), (2, 0, 10)]);
+        let issues = validator.validate(&repeated);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].rule, "duplicate-consecutive-nodes");
+
+        let long: Vec<(i64, i64, i64)> = (0..2001).map(|i| (i, 0, i)).collect();
+        assert_eq!(validator.validate(&way(&long))[0].rule, "way-node-count");
+    }
+}
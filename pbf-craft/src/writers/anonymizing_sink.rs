@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use super::traits::ElementSink;
+use crate::models::{intern, Element, FileHeader, OsmUser};
+use crate::readers::Provenance;
+
+/// What `AnonymizingSink` does with the users and changeset IDs of the elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anonymization {
+    /// Leaves them unchanged, e.g. to only round the timestamps.
+    Keep,
+    /// Removes the users and sets the changeset IDs to 0.
+    Remove,
+    /// Replaces every user and changeset with a pseudonym numbered in the order of appearance.
+    /// Edits of the same user or changeset stay recognizable as such, but can't be traced
+    /// back to them.
+    Pseudonymize,
+}
+
+impl FromStr for Anonymization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Anonymization::Keep),
+            "remove" => Ok(Anonymization::Remove),
+            "pseudonymize" => Ok(Anonymization::Pseudonymize),
+            _ => Err(anyhow!("Unknown anonymization: {}", s)),
+        }
+    }
+}
+
+/// A sink that strips personal data from the metadata of the elements before passing them on
+/// to the wrapped sink, for sharing data in compliance with privacy regulations.
+///
+/// User names, user IDs and changeset IDs are removed or pseudonymized according to the
+/// `Anonymization`. The timestamps can be rounded down as well, as exact edit times may
+/// identify a user too.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{ElementSource, IterableReader};
+/// use pbf_craft::writers::{Anonymization, AnonymizingSink, ElementSink, PbfWriter};
+///
+/// let mut reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let writer = PbfWriter::new(Vec::new(), true);
+/// let mut sink = AnonymizingSink::new(writer, Anonymization::Pseudonymize);
+/// // Keeps only the day of the edits
+/// sink.set_timestamp_rounding(24 * 60 * 60);
+/// while let Some(element) = reader.next_element().unwrap() {
+///     sink.write(element).unwrap();
+/// }
+/// sink.finish().unwrap();
+/// ```
+pub struct AnonymizingSink<S: ElementSink> {
+    sink: S,
+    anonymization: Anonymization,
+    timestamp_rounding: Option<i64>,
+    users: HashMap<i32, OsmUser>,
+    changesets: HashMap<i64, i64>,
+}
+
+impl<S: ElementSink> AnonymizingSink<S> {
+    pub fn new(sink: S, anonymization: Anonymization) -> Self {
+        Self {
+            sink,
+            anonymization,
+            timestamp_rounding: None,
+            users: HashMap::new(),
+            changesets: HashMap::new(),
+        }
+    }
+
+    /// Rounds the timestamps down to a multiple of `seconds`, which must be positive.
+    pub fn set_timestamp_rounding(&mut self, seconds: i64) {
+        assert!(seconds > 0, "The timestamp rounding must be positive");
+        self.timestamp_rounding = Some(seconds);
+    }
+
+    /// Consumes the `AnonymizingSink` and returns the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    fn anonymize_user(&mut self, user: Option<OsmUser>) -> Option<OsmUser> {
+        match self.anonymization {
+            Anonymization::Keep => user,
+            Anonymization::Remove => None,
+            Anonymization::Pseudonymize => user.map(|user| {
+                let pseudonym = self.users.len() as i32 + 1;
+                self.users
+                    .entry(user.id)
+                    .or_insert_with(|| OsmUser {
+                        id: pseudonym,
+                        name: intern(&format!("user{}", pseudonym)),
+                    })
+                    .clone()
+            }),
+        }
+    }
+
+    fn anonymize_changeset(&mut self, changeset_id: i64) -> i64 {
+        match self.anonymization {
+            Anonymization::Keep => changeset_id,
+            Anonymization::Remove => 0,
+            Anonymization::Pseudonymize => {
+                let pseudonym = self.changesets.len() as i64 + 1;
+                *self.changesets.entry(changeset_id).or_insert(pseudonym)
+            }
+        }
+    }
+
+    fn round_timestamp(&self, timestamp: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        match (timestamp, self.timestamp_rounding) {
+            (Some(timestamp), Some(seconds)) => {
+                let rounded = timestamp.timestamp() - timestamp.timestamp().rem_euclid(seconds);
+                DateTime::from_timestamp(rounded, 0)
+            }
+            (timestamp, _) => timestamp,
+        }
+    }
+}
+
+impl<S: ElementSink> ElementSink for AnonymizingSink<S> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        self.write_with_provenance(element, None)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        mut element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        let (user, changeset_id, timestamp) = match &mut element {
+            Element::Node(node) => (&mut node.user, &mut node.changeset_id, &mut node.timestamp),
+            Element::Way(way) => (&mut way.user, &mut way.changeset_id, &mut way.timestamp),
+            Element::Relation(relation) => (
+                &mut relation.user,
+                &mut relation.changeset_id,
+                &mut relation.timestamp,
+            ),
+        };
+        *user = self.anonymize_user(user.take());
+        *changeset_id = self.anonymize_changeset(*changeset_id);
+        *timestamp = self.round_timestamp(*timestamp);
+        self.sink.write_with_provenance(element, provenance)
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        self.sink.set_header(header);
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Way};
+
+    #[derive(Default)]
+    struct VecSink(Vec<Element>);
+
+    impl ElementSink for VecSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.0.push(element);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn elements() -> Vec<Element> {
+        let user = |id: i32, name: &str| {
+            Some(OsmUser {
+                id,
+                name: name.into(),
+            })
+        };
+        vec![
+            Element::Node(Node {
+                id: 1,
+                changeset_id: 500,
+                user: user(42, "alice"),
+                timestamp: DateTime::from_timestamp(1_700_000_123, 0),
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 2,
+                changeset_id: 300,
+                user: user(7, "bob"),
+                ..Default::default()
+            }),
+            Element::Way(Way {
+                id: 1,
+                changeset_id: 500,
+                user: user(42, "alice"),
+                ..Default::default()
+            }),
+        ]
+    }
+
+    fn anonymize(anonymization: Anonymization) -> Vec<(Option<OsmUser>, i64)> {
+        let mut sink = AnonymizingSink::new(VecSink::default(), anonymization);
+        for element in elements() {
+            sink.write(element).unwrap();
+        }
+        sink.into_inner()
+            .0
+            .into_iter()
+            .map(|element| match element {
+                Element::Node(node) => (node.user, node.changeset_id),
+                Element::Way(way) => (way.user, way.changeset_id),
+                Element::Relation(relation) => (relation.user, relation.changeset_id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_anonymization() {
+        let removed = anonymize(Anonymization::Remove);
+        assert!(removed.iter().all(|metadata| *metadata == (None, 0)));
+
+        let pseudonymized = anonymize(Anonymization::Pseudonymize);
+        let user = |id: i32| {
+            Some(OsmUser {
+                id,
+                name: intern(&format!("user{}", id)),
+            })
+        };
+        assert_eq!(
+            pseudonymized,
+            vec![(user(1), 1), (user(2), 2), (user(1), 1)]
+        );
+
+        let kept = anonymize(Anonymization::Keep);
+        assert_eq!(&*kept[0].0.as_ref().unwrap().name, "alice");
+        assert_eq!(kept[1].1, 300);
+    }
+
+    #[test]
+    fn test_timestamp_rounding() {
+        let mut sink = AnonymizingSink::new(VecSink::default(), Anonymization::Keep);
+        sink.set_timestamp_rounding(3600);
+        for element in elements() {
+            sink.write(element).unwrap();
+        }
+        match &sink.into_inner().0[..2] {
+            [Element::Node(rounded), Element::Node(missing)] => {
+                assert_eq!(
+                    rounded.timestamp,
+                    DateTime::from_timestamp(1_699_999_200, 0)
+                );
+                assert_eq!(missing.timestamp, None);
+            }
+            _ => panic!("Unexpected elements"),
+        }
+    }
+}
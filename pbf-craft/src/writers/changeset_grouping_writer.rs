@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use super::traits::ElementSink;
+use crate::models::{Element, ElementType, FileHeader, OsmUser};
+use crate::readers::Provenance;
+
+/// The changeset, type, ID and version of an element.
+type VersionKey = (i64, ElementType, i64, i32);
+
+/// A summary of the elements of one changeset, as collected by `ChangesetGroupingWriter`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangesetSummary {
+    pub changeset_id: i64,
+    /// The user of the first element of the changeset, if any.
+    pub user: Option<OsmUser>,
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+    /// The number of elements which are deleted, i.e. not visible.
+    pub deleted: u64,
+    pub first_timestamp: Option<DateTime<Utc>>,
+    pub last_timestamp: Option<DateTime<Utc>>,
+}
+
+impl ChangesetSummary {
+    fn new(changeset_id: i64) -> Self {
+        Self {
+            changeset_id,
+            user: None,
+            nodes: 0,
+            ways: 0,
+            relations: 0,
+            deleted: 0,
+            first_timestamp: None,
+            last_timestamp: None,
+        }
+    }
+
+    /// Returns the total number of elements of the changeset.
+    pub fn total(&self) -> u64 {
+        self.nodes + self.ways + self.relations
+    }
+}
+
+/// A sink that groups the elements by changeset and writes them to the wrapped sink ordered by
+/// changeset, then type, id and version, when finished.
+///
+/// It reconstructs the edit sessions of a history file, e.g. for replaying them in QA tools,
+/// and collects a `ChangesetSummary` of every changeset on the way. All elements are kept in
+/// memory until `finish` is called.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node};
+/// use pbf_craft::writers::{ChangesetGroupingWriter, ElementSink, NullSink};
+///
+/// let mut writer = ChangesetGroupingWriter::new(NullSink);
+/// writer.write(Element::Node(Node { id: 1, changeset_id: 20, ..Default::default() })).unwrap();
+/// writer.write(Element::Node(Node { id: 2, changeset_id: 10, ..Default::default() })).unwrap();
+/// writer.finish().unwrap();
+/// assert_eq!(writer.summaries()[0].changeset_id, 10);
+/// ```
+pub struct ChangesetGroupingWriter<S: ElementSink> {
+    sink: S,
+    elements: BTreeMap<VersionKey, (Element, Option<Provenance>)>,
+    summaries: BTreeMap<i64, ChangesetSummary>,
+}
+
+impl<S: ElementSink> ChangesetGroupingWriter<S> {
+    /// Creates a new `ChangesetGroupingWriter` writing to `sink`.
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            elements: BTreeMap::new(),
+            summaries: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the summaries of the changesets of the elements written so far, ordered by
+    /// changeset id.
+    pub fn summaries(&self) -> Vec<&ChangesetSummary> {
+        self.summaries.values().collect()
+    }
+
+    /// Consumes the `ChangesetGroupingWriter` and returns the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    fn summarize(&mut self, element: &Element) {
+        let (changeset_id, timestamp, user, visible) = match element {
+            Element::Node(node) => (node.changeset_id, node.timestamp, &node.user, node.visible),
+            Element::Way(way) => (way.changeset_id, way.timestamp, &way.user, way.visible),
+            Element::Relation(relation) => (
+                relation.changeset_id,
+                relation.timestamp,
+                &relation.user,
+                relation.visible,
+            ),
+        };
+        let summary = self
+            .summaries
+            .entry(changeset_id)
+            .or_insert_with(|| ChangesetSummary::new(changeset_id));
+        match element {
+            Element::Node(_) => summary.nodes += 1,
+            Element::Way(_) => summary.ways += 1,
+            Element::Relation(_) => summary.relations += 1,
+        }
+        if !visible {
+            summary.deleted += 1;
+        }
+        if summary.user.is_none() {
+            summary.user = user.clone();
+        }
+        if let Some(timestamp) = timestamp {
+            summary.first_timestamp = Some(
+                summary
+                    .first_timestamp
+                    .map_or(timestamp, |first| first.min(timestamp)),
+            );
+            summary.last_timestamp = Some(
+                summary
+                    .last_timestamp
+                    .map_or(timestamp, |last| last.max(timestamp)),
+            );
+        }
+    }
+}
+
+impl<S: ElementSink> ElementSink for ChangesetGroupingWriter<S> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        self.write_with_provenance(element, None)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        self.summarize(&element);
+        let (changeset_id, version) = match &element {
+            Element::Node(node) => (node.changeset_id, node.version),
+            Element::Way(way) => (way.changeset_id, way.version),
+            Element::Relation(relation) => (relation.changeset_id, relation.version),
+        };
+        let (element_type, id) = element.get_meta();
+        self.elements.insert(
+            (changeset_id, element_type, id, version),
+            (element, provenance),
+        );
+        Ok(())
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        self.sink.set_header(header);
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        for (_, (element, provenance)) in std::mem::take(&mut self.elements) {
+            self.sink.write_with_provenance(element, provenance)?;
+        }
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Way};
+
+    #[derive(Default)]
+    struct VecSink(Vec<(i64, ElementType, i64, i32)>);
+
+    impl ElementSink for VecSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            let (changeset_id, version) = match &element {
+                Element::Node(node) => (node.changeset_id, node.version),
+                Element::Way(way) => (way.changeset_id, way.version),
+                Element::Relation(relation) => (relation.changeset_id, relation.version),
+            };
+            let (element_type, id) = element.get_meta();
+            self.0.push((changeset_id, element_type, id, version));
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn node(id: i64, version: i32, changeset_id: i64, seconds: i64) -> Element {
+        Element::Node(Node {
+            id,
+            version,
+            changeset_id,
+            timestamp: DateTime::from_timestamp(seconds, 0),
+            visible: true,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_group_by_changeset() {
+        let mut writer = ChangesetGroupingWriter::new(VecSink::default());
+        writer.write(node(2, 1, 10, 100)).unwrap();
+        writer
+            .write(Element::Way(Way {
+                id: 1,
+                version: 1,
+                changeset_id: 10,
+                visible: true,
+                ..Default::default()
+            }))
+            .unwrap();
+        writer.write(node(1, 2, 20, 300)).unwrap();
+        writer.write(node(1, 1, 10, 50)).unwrap();
+        let mut deleted = node(2, 2, 20, 200);
+        if let Element::Node(node) = &mut deleted {
+            node.visible = false;
+        }
+        writer.write(deleted).unwrap();
+        writer.finish().unwrap();
+
+        let summaries = writer.summaries();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!((summaries[0].nodes, summaries[0].ways), (2, 1));
+        assert_eq!(
+            summaries[0].first_timestamp,
+            DateTime::from_timestamp(50, 0)
+        );
+        assert_eq!(
+            summaries[0].last_timestamp,
+            DateTime::from_timestamp(100, 0)
+        );
+        assert_eq!((summaries[1].total(), summaries[1].deleted), (2, 1));
+
+        assert_eq!(
+            writer.into_inner().0,
+            vec![
+                (10, ElementType::Node, 1, 1),
+                (10, ElementType::Node, 2, 1),
+                (10, ElementType::Way, 1, 1),
+                (20, ElementType::Node, 1, 2),
+                (20, ElementType::Node, 2, 2),
+            ]
+        );
+    }
+}
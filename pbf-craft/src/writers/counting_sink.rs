@@ -0,0 +1,78 @@
+use super::traits::ElementSink;
+use crate::models::Element;
+
+/// A sink that discards every element written to it.
+///
+/// It is useful for measuring the throughput of a pipeline without any output cost.
+#[derive(Debug, Default)]
+pub struct NullSink;
+
+impl ElementSink for NullSink {
+    fn write(&mut self, _element: Element) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// A sink that counts the elements written to it by type and discards them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CountingSink {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+}
+
+impl CountingSink {
+    /// Creates a new `CountingSink` with all counters set to zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the total number of elements written.
+    pub fn total(&self) -> u64 {
+        self.nodes + self.ways + self.relations
+    }
+}
+
+impl ElementSink for CountingSink {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        match element {
+            Element::Node(_) => self.nodes += 1,
+            Element::Way(_) => self.ways += 1,
+            Element::Relation(_) => self.relations += 1,
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::readers::PbfReader;
+
+    #[test]
+    fn test_counting_sink() {
+        let mut reader = PbfReader::from_path("./resources/andorra-latest.osm.pbf").unwrap();
+        let mut sink = CountingSink::new();
+        reader
+            .read(|_, element| {
+                if let Some(element) = element {
+                    sink.write(element).unwrap();
+                }
+            })
+            .unwrap();
+        sink.finish().unwrap();
+
+        assert!(sink.nodes > 0);
+        assert!(sink.ways > 0);
+        assert!(sink.relations > 0);
+        assert_eq!(sink.total(), sink.nodes + sink.ways + sink.relations);
+    }
+}
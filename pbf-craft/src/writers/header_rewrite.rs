@@ -0,0 +1,121 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+use super::PbfWriter;
+use crate::codecs::blob::{BlobReader, DecodedBlob};
+use crate::proto::osmformat;
+
+/// Writes a copy of a PBF file with its header block changed by `edit`, e.g. to fix the
+/// bounding box, the replication fields or the writing program.
+///
+/// Only the header blob is re-encoded; the data blobs are copied verbatim without decoding
+/// them, so it takes about as long as copying the file.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::proto::osmformat::HeaderBBox;
+/// use pbf_craft::writers::rewrite_header;
+///
+/// rewrite_header(
+///     "resources/andorra-latest.osm.pbf",
+///     "resources/andorra-rewritten.osm.pbf",
+///     |header| {
+///         let mut bbox = HeaderBBox::new();
+///         bbox.set_left(1_400_000_000);
+///         bbox.set_right(1_800_000_000);
+///         bbox.set_top(42_700_000_000);
+///         bbox.set_bottom(42_400_000_000);
+///         header.set_bbox(bbox);
+///     },
+/// )
+/// .unwrap();
+/// # std::fs::remove_file("resources/andorra-rewritten.osm.pbf").unwrap();
+/// ```
+pub fn rewrite_header<P, Q, F>(input: P, output: Q, edit: F) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnOnce(&mut osmformat::HeaderBlock),
+{
+    if let (Ok(input), Ok(output)) = (fs::canonicalize(&input), fs::canonicalize(&output)) {
+        if input == output {
+            bail!("The header can't be rewritten in place; write to another file");
+        }
+    }
+    let mut blob_reader = BlobReader::new(BufReader::new(File::open(input)?));
+    let mut header_block = match blob_reader.next_blob()? {
+        Some(raw_blob) if raw_blob.blob_type() == "OSMHeader" => match raw_blob.decode()? {
+            DecodedBlob::OsmHeader(header_block) => header_block,
+            DecodedBlob::OsmData(_) => unreachable!("The blob is a header blob"),
+        },
+        Some(raw_blob) => bail!("The file starts with a {} blob", raw_blob.blob_type()),
+        None => bail!("The file is empty"),
+    };
+    edit(&mut header_block);
+
+    let mut writer = PbfWriter::from_path(output, true)?;
+    writer.write_header_block(&header_block)?;
+    while let Some(raw_blob) = blob_reader.next_blob()? {
+        writer.copy_blob(&raw_blob)?;
+    }
+    writer.finish_copy()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::readers::{IterableReader, PbfReader};
+
+    #[test]
+    fn test_rewrite_header() {
+        let input = "./resources/andorra-latest.osm.pbf";
+        let output = "./resources/test_rewrite_header.osm.pbf";
+        rewrite_header(input, output, |header| {
+            header.clear_bbox();
+            header.set_writingprogram("pbf-craft-test".to_string());
+            header.set_osmosis_replication_sequence_number(42);
+        })
+        .unwrap();
+
+        let mut header = None;
+        PbfReader::from_path(output)
+            .unwrap()
+            .read(|h, _| {
+                if h.is_some() {
+                    header = h;
+                }
+            })
+            .unwrap();
+        let header = header.unwrap();
+        assert_eq!(header.bound(), None);
+        assert_eq!(header.writing_program(), Some("pbf-craft-test"));
+        assert_eq!(
+            header
+                .header_block()
+                .get_osmosis_replication_sequence_number(),
+            42
+        );
+
+        // The data blobs are the same
+        let blob_sizes = |path: &str| {
+            let mut blob_reader = BlobReader::new(BufReader::new(File::open(path).unwrap()));
+            let mut sizes = Vec::new();
+            while let Some(raw_blob) = blob_reader.next_blob().unwrap() {
+                if raw_blob.is_osm_data() {
+                    sizes.push(raw_blob.data().len());
+                }
+            }
+            sizes
+        };
+        assert_eq!(blob_sizes(output), blob_sizes(input));
+        assert_eq!(
+            IterableReader::from_path(output).unwrap().count(),
+            IterableReader::from_path(input).unwrap().count()
+        );
+
+        assert!(rewrite_header(output, output, |_| {}).is_err());
+        fs::remove_file(output).unwrap();
+    }
+}
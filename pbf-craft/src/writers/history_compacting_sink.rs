@@ -0,0 +1,173 @@
+use super::traits::ElementSink;
+use crate::models::{has_same_content, Element, FileHeader};
+use crate::readers::Provenance;
+
+/// A sink that collapses consecutive versions of an element which differ only in their
+/// metadata, passing the remaining elements on to the wrapped sink.
+///
+/// Full-history files contain many versions which change nothing but the version, timestamp,
+/// user or changeset, e.g. from reverted edits or tools touching elements without changing
+/// them. Of each run of versions with the same data, only the first is kept, so the result
+/// still tells when the data changed. A deletion is a change of the data, as the visibility is
+/// compared too.
+///
+/// The versions of an element are expected to come one after another, as in history files.
+/// The last element is held back until the next one arrives.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{ElementSource, IterableReader};
+/// use pbf_craft::writers::{ElementSink, HistoryCompactingSink, PbfWriter};
+///
+/// let mut reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let mut writer = PbfWriter::new(Vec::new(), true);
+/// writer.set_historical_information(true);
+/// let mut sink = HistoryCompactingSink::new(writer);
+/// while let Some(element) = reader.next_element().unwrap() {
+///     sink.write(element).unwrap();
+/// }
+/// sink.finish().unwrap();
+/// println!("{} versions dropped", sink.dropped());
+/// ```
+pub struct HistoryCompactingSink<S: ElementSink> {
+    sink: S,
+    pending: Option<(Element, Option<Provenance>)>,
+    dropped: u64,
+}
+
+impl<S: ElementSink> HistoryCompactingSink<S> {
+    /// Creates a new `HistoryCompactingSink` writing to `sink`.
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            pending: None,
+            dropped: 0,
+        }
+    }
+
+    /// Returns the number of versions dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Consumes the `HistoryCompactingSink` and returns the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: ElementSink> ElementSink for HistoryCompactingSink<S> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        self.write_with_provenance(element, None)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        if let Some((pending, _)) = &self.pending {
+            if has_same_content(pending, &element) {
+                self.dropped += 1;
+                return Ok(());
+            }
+        }
+        if let Some((pending, pending_provenance)) = self.pending.replace((element, provenance)) {
+            self.sink
+                .write_with_provenance(pending, pending_provenance)?;
+        }
+        Ok(())
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        self.sink.set_header(header);
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some((pending, provenance)) = self.pending.take() {
+            self.sink.write_with_provenance(pending, provenance)?;
+        }
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Tag, Way, WayNode};
+
+    struct VecSink(Vec<Element>);
+
+    impl ElementSink for VecSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.0.push(element);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_compact_history() {
+        let node = |version: i32, latitude: i64, visible: bool| {
+            Element::Node(Node {
+                id: 1,
+                version,
+                changeset_id: version as i64 * 10,
+                latitude,
+                visible,
+                ..Default::default()
+            })
+        };
+        let way = |id: i64, version: i32, highway: &str| {
+            Element::Way(Way {
+                id,
+                version,
+                visible: true,
+                tags: vec![Tag {
+                    key: "highway".to_string(),
+                    value: highway.to_string(),
+                }],
+                way_nodes: vec![WayNode::new_without_coords(1)],
+                ..Default::default()
+            })
+        };
+        let elements = vec![
+            node(1, 100, true),
+            node(2, 100, true),
+            node(3, 200, true),
+            node(4, 200, true),
+            node(5, 200, false),
+            node(6, 200, false),
+            way(1, 1, "road"),
+            way(1, 2, "road"),
+            // The same data in another element is kept
+            way(2, 1, "road"),
+            way(2, 2, "primary"),
+        ];
+        let mut sink = HistoryCompactingSink::new(VecSink(Vec::new()));
+        for element in elements {
+            sink.write(element).unwrap();
+        }
+        sink.finish().unwrap();
+        assert_eq!(sink.dropped(), 4);
+
+        let versions: Vec<(i64, i32)> = sink
+            .into_inner()
+            .0
+            .iter()
+            .map(|element| match element {
+                Element::Node(node) => (node.id, node.version),
+                Element::Way(way) => (way.id + 100, way.version),
+                Element::Relation(_) => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            versions,
+            vec![(1, 1), (1, 3), (1, 5), (101, 1), (102, 1), (102, 2)]
+        );
+    }
+}
@@ -1,3 +1,38 @@
+mod anonymizing_sink;
+mod changeset_grouping_writer;
+mod counting_sink;
+#[cfg(feature = "fs")]
+mod header_rewrite;
+mod history_compacting_sink;
+mod ndjson_writer;
+mod o5m_writer;
+mod opl_writer;
+mod orphan_pruning_sink;
 mod raw_writer;
+mod reference_checking_sink;
+mod sorting_writer;
+mod split_writer;
+mod tee_sink;
+mod traits;
+mod xml_writer;
 
-pub use raw_writer::PbfWriter;
+pub use crate::codecs::block_builder::NodeEncoding;
+pub use anonymizing_sink::{Anonymization, AnonymizingSink};
+pub use changeset_grouping_writer::{ChangesetGroupingWriter, ChangesetSummary};
+pub use counting_sink::{CountingSink, NullSink};
+#[cfg(feature = "fs")]
+pub use header_rewrite::rewrite_header;
+pub use history_compacting_sink::HistoryCompactingSink;
+pub use ndjson_writer::{NdjsonSchema, NdjsonWriter};
+pub use o5m_writer::O5mWriter;
+pub use opl_writer::OplWriter;
+pub use orphan_pruning_sink::OrphanPruningSink;
+pub use raw_writer::{
+    BlobCompression, BlockComposition, BlockReport, PbfWriter, WriteReport, WriterCheckpoint,
+};
+pub use reference_checking_sink::{ReferenceCheckingSink, ReferencePolicy};
+pub use sorting_writer::SortingWriter;
+pub use split_writer::SplitWriter;
+pub use tee_sink::TeeSink;
+pub use traits::ElementSink;
+pub use xml_writer::XmlWriter;
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use chrono::SecondsFormat;
+use geo::Coord;
+use serde_json::{json, Map, Value};
+
+use super::traits::ElementSink;
+use crate::models::{BasicElement, Element};
+#[cfg(feature = "proj")]
+use crate::utils::Projection;
+use crate::utils::{simplify, SimplifyAlgorithm};
+
+/// The layout of each line written by `NdjsonWriter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NdjsonSchema {
+    /// The serialized `Element` model, as produced by `serde_json`.
+    Raw,
+    /// A GeoJSON feature per element (GeoJSONSeq). Tags become properties, while the element
+    /// type, id and metadata are stored in properties prefixed with `@`.
+    GeoJson,
+}
+
+/// A streaming writer of line-delimited JSON, writing one element per line.
+///
+/// With `NdjsonSchema::GeoJson`, nodes become `Point` features, ways become `LineString`
+/// features and relations are written without geometry. Way geometries use the coordinates
+/// stored on the way nodes, falling back to the locations of the nodes written before the way.
+/// A way whose locations are unknown is written with a `null` geometry. Way geometries can be
+/// simplified with `set_simplification`. The coordinates are WGS84 degrees, unless they are
+/// projected with `set_projection` with the `proj` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node};
+/// use pbf_craft::writers::{NdjsonSchema, NdjsonWriter};
+///
+/// let mut writer = NdjsonWriter::new(Vec::new(), NdjsonSchema::GeoJson);
+/// writer.write(Element::Node(Node { id: 1, visible: true, ..Default::default() })).unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct NdjsonWriter<W: Write> {
+    writer: W,
+    schema: NdjsonSchema,
+    include_metadata: bool,
+    simplification: Option<(f64, SimplifyAlgorithm)>,
+    locations: HashMap<i64, (i64, i64)>,
+    #[cfg(feature = "proj")]
+    projection: Option<Projection>,
+}
+
+#[cfg(feature = "fs")]
+impl NdjsonWriter<BufWriter<File>> {
+    /// Creates a new `NdjsonWriter` from a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P, schema: NdjsonSchema) -> anyhow::Result<Self> {
+        let f = File::create(path)?;
+        Ok(Self::new(BufWriter::new(f), schema))
+    }
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    /// Creates a new `NdjsonWriter` from an existing writer.
+    pub fn new(writer: W, schema: NdjsonSchema) -> Self {
+        Self {
+            writer,
+            schema,
+            include_metadata: true,
+            simplification: None,
+            locations: HashMap::new(),
+            #[cfg(feature = "proj")]
+            projection: None,
+        }
+    }
+
+    /// Sets whether version, timestamp, changeset and user are written. Defaults to `true`.
+    pub fn set_include_metadata(&mut self, include_metadata: bool) {
+        self.include_metadata = include_metadata;
+    }
+
+    /// Sets the tolerance in meters the way geometries of GeoJSON features are simplified
+    /// within, or disables the simplification with `None`, the default.
+    pub fn set_simplification(&mut self, tolerance: Option<f64>, algorithm: SimplifyAlgorithm) {
+        self.simplification = tolerance.map(|tolerance| (tolerance, algorithm));
+    }
+
+    /// Sets the projection of the coordinates of GeoJSON features, or writes them in WGS84
+    /// degrees with `None`, the default. Way geometries are simplified before they are
+    /// projected.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::{Element, Node};
+    /// use pbf_craft::utils::Projection;
+    /// use pbf_craft::writers::{NdjsonSchema, NdjsonWriter};
+    ///
+    /// let mut writer = NdjsonWriter::new(Vec::new(), NdjsonSchema::GeoJson);
+    /// writer.set_projection(Some(Projection::web_mercator()));
+    /// writer.write(Element::Node(Node { id: 1, visible: true, ..Default::default() })).unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    #[cfg(feature = "proj")]
+    pub fn set_projection(&mut self, projection: Option<Projection>) {
+        self.projection = projection;
+    }
+
+    /// Writes an element as a single line.
+    pub fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        let value = match self.schema {
+            NdjsonSchema::Raw => self.encode_raw(&element)?,
+            NdjsonSchema::GeoJson => self.encode_feature(&element)?,
+        };
+        serde_json::to_writer(&mut self.writer, &value)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Flushes the underlying writer.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn encode_raw(&self, element: &Element) -> anyhow::Result<Value> {
+        let mut value = serde_json::to_value(element)?;
+        if !self.include_metadata {
+            if let Some(object) = value.as_object_mut() {
+                for key in ["version", "timestamp", "user", "changeset_id"] {
+                    object.remove(key);
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    fn encode_feature(&mut self, element: &Element) -> anyhow::Result<Value> {
+        let (element_type, id) = element.get_meta();
+        let (mut properties, geometry) = match element {
+            Element::Node(node) => {
+                self.locations
+                    .insert(node.id, (node.latitude, node.longitude));
+                let geometry = json!({
+                    "type": "Point",
+                    "coordinates": self.position(node.latitude, node.longitude)?,
+                });
+                (self.properties(node), geometry)
+            }
+            Element::Way(way) => {
+                let coords: Option<Vec<Coord<f64>>> = way
+                    .way_nodes
+                    .iter()
+                    .map(|way_node| match (way_node.latitude, way_node.longitude) {
+                        (Some(latitude), Some(longitude)) => Some(to_coord(latitude, longitude)),
+                        _ => self
+                            .locations
+                            .get(&way_node.id)
+                            .map(|(latitude, longitude)| to_coord(*latitude, *longitude)),
+                    })
+                    .collect();
+                let coords = match (coords, self.simplification) {
+                    (Some(coords), Some((tolerance, algorithm))) => {
+                        Some(simplify(&coords, tolerance, algorithm))
+                    }
+                    (coords, _) => coords,
+                };
+                let geometry = match coords {
+                    Some(coords) if coords.len() >= 2 => {
+                        let coordinates = coords
+                            .iter()
+                            .map(|coord| {
+                                // Simplification keeps a subset of the locations, so they
+                                // convert back exactly
+                                let latitude = (coord.y * 1e9).round() as i64;
+                                let longitude = (coord.x * 1e9).round() as i64;
+                                self.position(latitude, longitude)
+                            })
+                            .collect::<anyhow::Result<Vec<Value>>>()?;
+                        json!({
+                            "type": "LineString",
+                            "coordinates": coordinates,
+                        })
+                    }
+                    _ => Value::Null,
+                };
+                (self.properties(way), geometry)
+            }
+            Element::Relation(relation) => {
+                let mut properties = self.properties(relation);
+                let members: Vec<Value> = relation
+                    .members
+                    .iter()
+                    .map(|member| {
+                        json!({
+                            "type": member.member_type.as_str(),
+                            "ref": member.member_id,
+                            "role": member.role,
+                        })
+                    })
+                    .collect();
+                properties.insert("@members".to_string(), Value::Array(members));
+                (properties, Value::Null)
+            }
+        };
+        properties.insert("@type".to_string(), Value::from(element_type.as_str()));
+        properties.insert("@id".to_string(), Value::from(id));
+        Ok(json!({
+            "type": "Feature",
+            "id": element.to_string(),
+            "geometry": geometry,
+            "properties": properties,
+        }))
+    }
+
+    /// Returns the GeoJSON position of a location, projected if a projection is set.
+    fn position(&self, latitude: i64, longitude: i64) -> anyhow::Result<Value> {
+        #[cfg(feature = "proj")]
+        if let Some(projection) = &self.projection {
+            let (x, y) = projection.project(latitude, longitude)?;
+            return Ok(json!([x, y]));
+        }
+        Ok(to_position(latitude, longitude))
+    }
+
+    fn properties<E: BasicElement>(&self, element: &E) -> Map<String, Value> {
+        let mut properties = Map::new();
+        for tag in element.get_tags() {
+            properties.insert(tag.key.clone(), Value::from(tag.value.clone()));
+        }
+        if self.include_metadata {
+            properties.insert("@version".to_string(), Value::from(element.get_version()));
+            if let Some(timestamp) = element.get_timestamp() {
+                properties.insert(
+                    "@timestamp".to_string(),
+                    Value::from(timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                );
+            }
+            properties.insert(
+                "@changeset".to_string(),
+                Value::from(element.get_changeset_id()),
+            );
+            if let Some(user) = element.get_user() {
+                properties.insert("@uid".to_string(), Value::from(user.id));
+                properties.insert("@user".to_string(), Value::from(&*user.name));
+            }
+        }
+        properties
+    }
+}
+
+/// Converts nanodegrees to a GeoJSON position, i.e. `[longitude, latitude]` in degrees.
+fn to_position(latitude: i64, longitude: i64) -> Value {
+    json!([longitude as f64 / 1e9, latitude as f64 / 1e9])
+}
+
+/// Converts nanodegrees to a coordinate in degrees.
+fn to_coord(latitude: i64, longitude: i64) -> Coord<f64> {
+    Coord {
+        x: longitude as f64 / 1e9,
+        y: latitude as f64 / 1e9,
+    }
+}
+
+impl<W: Write> ElementSink for NdjsonWriter<W> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        NdjsonWriter::write(self, element)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        NdjsonWriter::finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Tag, Way, WayNode};
+
+    fn elements() -> Vec<Element> {
+        let tags = vec![Tag {
+            key: "highway".to_string(),
+            value: "residential".to_string(),
+        }];
+        vec![
+            Element::Node(Node {
+                id: 1,
+                latitude: 42_500_000_000,
+                longitude: 1_500_000_000,
+                version: 2,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 2,
+                latitude: 42_600_000_000,
+                longitude: 1_600_000_000,
+                ..Default::default()
+            }),
+            Element::Way(Way {
+                id: 10,
+                tags,
+                way_nodes: vec![
+                    WayNode::new_without_coords(1),
+                    WayNode::new_without_coords(2),
+                ],
+                ..Default::default()
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_geojson() {
+        let mut data = Vec::new();
+        let mut writer = NdjsonWriter::new(&mut data, NdjsonSchema::GeoJson);
+        for element in elements() {
+            writer.write(element).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let lines: Vec<Value> = String::from_utf8(data)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["geometry"]["coordinates"], json!([1.5, 42.5]));
+        assert_eq!(lines[0]["properties"]["@version"], json!(2));
+        assert_eq!(lines[2]["id"], json!("way/10"));
+        assert_eq!(lines[2]["geometry"]["type"], json!("LineString"));
+        assert_eq!(
+            lines[2]["geometry"]["coordinates"],
+            json!([[1.5, 42.5], [1.6, 42.6]])
+        );
+        assert_eq!(lines[2]["properties"]["highway"], json!("residential"));
+    }
+
+    #[test]
+    fn test_geojson_simplification() {
+        let mut elements = elements();
+        // A node 1 cm off the line between the other two
+        elements.insert(
+            2,
+            Element::Node(Node {
+                id: 3,
+                latitude: 42_550_000_100,
+                longitude: 1_550_000_000,
+                ..Default::default()
+            }),
+        );
+        if let Element::Way(way) = &mut elements[3] {
+            way.way_nodes.insert(1, WayNode::new_without_coords(3));
+        }
+        let mut data = Vec::new();
+        let mut writer = NdjsonWriter::new(&mut data, NdjsonSchema::GeoJson);
+        writer.set_simplification(Some(1.0), SimplifyAlgorithm::DouglasPeucker);
+        for element in elements {
+            writer.write(element).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let text = String::from_utf8(data).unwrap();
+        let way: Value = serde_json::from_str(text.lines().last().unwrap()).unwrap();
+        assert_eq!(
+            way["geometry"]["coordinates"],
+            json!([[1.5, 42.5], [1.6, 42.6]])
+        );
+    }
+
+    #[cfg(feature = "proj")]
+    #[test]
+    fn test_geojson_projection() {
+        let mut data = Vec::new();
+        let mut writer = NdjsonWriter::new(&mut data, NdjsonSchema::GeoJson);
+        writer.set_projection(Some(Projection::web_mercator()));
+        for element in elements() {
+            writer.write(element).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let lines: Vec<Value> = String::from_utf8(data)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let point = lines[0]["geometry"]["coordinates"].as_array().unwrap();
+        assert!((point[0].as_f64().unwrap() - 166_979.2).abs() < 0.1);
+        assert!((point[1].as_f64().unwrap() - 5_236_173.8).abs() < 0.1);
+        let line = lines[2]["geometry"]["coordinates"].as_array().unwrap();
+        assert_eq!(line.len(), 2);
+        assert_eq!(line[0], lines[0]["geometry"]["coordinates"]);
+    }
+
+    #[test]
+    fn test_raw_without_metadata() {
+        let mut data = Vec::new();
+        let mut writer = NdjsonWriter::new(&mut data, NdjsonSchema::Raw);
+        writer.set_include_metadata(false);
+        for element in elements() {
+            writer.write(element).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let text = String::from_utf8(data).unwrap();
+        let first: Value = serde_json::from_str(text.lines().next().unwrap()).unwrap();
+        assert_eq!(first["type"], json!("Node"));
+        assert_eq!(first["id"], json!(1));
+        assert!(first.get("version").is_none());
+    }
+}
@@ -0,0 +1,288 @@
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use super::traits::ElementSink;
+use crate::codecs::o5m::{self, StringTable};
+use crate::models::{BasicElement, Bound, Element, ElementType, FileHeader};
+
+/// A writer for the o5m format.
+///
+/// Like `PbfWriter`, the `O5mWriter` writes elements in the order in which `write` is called,
+/// which should be nodes, ways and relations, each in ascending ID order. Elements which are not
+/// visible are written as deleted objects.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node};
+/// use pbf_craft::writers::O5mWriter;
+///
+/// let mut writer = O5mWriter::new(Vec::new());
+/// writer.write(Element::Node(Node { id: 1, visible: true, ..Default::default() })).unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct O5mWriter<W: Write> {
+    writer: W,
+    bbox: Option<Bound>,
+    has_written_header: bool,
+    current_type: Option<ElementType>,
+    string_table: StringTable,
+    id: i64,
+    timestamp: i64,
+    changeset: i64,
+    latitude: i64,
+    longitude: i64,
+    references: [i64; 3],
+}
+
+#[cfg(feature = "fs")]
+impl O5mWriter<BufWriter<File>> {
+    /// Creates a new `O5mWriter` from a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let f = File::create(path)?;
+        Ok(Self::new(BufWriter::new(f)))
+    }
+}
+
+impl<W: Write> O5mWriter<W> {
+    /// Creates a new `O5mWriter` from an existing writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            bbox: None,
+            has_written_header: false,
+            current_type: None,
+            string_table: StringTable::new(),
+            id: 0,
+            timestamp: 0,
+            changeset: 0,
+            latitude: 0,
+            longitude: 0,
+            references: [0; 3],
+        }
+    }
+
+    /// Sets the bounding box of the file. It must be called before writing any elements.
+    pub fn set_bbox(&mut self, bbox: Bound) {
+        self.bbox = Some(bbox);
+    }
+
+    fn write_dataset(&mut self, dataset_type: u8, data: &[u8]) -> anyhow::Result<()> {
+        let mut length = Vec::new();
+        o5m::write_uvarint(&mut length, data.len() as u64);
+        self.writer.write_all(&[dataset_type])?;
+        self.writer.write_all(&length)?;
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> anyhow::Result<()> {
+        self.writer.write_all(&[o5m::DATASET_RESET])?;
+        self.write_dataset(o5m::DATASET_HEADER, o5m::HEADER_MAGIC)?;
+        if let Some(bbox) = self.bbox.take() {
+            let mut data = Vec::new();
+            o5m::write_svarint(&mut data, to_o5m_coordinate(bbox.left));
+            o5m::write_svarint(&mut data, to_o5m_coordinate(bbox.bottom));
+            o5m::write_svarint(&mut data, to_o5m_coordinate(bbox.right));
+            o5m::write_svarint(&mut data, to_o5m_coordinate(bbox.top));
+            self.write_dataset(o5m::DATASET_BOUNDING_BOX, &data)?;
+        }
+        self.has_written_header = true;
+        Ok(())
+    }
+
+    /// Starts a new section with fresh delta counters whenever the element type changes.
+    fn switch_type(&mut self, element_type: ElementType) -> anyhow::Result<()> {
+        if self.current_type.as_ref() != Some(&element_type) {
+            if self.current_type.is_some() {
+                self.writer.write_all(&[o5m::DATASET_RESET])?;
+                self.string_table.reset();
+                self.id = 0;
+                self.timestamp = 0;
+                self.changeset = 0;
+                self.latitude = 0;
+                self.longitude = 0;
+                self.references = [0; 3];
+            }
+            self.current_type = Some(element_type);
+        }
+        Ok(())
+    }
+
+    fn encode_base<E: BasicElement>(&mut self, data: &mut Vec<u8>, element: &E) {
+        o5m::write_svarint(data, element.get_id() - self.id);
+        self.id = element.get_id();
+
+        let version = element.get_version().max(0) as u64;
+        o5m::write_uvarint(data, version);
+        if version == 0 {
+            return;
+        }
+        let timestamp = element
+            .get_timestamp()
+            .map(|time| time.timestamp())
+            .unwrap_or(0);
+        o5m::write_svarint(data, timestamp - self.timestamp);
+        self.timestamp = timestamp;
+        if timestamp == 0 {
+            return;
+        }
+        o5m::write_svarint(data, element.get_changeset_id() - self.changeset);
+        self.changeset = element.get_changeset_id();
+        let (uid, name) = match element.get_user() {
+            Some(user) => (user.id, user.name.as_bytes()),
+            None => (0, "".as_bytes()),
+        };
+        self.string_table
+            .write(data, &[o5m::encode_uid(uid).as_slice(), name]);
+    }
+
+    fn encode_tags<E: BasicElement>(&mut self, data: &mut Vec<u8>, element: &E) {
+        for tag in element.get_tags() {
+            self.string_table
+                .write(data, &[tag.key.as_bytes(), tag.value.as_bytes()]);
+        }
+    }
+
+    /// Writes an element.
+    pub fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        if !self.has_written_header {
+            self.write_header()?;
+        }
+        let (element_type, _) = element.get_meta();
+        self.switch_type(element_type)?;
+
+        let mut data = Vec::new();
+        let dataset_type = match &element {
+            Element::Node(node) => {
+                self.encode_base(&mut data, node);
+                if node.visible {
+                    let longitude = to_o5m_coordinate(node.longitude);
+                    let latitude = to_o5m_coordinate(node.latitude);
+                    o5m::write_svarint(&mut data, longitude - self.longitude);
+                    o5m::write_svarint(&mut data, latitude - self.latitude);
+                    self.longitude = longitude;
+                    self.latitude = latitude;
+                    self.encode_tags(&mut data, node);
+                }
+                o5m::DATASET_NODE
+            }
+            Element::Way(way) => {
+                self.encode_base(&mut data, way);
+                if way.visible {
+                    let mut references = Vec::new();
+                    for way_node in &way.way_nodes {
+                        o5m::write_svarint(&mut references, way_node.id - self.references[0]);
+                        self.references[0] = way_node.id;
+                    }
+                    o5m::write_uvarint(&mut data, references.len() as u64);
+                    data.extend_from_slice(&references);
+                    self.encode_tags(&mut data, way);
+                }
+                o5m::DATASET_WAY
+            }
+            Element::Relation(relation) => {
+                self.encode_base(&mut data, relation);
+                if relation.visible {
+                    let mut references = Vec::new();
+                    for member in &relation.members {
+                        let (index, type_char) = match member.member_type {
+                            ElementType::Node => (0, b'0'),
+                            ElementType::Way => (1, b'1'),
+                            ElementType::Relation => (2, b'2'),
+                        };
+                        o5m::write_svarint(
+                            &mut references,
+                            member.member_id - self.references[index],
+                        );
+                        self.references[index] = member.member_id;
+                        let mut type_and_role = vec![type_char];
+                        type_and_role.extend_from_slice(member.role.as_bytes());
+                        self.string_table
+                            .write(&mut references, &[type_and_role.as_slice()]);
+                    }
+                    o5m::write_uvarint(&mut data, references.len() as u64);
+                    data.extend_from_slice(&references);
+                    self.encode_tags(&mut data, relation);
+                }
+                o5m::DATASET_RELATION
+            }
+        };
+        self.write_dataset(dataset_type, &data)
+    }
+
+    /// Finishes writing the o5m file.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        if !self.has_written_header {
+            self.write_header()?;
+        }
+        self.writer.write_all(&[o5m::DATASET_END_OF_FILE])?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn to_o5m_coordinate(nanodegrees: i64) -> i64 {
+    (nanodegrees + o5m::COORDINATE_UNIT / 2).div_euclid(o5m::COORDINATE_UNIT)
+}
+
+impl<W: Write> ElementSink for O5mWriter<W> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        O5mWriter::write(self, element)
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        if let Some(bbox) = header.bbox {
+            self.set_bbox(bbox);
+        }
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        O5mWriter::finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::readers::{ElementSource, IterableReader, O5mReader};
+
+    #[test]
+    fn test_round_trip() {
+        let elements: Vec<Element> =
+            IterableReader::from_path("./resources/andorra-latest.osm.pbf")
+                .unwrap()
+                .collect();
+
+        let mut data = Vec::new();
+        let mut writer = O5mWriter::new(&mut data);
+        writer.set_bbox(Bound {
+            left: 1_400_000_000,
+            right: 1_800_000_000,
+            top: 42_700_000_000,
+            bottom: 42_400_000_000,
+        });
+        for element in elements.iter() {
+            writer.write(element.clone()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = O5mReader::new(data.as_slice());
+        for expected in elements.iter() {
+            let actual = reader.next_element().unwrap().unwrap();
+            match (expected, &actual) {
+                (Element::Node(a), Element::Node(b)) => assert_eq!(a, b),
+                (Element::Way(a), Element::Way(b)) => assert_eq!(a, b),
+                (Element::Relation(a), Element::Relation(b)) => assert_eq!(a, b),
+                _ => panic!("element type mismatch"),
+            }
+        }
+        assert!(reader.next_element().unwrap().is_none());
+        assert_eq!(reader.bound().unwrap().top, 42_700_000_000);
+    }
+}
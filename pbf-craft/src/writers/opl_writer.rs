@@ -0,0 +1,210 @@
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use chrono::SecondsFormat;
+
+use super::traits::ElementSink;
+use crate::models::{BasicElement, Element, ElementType};
+use crate::utils::xml::format_coordinate;
+
+/// A writer of the OPL format, writing one element per line, as in
+/// `n1 v2 dV c3 t2024-01-01T00:00:00Z i4 ualice Tname=Main%20%Street x1.5000000 y42.5000000`.
+///
+/// It's easy to grep and diff, which makes it handy for debugging. Spaces, commas, equal signs,
+/// at signs, percent signs and control characters in strings are escaped as `%` followed by
+/// their hexadecimal code point and another `%`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node};
+/// use pbf_craft::writers::OplWriter;
+///
+/// let mut writer = OplWriter::new(Vec::new());
+/// writer.write(Element::Node(Node { id: 1, visible: true, ..Default::default() })).unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct OplWriter<W: Write> {
+    writer: W,
+}
+
+#[cfg(feature = "fs")]
+impl OplWriter<BufWriter<File>> {
+    /// Creates a new `OplWriter` from a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let f = File::create(path)?;
+        Ok(Self::new(BufWriter::new(f)))
+    }
+}
+
+impl<W: Write> OplWriter<W> {
+    /// Creates a new `OplWriter` from an existing writer.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes an element as a single line.
+    pub fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        let line = match &element {
+            Element::Node(node) => format!(
+                "n{} x{} y{}",
+                attributes(node),
+                format_coordinate(node.longitude),
+                format_coordinate(node.latitude)
+            ),
+            Element::Way(way) => {
+                let way_nodes: Vec<String> = way
+                    .way_nodes
+                    .iter()
+                    .map(|way_node| format!("n{}", way_node.id))
+                    .collect();
+                format!("w{} N{}", attributes(way), way_nodes.join(","))
+            }
+            Element::Relation(relation) => {
+                let members: Vec<String> = relation
+                    .members
+                    .iter()
+                    .map(|member| {
+                        let prefix = match member.member_type {
+                            ElementType::Node => 'n',
+                            ElementType::Way => 'w',
+                            ElementType::Relation => 'r',
+                        };
+                        format!("{}{}@{}", prefix, member.member_id, escape(&member.role))
+                    })
+                    .collect();
+                format!("r{} M{}", attributes(relation), members.join(","))
+            }
+        };
+        writeln!(self.writer, "{}", line)?;
+        Ok(())
+    }
+
+    /// Flushes the underlying writer.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Formats the ID, metadata and tags of an element.
+fn attributes<E: BasicElement>(element: &E) -> String {
+    let timestamp = element
+        .get_timestamp()
+        .map(|timestamp| timestamp.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default();
+    let (uid, user) = match element.get_user() {
+        Some(user) => (user.id, escape(&user.name)),
+        None => (0, String::new()),
+    };
+    let tags: Vec<String> = element
+        .get_tags()
+        .iter()
+        .map(|tag| format!("{}={}", escape(&tag.key), escape(&tag.value)))
+        .collect();
+    format!(
+        "{} v{} d{} c{} t{} i{} u{} T{}",
+        element.get_id(),
+        element.get_version(),
+        if element.is_visible() { 'V' } else { 'D' },
+        element.get_changeset_id(),
+        timestamp,
+        uid,
+        user,
+        tags.join(",")
+    )
+}
+
+/// Escapes the characters which separate the fields of a line.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_whitespace() || c.is_control() || matches!(c, ',' | '=' | '@' | '%') {
+            escaped.push_str(&format!("%{:x}%", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+impl<W: Write> ElementSink for OplWriter<W> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        OplWriter::write(self, element)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        OplWriter::finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, OsmUser, Relation, RelationMember, Tag, Way, WayNode};
+    use chrono::DateTime;
+
+    #[test]
+    fn test_opl_lines() {
+        let mut data = Vec::new();
+        let mut writer = OplWriter::new(&mut data);
+        writer
+            .write(Element::Node(Node {
+                id: 1,
+                version: 2,
+                changeset_id: 3,
+                timestamp: DateTime::from_timestamp(1_700_000_000, 0),
+                user: Some(OsmUser {
+                    id: 4,
+                    name: "alice".into(),
+                }),
+                latitude: 42_500_000_000,
+                longitude: 1_500_000_000,
+                visible: true,
+                tags: vec![Tag {
+                    key: "name".to_string(),
+                    value: "Main Street, 50%".to_string(),
+                }],
+            }))
+            .unwrap();
+        writer
+            .write(Element::Way(Way {
+                id: 10,
+                way_nodes: vec![
+                    WayNode::new_without_coords(1),
+                    WayNode::new_without_coords(2),
+                ],
+                ..Default::default()
+            }))
+            .unwrap();
+        writer
+            .write(Element::Relation(Relation {
+                id: 5,
+                visible: true,
+                members: vec![RelationMember {
+                    member_type: ElementType::Way,
+                    member_id: 10,
+                    role: "outer".into(),
+                }],
+                ..Default::default()
+            }))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let document = String::from_utf8(data).unwrap();
+        let lines: Vec<&str> = document.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "n1 v2 dV c3 t2023-11-14T22:13:20Z i4 ualice Tname=Main%20%Street%2c%%20%50%25% x1.5000000 y42.5000000",
+                "w10 v0 dD c0 t i0 u T Nn1,n2",
+                "r5 v0 dV c0 t i0 u T Mw10@outer",
+            ]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use super::traits::ElementSink;
+use crate::analysis::OrphanNodes;
+use crate::models::{Element, FileHeader};
+use crate::readers::Provenance;
+
+/// A sink that drops orphan nodes and passes all other elements on to the wrapped sink.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::analysis::OrphanNodes;
+/// use pbf_craft::readers::{ElementSource, IterableReader};
+/// use pbf_craft::writers::{ElementSink, OrphanPruningSink, PbfWriter};
+///
+/// let path = "resources/andorra-latest.osm.pbf";
+/// let orphans = OrphanNodes::scan(IterableReader::from_path(path).unwrap()).unwrap();
+///
+/// let mut reader = IterableReader::from_path(path).unwrap();
+/// let mut writer = OrphanPruningSink::new(PbfWriter::new(Vec::new(), true), orphans);
+/// while let Some(element) = reader.next_element().unwrap() {
+///     writer.write(element).unwrap();
+/// }
+/// writer.finish().unwrap();
+/// println!("{} orphan nodes dropped", writer.pruned());
+/// ```
+pub struct OrphanPruningSink<S: ElementSink> {
+    sink: S,
+    orphans: OrphanNodes,
+    pruned: u64,
+}
+
+impl<S: ElementSink> OrphanPruningSink<S> {
+    /// Creates a new `OrphanPruningSink` from the result of `OrphanNodes::scan`.
+    pub fn new(sink: S, orphans: OrphanNodes) -> Self {
+        Self {
+            sink,
+            orphans,
+            pruned: 0,
+        }
+    }
+
+    /// Returns the number of nodes dropped so far.
+    pub fn pruned(&self) -> u64 {
+        self.pruned
+    }
+
+    /// Consumes the `OrphanPruningSink` and returns the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: ElementSink> ElementSink for OrphanPruningSink<S> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        self.write_with_provenance(element, None)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        if let Element::Node(node) = &element {
+            if self.orphans.is_orphan(node) {
+                self.pruned += 1;
+                return Ok(());
+            }
+        }
+        self.sink.write_with_provenance(element, provenance)
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        self.sink.set_header(header);
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Tag, Way, WayNode};
+    use crate::writers::CountingSink;
+
+    #[test]
+    fn test_prune_orphans() {
+        let elements = vec![
+            Element::Node(Node {
+                id: 1,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 2,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 3,
+                tags: vec![Tag {
+                    key: "amenity".to_string(),
+                    value: "bench".to_string(),
+                }],
+                ..Default::default()
+            }),
+            Element::Way(Way {
+                id: 1,
+                way_nodes: vec![WayNode::new_without_coords(2)],
+                ..Default::default()
+            }),
+        ];
+        let orphans = OrphanNodes::scan(elements.clone().into_iter()).unwrap();
+        assert_eq!(orphans.find(elements.clone().into_iter()).unwrap(), vec![1]);
+
+        let mut sink = OrphanPruningSink::new(CountingSink::new(), orphans);
+        for element in elements {
+            sink.write(element).unwrap();
+        }
+        sink.finish().unwrap();
+        assert_eq!(sink.pruned(), 1);
+        let counts = sink.into_inner();
+        assert_eq!((counts.nodes, counts.ways), (2, 1));
+    }
+}
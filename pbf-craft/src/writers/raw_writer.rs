@@ -1,18 +1,131 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
+#[cfg(feature = "fs")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+use std::io::{Seek, SeekFrom, Write};
 use std::mem;
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 use byteorder::{self, WriteBytesExt};
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use protobuf::Message;
+use serde::{Deserialize, Serialize};
 
-use crate::codecs::block_builder::PrimitiveBuilder;
-use crate::models::{Bound, Element};
+use super::traits::ElementSink;
+use crate::codecs::blob::{RawBlob, MAX_BLOB_HEADER_SIZE, MAX_BLOB_SIZE};
+use crate::codecs::block_builder::{NodeEncoding, PrimitiveBuilder};
+use crate::codecs::block_decorators::HeaderReader;
+use crate::models::{Bound, Element, ElementType, FileHeader};
 use crate::proto::{fileformat, osmformat};
+use crate::utils::LocationIndex;
+use crate::validation::{ValidationIssue, ValidationPolicy, ValidationRule};
 
 const MAX_BLOCK_ITEM_LENGTH: usize = 8000;
+/// The date granularity of the PBF specification, in milliseconds.
+const DEFAULT_DATE_GRANULARITY: i32 = 1000;
+/// The size of a `HeaderBBox` message whose four coordinates are varints padded to 10 bytes.
+const PADDED_BBOX_SIZE: usize = 4 * 11;
+/// The key of the `bbox` field of a `HeaderBlock`: field 1, length-delimited.
+const BBOX_KEY: u8 = (1 << 3) | 2;
+/// The key of field 15 of a `HeaderBlock`, which is unused, so readers skip it.
+const UNUSED_FIELD_KEY: u8 = (15 << 3) | 2;
+
+/// How the blobs written by `PbfWriter` are compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlobCompression {
+    /// Writes uncompressed `raw` blobs. The files are several times larger, but writing and
+    /// reading them takes less CPU, e.g. when the output is consumed by another local process.
+    None,
+    /// Compresses the blobs with zlib, which all PBF readers support.
+    #[default]
+    Zlib,
+}
+
+/// Which element types a block written by `PbfWriter` may contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockComposition {
+    /// Blocks are flushed when they are full, so a block may contain elements of several
+    /// types, each type in its own primitive group.
+    #[default]
+    Mixed,
+    /// A block is also flushed when the type of the elements written changes, so every block
+    /// contains a single type. Some consumers, such as older versions of osmosis, expect this.
+    Homogeneous,
+}
+
+/// The sizes and contents of a data block written by `PbfWriter`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockReport {
+    /// The byte offset of the blob in the output.
+    pub offset: u64,
+    /// The size of the encoded block before compression.
+    pub uncompressed_size: u64,
+    /// The size of the block data stored in the blob, which equals the uncompressed size for
+    /// uncompressed blobs.
+    pub compressed_size: u64,
+    /// The number of strings in the string table of the block.
+    pub strings: u64,
+    /// The total size of the strings in the string table.
+    pub string_table_size: u64,
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+}
+
+/// The state of a `PbfWriter` at a checkpoint, to continue the output after a crash with
+/// `PbfWriter::resume_from`. It can be stored as JSON with serde.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriterCheckpoint {
+    /// The size of the output up to the checkpoint, which ends after a complete blob.
+    pub offset: u64,
+    /// The type and ID of the last element written before the checkpoint. The elements after
+    /// it are the ones to write after resuming.
+    pub last_element: Option<(ElementType, i64)>,
+    /// The number of elements written before the checkpoint.
+    pub elements: u64,
+    /// Whether the header has the `HistoricalInformation` feature, allowing deleted elements.
+    pub historical_information: bool,
+}
+
+/// A report of the data blocks written by `PbfWriter`, e.g. for tuning the file size.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteReport {
+    /// The data blocks in the order they were written.
+    pub blocks: Vec<BlockReport>,
+}
+
+impl WriteReport {
+    /// Returns the total size of the blocks before compression.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.blocks
+            .iter()
+            .map(|block| block.uncompressed_size)
+            .sum()
+    }
+
+    /// Returns the total size of the block data stored in the blobs.
+    pub fn compressed_size(&self) -> u64 {
+        self.blocks.iter().map(|block| block.compressed_size).sum()
+    }
+
+    /// Returns the total size of the string tables.
+    pub fn string_table_size(&self) -> u64 {
+        self.blocks
+            .iter()
+            .map(|block| block.string_table_size)
+            .sum()
+    }
+
+    /// Returns the total number of elements written.
+    pub fn elements(&self) -> u64 {
+        self.blocks
+            .iter()
+            .map(|block| block.nodes + block.ways + block.relations)
+            .sum()
+    }
+}
 
 /// A writer for creating PBF files.
 ///
@@ -41,12 +154,97 @@ const MAX_BLOCK_ITEM_LENGTH: usize = 8000;
 /// ```
 pub struct PbfWriter<W: Write> {
     writer: W,
-    use_dense: bool,
-    bbox: Option<Bound>,
+    node_encoding: NodeEncoding,
+    compression: BlobCompression,
+    block_composition: BlockComposition,
+    date_granularity: i32,
+    lossless_timestamps: bool,
+    anonymous_users_as_none: bool,
+    enforce_size_limits: bool,
+    header: FileHeader,
+    source_header: Option<HeaderReader>,
+    locations_on_ways: bool,
+    historical_information: bool,
+    location_index: LocationIndex,
     cache: Vec<Element>,
     has_writen_header: bool,
+    position: u64,
+    report: WriteReport,
+    auto_bbox: Option<AutoBbox<W>>,
+    last_element: Option<(ElementType, i64)>,
+    elements: u64,
+    validation_rules: Vec<(Box<dyn ValidationRule>, ValidationPolicy)>,
+    validation_issues: Vec<ValidationIssue>,
+}
+
+/// The bounding box computed from the elements written, see `PbfWriter::set_auto_bbox`.
+struct AutoBbox<W> {
+    bound: Option<Bound>,
+    /// The offset of the placeholder of the bounding box in the output, once the header is
+    /// written.
+    placeholder: Option<u64>,
+    /// Overwrites bytes the given distance back from the current position of the output.
+    patch: fn(&mut W, u64, &[u8]) -> std::io::Result<()>,
 }
 
+impl<W> AutoBbox<W> {
+    fn extend(&mut self, latitude: i64, longitude: i64) {
+        match &mut self.bound {
+            Some(bound) => {
+                bound.left = bound.left.min(longitude);
+                bound.right = bound.right.max(longitude);
+                bound.bottom = bound.bottom.min(latitude);
+                bound.top = bound.top.max(latitude);
+            }
+            None => {
+                self.bound = Some(Bound {
+                    left: longitude,
+                    right: longitude,
+                    top: latitude,
+                    bottom: latitude,
+                })
+            }
+        }
+    }
+}
+
+/// Encodes a `HeaderBBox` message with the coordinates padded to 10-byte varints, so that it
+/// can be overwritten by the one of any other bounding box.
+fn encode_padded_bbox(bound: &Bound) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(PADDED_BBOX_SIZE);
+    for (field, value) in [bound.left, bound.right, bound.top, bound.bottom]
+        .into_iter()
+        .enumerate()
+    {
+        // The fields are sint64 with the numbers 1 to 4
+        bytes.push(((field as u8) + 1) << 3);
+        let zigzag = ((value << 1) ^ (value >> 63)) as u64;
+        for i in 0..9 {
+            bytes.push((zigzag >> (7 * i)) as u8 & 0x7f | 0x80);
+        }
+        bytes.push((zigzag >> 63) as u8);
+    }
+    bytes
+}
+
+/// The bounding box of the whole world, in nanodegrees.
+fn world_bound() -> Bound {
+    Bound {
+        left: -180_000_000_000,
+        right: 180_000_000_000,
+        top: 90_000_000_000,
+        bottom: -90_000_000_000,
+    }
+}
+
+fn patch_output<W: Write + Seek>(writer: &mut W, back: u64, bytes: &[u8]) -> std::io::Result<()> {
+    writer.seek(SeekFrom::Current(-(back as i64)))?;
+    writer.write_all(bytes)?;
+    writer.seek(SeekFrom::Current(back as i64 - bytes.len() as i64))?;
+    Ok(())
+}
+
+#[cfg(feature = "fs")]
 impl PbfWriter<BufWriter<File>> {
     /// Creates a new `PbfWriter` from a file path.
     ///
@@ -60,6 +258,36 @@ impl PbfWriter<BufWriter<File>> {
         let writer = BufWriter::new(f);
         Ok(Self::new(writer, use_dense))
     }
+
+    /// Continues a file after a checkpoint with `PbfWriter::resume_from`, removing anything
+    /// written to it after the checkpoint.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be opened or is shorter than the
+    /// output at the checkpoint.
+    pub fn resume_from_path<P: AsRef<Path>>(
+        path: P,
+        checkpoint: &WriterCheckpoint,
+        use_dense: bool,
+    ) -> anyhow::Result<Self> {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        let size = file.metadata()?.len();
+        if size < checkpoint.offset {
+            bail!(
+                "The file has {} bytes, fewer than the {} bytes written at the checkpoint",
+                size,
+                checkpoint.offset
+            );
+        }
+        file.set_len(checkpoint.offset)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Self::resume_from(
+            BufWriter::new(file),
+            checkpoint,
+            use_dense,
+        ))
+    }
 }
 
 impl<W: Write> PbfWriter<W> {
@@ -74,56 +302,474 @@ impl<W: Write> PbfWriter<W> {
     pub fn new(writer: W, use_dense: bool) -> PbfWriter<W> {
         Self {
             writer,
-            use_dense,
-            bbox: None,
+            node_encoding: if use_dense {
+                NodeEncoding::Dense
+            } else {
+                NodeEncoding::NonDense
+            },
+            compression: BlobCompression::default(),
+            block_composition: BlockComposition::default(),
+            date_granularity: DEFAULT_DATE_GRANULARITY,
+            lossless_timestamps: false,
+            anonymous_users_as_none: false,
+            enforce_size_limits: true,
+            header: FileHeader::default(),
+            source_header: None,
+            locations_on_ways: false,
+            historical_information: false,
+            location_index: LocationIndex::new(),
             cache: Vec::new(),
             has_writen_header: false,
+            position: 0,
+            report: WriteReport::default(),
+            auto_bbox: None,
+            last_element: None,
+            elements: 0,
+            validation_rules: Vec::new(),
+            validation_issues: Vec::new(),
         }
     }
 
-    fn build_raw_blob(&mut self, raw: Vec<u8>) -> anyhow::Result<fileformat::Blob> {
-        let raw_size = raw.len();
-        let mut zlib_encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        zlib_encoder.write_all(raw.as_slice())?;
-        let compressed = zlib_encoder.finish()?;
+    /// Creates a `PbfWriter` continuing an output after a checkpoint, e.g. a long export which
+    /// crashed. The writer must be positioned at `checkpoint.offset`, with anything written
+    /// after it removed, and the elements after `checkpoint.last_element` are to be written.
+    ///
+    /// The header isn't written again, so the settings changing it, such as the bounding box,
+    /// have no effect; an automatic bounding box only covers the elements written after
+    /// resuming. The location index of `set_locations_on_ways` starts empty and may need to be
+    /// restored with `set_location_index`. The other settings should be set as before.
+    pub fn resume_from(writer: W, checkpoint: &WriterCheckpoint, use_dense: bool) -> PbfWriter<W> {
+        let mut pbf_writer = Self::new(writer, use_dense);
+        pbf_writer.has_writen_header = true;
+        pbf_writer.position = checkpoint.offset;
+        pbf_writer.historical_information = checkpoint.historical_information;
+        pbf_writer.last_element = checkpoint.last_element.clone();
+        pbf_writer.elements = checkpoint.elements;
+        pbf_writer
+    }
 
+    /// Writes the cached elements to a block and flushes the output, and returns the state to
+    /// continue from with `resume_from` if the process stops before `finish`.
+    ///
+    /// Blocks written at checkpoints may hold fewer elements than the others, so checkpoints
+    /// should be taken every few million elements rather than after each one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::{Element, ElementType, Node};
+    /// use pbf_craft::writers::PbfWriter;
+    ///
+    /// let path = std::env::temp_dir().join("pbf_craft_checkpoint.osm.pbf");
+    /// let node = |id: i64| {
+    ///     let mut node = Node::default();
+    ///     node.id = id;
+    ///     node.visible = true;
+    ///     Element::Node(node)
+    /// };
+    /// let mut writer = PbfWriter::from_path(&path, true).unwrap();
+    /// writer.write(node(1)).unwrap();
+    /// let checkpoint = writer.checkpoint().unwrap();
+    /// writer.write(node(2)).unwrap();
+    /// drop(writer);
+    ///
+    /// // The export stopped before `finish`, so it continues after the last checkpoint
+    /// assert_eq!(checkpoint.last_element, Some((ElementType::Node, 1)));
+    /// let mut writer = PbfWriter::resume_from_path(&path, &checkpoint, true).unwrap();
+    /// writer.write(node(2)).unwrap();
+    /// writer.finish().unwrap();
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn checkpoint(&mut self) -> anyhow::Result<WriterCheckpoint> {
+        if !self.has_writen_header {
+            self.write_header()?;
+        }
+        self.flush_block()?;
+        self.writer.flush()?;
+        Ok(WriterCheckpoint {
+            offset: self.position,
+            last_element: self.last_element.clone(),
+            elements: self.elements,
+            historical_information: self.historical_information,
+        })
+    }
+
+    fn build_raw_blob(&mut self, raw: Vec<u8>) -> anyhow::Result<fileformat::Blob> {
+        if self.enforce_size_limits && raw.len() as u64 > MAX_BLOB_SIZE {
+            bail!(
+                "The block is {} bytes uncompressed, exceeding the limit of {} bytes",
+                raw.len(),
+                MAX_BLOB_SIZE
+            );
+        }
         let mut blob = fileformat::Blob::new();
-        blob.set_zlib_data(compressed);
-        blob.set_raw_size(raw_size as i32);
+        match self.compression {
+            BlobCompression::None => blob.set_raw(raw),
+            BlobCompression::Zlib => {
+                let raw_size = raw.len();
+                let mut zlib_encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                zlib_encoder.write_all(raw.as_slice())?;
+                let compressed = zlib_encoder.finish()?;
+
+                blob.set_zlib_data(compressed);
+                blob.set_raw_size(raw_size as i32);
+            }
+        }
         Ok(blob)
     }
 
     /// Sets the bounding box for the PBF file.
     ///
     /// If you want to include a bounding box in the PBF file, you set it before writing any elements.
+    /// It replaces a bounding box computed with `set_auto_bbox`.
     ///
     pub fn set_bbox(&mut self, bbox: Bound) {
-        self.bbox = Some(bbox);
+        self.header.bbox = Some(bbox);
+        self.auto_bbox = None;
+    }
+
+    /// Sets the header of the PBF file, e.g. one read with `HeaderReader::file_header`.
+    ///
+    /// The bounding box, source, writing program and replication information replace those of
+    /// the source header if they are set, and the features are added to its features. The
+    /// features describing the encoding are adjusted as with `set_source_header`.
+    ///
+    /// It must be called before writing any elements.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::{Bound, FileHeader};
+    /// use pbf_craft::writers::PbfWriter;
+    ///
+    /// let mut writer = PbfWriter::new(Vec::new(), true);
+    /// writer.set_header(FileHeader {
+    ///     bbox: Some(Bound {
+    ///         left: 1_400_000_000,
+    ///         right: 1_800_000_000,
+    ///         top: 42_700_000_000,
+    ///         bottom: 42_400_000_000,
+    ///     }),
+    ///     source: Some("http://www.openstreetmap.org/api/0.6".to_string()),
+    ///     replication_sequence_number: Some(4_200),
+    ///     ..Default::default()
+    /// });
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn set_header(&mut self, header: FileHeader) {
+        if header.bbox.is_some() {
+            self.auto_bbox = None;
+        }
+        self.header = header;
+    }
+
+    /// Sets how nodes are encoded, replacing the choice made with `use_dense` when creating the
+    /// writer. `NodeEncoding::Auto` chooses the smaller encoding for the nodes of each block.
+    ///
+    /// It must be called before writing any elements.
+    ///
+    pub fn set_node_encoding(&mut self, node_encoding: NodeEncoding) {
+        self.node_encoding = node_encoding;
+    }
+
+    /// Sets how the blobs are compressed. It must be called before writing any elements.
+    pub fn set_compression(&mut self, compression: BlobCompression) {
+        self.compression = compression;
+    }
+
+    /// Sets which element types a block may contain. It must be called before writing any
+    /// elements.
+    pub fn set_block_composition(&mut self, block_composition: BlockComposition) {
+        self.block_composition = block_composition;
+    }
+
+    /// Sets the unit of the timestamps written, in milliseconds. It defaults to 1000, so the
+    /// milliseconds of timestamps are dropped; use 1 to keep them. Readers decode timestamps with
+    /// the date granularity of each block, so any value is read back correctly.
+    ///
+    /// It must be called before writing any elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `date_granularity` isn't positive.
+    pub fn set_date_granularity(&mut self, date_granularity: i32) {
+        assert!(
+            date_granularity > 0,
+            "The date granularity must be positive"
+        );
+        self.date_granularity = date_granularity;
+    }
+
+    /// Sets whether writing an element fails if its timestamp can't be represented exactly with
+    /// the date granularity, instead of truncating the timestamp. It's disabled by default.
+    pub fn set_lossless_timestamps(&mut self, lossless_timestamps: bool) {
+        self.lossless_timestamps = lossless_timestamps;
+    }
+
+    /// Sets whether the users with uid 0 and an empty name are written like missing users, the
+    /// reverse of `DecodeOptions::anonymous_users_as_none`. The metadata of elements which have
+    /// no other metadata is then left out. It's disabled by default.
+    pub fn set_anonymous_users_as_none(&mut self, anonymous_users_as_none: bool) {
+        self.anonymous_users_as_none = anonymous_users_as_none;
+    }
+
+    /// Sets whether writing blobs exceeding the sizes allowed by the specification, 64 KiB for
+    /// blob headers and 32 MiB for blobs, fails. It's enabled by default, as most readers
+    /// reject such files.
+    pub fn set_enforce_size_limits(&mut self, enforce_size_limits: bool) {
+        self.enforce_size_limits = enforce_size_limits;
+    }
+
+    /// Uses the header of another PBF file as the base of the header to write.
+    ///
+    /// Fields the writer does not know about, such as optional features like
+    /// `Sort.Type_then_ID` or replication information, are copied as they are. The features
+    /// describing the encoding (`DenseNodes` and `LocationsOnWays`) are adjusted to match how
+    /// this writer encodes the elements, and the other fields are replaced if they are set with
+    /// `set_header`, `set_bbox` or `set_writing_program`.
+    ///
+    /// It must be called before writing any elements.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::PbfReader;
+    /// use pbf_craft::writers::PbfWriter;
+    ///
+    /// let mut reader = PbfReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+    /// let mut writer = PbfWriter::new(Vec::new(), true);
+    /// reader.read(|header, element| {
+    ///     if let Some(header) = header {
+    ///         writer.set_source_header(header);
+    ///     }
+    ///     if let Some(element) = element {
+    ///         writer.write(element).unwrap();
+    ///     }
+    /// }).unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    ///
+    pub fn set_source_header(&mut self, header: HeaderReader) {
+        self.source_header = Some(header);
+    }
+
+    /// Sets the writing program recorded in the header.
+    pub fn set_writing_program(&mut self, writing_program: String) {
+        self.header.writing_program = Some(writing_program);
+    }
+
+    /// Sets whether the locations of way nodes are written with the ways, which is announced
+    /// by the `LocationsOnWays` feature in the header.
+    ///
+    /// Way nodes without a location get the location from the location index. The nodes written
+    /// by this writer are added to the index, so when writing a complete file in the proper
+    /// order, the locations are known before the ways are written. Otherwise, provide the
+    /// locations with `set_location_index`. Writing a way fails if the location of one of its
+    /// nodes is unknown.
+    ///
+    /// It must be called before writing any elements.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::readers::IterableReader;
+    /// use pbf_craft::writers::PbfWriter;
+    ///
+    /// let mut writer = PbfWriter::new(Vec::new(), true);
+    /// writer.set_locations_on_ways(true);
+    /// for element in IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap() {
+    ///     writer.write(element).unwrap();
+    /// }
+    /// writer.finish().unwrap();
+    /// ```
+    ///
+    pub fn set_locations_on_ways(&mut self, locations_on_ways: bool) {
+        self.locations_on_ways = locations_on_ways;
+    }
+
+    /// Sets whether the header announces the `HistoricalInformation` feature, which is required
+    /// for writing deleted elements, i.e. elements which aren't visible.
+    ///
+    /// The feature is also added if a deleted element is among the elements of the first block,
+    /// or if the source header has it. Writing a deleted element after the header has been
+    /// written without the feature fails, so set it when writing history files.
+    ///
+    /// It must be called before writing any elements.
+    ///
+    pub fn set_historical_information(&mut self, historical_information: bool) {
+        self.historical_information = historical_information;
+    }
+
+    /// Sets the index the locations of way nodes are taken from when writing locations on ways.
+    pub fn set_location_index(&mut self, location_index: LocationIndex) {
+        self.location_index = location_index;
+    }
+
+    /// Adds a rule checking the elements before they are written, e.g. `CoordinateRangeRule` to
+    /// keep out-of-range coordinates from breaking the consumers of the file. The policy sets
+    /// whether an element with an issue fails the write, is left out or is written anyway.
+    ///
+    /// The issues found are kept, see `validation_issues`, and those of skipped and written
+    /// elements are logged as warnings.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pbf_craft::models::{Element, Node};
+    /// use pbf_craft::validation::{CoordinateRangeRule, TagLengthRule, ValidationPolicy};
+    /// use pbf_craft::writers::PbfWriter;
+    ///
+    /// let mut writer = PbfWriter::new(Vec::new(), true);
+    /// writer.add_validation_rule(CoordinateRangeRule, ValidationPolicy::Reject);
+    /// writer.add_validation_rule(TagLengthRule::default(), ValidationPolicy::Warn);
+    /// let mut node = Node::default();
+    /// node.visible = true;
+    /// node.latitude = 91_000_000_000;
+    /// assert!(writer.write(Element::Node(node)).is_err());
+    /// assert_eq!(writer.validation_issues()[0].rule, "coordinate-range");
+    /// ```
+    pub fn add_validation_rule<R: ValidationRule + 'static>(
+        &mut self,
+        rule: R,
+        policy: ValidationPolicy,
+    ) {
+        self.validation_rules.push((Box::new(rule), policy));
+    }
+
+    /// Returns the issues found by the rules added with `add_validation_rule`.
+    pub fn validation_issues(&self) -> &[ValidationIssue] {
+        &self.validation_issues
+    }
+
+    /// Checks an element against the validation rules, and returns whether to write it.
+    fn validate(&mut self, element: &Element) -> anyhow::Result<bool> {
+        let mut keep = true;
+        for (rule, policy) in &self.validation_rules {
+            let found = self.validation_issues.len();
+            rule.check(element, &mut self.validation_issues);
+            for issue in &self.validation_issues[found..] {
+                match policy {
+                    ValidationPolicy::Reject => bail!(
+                        "{}/{} was rejected by the rule {}: {}",
+                        issue.element_type,
+                        issue.element_id,
+                        issue.rule,
+                        issue.message
+                    ),
+                    ValidationPolicy::Skip => {
+                        warn!(
+                            "Skipping {}/{}: {}",
+                            issue.element_type, issue.element_id, issue.message
+                        );
+                        keep = false;
+                    }
+                    ValidationPolicy::Warn => warn!(
+                        "Writing {}/{} despite an issue: {}",
+                        issue.element_type, issue.element_id, issue.message
+                    ),
+                }
+            }
+        }
+        Ok(keep)
     }
 
     fn write_header(&mut self) -> anyhow::Result<()> {
-        let mut header_block = osmformat::HeaderBlock::new();
+        let mut header_block = match &self.source_header {
+            Some(source_header) => source_header.header_block().clone(),
+            None => osmformat::HeaderBlock::new(),
+        };
+        let header = &self.header;
+        for feature in &header.required_features {
+            if !header_block.required_features.contains(feature) {
+                header_block.required_features.push(feature.clone());
+            }
+        }
+        for feature in &header.optional_features {
+            if !header_block.optional_features.contains(feature) {
+                header_block.optional_features.push(feature.clone());
+            }
+        }
+        if let Some(source) = &header.source {
+            header_block.set_source(source.clone());
+        }
+        if let Some(timestamp) = header.replication_timestamp {
+            header_block.set_osmosis_replication_timestamp(timestamp.timestamp());
+        }
+        if let Some(sequence_number) = header.replication_sequence_number {
+            header_block.set_osmosis_replication_sequence_number(sequence_number);
+        }
+        if let Some(base_url) = &header.replication_base_url {
+            header_block.set_osmosis_replication_base_url(base_url.clone());
+        }
+        // Locations on ways and dense nodes are only written if they are enabled; in the auto
+        // mode, any block may contain dense nodes
         header_block
             .required_features
-            .push("OsmSchema-V0.6".to_string());
-        if self.use_dense {
+            .retain(|feature| feature != "DenseNodes" && feature != "LocationsOnWays");
+        header_block
+            .optional_features
+            .retain(|feature| feature != "LocationsOnWays");
+        if !header_block
+            .required_features
+            .contains(&"OsmSchema-V0.6".to_string())
+        {
+            header_block
+                .required_features
+                .insert(0, "OsmSchema-V0.6".to_string());
+        }
+        if self.node_encoding != NodeEncoding::NonDense {
             header_block
                 .required_features
                 .push("DenseNodes".to_string());
         }
+        if self.locations_on_ways {
+            header_block
+                .optional_features
+                .push("LocationsOnWays".to_string());
+        }
+        if self.cache.iter().any(|element| !is_visible(element)) {
+            self.historical_information = true;
+        }
+        let historical_feature = "HistoricalInformation".to_string();
+        if header_block.required_features.contains(&historical_feature) {
+            self.historical_information = true;
+        } else if self.historical_information {
+            header_block.required_features.push(historical_feature);
+        }
+        if let Some(writing_program) = &self.header.writing_program {
+            header_block.set_writingprogram(writing_program.clone());
+        }
 
-        if let Some(bbox) = &self.bbox {
+        if self.auto_bbox.is_some() {
+            header_block.clear_bbox();
+        } else if let Some(bbox) = &self.header.bbox {
             let mut header_bbox = osmformat::HeaderBBox::new();
             header_bbox.set_left(bbox.left);
             header_bbox.set_right(bbox.right);
             header_bbox.set_top(bbox.top);
             header_bbox.set_bottom(bbox.bottom);
             header_block.set_bbox(header_bbox);
-            header_block.set_source(bbox.origin.clone());
         }
 
-        let blob = self.build_raw_blob(header_block.write_to_bytes()?)?;
-        self.write_blob(blob, "OSMHeader")?;
+        let mut header_bytes = header_block.write_to_bytes()?;
+        if self.auto_bbox.is_some() {
+            // The placeholder covers the whole world until it is patched on `finish`. Fields
+            // may come in any order, so it's appended as the `bbox` field to the end of the
+            // message, which ends the blob as the blob is uncompressed.
+            header_bytes.push(BBOX_KEY);
+            header_bytes.push(PADDED_BBOX_SIZE as u8);
+            header_bytes.extend(encode_padded_bbox(&world_bound()));
+            let mut blob = fileformat::Blob::new();
+            blob.set_raw(header_bytes);
+            self.write_blob(blob, "OSMHeader")?;
+            let placeholder = self.position - PADDED_BBOX_SIZE as u64;
+            if let Some(auto_bbox) = &mut self.auto_bbox {
+                auto_bbox.placeholder = Some(placeholder);
+            }
+        } else {
+            let blob = self.build_raw_blob(header_bytes)?;
+            self.write_blob(blob, "OSMHeader")?;
+        }
         self.has_writen_header = true;
         Ok(())
     }
@@ -135,7 +781,54 @@ impl<W: Write> PbfWriter<W> {
     /// of smallest to largest. PbfWriter writes elements in the order in which `write` is called, so it
     /// is up to the programmer to make sure that elements are written in the proper order.
     ///
-    pub fn write(&mut self, element: Element) -> anyhow::Result<()> {
+    pub fn write(&mut self, mut element: Element) -> anyhow::Result<()> {
+        if self.has_writen_header && !self.historical_information && !is_visible(&element) {
+            let (element_type, id) = element.get_meta();
+            bail!(
+                "{}/{} is deleted, but the header was written without the HistoricalInformation feature; call set_historical_information first",
+                element_type,
+                id
+            );
+        }
+        if !self.validation_rules.is_empty() && !self.validate(&element)? {
+            return Ok(());
+        }
+        if self.locations_on_ways {
+            match &mut element {
+                Element::Node(node) => {
+                    self.location_index
+                        .insert(node.id, node.latitude, node.longitude)
+                }
+                Element::Way(way) => self.location_index.fill_way(way)?,
+                Element::Relation(_) => {}
+            }
+        }
+        if let Some(auto_bbox) = &mut self.auto_bbox {
+            match &element {
+                Element::Node(node) if node.visible => {
+                    auto_bbox.extend(node.latitude, node.longitude)
+                }
+                Element::Way(way) => {
+                    for way_node in &way.way_nodes {
+                        if let (Some(latitude), Some(longitude)) =
+                            (way_node.latitude, way_node.longitude)
+                        {
+                            auto_bbox.extend(latitude, longitude);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if self.block_composition == BlockComposition::Homogeneous {
+            if let Some(last) = self.cache.last() {
+                if mem::discriminant(last) != mem::discriminant(&element) {
+                    self.write_to_block()?;
+                }
+            }
+        }
+        self.last_element = Some(element.get_meta());
+        self.elements += 1;
         self.cache.push(element);
         if self.cache.len() >= MAX_BLOCK_ITEM_LENGTH {
             self.write_to_block()?;
@@ -147,27 +840,130 @@ impl<W: Write> PbfWriter<W> {
         if !self.has_writen_header {
             self.write_header()?;
         }
-        let block_builder = PrimitiveBuilder::new();
+        let _span = trace_span!("encode_block", elements = self.cache.len());
+        let mut block_builder = PrimitiveBuilder::new();
+        block_builder.set_locations_on_ways(self.locations_on_ways);
+        block_builder.set_date_granularity(self.date_granularity);
+        block_builder.set_lossless_timestamps(self.lossless_timestamps);
+        block_builder.set_anonymous_users_as_none(self.anonymous_users_as_none);
         let cache = mem::replace(&mut self.cache, Vec::new());
-        let block = block_builder.build(cache, self.use_dense);
+        let mut block_report = BlockReport::default();
+        for element in &cache {
+            match element {
+                Element::Node(_) => block_report.nodes += 1,
+                Element::Way(_) => block_report.ways += 1,
+                Element::Relation(_) => block_report.relations += 1,
+            }
+        }
+        let block = block_builder.build(cache, self.node_encoding)?;
+        let strings = block.get_stringtable().get_s();
+        block_report.strings = strings.len() as u64;
+        block_report.string_table_size = strings.iter().map(|s| s.len() as u64).sum();
 
-        let blob = self.build_raw_blob(block.write_to_bytes()?)?;
+        let raw = block.write_to_bytes()?;
+        block_report.uncompressed_size = raw.len() as u64;
+        let blob = self.build_raw_blob(raw)?;
+        block_report.compressed_size = if blob.has_zlib_data() {
+            blob.get_zlib_data().len()
+        } else {
+            blob.get_raw().len()
+        } as u64;
+        block_report.offset = self.position;
         self.write_blob(blob, "OSMData")?;
+        self.report.blocks.push(block_report);
+        Ok(())
+    }
+
+    /// Writes the cached elements to a block now instead of when the block is full, so that
+    /// the next elements start a new block.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn flush_block(&mut self) -> anyhow::Result<()> {
+        if !self.cache.is_empty() {
+            self.write_to_block()?;
+        }
         Ok(())
     }
 
+    /// Copies a blob of another PBF file without decoding it, after the cached elements. A
+    /// header blob replaces the header the writer would write, so it must be copied first.
+    ///
+    /// Returns the offset of the copied blob in the output.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn copy_blob(&mut self, raw_blob: &RawBlob) -> anyhow::Result<u64> {
+        self.flush_block()?;
+        if raw_blob.blob_type() == "OSMHeader" {
+            if self.has_writen_header {
+                bail!("The header was already written");
+            }
+            self.has_writen_header = true;
+        } else if !self.has_writen_header {
+            self.write_header()?;
+        }
+        let offset = self.position;
+        self.write_blob_bytes(raw_blob.data(), raw_blob.blob_type())?;
+        Ok(offset)
+    }
+
+    /// Writes a header block as it is, instead of the header the writer would build.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn write_header_block(
+        &mut self,
+        header_block: &osmformat::HeaderBlock,
+    ) -> anyhow::Result<()> {
+        if self.has_writen_header {
+            bail!("The header was already written");
+        }
+        let blob = self.build_raw_blob(header_block.write_to_bytes()?)?;
+        self.write_blob(blob, "OSMHeader")?;
+        self.has_writen_header = true;
+        Ok(())
+    }
+
+    /// Flushes the output after copying blobs, without the final block `finish` writes.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn finish_copy(&mut self) -> anyhow::Result<()> {
+        self.flush_block()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns the report of the data blocks written so far, which is complete after `finish`.
+    pub fn report(&self) -> &WriteReport {
+        &self.report
+    }
+
     fn write_blob(&mut self, blob: fileformat::Blob, blob_type: &str) -> anyhow::Result<()> {
         let blob_bytes = blob.write_to_bytes()?;
+        self.write_blob_bytes(&blob_bytes, blob_type)
+    }
 
+    fn write_blob_bytes(&mut self, blob_bytes: &[u8], blob_type: &str) -> anyhow::Result<()> {
         let mut header = fileformat::BlobHeader::new();
         header.set_datasize(blob_bytes.len() as i32);
         header.set_field_type(blob_type.to_owned());
         let header_bytes = header.write_to_bytes()?;
+        if self.enforce_size_limits {
+            if header_bytes.len() as u64 > MAX_BLOB_HEADER_SIZE {
+                bail!(
+                    "The blob header is {} bytes, exceeding the limit of {} bytes",
+                    header_bytes.len(),
+                    MAX_BLOB_HEADER_SIZE
+                );
+            }
+            if blob_bytes.len() as u64 > MAX_BLOB_SIZE {
+                bail!(
+                    "The blob is {} bytes, exceeding the limit of {} bytes",
+                    blob_bytes.len(),
+                    MAX_BLOB_SIZE
+                );
+            }
+        }
 
         self.writer
             .write_u32::<byteorder::BigEndian>(header_bytes.len() as u32)?;
         self.writer.write_all(header_bytes.as_slice())?;
-        self.writer.write_all(blob_bytes.as_slice())?;
+        self.writer.write_all(blob_bytes)?;
+        self.position += 4 + header_bytes.len() as u64 + blob_bytes.len() as u64;
 
         Ok(())
     }
@@ -178,7 +974,875 @@ impl<W: Write> PbfWriter<W> {
     ///
     pub fn finish(&mut self) -> anyhow::Result<()> {
         self.write_to_block()?;
+        if let Some(auto_bbox) = &self.auto_bbox {
+            if let Some(placeholder) = auto_bbox.placeholder {
+                let back = self.position - placeholder;
+                match &auto_bbox.bound {
+                    Some(bound) => {
+                        (auto_bbox.patch)(&mut self.writer, back, &encode_padded_bbox(bound))?
+                    }
+                    // Without located nodes there's no bounding box, so the placeholder is
+                    // turned into an unknown field, skipped by readers, rather than claiming
+                    // the whole world. The key is 2 bytes before the placeholder, followed by
+                    // its length.
+                    None => (auto_bbox.patch)(&mut self.writer, back + 2, &[UNUSED_FIELD_KEY])?,
+                }
+            }
+        }
         self.writer.flush()?;
         Ok(())
     }
 }
+
+impl<W: Write + Seek> PbfWriter<W> {
+    /// Computes the bounding box of the header from the coordinates of the nodes written, and
+    /// of the way nodes if they have locations, instead of setting it with `set_bbox`.
+    ///
+    /// The header is written with a placeholder covering the whole world, which `finish`
+    /// overwrites, so the output must be seekable. The header blob is uncompressed. A copied
+    /// header keeps its bounding box. Without located nodes, the header has no bounding box:
+    /// the placeholder is kept as an unknown field, which readers ignore.
+    ///
+    /// It must be called before writing any elements.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use std::io::Cursor;
+    ///
+    /// use pbf_craft::models::{Element, Node};
+    /// use pbf_craft::writers::PbfWriter;
+    ///
+    /// let mut writer = PbfWriter::new(Cursor::new(Vec::new()), true);
+    /// writer.set_auto_bbox();
+    /// let mut node = Node::default();
+    /// node.visible = true;
+    /// node.latitude = 42_500_000_000;
+    /// node.longitude = 1_500_000_000;
+    /// writer.write(Element::Node(node)).unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn set_auto_bbox(&mut self) {
+        self.header.bbox = None;
+        self.auto_bbox = Some(AutoBbox {
+            bound: None,
+            placeholder: None,
+            patch: patch_output::<W>,
+        });
+    }
+}
+
+fn is_visible(element: &Element) -> bool {
+    match element {
+        Element::Node(node) => node.visible,
+        Element::Way(way) => way.visible,
+        Element::Relation(relation) => relation.visible,
+    }
+}
+
+impl<W: Write> ElementSink for PbfWriter<W> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        PbfWriter::write(self, element)
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        PbfWriter::set_header(self, header)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        PbfWriter::finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ElementType, Node, OsmUser, Relation, RelationMember, Tag, Way, WayNode};
+    use crate::readers::{DecodeOptions, DecodedBlob, IterableReader, PbfReader};
+    use chrono::DateTime;
+
+    fn read_header(data: &[u8]) -> HeaderReader {
+        let mut header = None;
+        PbfReader::new(data)
+            .read(|h, _| {
+                if h.is_some() {
+                    header = h;
+                }
+            })
+            .unwrap();
+        header.unwrap()
+    }
+
+    #[test]
+    fn test_source_header_passthrough() {
+        let mut source = PbfReader::from_path("./resources/andorra-latest.osm.pbf").unwrap();
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, false);
+        let mut source_header = None;
+        source
+            .read(|header, element| {
+                if let Some(header) = header {
+                    source_header = Some(header.clone());
+                    writer.set_source_header(header);
+                    writer.set_writing_program("pbf-craft-test".to_string());
+                }
+                if let Some(element) = element {
+                    writer.write(element).unwrap();
+                }
+            })
+            .unwrap();
+        writer.finish().unwrap();
+
+        let source_header = source_header.unwrap();
+        let header = read_header(&data);
+        assert_eq!(
+            header.optional_features(),
+            source_header.optional_features()
+        );
+        assert_eq!(header.required_features(), &["OsmSchema-V0.6".to_string()]);
+        assert_eq!(header.writing_program(), Some("pbf-craft-test"));
+        assert_eq!(
+            header.bound().map(|b| b.left),
+            source_header.bound().map(|b| b.left)
+        );
+    }
+
+    #[test]
+    fn test_file_header() {
+        let header = FileHeader {
+            bbox: Some(Bound {
+                left: 1_400_000_000,
+                right: 1_800_000_000,
+                top: 42_700_000_000,
+                bottom: 42_400_000_000,
+            }),
+            source: Some("http://www.openstreetmap.org/api/0.6".to_string()),
+            writing_program: Some("pbf-craft-test".to_string()),
+            required_features: vec!["OsmSchema-V0.6".to_string(), "DenseNodes".to_string()],
+            optional_features: vec!["Sort.Type_then_ID".to_string()],
+            replication_timestamp: DateTime::from_timestamp(1_700_000_000, 0),
+            replication_sequence_number: Some(4_200),
+            replication_base_url: Some(
+                "https://planet.openstreetmap.org/replication/minute".to_string(),
+            ),
+        };
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, true);
+        writer.set_header(header.clone());
+        writer.finish().unwrap();
+        assert_eq!(read_header(&data).file_header(), header);
+
+        // A header read from a file is written back as it is
+        let source = std::fs::read("./resources/andorra-latest.osm.pbf").unwrap();
+        let source_header = read_header(&source).file_header();
+        assert!(source_header.replication_base_url.is_some());
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, true);
+        writer.set_header(source_header.clone());
+        writer.finish().unwrap();
+        assert_eq!(read_header(&data).file_header(), source_header);
+    }
+
+    #[test]
+    fn test_locations_on_ways() {
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, true);
+        writer.set_locations_on_ways(true);
+        writer
+            .write(Element::Node(Node {
+                id: 1,
+                latitude: 42_500_000_000,
+                longitude: 1_500_000_000,
+                ..Default::default()
+            }))
+            .unwrap();
+        writer
+            .write(Element::Way(Way {
+                id: 1,
+                way_nodes: vec![WayNode::new_without_coords(1), WayNode::new(2, 1, 2)],
+                ..Default::default()
+            }))
+            .unwrap();
+        assert!(writer
+            .write(Element::Way(Way {
+                id: 2,
+                way_nodes: vec![WayNode::new_without_coords(3)],
+                ..Default::default()
+            }))
+            .is_err());
+        writer.finish().unwrap();
+
+        let header = read_header(&data);
+        assert!(header
+            .optional_features()
+            .contains(&"LocationsOnWays".to_string()));
+        let ways: Vec<Way> = IterableReader::new(PbfReader::new(data.as_slice()))
+            .filter_map(|element| match element {
+                Element::Way(way) => Some(way),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            ways[0].way_nodes,
+            vec![
+                WayNode::new(1, 42_500_000_000, 1_500_000_000),
+                WayNode::new(2, 0, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_bbox() {
+        let elements: Vec<Element> =
+            IterableReader::from_path("./resources/andorra-latest.osm.pbf")
+                .unwrap()
+                .collect();
+        let mut expected: Option<Bound> = None;
+        for element in &elements {
+            if let Element::Node(node) = element {
+                let bound = expected.get_or_insert(Bound {
+                    left: node.longitude,
+                    right: node.longitude,
+                    top: node.latitude,
+                    bottom: node.latitude,
+                });
+                bound.left = bound.left.min(node.longitude);
+                bound.right = bound.right.max(node.longitude);
+                bound.bottom = bound.bottom.min(node.latitude);
+                bound.top = bound.top.max(node.latitude);
+            }
+        }
+
+        let mut writer = PbfWriter::new(std::io::Cursor::new(Vec::new()), true);
+        writer.set_auto_bbox();
+        for element in elements.iter().cloned() {
+            writer.write(element).unwrap();
+        }
+        writer.finish().unwrap();
+        let data = writer.writer.into_inner();
+        assert_eq!(read_header(&data).bound(), expected);
+        let read: Vec<Element> = IterableReader::new(PbfReader::new(data.as_slice())).collect();
+        assert_eq!(read.len(), elements.len());
+
+        // Without located nodes, the header has no bounding box
+        let mut writer = PbfWriter::new(std::io::Cursor::new(Vec::new()), true);
+        writer.set_auto_bbox();
+        writer.finish().unwrap();
+        let data = writer.writer.into_inner();
+        assert_eq!(read_header(&data).bound(), None);
+
+        let way = elements
+            .iter()
+            .find(|element| matches!(element, Element::Way(_)))
+            .unwrap()
+            .clone();
+        let mut writer = PbfWriter::new(std::io::Cursor::new(Vec::new()), true);
+        writer.set_auto_bbox();
+        writer.write(way.clone()).unwrap();
+        writer.finish().unwrap();
+        let data = writer.writer.into_inner();
+        assert_eq!(read_header(&data).bound(), None);
+        let read: Vec<Element> = IterableReader::new(PbfReader::new(data.as_slice())).collect();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].get_meta(), way.get_meta());
+
+        // Negative and extreme coordinates survive the padded encoding
+        let bound = Bound {
+            left: i64::MIN,
+            right: i64::MAX,
+            top: -1,
+            bottom: 0,
+        };
+        let mut header_bbox = osmformat::HeaderBBox::new();
+        header_bbox
+            .merge_from_bytes(&encode_padded_bbox(&bound))
+            .unwrap();
+        assert_eq!(
+            (header_bbox.get_left(), header_bbox.get_right()),
+            (i64::MIN, i64::MAX)
+        );
+        assert_eq!((header_bbox.get_top(), header_bbox.get_bottom()), (-1, 0));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let elements: Vec<Element> =
+            IterableReader::from_path("./resources/andorra-latest.osm.pbf")
+                .unwrap()
+                .collect();
+        let output = std::env::temp_dir().join("pbf_craft_test_checkpoint.osm.pbf");
+        let mut writer = PbfWriter::from_path(&output, true).unwrap();
+        for element in elements.iter().take(20000).cloned() {
+            writer.write(element).unwrap();
+        }
+        let checkpoint = writer.checkpoint().unwrap();
+        assert_eq!(checkpoint.elements, 20000);
+        assert_eq!(checkpoint.last_element, Some(elements[19999].get_meta()));
+        // The writer crashes after writing some more blocks
+        for element in elements.iter().skip(20000).take(10000).cloned() {
+            writer.write(element).unwrap();
+        }
+        drop(writer);
+
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        let checkpoint: WriterCheckpoint = serde_json::from_str(&json).unwrap();
+        let mut writer = PbfWriter::resume_from_path(&output, &checkpoint, true).unwrap();
+        let last = checkpoint.last_element.clone().unwrap();
+        for element in elements
+            .iter()
+            .skip_while(|element| element.get_meta() != last)
+        {
+            if element.get_meta() != last {
+                writer.write(element.clone()).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+        let read: Vec<(ElementType, i64)> = IterableReader::from_path(&output)
+            .unwrap()
+            .map(|element| element.get_meta())
+            .collect();
+        let expected: Vec<(ElementType, i64)> =
+            elements.iter().map(|element| element.get_meta()).collect();
+        assert_eq!(read, expected);
+
+        // A file shorter than the checkpoint can't be resumed
+        std::fs::write(&output, b"").unwrap();
+        assert!(PbfWriter::resume_from_path(&output, &checkpoint, true).is_err());
+        std::fs::remove_file(&output).unwrap();
+    }
+
+    #[test]
+    fn test_validation_rules() {
+        use crate::validation::{CoordinateRangeRule, PositiveIdRule, ValidationPolicy};
+
+        let node = |id: i64, latitude: i64| {
+            Element::Node(Node {
+                id,
+                latitude,
+                visible: true,
+                ..Default::default()
+            })
+        };
+        let write = |policy: ValidationPolicy| {
+            let mut writer = PbfWriter::new(Vec::new(), true);
+            writer.add_validation_rule(CoordinateRangeRule, policy);
+            writer.add_validation_rule(PositiveIdRule, ValidationPolicy::Warn);
+            let results: Vec<bool> = [node(1, 0), node(2, 95_000_000_000), node(-3, 0)]
+                .into_iter()
+                .map(|element| writer.write(element).is_ok())
+                .collect();
+            writer.finish().unwrap();
+            let rules: Vec<String> = writer
+                .validation_issues()
+                .iter()
+                .map(|issue| issue.rule.clone())
+                .collect();
+            let written: Vec<i64> = IterableReader::new(PbfReader::new(writer.writer.as_slice()))
+                .map(|element| element.get_meta().1)
+                .collect();
+            (results, rules, written)
+        };
+
+        let (results, rules, written) = write(ValidationPolicy::Reject);
+        assert_eq!(results, [true, false, true]);
+        assert_eq!(rules, ["coordinate-range", "positive-id"]);
+        assert_eq!(written, [1, -3]);
+
+        let (results, _, written) = write(ValidationPolicy::Skip);
+        assert_eq!(results, [true, true, true]);
+        assert_eq!(written, [1, -3]);
+
+        let (_, _, written) = write(ValidationPolicy::Warn);
+        assert_eq!(written, [1, 2, -3]);
+    }
+
+    #[test]
+    fn test_uncompressed_blobs() {
+        let elements: Vec<Element> =
+            IterableReader::from_path("./resources/andorra-latest.osm.pbf")
+                .unwrap()
+                .take(20000)
+                .collect();
+        let write = |compression: BlobCompression| {
+            let mut data = Vec::new();
+            let mut writer = PbfWriter::new(&mut data, true);
+            writer.set_compression(compression);
+            for element in elements.iter().cloned() {
+                writer.write(element).unwrap();
+            }
+            writer.finish().unwrap();
+            data
+        };
+        let compressed = write(BlobCompression::Zlib);
+        let raw = write(BlobCompression::None);
+        assert!(raw.len() > compressed.len());
+
+        // The strings of the string tables are readable in raw blobs
+        let contains = |data: &[u8], text: &[u8]| data.windows(text.len()).any(|w| w == text);
+        assert!(contains(&raw, b"highway"));
+        assert!(!contains(&compressed, b"highway"));
+
+        let read: Vec<Element> = IterableReader::new(PbfReader::new(raw.as_slice())).collect();
+        assert_eq!(read.len(), elements.len());
+    }
+
+    #[test]
+    fn test_size_limits() {
+        // A single way with a huge tag value makes a block exceeding the limit
+        let huge_way = || {
+            Element::Way(Way {
+                id: 1,
+                tags: vec![crate::models::Tag {
+                    key: "note".to_string(),
+                    value: "x".repeat(MAX_BLOB_SIZE as usize),
+                }],
+                ..Default::default()
+            })
+        };
+        let mut writer = PbfWriter::new(std::io::sink(), true);
+        writer.write(huge_way()).unwrap();
+        assert!(writer.finish().is_err());
+
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, true);
+        writer.set_enforce_size_limits(false);
+        writer.write(huge_way()).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = PbfReader::new(data.as_slice());
+        assert!(reader.read(|_, _| {}).is_err());
+        let mut reader = PbfReader::new(data.as_slice());
+        reader.set_enforce_size_limits(false);
+        let mut ways = 0;
+        reader
+            .read(|_, element| {
+                if let Some(Element::Way(_)) = element {
+                    ways += 1;
+                }
+            })
+            .unwrap();
+        assert_eq!(ways, 1);
+    }
+
+    #[test]
+    fn test_homogeneous_blocks() {
+        let elements: Vec<Element> =
+            IterableReader::from_path("./resources/andorra-latest.osm.pbf")
+                .unwrap()
+                .collect();
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, true);
+        writer.set_block_composition(BlockComposition::Homogeneous);
+        for element in elements.iter().cloned() {
+            writer.write(element).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut element_count = 0;
+        PbfReader::new(data.as_slice())
+            .read_blocks(|block| {
+                if let DecodedBlob::OsmData(block) = block {
+                    let mut types = [false; 3];
+                    for group in block.get_primitivegroup() {
+                        types[0] |= group.has_dense() || !group.get_nodes().is_empty();
+                        types[1] |= !group.get_ways().is_empty();
+                        types[2] |= !group.get_relations().is_empty();
+                        element_count += group.get_dense().get_id().len()
+                            + group.get_ways().len()
+                            + group.get_relations().len();
+                    }
+                    assert!(types.iter().filter(|has_type| **has_type).count() <= 1);
+                }
+            })
+            .unwrap();
+        assert_eq!(element_count, elements.len());
+    }
+
+    #[test]
+    fn test_date_granularity() {
+        let node = |millis: i64| {
+            Element::Node(Node {
+                id: 1,
+                version: 1,
+                timestamp: DateTime::from_timestamp_millis(millis),
+                visible: true,
+                ..Default::default()
+            })
+        };
+        let read_timestamp =
+            |data: &[u8]| match IterableReader::new(PbfReader::new(data)).next().unwrap() {
+                Element::Node(node) => node.timestamp.unwrap().timestamp_millis(),
+                _ => unreachable!(),
+            };
+
+        // The milliseconds are truncated by default
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, true);
+        writer.write(node(1_700_000_000_123)).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(read_timestamp(&data), 1_700_000_000_000);
+
+        let mut writer = PbfWriter::new(std::io::sink(), true);
+        writer.set_lossless_timestamps(true);
+        writer.write(node(1_700_000_000_123)).unwrap();
+        assert!(writer.finish().is_err());
+
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, false);
+        writer.set_date_granularity(1);
+        writer.set_lossless_timestamps(true);
+        writer.write(node(1_700_000_000_123)).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(read_timestamp(&data), 1_700_000_000_123);
+    }
+
+    #[test]
+    fn test_anonymous_users() {
+        let anonymous = Some(OsmUser {
+            id: 0,
+            name: "".into(),
+        });
+        let elements = vec![
+            Element::Node(Node {
+                id: 1,
+                version: 1,
+                visible: true,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 2,
+                user: anonymous.clone(),
+                visible: true,
+                ..Default::default()
+            }),
+        ];
+        let read_users = |data: &[u8], anonymous_users_as_none: bool| {
+            let mut reader = IterableReader::new(PbfReader::new(data));
+            reader.set_decode_options(DecodeOptions {
+                anonymous_users_as_none,
+                ..Default::default()
+            });
+            reader
+                .map(|element| match element {
+                    Element::Node(node) => node.user,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Missing users are written as anonymous ones, which can be decoded as missing again
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, false);
+        for element in &elements {
+            writer.write(element.clone()).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(
+            read_users(&data, false),
+            [anonymous.clone(), anonymous.clone()]
+        );
+        assert_eq!(read_users(&data, true), [None, None]);
+
+        // The node whose only metadata is an anonymous user is written without metadata
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, false);
+        writer.set_anonymous_users_as_none(true);
+        for element in elements {
+            writer.write(element).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(read_users(&data, false), [anonymous, None]);
+    }
+
+    #[test]
+    fn test_deleted_elements() {
+        let node = |id: i64, visible: bool| {
+            Element::Node(Node {
+                id,
+                version: 2,
+                visible,
+                ..Default::default()
+            })
+        };
+        for use_dense in [true, false] {
+            let mut data = Vec::new();
+            let mut writer = PbfWriter::new(&mut data, use_dense);
+            writer.write(node(1, true)).unwrap();
+            writer.write(node(2, false)).unwrap();
+            writer
+                .write(Element::Way(Way {
+                    id: 1,
+                    version: 2,
+                    visible: false,
+                    ..Default::default()
+                }))
+                .unwrap();
+            writer.finish().unwrap();
+
+            assert!(read_header(&data)
+                .header_block()
+                .get_required_features()
+                .contains(&"HistoricalInformation".to_string()));
+            let visible: Vec<bool> = IterableReader::new(PbfReader::new(data.as_slice()))
+                .map(|element| is_visible(&element))
+                .collect();
+            assert_eq!(visible, vec![true, false, false]);
+        }
+
+        // The header of the first block has been written without the feature
+        let mut writer = PbfWriter::new(std::io::sink(), true);
+        for id in 1..=MAX_BLOCK_ITEM_LENGTH as i64 {
+            writer.write(node(id, true)).unwrap();
+        }
+        assert!(writer.write(node(0, false)).is_err());
+    }
+
+    #[test]
+    fn test_omitted_metadata() {
+        let elements = vec![
+            Element::Node(Node {
+                id: 1,
+                latitude: 42_500_000_000,
+                longitude: 1_500_000_000,
+                visible: true,
+                ..Default::default()
+            }),
+            Element::Node(Node {
+                id: 2,
+                visible: true,
+                tags: vec![Tag {
+                    key: "name".to_string(),
+                    value: "x".to_string(),
+                }],
+                ..Default::default()
+            }),
+            Element::Way(Way {
+                id: 1,
+                visible: true,
+                way_nodes: vec![
+                    WayNode::new_without_coords(1),
+                    WayNode::new_without_coords(2),
+                ],
+                ..Default::default()
+            }),
+        ];
+        for use_dense in [true, false] {
+            let mut data = Vec::new();
+            let mut writer = PbfWriter::new(&mut data, use_dense);
+            for element in elements.iter().cloned() {
+                writer.write(element).unwrap();
+            }
+            writer.finish().unwrap();
+
+            PbfReader::new(data.as_slice())
+                .read_blocks(|block| {
+                    if let DecodedBlob::OsmData(block) = block {
+                        for group in block.get_primitivegroup() {
+                            assert!(!group.get_dense().has_denseinfo());
+                            assert!(group.get_nodes().iter().all(|node| !node.has_info()));
+                            assert!(group.get_ways().iter().all(|way| !way.has_info()));
+                        }
+                    }
+                })
+                .unwrap();
+
+            let read: Vec<Element> = IterableReader::new(PbfReader::new(data.as_slice())).collect();
+            assert_eq!(read.len(), elements.len());
+            for (read, expected) in read.iter().zip(&elements) {
+                match (read, expected) {
+                    (Element::Node(read), Element::Node(expected)) => assert_eq!(read, expected),
+                    (Element::Way(read), Element::Way(expected)) => assert_eq!(read, expected),
+                    _ => panic!("Unexpected element"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tagged_dense_nodes_without_metadata() {
+        // Without DenseInfo, the first string of the block is a tag key, which mustn't get
+        // the index 0 delimiting the tags of dense nodes
+        let tag = |key: &str, value: &str| Tag {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let nodes: Vec<Node> = (1..=3)
+            .map(|id| Node {
+                id,
+                visible: true,
+                tags: vec![
+                    tag("name", &format!("node {}", id)),
+                    tag("amenity", "bench"),
+                ],
+                ..Default::default()
+            })
+            .collect();
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, true);
+        for node in &nodes {
+            writer.write(Element::Node(node.clone())).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let read: Vec<Node> = IterableReader::new(PbfReader::new(data.as_slice()))
+            .map(|element| match element {
+                Element::Node(node) => node,
+                _ => panic!("Unexpected element"),
+            })
+            .collect();
+        assert_eq!(read, nodes);
+    }
+
+    fn encoded_size(elements: &[Element], node_encoding: NodeEncoding) -> usize {
+        let block = PrimitiveBuilder::new()
+            .build(elements.to_vec(), node_encoding)
+            .unwrap();
+        block.write_to_bytes().unwrap().len()
+    }
+
+    #[test]
+    fn test_report() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, true);
+        let mut total = 0;
+        PbfReader::from_path(path)
+            .unwrap()
+            .read(|_, element| {
+                if let Some(element) = element {
+                    total += 1;
+                    writer.write(element).unwrap();
+                }
+            })
+            .unwrap();
+        writer.finish().unwrap();
+        let report = writer.report().clone();
+        drop(writer);
+
+        assert_eq!(report.elements(), total);
+        assert!(report.compressed_size() < report.uncompressed_size());
+        assert!(report.string_table_size() > 0);
+        let mut reader = PbfReader::new(data.as_slice());
+        reader.read_next_blob().unwrap();
+        for block in &report.blocks {
+            let blob = reader.read_next_blob().unwrap().unwrap();
+            assert_eq!(blob.offset, block.offset);
+            assert_eq!(blob.nodes.len() as u64, block.nodes);
+            assert_eq!(blob.ways.len() as u64, block.ways);
+            assert_eq!(blob.relations.len() as u64, block.relations);
+        }
+        assert!(reader.read_next_blob().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_negative_ids() {
+        let nodes: Vec<Node> = [-2, -1, 1]
+            .into_iter()
+            .map(|id| Node {
+                id,
+                visible: true,
+                ..Default::default()
+            })
+            .collect();
+        let way = Way {
+            id: -1,
+            visible: true,
+            way_nodes: [-1, 1, -2]
+                .into_iter()
+                .map(WayNode::new_without_coords)
+                .collect(),
+            ..Default::default()
+        };
+        let relation = Relation {
+            id: -1,
+            visible: true,
+            members: vec![
+                RelationMember {
+                    member_id: -1,
+                    member_type: ElementType::Way,
+                    role: "outer".into(),
+                },
+                RelationMember {
+                    member_id: 1,
+                    member_type: ElementType::Node,
+                    role: "".into(),
+                },
+            ],
+            ..Default::default()
+        };
+        for use_dense in [true, false] {
+            let mut data = Vec::new();
+            let mut writer = PbfWriter::new(&mut data, use_dense);
+            for node in nodes.iter().cloned() {
+                writer.write(Element::Node(node)).unwrap();
+            }
+            writer.write(Element::Way(way.clone())).unwrap();
+            writer.write(Element::Relation(relation.clone())).unwrap();
+            writer.finish().unwrap();
+
+            let mut reader = PbfReader::new(data.as_slice());
+            // Skips the header
+            reader.read_next_blob().unwrap();
+            let blob = reader.read_next_blob().unwrap().unwrap();
+            assert_eq!(blob.nodes, nodes);
+            assert_eq!(blob.ways, vec![way.clone()]);
+            assert_eq!(blob.relations, vec![relation.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_delta_overflow() {
+        let way = Way {
+            id: 1,
+            way_nodes: vec![
+                WayNode::new_without_coords(i64::MIN),
+                WayNode::new_without_coords(1),
+            ],
+            ..Default::default()
+        };
+        let mut writer = PbfWriter::new(Vec::new(), true);
+        let result = writer
+            .write(Element::Way(way))
+            .and_then(|_| writer.finish());
+        assert!(format!("{:#}", result.unwrap_err()).contains("way 1"));
+    }
+
+    #[test]
+    fn test_auto_node_encoding() {
+        let plain_node = |id: i64| Node {
+            id,
+            latitude: 42_512_345_600 + id * 1000,
+            longitude: 1_523_456_700 - id * 3000,
+            visible: true,
+            ..Default::default()
+        };
+        let node = |id: i64| Element::Node(plain_node(id));
+        for elements in [vec![node(1)], (1..100).map(node).collect()] {
+            let auto = encoded_size(&elements, NodeEncoding::Auto);
+            let dense = encoded_size(&elements, NodeEncoding::Dense);
+            let non_dense = encoded_size(&elements, NodeEncoding::NonDense);
+            assert_eq!(auto, dense.min(non_dense));
+        }
+        assert!(
+            encoded_size(&[node(1)], NodeEncoding::Auto)
+                < encoded_size(&[node(1)], NodeEncoding::Dense)
+        );
+
+        let mut data = Vec::new();
+        let mut writer = PbfWriter::new(&mut data, false);
+        writer.set_node_encoding(NodeEncoding::Auto);
+        writer.write(node(1)).unwrap();
+        writer.finish().unwrap();
+        assert!(read_header(&data)
+            .required_features()
+            .contains(&"DenseNodes".to_string()));
+        let nodes: Vec<Node> = IterableReader::new(PbfReader::new(data.as_slice()))
+            .filter_map(|element| match element {
+                Element::Node(node) => Some(node),
+                _ => None,
+            })
+            .collect();
+        // Without metadata, the node is written without `Info` and read back unchanged
+        assert_eq!(nodes, vec![plain_node(1)]);
+    }
+}
@@ -0,0 +1,243 @@
+use super::traits::ElementSink;
+use crate::models::{Element, ElementType, FileHeader};
+use crate::readers::Provenance;
+use crate::utils::IdSet;
+
+/// What `ReferenceCheckingSink` does with a reference to an element not written before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferencePolicy {
+    /// Fails the write of the element holding the reference.
+    Error,
+    /// Removes the way node or relation member, and writes the rest of the element.
+    Drop,
+    /// Writes the element unchanged, only counting the dangling reference.
+    Keep,
+}
+
+/// A sink that verifies that every way node and relation member refers to an element written
+/// earlier to it, before passing the elements on to the wrapped sink.
+///
+/// It makes sure an extract is self-contained. As elements must be written before the elements
+/// referring to them, relations referring to later relations count as dangling too.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Way, WayNode};
+/// use pbf_craft::writers::{CountingSink, ElementSink, ReferenceCheckingSink, ReferencePolicy};
+///
+/// let mut sink = ReferenceCheckingSink::new(CountingSink::new(), ReferencePolicy::Drop);
+/// sink.write(Element::Way(Way {
+///     id: 1,
+///     way_nodes: vec![WayNode::new_without_coords(1)],
+///     ..Default::default()
+/// }))
+/// .unwrap();
+/// sink.finish().unwrap();
+/// assert_eq!(sink.dangling(), 1);
+/// ```
+pub struct ReferenceCheckingSink<S: ElementSink> {
+    sink: S,
+    policy: ReferencePolicy,
+    nodes: IdSet,
+    ways: IdSet,
+    relations: IdSet,
+    dangling: u64,
+}
+
+impl<S: ElementSink> ReferenceCheckingSink<S> {
+    pub fn new(sink: S, policy: ReferencePolicy) -> Self {
+        Self {
+            sink,
+            policy,
+            nodes: IdSet::new(),
+            ways: IdSet::new(),
+            relations: IdSet::new(),
+            dangling: 0,
+        }
+    }
+
+    /// Returns the number of dangling references found so far.
+    pub fn dangling(&self) -> u64 {
+        self.dangling
+    }
+
+    /// Consumes the `ReferenceCheckingSink` and returns the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    fn exists(&self, element_type: &ElementType, id: i64) -> bool {
+        match element_type {
+            ElementType::Node => self.nodes.contains(id),
+            ElementType::Way => self.ways.contains(id),
+            ElementType::Relation => self.relations.contains(id),
+        }
+    }
+
+    /// Handles a reference of the element `(owner_type, owner_id)` according to the policy.
+    /// Returns whether the reference should be kept.
+    fn check(
+        &mut self,
+        owner_type: &ElementType,
+        owner_id: i64,
+        element_type: &ElementType,
+        id: i64,
+    ) -> anyhow::Result<bool> {
+        if self.exists(element_type, id) {
+            return Ok(true);
+        }
+        self.dangling += 1;
+        match self.policy {
+            ReferencePolicy::Error => bail!(
+                "{}/{} refers to {}/{}, which hasn't been written before",
+                owner_type,
+                owner_id,
+                element_type,
+                id
+            ),
+            ReferencePolicy::Drop => Ok(false),
+            ReferencePolicy::Keep => Ok(true),
+        }
+    }
+}
+
+impl<S: ElementSink> ElementSink for ReferenceCheckingSink<S> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        self.write_with_provenance(element, None)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        mut element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        match &mut element {
+            Element::Node(node) => {
+                self.nodes.insert(node.id);
+            }
+            Element::Way(way) => {
+                let mut way_nodes = Vec::with_capacity(way.way_nodes.len());
+                for way_node in way.way_nodes.drain(..) {
+                    if self.check(&ElementType::Way, way.id, &ElementType::Node, way_node.id)? {
+                        way_nodes.push(way_node);
+                    }
+                }
+                way.way_nodes = way_nodes;
+                self.ways.insert(way.id);
+            }
+            Element::Relation(relation) => {
+                let mut members = Vec::with_capacity(relation.members.len());
+                for member in relation.members.drain(..) {
+                    if self.check(
+                        &ElementType::Relation,
+                        relation.id,
+                        &member.member_type,
+                        member.member_id,
+                    )? {
+                        members.push(member);
+                    }
+                }
+                relation.members = members;
+                self.relations.insert(relation.id);
+            }
+        }
+        self.sink.write_with_provenance(element, provenance)
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        self.sink.set_header(header);
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Relation, RelationMember, Way, WayNode};
+
+    fn elements() -> Vec<Element> {
+        vec![
+            Element::Node(Node {
+                id: 1,
+                ..Default::default()
+            }),
+            Element::Way(Way {
+                id: 10,
+                way_nodes: vec![
+                    WayNode::new_without_coords(1),
+                    WayNode::new_without_coords(2),
+                ],
+                ..Default::default()
+            }),
+            Element::Relation(Relation {
+                id: 100,
+                members: vec![
+                    RelationMember {
+                        member_id: 10,
+                        member_type: ElementType::Way,
+                        role: "".into(),
+                    },
+                    RelationMember {
+                        member_id: 101,
+                        member_type: ElementType::Relation,
+                        role: "".into(),
+                    },
+                ],
+                ..Default::default()
+            }),
+        ]
+    }
+
+    #[derive(Default)]
+    struct VecSink(Vec<Element>);
+
+    impl ElementSink for VecSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.0.push(element);
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns the number of way nodes of the way and members of the relation written.
+    fn reference_counts(sink: ReferenceCheckingSink<VecSink>) -> (usize, usize) {
+        match &sink.into_inner().0[1..] {
+            [Element::Way(way), Element::Relation(relation)] => {
+                (way.way_nodes.len(), relation.members.len())
+            }
+            _ => panic!("Unexpected elements"),
+        }
+    }
+
+    fn write_all(policy: ReferencePolicy) -> (anyhow::Result<()>, ReferenceCheckingSink<VecSink>) {
+        let mut sink = ReferenceCheckingSink::new(VecSink::default(), policy);
+        let result = elements()
+            .into_iter()
+            .try_for_each(|element| sink.write(element));
+        (result, sink)
+    }
+
+    #[test]
+    fn test_reference_policies() {
+        let (result, sink) = write_all(ReferencePolicy::Error);
+        assert!(result.unwrap_err().to_string().contains("way/10"));
+        assert_eq!(sink.dangling(), 1);
+
+        let (result, sink) = write_all(ReferencePolicy::Drop);
+        result.unwrap();
+        assert_eq!(sink.dangling(), 2);
+        assert_eq!(reference_counts(sink), (1, 1));
+
+        let (result, sink) = write_all(ReferencePolicy::Keep);
+        result.unwrap();
+        assert_eq!(sink.dangling(), 2);
+        assert_eq!(reference_counts(sink), (2, 2));
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{self, File, OpenOptions},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[cfg(feature = "fs")]
+use serde::{Deserialize, Serialize};
+
+use super::traits::ElementSink;
+use crate::models::{Element, ElementType, FileHeader};
+use crate::readers::Provenance;
+#[cfg(feature = "fs")]
+use crate::utils::{EstimatedSize, MemoryBudget, MemoryReservation};
+
+/// The fewest elements spilled at once, which keeps the number of runs to merge low when the
+/// other components leave little of the budget.
+#[cfg(feature = "fs")]
+const MIN_SPILLED_ELEMENTS: usize = 1024;
+
+type Entry = (Element, Option<Provenance>);
+
+/// A sink that accepts elements in any order and writes them to the wrapped sink in the
+/// canonical order (nodes, ways and relations, each sorted by id) when finished.
+///
+/// All elements are kept in memory until `finish` is called, unless the writer has a memory
+/// budget, see `set_memory_budget`. If an element is written more than once, the last one wins.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node, Way};
+/// use pbf_craft::writers::{ElementSink, PbfWriter, SortingWriter};
+///
+/// let mut writer = SortingWriter::new(PbfWriter::new(Vec::new(), true));
+/// writer.write(Element::Way(Way { id: 1, ..Default::default() })).unwrap();
+/// writer.write(Element::Node(Node { id: 2, ..Default::default() })).unwrap();
+/// writer.write(Element::Node(Node { id: 1, ..Default::default() })).unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct SortingWriter<S: ElementSink> {
+    sink: S,
+    elements: BTreeMap<(ElementType, i64), Entry>,
+    #[cfg(feature = "fs")]
+    reservation: Option<MemoryReservation>,
+    #[cfg(feature = "fs")]
+    spill_dir: PathBuf,
+    #[cfg(feature = "fs")]
+    runs: Vec<SpillRun>,
+}
+
+impl<S: ElementSink> SortingWriter<S> {
+    /// Creates a new `SortingWriter` writing to `sink`.
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            elements: BTreeMap::new(),
+            #[cfg(feature = "fs")]
+            reservation: None,
+            #[cfg(feature = "fs")]
+            spill_dir: std::env::temp_dir(),
+            #[cfg(feature = "fs")]
+            runs: Vec::new(),
+        }
+    }
+
+    /// Limits the memory of the elements held by a budget shared with other components, see
+    /// `MemoryBudget`. When the budget is exhausted, the elements held are spilled as a sorted
+    /// run to a temporary file, and the runs are merged when finished.
+    #[cfg(feature = "fs")]
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        let mut reservation = budget.reservation();
+        reservation.grow(
+            self.elements
+                .values()
+                .map(|(element, _)| element.estimated_size())
+                .sum(),
+        );
+        self.reservation = Some(reservation);
+    }
+
+    /// Sets the directory of the spilled runs. By default, it's the temporary directory of the
+    /// system.
+    #[cfg(feature = "fs")]
+    pub fn set_spill_dir<P: AsRef<Path>>(&mut self, spill_dir: P) {
+        self.spill_dir = spill_dir.as_ref().to_path_buf();
+    }
+
+    /// Returns the number of elements waiting to be written. An element written again after
+    /// it was spilled is counted twice.
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "fs")]
+        let spilled: usize = self.runs.iter().map(|run| run.len).sum();
+        #[cfg(not(feature = "fs"))]
+        let spilled = 0;
+        self.elements.len() + spilled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Consumes the `SortingWriter` and returns the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    /// Reserves the memory of an element, spilling the elements held if the budget is
+    /// exhausted.
+    #[cfg(feature = "fs")]
+    fn reserve(&mut self, element: &Element) -> anyhow::Result<()> {
+        let size = element.estimated_size();
+        let granted = match &mut self.reservation {
+            Some(reservation) => reservation.try_grow(size),
+            None => true,
+        };
+        if granted {
+            return Ok(());
+        }
+        if self.elements.len() >= MIN_SPILLED_ELEMENTS {
+            let elements = std::mem::take(&mut self.elements);
+            self.runs
+                .push(SpillRun::write(&self.spill_dir, elements.into_values())?);
+        }
+        if let Some(reservation) = &mut self.reservation {
+            if self.elements.is_empty() {
+                reservation.resize(0);
+            }
+            reservation.grow(size);
+        }
+        Ok(())
+    }
+
+    /// Writes the spilled runs and the elements held to the sink, merged in the canonical
+    /// order. Of the copies of an element, the one written last wins.
+    #[cfg(feature = "fs")]
+    fn merge_runs(&mut self) -> anyhow::Result<()> {
+        let runs = std::mem::take(&mut self.runs);
+        let mut sources: Vec<Box<dyn Iterator<Item = anyhow::Result<Entry>>>> =
+            Vec::with_capacity(runs.len() + 1);
+        for run in &runs {
+            sources.push(run.read()?);
+        }
+        let elements = std::mem::take(&mut self.elements);
+        sources.push(Box::new(elements.into_values().map(Ok)));
+
+        // The heap pops the smallest key first, and of equal keys the one of the latest source
+        let mut heads: Vec<Option<Entry>> = Vec::with_capacity(sources.len());
+        let mut heap = BinaryHeap::with_capacity(sources.len());
+        for (index, source) in sources.iter_mut().enumerate() {
+            heads.push(source.next().transpose()?);
+            if let Some((element, _)) = &heads[index] {
+                heap.push((Reverse(element.get_meta()), index));
+            }
+        }
+        let mut last_key = None;
+        while let Some((Reverse(key), index)) = heap.pop() {
+            let (element, provenance) = heads[index].take().unwrap();
+            heads[index] = sources[index].next().transpose()?;
+            if let Some((next, _)) = &heads[index] {
+                heap.push((Reverse(next.get_meta()), index));
+            }
+            if last_key.as_ref() != Some(&key) {
+                self.sink.write_with_provenance(element, provenance)?;
+                last_key = Some(key);
+            }
+        }
+        if let Some(reservation) = &mut self.reservation {
+            reservation.resize(0);
+        }
+        Ok(())
+    }
+}
+
+impl<S: ElementSink> ElementSink for SortingWriter<S> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        self.write_with_provenance(element, None)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        #[cfg(feature = "fs")]
+        self.reserve(&element)?;
+        #[cfg_attr(not(feature = "fs"), allow(unused_variables))]
+        let previous = self
+            .elements
+            .insert(element.get_meta(), (element, provenance));
+        #[cfg(feature = "fs")]
+        if let (Some(reservation), Some((previous, _))) = (&mut self.reservation, previous) {
+            reservation.shrink(previous.estimated_size());
+        }
+        Ok(())
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        self.sink.set_header(header);
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        #[cfg(feature = "fs")]
+        if !self.runs.is_empty() {
+            self.merge_runs()?;
+            return self.sink.finish();
+        }
+        for (_, (element, provenance)) in std::mem::take(&mut self.elements) {
+            self.sink.write_with_provenance(element, provenance)?;
+        }
+        #[cfg(feature = "fs")]
+        if let Some(reservation) = &mut self.reservation {
+            reservation.resize(0);
+        }
+        self.sink.finish()
+    }
+}
+
+/// The number of the next spilled run, which makes the file names unique within the process.
+#[cfg(feature = "fs")]
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// A spilled element, with the provenance it was written with.
+#[cfg(feature = "fs")]
+#[derive(Serialize, Deserialize)]
+struct SpilledEntry {
+    element: Element,
+    provenance: Option<(String, u64, u64, u64)>,
+}
+
+/// A sorted run of elements spilled to a file, which is removed when the run is dropped.
+#[cfg(feature = "fs")]
+struct SpillRun {
+    path: PathBuf,
+    len: usize,
+}
+
+#[cfg(feature = "fs")]
+impl SpillRun {
+    fn write<I: Iterator<Item = Entry>>(dir: &Path, entries: I) -> anyhow::Result<Self> {
+        let path = dir.join(format!(
+            "pbf-craft-sort-{}-{}.json",
+            std::process::id(),
+            NEXT_RUN.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let mut run = Self { path, len: 0 };
+        let mut writer = BufWriter::new(file);
+        for (element, provenance) in entries {
+            let provenance = provenance.map(|provenance| {
+                (
+                    provenance.source.to_string(),
+                    provenance.blob_offset,
+                    provenance.blob_size,
+                    provenance.index,
+                )
+            });
+            serde_json::to_writer(
+                &mut writer,
+                &SpilledEntry {
+                    element,
+                    provenance,
+                },
+            )?;
+            run.len += 1;
+        }
+        writer.flush()?;
+        debug!("Spilled {} elements to {}", run.len, run.path.display());
+        Ok(run)
+    }
+
+    fn read(&self) -> anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<Entry>>>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let entries = serde_json::Deserializer::from_reader(reader)
+            .into_iter::<SpilledEntry>()
+            .map(|entry| {
+                let SpilledEntry {
+                    element,
+                    provenance,
+                } = entry?;
+                let provenance =
+                    provenance.map(|(source, blob_offset, blob_size, index)| Provenance {
+                        source: source.into(),
+                        blob_offset,
+                        blob_size,
+                        index,
+                    });
+                Ok((element, provenance))
+            });
+        Ok(Box::new(entries))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Drop for SpillRun {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("Unable to remove {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Relation, Way};
+
+    #[derive(Default)]
+    struct VecSink(Vec<(ElementType, i64)>);
+
+    impl ElementSink for VecSink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.0.push(element.get_meta());
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sorting() {
+        let mut writer = SortingWriter::new(VecSink::default());
+        writer
+            .write(Element::Relation(Relation {
+                id: 1,
+                ..Default::default()
+            }))
+            .unwrap();
+        writer
+            .write(Element::Way(Way {
+                id: 5,
+                ..Default::default()
+            }))
+            .unwrap();
+        writer
+            .write(Element::Node(Node {
+                id: 3,
+                ..Default::default()
+            }))
+            .unwrap();
+        writer
+            .write(Element::Node(Node {
+                id: 2,
+                ..Default::default()
+            }))
+            .unwrap();
+        writer
+            .write(Element::Node(Node {
+                id: 3,
+                ..Default::default()
+            }))
+            .unwrap();
+        writer.finish().unwrap();
+
+        assert!(writer.is_empty());
+        assert_eq!(
+            writer.into_inner().0,
+            vec![
+                (ElementType::Node, 2),
+                (ElementType::Node, 3),
+                (ElementType::Way, 5),
+                (ElementType::Relation, 1),
+            ]
+        );
+    }
+
+    #[derive(Default)]
+    struct EntrySink(Vec<Entry>);
+
+    impl ElementSink for EntrySink {
+        fn write(&mut self, element: Element) -> anyhow::Result<()> {
+            self.write_with_provenance(element, None)
+        }
+
+        fn write_with_provenance(
+            &mut self,
+            element: Element,
+            provenance: Option<Provenance>,
+        ) -> anyhow::Result<()> {
+            self.0.push((element, provenance));
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_spilling() {
+        let spill_dir = std::env::temp_dir().join("pbf-craft-test-spilling");
+        std::fs::create_dir_all(&spill_dir).unwrap();
+        let node = |id, version| {
+            Element::Node(Node {
+                id,
+                version,
+                ..Default::default()
+            })
+        };
+
+        // Room for about 2000 nodes
+        let budget = MemoryBudget::new(2000 * node(0, 0).estimated_size());
+        let mut writer = SortingWriter::new(EntrySink::default());
+        writer.set_memory_budget(budget.clone());
+        writer.set_spill_dir(&spill_dir);
+        for id in (1..=5000).rev() {
+            writer.write(node(id, 1)).unwrap();
+        }
+        writer.write(node(4000, 2)).unwrap();
+        writer
+            .write_with_provenance(
+                node(10, 2),
+                Some(Provenance {
+                    source: "a.osm.pbf".into(),
+                    blob_offset: 1,
+                    blob_size: 2,
+                    index: 3,
+                }),
+            )
+            .unwrap();
+        assert!(!writer.runs.is_empty());
+        assert!(std::fs::read_dir(&spill_dir).unwrap().count() > 0);
+        assert!(!budget.is_exceeded());
+
+        writer.finish().unwrap();
+        let written = writer.into_inner().0;
+        assert_eq!(written.len(), 5000);
+        for (index, (element, provenance)) in written.iter().enumerate() {
+            let Element::Node(node) = element else {
+                panic!("{} is not a node", element);
+            };
+            assert_eq!(node.id, index as i64 + 1);
+            let version = if node.id == 10 || node.id == 4000 {
+                2
+            } else {
+                1
+            };
+            assert_eq!(node.version, version);
+            assert_eq!(provenance.is_some(), node.id == 10);
+        }
+        assert_eq!(written[9].1.as_ref().unwrap().index, 3);
+        assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+        assert_eq!(budget.used(), 0);
+    }
+}
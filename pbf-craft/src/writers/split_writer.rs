@@ -0,0 +1,157 @@
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[cfg(feature = "fs")]
+use super::raw_writer::PbfWriter;
+use super::traits::ElementSink;
+use crate::models::{Element, FileHeader};
+use crate::readers::Provenance;
+
+/// A writer that writes nodes, ways and relations to three separate sinks in one pass.
+///
+/// It's meant for loading the element types in parallel, e.g. into separate tables. Each output
+/// gets the header set on the `SplitWriter`.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{ElementSource, IterableReader};
+/// use pbf_craft::writers::{ElementSink, SplitWriter};
+///
+/// let mut reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let mut writer = SplitWriter::from_paths(
+///     "resources/nodes.osm.pbf",
+///     "resources/ways.osm.pbf",
+///     "resources/relations.osm.pbf",
+///     true,
+/// )
+/// .unwrap();
+/// while let Some(element) = reader.next_element().unwrap() {
+///     writer.write(element).unwrap();
+/// }
+/// writer.finish().unwrap();
+/// ```
+pub struct SplitWriter<S: ElementSink> {
+    nodes: S,
+    ways: S,
+    relations: S,
+}
+
+#[cfg(feature = "fs")]
+impl SplitWriter<PbfWriter<BufWriter<File>>> {
+    /// Creates a new `SplitWriter` writing each element type to a PBF file.
+    pub fn from_paths<P: AsRef<Path>>(
+        node_path: P,
+        way_path: P,
+        relation_path: P,
+        use_dense: bool,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            PbfWriter::from_path(node_path, use_dense)?,
+            PbfWriter::from_path(way_path, use_dense)?,
+            PbfWriter::from_path(relation_path, use_dense)?,
+        ))
+    }
+}
+
+impl<S: ElementSink> SplitWriter<S> {
+    pub fn new(nodes: S, ways: S, relations: S) -> Self {
+        Self {
+            nodes,
+            ways,
+            relations,
+        }
+    }
+
+    /// Consumes the `SplitWriter` and returns the sinks of nodes, ways and relations.
+    pub fn into_inner(self) -> (S, S, S) {
+        (self.nodes, self.ways, self.relations)
+    }
+}
+
+impl<S: ElementSink> ElementSink for SplitWriter<S> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        self.write_with_provenance(element, None)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        match element {
+            Element::Node(_) => self.nodes.write_with_provenance(element, provenance),
+            Element::Way(_) => self.ways.write_with_provenance(element, provenance),
+            Element::Relation(_) => self.relations.write_with_provenance(element, provenance),
+        }
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        self.nodes.set_header(header.clone());
+        self.ways.set_header(header.clone());
+        self.relations.set_header(header);
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.nodes.finish()?;
+        self.ways.finish()?;
+        self.relations.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::readers::PbfReader;
+    use crate::writers::CountingSink;
+
+    #[test]
+    fn test_split() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let mut expected = CountingSink::new();
+        let mut outputs = [Vec::new(), Vec::new(), Vec::new()];
+        let [node_data, way_data, relation_data] = &mut outputs;
+        let mut writer = SplitWriter::new(
+            PbfWriter::new(node_data, true),
+            PbfWriter::new(way_data, true),
+            PbfWriter::new(relation_data, true),
+        );
+        PbfReader::from_path(path)
+            .unwrap()
+            .read(|_, element| {
+                if let Some(element) = element {
+                    expected.write(element.clone()).unwrap();
+                    writer.write(element).unwrap();
+                }
+            })
+            .unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let counts: Vec<CountingSink> = outputs
+            .iter()
+            .map(|data| {
+                let mut counts = CountingSink::new();
+                PbfReader::new(data.as_slice())
+                    .read(|_, element| {
+                        if let Some(element) = element {
+                            counts.write(element).unwrap();
+                        }
+                    })
+                    .unwrap();
+                counts
+            })
+            .collect();
+        assert_eq!(counts[0].nodes, expected.nodes);
+        assert_eq!(counts[1].ways, expected.ways);
+        assert_eq!(counts[2].relations, expected.relations);
+        assert_eq!(
+            counts.iter().map(CountingSink::total).sum::<u64>(),
+            expected.total()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use super::traits::ElementSink;
+use crate::models::{Element, FileHeader};
+use crate::readers::Provenance;
+
+/// A sink that forwards every element to several sinks, so that one pass over the input can
+/// produce several outputs, e.g. a PBF file, statistics and a log.
+///
+/// The sinks may be of different types, and borrowed with `&mut` to be inspected after the
+/// pass. Each sink gets a copy of the elements and of the header, in the order they were added.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::readers::{ElementSource, IterableReader};
+/// use pbf_craft::writers::{
+///     CountingSink, ElementSink, NdjsonSchema, NdjsonWriter, PbfWriter, TeeSink,
+/// };
+///
+/// let mut reader = IterableReader::from_path("resources/andorra-latest.osm.pbf").unwrap();
+/// let mut counter = CountingSink::new();
+/// let mut tee = TeeSink::new();
+/// tee.add(PbfWriter::new(Vec::new(), true));
+/// tee.add(NdjsonWriter::new(Vec::new(), NdjsonSchema::Raw));
+/// tee.add(&mut counter);
+/// while let Some(element) = reader.next_element().unwrap() {
+///     tee.write(element).unwrap();
+/// }
+/// tee.finish().unwrap();
+/// drop(tee);
+/// assert!(counter.nodes > 0);
+/// ```
+#[derive(Default)]
+pub struct TeeSink<'a> {
+    sinks: Vec<Box<dyn ElementSink + 'a>>,
+}
+
+impl<'a> TeeSink<'a> {
+    /// Creates a `TeeSink` without any sinks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sink receiving the elements written from now on.
+    pub fn add<S: ElementSink + 'a>(&mut self, sink: S) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    /// Returns the number of sinks.
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    /// Returns `true` if there are no sinks.
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+}
+
+impl ElementSink for TeeSink<'_> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        self.write_with_provenance(element, None)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        // The last sink gets the element itself, saving a copy
+        if let Some((last, others)) = self.sinks.split_last_mut() {
+            for sink in others {
+                sink.write_with_provenance(element.clone(), provenance.clone())?;
+            }
+            last.write_with_provenance(element, provenance)?;
+        }
+        Ok(())
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        for sink in &mut self.sinks {
+            sink.set_header(header.clone());
+        }
+    }
+
+    /// Finishes all the sinks, even if some of them fail, and returns the first error.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for sink in &mut self.sinks {
+            let finished = sink.finish();
+            if result.is_ok() {
+                result = finished;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::readers::PbfReader;
+    use crate::writers::{CountingSink, PbfWriter};
+
+    struct FailingSink;
+
+    impl ElementSink for FailingSink {
+        fn write(&mut self, _element: Element) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            bail!("Failed to finish")
+        }
+    }
+
+    #[test]
+    fn test_tee() {
+        let path = "./resources/andorra-latest.osm.pbf";
+        let mut counter = CountingSink::new();
+        let mut data = Vec::new();
+        let mut tee = TeeSink::new();
+        tee.add(&mut counter);
+        tee.add(PbfWriter::new(&mut data, true));
+        assert_eq!(tee.len(), 2);
+        PbfReader::from_path(path)
+            .unwrap()
+            .read(|_, element| {
+                if let Some(element) = element {
+                    tee.write(element).unwrap();
+                }
+            })
+            .unwrap();
+        tee.finish().unwrap();
+        drop(tee);
+
+        let mut copied = CountingSink::new();
+        PbfReader::new(data.as_slice())
+            .read(|_, element| {
+                if let Some(element) = element {
+                    copied.write(element).unwrap();
+                }
+            })
+            .unwrap();
+        assert!(counter.total() > 0);
+        assert_eq!(copied, counter);
+
+        // A failing sink doesn't keep the others from finishing
+        let mut data = Vec::new();
+        let mut tee = TeeSink::new();
+        tee.add(FailingSink);
+        tee.add(PbfWriter::new(&mut data, true));
+        assert!(tee.finish().is_err());
+        drop(tee);
+        assert!(!data.is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::models::{Element, FileHeader};
+use crate::readers::Provenance;
+
+/// A destination which elements can be written to.
+///
+/// `ElementSink` abstracts over output formats so that pipelines, extractors and tools can be
+/// written once and target any kind of output, e.g. a PBF file or a simple counter.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node};
+/// use pbf_craft::writers::{CountingSink, ElementSink, PbfWriter};
+///
+/// fn copy_node<S: ElementSink>(sink: &mut S) -> anyhow::Result<()> {
+///     sink.write(Element::Node(Node::default()))?;
+///     sink.finish()
+/// }
+///
+/// let mut counter = CountingSink::new();
+/// copy_node(&mut counter).unwrap();
+/// assert_eq!(counter.nodes, 1);
+///
+/// let mut writer = PbfWriter::new(Vec::new(), true);
+/// copy_node(&mut writer).unwrap();
+/// ```
+pub trait ElementSink {
+    /// Writes an element to the sink.
+    fn write(&mut self, element: Element) -> anyhow::Result<()>;
+
+    /// Writes an element together with where it was read from.
+    ///
+    /// Transforms wrapping another sink pass the provenance on, and sinks recording it, e.g.
+    /// for audits, override this method. All other sinks ignore it, which is the default.
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        _provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        self.write(element)
+    }
+
+    /// Sets the header of the output.
+    ///
+    /// It should be called before writing any elements. Sinks without a header ignore it.
+    fn set_header(&mut self, _header: FileHeader) {}
+
+    /// Flushes any buffered elements and finishes the output.
+    fn finish(&mut self) -> anyhow::Result<()>;
+}
+
+impl<S: ElementSink + ?Sized> ElementSink for Box<S> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        (**self).write(element)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        (**self).write_with_provenance(element, provenance)
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        (**self).set_header(header)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        (**self).finish()
+    }
+}
+
+impl<S: ElementSink + ?Sized> ElementSink for &mut S {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        (**self).write(element)
+    }
+
+    fn write_with_provenance(
+        &mut self,
+        element: Element,
+        provenance: Option<Provenance>,
+    ) -> anyhow::Result<()> {
+        (**self).write_with_provenance(element, provenance)
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        (**self).set_header(header)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        (**self).finish()
+    }
+}
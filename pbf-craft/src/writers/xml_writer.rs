@@ -0,0 +1,170 @@
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::BufWriter;
+use std::io::Write;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use super::traits::ElementSink;
+use crate::models::{Bound, Element, FileHeader};
+use crate::utils::xml;
+
+/// A writer of OSM XML (`.osm`) documents.
+///
+/// The document is opened with the first element and closed by `finish`, so `finish` must be
+/// called even if no element is written. A header set before the first element is written as
+/// the `bounds` of the document.
+///
+/// # Example
+///
+/// ```rust
+/// use pbf_craft::models::{Element, Node};
+/// use pbf_craft::writers::XmlWriter;
+///
+/// let mut writer = XmlWriter::new(Vec::new());
+/// writer.write(Element::Node(Node { id: 1, visible: true, ..Default::default() })).unwrap();
+/// writer.finish().unwrap();
+/// ```
+pub struct XmlWriter<W: Write> {
+    writer: W,
+    bbox: Option<Bound>,
+    has_written_header: bool,
+}
+
+#[cfg(feature = "fs")]
+impl XmlWriter<BufWriter<File>> {
+    /// Creates a new `XmlWriter` from a file path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let f = File::create(path)?;
+        Ok(Self::new(BufWriter::new(f)))
+    }
+}
+
+impl<W: Write> XmlWriter<W> {
+    /// Creates a new `XmlWriter` from an existing writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            bbox: None,
+            has_written_header: false,
+        }
+    }
+
+    /// Sets the bounding box written as the `bounds` of the document, before any element.
+    pub fn set_bbox(&mut self, bbox: Bound) {
+        self.bbox = Some(bbox);
+    }
+
+    fn write_header(&mut self) -> anyhow::Result<()> {
+        writeln!(self.writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            self.writer,
+            "<osm version=\"0.6\" generator=\"pbf-craft {}\">",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        if let Some(bbox) = &self.bbox {
+            writeln!(
+                self.writer,
+                "  <bounds minlat=\"{}\" minlon=\"{}\" maxlat=\"{}\" maxlon=\"{}\"/>",
+                xml::format_coordinate(bbox.bottom),
+                xml::format_coordinate(bbox.left),
+                xml::format_coordinate(bbox.top),
+                xml::format_coordinate(bbox.right)
+            )?;
+        }
+        self.has_written_header = true;
+        Ok(())
+    }
+
+    /// Writes an element.
+    pub fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        if !self.has_written_header {
+            self.write_header()?;
+        }
+        xml::write_element(&mut self.writer, &element, "  ")
+    }
+
+    /// Closes the document and flushes the underlying writer.
+    pub fn finish(&mut self) -> anyhow::Result<()> {
+        if !self.has_written_header {
+            self.write_header()?;
+        }
+        writeln!(self.writer, "</osm>")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> ElementSink for XmlWriter<W> {
+    fn write(&mut self, element: Element) -> anyhow::Result<()> {
+        XmlWriter::write(self, element)
+    }
+
+    fn set_header(&mut self, header: FileHeader) {
+        if let Some(bbox) = header.bbox {
+            self.set_bbox(bbox);
+        }
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        XmlWriter::finish(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Node, Tag, Way, WayNode};
+
+    #[test]
+    fn test_xml_document() {
+        let mut data = Vec::new();
+        let mut writer = XmlWriter::new(&mut data);
+        writer.set_bbox(Bound {
+            left: 1_000_000_000,
+            right: 2_000_000_000,
+            top: 43_000_000_000,
+            bottom: 42_000_000_000,
+        });
+        writer
+            .write(Element::Node(Node {
+                id: 1,
+                latitude: 42_500_000_000,
+                longitude: 1_500_000_000,
+                tags: vec![Tag {
+                    key: "name".to_string(),
+                    value: "A & B".to_string(),
+                }],
+                ..Default::default()
+            }))
+            .unwrap();
+        writer
+            .write(Element::Way(Way {
+                id: 10,
+                way_nodes: vec![WayNode::new_without_coords(1)],
+                ..Default::default()
+            }))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let document = String::from_utf8(data).unwrap();
+        let lines: Vec<&str> = document.lines().collect();
+        assert_eq!(lines[0], "<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+        assert_eq!(
+            lines[2],
+            "  <bounds minlat=\"42.0000000\" minlon=\"1.0000000\" maxlat=\"43.0000000\" maxlon=\"2.0000000\"/>"
+        );
+        assert_eq!(
+            lines[3],
+            "  <node id=\"1\" lat=\"42.5000000\" lon=\"1.5000000\">"
+        );
+        assert_eq!(lines[4], "    <tag k=\"name\" v=\"A &amp; B\"/>");
+        assert_eq!(lines[7], "    <nd ref=\"1\"/>");
+        assert_eq!(lines.last(), Some(&"</osm>"));
+
+        let mut empty = Vec::new();
+        XmlWriter::new(&mut empty).finish().unwrap();
+        assert!(String::from_utf8(empty).unwrap().ends_with("\">\n</osm>\n"));
+    }
+}